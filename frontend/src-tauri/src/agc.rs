@@ -0,0 +1,172 @@
+//! Automatic gain control detection from gain pumping after loud events
+
+use serde::{Deserialize, Serialize};
+
+/// Level frames (seconds) and the smoothing the floor is followed with
+const FRAME_SECS: f32 = 0.01;
+const SMOOTH_SECS: f32 = 0.1;
+/// Frames within this much of the 95th percentile are loud, as long as
+/// they are this far above the 10th percentile (dB)
+const LOUD_RANGE_DB: f32 = 20.0;
+const MIN_LOUD_ABOVE_FLOOR_DB: f32 = 12.0;
+/// Pauses after a loud event shorter than this are too short to show a
+/// recovery (seconds), and only this much of longer ones is followed
+const MIN_PAUSE_SECS: f32 = 1.0;
+const MAX_RECOVERY_SECS: f32 = 6.0;
+/// The lowest point is searched for in this much of the pause (seconds), so
+/// a reverberant tail is past before the recovery is measured
+const DIP_SEARCH_SECS: f32 = 0.5;
+/// The settled level is the median over the last this much of the pause
+const SETTLED_SECS: f32 = 0.3;
+/// Least rise of the floor from its dip to the settled level (dB), and the
+/// least fit of a steady rise (R²) over the recovery
+const MIN_RISE_DB: f32 = 4.0;
+const MIN_RISE_FIT: f32 = 0.6;
+/// The recovery ends when the floor is this close to the settled level (dB),
+/// and takes at least this long (seconds); a quicker rise is a change in the
+/// background rather than a gain coming back
+const RECOVERED_DB: f32 = 1.0;
+const MIN_RECOVERY_SECS: f32 = 0.2;
+/// Least recoveries, and least fraction of the pauses examined that show
+/// one, for the recording to be flagged
+const MIN_EVENTS: usize = 3;
+const MIN_EVENT_FRACTION: f32 = 0.5;
+
+const CAVEAT: &str = "Gain pumping is read from the background recovering after loud sounds. A background \
+that itself rises after them (an approaching vehicle, a ducked music bed) mimics it, and a gain control \
+slow enough to track whole phrases leaves no pauses to measure.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgcEvent {
+    pub start_time: f32,        // Start of the loud event
+    pub end_time: f32,          // Floor back to the settled level
+    pub trigger_dbfs: f32,      // Loudest frame of the event
+    pub depth_db: f32,          // Floor just after the event below the settled floor
+    pub recovery_s: f32,        // From the dip until the floor is within 1 dB of settled
+    pub recovery_rate_db_s: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgcReport {
+    pub detected: bool,
+    pub pauses_examined: usize,         // Pauses after loud events long enough to show a recovery
+    pub events: Vec<AgcEvent>,          // Pauses that show one
+    pub median_depth_db: Option<f32>,
+    pub median_recovery_s: Option<f32>,
+    pub caveat: String,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted[((p * (sorted.len() - 1) as f32) as usize).min(sorted.len() - 1)]
+}
+
+/// R² of a least-squares line through `levels`
+fn line_fit(levels: &[f32]) -> f32 {
+    let n = levels.len() as f32;
+    let mx = (n - 1.0) / 2.0;
+    let my = levels.iter().sum::<f32>() / n;
+    let (sxy, sxx, syy) = levels.iter().enumerate().fold((0.0, 0.0, 0.0), |(sxy, sxx, syy), (i, &y)| {
+        let (dx, dy) = (i as f32 - mx, y - my);
+        (sxy + dx * dy, sxx + dx * dx, syy + dy * dy)
+    });
+    if sxx <= 0.0 || syy <= 0.0 {
+        return 0.0;
+    }
+    sxy * sxy / (sxx * syy)
+}
+
+/// Look for a gain control in the recording chain. Such a control turns the
+/// gain down within milliseconds of a loud sound and lets it back up over
+/// the following seconds, so in the pause after a loud event the background
+/// starts low and climbs steadily back to where it was, where a room's
+/// reverberation only falls away. Every pause long enough to show this is
+/// examined; the recording is flagged when several do and they are at least
+/// half of those examined. A gain control explains level steps around loud
+/// sounds that could otherwise be read as edits.
+pub fn detect(samples: &[f32], sample_rate: u32) -> AgcReport {
+    let frame = ((FRAME_SECS * sample_rate as f32) as usize).max(1);
+    let power: Vec<f32> = samples.chunks_exact(frame).map(|c| c.iter().map(|s| s * s).sum::<f32>() / frame as f32).collect();
+    let mut report = AgcReport {
+        detected: false,
+        pauses_examined: 0,
+        events: Vec::new(),
+        median_depth_db: None,
+        median_recovery_s: None,
+        caveat: CAVEAT.to_string(),
+    };
+    if power.len() < 2 {
+        return report;
+    }
+    let half = ((SMOOTH_SECS / FRAME_SECS) as usize / 2).max(1);
+    let level: Vec<f32> = (0..power.len())
+        .map(|i| {
+            let near = &power[i.saturating_sub(half)..(i + half + 1).min(power.len())];
+            10.0 * (near.iter().sum::<f32>() / near.len() as f32).max(1e-20).log10()
+        })
+        .collect();
+
+    let floor = percentile(&level, 0.1);
+    let loud = (percentile(&level, 0.95) - LOUD_RANGE_DB).max(floor + MIN_LOUD_ABOVE_FLOOR_DB);
+    let quiet = (floor + loud) / 2.0;
+    let frames = |secs: f32| ((secs / FRAME_SECS) as usize).max(1);
+    let (min_pause, max_recovery, dip_search, settled, min_recovery) = (
+        frames(MIN_PAUSE_SECS),
+        frames(MAX_RECOVERY_SECS),
+        frames(DIP_SEARCH_SECS),
+        frames(SETTLED_SECS),
+        frames(MIN_RECOVERY_SECS),
+    );
+
+    let mut i = 0;
+    while i < level.len() {
+        // A loud event: a run above the quiet level that reaches the loud one
+        if level[i] < quiet {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < level.len() && level[i] >= quiet {
+            i += 1;
+        }
+        let trigger = level[start..i].iter().copied().fold(f32::MIN, f32::max);
+        let pause_start = i;
+        while i < level.len() && level[i] < quiet {
+            i += 1;
+        }
+        if trigger < loud || i - pause_start < min_pause {
+            continue;
+        }
+        report.pauses_examined += 1;
+
+        // Up to where the smoothing starts to feel the next sound
+        let pause = &level[pause_start..(i - half).min(pause_start + max_recovery)];
+        let dip = (0..dip_search.min(pause.len())).fold(0, |best, k| if pause[k] < pause[best] { k } else { best });
+        let settled_level = percentile(&pause[pause.len().saturating_sub(settled)..], 0.5);
+        let depth = settled_level - pause[dip];
+        let recovered = (dip..pause.len()).find(|&k| pause[k] >= settled_level - RECOVERED_DB).unwrap_or(pause.len() - 1);
+        if depth < MIN_RISE_DB || recovered - dip < min_recovery || line_fit(&pause[dip..=recovered]) < MIN_RISE_FIT {
+            continue;
+        }
+        let recovery_s = (recovered - dip).max(1) as f32 * FRAME_SECS;
+        report.events.push(AgcEvent {
+            start_time: start as f32 * FRAME_SECS,
+            end_time: (pause_start + recovered) as f32 * FRAME_SECS,
+            trigger_dbfs: trigger,
+            depth_db: depth,
+            recovery_s,
+            recovery_rate_db_s: (depth - RECOVERED_DB) / recovery_s,
+        });
+    }
+
+    if !report.events.is_empty() {
+        let depths: Vec<f32> = report.events.iter().map(|e| e.depth_db).collect();
+        let recoveries: Vec<f32> = report.events.iter().map(|e| e.recovery_s).collect();
+        report.median_depth_db = Some(percentile(&depths, 0.5));
+        report.median_recovery_s = Some(percentile(&recoveries, 0.5));
+    }
+    report.detected = report.events.len() >= MIN_EVENTS
+        && report.events.len() as f32 >= MIN_EVENT_FRACTION * report.pauses_examined as f32;
+    report
+}
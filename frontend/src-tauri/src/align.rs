@@ -0,0 +1,90 @@
+//! Time alignment of two signals by cross-correlation
+
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+
+/// Lag (within ±`max_lag`) maximizing the absolute normalized
+/// cross-correlation, computed via FFT. A positive lag means `b` is
+/// delayed: `b[i + lag] ≈ a[i]`.
+pub fn xcorr_lag(a: &[f32], b: &[f32], max_lag: usize) -> (i64, f32) {
+    let n = (a.len().max(b.len()) + max_lag).next_power_of_two();
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n);
+    let inverse = planner.plan_fft_inverse(n);
+
+    let spectrum_of = |x: &[f32]| {
+        let mut buf = vec![0.0f32; n];
+        buf[..x.len()].copy_from_slice(x);
+        let mut out = forward.make_output_vec();
+        forward.process(&mut buf, &mut out).ok().map(|_| out)
+    };
+    let (Some(sa), Some(sb)) = (spectrum_of(a), spectrum_of(b)) else {
+        return (0, 0.0);
+    };
+
+    let mut cross: Vec<Complex<f32>> = sa.iter().zip(&sb).map(|(x, y)| x.conj() * y).collect();
+    let last = cross.len() - 1;
+    cross[0].im = 0.0;
+    cross[last].im = 0.0;
+    let mut xcorr = inverse.make_output_vec();
+    if inverse.process(&mut cross, &mut xcorr).is_err() {
+        return (0, 0.0);
+    }
+
+    let norm = (a.iter().map(|&s| s * s).sum::<f32>() * b.iter().map(|&s| s * s).sum::<f32>()).sqrt() * n as f32;
+    if norm <= 0.0 {
+        return (0, 0.0);
+    }
+
+    // Index k holds lag k, index n - k holds lag -k
+    let max_lag = max_lag.min(n / 2 - 1) as i64;
+    (-max_lag..=max_lag)
+        .map(|lag| {
+            let idx = if lag >= 0 { lag as usize } else { n - (-lag) as usize };
+            (lag, xcorr[idx] / norm)
+        })
+        .max_by(|x, y| x.1.abs().total_cmp(&y.1.abs()))
+        .unwrap_or((0, 0.0))
+}
+
+/// Offset of `other` relative to `reference` (`other[i + offset] ≈ reference[i]`)
+/// within ±`max_offset` seconds. A coarse FFT search runs on decimated
+/// excerpts, then the lag is refined at full rate by direct correlation.
+/// Returns (offset in samples, normalized correlation at that offset).
+pub fn find_offset(reference: &[f32], other: &[f32], sample_rate: u32, max_offset: f32) -> (i64, f32) {
+    let sr = sample_rate as usize;
+    let max_lag = (max_offset.max(0.0) * sample_rate as f32) as usize;
+    // Decimate to roughly 4 kHz for the coarse search
+    let d = (sr / 4000).max(1);
+
+    // Excerpts covering the search range plus a minute of material
+    let excerpt = |x: &[f32]| -> Vec<f32> {
+        let len = x.len().min(max_lag + 60 * sr);
+        x[..len].chunks(d).map(|c| c.iter().sum::<f32>() / c.len() as f32).collect()
+    };
+    let (coarse_lag, _) = xcorr_lag(&excerpt(reference), &excerpt(other), max_lag / d + 1);
+
+    // Full-rate refinement around the coarse estimate on a centered ten-second window
+    let window = reference.len().min(10 * sr);
+    let start = (reference.len() - window) / 2;
+    let score = |lag: i64| -> f32 {
+        let (mut dot, mut ea, mut eb) = (0.0f64, 0.0f64, 0.0f64);
+        for (i, &x) in reference.iter().enumerate().skip(start).take(window) {
+            let j = i as i64 + lag;
+            if j < 0 || j as usize >= other.len() {
+                continue;
+            }
+            let (x, y) = (x as f64, other[j as usize] as f64);
+            dot += x * y;
+            ea += x * x;
+            eb += y * y;
+        }
+        if ea * eb > 0.0 { (dot / (ea * eb).sqrt()) as f32 } else { 0.0 }
+    };
+    let center = coarse_lag * d as i64;
+    let span = 2 * d as i64;
+    (center - span..=center + span)
+        .map(|lag| (lag, score(lag)))
+        .max_by(|x, y| x.1.abs().total_cmp(&y.1.abs()))
+        .unwrap_or((center, 0.0))
+}
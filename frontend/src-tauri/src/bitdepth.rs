@@ -0,0 +1,96 @@
+//! Effective bit depth from sample value statistics
+
+use serde::{Deserialize, Serialize};
+
+/// Share of non-zero samples that must fit a word length for it to count
+const COVERAGE: f64 = 0.999;
+/// Most samples examined for the spacing of distinct values
+const MAX_LATTICE_SAMPLES: usize = 1 << 21;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BitDepthReport {
+    pub declared_bits: Option<u32>,  // Bits per sample stated by the file
+    pub used_bits: u32,              // Word length the sample values fit in (33 = not on a 32-bit grid)
+    pub step_bits: f32,              // Resolution implied by the spacing of distinct values
+    pub effective_bits: f32,         // Step bits when clearly below the used bits, else used bits
+    pub padded: bool,                // Fewer effective bits than declared
+    pub float_data: bool,            // Values off every integer grid up to 32 bits
+    pub distinct_values: usize,      // Among up to 2M evenly spaced samples
+    pub bit_activity: Vec<f32>,      // Fraction of samples with each bit set, MSB first
+}
+
+/// Bits a sample needs as a two's-complement integer, sign included
+/// (33 when it is not on the 32-bit grid)
+fn bits_needed(v: f32) -> u32 {
+    let scaled = v as f64 * 2f64.powi(31);
+    if scaled.fract() != 0.0 || scaled.abs() > 2f64.powi(31) {
+        return 33;
+    }
+    32 - (scaled as i64).trailing_zeros().min(31)
+}
+
+/// Measure how many bits of resolution `samples` (full scale ±1) really
+/// use. Zero-padded low bits show in the word length the values fit;
+/// low-resolution audio that was scaled by a non-power-of-two gain still
+/// fills every bit but keeps the wide spacing of its distinct values.
+pub fn analyze(samples: &[f32], declared_bits: Option<u32>) -> BitDepthReport {
+    let mut counts = [0usize; 34];
+    for &v in samples.iter().filter(|v| **v != 0.0) {
+        counts[bits_needed(v) as usize] += 1;
+    }
+    let nonzero: usize = counts.iter().sum();
+    let mut used_bits = 0;
+    let mut covered = 0;
+    for (bits, &count) in counts.iter().enumerate() {
+        if count > 0 && (covered as f64) < COVERAGE * nonzero as f64 {
+            used_bits = bits as u32;
+        }
+        covered += count;
+    }
+    let float_data = counts[33] as f64 > (1.0 - COVERAGE) * nonzero.max(1) as f64;
+
+    // Spacing of distinct values in the middle of the range; a low
+    // percentile, as high-resolution audio rarely fills every level
+    let stride = samples.len().div_ceil(MAX_LATTICE_SAMPLES).max(1);
+    let peak = samples.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+    let mut values: Vec<f32> = samples.iter().step_by(stride).copied().collect();
+    values.sort_by(f32::total_cmp);
+    values.dedup();
+    let distinct_values = values.len();
+    values.retain(|v| v.abs() < 0.25 * peak);
+    let mut steps: Vec<f32> = values.windows(2).map(|w| w[1] - w[0]).collect();
+    let step_bits = if steps.is_empty() {
+        0.0
+    } else {
+        let low = steps.len() / 10;
+        let (_, &mut step, _) = steps.select_nth_unstable_by(low, f32::total_cmp);
+        (2.0 / step).log2().min(33.0)
+    };
+
+    // Bit usage over the declared word (24 bits when unknown)
+    let word = declared_bits.unwrap_or(24).clamp(8, 32);
+    let scale = 2f64.powi(word as i32 - 1);
+    let mut set = vec![0usize; word as usize];
+    for &v in samples {
+        let n = (v as f64 * scale).round().clamp(-scale, scale - 1.0) as i64;
+        for (bit, count) in set.iter_mut().enumerate() {
+            if (n >> (word as usize - 1 - bit)) & 1 == 1 {
+                *count += 1;
+            }
+        }
+    }
+    let bit_activity = set.iter().map(|&c| c as f32 / samples.len().max(1) as f32).collect();
+
+    // Sparse occupancy alone makes the spacing look up to a couple of bits coarse
+    let effective_bits = if step_bits < used_bits as f32 - 2.0 { step_bits } else { used_bits as f32 };
+    BitDepthReport {
+        declared_bits,
+        used_bits,
+        step_bits,
+        effective_bits,
+        padded: declared_bits.is_some_and(|d| effective_bits < d as f32 - 0.5),
+        float_data,
+        distinct_values,
+        bit_activity,
+    }
+}
@@ -0,0 +1,132 @@
+//! Channel selection for analyses that operate on a single signal
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::audio::Channels;
+
+/// Short speaker labels in symphonia's (interleaving) bit order
+const SPEAKER_LABELS: [(Channels, &str); 26] = [
+    (Channels::FRONT_LEFT, "FL"),
+    (Channels::FRONT_RIGHT, "FR"),
+    (Channels::FRONT_CENTRE, "FC"),
+    (Channels::LFE1, "LFE"),
+    (Channels::REAR_LEFT, "BL"),
+    (Channels::REAR_RIGHT, "BR"),
+    (Channels::FRONT_LEFT_CENTRE, "FLC"),
+    (Channels::FRONT_RIGHT_CENTRE, "FRC"),
+    (Channels::REAR_CENTRE, "BC"),
+    (Channels::SIDE_LEFT, "SL"),
+    (Channels::SIDE_RIGHT, "SR"),
+    (Channels::TOP_CENTRE, "TC"),
+    (Channels::TOP_FRONT_LEFT, "TFL"),
+    (Channels::TOP_FRONT_CENTRE, "TFC"),
+    (Channels::TOP_FRONT_RIGHT, "TFR"),
+    (Channels::TOP_REAR_LEFT, "TBL"),
+    (Channels::TOP_REAR_CENTRE, "TBC"),
+    (Channels::TOP_REAR_RIGHT, "TBR"),
+    (Channels::REAR_LEFT_CENTRE, "BLC"),
+    (Channels::REAR_RIGHT_CENTRE, "BRC"),
+    (Channels::FRONT_LEFT_WIDE, "FLW"),
+    (Channels::FRONT_RIGHT_WIDE, "FRW"),
+    (Channels::FRONT_LEFT_HIGH, "FLH"),
+    (Channels::FRONT_CENTRE_HIGH, "FCH"),
+    (Channels::FRONT_RIGHT_HIGH, "FRH"),
+    (Channels::LFE2, "LFE2"),
+];
+
+/// Which signal an analysis runs on. Serialized as `"mix"`, `"mid"`,
+/// `"side"`, `"all"` or `{ "channel": n }` (zero-based).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelSelect {
+    /// Downmixed mono signal
+    #[default]
+    Mix,
+    /// A single source channel
+    Channel(usize),
+    /// (L + R) / 2 of the first two channels
+    Mid,
+    /// (L - R) / 2 of the first two channels
+    Side,
+    /// The mix plus a separate result for every channel
+    All,
+}
+
+/// Split interleaved samples into one buffer per channel
+pub fn deinterleave(interleaved: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    let channels = channels.max(1);
+    let frames = interleaved.len() / channels;
+    let mut out = vec![Vec::with_capacity(frames); channels];
+    for frame in interleaved.chunks_exact(channels) {
+        for (buf, &s) in out.iter_mut().zip(frame) {
+            buf.push(s);
+        }
+    }
+    out
+}
+
+/// Speaker label for every interleaved channel. Falls back to "Ch1".."ChN"
+/// when the decoder reports no (or an inconsistent) layout.
+pub fn channel_labels(layout: Option<Channels>, count: usize) -> Vec<String> {
+    match layout {
+        Some(mask) if mask.count() == count => mask
+            .iter()
+            .map(|ch| {
+                SPEAKER_LABELS
+                    .iter()
+                    .find(|(flag, _)| *flag == ch)
+                    .map(|(_, label)| label.to_string())
+                    .unwrap_or_else(|| "?".to_string())
+            })
+            .collect(),
+        _ => generic_labels(count),
+    }
+}
+
+pub fn generic_labels(count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("Ch{}", i)).collect()
+}
+
+/// Conventional name for a layout ("mono", "stereo", "5.1", "7.1", ...)
+pub fn layout_name(labels: &[String]) -> String {
+    let has = |l: &str| labels.iter().any(|x| x == l);
+    let lfe = labels.iter().filter(|l| l.starts_with("LFE")).count();
+    match (labels.len(), lfe) {
+        (1, _) => "mono".to_string(),
+        (2, 0) => "stereo".to_string(),
+        (6, 1) if has("FC") => "5.1".to_string(),
+        (8, 1) if has("FC") => "7.1".to_string(),
+        (n, l) if l > 0 => format!("{}.{}", n - l, l),
+        (n, _) => format!("{} channels", n),
+    }
+}
+
+/// Default downmix coefficients for the mono analysis path: an equal-weight
+/// average for mono/stereo and generic layouts; for surround, ITU-style
+/// weights (fronts 1.0, centre and surrounds -3 dB, LFE excluded),
+/// normalized so the coefficients sum to one
+pub fn default_downmix(labels: &[String]) -> Vec<f32> {
+    let surround = labels.len() > 2 && labels.iter().any(|l| l.starts_with("LFE") || l == "FC");
+    let weights: Vec<f32> = labels
+        .iter()
+        .map(|l| match l.as_str() {
+            _ if !surround => 1.0,
+            "FL" | "FR" => 1.0,
+            l if l.starts_with("LFE") => 0.0,
+            _ => std::f32::consts::FRAC_1_SQRT_2,
+        })
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.iter().map(|w| if total > 0.0 { w / total } else { 0.0 }).collect()
+}
+
+/// Weighted sum of the channel buffers
+pub fn downmix(channels: &[Vec<f32>], coefficients: &[f32]) -> Vec<f32> {
+    let len = channels.iter().map(Vec::len).min().unwrap_or(0);
+    let mut out = vec![0.0f32; len];
+    for (buf, &c) in channels.iter().zip(coefficients) {
+        if c != 0.0 {
+            out.iter_mut().zip(buf).for_each(|(o, &s)| *o += c * s);
+        }
+    }
+    out
+}
@@ -0,0 +1,217 @@
+//! Click, pop and dropout detection on the residual of a short-term linear prediction
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Prediction blocks (samples) and predictor order
+const BLOCK: usize = 4096;
+const ORDER: usize = 24;
+/// The matched output's scale is measured per this many samples, and taken
+/// as the largest over this many of them either side
+const SCALE_BLOCK: usize = 256;
+const SCALE_SPAN: usize = 2;
+/// Exceedances this close together form one event (seconds)
+const MERGE_SECS: f32 = 0.001;
+/// Longest click or pop (seconds); longer runs are transients, not clicks
+const MAX_CLICK_SECS: f32 = 0.01;
+/// Signal level compared either side of an event (seconds), and the ratio
+/// beyond which the event starts or ends a sound rather than interrupting it
+const CONTEXT_SECS: f32 = 0.02;
+const MAX_LEVEL_RATIO: f32 = 4.0;
+/// Smallest click amplitude reported (full scale, -70 dBFS)
+const MIN_AMPLITUDE: f32 = 3e-4;
+/// Least run of held sample values for a dropout (seconds), and the level
+/// the signal around it must have for the hold to be a loss (dBFS)
+const MIN_DROPOUT_SECS: f32 = 0.0005;
+const MIN_DROPOUT_CONTEXT_DBFS: f32 = -80.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickKind {
+    Click,      // Short wideband impulse (scratch, static, digital error)
+    Dropout,    // Samples held at one value (lost buffer, dead interface)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClickEvent {
+    pub time: f32,
+    pub duration_ms: f32,
+    pub kind: ClickKind,
+    pub amplitude: f32,      // Click: estimated impulse height; dropout: RMS of the signal around it (full scale)
+    pub amplitude_dbfs: f32,
+    pub score: f32,          // Matched-filter output in robust standard deviations (0 for dropouts)
+}
+
+/// LPC coefficients a[0..=order] (a[0] = 1) of `frame`
+fn lpc(frame: &[f32], order: usize) -> Option<Vec<f64>> {
+    let r: Vec<f64> = (0..=order)
+        .map(|lag| frame.iter().zip(&frame[lag..]).map(|(&a, &b)| a as f64 * b as f64).sum())
+        .collect();
+    levinson(&r)
+}
+
+/// LPC coefficients a[0..=order] (a[0] = 1) from the autocorrelation
+/// r[0..=order], by Levinson-Durbin
+pub fn levinson(r: &[f64]) -> Option<Vec<f64>> {
+    let order = r.len() - 1;
+    if r[0] <= 0.0 {
+        return None;
+    }
+    let mut a = vec![0.0f64; order + 1];
+    a[0] = 1.0;
+    // Slight white-noise correction keeps the recursion stable on tonal blocks
+    let mut error = r[0] * (1.0 + 1e-9);
+    for i in 1..=order {
+        let k = -(0..i).map(|j| a[j] * r[i - j]).sum::<f64>() / error;
+        let previous = a.clone();
+        for j in 1..i {
+            a[j] = previous[j] + k * previous[i - j];
+        }
+        a[i] = k;
+        error *= 1.0 - k * k;
+        if error <= 0.0 {
+            return None;
+        }
+    }
+    Some(a)
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    let (_, &mut m, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    m
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt()
+}
+
+/// Predict each block from its own LPC fit and pass the residual through
+/// the filter matched to an impulse seen through the inverse filter, so a
+/// click's residual, spread over the predictor's length, is gathered back
+/// into one peak. Returns the matched output and the click amplitude per
+/// unit of it.
+fn predict_block(samples: &[f32], start: usize) -> (Vec<f32>, f32) {
+    let end = (start + BLOCK).min(samples.len());
+    let window: Vec<f32> = (start..end)
+        .map(|n| {
+            let w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * (n - start) as f32 / (end - start) as f32).cos();
+            samples[n] * w
+        })
+        .collect();
+    let Some(a) = lpc(&window, ORDER) else {
+        return (vec![0.0; end - start], 0.0);
+    };
+    let predict_end = (end + ORDER).min(samples.len());
+    let residual: Vec<f32> = (start..predict_end)
+        .map(|n| a.iter().enumerate().filter(|&(k, _)| k <= n).map(|(k, &ak)| ak * samples[n - k] as f64).sum::<f64>() as f32)
+        .collect();
+    let matched: Vec<f32> = (0..end - start)
+        .map(|i| a.iter().enumerate().filter_map(|(k, &ak)| residual.get(i + k).map(|&e| ak * e as f64)).sum::<f64>() as f32)
+        .collect();
+    (matched, (1.0 / a.iter().map(|ak| ak * ak).sum::<f64>()) as f32)
+}
+
+/// Runs of held sample values inside audible signal: (start, length, RMS around)
+fn dropouts(samples: &[f32], sample_rate: u32) -> Vec<(usize, usize, f32)> {
+    let min_len = ((MIN_DROPOUT_SECS * sample_rate as f32) as usize).max(2);
+    let context = (CONTEXT_SECS * sample_rate as f32) as usize;
+    let floor = 10f32.powf(MIN_DROPOUT_CONTEXT_DBFS / 20.0);
+    let mut found = Vec::new();
+    let mut n = 1;
+    while n < samples.len() {
+        let start = n - 1;
+        while n < samples.len() && samples[n] == samples[start] {
+            n += 1;
+        }
+        let len = n - start;
+        if len >= min_len {
+            let before = &samples[start.saturating_sub(context)..start];
+            let after = &samples[n..(n + context).min(samples.len())];
+            let level = rms(before).min(rms(after));
+            if level >= floor {
+                found.push((start, len, level));
+            }
+        }
+        n += 1;
+    }
+    found
+}
+
+/// Find short impulsive artifacts: clicks and pops (scratches, static,
+/// digital errors) and dropouts (sample values held through a lost buffer).
+/// Clicks are peaks of the matched-filtered prediction residual more than
+/// `threshold` robust standard deviations out, merged within a millisecond
+/// and no longer than 10 ms; peaks where the level either side differs
+/// start or end a sound and are left out. An edit shows as a change in
+/// the material either side, not as a click.
+pub fn detect_clicks(samples: &[f32], sample_rate: u32, threshold: f32) -> Vec<ClickEvent> {
+    let sr = sample_rate as f32;
+    let starts: Vec<usize> = (0..samples.len()).step_by(BLOCK).collect();
+    let blocks: Vec<(Vec<f32>, f32)> = starts.par_iter().map(|&start| predict_block(samples, start)).collect();
+    let matched: Vec<f32> = blocks.iter().flat_map(|b| b.0.iter().copied()).collect();
+
+    // Robust local scale, the largest nearby so the edge of a loud passage
+    // does not stand out against the quiet one beside it
+    let scales: Vec<f32> = matched
+        .par_chunks(SCALE_BLOCK)
+        .map(|chunk| 1.4826 * median(&mut chunk.iter().map(|d| d.abs()).collect::<Vec<_>>()))
+        .collect();
+    let local: Vec<f32> = (0..scales.len())
+        .map(|i| scales[i.saturating_sub(SCALE_SPAN)..(i + SCALE_SPAN + 1).min(scales.len())].iter().copied().fold(0.0, f32::max))
+        .collect();
+    let hits: Vec<(usize, f32, f32)> = matched
+        .iter()
+        .enumerate()
+        .filter_map(|(n, &d)| {
+            let sigma = local[n / SCALE_BLOCK];
+            let amplitude = d.abs() * blocks[n / BLOCK].1;
+            (sigma > 0.0 && d.abs() > threshold * sigma && amplitude >= MIN_AMPLITUDE).then_some((n, d.abs() / sigma, amplitude))
+        })
+        .collect();
+
+    let merge = ((MERGE_SECS * sr) as usize).max(1);
+    let max_len = (MAX_CLICK_SECS * sr) as usize;
+    let context = ((CONTEXT_SECS * sr) as usize).max(1);
+    let dropouts = dropouts(samples, sample_rate);
+    let near_dropout = |n: usize| dropouts.iter().any(|&(start, len, _)| n + merge >= start && n <= start + len + merge);
+
+    let mut events: Vec<ClickEvent> = Vec::new();
+    let mut i = 0;
+    while i < hits.len() {
+        let mut j = i + 1;
+        while j < hits.len() && hits[j].0 - hits[j - 1].0 <= merge {
+            j += 1;
+        }
+        let group = &hits[i..j];
+        i = j;
+        let (first, last) = (group[0].0, group[group.len() - 1].0);
+        if last - first > max_len || near_dropout(first) {
+            continue;
+        }
+        let before = rms(&samples[first.saturating_sub(merge + context)..first.saturating_sub(merge)]);
+        let after = rms(&samples[(last + merge).min(samples.len())..(last + merge + context).min(samples.len())]);
+        if after > MAX_LEVEL_RATIO * before || before > MAX_LEVEL_RATIO * after {
+            continue;
+        }
+        let &(peak, score, amplitude) = group.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        events.push(ClickEvent {
+            time: peak as f32 / sr,
+            duration_ms: 1000.0 * (last - first + 1) as f32 / sr,
+            kind: ClickKind::Click,
+            amplitude,
+            amplitude_dbfs: 20.0 * amplitude.log10(),
+            score,
+        });
+    }
+    events.extend(dropouts.iter().map(|&(start, len, level)| ClickEvent {
+        time: start as f32 / sr,
+        duration_ms: 1000.0 * len as f32 / sr,
+        kind: ClickKind::Dropout,
+        amplitude: level,
+        amplitude_dbfs: 20.0 * level.log10(),
+        score: 0.0,
+    }));
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    events
+}
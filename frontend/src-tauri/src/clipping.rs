@@ -0,0 +1,286 @@
+//! Clipping classification: flat-topped digital clipping, inter-sample overs
+//! and limiter or soft-clipper saturation
+
+use serde::{Deserialize, Serialize};
+
+/// Least run of samples held at the ceiling for a flat top
+const MIN_FLAT_RUN: usize = 3;
+/// Step into or out of a flat top, as a fraction of the ceiling, that sets
+/// it apart from the rounded peak of a low tone quantized to one code
+const MIN_EDGE_STEP: f32 = 0.001;
+/// Events of one kind this close together are merged (seconds)
+const MERGE_SECS: f32 = 0.01;
+/// Oversampling of the true-peak reconstruction, and its interpolation taps
+/// either side (ITU-R BS.1770 uses 4x with 48 taps)
+const OVERSAMPLE: usize = 4;
+const HALF_TAPS: i64 = 6;
+/// Samples below this level (dBFS) cannot reconstruct an over, so the
+/// interpolation skips them
+const OVER_SCREEN_DBFS: f32 = -6.0;
+/// Overs count from this far above full scale (dB), clear of the ripple of
+/// the interpolation and of quantization noise on a full-scale peak
+const OVER_MARGIN_DB: f32 = 0.01;
+/// Limiting: blocks (seconds) whose peak is within this much of the ceiling
+/// (dB) are pinned. A window (seconds) with this fraction of them pinned, a
+/// median crest factor of at least this much (dB) and a crest factor that
+/// moves by at least this much between its 10th and 90th percentiles (dB) is
+/// program held down by a limiter or soft clipper, rather than a steady test
+/// tone at the ceiling or a repeated hit reaching it now and then
+const BLOCK_SECS: f32 = 0.05;
+const PINNED_DB: f32 = 0.5;
+const LIMIT_WINDOW_SECS: f32 = 1.0;
+const MIN_PINNED_FRACTION: f32 = 0.5;
+const MIN_CREST_DB: f32 = 4.0;
+const MIN_CREST_SPREAD_DB: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipKind {
+    /// Consecutive samples flat at the ceiling
+    HardClip,
+    /// Reconstructed waveform above full scale between samples
+    InterSampleOver,
+    /// Peaks held at a ceiling by a limiter or soft clipper
+    Limiting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipEvent {
+    pub kind: ClipKind,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub level_dbfs: f32,            // Ceiling, or the true peak of an over
+    pub clipped_samples: usize,     // Samples on flat tops
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClippingReport {
+    pub sample_peak_dbfs: f32,
+    pub true_peak_dbtp: f32,
+    pub hard_clip_events: usize,
+    pub clipped_samples: usize,
+    pub longest_flat_run: usize,            // Samples
+    pub worst_hard_clip_time: Option<f32>,  // Event with the most flat-top samples
+    pub inter_sample_overs: usize,
+    pub worst_over_time: Option<f32>,       // Highest true peak
+    pub limiting_regions: usize,
+    pub limited_secs: f32,
+    pub worst_limiting_time: Option<f32>,   // Longest region
+    pub events: Vec<ClipEvent>,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-10).log10()
+}
+
+/// Merge sample spans (start, end, level, clipped samples) closer than `gap`
+fn merge(spans: Vec<(usize, usize, f32, usize)>, gap: usize) -> Vec<(usize, usize, f32, usize)> {
+    let mut merged: Vec<(usize, usize, f32, usize)> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.0 <= last.1 + gap => {
+                last.1 = last.1.max(span.1);
+                last.2 = last.2.max(span.2);
+                last.3 += span.3;
+            }
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// Flat tops: runs at the positive or negative ceiling entered or left by a
+/// steep step. Returns the runs as (start, end exclusive, |ceiling|, length).
+pub fn flat_tops(samples: &[f32]) -> Vec<(usize, usize, f32, usize)> {
+    let ceilings = [
+        samples.iter().copied().fold(f32::MIN, f32::max),
+        samples.iter().copied().fold(f32::MAX, f32::min),
+    ];
+    let mut runs = Vec::new();
+    for ceiling in ceilings {
+        if ceiling == 0.0 {
+            continue;
+        }
+        let at_ceiling = |s: f32| (s - ceiling).abs() <= ceiling.abs() * f32::EPSILON;
+        let mut i = 0;
+        while i < samples.len() {
+            if !at_ceiling(samples[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < samples.len() && at_ceiling(samples[i]) {
+                i += 1;
+            }
+            let entry = start.checked_sub(1).map_or(0.0, |k| (ceiling - samples[k]).abs());
+            let exit = samples.get(i).map_or(0.0, |&s| (ceiling - s).abs());
+            if i - start >= MIN_FLAT_RUN && entry.max(exit) >= MIN_EDGE_STEP * ceiling.abs() {
+                runs.push((start, i, ceiling.abs(), i - start));
+            }
+        }
+    }
+    runs.sort_by_key(|r| r.0);
+    runs
+}
+
+/// Windowed-sinc coefficients of the `OVERSAMPLE - 1` points between two
+/// samples, over the `2 * HALF_TAPS` samples around them
+fn interpolation_taps() -> Vec<Vec<f32>> {
+    (1..OVERSAMPLE)
+        .map(|phase| {
+            let t = phase as f64 / OVERSAMPLE as f64;
+            let taps: Vec<f64> = (-HALF_TAPS + 1..=HALF_TAPS)
+                .map(|j| {
+                    let u = j as f64 - t;
+                    let sinc = if u == 0.0 { 1.0 } else { (std::f64::consts::PI * u).sin() / (std::f64::consts::PI * u) };
+                    sinc * (0.5 + 0.5 * (std::f64::consts::PI * u / (HALF_TAPS as f64 + 0.5)).cos())
+                })
+                .collect();
+            // Unity gain at low frequencies, so a full-scale bass note does
+            // not read as over by the window's ripple
+            let gain: f64 = taps.iter().sum();
+            taps.iter().map(|h| (h / gain) as f32).collect()
+        })
+        .collect()
+}
+
+/// Largest reconstructed magnitude between `samples[i]` and `samples[i + 1]`
+fn between(samples: &[f32], i: usize, taps: &[Vec<f32>]) -> f32 {
+    taps.iter()
+        .map(|phase| {
+            phase
+                .iter()
+                .zip(-HALF_TAPS + 1..=HALF_TAPS)
+                .map(|(h, j)| {
+                    let k = i as i64 + j;
+                    if k < 0 || k as usize >= samples.len() { 0.0 } else { h * samples[k as usize] }
+                })
+                .sum::<f32>()
+                .abs()
+        })
+        .fold(0.0, f32::max)
+}
+
+/// Classify the clipping in `samples`. Flat tops are hard digital clipping,
+/// found at whatever level the signal was clipped at, since a clipped
+/// recording is often normalized afterwards. Overs are stretches where the
+/// waveform reconstructed at 4x rises above full scale between samples that
+/// stay below it, which a DAC or a lossy encoder will clip. Limiting is a
+/// stretch where most peaks of program material sit pinned just under the
+/// ceiling, the mark of a limiter or soft clipper.
+pub fn classify(samples: &[f32], sample_rate: u32) -> ClippingReport {
+    let sr = sample_rate as f32;
+    let time = |i: usize| i as f32 / sr;
+    let gap = (MERGE_SECS * sr) as usize;
+    let peak = samples.iter().fold(0.0f32, |m, &s| m.max(s.abs()));
+    let mut events = Vec::new();
+
+    // Hard clipping
+    let runs = flat_tops(samples);
+    let longest_flat_run = runs.iter().map(|r| r.3).max().unwrap_or(0);
+    let clipped: Vec<(usize, usize, f32, usize)> = merge(runs, gap);
+    let clipped_samples = clipped.iter().map(|c| c.3).sum();
+    let worst_hard_clip_time = clipped.iter().max_by_key(|c| c.3).map(|c| time(c.0));
+    events.extend(clipped.iter().map(|&(start, end, level, count)| ClipEvent {
+        kind: ClipKind::HardClip,
+        start_time: time(start),
+        end_time: time(end),
+        level_dbfs: to_db(level),
+        clipped_samples: count,
+    }));
+    let in_clip = |i: usize| clipped.iter().any(|c| i + gap >= c.0 && i <= c.1 + gap);
+
+    // Inter-sample overs, away from the flat tops
+    let taps = interpolation_taps();
+    let screen = 10f32.powf(OVER_SCREEN_DBFS / 20.0);
+    let over = 10f32.powf(OVER_MARGIN_DB / 20.0);
+    let mut true_peak = peak;
+    let mut overs = Vec::new();
+    for i in 0..samples.len().saturating_sub(1) {
+        if samples[i].abs().max(samples[i + 1].abs()) < screen {
+            continue;
+        }
+        let reconstructed = between(samples, i, &taps);
+        true_peak = true_peak.max(reconstructed);
+        if reconstructed > over && samples[i].abs() <= 1.0 && samples[i + 1].abs() <= 1.0 && !in_clip(i) {
+            overs.push((i, i + 1, reconstructed, 0));
+        }
+    }
+    let overs = merge(overs, gap);
+    let worst_over_time = overs.iter().max_by(|a, b| a.2.total_cmp(&b.2)).map(|o| time(o.0));
+    events.extend(overs.iter().map(|&(start, end, level, _)| ClipEvent {
+        kind: ClipKind::InterSampleOver,
+        start_time: time(start),
+        end_time: time(end),
+        level_dbfs: to_db(level),
+        clipped_samples: 0,
+    }));
+
+    // Limiting: windows of pinned block peaks over a moving level
+    let block = ((BLOCK_SECS * sr) as usize).max(1);
+    let pinned_level = peak * 10f32.powf(-PINNED_DB / 20.0);
+    let blocks: Vec<(f32, f32)> = samples
+        .chunks_exact(block)
+        .map(|c| {
+            let rms = (c.iter().map(|s| s * s).sum::<f32>() / block as f32).sqrt();
+            let block_peak = c.iter().fold(0.0f32, |m, &s| m.max(s.abs()));
+            (block_peak, to_db(block_peak) - to_db(rms))
+        })
+        .collect();
+    let window = ((LIMIT_WINDOW_SECS / BLOCK_SECS) as usize).max(1);
+    let mut limited = vec![false; blocks.len()];
+    for start in 0..blocks.len().saturating_sub(window - 1) {
+        let range = start..start + window;
+        let pinned = blocks[range.clone()].iter().filter(|b| b.0 >= pinned_level).count();
+        if peak == 0.0 || (pinned as f32) < MIN_PINNED_FRACTION * window as f32 {
+            continue;
+        }
+        let crests: Vec<f32> = blocks[range.clone()].iter().map(|b| b.1).collect();
+        if percentile(&crests, 0.5) >= MIN_CREST_DB
+            && percentile(&crests, 0.9) - percentile(&crests, 0.1) >= MIN_CREST_SPREAD_DB
+            && !clipped.iter().any(|c| c.0 < (start + window) * block && c.1 > start * block)
+        {
+            limited[range].iter_mut().for_each(|l| *l = true);
+        }
+    }
+    let mut regions = Vec::new();
+    let mut i = 0;
+    while i < limited.len() {
+        let run = limited[i..].iter().take_while(|&&l| l).count();
+        if run > 0 {
+            regions.push((i * block, (i + run) * block, peak, 0));
+        }
+        i += run.max(1);
+    }
+    let limited_secs = regions.iter().map(|r| time(r.1 - r.0)).sum();
+    let worst_limiting_time = regions.iter().max_by_key(|r| r.1 - r.0).map(|r| time(r.0));
+    events.extend(regions.iter().map(|&(start, end, level, _)| ClipEvent {
+        kind: ClipKind::Limiting,
+        start_time: time(start),
+        end_time: time(end),
+        level_dbfs: to_db(level),
+        clipped_samples: 0,
+    }));
+    events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+
+    ClippingReport {
+        sample_peak_dbfs: to_db(peak),
+        true_peak_dbtp: to_db(true_peak),
+        hard_clip_events: clipped.len(),
+        clipped_samples,
+        longest_flat_run,
+        worst_hard_clip_time,
+        inter_sample_overs: overs.len(),
+        worst_over_time,
+        limiting_regions: regions.len(),
+        limited_secs,
+        worst_limiting_time,
+        events,
+    }
+}
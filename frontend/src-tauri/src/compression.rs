@@ -0,0 +1,398 @@
+//! Lossy-coding history: MDCT frame-grid detection of earlier encodings and
+//! the band limit lossy encoders leave behind
+
+use crate::spectrum::{self, WindowType};
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Length of the analyzed excerpt (seconds)
+const EXCERPT_SECS: f32 = 6.0;
+/// Step of the output quantizer the noise floor assumes (16-bit)
+const QUANT_STEP: f32 = 1.0 / 32768.0;
+/// Coefficients this far below the frame's mean power count as zero
+const RELATIVE_FLOOR: f32 = 1e-6;
+/// Robust z-score and least excess depth (dB) for a grid phase to count
+const PEAK_Z: f32 = 8.0;
+const MIN_EXCESS_DB: f32 = 0.5;
+/// Most encoder grids reported
+const MAX_GRIDS: usize = 4;
+
+/// Welch segment length of the long-term spectrum used for cutoff search
+const LTAS_SEGMENT: usize = 4096;
+/// Cutoffs are searched from this frequency up (Hz)
+const MIN_CUTOFF_HZ: f32 = 3000.0;
+/// Level before and after a candidate cutoff is averaged over this span,
+/// starting this far from it (Hz)
+const EDGE_SPAN_HZ: f32 = 500.0;
+const EDGE_GAP_HZ: f32 = 100.0;
+/// Least level fall across a cutoff for it to count as sharp (dB)
+const SHARP_DROP_DB: f32 = 20.0;
+/// Least step for a shelf below the cutoff (dB)
+const SHELF_DROP_DB: f32 = 8.0;
+/// Converter antialiasing filters leave the band up to about this fraction
+/// of Nyquist; sharp cutoffs below it point to a lossy encoder
+const CONVERTER_BANDWIDTH: f32 = 0.9;
+/// A frame's top band counts as switched off this far below the band under it (dB)
+const GAP_DB: f32 = 30.0;
+/// Frame length of the top-band gap check (about two MP3 granules)
+const GAP_FRAME: usize = 1024;
+
+/// Lowpass cutoffs (kbps, Hz) LAME applies per CBR bitrate
+const MP3_BANDWIDTHS: [(u32, f32); 17] = [
+    (8, 2000.0), (16, 3700.0), (24, 3900.0), (32, 5500.0), (40, 7000.0), (48, 7500.0),
+    (56, 10000.0), (64, 11000.0), (80, 13500.0), (96, 15100.0), (112, 15600.0),
+    (128, 17000.0), (160, 17500.0), (192, 18600.0), (224, 19400.0), (256, 19700.0),
+    (320, 20500.0),
+];
+/// Typical AAC-LC encoder bandwidths (kbps stereo, Hz); encoders differ
+const AAC_BANDWIDTHS: [(u32, f32); 8] = [
+    (32, 8000.0), (48, 11000.0), (64, 14000.0), (96, 15500.0), (128, 17000.0),
+    (160, 18500.0), (192, 19500.0), (256, 20000.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodecFamily {
+    Mp3,  // 576-sample granules
+    Aac,  // 1024-sample long blocks
+}
+
+impl CodecFamily {
+    const ALL: [CodecFamily; 2] = [CodecFamily::Mp3, CodecFamily::Aac];
+
+    /// MDCT hop (half the transform length)
+    fn block_len(self) -> usize {
+        match self {
+            CodecFamily::Mp3 => 576,
+            CodecFamily::Aac => 1024,
+        }
+    }
+
+    fn bandwidths(self) -> &'static [(u32, f32)] {
+        match self {
+            CodecFamily::Mp3 => &MP3_BANDWIDTHS,
+            CodecFamily::Aac => &AAC_BANDWIDTHS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridPeak {
+    pub codec: CodecFamily,
+    pub offset: usize,   // Phase of the frame grid relative to the file start (samples)
+    pub depth_db: f32,   // Mean depth of the MDCT coefficients below their frame mean
+    pub strength: f32,   // Robust z-score against the other phases
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionReport {
+    pub codec: Option<CodecFamily>,         // Family of the strongest grid
+    pub generations: usize,                 // Distinct encoder grids found (0 = no lossy history seen)
+    pub grids: Vec<GridPeak>,               // Strongest first
+    pub bandwidth_hz: Option<f32>,          // Coded bandwidth on the strongest grid
+    pub estimated_bitrate_kbps: Option<u32>,
+    pub depth_by_offset: Vec<f32>,          // For the detected codec (MP3 when none)
+}
+
+/// Sine-windowed MDCT of 2·`m`-sample frames, computed from a 4·`m` real FFT
+struct Mdct {
+    m: usize,
+    window: Vec<f32>,
+    twiddle: Vec<Complex<f32>>,
+}
+
+impl Mdct {
+    fn new(m: usize) -> Self {
+        let n0 = 0.5 + m as f32 / 2.0;
+        Mdct {
+            m,
+            window: (0..2 * m).map(|n| (PI * (n as f32 + 0.5) / (2 * m) as f32).sin()).collect(),
+            twiddle: (0..m)
+                .map(|k| Complex::from_polar(1.0, -2.0 * PI * n0 * (2 * k + 1) as f32 / (4 * m) as f32))
+                .collect(),
+        }
+    }
+
+    /// Mean depth (dB) of the coefficients below their frame's mean power
+    /// for the frames of `x` starting at `offset`. Adds the per-bin count of
+    /// frames where the coefficient is not zero to `active`; returns the
+    /// depth and the number of non-silent frames.
+    fn depth(&self, x: &[f32], offset: usize, floor: f32, active: &mut [usize]) -> (f32, usize) {
+        let m = self.m;
+        let mut planner = RealFftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(4 * m);
+        let mut input = vec![0.0f32; 4 * m];
+        let mut spectrum = fft.make_output_vec();
+        let mut coeffs = vec![0.0f32; m];
+        let (mut depth_sum, mut frames) = (0.0f64, 0);
+
+        let mut start = offset;
+        while start + 2 * m <= x.len() {
+            input[..2 * m].iter_mut().zip(&x[start..]).zip(&self.window).for_each(|((d, &s), &w)| *d = s * w);
+            input[2 * m..].fill(0.0);
+            start += m;
+            if fft.process(&mut input, &mut spectrum).is_err() {
+                continue;
+            }
+            for (k, c) in coeffs.iter_mut().enumerate() {
+                *c = (self.twiddle[k] * spectrum[2 * k + 1]).re.powi(2);
+            }
+            let mean = coeffs.iter().sum::<f32>() / m as f32;
+            if mean <= floor {
+                continue; // Silent frame
+            }
+            let threshold = floor.max(mean * RELATIVE_FLOOR);
+            let mut log_sum = 0.0f64;
+            for (k, &c) in coeffs.iter().enumerate() {
+                log_sum += ((c + floor) / mean).log10() as f64;
+                if c >= threshold {
+                    active[k] += 1;
+                }
+            }
+            depth_sum -= 10.0 * log_sum / m as f64;
+            frames += 1;
+        }
+        ((depth_sum / frames.max(1) as f64) as f32, frames)
+    }
+}
+
+/// Output rounding noise of a 16-bit decoder, 4σ per coefficient
+fn noise_floor(m: usize) -> f32 {
+    16.0 * m as f32 * QUANT_STEP * QUANT_STEP / 12.0
+}
+
+/// Local maxima of the circular depth curve that stand out from the
+/// other grid phases
+fn grid_peaks(codec: CodecFamily, curve: &[f32]) -> Vec<GridPeak> {
+    let mut sorted = curve.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    let mut deviations: Vec<f32> = curve.iter().map(|s| (s - median).abs()).collect();
+    deviations.sort_by(f32::total_cmp);
+    let sigma = (1.4826 * deviations[deviations.len() / 2]).max(1e-4);
+
+    let n = curve.len();
+    (0..n)
+        .filter(|&i| (1..=2).all(|d| curve[i] >= curve[(i + d) % n] && curve[i] > curve[(i + n - d) % n]))
+        .map(|i| GridPeak { codec, offset: i, depth_db: curve[i], strength: (curve[i] - median) / sigma })
+        .filter(|p| p.strength >= PEAK_Z && p.depth_db - median >= MIN_EXCESS_DB)
+        .collect()
+}
+
+/// Typical encoder bandwidth at `kbps` (LAME's lowpass for MP3)
+pub fn typical_bandwidth(codec: CodecFamily, kbps: u32) -> Option<f32> {
+    codec.bandwidths().iter().find(|&&(rate, _)| rate == kbps).map(|&(_, hz)| hz)
+}
+
+/// Bitrate whose typical encoder bandwidth is closest to `bandwidth`
+fn nearest_bitrate(codec: CodecFamily, bandwidth: f32) -> u32 {
+    codec
+        .bandwidths()
+        .iter()
+        .min_by(|a, b| (a.1 - bandwidth).abs().total_cmp(&(b.1 - bandwidth).abs()))
+        .map_or(0, |&(kbps, _)| kbps)
+}
+
+/// Look for the frame grids of earlier MP3 or AAC encodings.
+///
+/// Decoded lossy audio re-analyzed with the codec's MDCT at the encoder's
+/// frame alignment shows coefficients quantized to zero or near it; at any
+/// other alignment these holes smear into their neighbours. The mean depth
+/// of the coefficients below their frame's mean power therefore peaks at
+/// each encoder's grid phase, and a later encoding on a different grid
+/// fills the earlier holes only partly. Several phases that stand out mean
+/// several generations, of either codec family. Generations on the same
+/// grid are not told apart. The coded bandwidth on the strongest grid gives the bitrate
+/// estimate.
+pub fn analyze(samples: &[f32], sample_rate: u32) -> CompressionReport {
+    let len = ((EXCERPT_SECS * sample_rate as f32) as usize).min(samples.len());
+    // Start on a multiple of both block lengths so grid phases refer to the file start
+    let start = (samples.len() - len) / 2 / 9216 * 9216;
+    let excerpt = &samples[start..start + len];
+
+    let mut grids = Vec::new();
+    let mut curves = Vec::new();
+    for codec in CodecFamily::ALL {
+        let m = codec.block_len();
+        if excerpt.len() < 20 * m {
+            continue;
+        }
+        let mdct = Mdct::new(m);
+        let curve: Vec<f32> = (0..m)
+            .into_par_iter()
+            .map(|offset| mdct.depth(excerpt, offset, noise_floor(m), &mut vec![0; m]).0)
+            .collect();
+        grids.extend(grid_peaks(codec, &curve));
+        curves.push((codec, curve));
+    }
+    grids.sort_by(|a, b| b.strength.total_cmp(&a.strength));
+    grids.truncate(MAX_GRIDS);
+
+    let codec = grids.first().map(|g| g.codec);
+    let depth_by_offset = curves
+        .into_iter()
+        .find(|(c, _)| *c == codec.unwrap_or(CodecFamily::Mp3))
+        .map(|(_, curve)| curve)
+        .unwrap_or_default();
+
+    // Coded bandwidth on the strongest grid: highest bin that is non-zero
+    // in at least a tenth of the frames
+    let bandwidth = grids.first().and_then(|grid| {
+        let m = grid.codec.block_len();
+        let mut active = vec![0; m];
+        let (_, frames) = Mdct::new(m).depth(excerpt, grid.offset, noise_floor(m), &mut active);
+        let bin_hz = sample_rate as f32 / (2 * m) as f32;
+        active
+            .iter()
+            .rposition(|&count| count * 10 >= frames.max(1))
+            .map(|k| (k + 1) as f32 * bin_hz)
+            .filter(|&hz| hz < 0.95 * sample_rate as f32 / 2.0)
+    });
+
+    CompressionReport {
+        codec,
+        generations: grids.len(),
+        grids,
+        bandwidth_hz: bandwidth,
+        estimated_bitrate_kbps: codec.zip(bandwidth).map(|(codec, hz)| nearest_bitrate(codec, hz)),
+        depth_by_offset,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CutoffReport {
+    pub cutoff_hz: Option<f32>,                 // Upper edge of the coded band
+    pub drop_db: f32,                           // Level fall across the cutoff
+    pub shelf_hz: Option<f32>,                  // Lower step where the top band starts (MP3 sfb21)
+    pub band_gaps: f32,                         // Fraction of frames with the top band switched off
+    pub transcoded: bool,                       // Band limit points to an earlier lossy encoding
+    pub estimated_bitrate_kbps: Option<u32>,
+    pub freqs: Vec<f32>,
+    pub ltas_db: Vec<f32>,                      // Long-term spectrum the cutoff was found on
+}
+
+/// Mean of `db` over bins `lo..hi`
+pub fn mean_db(db: &[f32], lo: usize, hi: usize) -> f32 {
+    let hi = hi.min(db.len());
+    if lo >= hi {
+        return f32::NEG_INFINITY;
+    }
+    db[lo..hi].iter().sum::<f32>() / (hi - lo) as f32
+}
+
+/// Bin in `lo..hi` with the largest fall from the span below it to the
+/// span above it, among those where nothing above rises over the level
+/// just above. Returns (bin, fall in dB).
+fn sharpest_edge(db: &[f32], lo: usize, hi: usize, gap: usize, span: usize) -> Option<(usize, f32)> {
+    // Highest level from each bin up to Nyquist
+    let mut ceiling = db.to_vec();
+    for k in (0..db.len().saturating_sub(1)).rev() {
+        ceiling[k] = ceiling[k].max(ceiling[k + 1]);
+    }
+    (lo.max(gap + span)..hi.min(db.len().saturating_sub(gap + 1)))
+        .filter_map(|k| {
+            let below = mean_db(db, k - gap - span, k - gap);
+            let above = mean_db(db, k + gap, k + gap + span);
+            (ceiling[k + gap] <= above + 6.0).then_some((k, below - above))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// First bin around the edge at `edge` that lies 6 dB under the level
+/// below it
+fn fall_point(db: &[f32], edge: usize, gap: usize, span: usize) -> usize {
+    let below = mean_db(db, edge - gap - span, edge - gap);
+    (edge - gap - span..=edge + gap).find(|&k| db[k] < below - 6.0).unwrap_or(edge)
+}
+
+/// Sharpest band-limiting edge above `min_hz` in the dB spectrum `db`
+/// with bins `bin_hz` apart, with nothing rising again above it.
+/// Returns (bin of its -6 dB point, fall in dB).
+pub fn band_edge(db: &[f32], bin_hz: f32, min_hz: f32) -> Option<(usize, f32)> {
+    let bins = |hz: f32| (hz / bin_hz).round() as usize;
+    let (gap, span) = (bins(EDGE_GAP_HZ).max(1), bins(EDGE_SPAN_HZ).max(2));
+    let (edge, drop) = sharpest_edge(db, bins(min_hz), db.len(), gap, span)?;
+    Some((fall_point(db, edge, gap, span), drop))
+}
+
+/// Find a sharp high-frequency cutoff, the shelf below it and gaps in the
+/// top band, the marks an earlier lossy encoding leaves on audio that now
+/// presents as lossless. The bitrate estimate uses `codec`'s typical
+/// bandwidths. A cutoff from a 320 kbps MP3 (20.5 kHz) cannot be told from
+/// a 44.1 kHz converter's own antialiasing filter.
+pub fn spectral_cutoff(samples: &[f32], sample_rate: u32, codec: CodecFamily) -> CutoffReport {
+    let psd = spectrum::welch_psd(samples, sample_rate, LTAS_SEGMENT, LTAS_SEGMENT / 2, WindowType::Hann);
+    let db: Vec<f32> = psd.power.iter().map(|p| 10.0 * p.max(1e-20).log10()).collect();
+    let bin_hz = sample_rate as f32 / LTAS_SEGMENT as f32;
+    let bins = |hz: f32| (hz / bin_hz).round() as usize;
+    let (gap, span) = (bins(EDGE_GAP_HZ).max(1), bins(EDGE_SPAN_HZ).max(2));
+    let nyquist = sample_rate as f32 / 2.0;
+
+    let mut report = CutoffReport {
+        cutoff_hz: None,
+        drop_db: 0.0,
+        shelf_hz: None,
+        band_gaps: 0.0,
+        transcoded: false,
+        estimated_bitrate_kbps: None,
+        freqs: psd.freqs,
+        ltas_db: db.clone(),
+    };
+    if psd.segments == 0 {
+        return report;
+    }
+
+    let Some((edge, drop)) = sharpest_edge(&db, bins(MIN_CUTOFF_HZ), db.len(), gap, span) else {
+        return report;
+    };
+    report.drop_db = drop;
+    if drop < SHARP_DROP_DB {
+        return report;
+    }
+    let crossing = fall_point(&db, edge, gap, span);
+    let cutoff = crossing as f32 * bin_hz;
+    report.cutoff_hz = Some(cutoff);
+
+    // A smaller step within 5 kHz below the cutoff
+    let shelf_lo = bins((cutoff - 5000.0).max(MIN_CUTOFF_HZ));
+    let shelf_hi = crossing.saturating_sub(2 * (gap + span));
+    let shelf = sharpest_edge(&db[..crossing], shelf_lo, shelf_hi, gap, span)
+        .filter(|&(_, step)| step >= SHELF_DROP_DB)
+        .map(|(k, _)| fall_point(&db, k, gap, span));
+    report.shelf_hz = shelf.map(|k| k as f32 * bin_hz);
+
+    // Per-frame gaps: the top band (from the shelf or 2 kHz below the
+    // cutoff) against the 2 kHz under it
+    let top_lo = shelf.unwrap_or(crossing.saturating_sub(bins(2000.0)));
+    let ref_lo = top_lo.saturating_sub(bins(2000.0));
+    let window = WindowType::Hann.coefficients(GAP_FRAME);
+    let scale = LTAS_SEGMENT / GAP_FRAME;
+    let (top_lo, ref_lo, top_hi) = (top_lo / scale, ref_lo / scale, crossing / scale);
+    let frames: Vec<Option<bool>> = samples
+        .par_chunks_exact(GAP_FRAME)
+        .map(|frame| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(GAP_FRAME);
+            let mut input: Vec<f32> = frame.iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).ok()?;
+            let band = |lo: usize, hi: usize| spectrum[lo..hi].iter().map(|c| c.norm_sqr()).sum::<f32>() / (hi - lo).max(1) as f32;
+            let (top, reference) = (band(top_lo, top_hi), band(ref_lo, top_lo));
+            // Frames with nothing under the top band tell nothing
+            (reference > 1e-9).then(|| 10.0 * (reference / top.max(1e-20)).log10() > GAP_DB)
+        })
+        .collect();
+    let judged = frames.iter().flatten().count();
+    if judged > 0 {
+        report.band_gaps = frames.iter().flatten().filter(|&&gap| gap).count() as f32 / judged as f32;
+    }
+
+    report.transcoded = cutoff < CONVERTER_BANDWIDTH * nyquist || report.band_gaps > 0.05;
+    if report.transcoded {
+        report.estimated_bitrate_kbps = Some(nearest_bitrate(codec, cutoff));
+    }
+    report
+}
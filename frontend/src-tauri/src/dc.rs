@@ -0,0 +1,64 @@
+//! DC offset measurement and removal
+
+use crate::filters;
+use serde::{Deserialize, Serialize};
+
+/// Offsets from this size (full scale, -60 dBFS) skew level readings and
+/// are flagged
+const SIGNIFICANT_OFFSET: f32 = 1e-3;
+/// Block the offset is followed in (seconds)
+const BLOCK_SECS: f32 = 1.0;
+/// Order of the removal high-pass
+const HIGHPASS_ORDER: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DcOffset {
+    pub channel: usize,
+    pub offset: f32,            // Mean sample value (full scale)
+    pub offset_dbfs: f32,       // Its magnitude in dBFS
+    pub drift: f32,             // Standard deviation of the 1 s means
+    pub significant: bool,      // At least -60 dBFS
+}
+
+fn mean(samples: &[f32]) -> f32 {
+    samples.iter().map(|&s| s as f64).sum::<f64>() as f32 / samples.len().max(1) as f32
+}
+
+/// DC offset of every channel, with how far it wanders over the recording
+/// (a drifting offset comes from the capture electronics warming up or a
+/// failing coupling capacitor rather than a fixed converter bias)
+pub fn measure(channels: &[Vec<f32>], sample_rate: u32) -> Vec<DcOffset> {
+    let block = ((BLOCK_SECS * sample_rate as f32) as usize).max(1);
+    channels
+        .iter()
+        .enumerate()
+        .map(|(channel, samples)| {
+            let offset = mean(samples);
+            let blocks: Vec<f32> = samples.chunks_exact(block).map(mean).collect();
+            let average = mean(&blocks);
+            let drift = (blocks.iter().map(|m| (m - average).powi(2)).sum::<f32>() / blocks.len().max(1) as f32).sqrt();
+            DcOffset {
+                channel,
+                offset,
+                offset_dbfs: 20.0 * offset.abs().max(1e-10).log10(),
+                drift,
+                significant: offset.abs() >= SIGNIFICANT_OFFSET,
+            }
+        })
+        .collect()
+}
+
+/// Subtract the channel's mean
+pub fn remove_offset(samples: &mut [f32]) {
+    let offset = mean(samples);
+    samples.iter_mut().for_each(|s| *s -= offset);
+}
+
+/// High-pass the channel at `cutoff` Hz, which also removes a drifting
+/// offset. The mean is taken out first so the filter does not ring on the
+/// step at the start.
+pub fn highpass(samples: &[f32], cutoff: f32, sample_rate: u32) -> Vec<f32> {
+    let offset = mean(samples);
+    let centered: Vec<f32> = samples.iter().map(|s| s - offset).collect();
+    filters::butterworth_highpass(cutoff as f64, HIGHPASS_ORDER, sample_rate as f64).process_buffer(&centered)
+}
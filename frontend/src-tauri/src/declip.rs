@@ -0,0 +1,123 @@
+//! Restoration of hard clipping: the flat tops are interpolated from the
+//! waveform around them
+
+use crate::clicks;
+use crate::clipping;
+use serde::Deserialize;
+
+/// Autoregressive interpolation: predictor order, and the context fitted
+/// either side of a run (a multiple of its length, at least this many
+/// samples)
+const ORDER: usize = 32;
+const CONTEXT_RUNS: usize = 4;
+const MIN_CONTEXT: usize = 256;
+/// Runs longer than this (seconds) are interpolated with the cubic, which
+/// takes no equation system to solve
+const MAX_AR_SECS: f32 = 0.01;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeclipMethod {
+    /// Hermite cubic between the samples either side, following their slopes
+    Cubic,
+    /// Least-squares autoregressive interpolation from a predictor fitted to
+    /// the waveform around each run: follows the signal's own resonances
+    #[default]
+    Autoregressive,
+}
+
+/// Hermite cubic through the samples either side of `start..end`, with the
+/// slopes of their neighbours
+fn cubic(x: &[f32], start: usize, end: usize) -> Option<Vec<f32>> {
+    let before = start.checked_sub(2)?;
+    let after = (end + 1 < x.len()).then_some(end + 1)?;
+    let (p0, p1) = (x[start - 1] as f64, x[end] as f64);
+    let span = (end - start + 1) as f64;
+    let (m0, m1) = ((p0 - x[before] as f64) * span, (x[after] as f64 - p1) * span);
+    Some(
+        (start..end)
+            .map(|i| {
+                let t = (i + 1 - start) as f64 / span;
+                let (t2, t3) = (t * t, t * t * t);
+                ((2.0 * t3 - 3.0 * t2 + 1.0) * p0 + (t3 - 2.0 * t2 + t) * m0 + (-2.0 * t3 + 3.0 * t2) * p1 + (t3 - t2) * m1) as f32
+            })
+            .collect(),
+    )
+}
+
+/// Solve the symmetric positive definite system `m` y = `b` by Cholesky
+fn solve(mut m: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for j in 0..n {
+        let diagonal = m[j][j] - (0..j).map(|k| m[j][k] * m[j][k]).sum::<f64>();
+        if diagonal <= 0.0 {
+            return None;
+        }
+        m[j][j] = diagonal.sqrt();
+        for i in j + 1..n {
+            m[i][j] = (m[i][j] - (0..j).map(|k| m[i][k] * m[j][k]).sum::<f64>()) / m[j][j];
+        }
+    }
+    for i in 0..n {
+        b[i] = (b[i] - (0..i).map(|k| m[i][k] * b[k]).sum::<f64>()) / m[i][i];
+    }
+    for i in (0..n).rev() {
+        b[i] = (b[i] - (i + 1..n).map(|k| m[k][i] * b[k]).sum::<f64>()) / m[i][i];
+    }
+    Some(b)
+}
+
+/// The samples of `start..end` that make the prediction error of an
+/// autoregressive model, fitted to the context either side, smallest
+/// (Janssen, Veldhuis and Vries' LSAR)
+fn autoregressive(x: &[f32], start: usize, end: usize) -> Option<Vec<f32>> {
+    let run = end - start;
+    let context = (CONTEXT_RUNS * run).max(MIN_CONTEXT);
+    let (lo, hi) = (start.checked_sub(context)?, end + context);
+    if hi > x.len() {
+        return None;
+    }
+    let correlate = |side: &[f32], lag: usize| side.iter().zip(&side[lag..]).map(|(&a, &b)| a as f64 * b as f64).sum::<f64>();
+    let r: Vec<f64> = (0..=ORDER).map(|lag| correlate(&x[lo..start], lag) + correlate(&x[end..hi], lag)).collect();
+    let a = clicks::levinson(&r)?;
+
+    // Errors e_t = sum_j a_j x_(t - j) involving unknowns, for t in start..end + ORDER:
+    // minimizing them gives (A_u' A_u) x_u = -A_u' A_k x_k, with A_u' A_u
+    // Toeplitz in the coefficients' own autocorrelation
+    let ra: Vec<f64> = (0..run).map(|lag| if lag > ORDER { 0.0 } else { a.iter().zip(&a[lag..]).map(|(p, q)| p * q).sum() }).collect();
+    let m: Vec<Vec<f64>> = (0..run).map(|i| (0..run).map(|j| ra[i.abs_diff(j)]).collect()).collect();
+    let mut rhs = vec![0.0f64; run];
+    for t in start..end + ORDER {
+        let known: f64 = (0..=ORDER).filter(|&j| !(start..end).contains(&(t - j))).map(|j| a[j] * x[t - j] as f64).sum();
+        for j in (0..=ORDER).filter(|&j| (start..end).contains(&(t - j))) {
+            rhs[t - j - start] -= a[j] * known;
+        }
+    }
+    solve(m, rhs).map(|y| y.into_iter().map(|v| v as f32).collect())
+}
+
+/// Replace each flat top the clipping detection finds with an
+/// interpolation, held at least as far out as the ceiling on its side.
+/// Restored peaks rise above the old ceiling, so integer exports want the
+/// level brought down (normalized) as well. Returns the restored samples
+/// and how many runs were restored.
+pub fn declip(samples: &[f32], sample_rate: u32, method: DeclipMethod) -> (Vec<f32>, usize) {
+    let max_ar = (MAX_AR_SECS * sample_rate as f32) as usize;
+    let mut out = samples.to_vec();
+    let mut restored = 0;
+    for (start, end, ceiling, _) in clipping::flat_tops(samples) {
+        let sign = out[start].signum();
+        let interpolated = match method {
+            DeclipMethod::Autoregressive if end - start <= max_ar => autoregressive(&out, start, end),
+            _ => None,
+        }
+        .or_else(|| cubic(&out, start, end));
+        if let Some(values) = interpolated {
+            for (y, v) in out[start..end].iter_mut().zip(values) {
+                *y = sign * (sign * v).max(ceiling);
+            }
+            restored += 1;
+        }
+    }
+    (out, restored)
+}
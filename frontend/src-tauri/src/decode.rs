@@ -0,0 +1,87 @@
+//! Audio file decoding via symphonia
+
+use crate::channels;
+use log::{debug, info, warn};
+use std::path::PathBuf;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Fully decoded audio file
+pub struct DecodedAudio {
+    pub interleaved: Vec<f32>,
+    pub sample_rate: u32,
+    pub layout: Vec<String>,  // Speaker label per channel
+    pub bits_per_sample: Option<u32>, // Sample word length stated by the stream
+}
+
+/// Decode the default track of `path` to interleaved f32 samples
+pub fn decode_file(path: &str) -> Result<DecodedAudio, String> {
+    let file = std::fs::File::open(path).map_err(|e| {
+        warn!("Failed to open file: {}", e);
+        e.to_string()
+    })?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = PathBuf::from(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    debug!("Probing format...");
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| {
+            warn!("Probe failed: {}", e);
+            e.to_string()
+        })?;
+
+    let mut format = probed.format;
+    let track = format.default_track().ok_or("No audio track found")?;
+    info!("Found track: {}Hz, {} channels",
+        track.codec_params.sample_rate.unwrap_or(0),
+        track.codec_params.channels.map(|c| c.count()).unwrap_or(0));
+    let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+    let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(2);
+    let bits_per_sample = track.codec_params.bits_per_sample;
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let track_id = track.id;
+    let mut interleaved = Vec::new();
+    let mut actual_channels = channels;
+    let mut layout = track.codec_params.channels;
+
+    while let Ok(packet) = format.next_packet() {
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                let ch = spec.channels.count();
+                let mut sample_buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                sample_buf.copy_interleaved_ref(decoded);
+
+                // Store interleaved samples for playback
+                interleaved.extend_from_slice(sample_buf.samples());
+                actual_channels = ch;
+                layout = Some(spec.channels);
+            }
+            Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
+            Err(_) => break,
+        }
+    }
+
+    Ok(DecodedAudio {
+        interleaved,
+        sample_rate,
+        layout: channels::channel_labels(layout, actual_channels),
+        bits_per_sample,
+    })
+}
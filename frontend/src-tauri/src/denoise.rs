@@ -0,0 +1,136 @@
+//! Stationary noise reduction by spectral subtraction or Wiener filtering
+
+use crate::spectrum::{self, WindowType};
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::Deserialize;
+
+/// STFT frame (seconds, rounded up to a power of two)
+const FRAME_SECS: f32 = 0.04;
+/// Without a noise print the noise is the mean of the quietest tenth of the
+/// frames, leaving out digital silence
+const QUIET_FRACTION: f32 = 0.1;
+const MIN_FRAME_POWER: f32 = 1e-10;
+/// Spectral subtraction takes off this multiple of the noise power, which
+/// leaves less of it behind in random peaks ("musical noise")
+const OVER_SUBTRACTION: f32 = 2.0;
+/// Wiener filter: weight of the last frame's clean power in the a priori
+/// SNR (the decision-directed estimate, which keeps the gains smooth)
+const SNR_SMOOTHING: f32 = 0.98;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DenoiseMethod {
+    /// Power spectral subtraction
+    SpectralSubtraction,
+    /// Wiener gain on a smoothed SNR: fewer artifacts
+    #[default]
+    Wiener,
+}
+
+/// Noise reduction settings; every field has a default
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Denoise {
+    /// Noise print: a stretch of the recording (seconds) holding only the
+    /// noise. The quietest frames of the recording stand in without one.
+    pub noise_start: Option<f32>,
+    pub noise_end: Option<f32>,
+    /// Most the gain drops in any bin (dB); less sounds more natural
+    pub reduction_db: f32,
+    pub method: DenoiseMethod,
+}
+
+impl Default for Denoise {
+    fn default() -> Self {
+        Self { noise_start: None, noise_end: None, reduction_db: 12.0, method: DenoiseMethod::default() }
+    }
+}
+
+/// Noise power in each bin of each channel's STFT frames
+#[derive(Debug, Clone)]
+pub struct NoiseProfile {
+    pub frame: usize,
+    pub power: Vec<Vec<f32>>,
+}
+
+/// The STFT frame length used at `sample_rate`
+pub fn frame_length(sample_rate: u32) -> usize {
+    ((FRAME_SECS * sample_rate as f32) as usize).next_power_of_two()
+}
+
+/// Each channel's noise spectrum, from the frames of `range` (sample
+/// indices) or, without one, from the quietest frames of the whole
+/// recording
+pub fn noise_profile(channels: &[&[f32]], sample_rate: u32, range: Option<(usize, usize)>) -> Result<NoiseProfile, String> {
+    let frame = frame_length(sample_rate);
+    let (start, end) = range.unwrap_or((0, channels.first().map_or(0, |c| c.len())));
+    if end < start + frame {
+        return Err(format!("The noise print must be at least {:.0} ms long", 1000.0 * frame as f32 / sample_rate as f32));
+    }
+    let window = WindowType::Hann.coefficients(frame);
+
+    // Power spectra of half-overlapping frames, per channel
+    let spectra: Vec<Vec<Vec<f32>>> = channels
+        .par_iter()
+        .map(|samples| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(frame);
+            let mut spectrum = fft.make_output_vec();
+            (start..=end - frame)
+                .step_by(frame / 2)
+                .map(|at| {
+                    let mut input: Vec<f32> = samples[at..at + frame].iter().zip(&window).map(|(s, w)| s * w).collect();
+                    fft.process(&mut input, &mut spectrum).unwrap();
+                    spectrum.iter().map(|c| c.norm_sqr()).collect()
+                })
+                .collect()
+        })
+        .collect();
+
+    let n_frames = spectra.first().map_or(0, Vec::len);
+    let mut picked: Vec<usize> = (0..n_frames).collect();
+    if range.is_none() {
+        let energy = |f: usize| spectra.iter().map(|c| c[f].iter().sum::<f32>()).sum::<f32>() / (frame * channels.len()) as f32;
+        picked.retain(|&f| energy(f) > MIN_FRAME_POWER);
+        if picked.is_empty() {
+            return Err("The recording is digital silence; there is no noise to measure".to_string());
+        }
+        picked.sort_by(|&a, &b| energy(a).total_cmp(&energy(b)));
+        picked.truncate(((picked.len() as f32 * QUIET_FRACTION).ceil() as usize).max(1));
+    }
+
+    let power = spectra
+        .iter()
+        .map(|frames| {
+            let mut mean = vec![0.0f32; frame / 2 + 1];
+            for &f in &picked {
+                mean.iter_mut().zip(&frames[f]).for_each(|(m, p)| *m += p / picked.len() as f32);
+            }
+            mean
+        })
+        .collect();
+    Ok(NoiseProfile { frame, power })
+}
+
+/// Pull the noise in `noise` (one channel's profile) out of `samples`
+pub fn reduce(samples: &[f32], noise: &[f32], frame: usize, settings: &Denoise) -> Vec<f32> {
+    let floor = 10f32.powf(-settings.reduction_db / 20.0);
+    let mut clean = vec![0.0f32; noise.len()];
+    spectrum::stft_filter(samples, frame, |_, spectrum| {
+        for ((bin, &n), previous) in spectrum.iter_mut().zip(noise).zip(clean.iter_mut()) {
+            let power = bin.norm_sqr();
+            let n = n.max(1e-20);
+            let gain = match settings.method {
+                DenoiseMethod::SpectralSubtraction => (1.0 - OVER_SUBTRACTION * n / power.max(1e-20)).max(0.0).sqrt(),
+                DenoiseMethod::Wiener => {
+                    let prior = SNR_SMOOTHING * *previous / n + (1.0 - SNR_SMOOTHING) * (power / n - 1.0).max(0.0);
+                    prior / (1.0 + prior)
+                }
+            }
+            .max(floor);
+            *bin *= gain;
+            *previous = power * gain * gain;
+        }
+    })
+}
@@ -0,0 +1,214 @@
+//! A/B comparison: two recordings aligned, their spectrograms subtracted
+//! frame by frame, and the difference summarized per octave band, to show
+//! where a supposed copy departs from the original
+
+use crate::align;
+use crate::segments::{self, TimeRange};
+use crate::spectrum::{spectrogram_db, WindowType};
+use serde::{Deserialize, Serialize};
+
+/// Levels this far below the louder file's loudest bin count as silence;
+/// both files are raised to that floor before subtracting, so noise in
+/// quiet passages does not show as difference
+const FLOOR_DB: f32 = 90.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DifferenceOptions {
+    /// Compare this channel of both files instead of their mixes
+    pub channel: Option<usize>,
+    /// Level the comparison to the reference before comparing
+    pub match_gain: bool,
+    /// Largest offset searched (seconds)
+    pub max_offset: f32,
+    pub n_fft: usize,
+    pub hop_length: usize,
+    pub window: WindowType,
+    /// Hz; Nyquist when unset
+    pub max_freq: Option<f32>,
+    /// Difference (dB) above which a cell counts as divergent, and a frame
+    /// when its mean absolute difference exceeds it
+    pub threshold_db: f32,
+}
+
+impl Default for DifferenceOptions {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            match_gain: true,
+            max_offset: 10.0,
+            n_fft: 2048,
+            hop_length: 512,
+            window: WindowType::Hann,
+            max_freq: None,
+            threshold_db: 6.0,
+        }
+    }
+}
+
+impl DifferenceOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(64..=65536).contains(&self.n_fft) {
+            return Err("FFT size must be between 64 and 65536".to_string());
+        }
+        if self.hop_length == 0 || self.hop_length > self.n_fft {
+            return Err("Hop length must be between 1 and the FFT size".to_string());
+        }
+        if self.max_offset.is_nan() || self.max_offset < 0.0 {
+            return Err("Largest offset must not be negative".to_string());
+        }
+        if self.threshold_db.is_nan() || self.threshold_db <= 0.0 {
+            return Err("Divergence threshold must be above 0 dB".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How the comparison departs from the reference in one octave band, over
+/// the cells (frame and bin) either file has sound in
+#[derive(Debug, Clone, Serialize)]
+pub struct BandDifference {
+    pub center_hz: f32,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    pub mean_db: f32,               // Comparison minus reference; positive is louder
+    pub mean_abs_db: f32,
+    pub max_abs_db: f32,
+    pub divergent_fraction: f32,    // Share of cells differing by more than the threshold
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DifferenceReport {
+    pub offset_samples: i64,        // comparison[i + offset] lines up with reference[i]
+    pub offset_seconds: f32,
+    pub alignment_correlation: f32,
+    pub gain_db: f32,               // Added to the comparison's levels before comparing
+    pub overlap_start: f32,         // Overlap in reference time (seconds)
+    pub overlap_duration: f32,
+    pub n_fft: usize,
+    pub hop_length: usize,
+    pub bin_hz: f32,
+    pub times: Vec<f32>,            // Frame starts (reference time)
+    pub difference_db: Vec<Vec<f32>>,  // Frames × bins, comparison minus reference
+    pub frame_difference_db: Vec<f32>, // Mean absolute difference per frame
+    pub mean_abs_db: f32,
+    pub bands: Vec<BandDifference>,
+    pub divergent_ranges: Vec<TimeRange>,
+}
+
+/// Align `other` to `reference` and subtract their spectrograms
+pub fn difference(reference: &[f32], other: &[f32], sample_rate: u32, options: &DifferenceOptions) -> Result<DifferenceReport, String> {
+    options.validate()?;
+    let sr = sample_rate as f32;
+    let (offset, correlation) = align::find_offset(reference, other, sample_rate, options.max_offset);
+
+    // Overlap in reference indices
+    let start = (-offset).max(0) as usize;
+    let end = (reference.len() as i64).min(other.len() as i64 - offset).max(0) as usize;
+    if end < start + options.n_fft {
+        return Err("The files do not overlap by a whole FFT frame".to_string());
+    }
+    let a = &reference[start..end];
+    let b = &other[(start as i64 + offset) as usize..(end as i64 + offset) as usize];
+
+    let max_freq = options.max_freq.unwrap_or(sr / 2.0).min(sr / 2.0);
+    let (times, spec_a) = spectrogram_db(a, sample_rate, options.n_fft, options.hop_length, options.window, max_freq);
+    let (_, spec_b) = spectrogram_db(b, sample_rate, options.n_fft, options.hop_length, options.window, max_freq);
+    let top = spec_a.iter().chain(&spec_b).flatten().copied().fold(f32::NEG_INFINITY, f32::max);
+    let floor = top - FLOOR_DB;
+    let bin_hz = sr / options.n_fft as f32;
+
+    // Level the files by the median difference where both have sound, which
+    // a passage added or changed in one of them barely moves
+    let gain_db = if options.match_gain {
+        let mut both: Vec<f32> = spec_a
+            .iter()
+            .flatten()
+            .zip(spec_b.iter().flatten())
+            .filter(|&(&x, &y)| x > floor && y > floor)
+            .map(|(&x, &y)| x - y)
+            .collect();
+        if both.is_empty() {
+            0.0
+        } else {
+            let middle = both.len() / 2;
+            *both.select_nth_unstable_by(middle, f32::total_cmp).1
+        }
+    } else {
+        0.0
+    };
+    let spec_b: Vec<Vec<f32>> = spec_b.into_iter().map(|frame| frame.into_iter().map(|y| y + gain_db).collect()).collect();
+
+    let difference_db: Vec<Vec<f32>> = spec_a
+        .iter()
+        .zip(&spec_b)
+        .map(|(fa, fb)| fa.iter().zip(fb).map(|(&x, &y)| y.max(floor) - x.max(floor)).collect())
+        .collect();
+    // Bins where either file has sound, so silence does not dilute the means
+    let sounding = |frame: usize, bin: usize| spec_a[frame][bin] > floor || spec_b[frame][bin] > floor;
+
+    let mean_abs = |cells: &mut dyn Iterator<Item = f32>| {
+        let (sum, count) = cells.fold((0.0f64, 0usize), |(s, n), d| (s + d.abs() as f64, n + 1));
+        if count == 0 { 0.0 } else { (sum / count as f64) as f32 }
+    };
+    let frame_difference_db: Vec<f32> = difference_db
+        .iter()
+        .enumerate()
+        .map(|(f, frame)| mean_abs(&mut frame.iter().enumerate().filter(|&(k, _)| sounding(f, k)).map(|(_, &d)| d)))
+        .collect();
+    let mean_abs_db = mean_abs(&mut difference_db.iter().enumerate()
+        .flat_map(|(f, frame)| frame.iter().enumerate().filter(move |&(k, _)| sounding(f, k)).map(|(_, &d)| d)));
+
+    // Octave bands from 31.5 Hz up, those that start below the top bin
+    let bins = difference_db.first().map_or(0, Vec::len);
+    let mut bands = Vec::new();
+    for center in (-5..=5).map(|k| 1000.0 * 2f32.powi(k)) {
+        let (low_hz, high_hz) = (center / 2f32.sqrt(), center * 2f32.sqrt());
+        let (first, last) = ((low_hz / bin_hz).ceil() as usize, ((high_hz / bin_hz).ceil() as usize).min(bins));
+        if first >= last {
+            continue;
+        }
+        let cells: Vec<f32> = difference_db
+            .iter()
+            .enumerate()
+            .flat_map(|(f, frame)| (first..last).filter(move |&k| sounding(f, k)).map(move |k| frame[k]))
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+        let n = cells.len() as f32;
+        bands.push(BandDifference {
+            center_hz: center,
+            low_hz,
+            high_hz: high_hz.min(max_freq),
+            mean_db: cells.iter().sum::<f32>() / n,
+            mean_abs_db: cells.iter().map(|d| d.abs()).sum::<f32>() / n,
+            max_abs_db: cells.iter().fold(0.0, |m, d| m.max(d.abs())),
+            divergent_fraction: cells.iter().filter(|d| d.abs() > options.threshold_db).count() as f32 / n,
+        });
+    }
+
+    let divergent: Vec<bool> = frame_difference_db.iter().map(|&d| d > options.threshold_db).collect();
+    let divergent_ranges = segments::runs_to_ranges(&segments::flag_runs(&divergent, 2), options.hop_length, options.n_fft, sample_rate)
+        .into_iter()
+        .map(|r| TimeRange { start_time: r.start_time + start as f32 / sr, end_time: r.end_time + start as f32 / sr })
+        .collect();
+
+    Ok(DifferenceReport {
+        offset_samples: offset,
+        offset_seconds: offset as f32 / sr,
+        alignment_correlation: correlation,
+        gain_db,
+        overlap_start: start as f32 / sr,
+        overlap_duration: (end - start) as f32 / sr,
+        n_fft: options.n_fft,
+        hop_length: options.hop_length,
+        bin_hz,
+        times: times.iter().map(|t| t + start as f32 / sr).collect(),
+        difference_db,
+        frame_difference_db,
+        mean_abs_db,
+        bands,
+        divergent_ranges,
+    })
+}
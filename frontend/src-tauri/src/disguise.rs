@@ -0,0 +1,177 @@
+//! Pitch- and formant-shift (voice disguise) detection
+
+use crate::pitch::{self, ANALYSIS_RATE, HOP_SECS};
+use serde::{Deserialize, Serialize};
+
+/// LPC analysis frame (seconds) and order
+const LPC_FRAME_SECS: f32 = 0.025;
+const LPC_ORDER: usize = 16;
+/// Formants sought between these frequencies (Hz), at most this many
+const FORMANT_RANGE: (f32, f32) = (200.0, 7000.0);
+const MAX_FORMANTS: usize = 4;
+/// Envelope resolution for the peak search
+const ENVELOPE_POINTS: usize = 512;
+/// Frames more than this far under the loudest voiced frame are skipped (dB)
+const LEVEL_RANGE_DB: f32 = 25.0;
+/// Least voiced frames for an estimate
+const MIN_FRAMES: usize = 50;
+/// Natural voices: ln(spacing / 1000 Hz) ≈ SLOPE · ln(F0 / 120 Hz), from
+/// adult male (120 Hz, 17.5 cm tract) to adult female (210 Hz, 14.8 cm)
+const REFERENCE_F0: f32 = 120.0;
+const REFERENCE_SPACING: f32 = 1000.0;
+const SLOPE: f32 = 0.3;
+/// Deviation from that line (natural log) beyond which a voice is flagged;
+/// speakers scatter by about 0.1
+const MAX_DEVIATION: f32 = 0.2;
+/// Speed of sound (cm/s) for the tract length
+const SOUND_SPEED: f32 = 35_000.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisguiseReport {
+    pub voiced_frames: usize,
+    pub f0_hz: Option<f32>,               // Median voiced F0
+    pub formant_spacing_hz: Option<f32>,  // Median spacing of F1-F4 (uniform-tube fit)
+    pub tract_length_cm: Option<f32>,     // Vocal tract length the spacing implies
+    pub deviation: Option<f32>,           // ln(spacing) off the natural F0-spacing line
+    pub shift_factor: Option<f32>,        // Pitch shift (formants moved along) that explains it
+    pub formant_factor: Option<f32>,      // Formant-only shift that explains it
+    pub likely_shifted: bool,
+}
+
+/// LPC coefficients a[1..=order] by Levinson-Durbin on the autocorrelation
+fn lpc(frame: &[f32], order: usize) -> Option<Vec<f64>> {
+    let r: Vec<f64> = (0..=order)
+        .map(|lag| frame.iter().zip(&frame[lag..]).map(|(&a, &b)| a as f64 * b as f64).sum())
+        .collect();
+    if r[0] <= 0.0 {
+        return None;
+    }
+    let mut a = vec![0.0f64; order + 1];
+    a[0] = 1.0;
+    let mut error = r[0];
+    for i in 1..=order {
+        let k = -(0..i).map(|j| a[j] * r[i - j]).sum::<f64>() / error;
+        let previous = a.clone();
+        for j in 1..i {
+            a[j] = previous[j] + k * previous[i - j];
+        }
+        a[i] = k;
+        error *= 1.0 - k * k;
+        if error <= 0.0 {
+            return None;
+        }
+    }
+    Some(a)
+}
+
+/// Peaks of the LPC envelope in `FORMANT_RANGE`, lowest first
+fn formants(a: &[f64], sample_rate: f32) -> Vec<f32> {
+    let top = FORMANT_RANGE.1.min(0.45 * sample_rate);
+    let freqs: Vec<f32> = (0..ENVELOPE_POINTS).map(|i| top * i as f32 / (ENVELOPE_POINTS - 1) as f32).collect();
+    let envelope: Vec<f64> = freqs
+        .iter()
+        .map(|&f| {
+            let w = 2.0 * std::f64::consts::PI * f as f64 / sample_rate as f64;
+            let (re, im) = a.iter().enumerate().fold((0.0, 0.0), |(re, im), (k, &ak)| {
+                (re + ak * (w * k as f64).cos(), im - ak * (w * k as f64).sin())
+            });
+            -(re * re + im * im).ln()
+        })
+        .collect();
+    (1..ENVELOPE_POINTS - 1)
+        .filter(|&i| envelope[i] > envelope[i - 1] && envelope[i] >= envelope[i + 1] && freqs[i] >= FORMANT_RANGE.0)
+        .map(|i| freqs[i])
+        .take(MAX_FORMANTS)
+        .collect()
+}
+
+/// Formant spacing of a uniform tube closed at one end, fitted to the
+/// formants in order: F_i ≈ (2i - 1) · spacing / 2
+fn formant_spacing(formants: &[f32]) -> Option<f32> {
+    if formants.len() < 3 {
+        return None;
+    }
+    let (num, den) = formants.iter().enumerate().fold((0.0f32, 0.0f32), |(num, den), (i, &f)| {
+        let c = (2 * i + 1) as f32 / 2.0;
+        (num + f * c, den + c * c)
+    });
+    Some(num / den)
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    let (_, &mut m, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    m
+}
+
+/// Compare the voice's pitch with the vocal tract length its formant
+/// spacing implies. Natural voices follow a loose line (longer tracts, lower
+/// voices); a plain pitch shift moves pitch and formants by one factor and
+/// leaves the voice off that line, as does shifting the formants alone. The
+/// deviation is converted to the factor of either kind of shift that would
+/// explain it; a formant-preserving pitch shift moves the voice only weakly
+/// off the line and is rarely caught. Assumes one speaker.
+pub fn analyze(samples: &[f32], sample_rate: u32) -> DisguiseReport {
+    let audio = pitch::to_analysis_rate(samples, sample_rate);
+    let frames = pitch::track(&audio);
+    let sr = ANALYSIS_RATE as f32;
+    let hop = (HOP_SECS * sr) as usize;
+    let lpc_len = (LPC_FRAME_SECS * sr) as usize;
+    // Pitch frames are 40 ms; the LPC frame sits in their middle
+    let offset = (4 * hop).saturating_sub(lpc_len) / 2;
+    let window: Vec<f32> = (0..lpc_len)
+        .map(|i| 0.54 - 0.46 * (2.0 * std::f32::consts::PI * i as f32 / (lpc_len - 1) as f32).cos())
+        .collect();
+
+    let loudest = frames.iter().filter(|f| f.f0.is_some()).map(|f| f.level_db).fold(f32::NEG_INFINITY, f32::max);
+    let mut f0s = Vec::new();
+    let mut spacings = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let Some(f0) = frame.f0.filter(|_| frame.level_db >= loudest - LEVEL_RANGE_DB) else {
+            continue;
+        };
+        let start = i * hop + offset;
+        if start + lpc_len > audio.len() {
+            break;
+        }
+        // Pre-emphasized, Hamming-windowed
+        let segment: Vec<f32> = (0..lpc_len)
+            .map(|j| {
+                let previous = if start + j > 0 { audio[start + j - 1] } else { 0.0 };
+                (audio[start + j] - 0.97 * previous) * window[j]
+            })
+            .collect();
+        let Some(spacing) = lpc(&segment, LPC_ORDER).and_then(|a| formant_spacing(&formants(&a, sr))) else {
+            continue;
+        };
+        f0s.push(f0);
+        spacings.push(spacing);
+    }
+
+    let mut report = DisguiseReport {
+        voiced_frames: f0s.len(),
+        f0_hz: None,
+        formant_spacing_hz: None,
+        tract_length_cm: None,
+        deviation: None,
+        shift_factor: None,
+        formant_factor: None,
+        likely_shifted: false,
+    };
+    if f0s.len() < MIN_FRAMES {
+        return report;
+    }
+    let (f0, spacing) = (median(&mut f0s), median(&mut spacings));
+    // With x = ln(F0 / ref) and y = ln(spacing / ref), natural voices sit
+    // near y = SLOPE·x; a pitch shift by k adds ln k to both, a formant
+    // shift only to y
+    let deviation = (spacing / REFERENCE_SPACING).ln() - SLOPE * (f0 / REFERENCE_F0).ln();
+    report.f0_hz = Some(f0);
+    report.formant_spacing_hz = Some(spacing);
+    report.tract_length_cm = Some(SOUND_SPEED / (2.0 * spacing));
+    report.deviation = Some(deviation);
+    report.shift_factor = Some((deviation / (1.0 - SLOPE)).exp());
+    report.formant_factor = Some(deviation.exp());
+    report.likely_shifted = deviation.abs() > MAX_DEVIATION;
+    report
+}
@@ -0,0 +1,120 @@
+//! Harmonic distortion measurements on steady test tones
+
+use crate::spectrum::WindowType;
+use realfft::RealFftPlanner;
+use serde::Serialize;
+
+/// Bins either side of a peak summed as that component's power
+/// (covers the Blackman-Harris main lobe)
+const LOBE_BINS: usize = 5;
+
+/// Longest stretch analyzed; a few seconds already gives sub-Hz resolution
+const MAX_FFT_LEN: usize = 1 << 19;
+
+#[derive(Serialize)]
+pub struct HarmonicLevel {
+    pub order: usize,
+    pub freq: f32,
+    pub level_dbc: f32,
+}
+
+#[derive(Serialize)]
+pub struct DistortionReport {
+    pub fundamental_hz: f32,
+    pub fundamental_dbfs: f32,
+    pub thd_percent: f32,
+    pub thd_db: f32,
+    pub thd_n_percent: f32,
+    pub thd_n_db: f32,
+    pub sinad_db: f32,
+    pub harmonics: Vec<HarmonicLevel>,
+}
+
+/// Estimate fundamental, THD and THD+N of a test tone. The noise
+/// measurement bandwidth is 20 Hz to min(20 kHz, Nyquist).
+pub fn measure_thd(samples: &[f32], sample_rate: u32, max_harmonic: usize) -> Option<DistortionReport> {
+    let n = samples.len().min(MAX_FFT_LEN);
+    if n < 1024 {
+        return None;
+    }
+    let sr = sample_rate as f32;
+    let bin_hz = sr / n as f32;
+
+    let window = WindowType::BlackmanHarris.coefficients(n);
+    let win_power: f32 = window.iter().map(|w| w * w).sum();
+    let mut input: Vec<f32> = samples[..n].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(n);
+    let mut spectrum = fft.make_output_vec();
+    fft.process(&mut input, &mut spectrum).ok()?;
+    let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+
+    let band_low = ((20.0 / bin_hz).ceil() as usize).max(1);
+    let band_high = ((20_000.0f32.min(sr / 2.0) / bin_hz) as usize).min(power.len() - 1);
+    if band_low + 2 * LOBE_BINS >= band_high {
+        return None;
+    }
+
+    // Strongest in-band peak, refined by parabolic interpolation on log power
+    let peak_bin = (band_low..=band_high).max_by(|&a, &b| power[a].total_cmp(&power[b]))?;
+    let offset = if peak_bin > 0 && peak_bin + 1 < power.len() {
+        let (a, b, c) = (
+            power[peak_bin - 1].max(1e-30).ln(),
+            power[peak_bin].max(1e-30).ln(),
+            power[peak_bin + 1].max(1e-30).ln(),
+        );
+        let denom = a - 2.0 * b + c;
+        if denom.abs() > 1e-12 { 0.5 * (a - c) / denom } else { 0.0 }
+    } else {
+        0.0
+    };
+    let fundamental_hz = (peak_bin as f32 + offset) * bin_hz;
+
+    let lobe_power = |center: usize| -> f32 {
+        let lo = center.saturating_sub(LOBE_BINS);
+        let hi = (center + LOBE_BINS).min(power.len() - 1);
+        power[lo..=hi].iter().sum()
+    };
+
+    let fund_power = lobe_power(peak_bin);
+    if fund_power <= 0.0 {
+        return None;
+    }
+
+    let mut harmonic_power = 0.0;
+    let mut harmonics = Vec::new();
+    for order in 2..=max_harmonic.max(2) {
+        let freq = fundamental_hz * order as f32;
+        let bin = (freq / bin_hz).round() as usize;
+        if bin + LOBE_BINS > band_high {
+            break;
+        }
+        let p = lobe_power(bin);
+        harmonic_power += p;
+        harmonics.push(HarmonicLevel {
+            order,
+            freq,
+            level_dbc: 10.0 * (p / fund_power).max(1e-20).log10(),
+        });
+    }
+
+    let total_power: f32 = power[band_low..=band_high].iter().sum();
+    let residual = (total_power - fund_power).max(1e-30);
+
+    let thd = (harmonic_power / fund_power).sqrt();
+    let thd_n = (residual / total_power).sqrt();
+    // A sine of amplitude A puts A²·N·Σw²/4 into its one-sided main lobe
+    let amplitude = (4.0 * fund_power / (n as f32 * win_power)).sqrt();
+
+    Some(DistortionReport {
+        fundamental_hz,
+        fundamental_dbfs: 20.0 * amplitude.max(1e-10).log10(),
+        thd_percent: thd * 100.0,
+        thd_db: 20.0 * thd.max(1e-10).log10(),
+        thd_n_percent: thd_n * 100.0,
+        thd_n_db: 20.0 * thd_n.max(1e-10).log10(),
+        sinad_db: 10.0 * (total_power / residual).log10(),
+        harmonics,
+    })
+}
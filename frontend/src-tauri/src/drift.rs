@@ -0,0 +1,179 @@
+//! Clock drift between two recordings of the same event: the offset is
+//! measured piecewise along the reference and a line fitted through it,
+//! whose slope is how much faster the comparison's sample clock runs
+
+use crate::align;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DriftOptions {
+    /// Compare this channel of both files instead of their mixes
+    pub channel: Option<usize>,
+    /// Largest offset searched at the start (seconds)
+    pub max_offset: f32,
+    /// Excerpt correlated at each point (seconds)
+    pub window: f32,
+    /// Spacing of the points (seconds)
+    pub step: f32,
+    /// How far each point is searched from the one before (seconds)
+    pub search: f32,
+    /// Points correlating less than this are reported but not fitted
+    pub min_correlation: f32,
+}
+
+impl Default for DriftOptions {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            max_offset: 10.0,
+            window: 5.0,
+            step: 30.0,
+            search: 0.05,
+            min_correlation: 0.3,
+        }
+    }
+}
+
+impl DriftOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_offset.is_nan() || self.max_offset < 0.0 {
+            return Err("Largest offset must not be negative".to_string());
+        }
+        if self.window.is_nan() || self.window < 0.1 {
+            return Err("Window must be at least 0.1 s".to_string());
+        }
+        if self.step.is_nan() || self.step < self.window {
+            return Err("Step must be at least the window".to_string());
+        }
+        if !(self.search > 0.0 && self.search <= 1.0) {
+            return Err("Search range must be above 0 and at most 1 s".to_string());
+        }
+        if !(0.0..1.0).contains(&self.min_correlation) {
+            return Err("Minimum correlation must be from 0 to below 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The offset measured at one point of the reference
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftPoint {
+    pub time: f32,                  // Middle of the excerpt (reference time, seconds)
+    pub offset_seconds: f32,        // comparison[t + offset] lines up with reference[t]
+    pub correlation: f32,
+    pub residual_ms: Option<f32>,   // From the fitted line, for the points it was fitted to
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftReport {
+    pub drift_ppm: Option<f32>,             // Positive: the comparison's clock runs fast
+    pub offset_at_start: Option<f32>,       // The fitted line at reference time 0 (seconds)
+    pub rate_ratio: Option<f64>,            // Comparison samples per reference sample
+    pub residual_rms_ms: Option<f32>,
+    pub residual_max_ms: Option<f32>,
+    pub points_fitted: usize,
+    pub points: Vec<DriftPoint>,
+}
+
+/// Normalized correlation of `reference[start..start + len]` with `other`
+/// shifted by `lag`, zero where they do not overlap
+fn correlation_at(reference: &[f32], other: &[f32], start: usize, len: usize, lag: i64) -> f64 {
+    let (mut dot, mut ea, mut eb) = (0.0f64, 0.0f64, 0.0f64);
+    for (i, &x) in reference.iter().enumerate().skip(start).take(len) {
+        let j = i as i64 + lag;
+        if j < 0 || j as usize >= other.len() {
+            continue;
+        }
+        let (x, y) = (x as f64, other[j as usize] as f64);
+        dot += x * y;
+        ea += x * x;
+        eb += y * y;
+    }
+    if ea * eb > 0.0 { dot / (ea * eb).sqrt() } else { 0.0 }
+}
+
+/// Measure the offset of `other` against `reference` every `step` seconds,
+/// following it from point to point, and fit the drift to the points that
+/// correlate well
+pub fn estimate_drift(reference: &[f32], other: &[f32], sample_rate: u32, options: &DriftOptions) -> Result<DriftReport, String> {
+    options.validate()?;
+    let sr = sample_rate as f64;
+    let window = (options.window as f64 * sr) as usize;
+    let step = (options.step as f64 * sr) as usize;
+    let search = ((options.search as f64 * sr) as usize).max(1);
+    if reference.len() < window || other.len() < window {
+        return Err(format!("Both files must be longer than the {} s window", options.window));
+    }
+
+    let (mut lag, _) = align::find_offset(reference, other, sample_rate, options.max_offset);
+    let mut points = Vec::new();
+    let mut start = 0;
+    while start + window <= reference.len() {
+        let from = start as i64 + lag;
+        if from < 0 || from as usize + window > other.len() {
+            start += step;
+            continue;
+        }
+        let a = &reference[start..start + window];
+        let b = &other[from as usize..from as usize + window];
+        let (local, _) = align::xcorr_lag(a, b, search);
+        let found = lag + local;
+
+        // Sub-sample peak by a parabola through the neighbouring lags
+        let [before, at, after] = [found - 1, found, found + 1].map(|l| correlation_at(reference, other, start, window, l).abs());
+        let curvature = before - 2.0 * at + after;
+        let shift = if curvature < 0.0 { (0.5 * (before - after) / curvature).clamp(-0.5, 0.5) } else { 0.0 };
+        let correlation = correlation_at(reference, other, start, window, found) as f32;
+
+        points.push((start as f64 + window as f64 / 2.0, found as f64 + shift, correlation));
+        // Follow the offset only from points that can be trusted
+        if correlation.abs() >= options.min_correlation {
+            lag = found;
+        }
+        start += step;
+    }
+    if points.is_empty() {
+        return Err("The files do not overlap by a whole window".to_string());
+    }
+
+    // Least-squares line offset = intercept + slope · time, in samples
+    let fitted: Vec<(f64, f64)> = points
+        .iter()
+        .filter(|p| p.2.abs() >= options.min_correlation)
+        .map(|&(t, offset, _)| (t, offset))
+        .collect();
+    let line = (fitted.len() >= 2).then(|| {
+        let n = fitted.len() as f64;
+        let mean_t = fitted.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_o = fitted.iter().map(|p| p.1).sum::<f64>() / n;
+        let stt: f64 = fitted.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+        let sto: f64 = fitted.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_o)).sum();
+        let slope = sto / stt;
+        (mean_o - slope * mean_t, slope)
+    });
+
+    let points: Vec<DriftPoint> = points
+        .iter()
+        .map(|&(t, offset, correlation)| DriftPoint {
+            time: (t / sr) as f32,
+            offset_seconds: (offset / sr) as f32,
+            correlation,
+            residual_ms: line
+                .filter(|_| correlation.abs() >= options.min_correlation)
+                .map(|(intercept, slope)| ((offset - intercept - slope * t) / sr * 1000.0) as f32),
+        })
+        .collect();
+    let residuals: Vec<f32> = points.iter().filter_map(|p| p.residual_ms).collect();
+    let fitted_any = !residuals.is_empty();
+
+    Ok(DriftReport {
+        drift_ppm: line.map(|(_, slope)| (slope * 1e6) as f32),
+        offset_at_start: line.map(|(intercept, _)| (intercept / sr) as f32),
+        rate_ratio: line.map(|(_, slope)| 1.0 + slope),
+        residual_rms_ms: fitted_any.then(|| (residuals.iter().map(|r| r * r).sum::<f32>() / residuals.len() as f32).sqrt()),
+        residual_max_ms: fitted_any.then(|| residuals.iter().fold(0.0f32, |m, r| m.max(r.abs()))),
+        points_fitted: fitted.len(),
+        points,
+    })
+}
@@ -0,0 +1,201 @@
+//! DTMF (touch-tone) digit decoding
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Row and column frequencies (Hz) and the keypad they address
+const LOW_TONES: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
+const HIGH_TONES: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+const KEYPAD: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+/// Analysis frame and hop (seconds): 25.6 ms is the classic 205 samples at
+/// 8 kHz, fine enough to part neighbouring tones 70 Hz apart, and the hop
+/// fits two whole frames into the shortest digit
+const FRAME_SECS: f32 = 0.0256;
+const HOP_SECS: f32 = 0.005;
+/// A digit frame: the two tones carry at least this fraction of the frame's
+/// power, each is at least this loud (dBFS) and this far over the others of
+/// its group (dB), the high tone is within these limits of the low (dB,
+/// ITU-T Q.24 twist), and the high tone has no second harmonic within this
+/// much of it (dB), which voiced speech and music do. (The low tones'
+/// harmonics fall beside the high tones and would only measure their leakage.)
+const MIN_TONE_FRACTION: f32 = 0.6;
+const MIN_TONE_DBFS: f32 = -45.0;
+const MIN_GROUP_MARGIN_DB: f32 = 8.0;
+const TWIST_LIMITS_DB: (f32, f32) = (-8.0, 4.0);
+const MAX_HARMONIC_DB: f32 = -20.0;
+/// Frequency deviation a tone may have (fraction, Q.24 accepts 1.5%); each
+/// tone is also measured this far either side of nominal
+const MAX_DEVIATION: f32 = 0.015;
+/// A digit lasts at least this long (seconds, Q.24 accepts 40 ms), bridging
+/// dropouts no longer than this (seconds)
+const MIN_DIGIT_SECS: f32 = 0.04;
+const MAX_DROPOUT_SECS: f32 = 0.02;
+/// Digits this close together (seconds) form one dialed sequence
+const SEQUENCE_GAP_SECS: f32 = 3.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtmfDigit {
+    pub digit: char,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub low_hz: f32,        // Nominal row tone
+    pub high_hz: f32,       // Nominal column tone
+    pub level_dbfs: f32,    // Median of the stronger tone over the digit
+    pub twist_db: f32,      // Median high tone over low tone
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtmfSequence {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub digits: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtmfReport {
+    pub digits: Vec<DtmfDigit>,
+    pub sequences: Vec<DtmfSequence>,
+}
+
+/// One frame's decision: keypad row and column, level of each tone (dBFS)
+#[derive(Clone, Copy)]
+struct Detection {
+    row: usize,
+    column: usize,
+    low_dbfs: f32,
+    high_dbfs: f32,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+/// Mean-square power of the component of `frame` at `freq` (Goertzel)
+fn tone_power(frame: &[f32], freq: f32, sample_rate: f32) -> f32 {
+    let coeff = 2.0 * (2.0 * std::f32::consts::PI * freq / sample_rate).cos();
+    let (mut s1, mut s2) = (0.0f32, 0.0f32);
+    for &x in frame {
+        let s = x + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    let magnitude_sq = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+    // A sine of amplitude a gives (a N / 2)², and carries a² / 2
+    2.0 * magnitude_sq / (frame.len() * frame.len()) as f32
+}
+
+/// Power of a DTMF tone that may be off its nominal frequency
+fn dtmf_power(frame: &[f32], freq: f32, sample_rate: f32) -> f32 {
+    [1.0 - MAX_DEVIATION, 1.0, 1.0 + MAX_DEVIATION]
+        .iter()
+        .map(|k| tone_power(frame, k * freq, sample_rate))
+        .fold(0.0, f32::max)
+}
+
+fn to_db(power: f32) -> f32 {
+    10.0 * power.max(1e-20).log10()
+}
+
+/// The digit a frame carries, if any
+fn detect_frame(frame: &[f32], sample_rate: f32) -> Option<Detection> {
+    let total = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+    if total <= 0.0 {
+        return None;
+    }
+    let strongest = |tones: &[f32; 4]| {
+        let powers = tones.map(|f| dtmf_power(frame, f, sample_rate));
+        let best = (0..4).fold(0, |b, k| if powers[k] > powers[b] { k } else { b });
+        let runner_up = (0..4).filter(|&k| k != best).map(|k| powers[k]).fold(0.0, f32::max);
+        (best, powers[best], runner_up)
+    };
+    let (row, low, low_runner_up) = strongest(&LOW_TONES);
+    let (column, high, high_runner_up) = strongest(&HIGH_TONES);
+    let (low_dbfs, high_dbfs) = (to_db(low), to_db(high));
+    let twist = high_dbfs - low_dbfs;
+    let harmonic_free = 2.0 * HIGH_TONES[column] >= sample_rate / 2.0
+        || to_db(tone_power(frame, 2.0 * HIGH_TONES[column], sample_rate)) - high_dbfs <= MAX_HARMONIC_DB;
+
+    let is_digit = (low + high) / total >= MIN_TONE_FRACTION
+        && low_dbfs.min(high_dbfs) >= MIN_TONE_DBFS
+        && low_dbfs - to_db(low_runner_up) >= MIN_GROUP_MARGIN_DB
+        && high_dbfs - to_db(high_runner_up) >= MIN_GROUP_MARGIN_DB
+        && (TWIST_LIMITS_DB.0..=TWIST_LIMITS_DB.1).contains(&twist)
+        && harmonic_free;
+    is_digit.then_some(Detection { row, column, low_dbfs, high_dbfs })
+}
+
+/// Decode the DTMF digits in a recording: frames where one row tone and one
+/// column tone carry nearly all the power, at a plausible twist and without
+/// the harmonics of a voice, held for at least 40 ms. Digits dialed within
+/// a few seconds of each other are gathered into sequences.
+pub fn detect(samples: &[f32], sample_rate: u32) -> DtmfReport {
+    let sr = sample_rate as f32;
+    let mut report = DtmfReport { digits: Vec::new(), sequences: Vec::new() };
+    let frame = (FRAME_SECS * sr) as usize;
+    let hop = ((HOP_SECS * sr) as usize).max(1);
+    if frame == 0 || samples.len() < frame || HIGH_TONES[3] >= sr / 2.0 {
+        return report;
+    }
+
+    let starts: Vec<usize> = (0..).map(|i| i * hop).take_while(|&start| start + frame <= samples.len()).collect();
+    let detections: Vec<Option<Detection>> =
+        starts.par_iter().map(|&start| detect_frame(&samples[start..start + frame], sr)).collect();
+
+    let frames = |secs: f32| (secs / HOP_SECS).round() as usize;
+    // Whole frames that fit inside the shortest digit
+    let min_frames = (((MIN_DIGIT_SECS - FRAME_SECS) / HOP_SECS) as usize).max(1);
+    let mut i = 0;
+    while i < detections.len() {
+        let Some(first) = detections[i] else {
+            i += 1;
+            continue;
+        };
+        let key = (first.row, first.column);
+        let start = i;
+        let mut end = i;
+        while i < detections.len() && i - end <= frames(MAX_DROPOUT_SECS) + 1 {
+            match detections[i] {
+                Some(d) if (d.row, d.column) == key => end = i,
+                Some(_) => break,
+                None => {}
+            }
+            i += 1;
+        }
+        i = end + 1;
+        let held: Vec<Detection> = detections[start..=end].iter().flatten().copied().collect();
+        if held.len() < min_frames {
+            continue;
+        }
+        report.digits.push(DtmfDigit {
+            digit: KEYPAD[key.0][key.1],
+            start_time: (start * hop) as f32 / sr,
+            end_time: (end * hop + frame) as f32 / sr,
+            low_hz: LOW_TONES[key.0],
+            high_hz: HIGH_TONES[key.1],
+            level_dbfs: percentile(&held.iter().map(|d| d.low_dbfs.max(d.high_dbfs)).collect::<Vec<_>>(), 0.5),
+            twist_db: percentile(&held.iter().map(|d| d.high_dbfs - d.low_dbfs).collect::<Vec<_>>(), 0.5),
+        });
+    }
+
+    for digit in &report.digits {
+        match report.sequences.last_mut() {
+            Some(sequence) if digit.start_time - sequence.end_time <= SEQUENCE_GAP_SECS => {
+                sequence.digits.push(digit.digit);
+                sequence.end_time = digit.end_time;
+            }
+            _ => report.sequences.push(DtmfSequence {
+                start_time: digit.start_time,
+                end_time: digit.end_time,
+                digits: digit.digit.to_string(),
+            }),
+        }
+    }
+    report
+}
@@ -0,0 +1,248 @@
+//! Copy-move detection: stretches of a recording repeated elsewhere in it
+
+use crate::align;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Fingerprint frame length (rounded to a power of two) and hops per frame
+const FRAME_SECS: f32 = 0.064;
+const HOPS_PER_FRAME: usize = 16;
+/// Bands per frame; adjacent-band energy differences give 32 bits
+const N_BANDS: usize = 33;
+const BAND_LOW_HZ: f32 = 300.0;
+const BAND_HIGH_HZ: f32 = 8000.0;
+/// Frames quieter than this (mean square) carry no fingerprint
+const SILENCE_POWER: f32 = 1e-9;
+/// Fingerprints recurring more often than this come from stationary
+/// material (steady tones, silence-like noise) and seed no matches
+pub const MAX_OCCURRENCES: usize = 8;
+/// Exact fingerprint hits needed at one offset before it is verified
+pub const MIN_HITS: usize = 3;
+/// Block length of the sample-level verification (seconds)
+const BLOCK_SECS: f32 = 0.01;
+/// Longest period checked when ruling out periodic material (seconds)
+const MAX_PERIOD_SECS: f32 = 0.02;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicatePair {
+    pub source_start: f32,
+    pub target_start: f32,
+    pub duration: f32,
+    pub offset: f32,      // target_start - source_start (seconds)
+    pub similarity: f32,  // Normalized correlation of the two stretches (1 = identical)
+}
+
+/// Fingerprint frame and hop lengths (samples) at `sample_rate`
+pub fn frame_and_hop(sample_rate: u32) -> (usize, usize) {
+    let frame = ((FRAME_SECS * sample_rate as f32) as usize).next_power_of_two() / 2;
+    (frame, (frame / HOPS_PER_FRAME).max(1))
+}
+
+/// Haitsma-Kalker style 32-bit sub-fingerprint per hop: the signs of the
+/// change over time of adjacent-band energy differences. Silent frames and
+/// the first frame have none.
+pub fn fingerprints(samples: &[f32], sample_rate: u32, frame: usize, hop: usize) -> Vec<Option<u32>> {
+    let window = WindowType::Hann.coefficients(frame);
+    let bin_hz = sample_rate as f32 / frame as f32;
+    let high = BAND_HIGH_HZ.min(0.45 * sample_rate as f32);
+    let edges: Vec<usize> = (0..=N_BANDS)
+        .map(|i| {
+            let f = BAND_LOW_HZ * (high / BAND_LOW_HZ).powf(i as f32 / N_BANDS as f32);
+            ((f / bin_hz) as usize).max(1)
+        })
+        .collect();
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * hop)
+        .take_while(|&start| start + frame <= samples.len())
+        .collect();
+
+    let energies: Vec<Option<[f32; N_BANDS]>> = starts
+        .par_iter()
+        .map(|&start| {
+            let segment = &samples[start..start + frame];
+            if segment.iter().map(|s| s * s).sum::<f32>() / (frame as f32) < SILENCE_POWER {
+                return None;
+            }
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(frame);
+            let mut input: Vec<f32> = segment.iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).ok()?;
+
+            let mut bands = [0.0f32; N_BANDS];
+            for (b, energy) in bands.iter_mut().enumerate() {
+                let hi = edges[b + 1].max(edges[b] + 1).min(spectrum.len());
+                *energy = spectrum[edges[b].min(hi - 1)..hi].iter().map(|c| c.norm_sqr()).sum();
+            }
+            Some(bands)
+        })
+        .collect();
+
+    let mut prints = vec![None; energies.len()];
+    for i in 1..energies.len() {
+        let (Some(prev), Some(cur)) = (&energies[i - 1], &energies[i]) else {
+            continue;
+        };
+        let mut bits = 0u32;
+        for m in 0..N_BANDS - 1 {
+            if (cur[m] - cur[m + 1]) - (prev[m] - prev[m + 1]) > 0.0 {
+                bits |= 1 << m;
+            }
+        }
+        prints[i] = Some(bits);
+    }
+    prints
+}
+
+/// Normalized correlation of two equal-length slices
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let (mut ab, mut aa, mut bb) = (0.0f64, 0.0f64, 0.0f64);
+    for (&x, &y) in a.iter().zip(b) {
+        ab += (x * y) as f64;
+        aa += (x * x) as f64;
+        bb += (y * y) as f64;
+    }
+    if aa <= 0.0 || bb <= 0.0 { 0.0 } else { (ab / (aa * bb).sqrt()) as f32 }
+}
+
+/// Best normalized autocorrelation of `x` beyond its first zero crossing,
+/// for lags up to `max_lag`: close to 1 for steady tones and other
+/// strictly periodic material
+fn periodicity(x: &[f32], max_lag: usize) -> f32 {
+    if x.len() <= 2 * max_lag {
+        return 0.0;
+    }
+    let len = x.len() - max_lag;
+    let mut past_zero = false;
+    let mut best = 0.0f32;
+    for lag in 1..=max_lag {
+        let r = correlation(&x[..len], &x[lag..lag + len]);
+        past_zero |= r <= 0.0;
+        if past_zero {
+            best = best.max(r);
+        }
+    }
+    best
+}
+
+/// Grow a verified copy around `seed` at offset `offset` (samples), block
+/// by block while the blocks correlate by at least `min_similarity`.
+/// Returns the source range.
+fn grow(samples: &[f32], seed: usize, offset: usize, block: usize, min_similarity: f32) -> Option<(usize, usize)> {
+    let matches = |start: usize| {
+        start + offset + block <= samples.len()
+            && correlation(&samples[start..start + block], &samples[start + offset..start + offset + block]) >= min_similarity
+    };
+    if !matches(seed) {
+        return None;
+    }
+    let mut start = seed;
+    while start >= block && matches(start - block) {
+        start -= block;
+    }
+    let mut end = seed + block;
+    while matches(end) {
+        end += block;
+    }
+    Some((start, end))
+}
+
+/// Find stretches of at least `min_duration` seconds that reappear later
+/// in the recording with a normalized correlation of `min_similarity` or
+/// more. Fingerprint hashes of short frames propose candidate offsets;
+/// each is refined to the sample and verified on the waveform.
+pub fn detect_duplicates(samples: &[f32], sample_rate: u32, min_duration: f32, min_similarity: f32) -> Vec<DuplicatePair> {
+    let sr = sample_rate as f32;
+    let (frame, hop) = frame_and_hop(sample_rate);
+    let block = ((BLOCK_SECS * sr) as usize).max(1);
+    let min_frames = ((min_duration * sr) as usize / hop).max(1);
+    if samples.len() < 2 * frame {
+        return Vec::new();
+    }
+
+    let prints = fingerprints(samples, sample_rate, frame, hop);
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, print) in prints.iter().enumerate() {
+        if let Some(bits) = print {
+            index.entry(*bits).or_default().push(i);
+        }
+    }
+
+    // Vote for offsets (in hops) between exact fingerprint matches
+    let mut votes: HashMap<usize, Vec<usize>> = HashMap::new();
+    for frames in index.values().filter(|f| f.len() > 1 && f.len() <= MAX_OCCURRENCES) {
+        for (k, &a) in frames.iter().enumerate() {
+            for &b in &frames[k + 1..] {
+                if b - a >= min_frames {
+                    votes.entry(b - a).or_default().push(a);
+                }
+            }
+        }
+    }
+    let mut candidates: Vec<(usize, Vec<usize>)> = votes.into_iter().filter(|(_, hits)| hits.len() >= MIN_HITS).collect();
+    candidates.sort_by_key(|(offset, hits)| (std::cmp::Reverse(hits.len()), *offset));
+
+    let mut pairs: Vec<(usize, usize, usize, f32)> = Vec::new(); // (start, end, offset, similarity) in samples
+    for (offset_hops, mut hits) in candidates {
+        hits.sort_unstable();
+        // Hits far apart at the same offset are separate copies
+        let mut clusters: Vec<&[usize]> = Vec::new();
+        let mut first = 0;
+        for k in 1..=hits.len() {
+            if k == hits.len() || hits[k] - hits[k - 1] > 2 * min_frames {
+                clusters.push(&hits[first..k]);
+                first = k;
+            }
+        }
+
+        for cluster in clusters.into_iter().filter(|c| c.len() >= MIN_HITS) {
+            let seed = cluster[cluster.len() / 2] * hop;
+            let coarse = offset_hops * hop;
+            let known = pairs.iter().any(|&(start, end, offset, _)| {
+                (start..end).contains(&seed) && offset.abs_diff(coarse) <= 2 * hop
+            });
+            if known {
+                continue;
+            }
+
+            // Refine the offset to the sample on a few frames around the seed
+            let len = (4 * frame).min(samples.len() - seed - coarse);
+            let (lag, _) = align::xcorr_lag(&samples[seed..seed + len], &samples[seed + coarse..seed + coarse + len], hop);
+            let offset = coarse as i64 + lag;
+            if offset < (min_frames * hop) as i64 || seed as i64 + offset + block as i64 > samples.len() as i64 {
+                continue;
+            }
+            let offset = offset as usize;
+
+            let Some((start, end)) = grow(samples, seed, offset, block, min_similarity) else {
+                continue;
+            };
+            if ((end - start) as f32) < min_duration * sr {
+                continue;
+            }
+            let similarity = correlation(&samples[start..end], &samples[start + offset..end + offset]);
+            // Periodic material matches itself at any multiple of its period;
+            // a copy has to match better than one period does
+            let excerpt = &samples[start..end.min(start + 4 * frame)];
+            if periodicity(excerpt, (MAX_PERIOD_SECS * sr) as usize) >= similarity - 0.01 {
+                continue;
+            }
+            pairs.push((start, end, offset, similarity));
+        }
+    }
+
+    pairs.sort_by_key(|&(start, _, offset, _)| (start, offset));
+    pairs
+        .into_iter()
+        .map(|(start, end, offset, similarity)| DuplicatePair {
+            source_start: start as f32 / sr,
+            target_start: (start + offset) as f32 / sr,
+            duration: (end - start) as f32 / sr,
+            offset: offset as f32 / sr,
+            similarity,
+        })
+        .collect()
+}
@@ -0,0 +1,715 @@
+//! Encoder identification from container headers, frame statistics and
+//! coded bandwidth
+
+use crate::compression::{self, CodecFamily};
+use serde::{Deserialize, Serialize};
+
+/// Measured bandwidth within this of LAME's lowpass counts as a match (Hz)
+const LOWPASS_TOLERANCE_HZ: f32 = 400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Container {
+    Mp3,
+    Adts,
+    Mp4,
+    Flac,
+    Wav,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncoderFamily {
+    Lame,
+    Fraunhofer,
+    Xing,
+    Ffmpeg,
+    Apple,
+    Nero,
+    Libflac,
+    Other,    // Named in the file but not one of the above
+    Unknown,
+}
+
+/// What the family verdict rests on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Basis {
+    Header,     // Encoder info header (LAME/Xing/VBRI tag, FLAC vendor string)
+    Metadata,   // Editable tags (ID3 TSSE, MP4 ©too, RIFF ISFT)
+    Heuristic,  // Frame statistics and coded bandwidth only
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BitrateMode {
+    Cbr,
+    Abr,
+    Vbr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataTag {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncoderFingerprint {
+    pub container: Container,
+    pub codec: Option<String>,            // "MPEG-1 Layer III", "AAC LC", "HE-AAC v2", "FLAC", "PCM"
+    pub family: EncoderFamily,
+    pub basis: Basis,
+    pub version: Option<String>,          // Encoder string as written, e.g. "LAME3.100"
+    pub bitrate_mode: Option<BitrateMode>,
+    pub bitrate_kbps: Option<u32>,        // Nominal (CBR) or average
+    pub lowpass_hz: Option<f32>,          // Encoder lowpass recorded in the LAME tag
+    pub encoder_delay: Option<u32>,       // Priming samples
+    pub end_padding: Option<u32>,         // Samples appended to fill the last frame
+    pub frames: usize,
+    pub padded_fraction: Option<f32>,     // MP3 frames with the padding bit set
+    pub tags: Vec<MetadataTag>,           // Encoder-related metadata
+    pub evidence: Vec<String>,            // Observations behind the verdict
+}
+
+impl EncoderFingerprint {
+    fn new(container: Container) -> Self {
+        EncoderFingerprint {
+            container,
+            codec: None,
+            family: EncoderFamily::Unknown,
+            basis: Basis::None,
+            version: None,
+            bitrate_mode: None,
+            bitrate_kbps: None,
+            lowpass_hz: None,
+            encoder_delay: None,
+            end_padding: None,
+            frames: 0,
+            padded_fraction: None,
+            tags: Vec::new(),
+            evidence: Vec::new(),
+        }
+    }
+
+    /// Record `family` unless stronger evidence already named one
+    fn attribute(&mut self, family: EncoderFamily, basis: Basis) {
+        let rank = |b: Basis| match b {
+            Basis::Header => 3,
+            Basis::Metadata => 2,
+            Basis::Heuristic => 1,
+            Basis::None => 0,
+        };
+        if rank(basis) > rank(self.basis) {
+            self.family = family;
+            self.basis = basis;
+        }
+    }
+
+    fn tag(&mut self, key: &str, value: String) {
+        self.tags.push(MetadataTag { key: key.to_string(), value });
+    }
+}
+
+fn be16(b: &[u8], i: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(b.get(i..i + 2)?.try_into().ok()?) as u32)
+}
+
+fn be32(b: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(i..i + 4)?.try_into().ok()?))
+}
+
+fn le32(b: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(i..i + 4)?.try_into().ok()?))
+}
+
+/// Printable text with trailing NULs and spaces removed
+fn text(b: &[u8]) -> String {
+    String::from_utf8_lossy(b).trim_end_matches(['\0', ' ']).to_string()
+}
+
+/// Family named by an encoder or software string
+fn family_of(name: &str) -> EncoderFamily {
+    let lower = name.to_ascii_lowercase();
+    if lower.starts_with("lavf") || lower.starts_with("lavc") || lower.contains("ffmpeg") {
+        EncoderFamily::Ffmpeg
+    } else if lower.contains("lame") {
+        EncoderFamily::Lame
+    } else if lower.contains("fraunhofer") || lower.contains("fhg") {
+        EncoderFamily::Fraunhofer
+    } else if lower.contains("itunes") || lower.contains("apple") || lower.contains("core media") {
+        EncoderFamily::Apple
+    } else if lower.contains("nero") {
+        EncoderFamily::Nero
+    } else if lower.contains("libflac") {
+        EncoderFamily::Libflac
+    } else {
+        EncoderFamily::Other
+    }
+}
+
+/// Identify the encoder of the file held in `bytes`. `decoded_rate` is the
+/// output sample rate of the decoder (reveals implicit HE-AAC) and
+/// `bandwidth_hz` the coded bandwidth measured on the decoded audio, if any.
+pub fn fingerprint(bytes: &[u8], decoded_rate: u32, bandwidth_hz: Option<f32>) -> EncoderFingerprint {
+    let mut fp = if bytes.starts_with(b"fLaC") {
+        flac(bytes)
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WAVE") {
+        wav(bytes)
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        mp4(bytes, decoded_rate)
+    } else {
+        let start = id3v2_len(bytes).min(bytes.len());
+        let body = &bytes[start..];
+        match (find_mp3_sync(body), find_adts_sync(body)) {
+            (Some(offset), _) => {
+                let mut fp = mp3(&body[offset..], bandwidth_hz);
+                id3v2_tags(&bytes[..start], &mut fp);
+                fp
+            }
+            (None, Some(offset)) => {
+                let mut fp = adts(&body[offset..], decoded_rate);
+                id3v2_tags(&bytes[..start], &mut fp);
+                fp
+            }
+            (None, None) => EncoderFingerprint::new(Container::Unknown),
+        }
+    };
+
+    // Family from editable tags when the stream itself names none
+    let named: Vec<String> = fp.tags.iter().map(|t| t.value.clone()).collect();
+    for value in named {
+        if fp.basis != Basis::Header {
+            fp.attribute(family_of(&value), Basis::Metadata);
+            fp.version.get_or_insert(value);
+        }
+    }
+    fp
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Mp3Header {
+    mpeg1: bool,
+    bitrate_kbps: u32,
+    sample_rate: u32,
+    padding: bool,
+    mono: bool,
+    joint_stereo: bool,
+    crc: bool,
+    frame_len: usize,
+}
+
+const MPEG1_L3_KBPS: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MPEG2_L3_KBPS: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// Layer III frame header at the start of `b` (free-format frames excluded)
+fn mp3_header(b: &[u8]) -> Option<Mp3Header> {
+    let h = be32(b, 0)?;
+    if h >> 21 != 0x7FF || (h >> 17) & 3 != 1 {
+        return None;
+    }
+    let version = (h >> 19) & 3; // 3 = MPEG-1, 2 = MPEG-2, 0 = MPEG-2.5
+    let bitrate_index = ((h >> 12) & 0xF) as usize;
+    let rate_index = ((h >> 10) & 3) as usize;
+    if version == 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+    let mpeg1 = version == 3;
+    let bitrate_kbps = if mpeg1 { MPEG1_L3_KBPS } else { MPEG2_L3_KBPS }[bitrate_index];
+    let sample_rate = [44100, 48000, 32000][rate_index] >> match version {
+        3 => 0,
+        2 => 1,
+        _ => 2,
+    };
+    let padding = (h >> 9) & 1 == 1;
+    let coefficient = if mpeg1 { 144 } else { 72 };
+    let mode = (h >> 6) & 3;
+    Some(Mp3Header {
+        mpeg1,
+        bitrate_kbps,
+        sample_rate,
+        padding,
+        mono: mode == 3,
+        joint_stereo: mode == 1,
+        crc: (h >> 16) & 1 == 0,
+        frame_len: (coefficient * bitrate_kbps * 1000 / sample_rate) as usize + padding as usize,
+    })
+}
+
+/// First offset holding two consecutive Layer III frames
+fn find_mp3_sync(b: &[u8]) -> Option<usize> {
+    (0..b.len().saturating_sub(4).min(1 << 16)).find(|&i| {
+        mp3_header(&b[i..]).is_some_and(|h| mp3_header(b.get(i + h.frame_len..).unwrap_or(&[])).is_some())
+    })
+}
+
+/// Size of a leading ID3v2 tag
+fn id3v2_len(b: &[u8]) -> usize {
+    if !b.starts_with(b"ID3") || b.len() < 10 {
+        return 0;
+    }
+    let size = b[6..10].iter().fold(0usize, |acc, &x| (acc << 7) | (x & 0x7F) as usize);
+    let footer = if b[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+/// Encoder-related text frames (TSSE, TENC) of an ID3v2.3/2.4 tag
+fn id3v2_tags(tag: &[u8], fp: &mut EncoderFingerprint) {
+    if tag.len() < 10 || tag[3] < 3 {
+        return;
+    }
+    let synchsafe = tag[3] >= 4;
+    let mut i = 10;
+    while i + 10 <= tag.len() && tag[i] != 0 {
+        let id = &tag[i..i + 4];
+        let size = if synchsafe {
+            tag[i + 4..i + 8].iter().fold(0usize, |acc, &x| (acc << 7) | (x & 0x7F) as usize)
+        } else {
+            be32(tag, i + 4).unwrap_or(0) as usize
+        };
+        let Some(body) = tag.get(i + 10..i + 10 + size) else {
+            break;
+        };
+        if (id == b"TSSE" || id == b"TENC") && !body.is_empty() {
+            let value = match body[0] {
+                1 | 2 => {
+                    // UTF-16 with BOM (1) or big-endian (2)
+                    let little = body[0] == 1 && body.get(1..3) == Some(&[0xFF, 0xFE]);
+                    let units: Vec<u16> = body[1..]
+                        .chunks_exact(2)
+                        .map(|c| if little { u16::from_le_bytes([c[0], c[1]]) } else { u16::from_be_bytes([c[0], c[1]]) })
+                        .filter(|&u| u != 0xFEFF)
+                        .collect();
+                    String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+                }
+                _ => text(&body[1..]),
+            };
+            if !value.is_empty() {
+                fp.tag(&String::from_utf8_lossy(id), value);
+            }
+        }
+        i += 10 + size;
+    }
+}
+
+fn mp3(b: &[u8], bandwidth_hz: Option<f32>) -> EncoderFingerprint {
+    let mut fp = EncoderFingerprint::new(Container::Mp3);
+    let Some(first) = mp3_header(b) else {
+        return fp;
+    };
+    fp.codec = Some(if first.mpeg1 { "MPEG-1 Layer III" } else { "MPEG-2 Layer III" }.to_string());
+
+    // Info headers live in the first frame, after the side information
+    let side_info = match (first.mpeg1, first.mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    let xing = 4 + side_info;
+    let mut skip_first = false;
+    match b.get(xing..xing + 4) {
+        Some(id) if id == b"Xing" || id == b"Info" => {
+            skip_first = true;
+            let flags = be32(b, xing + 4).unwrap_or(0);
+            let mut tag = xing + 8;
+            for (bit, len) in [(1, 4), (2, 4), (4, 100), (8, 4)] {
+                if flags & bit != 0 {
+                    tag += len;
+                }
+            }
+            fp.bitrate_mode = Some(if id == b"Info" { BitrateMode::Cbr } else { BitrateMode::Vbr });
+            lame_tag(b.get(tag..).unwrap_or(&[]), &mut fp);
+            if fp.basis == Basis::None {
+                fp.attribute(EncoderFamily::Xing, Basis::Header);
+                fp.evidence.push(format!("{} header without an encoder tag (Xing, or LAME before 3.90)", text(id)));
+            }
+        }
+        _ if b.get(36..40) == Some(b"VBRI") => {
+            skip_first = true;
+            fp.attribute(EncoderFamily::Fraunhofer, Basis::Header);
+            fp.bitrate_mode = Some(BitrateMode::Vbr);
+            fp.encoder_delay = be16(b, 36 + 6);
+            fp.evidence.push("VBRI header (Fraunhofer encoder)".to_string());
+        }
+        _ => {}
+    }
+
+    // Walk the frames
+    let mut bitrates: Vec<(u32, usize)> = Vec::new();
+    let (mut frames, mut padded, mut bytes, mut joint, mut crc) = (0usize, 0usize, 0usize, 0usize, 0usize);
+    let mut i = if skip_first { first.frame_len } else { 0 };
+    while let Some(h) = b.get(i..).and_then(mp3_header) {
+        match bitrates.iter_mut().find(|(kbps, _)| *kbps == h.bitrate_kbps) {
+            Some(entry) => entry.1 += 1,
+            None => bitrates.push((h.bitrate_kbps, 1)),
+        }
+        frames += 1;
+        padded += h.padding as usize;
+        joint += h.joint_stereo as usize;
+        crc += h.crc as usize;
+        bytes += h.frame_len;
+        i += h.frame_len;
+    }
+    fp.frames = frames;
+    if frames == 0 {
+        return fp;
+    }
+    let samples_per_frame = if first.mpeg1 { 1152 } else { 576 };
+    fp.padded_fraction = Some(padded as f32 / frames as f32);
+
+    if bitrates.len() == 1 {
+        fp.bitrate_mode.get_or_insert(BitrateMode::Cbr);
+        fp.bitrate_kbps = Some(bitrates[0].0);
+        // An exact-rate CBR stream pads the fractional part of 144·R/fs
+        let coefficient = if first.mpeg1 { 144.0 } else { 72.0 };
+        let exact = coefficient * bitrates[0].0 as f64 * 1000.0 / first.sample_rate as f64;
+        let expected = exact - exact.floor();
+        let actual = padded as f64 / frames as f64;
+        if (actual - expected).abs() > 0.05 {
+            fp.evidence.push(format!(
+                "Padding bit set in {:.0}% of frames, exact-rate encoders pad {:.0}%",
+                actual * 100.0,
+                expected * 100.0
+            ));
+        }
+    } else {
+        fp.bitrate_mode.get_or_insert(BitrateMode::Vbr);
+        fp.bitrate_kbps = Some((bytes as f64 * 8.0 * first.sample_rate as f64 / (frames * samples_per_frame) as f64 / 1000.0).round() as u32);
+    }
+    if !first.mono && joint == 0 {
+        fp.evidence.push("No joint-stereo frames (simple stereo throughout)".to_string());
+    }
+    if crc > 0 {
+        fp.evidence.push(format!("CRC protection in {} frames", crc));
+    }
+
+    // Coded bandwidth against LAME's default lowpass for this bitrate
+    let bitrate = fp.bitrate_kbps.unwrap_or(0);
+    let lame_lowpass = compression::typical_bandwidth(CodecFamily::Mp3, bitrate);
+    match (bandwidth_hz, fp.lowpass_hz, lame_lowpass) {
+        (Some(measured), Some(lowpass), _) if measured < lowpass - 2.0 * LOWPASS_TOLERANCE_HZ => {
+            fp.evidence.push(format!(
+                "Coded bandwidth {:.0} Hz is below the {:.0} Hz lowpass in the header: the input was already band-limited",
+                measured, lowpass
+            ));
+        }
+        (Some(measured), None, Some(lowpass)) if fp.bitrate_mode == Some(BitrateMode::Cbr) => {
+            if (measured - lowpass).abs() <= LOWPASS_TOLERANCE_HZ {
+                fp.attribute(EncoderFamily::Lame, Basis::Heuristic);
+                fp.evidence.push(format!("Coded bandwidth {:.0} Hz matches LAME's lowpass at {} kbps", measured, bitrate));
+            } else {
+                fp.evidence.push(format!(
+                    "Coded bandwidth {:.0} Hz differs from LAME's {:.0} Hz lowpass at {} kbps",
+                    measured, lowpass, bitrate
+                ));
+            }
+        }
+        _ => {}
+    }
+    fp
+}
+
+/// LAME/FFmpeg encoder tag following the Xing/Info fields
+fn lame_tag(tag: &[u8], fp: &mut EncoderFingerprint) {
+    let Some(name) = tag.get(..9) else {
+        return;
+    };
+    if !name.iter().take(4).all(|c| c.is_ascii_alphanumeric()) {
+        return;
+    }
+    let version = text(name);
+    let family = family_of(&version);
+    if !matches!(family, EncoderFamily::Lame | EncoderFamily::Ffmpeg) || tag.len() < 24 {
+        return;
+    }
+    fp.attribute(family, Basis::Header);
+    fp.evidence.push(format!("Encoder tag \"{}\"", version));
+    fp.version = Some(version);
+
+    fp.bitrate_mode = Some(match tag[9] & 0x0F {
+        1 | 8 => BitrateMode::Cbr,
+        2 | 9 => BitrateMode::Abr,
+        _ => BitrateMode::Vbr,
+    });
+    if tag[10] > 0 {
+        fp.lowpass_hz = Some(tag[10] as f32 * 100.0);
+    }
+    fp.encoder_delay = Some(((tag[21] as u32) << 4) | (tag[22] as u32 >> 4));
+    fp.end_padding = Some(((tag[22] as u32 & 0x0F) << 8) | tag[23] as u32);
+}
+
+const AAC_RATES: [u32; 13] = [96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350];
+
+fn aac_profile_name(object_type: u32) -> String {
+    match object_type {
+        1 => "AAC Main".to_string(),
+        2 => "AAC LC".to_string(),
+        3 => "AAC SSR".to_string(),
+        4 => "AAC LTP".to_string(),
+        5 => "HE-AAC".to_string(),
+        29 => "HE-AAC v2".to_string(),
+        23 => "AAC LD".to_string(),
+        39 => "AAC ELD".to_string(),
+        other => format!("MPEG-4 audio object type {}", other),
+    }
+}
+
+/// ADTS header: (object type, sample rate, frame length, VBR buffer fullness)
+fn adts_header(b: &[u8]) -> Option<(u32, u32, usize, bool)> {
+    let h = b.get(..7)?;
+    if h[0] != 0xFF || h[1] & 0xF6 != 0xF0 {
+        return None;
+    }
+    let object_type = (h[2] >> 6) as u32 + 1;
+    let sample_rate = *AAC_RATES.get(((h[2] >> 2) & 0x0F) as usize)?;
+    let len = (((h[3] & 3) as usize) << 11) | ((h[4] as usize) << 3) | (h[5] >> 5) as usize;
+    let fullness = (((h[5] & 0x1F) as u32) << 6) | (h[6] >> 2) as u32;
+    (len >= 7).then_some((object_type, sample_rate, len, fullness == 0x7FF))
+}
+
+fn find_adts_sync(b: &[u8]) -> Option<usize> {
+    (0..b.len().saturating_sub(7).min(1 << 16)).find(|&i| {
+        adts_header(&b[i..]).is_some_and(|(_, _, len, _)| adts_header(b.get(i + len..).unwrap_or(&[])).is_some())
+    })
+}
+
+fn adts(b: &[u8], decoded_rate: u32) -> EncoderFingerprint {
+    let mut fp = EncoderFingerprint::new(Container::Adts);
+    let Some((object_type, sample_rate, _, vbr)) = adts_header(b) else {
+        return fp;
+    };
+    let (mut i, mut bytes) = (0, 0);
+    while let Some((_, _, len, _)) = b.get(i..).and_then(adts_header) {
+        fp.frames += 1;
+        bytes += len;
+        i += len;
+    }
+    // SBR doubles the output rate without signalling it in ADTS
+    let sbr = decoded_rate == 2 * sample_rate;
+    fp.codec = Some(aac_profile_name(if sbr { 5 } else { object_type }));
+    if sbr {
+        fp.evidence.push(format!("Decoder output rate is twice the {} Hz core rate (implicit SBR)", sample_rate));
+    }
+    fp.bitrate_mode = Some(if vbr { BitrateMode::Vbr } else { BitrateMode::Cbr });
+    let frames_secs = fp.frames as f64 * 1024.0 / sample_rate as f64;
+    if frames_secs > 0.0 {
+        fp.bitrate_kbps = Some((bytes as f64 * 8.0 / frames_secs / 1000.0).round() as u32);
+    }
+    fp
+}
+
+/// Length of an MPEG-4 descriptor size field and the size it holds
+fn descriptor_len(b: &[u8], mut i: usize) -> Option<(usize, usize)> {
+    let mut len = 0;
+    for _ in 0..4 {
+        let byte = *b.get(i)?;
+        len = (len << 7) | (byte & 0x7F) as usize;
+        i += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Some((i, len))
+}
+
+/// Object type and sample rate from the AudioSpecificConfig in an esds box
+fn esds_config(esds: &[u8]) -> Option<(u32, u32, u32)> {
+    // version/flags, then ES_Descriptor (0x03)
+    let mut i = 4;
+    if *esds.get(i)? != 0x03 {
+        return None;
+    }
+    let (next, _) = descriptor_len(esds, i + 1)?;
+    let flags = *esds.get(next + 2)?;
+    i = next + 3;
+    if flags & 0x80 != 0 {
+        i += 2;
+    }
+    if flags & 0x40 != 0 {
+        i += 1 + *esds.get(i)? as usize;
+    }
+    if flags & 0x20 != 0 {
+        i += 2;
+    }
+    // DecoderConfigDescriptor (0x04): average bitrate, then DecoderSpecificInfo (0x05)
+    if *esds.get(i)? != 0x04 {
+        return None;
+    }
+    let (next, _) = descriptor_len(esds, i + 1)?;
+    let avg_bitrate = be32(esds, next + 9)?;
+    i = next + 13;
+    if *esds.get(i)? != 0x05 {
+        return None;
+    }
+    let (config, _) = descriptor_len(esds, i + 1)?;
+    let bits = be16(esds, config)?;
+    let mut object_type = bits >> 11;
+    let rate_index = (bits >> 7) & 0x0F;
+    if object_type == 31 {
+        object_type = 32 + ((bits >> 5) & 0x3F);
+    }
+    let rate = AAC_RATES.get(rate_index as usize).copied().unwrap_or(0);
+    Some((object_type, rate, avg_bitrate))
+}
+
+/// Walk MP4 boxes, collecting the audio config and encoder strings
+fn mp4_boxes(b: &[u8], fp: &mut EncoderFingerprint, decoded_rate: u32, depth: usize) {
+    let mut i = 0;
+    while i + 8 <= b.len() && depth < 8 {
+        let size = be32(b, i).unwrap_or(0) as usize;
+        let kind = &b[i + 4..i + 8];
+        let size = if size == 0 { b.len() - i } else { size };
+        if size < 8 || i + size > b.len() {
+            break;
+        }
+        let body = &b[i + 8..i + size];
+        match kind {
+            b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" | b"udta" | b"ilst" => mp4_boxes(body, fp, decoded_rate, depth + 1),
+            b"meta" => mp4_boxes(body.get(4..).unwrap_or(&[]), fp, decoded_rate, depth + 1),
+            b"stsd" => mp4_boxes(body.get(8..).unwrap_or(&[]), fp, decoded_rate, depth + 1),
+            b"mp4a" => {
+                // Audio sample entry; QuickTime versions 1 and 2 extend it
+                let extra = match be16(body, 8) {
+                    Some(1) => 16,
+                    Some(2) => 36,
+                    _ => 0,
+                };
+                mp4_boxes(body.get(28 + extra..).unwrap_or(&[]), fp, decoded_rate, depth + 1)
+            }
+            b"esds" => {
+                if let Some((object_type, rate, avg_bitrate)) = esds_config(body) {
+                    let sbr = object_type == 2 && decoded_rate == 2 * rate;
+                    fp.codec = Some(aac_profile_name(if sbr { 5 } else { object_type }));
+                    if avg_bitrate > 0 {
+                        fp.bitrate_kbps = Some((avg_bitrate as f32 / 1000.0).round() as u32);
+                    }
+                }
+            }
+            b"alac" => fp.codec = Some("ALAC".to_string()),
+            [0xA9, b't', b'o', b'o'] => {
+                // ©too holds a data box: size, "data", type, locale, value
+                if let Some(value) = body.get(16..) {
+                    fp.tag("©too", text(value));
+                }
+            }
+            b"----" => {
+                // Freeform iTunes item; its name box follows the mean box
+                let mean_len = be32(body, 0).unwrap_or(0) as usize;
+                if let Some(name) = body.get(mean_len + 12..mean_len + be32(body, mean_len).unwrap_or(0) as usize) {
+                    let name = text(name);
+                    if name == "iTunSMPB" || name == "Encoding Params" {
+                        fp.attribute(EncoderFamily::Apple, Basis::Metadata);
+                        fp.evidence.push(format!("Apple freeform atom \"{}\"", name));
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += size;
+    }
+}
+
+fn mp4(b: &[u8], decoded_rate: u32) -> EncoderFingerprint {
+    let mut fp = EncoderFingerprint::new(Container::Mp4);
+    if let Some(brand) = b.get(8..12) {
+        fp.evidence.push(format!("MP4 brand \"{}\"", text(brand)));
+    }
+    mp4_boxes(b, &mut fp, decoded_rate, 0);
+    fp
+}
+
+fn flac(b: &[u8]) -> EncoderFingerprint {
+    let mut fp = EncoderFingerprint::new(Container::Flac);
+    fp.codec = Some("FLAC".to_string());
+    let mut i = 4;
+    while i + 4 <= b.len() {
+        let header = b[i];
+        let len = (be32(b, i).unwrap_or(0) & 0x00FF_FFFF) as usize;
+        let Some(body) = b.get(i + 4..i + 4 + len) else {
+            break;
+        };
+        if header & 0x7F == 4 {
+            // VORBIS_COMMENT: vendor string, then KEY=value comments
+            let vendor_len = le32(body, 0).unwrap_or(0) as usize;
+            if let Some(vendor) = body.get(4..4 + vendor_len) {
+                let vendor = text(vendor);
+                fp.attribute(family_of(&vendor), Basis::Header);
+                fp.evidence.push(format!("FLAC vendor string \"{}\"", vendor));
+                fp.version = Some(vendor);
+            }
+            let mut j = 8 + vendor_len;
+            for _ in 0..le32(body, 4 + vendor_len).unwrap_or(0) {
+                let Some(raw) = le32(body, j).and_then(|n| body.get(j + 4..j + 4 + n as usize)) else {
+                    break;
+                };
+                let comment = text(raw);
+                if let Some((key, value)) = comment.split_once('=') {
+                    if matches!(key.to_ascii_uppercase().as_str(), "ENCODER" | "ENCODED-BY" | "ENCODED_BY" | "ENCODING") {
+                        fp.tag(key, value.to_string());
+                    }
+                }
+                j += 4 + raw.len();
+            }
+        }
+        if header & 0x80 != 0 {
+            break; // Last metadata block
+        }
+        i += 4 + len;
+    }
+    fp
+}
+
+fn wav(b: &[u8]) -> EncoderFingerprint {
+    let mut fp = EncoderFingerprint::new(Container::Wav);
+    let mut i = 12;
+    while i + 8 <= b.len() {
+        let kind = &b[i..i + 4];
+        let len = le32(b, i + 4).unwrap_or(0) as usize;
+        let body = &b[i + 8..(i + 8 + len).min(b.len())];
+        match kind {
+            b"fmt " if body.len() >= 2 => {
+                let format = u16::from_le_bytes([body[0], body[1]]);
+                fp.codec = Some(match format {
+                    1 => "PCM".to_string(),
+                    3 => "IEEE float".to_string(),
+                    0x11 => "IMA ADPCM".to_string(),
+                    0x55 => "MPEG Layer III".to_string(),
+                    0xFFFE => "PCM (extensible)".to_string(),
+                    other => format!("WAVE format 0x{:04X}", other),
+                });
+            }
+            b"LIST" if body.starts_with(b"INFO") => {
+                let mut j = 4;
+                while j + 8 <= body.len() {
+                    let sub_len = le32(body, j + 4).unwrap_or(0) as usize;
+                    let value = text(&body[j + 8..(j + 8 + sub_len).min(body.len())]);
+                    if &body[j..j + 4] == b"ISFT" && !value.is_empty() {
+                        fp.tag("ISFT", value);
+                    }
+                    j += 8 + sub_len + (sub_len & 1);
+                }
+            }
+            b"bext" if body.len() >= 602 => {
+                // Broadcast WAV: originator (the recorder or its software) and coding history
+                let originator = text(&body[256..288]);
+                if !originator.is_empty() {
+                    fp.attribute(EncoderFamily::Other, Basis::Header);
+                    fp.evidence.push(format!("BWF originator \"{}\"", originator));
+                    fp.version = Some(originator);
+                }
+                let history = text(&body[602..]);
+                if !history.is_empty() {
+                    fp.evidence.push(format!("BWF coding history: {}", history.trim()));
+                }
+            }
+            _ => {}
+        }
+        if kind == b"data" && i + 8 + len > b.len() {
+            break;
+        }
+        i += 8 + len + (len & 1);
+    }
+    fp
+}
@@ -0,0 +1,445 @@
+//! Electric network frequency (mains hum) analysis on a narrowband STFT
+
+use crate::filters;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Nominal mains frequencies: 50 Hz (Europe/Asia/Africa), 60 Hz (Americas)
+pub const GRID_FREQS: [f32; 2] = [50.0, 60.0];
+
+/// Zero padding factor, for a finer bin grid under the peak interpolation
+const PAD: usize = 2;
+
+/// Deviation searched either side of the nominal fundamental (Hz)
+pub const SEARCH_HZ: f32 = 0.5;
+/// Half-width of the band the hum peak is compared against (Hz, per harmonic)
+const NOISE_BAND_HZ: f32 = 5.0;
+
+/// Harmonics examined (100/150/200 Hz or 120/180/240 Hz above the fundamental)
+const MAX_HARMONIC: usize = 4;
+/// Median peak-over-background strength for a harmonic to count as hum
+/// (the largest of a band of noise bins alone sits 5-7 dB over the median)
+const PRESENT_DB: f32 = 10.0;
+
+/// Demodulation frame and hop for the phase analysis (seconds)
+const PHASE_FRAME_SECS: f32 = 0.5;
+const PHASE_HOP_SECS: f32 = 0.25;
+/// Phase steps are judged against the median step of this many neighbours
+const PHASE_CONTEXT: usize = 16;
+
+/// Settings of the ENF analysis STFT, independent of the display spectrogram
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnfParams {
+    pub analysis_rate: u32,  // Approximate rate after decimation (Hz)
+    pub frame_length: f32,   // Seconds
+    pub hop_length: f32,     // Seconds
+}
+
+impl Default for EnfParams {
+    fn default() -> Self {
+        Self {
+            analysis_rate: 1000,
+            frame_length: 8.0,
+            hop_length: 1.0,
+        }
+    }
+}
+
+impl EnfParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(200..=8000).contains(&self.analysis_rate) {
+            return Err("Analysis rate must be between 200 and 8000 Hz".to_string());
+        }
+        if !(0.5..=60.0).contains(&self.frame_length) {
+            return Err("Frame length must be between 0.5 and 60 seconds".to_string());
+        }
+        if !(self.hop_length > 0.0 && self.hop_length <= self.frame_length) {
+            return Err("Hop length must be positive and at most the frame length".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Power spectra of the decimated signal
+pub struct NarrowbandStft {
+    pub signal: Vec<f32>,      // Decimated signal the spectra were taken from
+    pub rate: f32,
+    pub n_fft: usize,
+    pub times: Vec<f32>,       // Frame centers
+    pub power: Vec<Vec<f32>>,  // Frames × bins, up to the decimation filter's passband edge
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnfTrack {
+    pub grid_freq: f32,
+    pub harmonics: Vec<usize>,  // Harmonics the trajectory was measured on
+    pub times: Vec<f32>,
+    pub freqs: Vec<f32>,        // Estimated mains frequency (fundamental, Hz)
+    pub strength_db: Vec<f32>,  // Peak over the local spectrum median
+}
+
+/// Lowpass and downsample to about `target_rate`, returning the new rate
+pub fn decimate(samples: &[f32], sample_rate: u32, target_rate: u32) -> (Vec<f32>, f32) {
+    let factor = (sample_rate / target_rate).max(1) as usize;
+    let rate = sample_rate as f32 / factor as f32;
+    if factor == 1 {
+        return (samples.to_vec(), rate);
+    }
+    let mut lowpass = filters::butterworth_lowpass(0.4 * rate as f64, 8, sample_rate as f64);
+    let filtered = lowpass.process_buffer(samples);
+    (filtered.iter().step_by(factor).copied().collect(), rate)
+}
+
+/// Long-window, zero-padded STFT of the decimated signal
+pub fn narrowband_stft(samples: &[f32], sample_rate: u32, params: &EnfParams) -> NarrowbandStft {
+    let (decimated, rate) = decimate(samples, sample_rate, params.analysis_rate);
+    let frame = ((params.frame_length * rate) as usize).max(1);
+    let hop = ((params.hop_length * rate) as usize).max(1);
+    let n_fft = (frame * PAD).next_power_of_two();
+    let window = WindowType::Hann.coefficients(frame);
+    // Bins above the decimation filter's cutoff are never searched
+    let max_bin = ((0.4 * n_fft as f32) as usize).min(n_fft / 2 + 1);
+
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * hop)
+        .take_while(|&start| start + frame <= decimated.len())
+        .collect();
+
+    let (times, power) = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(n_fft);
+            let mut input = vec![0.0f32; n_fft];
+            for (i, (&s, &w)) in decimated[start..start + frame].iter().zip(&window).enumerate() {
+                input[i] = s * w;
+            }
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let power = spectrum[..max_bin].iter().map(|c| c.norm_sqr()).collect::<Vec<f32>>();
+            ((start + frame / 2) as f32 / rate, power)
+        })
+        .unzip();
+
+    NarrowbandStft { signal: decimated, rate, n_fft, times, power }
+}
+
+/// Interpolated peak (bin offset, power) in `power[lo..=hi]` using a
+/// parabola through the log power of the peak and its neighbours
+pub fn interpolated_peak(power: &[f32], lo: usize, hi: usize) -> (f32, f32) {
+    let peak = (lo..=hi).max_by(|&a, &b| power[a].total_cmp(&power[b])).unwrap_or(lo);
+    if peak == 0 || peak + 1 >= power.len() {
+        return (peak as f32, power[peak]);
+    }
+    let (a, b, c) = (
+        power[peak - 1].max(1e-30).ln(),
+        power[peak].max(1e-30).ln(),
+        power[peak + 1].max(1e-30).ln(),
+    );
+    let denom = a - 2.0 * b + c;
+    let offset = if denom.abs() > 1e-12 { (0.5 * (a - c) / denom).clamp(-0.5, 0.5) } else { 0.0 };
+    (peak as f32 + offset, (b - 0.25 * (a - c) * offset).exp())
+}
+
+/// Mains frequency trajectory measured on `harmonic` of `grid_freq`,
+/// scaled back to the fundamental. `None` when that harmonic's band does
+/// not fit below the analysis Nyquist.
+pub fn track(stft: &NarrowbandStft, grid_freq: f32, harmonic: usize) -> Option<EnfTrack> {
+    let h = harmonic.max(1) as f32;
+    let bin_hz = stft.rate / stft.n_fft as f32;
+    let center = grid_freq * h;
+    if grid_freq <= NOISE_BAND_HZ || center + NOISE_BAND_HZ * h >= 0.4 * stft.rate {
+        return None;
+    }
+    let n_bins = stft.power.first().map(Vec::len).unwrap_or(0).max(1);
+    let bin = |f: f32| ((f / bin_hz).round() as usize).min(n_bins - 1);
+    let (lo, hi) = (bin(center - SEARCH_HZ * h), bin(center + SEARCH_HZ * h));
+    let (noise_lo, noise_hi) = (bin(center - NOISE_BAND_HZ * h), bin(center + NOISE_BAND_HZ * h));
+
+    let (freqs, strength_db) = stft
+        .power
+        .iter()
+        .map(|frame| {
+            let (peak_bin, peak_power) = interpolated_peak(frame, lo, hi);
+            let mut band = frame[noise_lo..=noise_hi].to_vec();
+            band.sort_by(f32::total_cmp);
+            let median = band[band.len() / 2].max(1e-30);
+            (peak_bin * bin_hz / h, 10.0 * (peak_power.max(1e-30) / median).log10())
+        })
+        .unzip();
+
+    Some(EnfTrack {
+        grid_freq,
+        harmonics: vec![harmonic.max(1)],
+        times: stft.times.clone(),
+        freqs,
+        strength_db,
+    })
+}
+
+impl EnfTrack {
+    /// Median strength over the whole trajectory (dB)
+    pub fn median_strength_db(&self) -> f32 {
+        let mut s = self.strength_db.clone();
+        s.sort_by(f32::total_cmp);
+        s.get(s.len() / 2).copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarmonicEvidence {
+    pub order: usize,
+    pub freq: f32,
+    pub strength_db: f32,   // Median peak over the local background
+    pub present: bool,
+}
+
+pub struct EnfDetection {
+    pub grid_freq: f32,
+    pub strength_db: f32,   // Strongest harmonic
+    pub harmonics: Vec<HarmonicEvidence>,
+    pub track: EnfTrack,    // Combined over the harmonics carrying hum
+}
+
+impl EnfDetection {
+    /// Harmonic with the most hum, the best one for phase measurements
+    pub fn strongest_harmonic(&self) -> usize {
+        self.harmonics
+            .iter()
+            .max_by(|a, b| a.strength_db.total_cmp(&b.strength_db))
+            .map(|e| e.order)
+            .unwrap_or(1)
+    }
+}
+
+/// Combine per-harmonic trajectories frame by frame, weighting each
+/// harmonic's frequency estimate by its linear strength
+pub fn combine(tracks: &[EnfTrack]) -> Option<EnfTrack> {
+    let first = tracks.first()?;
+    let (freqs, strength_db) = (0..first.times.len())
+        .map(|k| {
+            let (mut sum, mut weight, mut strongest) = (0.0f32, 0.0f32, f32::MIN);
+            for t in tracks {
+                let w = 10f32.powf(t.strength_db[k] / 10.0);
+                sum += w * t.freqs[k];
+                weight += w;
+                strongest = strongest.max(t.strength_db[k]);
+            }
+            (sum / weight, strongest)
+        })
+        .unzip();
+
+    Some(EnfTrack {
+        grid_freq: first.grid_freq,
+        harmonics: tracks.iter().flat_map(|t| t.harmonics.iter().copied()).collect(),
+        times: first.times.clone(),
+        freqs,
+        strength_db,
+    })
+}
+
+/// Evaluate the fundamental and harmonics of `grid_freq`. The track combines
+/// the harmonics carrying hum, or is the strongest one's when none does.
+pub fn analyze_grid(stft: &NarrowbandStft, grid_freq: f32) -> Option<EnfDetection> {
+    let tracks: Vec<EnfTrack> = (1..=MAX_HARMONIC).filter_map(|h| track(stft, grid_freq, h)).collect();
+    let harmonics: Vec<HarmonicEvidence> = tracks
+        .iter()
+        .map(|t| {
+            let strength_db = t.median_strength_db();
+            HarmonicEvidence {
+                order: t.harmonics[0],
+                freq: grid_freq * t.harmonics[0] as f32,
+                strength_db,
+                present: strength_db > PRESENT_DB,
+            }
+        })
+        .collect();
+    let strongest = harmonics.iter().map(|e| e.strength_db).fold(f32::MIN, f32::max);
+
+    let selected: Vec<EnfTrack> = tracks
+        .into_iter()
+        .zip(&harmonics)
+        .filter(|(_, e)| e.present || (e.strength_db == strongest && !harmonics.iter().any(|e| e.present)))
+        .map(|(t, _)| t)
+        .collect();
+    let track = combine(&selected)?;
+    Some(EnfDetection { grid_freq, strength_db: strongest, harmonics, track })
+}
+
+/// Look for hum on the fundamental and harmonics of both grid frequencies,
+/// choosing the grid with the most combined evidence
+pub fn detect(stft: &NarrowbandStft) -> Option<EnfDetection> {
+    GRID_FREQS
+        .iter()
+        .filter_map(|&grid_freq| analyze_grid(stft, grid_freq))
+        .map(|d| {
+            let evidence: f32 = d.harmonics.iter().filter(|e| e.present).map(|e| 10f32.powf(e.strength_db / 10.0)).sum();
+            (evidence, d)
+        })
+        .filter(|(evidence, _)| *evidence > 0.0)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, detection)| detection)
+}
+
+/// The detected grid, or when no hum passes the presence test, whichever
+/// grid analysis came out strongest
+pub fn most_likely_grid(stft: &NarrowbandStft) -> Option<EnfDetection> {
+    detect(stft).or_else(|| {
+        GRID_FREQS
+            .iter()
+            .filter_map(|&f| analyze_grid(stft, f))
+            .max_by(|a, b| a.strength_db.total_cmp(&b.strength_db))
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseJump {
+    pub time: f32,
+    pub jump_deg: f32,      // Step on the analyzed harmonic, beyond the local drift
+    pub harmonic: usize,
+}
+
+/// Abrupt steps in the phase of the hum at `harmonic` of `grid_freq`.
+///
+/// The harmonic is demodulated to baseband in overlapping frames; each
+/// frame-to-frame phase advance is compared with the median advance of its
+/// neighbours, which absorbs the slow drift of the grid frequency. Advances
+/// deviating by more than `threshold_deg` (raised to four times the phase
+/// noise when the hum is weak) are reported, skipping frames where the hum
+/// drops out and its phase is meaningless.
+pub fn phase_jumps(stft: &NarrowbandStft, grid_freq: f32, harmonic: usize, threshold_deg: f32) -> Vec<PhaseJump> {
+    use std::f32::consts::PI;
+    let freq = grid_freq * harmonic.max(1) as f32;
+    let frame = (PHASE_FRAME_SECS * stft.rate) as usize;
+    let hop = ((PHASE_HOP_SECS * stft.rate) as usize).max(1);
+    if frame == 0 || stft.signal.len() < frame || freq >= stft.rate / 2.0 {
+        return Vec::new();
+    }
+    let window = WindowType::Hann.coefficients(frame);
+
+    let phasors: Vec<(f32, f32)> = (0..)
+        .map(|i| i * hop)
+        .take_while(|&start| start + frame <= stft.signal.len())
+        .map(|start| {
+            let (mut re, mut im) = (0.0f64, 0.0f64);
+            for (i, (&s, &w)) in stft.signal[start..start + frame].iter().zip(&window).enumerate() {
+                let arg = -2.0 * std::f64::consts::PI * freq as f64 * (start + i) as f64 / stft.rate as f64;
+                re += (s * w) as f64 * arg.cos();
+                im += (s * w) as f64 * arg.sin();
+            }
+            (re.hypot(im) as f32, im.atan2(re) as f32)
+        })
+        .collect();
+    if phasors.len() < 3 {
+        return Vec::new();
+    }
+
+    let mut amplitudes: Vec<f32> = phasors.iter().map(|p| p.0).collect();
+    amplitudes.sort_by(f32::total_cmp);
+    let reliable = amplitudes[amplitudes.len() / 2] * 0.3;
+
+    let wrap = |x: f32| (x + PI).rem_euclid(2.0 * PI) - PI;
+    // Step k lies between frames k and k + 1
+    let steps: Vec<Option<f32>> = phasors
+        .windows(2)
+        .map(|w| (w[0].0 > reliable && w[1].0 > reliable).then(|| wrap(w[1].1 - w[0].1)))
+        .collect();
+
+    let deviations: Vec<Option<f32>> = steps
+        .iter()
+        .enumerate()
+        .map(|(k, step)| {
+            let step = (*step)?;
+            let lo = k.saturating_sub(PHASE_CONTEXT / 2);
+            let hi = (k + PHASE_CONTEXT / 2 + 1).min(steps.len());
+            let mut context: Vec<f32> = steps[lo..hi].iter().flatten().copied().collect();
+            context.sort_by(f32::total_cmp);
+            Some(wrap(step - context[context.len() / 2]))
+        })
+        .collect();
+
+    // Overlapping frames spread a step over two consecutive advances, so
+    // each pair is judged together
+    let pairs: Vec<Option<f32>> = (0..deviations.len())
+        .map(|k| Some(wrap(deviations[k]? + deviations.get(k + 1).copied().flatten().unwrap_or(0.0))))
+        .collect();
+
+    // Never flag within the phase noise: 4 sigma, estimated robustly from the MAD
+    let mut magnitudes: Vec<f32> = pairs.iter().flatten().map(|d| d.abs()).collect();
+    magnitudes.sort_by(f32::total_cmp);
+    let sigma = 1.4826 * magnitudes.get(magnitudes.len() / 2).copied().unwrap_or(0.0);
+    let threshold = threshold_deg.to_radians().max(4.0 * sigma);
+
+    let mut jumps: Vec<PhaseJump> = Vec::new();
+    let mut last_flagged = None;
+    for (k, deviation) in pairs.iter().enumerate() {
+        let Some(deviation) = *deviation else { continue };
+        if deviation.abs() <= threshold {
+            continue;
+        }
+
+        let jump = PhaseJump {
+            time: ((k + 1) * hop + frame / 2) as f32 / stft.rate,
+            jump_deg: deviation.to_degrees(),
+            harmonic: harmonic.max(1),
+        };
+        // Neighbouring pairs share an advance; keep the larger
+        match jumps.last_mut() {
+            Some(prev) if last_flagged == Some(k - 1) => {
+                if jump.jump_deg.abs() > prev.jump_deg.abs() {
+                    *prev = jump;
+                }
+            }
+            _ => jumps.push(jump),
+        }
+        last_flagged = Some(k);
+    }
+    jumps
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnfSegment {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub strength_db: f32,   // Median hum strength on the best harmonic
+    pub harmonic: usize,
+    pub present: bool,
+}
+
+/// Hum presence per block of `block_secs`, judged on whichever harmonic of
+/// `grid_freq` is strongest within each block
+pub fn segment_confidence(stft: &NarrowbandStft, grid_freq: f32, block_secs: f32) -> Vec<EnfSegment> {
+    let tracks: Vec<EnfTrack> = (1..=MAX_HARMONIC).filter_map(|h| track(stft, grid_freq, h)).collect();
+    let duration = stft.signal.len() as f32 / stft.rate;
+    let block = block_secs.max(0.1);
+
+    (0..)
+        .map(|i| i as f32 * block)
+        .take_while(|&start| start < duration)
+        .filter_map(|start| {
+            let end = (start + block).min(duration);
+            let frames: Vec<usize> = (0..stft.times.len())
+                .filter(|&k| (start..end).contains(&stft.times[k]))
+                .collect();
+            let (harmonic, strength_db) = tracks
+                .iter()
+                .map(|t| {
+                    let mut s: Vec<f32> = frames.iter().map(|&k| t.strength_db[k]).collect();
+                    s.sort_by(f32::total_cmp);
+                    (t.harmonics[0], s.get(s.len() / 2).copied())
+                })
+                .filter_map(|(h, s)| Some((h, s?)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))?;
+            Some(EnfSegment {
+                start_time: start,
+                end_time: end,
+                strength_db,
+                harmonic,
+                present: strength_db > PRESENT_DB,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! Parametric equalizer: a chain of biquad bands (RBJ cookbook designs)
+
+use crate::filters::{Biquad, FilterChain};
+use serde::Deserialize;
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::f64::consts::PI;
+
+/// Ranges a band's parameters must fall in
+const Q_RANGE: (f32, f32) = (0.1, 50.0);
+const GAIN_RANGE_DB: f32 = 36.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EqKind {
+    HighPass,
+    LowPass,
+    /// Bell boosting or cutting `gain_db` around the frequency
+    Peaking,
+    /// Boost or cut of `gain_db` below (low) or above (high) the frequency
+    LowShelf,
+    HighShelf,
+}
+
+/// One band of the equalizer
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct EqBand {
+    pub kind: EqKind,
+    pub frequency_hz: f32,
+    /// Resonance (passes), bandwidth (peaking, frequency / Q) or slope
+    /// (shelves); 0.707 by default, a Butterworth pass or a steepest
+    /// unpeaked shelf
+    #[serde(default)]
+    pub q: Option<f32>,
+    /// Peaking and shelves: boost (positive) or cut (dB)
+    #[serde(default)]
+    pub gain_db: f32,
+}
+
+impl EqBand {
+    pub fn validate(&self, sample_rate: u32) -> Result<(), String> {
+        let nyquist = sample_rate as f32 / 2.0;
+        if !(self.frequency_hz > 0.0 && self.frequency_hz < nyquist) {
+            return Err(format!("EQ frequencies must be between 0 and {} Hz", nyquist));
+        }
+        if !(Q_RANGE.0..=Q_RANGE.1).contains(&self.q.unwrap_or(FRAC_1_SQRT_2)) {
+            return Err(format!("EQ Q must be between {} and {}", Q_RANGE.0, Q_RANGE.1));
+        }
+        if !(-GAIN_RANGE_DB..=GAIN_RANGE_DB).contains(&self.gain_db) {
+            return Err(format!("EQ gain must be between -{0} and {0} dB", GAIN_RANGE_DB));
+        }
+        Ok(())
+    }
+
+    /// The band's biquad at `sample_rate`
+    pub fn design(&self, sample_rate: u32) -> Biquad {
+        let w0 = 2.0 * PI * self.frequency_hz as f64 / sample_rate as f64;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * self.q.unwrap_or(FRAC_1_SQRT_2) as f64));
+        let a = 10f64.powf(self.gain_db as f64 / 40.0);
+        let [b0, b1, b2, a0, a1, a2] = match self.kind {
+            EqKind::HighPass => [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            EqKind::LowPass => [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            EqKind::Peaking => [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+            EqKind::LowShelf => {
+                let k = 2.0 * a.sqrt() * alpha;
+                [
+                    a * ((a + 1.0) - (a - 1.0) * cos + k),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - k),
+                    (a + 1.0) + (a - 1.0) * cos + k,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - k,
+                ]
+            }
+            EqKind::HighShelf => {
+                let k = 2.0 * a.sqrt() * alpha;
+                [
+                    a * ((a + 1.0) + (a - 1.0) * cos + k),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - k),
+                    (a + 1.0) - (a - 1.0) * cos + k,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - k,
+                ]
+            }
+        };
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+}
+
+/// The bands in series, checked against `sample_rate`
+pub fn chain(bands: &[EqBand], sample_rate: u32) -> Result<FilterChain, String> {
+    bands.iter().map(|band| band.validate(sample_rate).map(|()| band.design(sample_rate))).collect::<Result<_, _>>().map(FilterChain::new)
+}
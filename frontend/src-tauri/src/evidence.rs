@@ -0,0 +1,151 @@
+//! Evidence mode: the source recording is hashed as it is loaded (decoding
+//! opens it for reading only), and every file made for the examiner
+//! (exports, figures, reports, sessions, signatures) must be a new file
+//! inside a designated working directory. The app's own settings and key
+//! stay in its data directory.
+//!
+//! `check_output` refuses a path before any work is done; the writers then
+//! open the file with `create_output`, which creates it only if it still
+//! does not exist, so a file that appears in between is never written over.
+//! Files written by an external encoder are created empty the same way
+//! before it starts, and the encoder fills them.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Every switch of evidence mode, appended as a JSON line to this file in
+/// the app's data directory
+pub const EVIDENCE_LOG_FILE: &str = "evidence-log.jsonl";
+
+/// Evidence mode switched on or off. This run's switches go into the
+/// status and every report, so work done with it off cannot pass unnoticed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceEvent {
+    pub at: String,                     // RFC 3339, UTC
+    pub enabled: bool,
+    pub working_dir: String,
+    pub source_path: Option<String>,    // Sealed when the switch was made
+    pub source_sha256: Option<String>,
+}
+
+/// The source as it was when loaded in evidence mode
+#[derive(Debug, Clone)]
+pub struct SourceSeal {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EvidenceMode {
+    pub working_dir: PathBuf,       // Canonical
+    pub source: Option<SourceSeal>,
+}
+
+/// Evidence mode as the interface shows it
+#[derive(Debug, Clone, Serialize)]
+pub struct EvidenceStatus {
+    pub enabled: bool,
+    pub working_dir: Option<String>,
+    pub source_path: Option<String>,
+    pub source_sha256: Option<String>,          // When loaded
+    pub source_intact: Option<bool>,            // Whether the file on disk still has that hash
+    pub log: Vec<EvidenceEvent>,                // This run's switches on and off, oldest first
+}
+
+impl EvidenceMode {
+    /// Evidence mode writing into `working_dir`, which must exist
+    pub fn new(working_dir: &str) -> Result<Self, String> {
+        let working_dir = Path::new(working_dir)
+            .canonicalize()
+            .map_err(|e| format!("Working directory {} is not usable: {}", working_dir, e))?;
+        if !working_dir.is_dir() {
+            return Err(format!("{} is not a directory", working_dir.display()));
+        }
+        Ok(Self { working_dir, source: None })
+    }
+
+    /// Refuse to write `path` unless it is a new file inside the working
+    /// directory. Links are followed, so neither `..` nor a symbolic link can
+    /// lead out of it, and a path that exists at all (a dangling link
+    /// included) is never written over.
+    pub fn check_output(&self, path: &str) -> Result<(), String> {
+        let target = Path::new(path);
+        if target.symlink_metadata().is_ok() {
+            return Err(format!("Evidence mode writes new files only; {} already exists", path));
+        }
+        let name = target.file_name().ok_or_else(|| format!("{} is not a file name", path))?;
+        let parent = match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let directory = parent.canonicalize().map_err(|e| format!("Cannot write to {}: {}", parent.display(), e))?;
+        if !directory.starts_with(&self.working_dir) {
+            return Err(format!("Evidence mode writes only inside {}; {} is outside it", self.working_dir.display(), path));
+        }
+        if self.source.as_ref().is_some_and(|s| Path::new(&s.path).canonicalize().ok() == Some(directory.join(name))) {
+            return Err("Evidence mode never writes to the source file".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> EvidenceStatus {
+        let intact = self.source.as_ref().map(|s| crate::report::identify(&s.path).is_ok_and(|now| now.sha256 == s.sha256));
+        EvidenceStatus {
+            enabled: true,
+            working_dir: Some(self.working_dir.to_string_lossy().into_owned()),
+            source_path: self.source.as_ref().map(|s| s.path.clone()),
+            source_sha256: self.source.as_ref().map(|s| s.sha256.clone()),
+            source_intact: intact,
+            log: Vec::new(),
+        }
+    }
+
+    /// The switch to `enabled` at `at`, with the working directory and seal
+    pub fn event(&self, enabled: bool, at: String) -> EvidenceEvent {
+        EvidenceEvent {
+            at,
+            enabled,
+            working_dir: self.working_dir.to_string_lossy().into_owned(),
+            source_path: self.source.as_ref().map(|s| s.path.clone()),
+            source_sha256: self.source.as_ref().map(|s| s.sha256.clone()),
+        }
+    }
+}
+
+/// Append `event` to the log at `path`
+pub fn append_log(path: &Path, event: &EvidenceEvent) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let line = serde_json::to_string(event).map_err(|e| format!("Failed to serialize evidence log: {}", e))?;
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Create `path` to write into. With `new_only` (evidence mode) it must
+/// not exist yet, checked by the same call that creates it; otherwise an
+/// existing file is truncated.
+pub fn create_output(path: impl AsRef<Path>, new_only: bool) -> Result<File, String> {
+    let path = path.as_ref();
+    let file = if new_only {
+        OpenOptions::new().write(true).create_new(true).open(path)
+    } else {
+        File::create(path)
+    };
+    file.map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => format!("Evidence mode writes new files only; {} already exists", path.display()),
+        _ => format!("Failed to create {}: {}", path.display(), e),
+    })
+}
+
+impl EvidenceStatus {
+    pub fn disabled() -> Self {
+        Self { enabled: false, working_dir: None, source_path: None, source_sha256: None, source_intact: None, log: Vec::new() }
+    }
+}
@@ -0,0 +1,682 @@
+//! Export options and the processing applied to selections before writing
+
+use crate::channels;
+use crate::dc;
+use crate::declip::{self, DeclipMethod};
+use crate::denoise::{self, Denoise, NoiseProfile};
+use crate::eq::{self, EqBand};
+use crate::fir;
+use crate::generator::Rng;
+use crate::hum;
+use crate::loudness;
+use crate::repair::{self, RepairRegion};
+use rayon::prelude::*;
+use rubato::{FftFixedIn, Resampler};
+use serde::{Deserialize, Serialize};
+
+/// Frames the resampler takes per call (about), and the rates it converts
+/// between (Hz)
+const RESAMPLE_CHUNK: usize = 4096;
+const RATE_RANGE: (u32, u32) = (8000, 384_000);
+/// Rates Opus encodes at (Hz) and the highest MP3 carries
+const OPUS_RATES: [u32; 5] = [8000, 12_000, 16_000, 24_000, 48_000];
+const MP3_MAX_RATE: u32 = 48_000;
+
+/// Optional processing for `export_audio`; every field defaults to "off"
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Zero-based source channels to write, in output order (all when empty)
+    pub channel_mask: Vec<usize>,
+    /// Restore hard-clipped peaks by interpolating the flat tops
+    pub declip: Option<DeclipMethod>,
+    /// Subtract each channel's DC offset
+    pub remove_dc: bool,
+    /// High-pass each channel at this frequency (Hz), removing drifting
+    /// offsets and subsonic rumble as well
+    pub highpass_hz: Option<f32>,
+    /// Notch out the mains hum the hum analysis found, following the ENF
+    /// track when there is one
+    pub remove_hum: bool,
+    /// Filled in from the analyses when `remove_hum` is set
+    #[serde(skip)]
+    pub hum_plan: Option<hum::RemovalPlan>,
+    /// Reduce steady background noise (hiss, hum, fans)
+    pub denoise: Option<Denoise>,
+    /// Filled in from the recording when `denoise` is set
+    #[serde(skip)]
+    pub noise_profile: Option<NoiseProfile>,
+    /// Time-frequency rectangles (times into the recording) to attenuate or
+    /// fill in from the spectrum around them, such as a beep over speech
+    pub spectral_repairs: Vec<RepairRegion>,
+    /// Set by `export_spectral_selection`: keep only this band (Hz) of the
+    /// spectrogram
+    #[serde(skip)]
+    pub isolate_band: Option<(f32, f32)>,
+    /// Keep only the band between these frequencies (Hz), either of which
+    /// may be left out, with a linear-phase FIR filter
+    pub band_low_hz: Option<f32>,
+    pub band_high_hz: Option<f32>,
+    /// Parametric EQ bands applied in order
+    pub eq: Vec<EqBand>,
+    /// Raise (positive) or lower the level by this much (dB)
+    pub gain_db: Option<f32>,
+    /// Play the selection backwards (a check for backward-masked content)
+    pub reverse: bool,
+    /// Flip the sign of every sample, as a null test against another
+    /// recording needs
+    pub invert_polarity: bool,
+    /// Fade the selection in and out over these durations (seconds)
+    pub fade_in_secs: Option<f32>,
+    pub fade_out_secs: Option<f32>,
+    /// Curve of both fades (linear unless set)
+    pub fade_shape: FadeShape,
+    /// Resample to this rate (Hz); Opus, and MP3 above 48 kHz, go to 48 kHz
+    /// when it is not set and they cannot carry the source rate
+    pub sample_rate: Option<u32>,
+    /// Bring the result to a level, given as `{ "peak_dbfs": -1.0 }` or
+    /// `{ "lufs": -16.0 }`
+    pub normalize: Option<Normalize>,
+    /// File format to write; the output path's extension decides when absent
+    pub format: Option<ExportFormat>,
+    /// WAV: sample format written (32-bit float unless set)
+    pub sample_format: WavSampleFormat,
+    /// WAV: add triangular dither of one LSB before rounding to integers
+    pub dither: bool,
+    /// Lossy formats: constant (MP3), nominal (Vorbis) or target (Opus)
+    /// bitrate in kbit/s; MP3 and Vorbis use variable bitrate at `quality`
+    /// without one
+    pub bitrate_kbps: Option<u32>,
+    /// MP3 and Vorbis: variable bitrate quality from 0 (smallest) to 10
+    /// (best), 6 by default
+    pub quality: Option<f32>,
+    /// Opus: how far the bitrate may follow the audio (variable by default)
+    pub bitrate_mode: Option<BitrateMode>,
+    /// Opus: what the encoder tunes for (general audio by default)
+    pub opus_application: Option<OpusApplication>,
+    /// Write a manifest of the file's SHA-256 beside it, signed with this
+    /// installation's key (`<file>.sha256` and `<file>.sha256.sig`; one
+    /// `manifest.sha256` for a batch)
+    pub sign: bool,
+}
+
+/// Gain curves for fades
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeShape {
+    /// Gain rising in a straight line
+    #[default]
+    Linear,
+    /// Quarter sine, whose power rises evenly: sounds smoother, and a fade
+    /// out overlapped with a fade in keeps the level
+    EqualPower,
+}
+
+impl FadeShape {
+    /// Gain at `position` through the fade (0 to 1)
+    fn gain(self, position: f32) -> f32 {
+        match self {
+            Self::Linear => position,
+            Self::EqualPower => (position * std::f32::consts::FRAC_PI_2).sin(),
+        }
+    }
+}
+
+/// One excerpt of `export_selections`; `name` is the file name inside the
+/// output directory, given the format's extension when it has none
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchSelection {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub name: String,
+}
+
+/// Levels an export can be normalized to
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalize {
+    /// Sample peak (dBFS)
+    PeakDbfs(f32),
+    /// Integrated loudness (LUFS, ITU-R BS.1770)
+    Lufs(f32),
+}
+
+/// Sample formats WAV export writes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WavSampleFormat {
+    /// 16-bit integer PCM
+    Int16,
+    /// 24-bit integer PCM
+    Int24,
+    /// 32-bit float, the full resolution of the analysis
+    #[default]
+    Float32,
+}
+
+impl WavSampleFormat {
+    /// Integer word length, `None` for float
+    pub fn integer_bits(self) -> Option<u32> {
+        match self {
+            Self::Int16 => Some(16),
+            Self::Int24 => Some(24),
+            Self::Float32 => None,
+        }
+    }
+}
+
+/// Bitrate control for Opus
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BitrateMode {
+    /// Spends more bits where the audio needs them
+    #[default]
+    Vbr,
+    /// Varies, but keeps close to the target over short spans
+    ConstrainedVbr,
+    /// Every packet the same size
+    Cbr,
+}
+
+/// Opus encoder application modes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpusApplication {
+    /// Speech intelligibility first
+    Voip,
+    /// Closest to the input, for any audio
+    #[default]
+    Audio,
+    /// Lowest delay, without the speech mode
+    LowDelay,
+}
+
+/// File formats `export_audio` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// WAV, in the sample format `sample_format` picks
+    Wav,
+    /// MPEG-1 Layer III, for review copies
+    Mp3,
+    /// Ogg Vorbis, for review copies
+    Vorbis,
+    /// Ogg Opus, for review copies and upload
+    Opus,
+}
+
+impl ExportFormat {
+    /// The format a file name's extension stands for, WAV when it names none
+    pub fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("mp3") => Self::Mp3,
+            Some("ogg") | Some("oga") => Self::Vorbis,
+            Some("opus") => Self::Opus,
+            _ => Self::Wav,
+        }
+    }
+
+    /// The file name extension the format is usually written with
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Mp3 => "mp3",
+            Self::Vorbis => "ogg",
+            Self::Opus => "opus",
+        }
+    }
+
+    /// The rate audio at `sample_rate` has to be resampled to for this
+    /// format, when it cannot carry that rate
+    pub fn required_rate(self, sample_rate: u32) -> Option<u32> {
+        match self {
+            Self::Opus if !OPUS_RATES.contains(&sample_rate) => Some(48_000),
+            Self::Mp3 if sample_rate > MP3_MAX_RATE => Some(MP3_MAX_RATE),
+            _ => None,
+        }
+    }
+}
+
+/// Samples as `bits`-bit integers, scaled by 2^(bits - 1) as decoding
+/// divides them (so audio from an integer file converts back bit for bit),
+/// rounded after optional TPDF dither and clamped to the word. Also
+/// returns how many samples had to be clamped.
+pub fn to_integer(samples: &[f32], bits: u32, dither: bool) -> (Vec<i32>, usize) {
+    let scale = 2f64.powi(bits as i32 - 1);
+    let (min, max) = (-scale, scale - 1.0);
+    let mut rng = Rng(1);
+    let mut clamped = 0;
+    let codes = samples
+        .iter()
+        .map(|&x| {
+            let noise = if dither { (rng.next_f32() - rng.next_f32()) as f64 } else { 0.0 };
+            let code = (x as f64 * scale + noise).round();
+            if code < min || code > max {
+                clamped += 1;
+            }
+            code.clamp(min, max) as i32
+        })
+        .collect();
+    (codes, clamped)
+}
+
+/// How much of an export goes past full scale, where integer and lossy
+/// formats clip it
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Clipping {
+    pub samples_over: usize,            // Past full scale after processing
+    pub peak_dbfs: Option<f32>,         // Highest sample after processing; None when silent
+    pub samples_clamped: usize,         // Clamped to the word of an integer WAV
+}
+
+impl Clipping {
+    /// Samples of `samples` past full scale, and their peak
+    pub fn measure(samples: &[f32]) -> Self {
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        Self {
+            samples_over: samples.iter().filter(|s| s.abs() > 1.0).count(),
+            peak_dbfs: (peak > 0.0).then(|| 20.0 * peak.log10()),
+            samples_clamped: 0,
+        }
+    }
+}
+
+/// Interleaved audio on its way to the writer
+pub struct ExportBuffer {
+    pub samples: Vec<f32>,
+    pub channels: usize,
+    pub sample_rate: u32,
+}
+
+/// Keep only the channels listed in `mask`, in that order
+pub fn select_channels(buffer: ExportBuffer, mask: &[usize]) -> Result<ExportBuffer, String> {
+    if mask.is_empty() {
+        return Ok(buffer);
+    }
+    if let Some(&bad) = mask.iter().find(|&&c| c >= buffer.channels) {
+        return Err(format!("Channel {} does not exist ({} channels)", bad, buffer.channels));
+    }
+
+    let per_channel = channels::deinterleave(&buffer.samples, buffer.channels);
+    let frames = per_channel.first().map(Vec::len).unwrap_or(0);
+    let samples = (0..frames)
+        .flat_map(|i| mask.iter().map(move |&c| (c, i)))
+        .map(|(c, i)| per_channel[c][i])
+        .collect();
+
+    Ok(ExportBuffer {
+        samples,
+        channels: mask.len(),
+        sample_rate: buffer.sample_rate,
+    })
+}
+
+fn interleave(per_channel: &[Vec<f32>]) -> Vec<f32> {
+    let frames = per_channel.first().map(Vec::len).unwrap_or(0);
+    (0..frames).flat_map(|i| per_channel.iter().map(move |c| c[i])).collect()
+}
+
+/// Interpolate over the clipped runs of every channel
+pub fn declip(buffer: ExportBuffer, method: DeclipMethod) -> ExportBuffer {
+    let restored: Vec<(Vec<f32>, usize)> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .par_iter()
+        .map(|samples| declip::declip(samples, buffer.sample_rate, method))
+        .collect();
+    log::info!("Declipped {} runs with {:?} interpolation", restored.iter().map(|(_, n)| n).sum::<usize>(), method);
+    let per_channel: Vec<Vec<f32>> = restored.into_iter().map(|(samples, _)| samples).collect();
+
+    ExportBuffer { samples: interleave(&per_channel), ..buffer }
+}
+
+/// Remove each channel's DC offset, by subtracting its mean or with a
+/// high-pass at `highpass_hz`
+pub fn remove_dc(buffer: ExportBuffer, highpass_hz: Option<f32>) -> Result<ExportBuffer, String> {
+    if let Some(cutoff) = highpass_hz {
+        if !(cutoff > 0.0 && cutoff < buffer.sample_rate as f32 / 2.0) {
+            return Err(format!("High-pass frequency must be between 0 and {} Hz", buffer.sample_rate / 2));
+        }
+    }
+
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .into_iter()
+        .map(|mut samples| match highpass_hz {
+            Some(cutoff) => dc::highpass(&samples, cutoff, buffer.sample_rate),
+            None => {
+                dc::remove_offset(&mut samples);
+                samples
+            }
+        })
+        .collect();
+    Ok(ExportBuffer { samples: interleave(&per_channel), ..buffer })
+}
+
+/// Notch the hum `plan` describes out of every channel
+pub fn remove_hum(buffer: ExportBuffer, plan: &hum::RemovalPlan) -> ExportBuffer {
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .par_iter()
+        .map(|samples| hum::remove(samples, buffer.sample_rate, plan))
+        .collect();
+    ExportBuffer { samples: interleave(&per_channel), ..buffer }
+}
+
+/// Reduce the noise `profile` holds for each channel
+pub fn denoise(buffer: ExportBuffer, settings: &Denoise, profile: &NoiseProfile) -> Result<ExportBuffer, String> {
+    if !(0.0..=60.0).contains(&settings.reduction_db) {
+        return Err("Noise reduction must be between 0 and 60 dB".to_string());
+    }
+    if profile.power.len() != buffer.channels {
+        return Err("The noise print does not match the exported channels".to_string());
+    }
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .par_iter()
+        .zip(&profile.power)
+        .map(|(samples, noise)| denoise::reduce(samples, noise, profile.frame, settings))
+        .collect();
+
+    Ok(ExportBuffer { samples: interleave(&per_channel), ..buffer })
+}
+
+/// Repair every region (times into the buffer) in every channel
+pub fn repair_regions(buffer: ExportBuffer, regions: &[RepairRegion]) -> Result<ExportBuffer, String> {
+    for region in regions {
+        region.validate(buffer.sample_rate)?;
+    }
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .into_par_iter()
+        .map(|mut samples| {
+            for region in regions {
+                repair::repair(&mut samples, buffer.sample_rate, region);
+            }
+            samples
+        })
+        .collect();
+
+    Ok(ExportBuffer { samples: interleave(&per_channel), ..buffer })
+}
+
+/// Reduce every channel to the `low_hz`..`high_hz` band of its spectrogram
+pub fn isolate_band(buffer: ExportBuffer, low_hz: f32, high_hz: f32) -> Result<ExportBuffer, String> {
+    let nyquist = buffer.sample_rate as f32 / 2.0;
+    if !(0.0 <= low_hz && low_hz < high_hz && high_hz <= nyquist) {
+        return Err(format!("The selected band must rise from 0 to at most {} Hz", nyquist));
+    }
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .par_iter()
+        .map(|samples| repair::isolate(samples, buffer.sample_rate, low_hz, high_hz))
+        .collect();
+
+    Ok(ExportBuffer { samples: interleave(&per_channel), ..buffer })
+}
+
+/// Band-limit every channel to `low_hz`..`high_hz`
+pub fn band_limit(buffer: ExportBuffer, low_hz: Option<f32>, high_hz: Option<f32>) -> Result<ExportBuffer, String> {
+    let nyquist = buffer.sample_rate as f32 / 2.0;
+    for cutoff in [low_hz, high_hz].into_iter().flatten() {
+        if !(cutoff > 0.0 && cutoff < nyquist) {
+            return Err(format!("Band limits must be between 0 and {} Hz", nyquist));
+        }
+    }
+    if let (Some(low), Some(high)) = (low_hz, high_hz) {
+        if low >= high {
+            return Err("The band's low limit must be below its high limit".to_string());
+        }
+    }
+
+    let taps = fir::band_pass(low_hz, high_hz, buffer.sample_rate);
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .par_iter()
+        .map(|samples| fir::filter(samples, &taps))
+        .collect();
+
+    Ok(ExportBuffer { samples: interleave(&per_channel), ..buffer })
+}
+
+/// Run every channel through the EQ `bands`
+pub fn equalize(buffer: ExportBuffer, bands: &[EqBand]) -> Result<ExportBuffer, String> {
+    let chain = eq::chain(bands, buffer.sample_rate)?;
+    let per_channel: Vec<Vec<f32>> = channels::deinterleave(&buffer.samples, buffer.channels)
+        .par_iter()
+        .map(|samples| chain.clone().process_buffer(samples))
+        .collect();
+
+    Ok(ExportBuffer { samples: interleave(&per_channel), ..buffer })
+}
+
+/// Scale every sample by `gain_db`, warning when that takes peaks past
+/// full scale (the export reports the final output's `Clipping`)
+pub fn apply_gain(mut buffer: ExportBuffer, gain_db: f32) -> Result<ExportBuffer, String> {
+    if !(-60.0..=60.0).contains(&gain_db) {
+        return Err("Gain must be between -60 and 60 dB".to_string());
+    }
+    let gain = 10f32.powf(gain_db / 20.0);
+    buffer.samples.iter_mut().for_each(|s| *s *= gain);
+
+    let clipping = Clipping::measure(&buffer.samples);
+    if clipping.samples_over > 0 {
+        log::warn!("{:+.1} dB gain takes {} samples past full scale (peak {:+.1} dBFS); they clip in integer and lossy formats",
+            gain_db, clipping.samples_over, clipping.peak_dbfs.unwrap_or(0.0));
+    }
+    Ok(buffer)
+}
+
+/// Reverse the order of the frames, keeping each frame's channels in place
+pub fn reverse(buffer: ExportBuffer) -> ExportBuffer {
+    let samples = buffer.samples.chunks_exact(buffer.channels).rev().flatten().copied().collect();
+    ExportBuffer { samples, ..buffer }
+}
+
+/// Flip the sign of every sample
+pub fn invert_polarity(mut buffer: ExportBuffer) -> ExportBuffer {
+    buffer.samples.iter_mut().for_each(|s| *s = -*s);
+    buffer
+}
+
+/// Join `pieces` (of one rate and channel count) in order, each overlapping
+/// the next by `crossfade` seconds with the outgoing piece fading out as the
+/// incoming one fades in
+pub fn concatenate(pieces: Vec<ExportBuffer>, crossfade: f32, shape: FadeShape) -> Result<ExportBuffer, String> {
+    if !(0.0..=60.0).contains(&crossfade) {
+        return Err("Crossfades must be between 0 and 60 seconds".to_string());
+    }
+    let mut pieces = pieces.into_iter();
+    let mut joined = pieces.next().ok_or("Nothing to join")?;
+    let overlap = (crossfade * joined.sample_rate as f32).round() as usize;
+
+    for piece in pieces {
+        let channels = joined.channels;
+        let frames = |b: &ExportBuffer| b.samples.len() / b.channels;
+        if frames(&joined) < overlap || frames(&piece) < overlap {
+            return Err(format!("Every selection must be at least as long as the {} s crossfade", crossfade));
+        }
+        let tail = joined.samples.len() - overlap * channels;
+        for (i, (out, incoming)) in joined.samples[tail..].chunks_exact_mut(channels).zip(piece.samples.chunks_exact(channels)).enumerate() {
+            let t = (i as f32 + 0.5) / overlap as f32;
+            let (fade_out, fade_in) = (shape.gain(1.0 - t), shape.gain(t));
+            out.iter_mut().zip(incoming).for_each(|(o, n)| *o = *o * fade_out + n * fade_in);
+        }
+        joined.samples.extend_from_slice(&piece.samples[overlap * channels..]);
+    }
+    Ok(joined)
+}
+
+/// Fade the first `fade_in` and the last `fade_out` seconds from and to
+/// silence
+pub fn fade(mut buffer: ExportBuffer, fade_in: f32, fade_out: f32, shape: FadeShape) -> Result<ExportBuffer, String> {
+    let frames = buffer.samples.len() / buffer.channels;
+    let duration = frames as f32 / buffer.sample_rate as f32;
+    for length in [fade_in, fade_out] {
+        if !(0.0..=duration).contains(&length) {
+            return Err(format!("Fades must be between 0 and {:.3} s (the selection), not {}", duration, length));
+        }
+    }
+
+    let [fade_in, fade_out] = [fade_in, fade_out].map(|secs| (secs * buffer.sample_rate as f32).round() as usize);
+    for (i, frame) in buffer.samples.chunks_exact_mut(buffer.channels).enumerate() {
+        let mut gain = 1.0;
+        if i < fade_in {
+            gain *= shape.gain(i as f32 / fade_in as f32);
+        }
+        if frames - 1 - i < fade_out {
+            gain *= shape.gain((frames - 1 - i) as f32 / fade_out as f32);
+        }
+        frame.iter_mut().for_each(|s| *s *= gain);
+    }
+    Ok(buffer)
+}
+
+/// Apply the gain that brings `buffer` to `target`. Loudness targets the
+/// peak would have to exceed full scale for are refused rather than
+/// clipped.
+pub fn normalize(mut buffer: ExportBuffer, target: Normalize) -> Result<ExportBuffer, String> {
+    let peak = buffer.samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if peak == 0.0 {
+        return Err("The selection is silent, so there is no level to normalize".to_string());
+    }
+    let peak_db = 20.0 * peak.log10();
+
+    let gain_db = match target {
+        Normalize::PeakDbfs(dbfs) => {
+            if !(-60.0..=0.0).contains(&dbfs) {
+                return Err(format!("Peak target must be between -60 and 0 dBFS, not {}", dbfs));
+            }
+            dbfs - peak_db
+        }
+        Normalize::Lufs(lufs) => {
+            if !(-60.0..=0.0).contains(&lufs) {
+                return Err(format!("Loudness target must be between -60 and 0 LUFS, not {}", lufs));
+            }
+            let channels = channels::deinterleave(&buffer.samples, buffer.channels);
+            let loudness = loudness::integrated(&channels, buffer.sample_rate)
+                .ok_or("The selection is too quiet to measure its loudness")?;
+            let gain_db = lufs - loudness;
+            if peak_db + gain_db > 0.0 {
+                return Err(format!(
+                    "Reaching {} LUFS from {:.1} LUFS would take the peak to {:+.1} dBFS; lower the target or normalize the peak instead",
+                    lufs, loudness, peak_db + gain_db
+                ));
+            }
+            gain_db
+        }
+    };
+    log::info!("Normalizing to {:?}: {:+.2} dB", target, gain_db);
+
+    let gain = 10f32.powf(gain_db / 20.0);
+    buffer.samples.iter_mut().for_each(|s| *s *= gain);
+    Ok(buffer)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Convert to `sample_rate` with an FFT-based band-limited resampler, the
+/// output trimmed of its delay so it lines up with the input and lasts as long
+pub fn resample(buffer: ExportBuffer, sample_rate: u32) -> Result<ExportBuffer, String> {
+    if sample_rate == buffer.sample_rate {
+        return Ok(buffer);
+    }
+    if !(RATE_RANGE.0..=RATE_RANGE.1).contains(&sample_rate) {
+        return Err(format!("Sample rate must be between {} and {} Hz, not {}", RATE_RANGE.0, RATE_RANGE.1, sample_rate));
+    }
+
+    let per_channel = channels::deinterleave(&buffer.samples, buffer.channels);
+    let frames_in = per_channel.first().map(Vec::len).unwrap_or(0);
+    let frames_out = (frames_in as f64 * sample_rate as f64 / buffer.sample_rate as f64).round() as usize;
+    // An even number of the smallest input block the ratio allows keeps
+    // the resampler's delay a whole number of output frames
+    let (rate_in, rate_out) = (buffer.sample_rate as usize, sample_rate as usize);
+    let block = 2 * rate_in / gcd(rate_in, rate_out);
+    let mut resampler = FftFixedIn::<f32>::new(rate_in, rate_out, RESAMPLE_CHUNK.div_ceil(block) * block, 1, buffer.channels)
+        .map_err(|e| format!("Failed to set up resampler: {}", e))?;
+    let delay = resampler.output_delay();
+
+    let mut resampled = vec![Vec::with_capacity(frames_out + delay); buffer.channels];
+    let mut position = 0;
+    while resampled[0].len() < frames_out + delay {
+        let end = (position + resampler.input_frames_next()).min(frames_in);
+        let input: Vec<&[f32]> = per_channel.iter().map(|c| &c[position..end]).collect();
+        // A short chunk is padded with silence, and past the end silence
+        // pushes out what the resampler still holds
+        let output = if end - position == resampler.input_frames_next() {
+            resampler.process(&input, None)
+        } else if end > position {
+            resampler.process_partial(Some(&input), None)
+        } else {
+            resampler.process_partial(None::<&[&[f32]]>, None)
+        }
+        .map_err(|e| format!("Resampling failed: {}", e))?;
+        position = end;
+        for (channel, chunk) in resampled.iter_mut().zip(output) {
+            channel.extend(chunk);
+        }
+    }
+
+    let samples = (delay..delay + frames_out).flat_map(|i| resampled.iter().map(move |c| c[i])).collect();
+    Ok(ExportBuffer { samples, sample_rate, ..buffer })
+}
+
+/// Apply all requested processing steps in order
+pub fn process(buffer: ExportBuffer, options: &ExportOptions) -> Result<ExportBuffer, String> {
+    let buffer = select_channels(buffer, &options.channel_mask)?;
+    // Before any filtering, which would move the flat tops off their ceiling
+    let buffer = match options.declip {
+        Some(method) => declip(buffer, method),
+        None => buffer,
+    };
+    let buffer = if options.remove_dc || options.highpass_hz.is_some() {
+        remove_dc(buffer, options.highpass_hz)?
+    } else {
+        buffer
+    };
+    let buffer = match (options.remove_hum, &options.hum_plan) {
+        (true, Some(plan)) => remove_hum(buffer, plan),
+        (true, None) => return Err("No hum to remove; analyze the recording's hum first".to_string()),
+        (false, _) => buffer,
+    };
+    let buffer = match (&options.denoise, &options.noise_profile) {
+        (Some(settings), Some(profile)) => denoise(buffer, settings, profile)?,
+        (Some(_), None) => return Err("Noise reduction needs a noise print".to_string()),
+        (None, _) => buffer,
+    };
+    let buffer = if options.spectral_repairs.is_empty() {
+        buffer
+    } else {
+        repair_regions(buffer, &options.spectral_repairs)?
+    };
+    let buffer = match options.isolate_band {
+        Some((low_hz, high_hz)) => isolate_band(buffer, low_hz, high_hz)?,
+        None => buffer,
+    };
+    let buffer = if options.band_low_hz.is_some() || options.band_high_hz.is_some() {
+        band_limit(buffer, options.band_low_hz, options.band_high_hz)?
+    } else {
+        buffer
+    };
+    let buffer = if options.eq.is_empty() { buffer } else { equalize(buffer, &options.eq)? };
+    let buffer = match options.gain_db {
+        Some(gain_db) => apply_gain(buffer, gain_db)?,
+        None => buffer,
+    };
+    let buffer = if options.reverse { reverse(buffer) } else { buffer };
+    let buffer = if options.invert_polarity { invert_polarity(buffer) } else { buffer };
+    let buffer = match options.sample_rate {
+        Some(rate) => resample(buffer, rate)?,
+        None => buffer,
+    };
+    finish(buffer, options)
+}
+
+/// The steps `process` ends with, which apply to the output as a whole:
+/// fades and normalization
+pub fn finish(buffer: ExportBuffer, options: &ExportOptions) -> Result<ExportBuffer, String> {
+    let buffer = if options.fade_in_secs.is_some() || options.fade_out_secs.is_some() {
+        let (fade_in, fade_out) = (options.fade_in_secs.unwrap_or(0.0), options.fade_out_secs.unwrap_or(0.0));
+        fade(buffer, fade_in, fade_out, options.fade_shape)?
+    } else {
+        buffer
+    };
+    match options.normalize {
+        Some(target) => normalize(buffer, target),
+        None => Ok(buffer),
+    }
+}
@@ -0,0 +1,132 @@
+//! Raster figures of the audio (spectrogram, waveform) encoded as PNG, for
+//! reports and image export
+
+/// An RGB image, rows from the top
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, background: [u8; 3]) -> Self {
+        Self { width, height, pixels: background.repeat(width * height) }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        if x < self.width && y < self.height {
+            let i = 3 * (y * self.width + x);
+            self.pixels[i..i + 3].copy_from_slice(&color);
+        }
+    }
+
+    /// Mix `color` into the pixel, `opacity` 0 (unchanged) to 1 (replaced)
+    pub fn blend(&mut self, x: usize, y: usize, color: [u8; 3], opacity: f32) {
+        if x < self.width && y < self.height {
+            let i = 3 * (y * self.width + x);
+            for (pixel, c) in self.pixels[i..i + 3].iter_mut().zip(color) {
+                *pixel = (*pixel as f32 + (c as f32 - *pixel as f32) * opacity).round() as u8;
+            }
+        }
+    }
+
+    pub fn png(&self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        writer.write_image_data(&self.pixels).map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        writer.finish().map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        Ok(out)
+    }
+}
+
+/// Viridis at `t` (0 to 1), the polynomial fit the spectrogram view uses
+pub fn viridis(t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |c: [f32; 5]| (c[0] + t * (c[1] + t * (c[2] + t * (c[3] + t * c[4])))).clamp(0.0, 255.0) as u8;
+    [
+        channel([68.0, -71.3, 338.3, -467.5, 387.5]),
+        channel([1.0, 169.0, -75.0, 134.0, 27.0]),
+        channel([84.0, 145.0, -520.0, 722.0, -395.0]),
+    ]
+}
+
+/// Axis ticks from `low` to `high`, about `count` of them on a 1-2-5 step
+pub fn ticks(low: f32, high: f32, count: usize) -> Vec<f32> {
+    let span = high - low;
+    if span.is_nan() || span <= 0.0 {
+        return vec![low];
+    }
+    let raw = span / count as f32;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|m| m * magnitude).find(|&s| s >= raw).unwrap_or(raw);
+    let first = (low / step).ceil() as i64;
+    (first..).map(|i| i as f32 * step).take_while(|&t| t <= high + step * 1e-3).collect()
+}
+
+/// The items of `total` that pixel `i` of `n` covers: at least one, so a
+/// short input is stretched and a long one reduced
+fn covered(i: usize, n: usize, total: usize) -> std::ops::Range<usize> {
+    let start = i * total / n;
+    start..((i + 1) * total / n).max(start + 1).min(total)
+}
+
+/// The levels a spectrogram is coloured between: from `range_db` below
+/// its loudest value up to that value
+pub fn levels(data: &[Vec<f32>], range_db: f32) -> (f32, f32) {
+    let top = data.iter().flatten().copied().fold(f32::NEG_INFINITY, f32::max);
+    (top - range_db.max(1.0), top)
+}
+
+/// A spectrogram (frames × bins in dB, lowest bin at the bottom) drawn
+/// `width` by `height`, each pixel the loudest value it covers, coloured
+/// from the `floor` to the `top` of `levels`
+pub fn spectrogram(data: &[Vec<f32>], width: usize, height: usize, (floor, top): (f32, f32)) -> Image {
+    let mut image = Image::new(width, height, viridis(0.0));
+    let bins = data.first().map_or(0, Vec::len);
+    if bins == 0 {
+        return image;
+    }
+
+    for x in 0..width {
+        let frames = &data[covered(x, width, data.len())];
+        for y in 0..height {
+            let band = covered(height - 1 - y, height, bins);
+            let level = frames.iter().flat_map(|frame| &frame[band.clone()]).copied().fold(f32::NEG_INFINITY, f32::max);
+            image.set(x, y, viridis((level - floor) / (top - floor)));
+        }
+    }
+    image
+}
+
+/// The waveform of `samples` (full scale ±1) drawn `width` by `height`:
+/// the range each column covers, with its RMS in a lighter shade inside
+pub fn waveform(samples: &[f32], width: usize, height: usize) -> Image {
+    const BACKGROUND: [u8; 3] = [255, 255, 255];
+    const PEAK: [u8; 3] = [40, 80, 150];
+    const RMS: [u8; 3] = [120, 160, 220];
+    let mut image = Image::new(width, height, BACKGROUND);
+    if samples.is_empty() {
+        return image;
+    }
+    let row = |v: f32| ((1.0 - v.clamp(-1.0, 1.0)) / 2.0 * (height - 1) as f32).round() as usize;
+
+    for x in 0..width {
+        let column = &samples[covered(x, width, samples.len())];
+        let (low, high) = column.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &s| (lo.min(s), hi.max(s)));
+        let rms = (column.iter().map(|s| s * s).sum::<f32>() / column.len() as f32).sqrt();
+        for y in row(high)..=row(low) {
+            image.set(x, y, PEAK);
+        }
+        for y in row(rms.min(high))..=row((-rms).max(low)) {
+            image.set(x, y, RMS);
+        }
+    }
+    let middle = row(0.0);
+    for x in 0..width {
+        image.set(x, middle, PEAK);
+    }
+    image
+}
@@ -0,0 +1,151 @@
+//! Second-order IIR filter sections used by the level meters and processing chain
+
+/// Biquad section in transposed direct form II
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    /// Create a section from normalized digital coefficients (a0 = 1)
+    pub fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self { b0, b1, b2, a1, a2, z1: 0.0, z2: 0.0 }
+    }
+
+    /// Map an analog section (b0 s² + b1 s + b2) / (a0 s² + a1 s + a2) to the
+    /// digital domain using the bilinear transform
+    pub fn from_analog(b: [f64; 3], a: [f64; 3], sample_rate: f64) -> Self {
+        let k = 2.0 * sample_rate;
+        let k2 = k * k;
+
+        let nb0 = b[0] * k2 + b[1] * k + b[2];
+        let nb1 = 2.0 * (b[2] - b[0] * k2);
+        let nb2 = b[0] * k2 - b[1] * k + b[2];
+        let na0 = a[0] * k2 + a[1] * k + a[2];
+        let na1 = 2.0 * (a[2] - a[0] * k2);
+        let na2 = a[0] * k2 - a[1] * k + a[2];
+
+        Self::new(nb0 / na0, nb1 / na0, nb2 / na0, na1 / na0, na2 / na0)
+    }
+
+    /// Notch at `freq` Hz whose -3 dB bandwidth is `freq / q`
+    pub fn notch(freq: f64, q: f64, sample_rate: f64) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self::new(1.0 / a0, -2.0 * w0.cos() / a0, 1.0 / a0, -2.0 * w0.cos() / a0, (1.0 - alpha) / a0)
+    }
+
+    /// Take over the coefficients of `other` but keep this section's state,
+    /// retuning a running filter
+    pub fn retune(&mut self, other: &Biquad) {
+        *self = Self { z1: self.z1, z2: self.z2, ..*other };
+    }
+
+    /// Scale the numerator so the section's gain is multiplied by `gain`
+    pub fn scale(&mut self, gain: f64) {
+        self.b0 *= gain;
+        self.b1 *= gain;
+        self.b2 *= gain;
+    }
+
+    /// Magnitude response at `freq` Hz
+    pub fn magnitude(&self, freq: f64, sample_rate: f64) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * freq / sample_rate;
+        let (c1, s1) = (w.cos(), -w.sin());
+        let (c2, s2) = ((2.0 * w).cos(), -(2.0 * w).sin());
+
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = self.b1 * s1 + self.b2 * s2;
+        let den_re = 1.0 + self.a1 * c1 + self.a2 * c2;
+        let den_im = self.a1 * s1 + self.a2 * s2;
+
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let x = x as f64;
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y as f32
+    }
+}
+
+/// Cascade of biquad sections applied in series
+#[derive(Clone, Debug, Default)]
+pub struct FilterChain {
+    sections: Vec<Biquad>,
+}
+
+impl FilterChain {
+    pub fn new(sections: Vec<Biquad>) -> Self {
+        Self { sections }
+    }
+
+    /// Combined magnitude response at `freq` Hz
+    pub fn magnitude(&self, freq: f64, sample_rate: f64) -> f64 {
+        self.sections.iter().map(|s| s.magnitude(freq, sample_rate)).product()
+    }
+
+    /// Apply a gain to the whole chain (folded into the first section)
+    pub fn scale(&mut self, gain: f64) {
+        if let Some(first) = self.sections.first_mut() {
+            first.scale(gain);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Biquad::reset);
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.sections.iter_mut().fold(x, |acc, s| s.process(acc))
+    }
+
+    /// Filter a whole buffer, starting from a cleared state
+    pub fn process_buffer(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.reset();
+        samples.iter().map(|&s| self.process(s)).collect()
+    }
+}
+
+/// Butterworth lowpass of even `order` with its -3 dB point at `cutoff` Hz
+pub fn butterworth_lowpass(cutoff: f64, order: usize, sample_rate: f64) -> FilterChain {
+    // Prewarp so the bilinear transform keeps the cutoff in place
+    let w = 2.0 * sample_rate * (std::f64::consts::PI * cutoff / sample_rate).tan();
+    let sections = (0..order / 2)
+        .map(|k| {
+            let theta = std::f64::consts::PI * (2 * k + 1) as f64 / (2 * order) as f64;
+            let q = 1.0 / (2.0 * theta.sin());
+            Biquad::from_analog([0.0, 0.0, w * w], [1.0, w / q, w * w], sample_rate)
+        })
+        .collect();
+    FilterChain::new(sections)
+}
+
+/// Butterworth highpass of even `order` with its -3 dB point at `cutoff` Hz
+pub fn butterworth_highpass(cutoff: f64, order: usize, sample_rate: f64) -> FilterChain {
+    let w = 2.0 * sample_rate * (std::f64::consts::PI * cutoff / sample_rate).tan();
+    let sections = (0..order / 2)
+        .map(|k| {
+            let theta = std::f64::consts::PI * (2 * k + 1) as f64 / (2 * order) as f64;
+            let q = 1.0 / (2.0 * theta.sin());
+            Biquad::from_analog([1.0, 0.0, 0.0], [1.0, w / q, w * w], sample_rate)
+        })
+        .collect();
+    FilterChain::new(sections)
+}
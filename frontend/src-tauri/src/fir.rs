@@ -0,0 +1,88 @@
+//! Linear-phase FIR filters: Kaiser-windowed sinc design and FFT convolution
+
+use realfft::RealFftPlanner;
+use std::f64::consts::PI;
+
+/// Stopband attenuation of the designs (dB)
+const STOPBAND_DB: f64 = 80.0;
+/// Transition band centred on each cutoff: this fraction of the cutoff
+/// frequency, and at least this wide (Hz)
+const TRANSITION_FRACTION: f64 = 0.1;
+const MIN_TRANSITION_HZ: f64 = 10.0;
+/// Longest filter designed (taps)
+const MAX_TAPS: usize = (1 << 16) - 1;
+
+/// Modified Bessel function of the first kind, order zero, by its series
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    for k in 1..50 {
+        term *= (x / (2.0 * k as f64)).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Taps of a linear-phase filter passing `low_hz` to `high_hz` (either may
+/// be left out for a high- or low-pass): half down (-6 dB) at each cutoff,
+/// and flat inside and 80 dB down outside at most a twentieth of the cutoff
+/// either side of it. Odd length.
+pub fn band_pass(low_hz: Option<f32>, high_hz: Option<f32>, sample_rate: u32) -> Vec<f32> {
+    let sr = sample_rate as f64;
+    let transition = [low_hz, high_hz]
+        .iter()
+        .flatten()
+        .map(|&f| (f as f64 * TRANSITION_FRACTION).max(MIN_TRANSITION_HZ))
+        .fold(sr / 4.0, f64::min);
+    let length = ((STOPBAND_DB - 7.95) / (2.285 * 2.0 * PI * transition / sr)).ceil() as usize + 1;
+    let length = (length | 1).min(MAX_TAPS);
+    let middle = (length / 2) as f64;
+    let beta = 0.1102 * (STOPBAND_DB - 8.7);
+
+    // Low-pass at `f` as a fraction of the sample rate
+    let low_pass = |f: f64, n: f64| if n == 0.0 { 2.0 * f } else { (2.0 * PI * f * n).sin() / (PI * n) };
+    (0..length)
+        .map(|i| {
+            let n = i as f64 - middle;
+            let pass = high_hz.map_or(if n == 0.0 { 1.0 } else { 0.0 }, |f| low_pass(f as f64 / sr, n));
+            let stop = low_hz.map_or(0.0, |f| low_pass(f as f64 / sr, n));
+            let window = bessel_i0(beta * (1.0 - (n / middle).powi(2)).max(0.0).sqrt()) / bessel_i0(beta);
+            ((pass - stop) * window) as f32
+        })
+        .collect()
+}
+
+/// Convolve with odd-length linear-phase `taps` by FFT overlap-add, taking
+/// out the filter's delay so the output lines up with the input
+pub fn filter(samples: &[f32], taps: &[f32]) -> Vec<f32> {
+    let n_fft = (2 * taps.len()).next_power_of_two();
+    let block = n_fft - taps.len() + 1;
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(n_fft);
+    let ifft = planner.plan_fft_inverse(n_fft);
+
+    let mut kernel = taps.to_vec();
+    kernel.resize(n_fft, 0.0);
+    let mut response = fft.make_output_vec();
+    fft.process(&mut kernel, &mut response).unwrap();
+
+    let delay = taps.len() / 2;
+    let mut out = vec![0.0f32; samples.len() + taps.len() - 1];
+    let mut spectrum = fft.make_output_vec();
+    let mut frame = vec![0.0f32; n_fft];
+    for (b, chunk) in samples.chunks(block).enumerate() {
+        frame.fill(0.0);
+        frame[..chunk.len()].copy_from_slice(chunk);
+        fft.process(&mut frame, &mut spectrum).unwrap();
+        spectrum.iter_mut().zip(&response).for_each(|(x, h)| *x *= h / n_fft as f32);
+        ifft.process(&mut spectrum, &mut frame).unwrap();
+        let at = b * block;
+        let len = (chunk.len() + taps.len() - 1).min(out.len() - at);
+        out[at..at + len].iter_mut().zip(&frame).for_each(|(y, x)| *y += x);
+    }
+    out.drain(..delay);
+    out.truncate(samples.len());
+    out
+}
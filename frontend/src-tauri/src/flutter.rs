@@ -0,0 +1,321 @@
+//! Wow and flutter: speed fluctuation of tape and disc transfers
+
+use crate::filters::{self, Biquad, FilterChain};
+use crate::{pitch, tones};
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Rate the frequency deviation of a test tone is sampled at, and the
+/// bandwidth kept either side of the carrier (Hz)
+const DEMOD_RATE: f32 = 1000.0;
+const DEMOD_BANDWIDTH_HZ: f64 = 250.0;
+/// Lowest carrier that leaves room for that bandwidth (Hz)
+const MIN_CARRIER_HZ: f32 = 1000.0;
+/// Shortest tone measured, and the settling time trimmed off either end of
+/// it (seconds)
+const MIN_TONE_SECS: f32 = 3.0;
+const EDGE_SECS: f32 = 1.0;
+/// Samples whose carrier amplitude falls below this fraction of the median
+/// are dropouts, and they and this much either side, where the mixing
+/// filters ring, hold the last deviation (seconds)
+const DROPOUT_RATIO: f32 = 0.1;
+const DROPOUT_GUARD_SECS: f32 = 0.005;
+/// Test tones of the tape standards (Hz), and how far the measured tone may
+/// sit from one to be read as it running off speed
+const TEST_TONES: [f32; 2] = [3000.0, 3150.0];
+const SPEED_TOLERANCE: f32 = 0.06;
+/// Modulation bands (Hz): the whole measurement, wow below the split and
+/// flutter above it
+const BAND: (f64, f64) = (0.5, 200.0);
+const WOW_FLUTTER_SPLIT_HZ: f64 = 6.0;
+/// Weighting curve poles (Hz): a damped second-order highpass, a first-order
+/// highpass and a first-order lowpass, fitted to the IEC 60386 table within
+/// 0.8 dB from 0.2 to 200 Hz
+const WEIGHTING_HP2_HZ: f64 = 0.811;
+const WEIGHTING_HP2_Q: f64 = 0.552;
+const WEIGHTING_HP1_HZ: f64 = 0.376;
+const WEIGHTING_LP_HZ: f64 = 11.9;
+/// The 2-sigma peak: the level the deviation exceeds this fraction of the
+/// time
+const PEAK_EXCEEDED: f32 = 0.05;
+/// Pitch track fallback: voiced runs at least this long (seconds) without a
+/// frame-to-frame step of this much (a new note or syllable, in natural log
+/// units), once the tracker's slips by a factor of up to this are folded back
+const MIN_PITCH_RUN_SECS: f32 = 1.5;
+const MAX_PITCH_STEP: f32 = 0.02;
+const MAX_PITCH_FOLD: usize = 3;
+
+const TONE_CAVEAT: &str = "Measured on a test tone. The figures are only as good as the tone was steady when it \
+was recorded; a tone from an oscillator on the recorder measures the whole chain.";
+const PITCH_CAVEAT: &str = "Estimated from the pitch of sustained notes: wow only, since the pitch track cannot \
+follow flutter, and vibrato or a wavering voice is counted as wow. A test tone gives a proper measurement.";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlutterSource {
+    Tone,
+    PitchTrack,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlutterReport {
+    pub source: FlutterSource,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub tone_hz: Option<f32>,                   // Mean frequency of the test tone
+    pub nominal_hz: Option<f32>,                // Standard test frequency it is taken for
+    pub speed_error_percent: Option<f32>,       // Tone against that frequency
+    pub weighted_peak_percent: f32,             // IEC 60386 weighted, 2-sigma peak
+    pub weighted_rms_percent: f32,              // Same weighting, RMS (JIS / NAB style)
+    pub unweighted_peak_percent: f32,           // 0.5-200 Hz, 2-sigma peak
+    pub wow_rms_percent: f32,                   // 0.5-6 Hz
+    pub flutter_rms_percent: Option<f32>,       // 6-200 Hz; not from the pitch track
+    pub dominant_rate_hz: Option<f32>,          // Strongest modulation rate
+    pub caveat: String,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+fn rms(values: &[f32]) -> f32 {
+    (values.iter().map(|v| v * v).sum::<f32>() / values.len().max(1) as f32).sqrt()
+}
+
+/// IEC 60386 weighting at `rate`, 0 dB at 4 Hz
+fn weighting_filter(rate: f64) -> FilterChain {
+    let w = |f: f64| 2.0 * std::f64::consts::PI * f;
+    let (w1, w2, w3) = (w(WEIGHTING_HP2_HZ), w(WEIGHTING_HP1_HZ), w(WEIGHTING_LP_HZ));
+    let mut chain = FilterChain::new(vec![
+        Biquad::from_analog([1.0, 0.0, 0.0], [1.0, w1 / WEIGHTING_HP2_Q, w1 * w1], rate),
+        Biquad::from_analog([0.0, w3, 0.0], [1.0, w2 + w3, w2 * w3], rate),
+    ]);
+    let gain = chain.magnitude(4.0, rate);
+    chain.scale(1.0 / gain);
+    chain
+}
+
+fn band_filter(lo: f64, hi: f64, rate: f64) -> impl FnMut(&[f32]) -> Vec<f32> {
+    let mut highpass = filters::butterworth_highpass(lo, 2, rate);
+    let mut lowpass = filters::butterworth_lowpass(hi, 4, rate);
+    move |x| {
+        highpass.reset();
+        lowpass.reset();
+        lowpass.process_buffer(&highpass.process_buffer(x))
+    }
+}
+
+/// Strongest rate in the `BAND` spectrum of `deviation`
+fn dominant_rate(deviation: &[f32], rate: f32) -> Option<f32> {
+    let n = deviation.len();
+    if n < 4 {
+        return None;
+    }
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(n);
+    let mean = deviation.iter().sum::<f32>() / n as f32;
+    let mut input: Vec<f32> = deviation.iter().map(|d| d - mean).collect();
+    let mut spectrum = fft.make_output_vec();
+    fft.process(&mut input, &mut spectrum).ok()?;
+    let bin_hz = rate / n as f32;
+    let (lo, hi) = (((BAND.0 as f32 / bin_hz).ceil() as usize).max(1), ((BAND.1 as f32).min(rate / 2.0) / bin_hz) as usize);
+    (lo..=hi.min(spectrum.len() - 1))
+        .max_by(|&a, &b| spectrum[a].norm_sqr().total_cmp(&spectrum[b].norm_sqr()))
+        .map(|k| k as f32 * bin_hz)
+}
+
+/// Fractional deviation segments at `rate` through the weighting and the
+/// bands, each with `skip` leading samples left out while the filters settle
+fn fill_report(report: &mut FlutterReport, segments: &[Vec<f32>], rate: f32, skip: usize) {
+    let rate_f64 = rate as f64;
+    let top = BAND.1.min(0.4 * rate_f64);
+    let mut weighting = weighting_filter(rate_f64);
+    let mut unweighted = band_filter(BAND.0, top, rate_f64);
+    let mut wow = band_filter(BAND.0, WOW_FLUTTER_SPLIT_HZ, rate_f64);
+    let mut flutter = band_filter(WOW_FLUTTER_SPLIT_HZ, top, rate_f64);
+    let (mut weighted_all, mut unweighted_all) = (Vec::new(), Vec::new());
+    let (mut wow_all, mut flutter_all) = (Vec::new(), Vec::new());
+    for segment in segments {
+        weighting.reset();
+        weighted_all.extend(weighting.process_buffer(segment).into_iter().skip(skip));
+        unweighted_all.extend(unweighted(segment).into_iter().skip(skip));
+        wow_all.extend(wow(segment).into_iter().skip(skip));
+        flutter_all.extend(flutter(segment).into_iter().skip(skip));
+    }
+    let peak = |values: &[f32]| 100.0 * percentile(&values.iter().map(|v| v.abs()).collect::<Vec<_>>(), 1.0 - PEAK_EXCEEDED);
+    report.weighted_peak_percent = peak(&weighted_all);
+    report.weighted_rms_percent = 100.0 * rms(&weighted_all);
+    report.unweighted_peak_percent = peak(&unweighted_all);
+    report.wow_rms_percent = 100.0 * rms(&wow_all);
+    report.flutter_rms_percent = matches!(report.source, FlutterSource::Tone).then(|| 100.0 * rms(&flutter_all));
+    report.dominant_rate_hz =
+        segments.iter().max_by_key(|s| s.len()).and_then(|s| dominant_rate(&s[skip.min(s.len())..], rate));
+}
+
+fn empty_report(source: FlutterSource, start_time: f32, end_time: f32, caveat: &'static str) -> FlutterReport {
+    FlutterReport {
+        source,
+        start_time,
+        end_time,
+        tone_hz: None,
+        nominal_hz: None,
+        speed_error_percent: None,
+        weighted_peak_percent: 0.0,
+        weighted_rms_percent: 0.0,
+        unweighted_peak_percent: 0.0,
+        wow_rms_percent: 0.0,
+        flutter_rms_percent: None,
+        dominant_rate_hz: None,
+        caveat: caveat.to_string(),
+    }
+}
+
+/// Measure wow and flutter on a test tone near `carrier_hz` in
+/// `samples[start..end]`. The tone is mixed down to 0 Hz, its phase followed
+/// at 1 kHz, and the instantaneous frequency taken over its mean is the
+/// speed deviation. `None` when the tone is too short or too low.
+pub fn from_tone(samples: &[f32], sample_rate: u32, carrier_hz: f32, start: usize, end: usize) -> Option<FlutterReport> {
+    let sr = sample_rate as f32;
+    let end = end.min(samples.len());
+    let too_short = (end.saturating_sub(start) as f32) < MIN_TONE_SECS * sr;
+    if too_short || carrier_hz < MIN_CARRIER_HZ || carrier_hz as f64 + DEMOD_BANDWIDTH_HZ >= sr as f64 / 2.0 {
+        return None;
+    }
+    let step = ((sr / DEMOD_RATE).round() as usize).max(1);
+    let rate = sr / step as f32;
+
+    // Mix down, keeping the phase accumulator in range
+    let omega = 2.0 * std::f64::consts::PI * carrier_hz as f64 / sr as f64;
+    let (mut i_mix, mut q_mix) = (Vec::with_capacity(end - start), Vec::with_capacity(end - start));
+    let mut phase = 0.0f64;
+    for &s in &samples[start..end] {
+        i_mix.push(s * phase.cos() as f32);
+        q_mix.push(-s * phase.sin() as f32);
+        phase = (phase + omega) % (2.0 * std::f64::consts::PI);
+    }
+    let i_low = filters::butterworth_lowpass(DEMOD_BANDWIDTH_HZ, 4, sr as f64).process_buffer(&i_mix);
+    let q_low = filters::butterworth_lowpass(DEMOD_BANDWIDTH_HZ, 4, sr as f64).process_buffer(&q_mix);
+    let (i_dec, q_dec): (Vec<f32>, Vec<f32>) = i_low.iter().zip(&q_low).step_by(step).map(|(&i, &q)| (i, q)).unzip();
+    let magnitude: Vec<f32> = i_dec.iter().zip(&q_dec).map(|(i, q)| i.hypot(*q)).collect();
+    let floor = DROPOUT_RATIO * percentile(&magnitude, 0.5);
+    let guard = (DROPOUT_GUARD_SECS * rate).ceil() as usize;
+    let dropout: Vec<bool> = (0..magnitude.len())
+        .map(|k| magnitude[k.saturating_sub(guard)..(k + guard + 1).min(magnitude.len())].iter().any(|&m| m < floor))
+        .collect();
+
+    // Instantaneous frequency from the phase step, held through dropouts
+    let mut frequency = Vec::with_capacity(i_dec.len());
+    let mut last = (q_dec[0].atan2(i_dec[0]), carrier_hz);
+    for k in 1..i_dec.len() {
+        let phase = q_dec[k].atan2(i_dec[k]);
+        if dropout[k] {
+            frequency.push(last.1);
+            continue;
+        }
+        let mut delta = phase - last.0;
+        delta -= (delta / (2.0 * std::f32::consts::PI)).round() * 2.0 * std::f32::consts::PI;
+        last = (phase, carrier_hz + delta * rate / (2.0 * std::f32::consts::PI));
+        frequency.push(last.1);
+    }
+    let edge = (EDGE_SECS * rate) as usize;
+    if frequency.len() <= 2 * edge {
+        return None;
+    }
+    let settled = &frequency[edge..frequency.len() - edge];
+    let mean = settled.iter().map(|&f| f as f64).sum::<f64>() as f32 / settled.len() as f32;
+    let deviation: Vec<f32> = frequency[..frequency.len() - edge].iter().map(|f| f / mean - 1.0).collect();
+
+    let mut report = empty_report(FlutterSource::Tone, start as f32 / sr, end as f32 / sr, TONE_CAVEAT);
+    report.tone_hz = Some(mean);
+    report.nominal_hz = TEST_TONES
+        .iter()
+        .copied()
+        .filter(|n| (mean / n - 1.0).abs() <= SPEED_TOLERANCE)
+        .min_by(|a, b| (mean - a).abs().total_cmp(&(mean - b).abs()));
+    report.speed_error_percent = report.nominal_hz.map(|n| 100.0 * (mean / n - 1.0));
+    fill_report(&mut report, &[deviation], rate, edge);
+    Some(report)
+}
+
+/// Estimate wow from the pitch track: runs of sustained, steady notes, each
+/// taken against a straight line through its log pitch so a glide is not
+/// counted. `None` when no run is long enough.
+pub fn from_pitch(samples: &[f32], sample_rate: u32) -> Option<FlutterReport> {
+    let frames = pitch::track(&pitch::to_analysis_rate(samples, sample_rate));
+    let rate = 1.0 / pitch::HOP_SECS;
+    let min_run = (MIN_PITCH_RUN_SECS * rate) as usize;
+
+    let mut runs: Vec<(usize, Vec<f32>)> = Vec::new();
+    let mut i = 0;
+    while i < frames.len() {
+        let Some(f0) = frames[i].f0 else {
+            i += 1;
+            continue;
+        };
+        let start = i;
+        let mut run = vec![f0.ln()];
+        i += 1;
+        while let Some(f) = frames.get(i).and_then(|f| f.f0) {
+            // The tracker's octave and subharmonic slips, folded back
+            let previous = run[run.len() - 1];
+            let folded = (1..=MAX_PITCH_FOLD)
+                .flat_map(|m| [(f * m as f32).ln(), (f / m as f32).ln()])
+                .min_by(|a, b| (a - previous).abs().total_cmp(&(b - previous).abs()))
+                .unwrap_or(f.ln());
+            if (folded - previous).abs() > MAX_PITCH_STEP {
+                break;
+            }
+            run.push(folded);
+            i += 1;
+        }
+        if run.len() >= min_run {
+            runs.push((start, run));
+        }
+    }
+    let (first, last) = (runs.first()?.0, runs.last().map(|(s, r)| s + r.len())?);
+
+    let segments: Vec<Vec<f32>> = runs
+        .iter()
+        .map(|(_, run)| {
+            let n = run.len() as f32;
+            let mx = (n - 1.0) / 2.0;
+            let my = run.iter().sum::<f32>() / n;
+            let sxx: f32 = (0..run.len()).map(|k| (k as f32 - mx).powi(2)).sum();
+            let slope = run.iter().enumerate().map(|(k, y)| (k as f32 - mx) * (y - my)).sum::<f32>() / sxx;
+            run.iter().enumerate().map(|(k, y)| (y - my - slope * (k as f32 - mx)).exp() - 1.0).collect()
+        })
+        .collect();
+    let mut report = empty_report(
+        FlutterSource::PitchTrack,
+        first as f32 * pitch::HOP_SECS,
+        last as f32 * pitch::HOP_SECS,
+        PITCH_CAVEAT,
+    );
+    fill_report(&mut report, &segments, rate, 0);
+    Some(report)
+}
+
+/// Measure wow and flutter on the longest steady test tone (near `tone_hz`
+/// when given); failing that on `tone_hz` over the whole of `samples`, as a
+/// tone off tape may waver too much in level to be found; failing that, wow
+/// alone from the pitch of sustained notes
+pub fn measure(samples: &[f32], sample_rate: u32, tone_hz: Option<f32>) -> Option<FlutterReport> {
+    let sr = sample_rate as f32;
+    let found = tones::detect_calibration_tones(samples, sample_rate, MIN_TONE_SECS)
+        .into_iter()
+        .filter(|t| tone_hz.is_none_or(|f| (t.frequency / f - 1.0).abs() <= 0.1))
+        .filter(|t| t.frequency >= MIN_CARRIER_HZ)
+        .max_by(|a, b| a.duration.total_cmp(&b.duration));
+    if let Some(tone) = found {
+        let (start, end) = ((tone.start_time * sr) as usize, (tone.end_time * sr) as usize);
+        if let Some(report) = from_tone(samples, sample_rate, tone.frequency, start, end) {
+            return Some(report);
+        }
+    }
+    if let Some(report) = tone_hz.and_then(|f| from_tone(samples, sample_rate, f, 0, samples.len())) {
+        return Some(report);
+    }
+    from_pitch(samples, sample_rate)
+}
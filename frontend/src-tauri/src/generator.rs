@@ -0,0 +1,121 @@
+//! Reference test signals (tones, sweeps, noise)
+
+use crate::sweep;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignalKind {
+    Sine { frequency: f32 },
+    LogSweep { start_freq: f32, end_freq: f32 },
+    WhiteNoise,
+    PinkNoise,
+    Silence,
+}
+
+/// Full description of a signal to generate
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SignalSpec {
+    pub signal: SignalKind,
+    pub duration: f32,
+    #[serde(default = "default_level_db")]
+    pub level_db: f32,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    #[serde(default = "default_channels")]
+    pub channels: usize,
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+}
+
+/// Rates signals are generated at (Hz)
+const RATE_RANGE: (u32, u32) = (8000, 384_000);
+
+fn default_level_db() -> f32 { -20.0 }
+fn default_sample_rate() -> u32 { 48000 }
+fn default_channels() -> usize { 1 }
+fn default_seed() -> u64 { 1 }
+
+/// Small xorshift64* generator so noise output is reproducible per seed
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        (bits as f32 + 0.5) / (1u64 << 24) as f32
+    }
+
+    /// Standard normal deviate (Box-Muller)
+    fn gaussian(&mut self) -> f32 {
+        let (u1, u2) = (self.next_f32(), self.next_f32());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+/// Generate the mono test signal for `spec`. The level is the peak level in
+/// dBFS for tones and sweeps and the RMS level for noise.
+pub fn generate(spec: &SignalSpec) -> Result<Vec<f32>, String> {
+    let SignalSpec { signal: kind, duration, level_db, sample_rate, seed, .. } = *spec;
+    if !(0.0..=3600.0).contains(&duration) {
+        return Err("Duration must be between 0 and 3600 seconds".to_string());
+    }
+    if !(RATE_RANGE.0..=RATE_RANGE.1).contains(&sample_rate) {
+        return Err(format!("Sample rate must be between {} and {} Hz", RATE_RANGE.0, RATE_RANGE.1));
+    }
+    if !level_db.is_finite() {
+        return Err("Level must be a finite number of dB".to_string());
+    }
+    let sr = sample_rate as f32;
+    let n = (duration * sr) as usize;
+    let nyquist = sr / 2.0;
+    let gain = 10f32.powf(level_db / 20.0);
+    let mut rng = Rng(seed.max(1));
+
+    let signal = match kind {
+        SignalKind::Sine { frequency } => {
+            if frequency <= 0.0 || frequency >= nyquist {
+                return Err("Frequency must be between 0 Hz and Nyquist".to_string());
+            }
+            (0..n).map(|i| gain * (2.0 * PI * frequency * i as f32 / sr).sin()).collect()
+        }
+        SignalKind::LogSweep { start_freq, end_freq } => {
+            if start_freq <= 0.0 || end_freq <= start_freq || end_freq > nyquist {
+                return Err("Sweep must rise from above 0 Hz to at most Nyquist".to_string());
+            }
+            let mut out = sweep::log_sweep(start_freq, end_freq, duration, sample_rate);
+            out.iter_mut().for_each(|s| *s *= gain);
+            out
+        }
+        SignalKind::WhiteNoise => (0..n).map(|_| gain * rng.gaussian()).collect(),
+        SignalKind::PinkNoise => {
+            // Paul Kellet's refined -3 dB/octave filter over white noise
+            let mut b = [0.0f32; 7];
+            let mut pink: Vec<f32> = (0..n)
+                .map(|_| {
+                    let white = rng.gaussian();
+                    b[0] = 0.99886 * b[0] + white * 0.0555179;
+                    b[1] = 0.99332 * b[1] + white * 0.0750759;
+                    b[2] = 0.96900 * b[2] + white * 0.153852;
+                    b[3] = 0.86650 * b[3] + white * 0.3104856;
+                    b[4] = 0.55000 * b[4] + white * 0.5329522;
+                    b[5] = -0.7616 * b[5] - white * 0.0168980;
+                    let out = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                    b[6] = white * 0.115926;
+                    out
+                })
+                .collect();
+            let rms = (pink.iter().map(|s| s * s).sum::<f32>() / n.max(1) as f32).sqrt();
+            if rms > 0.0 {
+                pink.iter_mut().for_each(|s| *s *= gain / rms);
+            }
+            pink
+        }
+        SignalKind::Silence => vec![0.0; n],
+    };
+
+    Ok(signal)
+}
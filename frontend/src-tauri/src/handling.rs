@@ -0,0 +1,150 @@
+//! Microphone handling and contact noise: short low-frequency thumps
+
+use crate::filters;
+use serde::{Deserialize, Serialize};
+
+/// Envelope blocks (seconds)
+const BLOCK_SECS: f32 = 0.005;
+/// Thump band below this (Hz), and the band above the voice fundamental
+/// it is compared against (Hz)
+const LOW_CUTOFF_HZ: f64 = 200.0;
+const MID_BAND_HZ: (f64, f64) = (300.0, 4000.0);
+const FILTER_ORDER: usize = 4;
+/// The background the low band rises from: its 10th percentile over this
+/// long before, so the voice's fundamental does not lift it
+const BACKGROUND_SECS: f32 = 1.0;
+/// A thump: blocks where the low band is this far over its background
+/// (dB) and over the mid band (dB), peaking at least this loud (dBFS) and
+/// this far over its median in the time before the onset (dB, seconds),
+/// and dying away, back near the background or this far below the
+/// peak (dB), within this long of the onset (seconds)
+const MIN_ABOVE_BACKGROUND_DB: f32 = 6.0;
+const MIN_LOW_EXCESS_DB: f32 = 10.0;
+const MIN_PEAK_DBFS: f32 = -50.0;
+const MIN_RISE_DB: f32 = 20.0;
+const ONSET_SECS: f32 = 0.1;
+const SETTLED_DB: f32 = 20.0;
+const MAX_THUMP_SECS: f32 = 0.5;
+/// Thumping blocks this close together are one thump (seconds)
+const MERGE_SECS: f32 = 0.05;
+/// A thump no longer than this, after which the mid band rises this far over
+/// its level during the thump within this long, is a plosive pop with its
+/// vowel (seconds, dB, seconds)
+const MAX_PLOSIVE_SECS: f32 = 0.08;
+const SPEECH_RISE_DB: f32 = 10.0;
+const VOICING_SECS: f32 = 0.05;
+/// Below this spread of its zero-crossing intervals (over their mean) the
+/// low band is a tone, a bass note or kick drum rather than a knock
+const MIN_CROSSING_SPREAD: f32 = 0.1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandlingEvent {
+    pub time: f32,              // Onset
+    pub duration_ms: f32,
+    pub peak_dbfs: f32,         // Loudest block of the band below 200 Hz
+    pub rise_db: f32,           // Over that band's median in the 0.1 s before
+    pub low_excess_db: f32,     // Below 200 Hz over 300-4000 Hz at the peak
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+/// RMS level (dBFS) of each block
+fn envelope(samples: &[f32], block: usize) -> Vec<f32> {
+    samples
+        .chunks_exact(block)
+        .map(|c| 10.0 * (c.iter().map(|s| s * s).sum::<f32>() / block as f32).max(1e-20).log10())
+        .collect()
+}
+
+/// Spread of the intervals between upward zero crossings, over their mean
+fn crossing_spread(samples: &[f32]) -> f32 {
+    let crossings: Vec<usize> = (1..samples.len()).filter(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0).collect();
+    let intervals: Vec<f32> = crossings.windows(2).map(|w| (w[1] - w[0]) as f32).collect();
+    if intervals.len() < 2 {
+        return f32::MAX;
+    }
+    let mean = intervals.iter().sum::<f32>() / intervals.len() as f32;
+    (intervals.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / intervals.len() as f32).sqrt() / mean
+}
+
+/// Find thumps from the microphone being handled, knocked or brushed:
+/// the band below 200 Hz jumps far above its recent level, well over the
+/// band above the voice, and falls back within half a second. Voiced speech
+/// keeps most of its energy above 300 Hz and never qualifies; plosive pops,
+/// short and followed at once by their vowel, are left out, as are tonal
+/// bursts (bass notes, kick drums) and low-frequency noise that does not
+/// die away, such as wind or rumble. A short knock right before a word
+/// reads as a plosive, and one under a loud voice is masked by it.
+pub fn detect_handling(samples: &[f32], sample_rate: u32) -> Vec<HandlingEvent> {
+    let sr = sample_rate as f64;
+    if MID_BAND_HZ.1 >= sr / 2.0 {
+        return Vec::new();
+    }
+    let block = ((BLOCK_SECS * sample_rate as f32) as usize).max(1);
+    let lowpassed = filters::butterworth_lowpass(LOW_CUTOFF_HZ, FILTER_ORDER, sr).process_buffer(samples);
+    let low = envelope(&lowpassed, block);
+    let highpassed = filters::butterworth_highpass(MID_BAND_HZ.0, FILTER_ORDER, sr).process_buffer(samples);
+    let mid = envelope(&filters::butterworth_lowpass(MID_BAND_HZ.1, FILTER_ORDER, sr).process_buffer(&highpassed), block);
+
+    let blocks = |secs: f32| ((secs / BLOCK_SECS) as usize).max(1);
+    let (background, onset, merge, max_thump, max_plosive, voicing) = (
+        blocks(BACKGROUND_SECS),
+        blocks(ONSET_SECS),
+        blocks(MERGE_SECS),
+        blocks(MAX_THUMP_SECS),
+        blocks(MAX_PLOSIVE_SECS),
+        blocks(VOICING_SECS),
+    );
+    let before = |levels: &[f32], i: usize| percentile(&levels[i.saturating_sub(background)..i], 0.1);
+
+    let mut events = Vec::new();
+    let mut i = background;
+    while i < low.len() {
+        let low_background = before(&low, i);
+        let thumping = |k: usize| low[k] - mid[k] >= MIN_LOW_EXCESS_DB && low[k] - low_background >= MIN_ABOVE_BACKGROUND_DB;
+        if !thumping(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i;
+        while i < low.len() && i - end <= merge {
+            if thumping(i) {
+                end = i;
+            }
+            i += 1;
+        }
+        i = end + 1;
+        let peak = (start..i).fold(start, |best, k| if low[k] > low[best] { k } else { best });
+        let rise_db = low[peak] - percentile(&low[start.saturating_sub(onset)..start], 0.5);
+        // Died away within the thump's time, which the low band under wind
+        // or a rumble does not
+        let settles = low[i..(start + max_thump).clamp(i, low.len())]
+            .iter()
+            .any(|&l| l < (low_background + MIN_ABOVE_BACKGROUND_DB).max(low[peak] - SETTLED_DB));
+        if !settles || low[peak] < MIN_PEAK_DBFS || rise_db < MIN_RISE_DB {
+            continue;
+        }
+        if crossing_spread(&lowpassed[start * block..i * block]) < MIN_CROSSING_SPREAD {
+            continue;
+        }
+        let thump_mid = mid[start..i].iter().copied().fold(f32::MIN, f32::max);
+        let voiced = mid[i..(i + voicing).min(mid.len())].iter().any(|&m| m - thump_mid >= SPEECH_RISE_DB);
+        if i - start <= max_plosive && voiced {
+            continue;
+        }
+        let (time, end_time) = ((start * block) as f32 / sample_rate as f32, (i * block) as f32 / sample_rate as f32);
+        events.push(HandlingEvent {
+            time,
+            duration_ms: 1000.0 * (end_time - time),
+            peak_dbfs: low[peak],
+            rise_db,
+            low_excess_db: low[peak] - mid[peak],
+        });
+    }
+    events
+}
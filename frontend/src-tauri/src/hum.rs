@@ -0,0 +1,341 @@
+//! Mains hum characterization: level, width and stability of every harmonic
+
+use crate::enf::{self, GRID_FREQS};
+use crate::filters::Biquad;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Rate the signal is decimated to, leaving room for harmonics up to 1.6 kHz
+const ANALYSIS_RATE: u32 = 4000;
+/// Analysis frame and hop (seconds); the frame sets a least width of 0.36 Hz
+const FRAME_SECS: f32 = 4.0;
+const HOP_SECS: f32 = 2.0;
+/// Zero padding factor, for a finer bin grid under the peak interpolation
+const PAD: usize = 2;
+/// Harmonics examined, as far as they fit below the analysis band
+const MAX_HARMONIC: usize = 25;
+/// Deviation searched either side of each harmonic (Hz, per harmonic order)
+const SEARCH_HZ: f32 = 0.3;
+/// Background measured this far beyond the search range either side (Hz)
+const BACKGROUND_HZ: f32 = 5.0;
+/// Peak-over-background strength for a harmonic to count as hum (dB), as
+/// in the ENF detection
+const PRESENT_DB: f32 = 10.0;
+
+/// Removal: notch Q when the notches follow the ENF track (about 1 Hz wide
+/// at the fundamental, wider in proportion above), and the most a fixed
+/// notch may have
+const TRACKED_Q: f32 = 50.0;
+/// Removal: notch frequencies are updated this often (seconds), and
+/// harmonics above this fraction of the sample rate are left alone
+const RETUNE_SECS: f32 = 0.01;
+const MAX_NOTCH_FRACTION: f32 = 0.45;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HumHarmonic {
+    pub order: usize,
+    pub frequency_hz: f32,          // Median measured frequency
+    pub level_dbfs: f32,            // Median amplitude re full scale
+    pub above_background_db: f32,   // Median peak over the surrounding spectrum
+    pub width_hz: Option<f32>,      // -3 dB width of the long-term peak, frequency wander included
+    pub suggested_q: Option<f32>,   // Notch Q that covers that width
+    pub frequency_std_hz: f32,      // Over the frames carrying it
+    pub level_std_db: f32,
+    pub presence: f32,              // Fraction of frames carrying it
+    pub present: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HumReport {
+    pub grid_freq: f32,
+    pub present: bool,                      // At least one harmonic carries hum
+    pub fundamental_hz: f32,                // Median mains frequency, from the strongest harmonic
+    pub total_level_dbfs: Option<f32>,      // Power sum of the harmonics carrying hum
+    pub highest_harmonic: Option<usize>,    // Highest order carrying hum
+    pub odd_even_db: Option<f32>,           // Power of the odd harmonics over the even ones
+    pub frame_secs: f32,
+    pub harmonics: Vec<HumHarmonic>,
+}
+
+/// Bins of one harmonic: the search range and the background range
+struct HarmonicBins {
+    order: usize,
+    lo: usize,
+    hi: usize,
+    background_lo: usize,
+    background_hi: usize,
+}
+
+/// One frame's reading of a harmonic
+#[derive(Clone, Copy)]
+struct Reading {
+    frequency: f32,
+    strength_db: f32,
+    amplitude: f32,
+}
+
+fn median(values: &[f32]) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get(sorted.len() / 2).copied().unwrap_or(0.0)
+}
+
+fn std_dev(values: &[f32]) -> f32 {
+    let mean = values.iter().sum::<f32>() / values.len().max(1) as f32;
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len().max(1) as f32).sqrt()
+}
+
+/// Harmonics of `grid_freq` whose search and background ranges fit below
+/// the decimation filter's passband edge
+fn harmonic_bins(grid_freq: f32, rate: f32, bin_hz: f32, n_bins: usize) -> Vec<HarmonicBins> {
+    let bin = |f: f32| ((f.max(0.0) / bin_hz).round() as usize).min(n_bins - 1);
+    (1..=MAX_HARMONIC)
+        .map_while(|order| {
+            let center = grid_freq * order as f32;
+            let search = SEARCH_HZ * order as f32;
+            (center + search + BACKGROUND_HZ < 0.4 * rate).then(|| HarmonicBins {
+                order,
+                lo: bin(center - search),
+                hi: bin(center + search),
+                background_lo: bin(center - search - BACKGROUND_HZ),
+                background_hi: bin(center + search + BACKGROUND_HZ),
+            })
+        })
+        .collect()
+}
+
+/// Width of the peak in `power[bins.lo..=bins.hi]` where it has fallen
+/// halfway (3 dB) to the background, in bins
+fn peak_width(power: &[f32], bins: &HarmonicBins) -> usize {
+    let peak = (bins.lo..=bins.hi).max_by(|&a, &b| power[a].total_cmp(&power[b])).unwrap_or(bins.lo);
+    let background = median(&power[bins.background_lo..=bins.background_hi]);
+    let half = background + (power[peak] - background) / 2.0;
+    let left = (bins.background_lo..=peak).rev().find(|&i| power[i] < half).map_or(bins.background_lo, |i| i + 1);
+    let right = (peak..=bins.background_hi).find(|&i| power[i] < half).map_or(bins.background_hi, |i| i - 1);
+    right - left + 1
+}
+
+/// Characterize the hum on `grid_freq`, or on whichever of 50/60 Hz carries
+/// more of it. Every harmonic is followed frame by frame for its frequency,
+/// its amplitude (the power in the window's main lobe above the local
+/// background) and its strength over that background; the long-term
+/// spectrum gives the width a removal notch must cover, the frequency
+/// wander included. `None` when the recording is shorter than one frame.
+pub fn analyze(samples: &[f32], sample_rate: u32, grid_freq: Option<f32>) -> Option<HumReport> {
+    let (signal, rate) = enf::decimate(samples, sample_rate, ANALYSIS_RATE);
+    let frame = (FRAME_SECS * rate) as usize;
+    let hop = ((HOP_SECS * rate) as usize).max(1);
+    if frame == 0 || signal.len() < frame {
+        return None;
+    }
+    let n_fft = (frame * PAD).next_power_of_two();
+    let n_bins = n_fft / 2 + 1;
+    let bin_hz = rate / n_fft as f32;
+    let window = WindowType::Hann.coefficients(frame);
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+    // The Hann main lobe spans two frame-resolution bins either side
+    let lobe = ((2.0 * rate / frame as f32 / bin_hz).round() as usize).max(1);
+
+    let grids: Vec<(f32, Vec<HarmonicBins>)> = match grid_freq {
+        Some(f) => vec![f],
+        None => GRID_FREQS.to_vec(),
+    }
+    .into_iter()
+    .map(|g| (g, harmonic_bins(g, rate, bin_hz, n_bins)))
+    .filter(|(_, bins)| !bins.is_empty())
+    .collect();
+    if grids.is_empty() {
+        return None;
+    }
+
+    let starts: Vec<usize> = (0..).map(|i| i * hop).take_while(|&start| start + frame <= signal.len()).collect();
+    // Readings per frame, grid and harmonic, and the summed power spectrum
+    let (frames, long_term) = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(n_fft);
+            let mut input = vec![0.0f32; n_fft];
+            for (i, (&s, &w)) in signal[start..start + frame].iter().zip(&window).enumerate() {
+                input[i] = s * w;
+            }
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+            let readings: Vec<Vec<Reading>> = grids
+                .iter()
+                .map(|(_, harmonics)| {
+                    harmonics
+                        .iter()
+                        .map(|h| {
+                            let (peak_bin, peak_power) = enf::interpolated_peak(&power, h.lo, h.hi);
+                            let background = median(&power[h.background_lo..=h.background_hi]).max(1e-30);
+                            let peak = peak_bin.round() as usize;
+                            let lobe_bins = &power[peak.saturating_sub(lobe)..(peak + lobe + 1).min(n_bins)];
+                            let excess = (lobe_bins.iter().sum::<f32>() - background * lobe_bins.len() as f32).max(0.0);
+                            Reading {
+                                frequency: peak_bin * bin_hz,
+                                strength_db: 10.0 * (peak_power.max(1e-30) / background).log10(),
+                                // A sinusoid's power over the positive bins is
+                                // n_fft · A² · Σw² / 4
+                                amplitude: (4.0 * excess / (n_fft as f32 * window_power)).sqrt(),
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            (vec![readings], power)
+        })
+        .reduce(
+            || (Vec::new(), vec![0.0; n_bins]),
+            |(mut frames, mut sum), (more, power)| {
+                frames.extend(more);
+                sum.iter_mut().zip(&power).for_each(|(s, p)| *s += p);
+                (frames, sum)
+            },
+        );
+
+    let reports: Vec<HumReport> = grids
+        .iter()
+        .enumerate()
+        .map(|(g, (grid_freq, bins))| {
+            let harmonics: Vec<HumHarmonic> = bins
+                .iter()
+                .enumerate()
+                .map(|(k, h)| {
+                    let readings: Vec<Reading> = frames.iter().map(|f| f[g][k]).collect();
+                    let carrying: Vec<Reading> = readings.iter().copied().filter(|r| r.strength_db > PRESENT_DB).collect();
+                    let above_background_db = median(&readings.iter().map(|r| r.strength_db).collect::<Vec<_>>());
+                    let present = above_background_db > PRESENT_DB;
+                    let measured = if carrying.is_empty() { &readings } else { &carrying };
+                    let frequencies: Vec<f32> = measured.iter().map(|r| r.frequency).collect();
+                    let levels: Vec<f32> = measured.iter().map(|r| 20.0 * r.amplitude.max(1e-10).log10()).collect();
+                    let frequency_hz = median(&frequencies);
+                    let width_hz = present.then(|| peak_width(&long_term, h) as f32 * bin_hz);
+                    HumHarmonic {
+                        order: h.order,
+                        frequency_hz,
+                        level_dbfs: median(&levels),
+                        above_background_db,
+                        width_hz,
+                        suggested_q: width_hz.map(|w| frequency_hz / w),
+                        frequency_std_hz: std_dev(&frequencies),
+                        level_std_db: std_dev(&levels),
+                        presence: carrying.len() as f32 / readings.len().max(1) as f32,
+                        present,
+                    }
+                })
+                .collect();
+
+            let power = |h: &HumHarmonic| 10f32.powf(h.level_dbfs / 10.0);
+            let carrying: Vec<&HumHarmonic> = harmonics.iter().filter(|h| h.present).collect();
+            let (odd, even): (Vec<&HumHarmonic>, Vec<&HumHarmonic>) = carrying.iter().partition(|h| h.order % 2 == 1);
+            let strongest = carrying
+                .iter()
+                .copied()
+                .chain(harmonics.iter().filter(|_| carrying.is_empty()))
+                .max_by(|a, b| a.above_background_db.total_cmp(&b.above_background_db));
+            HumReport {
+                grid_freq: *grid_freq,
+                present: !carrying.is_empty(),
+                fundamental_hz: strongest.map_or(*grid_freq, |h| h.frequency_hz / h.order as f32),
+                total_level_dbfs: (!carrying.is_empty())
+                    .then(|| 10.0 * carrying.iter().map(|h| power(h)).sum::<f32>().log10()),
+                highest_harmonic: carrying.iter().map(|h| h.order).max(),
+                odd_even_db: (!odd.is_empty() && !even.is_empty()).then(|| {
+                    10.0 * (odd.iter().map(|h| power(h)).sum::<f32>() / even.iter().map(|h| power(h)).sum::<f32>()).log10()
+                }),
+                frame_secs: FRAME_SECS,
+                harmonics,
+            }
+        })
+        .collect();
+
+    // As in the ENF detection: the grid with the most hum evidence, or the
+    // strongest when neither carries any
+    let evidence = |r: &HumReport| -> f32 {
+        r.harmonics.iter().filter(|h| h.present).map(|h| 10f32.powf(h.above_background_db / 10.0)).sum()
+    };
+    let strongest = |r: &HumReport| r.harmonics.iter().map(|h| h.above_background_db).fold(f32::MIN, f32::max);
+    reports
+        .into_iter()
+        .max_by(|a, b| evidence(a).total_cmp(&evidence(b)).then(strongest(a).total_cmp(&strongest(b))))
+}
+
+/// The notches `remove` applies: the mains frequency over time and the
+/// harmonics to cut out
+#[derive(Debug, Clone)]
+pub struct RemovalPlan {
+    pub times: Vec<f32>,                // Seconds into the audio being filtered
+    pub fundamental_hz: Vec<f32>,
+    pub harmonics: Vec<(usize, f32)>,   // Order and notch Q
+}
+
+impl RemovalPlan {
+    /// Notch every harmonic `report` finds hum on. With an ENF track on the
+    /// same grid the notches follow it and can stay narrow; without one they
+    /// sit on the median frequency, as wide as the measured wander. `None`
+    /// when there is no hum.
+    pub fn new(report: &HumReport, track: Option<&enf::EnfTrack>) -> Option<Self> {
+        let track = track.filter(|t| t.grid_freq == report.grid_freq && !t.times.is_empty());
+        let harmonics: Vec<(usize, f32)> = report
+            .harmonics
+            .iter()
+            .filter(|h| h.present)
+            .map(|h| (h.order, if track.is_some() { TRACKED_Q } else { h.suggested_q.map_or(TRACKED_Q, |q| q.min(TRACKED_Q)) }))
+            .collect();
+        if harmonics.is_empty() {
+            return None;
+        }
+        let (times, fundamental_hz) = match track {
+            Some(track) => (track.times.clone(), track.freqs.clone()),
+            None => (vec![0.0], vec![report.fundamental_hz]),
+        };
+        Some(Self { times, fundamental_hz, harmonics })
+    }
+
+    /// The plan for audio that starts `offset` seconds into the recording
+    pub fn shifted(&self, offset: f32) -> Self {
+        Self { times: self.times.iter().map(|t| t - offset).collect(), ..self.clone() }
+    }
+
+    /// Mains frequency at `time`, interpolated along the track and held
+    /// beyond its ends
+    fn fundamental_at(&self, time: f32) -> f32 {
+        let next = self.times.partition_point(|&t| t < time);
+        if next == 0 {
+            return self.fundamental_hz[0];
+        }
+        if next == self.times.len() {
+            return self.fundamental_hz[next - 1];
+        }
+        let (t0, t1) = (self.times[next - 1], self.times[next]);
+        let (f0, f1) = (self.fundamental_hz[next - 1], self.fundamental_hz[next]);
+        f0 + (f1 - f0) * (time - t0) / (t1 - t0)
+    }
+}
+
+/// Cut the hum out of one channel with a bank of notches on the mains
+/// harmonics, retuned as the mains frequency moves
+pub fn remove(samples: &[f32], sample_rate: u32, plan: &RemovalPlan) -> Vec<f32> {
+    let sr = sample_rate as f32;
+    let highest = plan.fundamental_hz.iter().fold(0.0f32, |m, &f| m.max(f));
+    let harmonics: Vec<(usize, f32)> =
+        plan.harmonics.iter().copied().filter(|&(order, _)| order as f32 * highest < MAX_NOTCH_FRACTION * sr).collect();
+    let design = |order: usize, q: f32, fundamental: f32| Biquad::notch((order as f32 * fundamental) as f64, q as f64, sr as f64);
+    let fundamental = plan.fundamental_at(0.0);
+    let mut notches: Vec<Biquad> = harmonics.iter().map(|&(order, q)| design(order, q, fundamental)).collect();
+
+    let block = ((RETUNE_SECS * sr) as usize).max(1);
+    let mut out = Vec::with_capacity(samples.len());
+    for (i, chunk) in samples.chunks(block).enumerate() {
+        let fundamental = plan.fundamental_at((i * block) as f32 / sr);
+        for (notch, &(order, q)) in notches.iter_mut().zip(&harmonics) {
+            notch.retune(&design(order, q, fundamental));
+        }
+        out.extend(chunk.iter().map(|&x| notches.iter_mut().fold(x, |y, notch| notch.process(y))));
+    }
+    out
+}
@@ -0,0 +1,366 @@
+//! Label tracks: findings laid out as Audacity labels, to review them in
+//! other editors, and annotations read back from Audacity, Praat and ELAN
+
+use crate::markers::FrequencyRange;
+use quick_xml::events::Event;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Kinds of finding a label track can carry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelSource {
+    Splices,
+    PhaseResets,
+    EnfPhaseJumps,
+    Clicks,
+    Clipping,
+    HandlingNoise,
+    /// Speech segments from voice activity detection
+    Speech,
+    /// Stretches copied from elsewhere in the recording, at the copy
+    Duplicates,
+    /// The examiner's markers and regions
+    Markers,
+}
+
+impl LabelSource {
+    pub const ALL: [LabelSource; 9] = [
+        Self::Splices,
+        Self::PhaseResets,
+        Self::EnfPhaseJumps,
+        Self::Clicks,
+        Self::Clipping,
+        Self::HandlingNoise,
+        Self::Speech,
+        Self::Duplicates,
+        Self::Markers,
+    ];
+}
+
+/// Annotation files `import_labels` reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelFormat {
+    /// Audacity label track (tab-separated text)
+    Audacity,
+    /// Praat TextGrid, long or short text format
+    TextGrid,
+    /// ELAN annotation document (.eaf)
+    Elan,
+}
+
+impl LabelFormat {
+    /// The format a file name's extension stands for, Audacity when it names
+    /// none
+    pub fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("textgrid") => Self::TextGrid,
+            Some("eaf") => Self::Elan,
+            _ => Self::Audacity,
+        }
+    }
+}
+
+/// A point (`start == end`) or region label
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+    pub frequency: Option<FrequencyRange>,  // Audacity's spectral selection
+}
+
+impl Label {
+    pub fn point(time: f32, text: String) -> Self {
+        Self { start: time, end: time, text, frequency: None }
+    }
+}
+
+/// A label read from a file, with the tier it was on where the format has
+/// tiers
+#[derive(Debug, Clone)]
+pub struct TierLabel {
+    pub tier: Option<String>,
+    pub label: Label,
+}
+
+/// `labels` as an Audacity label track: one `start<TAB>end<TAB>text` line
+/// each, in seconds, in order of start, followed by a `\<TAB>low<TAB>high`
+/// line (Hz) for those with frequencies. Tabs and line breaks in the text
+/// become spaces, as the format has no escaping.
+pub fn audacity(labels: &[Label]) -> String {
+    let mut sorted: Vec<&Label> = labels.iter().collect();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+    sorted
+        .iter()
+        .map(|label| {
+            let mut line = format!("{:.6}\t{:.6}\t{}\n", label.start, label.end, label.text.replace(['\t', '\r', '\n'], " "));
+            if let Some(range) = label.frequency {
+                line.push_str(&format!("\\\t{:.6}\t{:.6}\n", range.low_hz, range.high_hz));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Decode a text file: UTF-8, or UTF-16 with a byte order mark (Praat
+/// saves TextGrids with non-ASCII text that way)
+pub fn decode_text(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |big_endian: bool| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|b| if big_endian { u16::from_be_bytes([b[0], b[1]]) } else { u16::from_le_bytes([b[0], b[1]]) })
+            .collect();
+        String::from_utf16(&units).map_err(|_| "The file is not valid UTF-16".to_string())
+    };
+    match bytes {
+        [0xFE, 0xFF, ..] => utf16(true),
+        [0xFF, 0xFE, ..] => utf16(false),
+        _ => {
+            let text = String::from_utf8(bytes.to_vec()).map_err(|_| "The file is not UTF-8 or UTF-16 text".to_string())?;
+            Ok(text.strip_prefix('\u{feff}').map(str::to_string).unwrap_or(text))
+        }
+    }
+}
+
+/// Parse a file in `format`
+pub fn parse(text: &str, format: LabelFormat) -> Result<Vec<TierLabel>, String> {
+    match format {
+        LabelFormat::Audacity => parse_audacity(text),
+        LabelFormat::TextGrid => parse_textgrid(text),
+        LabelFormat::Elan => parse_elan(text),
+    }
+}
+
+/// An Audacity label track. The frequency line of a spectral label (one
+/// starting with a backslash) gives the label before it its frequencies;
+/// Audacity writes -1 for a bound left open, and such ranges are dropped.
+fn parse_audacity(text: &str) -> Result<Vec<TierLabel>, String> {
+    let mut labels: Vec<TierLabel> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('\\') {
+            let mut fields = rest.split('\t').filter(|f| !f.trim().is_empty()).map(|f| f.trim().parse::<f32>().ok());
+            if let (Some(Some(low_hz)), Some(Some(high_hz)), Some(last)) = (fields.next(), fields.next(), labels.last_mut()) {
+                if low_hz >= 0.0 && high_hz > low_hz {
+                    last.label.frequency = Some(FrequencyRange { low_hz, high_hz });
+                }
+            }
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let mut time = || fields.next().and_then(|f| f.trim().parse::<f32>().ok());
+        let (Some(start), Some(end)) = (time(), time()) else {
+            return Err(format!("Line {} is not an Audacity label (start, end and text separated by tabs)", n + 1));
+        };
+        let text = fields.next().unwrap_or("").to_string();
+        labels.push(TierLabel { tier: None, label: Label { start, end, text, frequency: None } });
+    }
+    Ok(labels)
+}
+
+enum Token {
+    Number(f64),
+    Text(String),
+    Flag(String),
+}
+
+/// The values of a TextGrid in order. The long and the short text format
+/// hold the same values, the long one with names (`xmin =`), indices
+/// (`[1]`) and flags (`<exists>`) between them; names and indices are
+/// dropped, as are `!` comments.
+fn textgrid_tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '"' {
+                        // A doubled quote stands for one inside the text
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    value.push(c);
+                }
+                tokens.push(Token::Text(value));
+            }
+            '!' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '[' => while chars.next().is_some_and(|c| c != ']') {},
+            '<' => tokens.push(Token::Flag(std::iter::from_fn(|| chars.next_if(|&c| c != '>')).collect())),
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut number = String::from(c);
+                number.extend(std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_digit() || "eE.+-".contains(*c))));
+                if let Ok(value) = number.parse() {
+                    tokens.push(Token::Number(value));
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => while chars.next_if(|c| c.is_alphanumeric() || *c == '_').is_some() {},
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// Reads values off the tokens of a TextGrid
+struct Cursor<'a>(std::slice::Iter<'a, Token>);
+
+impl Cursor<'_> {
+    fn number(&mut self) -> Result<f32, String> {
+        match self.0.next() {
+            Some(Token::Number(n)) => Ok(*n as f32),
+            _ => Err("Not a Praat TextGrid in text format".to_string()),
+        }
+    }
+
+    fn text(&mut self) -> Result<String, String> {
+        match self.0.next() {
+            Some(Token::Text(t)) => Ok(t.clone()),
+            _ => Err("Not a Praat TextGrid in text format".to_string()),
+        }
+    }
+}
+
+/// A Praat TextGrid in text format. Intervals with empty text (the gaps
+/// Praat fills every interval tier with) are skipped.
+fn parse_textgrid(text: &str) -> Result<Vec<TierLabel>, String> {
+    let tokens = textgrid_tokens(text);
+    let mut cursor = Cursor(tokens.iter());
+    if cursor.text()? != "ooTextFile" || cursor.text()? != "TextGrid" {
+        return Err("Not a Praat TextGrid in text format".to_string());
+    }
+    let (_start, _end) = (cursor.number()?, cursor.number()?);
+    // `<exists>`, or `<absent>` and no tiers at all
+    match cursor.0.next() {
+        Some(Token::Flag(flag)) if flag == "exists" => {}
+        _ => return Ok(Vec::new()),
+    }
+
+    let mut labels = Vec::new();
+    let tiers = cursor.number()? as usize;
+    for _ in 0..tiers {
+        let class = cursor.text()?;
+        let tier = cursor.text()?;
+        let (_start, _end) = (cursor.number()?, cursor.number()?);
+        let count = cursor.number()? as usize;
+        for _ in 0..count {
+            let label = match class.as_str() {
+                "IntervalTier" => Label { start: cursor.number()?, end: cursor.number()?, text: cursor.text()?, frequency: None },
+                "TextTier" => Label::point(cursor.number()?, cursor.text()?),
+                other => return Err(format!("Unknown TextGrid tier class {}", other)),
+            };
+            if !label.text.trim().is_empty() {
+                labels.push(TierLabel { tier: Some(tier.clone()), label });
+            }
+        }
+    }
+    Ok(labels)
+}
+
+/// An annotation of an ELAN document: aligned to time slots, or referring
+/// to a parent annotation whose times it shares
+enum Anchor {
+    Slots(String, String),
+    Parent(String),
+}
+
+/// Times of an ELAN annotation, following references up to an aligned one
+fn anchor_times<'a>(mut anchor: &'a Anchor, by_id: &HashMap<&str, &'a Anchor>, slots: &HashMap<String, f32>) -> Option<(f32, f32)> {
+    // Bounded, in case references go round in a circle
+    for _ in 0..=by_id.len() {
+        match anchor {
+            Anchor::Slots(start, end) => return Some((*slots.get(start)?, *slots.get(end)?)),
+            Anchor::Parent(parent) => anchor = by_id.get(parent.as_str())?,
+        }
+    }
+    None
+}
+
+/// An ELAN annotation document. Referring annotations take the times of
+/// the annotation they refer to; those on time slots without a time are
+/// skipped.
+fn parse_elan(text: &str) -> Result<Vec<TierLabel>, String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("Not a valid ELAN document: {}", e);
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut slots: HashMap<String, f32> = HashMap::new();
+    // (id, tier, anchor, value) in document order
+    let mut annotations: Vec<(String, String, Anchor, String)> = Vec::new();
+    let mut tier = String::new();
+    let mut value: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(|e| invalid(&e))? {
+            Event::Start(element) | Event::Empty(element) => {
+                let attribute = |name: &str| -> Result<Option<String>, String> {
+                    match element.try_get_attribute(name).map_err(|e| invalid(&e))? {
+                        Some(a) => Ok(Some(a.normalized_value(quick_xml::XmlVersion::Implicit1_0).map_err(|e| invalid(&e))?.into_owned())),
+                        None => Ok(None),
+                    }
+                };
+                match element.local_name().as_ref() {
+                    "TIME_SLOT" => {
+                        let time = attribute("TIME_VALUE")?.and_then(|v| v.trim().parse::<f32>().ok());
+                        if let (Some(id), Some(ms)) = (attribute("TIME_SLOT_ID")?, time) {
+                            slots.insert(id, ms / 1000.0);
+                        }
+                    }
+                    "TIER" => tier = attribute("TIER_ID")?.unwrap_or_default(),
+                    "ALIGNABLE_ANNOTATION" => {
+                        if let (Some(id), Some(start), Some(end)) = (attribute("ANNOTATION_ID")?, attribute("TIME_SLOT_REF1")?, attribute("TIME_SLOT_REF2")?) {
+                            annotations.push((id, tier.clone(), Anchor::Slots(start, end), String::new()));
+                        }
+                    }
+                    "REF_ANNOTATION" => {
+                        if let (Some(id), Some(parent)) = (attribute("ANNOTATION_ID")?, attribute("ANNOTATION_REF")?) {
+                            annotations.push((id, tier.clone(), Anchor::Parent(parent), String::new()));
+                        }
+                    }
+                    "ANNOTATION_VALUE" => value = Some(String::new()),
+                    _ => {}
+                }
+            }
+            Event::Text(content) => {
+                if let Some(value) = value.as_mut() {
+                    value.push_str(&content.xml10_content());
+                }
+            }
+            Event::CData(content) => {
+                if let Some(value) = value.as_mut() {
+                    value.push_str(&content.xml10_content());
+                }
+            }
+            Event::GeneralRef(reference) => {
+                if let Some(value) = value.as_mut() {
+                    match reference.resolve_char_ref().map_err(|e| invalid(&e))? {
+                        Some(c) => value.push(c),
+                        None => value.push_str(quick_xml::escape::resolve_predefined_entity(&reference.xml10_content()).unwrap_or_default()),
+                    }
+                }
+            }
+            Event::End(element) if element.local_name().as_ref() == "ANNOTATION_VALUE" => {
+                if let (Some(text), Some(annotation)) = (value.take(), annotations.last_mut()) {
+                    annotation.3 = text.trim().to_string();
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let by_id: HashMap<&str, &Anchor> = annotations.iter().map(|(id, _, anchor, _)| (id.as_str(), anchor)).collect();
+    Ok(annotations
+        .iter()
+        .filter(|(_, _, _, text)| !text.is_empty())
+        .filter_map(|(_, tier, anchor, text)| {
+            let (start, end) = anchor_times(anchor, &by_id, &slots)?;
+            Some(TierLabel { tier: Some(tier.clone()), label: Label { start, end, text: text.clone(), frequency: None } })
+        })
+        .collect())
+}
@@ -0,0 +1,111 @@
+//! Lossy review copies, encoded by the LAME and oggenc command-line tools
+
+use crate::export::{self, ExportBuffer, ExportFormat};
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+
+/// Quality used when neither a bitrate nor a quality is given (0-10)
+const DEFAULT_QUALITY: f32 = 6.0;
+/// Bitrates (kbit/s) either encoder is asked for
+const BITRATE_RANGE: (u32, u32) = (8, 500);
+/// MP3 carries at most two channels
+const MP3_MAX_CHANNELS: usize = 2;
+
+/// The buffer as a 16-bit WAV file in memory, the input both encoders read
+fn wav_bytes(buffer: &ExportBuffer) -> Result<Vec<u8>, String> {
+    let spec = hound::WavSpec {
+        channels: buffer.channels as u16,
+        sample_rate: buffer.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(|e| format!("Failed to start WAV stream: {}", e))?;
+    for code in export::to_integer(&buffer.samples, 16, false).0 {
+        writer.write_sample(code as i16).map_err(|e| format!("Failed to write sample: {}", e))?;
+    }
+    writer.finalize().map_err(|e| format!("Failed to finalize WAV stream: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+/// Encoder program and its arguments. `quality` runs 0 (smallest) to 10
+/// (best): oggenc takes it as it is, LAME as -V 9 (smallest) to 0 (best).
+fn command_line(format: ExportFormat, path: &str, bitrate_kbps: Option<u32>, quality: f32) -> (&'static str, Vec<String>) {
+    match format {
+        ExportFormat::Mp3 => {
+            let rate = match bitrate_kbps {
+                Some(kbps) => vec!["-b".to_string(), kbps.to_string()],
+                None => vec!["-V".to_string(), format!("{:.0}", 9.0 - 0.9 * quality)],
+            };
+            let args = ["--quiet".to_string()].into_iter().chain(rate).chain(["-".to_string(), path.to_string()]);
+            ("lame", args.collect())
+        }
+        _ => {
+            let rate = match bitrate_kbps {
+                Some(kbps) => vec!["-b".to_string(), kbps.to_string()],
+                None => vec!["-q".to_string(), format!("{:.1}", quality)],
+            };
+            let args = ["-Q".to_string()].into_iter().chain(rate).chain(["-o".to_string(), path.to_string(), "-".to_string()]);
+            ("oggenc", args.collect())
+        }
+    }
+}
+
+/// Encode `buffer` to an MP3 or Ogg Vorbis file at `path`. The audio is
+/// piped as 16-bit WAV to `lame` or `oggenc`, which must be on the PATH;
+/// either a constant bitrate or a variable bitrate quality (0-10) sets the
+/// size.
+pub fn encode(
+    buffer: &ExportBuffer,
+    format: ExportFormat,
+    path: &str,
+    bitrate_kbps: Option<u32>,
+    quality: Option<f32>,
+) -> Result<(), String> {
+    if !matches!(format, ExportFormat::Mp3 | ExportFormat::Vorbis) {
+        return Err(format!("{:?} is not encoded with lame or oggenc", format));
+    }
+    if let Some(kbps) = bitrate_kbps.filter(|k| !(BITRATE_RANGE.0..=BITRATE_RANGE.1).contains(k)) {
+        return Err(format!("Bitrate must be between {} and {} kbit/s, not {}", BITRATE_RANGE.0, BITRATE_RANGE.1, kbps));
+    }
+    let quality = quality.unwrap_or(DEFAULT_QUALITY);
+    if !(0.0..=10.0).contains(&quality) {
+        return Err(format!("Quality must be between 0 and 10, not {}", quality));
+    }
+    if format == ExportFormat::Mp3 {
+        if buffer.channels > MP3_MAX_CHANNELS {
+            return Err(format!("MP3 holds at most two channels, not {}; pick them with channel_mask", buffer.channels));
+        }
+        if format.required_rate(buffer.sample_rate).is_some() {
+            return Err(format!("MP3 holds sample rates up to 48 kHz, not {} Hz", buffer.sample_rate));
+        }
+    }
+
+    let wav = wav_bytes(buffer)?;
+    let (program, args) = command_line(format, path, bitrate_kbps, quality);
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                let name = if format == ExportFormat::Mp3 { "MP3" } else { "Ogg Vorbis" };
+                format!("{} export needs the `{}` encoder on the PATH", name, program)
+            }
+            _ => format!("Failed to start {}: {}", program, e),
+        })?;
+
+    // Feed the encoder from another thread so its messages cannot block it
+    let mut stdin = child.stdin.take().ok_or("Encoder input unavailable")?;
+    let feeder = std::thread::spawn(move || stdin.write_all(&wav));
+    let output = child.wait_with_output().map_err(|e| format!("{} failed: {}", program, e))?;
+    let fed = feeder.join().map_err(|_| format!("Feeding {} panicked", program))?;
+
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed ({}): {}", program, output.status, message.trim()));
+    }
+    fed.map_err(|e| format!("Failed to pipe audio to {}: {}", program, e))
+}
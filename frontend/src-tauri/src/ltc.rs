@@ -0,0 +1,307 @@
+//! SMPTE linear timecode (LTC) decoding
+
+use crate::filters;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Bits in an LTC frame and the sync word that ends it (bits 64-79 in the
+/// order they arrive)
+const FRAME_BITS: usize = 80;
+const SYNC_WORD: u128 = 0b0011_1111_1111_1101;
+/// High-pass (Hz) taking DC and rumble out before the zero crossings; the
+/// slowest LTC (all zeros at 24 fps) is a 960 Hz square wave
+const HIGHPASS_HZ: f64 = 100.0;
+/// Blocks (seconds) the signal level is measured over, and the share of
+/// that RMS a swing must pass to count as a transition
+const LEVEL_BLOCK_SECS: f32 = 0.01;
+const HYSTERESIS: f32 = 0.3;
+/// Bit cell (seconds) assumed whenever sync is lost, between 24 and 30 fps;
+/// it then follows the signal, so varispeed and any of the rates lock
+const INITIAL_CELL_SECS: f32 = 1.0 / (80.0 * 27.0);
+const CELL_TRACKING: f64 = 0.1;
+/// Nominal frame rates, for the one nearest the measured rate
+const FRAME_RATES: [f32; 5] = [23.976, 24.0, 25.0, 29.97, 30.0];
+/// A run of consistent frames shorter than this is a misread
+const MIN_RUN_FRAMES: usize = 3;
+/// Timecode and sample clock may drift apart by this fraction across a
+/// dropout before the timecode counts as having jumped
+const DRIFT_TOLERANCE: f32 = 0.001;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LtcSegment {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub start_timecode: String,
+    pub end_timecode: String,
+    pub frames: usize,          // Frames decoded in the segment
+    pub user_bits: String,      // Binary groups 1-8 of its first frame, in hex
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LtcDiscontinuity {
+    pub time: f32,              // Where the new timecode starts
+    pub from_timecode: String,  // Last frame before
+    pub to_timecode: String,
+    pub jump_frames: i64,       // Timecode advance beyond the time elapsed: positive skips ahead, negative goes back
+    pub gap_secs: f32,          // Audio between the two frames with no readable timecode
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LtcTrack {
+    pub channel: usize,
+    pub frame_rate: f32,        // Nominal: 23.976, 24, 25, 29.97 or 30
+    pub drop_frame: bool,
+    pub measured_fps: f32,      // Frame timing against the sample clock
+    pub start_timecode: String,
+    pub end_timecode: String,
+    pub frames_decoded: usize,
+    pub coverage: f32,          // Fraction of the recording with timecode
+    pub segments: Vec<LtcSegment>,
+    pub discontinuities: Vec<LtcDiscontinuity>,
+}
+
+/// A decoded frame and where it starts (seconds)
+#[derive(Clone, Copy)]
+struct Frame {
+    time: f32,
+    hours: u32,
+    minutes: u32,
+    seconds: u32,
+    frames: u32,
+    drop_frame: bool,
+    user_bits: u32,
+}
+
+impl Frame {
+    /// Frames since midnight at `base` frames per second
+    fn number(&self, base: u32, drop_frame: bool) -> i64 {
+        let minutes = (60 * self.hours + self.minutes) as i64;
+        let number = (minutes * 60 + self.seconds as i64) * base as i64 + self.frames as i64;
+        // Drop-frame skips frame numbers 0 and 1 every minute but every tenth
+        if drop_frame { number - 2 * (minutes - minutes / 10) } else { number }
+    }
+
+    fn timecode(&self) -> String {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        format!("{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, separator, self.frames)
+    }
+}
+
+/// Positions (samples, interpolated) where the signal changes polarity,
+/// with hysteresis against noise around zero
+fn transitions(samples: &[f32], sample_rate: f32) -> Vec<f64> {
+    let filtered = filters::butterworth_highpass(HIGHPASS_HZ, 2, sample_rate as f64).process_buffer(samples);
+    let block = ((LEVEL_BLOCK_SECS * sample_rate) as usize).max(1);
+    let mut positions = Vec::new();
+    let (mut polarity, mut crossing) = (0i8, 0f64);
+    for (b, chunk) in filtered.chunks(block).enumerate() {
+        let rms = (chunk.iter().map(|x| x * x).sum::<f32>() / chunk.len() as f32).sqrt();
+        let threshold = HYSTERESIS * rms;
+        for (j, &x) in chunk.iter().enumerate() {
+            let i = b * block + j;
+            let prev = if i > 0 { filtered[i - 1] } else { x };
+            if (prev < 0.0) != (x < 0.0) {
+                crossing = (i - 1) as f64 + (prev / (prev - x)) as f64;
+            }
+            let swing = if x > threshold { 1 } else if x < -threshold { -1 } else { 0 };
+            if swing != 0 && swing != polarity {
+                if polarity != 0 {
+                    positions.push(crossing);
+                }
+                polarity = swing;
+            }
+        }
+    }
+    positions
+}
+
+/// A frame from the 80 bits ending in the sync word, `None` when a field is
+/// out of range (a misread)
+fn parse(word: u128, time: f32) -> Option<Frame> {
+    let bit = |k: usize| ((word >> (FRAME_BITS - 1 - k)) & 1) as u32;
+    // Fields are sent least significant bit first
+    let field = |first: usize, len: usize| (0..len).map(|i| bit(first + i) << i).sum::<u32>();
+    let units = [field(0, 4), field(16, 4), field(32, 4), field(48, 4)];
+    let frame = Frame {
+        time,
+        frames: units[0] + 10 * field(8, 2),
+        drop_frame: bit(10) == 1,
+        seconds: units[1] + 10 * field(24, 3),
+        minutes: units[2] + 10 * field(40, 3),
+        hours: units[3] + 10 * field(56, 2),
+        user_bits: (0..8).fold(0, |bits, group| (bits << 4) | field(4 + 8 * group, 4)),
+    };
+    let valid = units.iter().all(|&u| u <= 9)
+        && frame.frames < 30
+        && frame.seconds < 60
+        && frame.minutes < 60
+        && frame.hours < 24;
+    valid.then_some(frame)
+}
+
+/// Read biphase-mark bits off the transitions (a transition starts every
+/// cell, and a one has another in the middle) and collect every frame
+/// whose sync word comes through
+fn decode_frames(transitions: &[f64], sample_rate: f32) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let initial_cell = (INITIAL_CELL_SECS * sample_rate) as f64;
+    let mut cell = initial_cell;
+    let (mut word, mut count) = (0u128, 0usize);
+    let mut bit_starts: VecDeque<f64> = VecDeque::with_capacity(FRAME_BITS);
+    let mut half: Option<f64> = None;
+
+    for pair in transitions.windows(2) {
+        let (start, length) = (pair[0], pair[1] - pair[0]);
+        let ratio = length / cell;
+        let bit = if (0.75..1.5).contains(&ratio) && half.is_none() {
+            cell += CELL_TRACKING * (length - cell);
+            Some((0, start))
+        } else if (0.25..0.75).contains(&ratio) {
+            match half.take() {
+                Some(first) => {
+                    cell += CELL_TRACKING * (pair[1] - first - cell);
+                    Some((1, first))
+                }
+                None => {
+                    half = Some(start);
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        let Some((value, bit_start)) = bit else {
+            // Not a bit: lose sync and start over
+            (word, count, half, cell) = (0, 0, None, initial_cell);
+            bit_starts.clear();
+            continue;
+        };
+        word = (word << 1 | value) & ((1u128 << FRAME_BITS) - 1);
+        count += 1;
+        if bit_starts.len() == FRAME_BITS {
+            bit_starts.pop_front();
+        }
+        bit_starts.push_back(bit_start);
+        if count >= FRAME_BITS && word & 0xFFFF == SYNC_WORD {
+            if let Some(frame) = parse(word, (bit_starts[0] / sample_rate as f64) as f32) {
+                frames.push(frame);
+            }
+        }
+    }
+    frames
+}
+
+/// Decode the LTC on one channel: the timecode read frame by frame, the
+/// frame rate (nominal, and as measured against the sample clock), and the
+/// segments over which it runs on as the audio does. Between segments the
+/// timecode jumps, which in production audio marks an edit, or starts
+/// again after a dropout. `None` when no timecode decodes.
+pub fn decode(samples: &[f32], sample_rate: u32) -> Option<LtcTrack> {
+    let sr = sample_rate as f32;
+    let frames = decode_frames(&transitions(samples, sr), sr);
+    if frames.len() < MIN_RUN_FRAMES {
+        return None;
+    }
+
+    // Measured rate: time over successive frames that are one apart
+    let drop_frame = 2 * frames.iter().filter(|f| f.drop_frame).count() > frames.len();
+    let max_frame = frames.iter().map(|f| f.frames).max().unwrap_or(0);
+    let rough_base = if max_frame >= 25 { 30 } else if max_frame == 24 { 25 } else { 24 };
+    let (elapsed, steps) = frames
+        .windows(2)
+        .filter(|w| w[1].number(rough_base, drop_frame) - w[0].number(rough_base, drop_frame) == 1)
+        .map(|w| w[1].time - w[0].time)
+        .filter(|&dt| dt < 1.5 / 23.976 && dt > 0.5 / 30.0)
+        .fold((0.0, 0usize), |(total, n), dt| (total + dt, n + 1));
+    if steps == 0 {
+        return None;
+    }
+    let measured_fps = steps as f32 / elapsed;
+    let frame_rate = FRAME_RATES
+        .iter()
+        .copied()
+        .filter(|&rate| if drop_frame { rate == 29.97 } else if max_frame >= 23 { rate.round() as u32 == rough_base } else { true })
+        .min_by(|a, b| (a - measured_fps).abs().total_cmp(&(b - measured_fps).abs()))
+        .unwrap_or(measured_fps);
+    let base = frame_rate.round() as u32;
+
+    // Frames whose timecode advances as the audio does, and the same test
+    // between runs once misreads are gone
+    let jump = |a: &Frame, b: &Frame| {
+        let expected = (b.time - a.time) * measured_fps;
+        let advance = b.number(base, drop_frame) - a.number(base, drop_frame) - expected.round() as i64;
+        if advance.abs() <= (expected * DRIFT_TOLERANCE).round() as i64 { 0 } else { advance }
+    };
+    let mut runs: Vec<Vec<Frame>> = Vec::new();
+    for frame in &frames {
+        match runs.last_mut() {
+            Some(run) if jump(run.last().unwrap(), frame) == 0 => run.push(*frame),
+            _ => runs.push(vec![*frame]),
+        }
+    }
+    runs.retain(|run| run.len() >= MIN_RUN_FRAMES);
+    let mut merged: Vec<Vec<Frame>> = Vec::new();
+    for run in runs {
+        match merged.last_mut() {
+            Some(last) if jump(last.last().unwrap(), &run[0]) == 0 => last.extend(run),
+            _ => merged.push(run),
+        }
+    }
+    if merged.is_empty() {
+        return None;
+    }
+
+    let frame_secs = 1.0 / measured_fps;
+    let segments: Vec<LtcSegment> = merged
+        .iter()
+        .map(|run| {
+            let (first, last) = (run[0], run[run.len() - 1]);
+            LtcSegment {
+                start_time: first.time,
+                end_time: last.time + frame_secs,
+                start_timecode: first.timecode(),
+                end_timecode: last.timecode(),
+                frames: run.len(),
+                user_bits: format!("{:08X}", first.user_bits),
+            }
+        })
+        .collect();
+    let discontinuities = merged
+        .windows(2)
+        .map(|pair| {
+            let (before, after) = (pair[0][pair[0].len() - 1], pair[1][0]);
+            LtcDiscontinuity {
+                time: after.time,
+                from_timecode: before.timecode(),
+                to_timecode: after.timecode(),
+                jump_frames: jump(&before, &after),
+                gap_secs: (after.time - before.time - frame_secs).max(0.0),
+            }
+        })
+        .collect();
+
+    let duration = samples.len() as f32 / sr;
+    Some(LtcTrack {
+        channel: 0,
+        frame_rate,
+        drop_frame,
+        measured_fps,
+        start_timecode: segments[0].start_timecode.clone(),
+        end_timecode: segments[segments.len() - 1].end_timecode.clone(),
+        frames_decoded: segments.iter().map(|s| s.frames).sum(),
+        coverage: (segments.iter().map(|s| s.end_time - s.start_time).sum::<f32>() / duration).min(1.0),
+        segments,
+        discontinuities,
+    })
+}
+
+/// Decode every channel and keep the one carrying the most timecode
+pub fn decode_channels(channels: &[Vec<f32>], sample_rate: u32) -> Option<LtcTrack> {
+    channels
+        .par_iter()
+        .enumerate()
+        .filter_map(|(channel, samples)| decode(samples, sample_rate).map(|track| LtcTrack { channel, ..track }))
+        .max_by_key(|track| track.frames_decoded)
+}
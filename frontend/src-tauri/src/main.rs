@@ -1,50 +1,321 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod agc;
+mod align;
+mod azimuth;
+mod bitdepth;
+mod channels;
+mod clicks;
+mod clipping;
+mod compression;
+mod dc;
+mod declip;
+mod decode;
+mod denoise;
+mod difference;
+mod disguise;
+mod distortion;
+mod drift;
+mod dtmf;
+mod duplication;
+mod encoder;
+mod enf;
+mod eq;
+mod evidence;
+mod export;
+mod figures;
+mod filters;
+mod fir;
+mod flutter;
+mod generator;
+mod handling;
+mod hum;
+mod labels;
+mod lossy;
+mod loudness;
+mod markers;
+mod ltc;
+mod morse;
+mod noise;
+mod notch;
+mod nulltest;
+mod numpy;
+mod octave;
+mod opus;
+mod pitch;
+mod plot;
+mod recorder;
+mod rerecord;
+mod resample;
+mod repair;
+mod report;
+mod reverb;
+mod room;
+mod segments;
+mod series;
+mod session;
+mod settings;
+mod sign;
+mod similarity;
+mod silence;
+mod splice;
+mod spectrum;
+mod stego;
+mod stereo;
+mod stretch;
+mod sweep;
+mod synthetic;
+mod tones;
+mod transcribe;
+mod ultrasonic;
+mod undo;
+mod vad;
+mod video;
+mod vocoder;
+mod watch;
+mod weighting;
+mod wind;
+
+use channels::ChannelSelect;
 use rayon::prelude::*;
-use realfft::RealFftPlanner;
-use serde::Serialize;
-use std::path::PathBuf;
+use segments::TimeRange;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 use std::sync::Mutex;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 use log::{debug, info, warn};
+use spectrum::WindowType;
 use tauri_plugin_log::{Target, TargetKind};
+use weighting::Weighting;
 
 /// Audio data state shared across commands
 struct AudioState {
     samples: Mutex<Vec<f32>>,           // Mono samples for analysis
     samples_interleaved: Mutex<Vec<f32>>, // Original interleaved for playback
+    channel_samples: Mutex<Vec<Vec<f32>>>, // Deinterleaved per-channel buffers
+    channel_layout: Mutex<Vec<String>>,   // Speaker label per channel
+    downmix: Mutex<Vec<f32>>,             // Per-channel weights of the mono analysis signal
     sample_rate: Mutex<u32>,
     channels: Mutex<usize>,
     spectrogram: Mutex<Vec<Vec<f32>>>,
     spec_times: Mutex<Vec<f32>>,
+    spec_info: Mutex<Option<SpectrogramInfo>>, // Parameters the cached spectrogram was computed with
     forensic_data: Mutex<ForensicData>,
+    comparison: Mutex<Option<ComparisonAudio>>, // Second file for null tests
+    enf_params: Mutex<enf::EnfParams>,          // ENF analysis STFT settings
+    calibration_offset_db: Mutex<f32>,          // Added to level, band and PSD measurements
+    source_path: Mutex<Option<String>>,         // File the audio was decoded from
+    bits_per_sample: Mutex<Option<u32>>,        // Word length stated by that file
+    noise_print: Mutex<Option<noise::NoisePrint>>, // Captured background noise, kept across loads
+    speech: Mutex<Option<vad::VadReport>>,      // Speech segments from the last VAD run, for gating
+    transcript: Mutex<Option<transcribe::Transcript>>, // Last transcription, for search
+    markers: Mutex<markers::MarkerSet>,                // On the loaded recording
+    selections: Mutex<Vec<TimeRange>>,                 // Selected in the view, for the session
+    settings: Mutex<settings::Settings>,               // Preferences, kept between launches
+    evidence: Mutex<Option<evidence::EvidenceMode>>,   // Evidence mode, when on
+    history: Mutex<undo::History<EditState>>,          // Changes to the loaded recording's state
+    watch: Mutex<watch::WatchStatus>,                  // The watch folder, as its thread last left it
+    evidence_log: Mutex<Vec<evidence::EvidenceEvent>>, // Evidence mode switched on and off this run
+    playback_eq: Mutex<Vec<eq::EqBand>>,               // Applied to the samples sent for playback
+}
+
+/// The state undo and redo restore: what marker, selection and processing
+/// commands change, with the results a change of downmix clears
+#[derive(Clone)]
+struct EditState {
+    markers: markers::MarkerSet,
+    selections: Vec<TimeRange>,
+    downmix: Vec<f32>,
+    calibration_offset_db: f32,
+    forensic: ForensicData,
+    speech: Option<vad::VadReport>,
+}
+
+/// A second decoded file held alongside the loaded audio
+struct ComparisonAudio {
+    path: String,
+    samples: Vec<f32>,             // Default downmix
+    channel_samples: Vec<Vec<f32>>,
+    sample_rate: u32,
 }
 
-#[derive(Default, Clone, Serialize)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 struct ForensicData {
     enf_present: bool,
     enf_strength_db: f32,
     grid_freq: f32,
+    enf_harmonics: Vec<enf::HarmonicEvidence>, // Hum evidence per harmonic of grid_freq
+    enf_track: Option<enf::EnfTrack>,  // Mains frequency over time when ENF is present
+    enf_segments: Vec<enf::EnfSegment>, // Hum presence per 10 s block
     splice_times: Vec<f32>,
+    splice_events: Vec<splice::SpliceEvent>,  // Multi-feature edit points with confidence
+    phase_resets: Vec<splice::PhaseReset>,    // Low/mid-band STFT phase coherence collapses
+    click_events: Vec<clicks::ClickEvent>,    // Clicks, pops and dropouts
+    handling_noise: Vec<handling::HandlingEvent>, // Microphone handling thumps
+    dtmf: Option<dtmf::DtmfReport>,        // Touch-tone digits dialed during the recording
+    cw_transmissions: Vec<morse::CwTransmission>, // Morse keyed on a tone, decoded
+    ultrasonic: Option<ultrasonic::UltrasonicReport>, // Beacons, bat calls and other content above 17 kHz
+    enf_phase_jumps: Vec<enf::PhaseJump>,  // Discontinuities in the hum phase (edit points)
+    hum: Option<hum::HumReport>,           // Level, width and stability of each mains harmonic
     snr_db: f32,
     dynamic_range_db: f32,
-    has_clipping: bool,
-    clipped_count: usize,
+    has_clipping: bool,                    // Flat-topped digital clipping
+    clipped_count: usize,                  // Samples on flat tops
+    clipping: Option<clipping::ClippingReport>, // Hard clips, inter-sample overs and limiting
+    level_weighting: Weighting,
+    channel: ChannelSelect,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    channel_reports: Vec<ForensicData>,  // One report per channel when `channel` is All
+    stereo: Option<stereo::StereoReport>, // Channel relationship (first pair) for multichannel audio
+    channel_dropouts: Vec<stereo::ChannelDropout>,
+    polarity_inversions: Vec<TimeRange>, // L/R inverted relative to each other
+    azimuth: Option<azimuth::AzimuthReport>, // Inter-channel delay of a tape transfer (head azimuth)
+    calibration_tones: Vec<tones::CalibrationTone>, // Line-up tones in the first minute
+    wow_flutter: Option<flutter::FlutterReport>,    // Speed fluctuation, measured on a line-up tone
+    ambience_changes: Vec<noise::AmbienceChange>,   // Shifts in the background noise between pauses
+    duplicates: Vec<duplication::DuplicatePair>,    // Stretches copied elsewhere in the recording
+    compression: Option<compression::CompressionReport>, // Earlier MP3/AAC encodings
+    spectral_cutoff: Option<compression::CutoffReport>,  // Band limit left by a lossy encoder
+    encoder: Option<encoder::EncoderFingerprint>,        // Encoder of the loaded file
+    bit_depth: Option<bitdepth::BitDepthReport>,         // Resolution the samples really use
+    steganography: Option<stego::StegoReport>,           // LSB statistics screened for hidden data
+    ltc: Option<ltc::LtcTrack>,                          // SMPTE timecode on whichever channel carries it
+    upsampling: Option<resample::UpsamplingReport>,      // Band limit of a lower original rate
+    src_artifacts: Option<resample::SrcArtifactReport>,  // Imaging, aliasing and interpolation patterns
+    rerecording: Option<rerecord::RerecordReport>,       // Signs of a microphone capture of a playback
+    synthetic_speech: Option<synthetic::SyntheticReport>, // TTS / voice conversion screening (heuristics)
+    vocoder_regions: Vec<vocoder::VocoderRegion>,        // Spectrogram regions with vocoder artifacts
+    pitch_shift: Option<disguise::DisguiseReport>,       // Pitch against formant spacing (voice disguise)
+    time_stretch: Option<stretch::StretchReport>,        // Phase vocoder / WSOLA traces of a changed duration
+    device_fingerprint: Option<recorder::DeviceFingerprint>, // Noise floor, DC and gain traits of the recorder
+    notches: Option<notch::NotchReport>,                 // Persistent notches cut by filtering
+    agc: Option<agc::AgcReport>,                         // Gain pumping after loud events
+    wind: Option<wind::WindReport>,                      // Wind noise regions, left out of the SNR
+    dc_offsets: Vec<dc::DcOffset>,                       // Per channel
 }
 
-#[derive(Serialize)]
+impl ForensicData {
+    /// `self` with the results of the detectors `analyze_forensics` leaves
+    /// to their own commands taken from `cached`
+    fn with_detectors(self, cached: ForensicData) -> Self {
+        ForensicData {
+            click_events: cached.click_events,
+            handling_noise: cached.handling_noise,
+            dtmf: cached.dtmf,
+            cw_transmissions: cached.cw_transmissions,
+            ultrasonic: cached.ultrasonic,
+            hum: cached.hum,
+            azimuth: cached.azimuth,
+            wow_flutter: cached.wow_flutter,
+            duplicates: cached.duplicates,
+            compression: cached.compression,
+            spectral_cutoff: cached.spectral_cutoff,
+            encoder: cached.encoder,
+            bit_depth: cached.bit_depth,
+            steganography: cached.steganography,
+            ltc: cached.ltc,
+            upsampling: cached.upsampling,
+            src_artifacts: cached.src_artifacts,
+            rerecording: cached.rerecording,
+            synthetic_speech: cached.synthetic_speech,
+            vocoder_regions: cached.vocoder_regions,
+            pitch_shift: cached.pitch_shift,
+            time_stretch: cached.time_stretch,
+            device_fingerprint: cached.device_fingerprint,
+            notches: cached.notches,
+            agc: cached.agc,
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
 struct AudioInfo {
     duration: f32,
     sample_rate: u32,
     channels: usize,
+    channel_layout: Vec<String>,  // Speaker labels ("FL", "FR", "FC", "LFE", ...)
+    layout_name: String,          // "mono", "stereo", "5.1", "7.1", ...
+    downmix: Vec<f32>,
+    bits_per_sample: Option<u32>, // Stated by the file; None for lossy and generated audio
+}
+
+/// How the cached spectrogram was computed; analyses reading it map bins
+/// and frames through these rather than assuming defaults
+#[derive(Clone, Copy, Serialize)]
+struct SpectrogramInfo {
+    n_fft: usize,
+    hop_length: usize,
+    window: WindowType,
+    max_freq: f32,   // Requested upper limit
+    bin_hz: f32,     // Frequency spacing of bins
+    frames: usize,
+    bins: usize,
+    channel: ChannelSelect,
 }
 
 #[derive(Serialize)]
 struct SpectrogramData {
     data: Vec<Vec<f32>>,
     times: Vec<f32>,
-    max_freq: f32,
+    #[serde(flatten)]
+    info: SpectrogramInfo,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    channel_data: Vec<Vec<Vec<f32>>>,  // Per-channel spectrograms when `channel` is All
+}
+
+#[derive(Serialize)]
+struct LevelMeasurement {
+    weighting: Weighting,
+    start_time: f32,
+    end_time: f32,
+    rms_dbfs: f32,       // Equivalent continuous level (Leq)
+    peak_dbfs: f32,
+    max_fast_dbfs: f32,  // 125 ms exponential time weighting
+    min_fast_dbfs: f32,
+    max_slow_dbfs: f32,  // 1 s exponential time weighting
+    calibration_offset_db: f32,  // Already included in the levels above
+}
+
+#[derive(Serialize)]
+struct OctaveBandData {
+    center_freqs: Vec<f32>,      // Nominal 1/3-octave mid-band frequencies
+    levels_db: Vec<f32>,         // Leq per band over the whole range (dBFS)
+    times: Vec<f32>,             // Start of each interval (empty for snapshots)
+    series_db: Vec<Vec<f32>>,    // Per interval, per band Leq (dBFS)
+    weighting: Weighting,
+    calibration_offset_db: f32,
+}
+
+#[derive(Serialize)]
+struct PsdData {
+    freqs: Vec<f32>,
+    psd_db: Vec<f32>,            // dB re 1 FS²/Hz
+    segment_length: usize,
+    overlap: f32,
+    window: WindowType,
+    segments: usize,
+    enbw_hz: f32,
+    calibration_offset_db: f32,
+}
+
+#[derive(Serialize)]
+struct ToneTrackData {
+    times: Vec<f32>,             // Window centers
+    tracks: Vec<tones::ToneTrack>,
+    window: f32,
+    resolution_hz: f32,          // Hann main-lobe half width: tones closer than this blur together
+}
+
+#[derive(Serialize)]
+struct CorrelationTrack {
+    times: Vec<f32>,             // Window centers
+    correlation: Vec<f32>,       // +1 (mono) .. -1 (out of phase)
+    overall: f32,                // Coefficient over the whole file
+    window: f32,
+    left_channel: usize,
+    right_channel: usize,
 }
 
 #[derive(Serialize)]
@@ -54,215 +325,365 @@ struct AudioSamples {
     channels: usize,
 }
 
+/// Convert an optional time range (seconds) into sample indices of a
+/// single-channel buffer, defaulting to the whole buffer
+fn selection_range(
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    sample_rate: u32,
+    len: usize,
+) -> Result<(usize, usize), String> {
+    let sr = sample_rate as f32;
+    let start = ((start_time.unwrap_or(0.0).max(0.0) * sr) as usize).min(len);
+    let end = end_time.map(|t| ((t.max(0.0) * sr) as usize).min(len)).unwrap_or(len);
+    if start >= end {
+        return Err("Invalid selection range".to_string());
+    }
+    Ok((start, end))
+}
+
 /// Load an audio file and compute spectrogram
 #[tauri::command]
-async fn load_audio(path: String, state: State<'_, AudioState>) -> Result<AudioInfo, String> {
-    use symphonia::core::audio::SampleBuffer;
-    use symphonia::core::codecs::DecoderOptions;
-    use symphonia::core::formats::FormatOptions;
-    use symphonia::core::io::MediaSourceStream;
-    use symphonia::core::meta::MetadataOptions;
-    use symphonia::core::probe::Hint;
+async fn load_audio(path: String, app: AppHandle, state: State<'_, AudioState>) -> Result<AudioInfo, String> {
+    open_recording(path, &app, &state)
+}
 
+/// Decode `path` into the state as the recording under examination
+fn open_recording(path: String, app: &AppHandle, state: &AudioState) -> Result<AudioInfo, String> {
     info!("Loading audio: {}", path);
-    let file = std::fs::File::open(&path).map_err(|e| {
-        warn!("Failed to open file: {}", e);
-        e.to_string()
-    })?;
-    let mss = MediaSourceStream::new(Box::new(file), Default::default());
-
-    let mut hint = Hint::new();
-    if let Some(ext) = PathBuf::from(&path).extension().and_then(|e| e.to_str()) {
-        hint.with_extension(ext);
-    }
-
-    debug!("Probing format...");
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
-        .map_err(|e| {
-            warn!("Probe failed: {}", e);
-            e.to_string()
-        })?;
-
-    let mut format = probed.format;
-    let track = format.default_track().ok_or("No audio track found")?;
-    info!("Found track: {}Hz, {} channels",
-        track.codec_params.sample_rate.unwrap_or(0),
-        track.codec_params.channels.map(|c| c.count()).unwrap_or(0));
-    let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
-    let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(2);
-
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &DecoderOptions::default())
-        .map_err(|e| e.to_string())?;
-
-    let track_id = track.id;
-    let mut samples = Vec::new();
-    let mut interleaved = Vec::new();
-    let mut actual_channels = channels;
-
-    loop {
-        match format.next_packet() {
-            Ok(packet) => {
-                if packet.track_id() != track_id {
-                    continue;
-                }
-                match decoder.decode(&packet) {
-                    Ok(decoded) => {
-                        let spec = *decoded.spec();
-                        let ch = spec.channels.count();
-                        let mut sample_buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
-                        sample_buf.copy_interleaved_ref(decoded);
-
-                        // Store interleaved samples for playback
-                        interleaved.extend_from_slice(sample_buf.samples());
-                        actual_channels = ch;
-
-                        // Convert to mono for analysis
-                        for chunk in sample_buf.samples().chunks(ch) {
-                            let mono: f32 = chunk.iter().sum::<f32>() / ch as f32;
-                            samples.push(mono);
-                        }
-                    }
-                    Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
-                    Err(_) => break,
-                }
-            }
-            Err(_) => break,
-        }
+    let decoded = decode::decode_file(&path)?;
+    // Hashed once, for the recent files and the evidence seal, before the
+    // audio is replaced, so a file that cannot be read leaves the current
+    // recording in place
+    let sha256 = report::identify(&path).map(|identity| identity.sha256);
+    if let Err(e) = sha256.clone().and_then(|sha256| remember_recent(app, state, &path, sha256)) {
+        warn!("Not added to the recent files: {}", e);
     }
+    let seal = if new_files_only(state) {
+        Some(evidence::SourceSeal { sha256: sha256?, path: path.clone() })
+    } else {
+        None
+    };
+    let info = store_audio(state, decoded);
+    if let Some(mode) = state.evidence.lock().unwrap().as_mut() {
+        mode.source = seal;
+    }
+    *state.source_path.lock().unwrap() = Some(path);
+    info!("Decoded {:.2}s of audio", info.duration);
+    Ok(info)
+}
 
-    let duration = samples.len() as f32 / sample_rate as f32;
-    info!("Decoded {} samples, duration: {:.2}s", samples.len(), duration);
+/// Load a second file to compare against the current audio (null test).
+/// The loaded audio and its cached results are left untouched.
+#[tauri::command]
+async fn load_comparison_audio(path: String, state: State<'_, AudioState>) -> Result<AudioInfo, String> {
+    info!("Loading comparison audio: {}", path);
+    let decoded = decode::decode_file(&path)?;
+    let (channel_samples, samples, info) = decoded_audio(&decoded);
+    *state.comparison.lock().unwrap() = Some(ComparisonAudio {
+        path,
+        samples,
+        channel_samples,
+        sample_rate: decoded.sample_rate,
+    });
+    Ok(info)
+}
 
-    // Store in state
-    *state.samples.lock().unwrap() = samples;
-    *state.samples_interleaved.lock().unwrap() = interleaved;
-    *state.sample_rate.lock().unwrap() = sample_rate;
-    *state.channels.lock().unwrap() = actual_channels;
+/// Split decoded audio into its channels and the mono analysis signal (the
+/// layout's default downmix), with what the interface shows of it. Audio
+/// without a layout is taken as mono.
+fn decoded_audio(decoded: &decode::DecodedAudio) -> (Vec<Vec<f32>>, Vec<f32>, AudioInfo) {
+    let layout = if decoded.layout.is_empty() { channels::generic_labels(1) } else { decoded.layout.clone() };
+    let channel_samples = channels::deinterleave(&decoded.interleaved, layout.len());
+    let downmix = channels::default_downmix(&layout);
+    let samples = channels::downmix(&channel_samples, &downmix);
+    let info = AudioInfo {
+        duration: samples.len() as f32 / decoded.sample_rate as f32,
+        sample_rate: decoded.sample_rate,
+        channels: layout.len(),
+        layout_name: channels::layout_name(&layout),
+        channel_layout: layout,
+        downmix,
+        bits_per_sample: decoded.bits_per_sample,
+    };
+    (channel_samples, samples, info)
+}
 
-    Ok(AudioInfo {
-        duration,
-        sample_rate,
-        channels: actual_channels,
-    })
+/// Replace the loaded audio with `decoded`, clearing results computed from
+/// the previous audio. The evidence seal is dropped too: the audio no
+/// longer comes from the sealed file.
+fn store_audio(state: &AudioState, decoded: decode::DecodedAudio) -> AudioInfo {
+    let (channel_samples, samples, info) = decoded_audio(&decoded);
+
+    *state.samples.lock().unwrap() = samples;
+    *state.channel_samples.lock().unwrap() = channel_samples;
+    *state.samples_interleaved.lock().unwrap() = decoded.interleaved;
+    *state.sample_rate.lock().unwrap() = info.sample_rate;
+    *state.channels.lock().unwrap() = info.channels;
+    *state.downmix.lock().unwrap() = info.downmix.clone();
+    *state.channel_layout.lock().unwrap() = info.channel_layout.clone();
+    state.spectrogram.lock().unwrap().clear();
+    state.spec_times.lock().unwrap().clear();
+    *state.spec_info.lock().unwrap() = None;
+    *state.forensic_data.lock().unwrap() = ForensicData::default();
+    *state.speech.lock().unwrap() = None;
+    *state.transcript.lock().unwrap() = None;
+    *state.markers.lock().unwrap() = markers::MarkerSet::default();
+    state.selections.lock().unwrap().clear();
+    state.history.lock().unwrap().clear();
+    *state.calibration_offset_db.lock().unwrap() = 0.0;
+    *state.source_path.lock().unwrap() = None;
+    *state.bits_per_sample.lock().unwrap() = decoded.bits_per_sample;
+    if let Some(mode) = state.evidence.lock().unwrap().as_mut() {
+        mode.source = None;
+    }
+    info
 }
 
-/// Compute spectrogram using parallel processing
+/// Set the per-channel weights used to build the mono analysis signal
+/// (`None` restores the layout's default). Cached results are cleared.
 #[tauri::command]
-async fn compute_spectrogram(max_freq: f32, state: State<'_, AudioState>) -> Result<SpectrogramData, String> {
-    info!("Starting spectrogram computation...");
-    let samples = state.samples.lock().unwrap().clone();
-    let sample_rate = *state.sample_rate.lock().unwrap();
-
-    if samples.is_empty() {
+fn set_downmix(coefficients: Option<Vec<f32>>, state: State<'_, AudioState>) -> Result<Vec<f32>, String> {
+    let channel_samples = state.channel_samples.lock().unwrap();
+    if channel_samples.is_empty() {
         return Err("No audio loaded".to_string());
     }
-    debug!("Processing {} samples for spectrogram", samples.len());
-
-    let n_fft = 2048;
-    let hop_length = 512;
-    let sr = sample_rate as f32;
-
-    // Hann window
-    let window: Vec<f32> = (0..n_fft)
-        .map(|i| 0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / n_fft as f32).cos()))
-        .collect();
-
-    // Limit frequency bins
-    let max_bin = ((max_freq / sr) * n_fft as f32) as usize;
-    let max_bin = max_bin.min(n_fft / 2 + 1);
-
-    // Frame positions
-    let frame_starts: Vec<usize> = (0..)
-        .map(|i| i * hop_length)
-        .take_while(|&start| start + n_fft <= samples.len())
-        .collect();
 
-    debug!("Computing {} FFT frames...", frame_starts.len());
-
-    // Parallel FFT computation
-    let results: Vec<(f32, Vec<f32>)> = frame_starts
-        .par_iter()
-        .map(|&frame_start| {
-            let mut planner = RealFftPlanner::<f32>::new();
-            let fft = planner.plan_fft_forward(n_fft);
+    let coefficients = match coefficients {
+        Some(c) if c.len() != channel_samples.len() => {
+            return Err(format!("Expected {} coefficients, got {}", channel_samples.len(), c.len()));
+        }
+        Some(c) if c.iter().any(|v| !v.is_finite()) => return Err("Coefficients must be finite".to_string()),
+        Some(c) => c,
+        None => channels::default_downmix(&state.channel_layout.lock().unwrap()),
+    };
 
-            let mut input: Vec<f32> = samples[frame_start..frame_start + n_fft]
-                .iter()
-                .zip(window.iter())
-                .map(|(&s, &w)| s * w)
-                .collect();
+    info!("Downmix coefficients: {:?}", coefficients);
+    let before = edit_state(&state);
+    state.history.lock().unwrap().record("Change downmix", before);
+    *state.samples.lock().unwrap() = channels::downmix(&channel_samples, &coefficients);
+    *state.downmix.lock().unwrap() = coefficients.clone();
+    state.spectrogram.lock().unwrap().clear();
+    state.spec_times.lock().unwrap().clear();
+    *state.spec_info.lock().unwrap() = None;
+    *state.forensic_data.lock().unwrap() = ForensicData::default();
+    *state.speech.lock().unwrap() = None;
+    Ok(coefficients)
+}
 
-            let mut spectrum = fft.make_output_vec();
-            fft.process(&mut input, &mut spectrum).unwrap();
+/// Resolve a channel selection to the signal an analysis should run on.
+/// `All` resolves to the mix; callers handle the per-channel part.
+fn select_signal(state: &AudioState, channel: ChannelSelect) -> Result<Vec<f32>, String> {
+    let samples = match channel {
+        ChannelSelect::Mix | ChannelSelect::All => state.samples.lock().unwrap().clone(),
+        ChannelSelect::Channel(c) => {
+            let channels = state.channel_samples.lock().unwrap();
+            if channels.is_empty() {
+                return Err("No audio loaded".to_string());
+            }
+            channels.get(c).cloned().ok_or_else(|| format!("Channel {} does not exist", c))?
+        }
+        ChannelSelect::Mid | ChannelSelect::Side => {
+            let (left, right, _, _) = channel_pair(state, None, None)?;
+            let (mid, side) = stereo::mid_side(&left, &right);
+            if channel == ChannelSelect::Mid { mid } else { side }
+        }
+    };
 
-            let magnitudes: Vec<f32> = spectrum[..max_bin]
-                .iter()
-                .map(|c| {
-                    let mag = (c.re * c.re + c.im * c.im).sqrt();
-                    20.0 * (mag + 1e-10).log10()
-                })
-                .collect();
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    Ok(samples)
+}
 
-            (frame_start as f32 / sr, magnitudes)
-        })
-        .collect();
+/// Compute spectrogram using parallel processing. `n_fft` (default 2048),
+/// `hop_length` (default 512) and `window` (default Hann) are stored with
+/// the result and reported by `get_spectrogram_info`.
+#[tauri::command]
+async fn compute_spectrogram(
+    max_freq: f32,
+    channel: Option<ChannelSelect>,
+    n_fft: Option<usize>,
+    hop_length: Option<usize>,
+    window: Option<WindowType>,
+    state: State<'_, AudioState>,
+) -> Result<SpectrogramData, String> {
+    info!("Starting spectrogram computation...");
+    let channel = channel.unwrap_or_default();
+    let samples = select_signal(&state, channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    debug!("Processing {} samples for spectrogram ({:?})", samples.len(), channel);
 
-    let mut times = Vec::new();
-    let mut data = Vec::new();
-    for (t, mags) in results {
-        times.push(t);
-        data.push(mags);
+    let n_fft = n_fft.unwrap_or(2048);
+    let hop_length = hop_length.unwrap_or(512);
+    let window = window.unwrap_or_default();
+    if !(64..=65536).contains(&n_fft) {
+        return Err("FFT size must be between 64 and 65536".to_string());
+    }
+    if hop_length == 0 || hop_length > n_fft {
+        return Err("Hop length must be between 1 and the FFT size".to_string());
     }
 
+    let (times, data) = spectrum::spectrogram_db(&samples, sample_rate, n_fft, hop_length, window, max_freq);
     info!("Spectrogram complete: {} frames x {} bins", data.len(), data.first().map(|d| d.len()).unwrap_or(0));
 
+    let channel_data = if channel == ChannelSelect::All {
+        let channels = state.channel_samples.lock().unwrap().clone();
+        channels
+            .iter()
+            .map(|c| spectrum::spectrogram_db(c, sample_rate, n_fft, hop_length, window, max_freq).1)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let info = SpectrogramInfo {
+        n_fft,
+        hop_length,
+        window,
+        max_freq,
+        bin_hz: sample_rate as f32 / n_fft as f32,
+        frames: data.len(),
+        bins: data.first().map(Vec::len).unwrap_or(0),
+        channel,
+    };
+
     // Store in state
     *state.spectrogram.lock().unwrap() = data.clone();
     *state.spec_times.lock().unwrap() = times.clone();
+    *state.spec_info.lock().unwrap() = Some(info);
 
     Ok(SpectrogramData {
         data,
         times,
-        max_freq,
+        info,
+        channel_data,
     })
 }
 
+/// Parameters of the cached spectrogram
+#[tauri::command]
+fn get_spectrogram_info(state: State<'_, AudioState>) -> Result<SpectrogramInfo, String> {
+    state.spec_info.lock().unwrap().ok_or_else(|| "No spectrogram computed".to_string())
+}
+
 /// Run forensic analysis
+///
+/// `weighting` selects the frequency weighting applied before the level
+/// metrics (dynamic range, SNR); clipping and splice detection always use
+/// the unweighted signal.
+///
+/// `channel` picks the mix or a single channel; `All` analyzes the mix and
+/// attaches a separate report for every channel.
+///
+/// Only the core metrics are run here. The other detectors (clicks, DTMF,
+/// duplication, compression history, ...) have commands of their own, whose
+/// cached results are kept and returned alongside.
 #[tauri::command]
-async fn analyze_forensics(state: State<'_, AudioState>) -> Result<ForensicData, String> {
-    let samples = state.samples.lock().unwrap().clone();
+async fn analyze_forensics(
+    weighting: Option<Weighting>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<ForensicData, String> {
+    forensic_analysis(&state, weighting.unwrap_or_default(), channel.unwrap_or_default())
+}
+
+/// The forensic analysis behind `analyze_forensics`, kept as the cached results
+fn forensic_analysis(state: &AudioState, weighting: Weighting, channel: ChannelSelect) -> Result<ForensicData, String> {
+    let samples = select_signal(state, channel)?;
     let sample_rate = *state.sample_rate.lock().unwrap();
 
-    if samples.is_empty() {
-        return Err("No audio loaded".to_string());
+    let enf_params = *state.enf_params.lock().unwrap();
+    let speech = state.speech.lock().unwrap().clone();
+    let mut forensic = forensic_report(&samples, sample_rate, weighting, &enf_params, speech.as_ref());
+    forensic.channel = channel;
+
+    if let Ok((left, right, _, _)) = channel_pair(state, None, None) {
+        forensic.stereo = Some(stereo::analyze_relationship(&left, &right, sample_rate));
+        let channels = state.channel_samples.lock().unwrap().clone();
+        let window = (0.05 * sample_rate as f32) as usize;
+        forensic.channel_dropouts = stereo::channel_balance(&channels, sample_rate, window, -60.0, 0.1).dropouts;
+        let window = (0.1 * sample_rate as f32) as usize;
+        forensic.polarity_inversions = stereo::polarity_inversions(&left, &right, sample_rate, window, -0.8);
     }
+    forensic.dc_offsets = dc::measure(&state.channel_samples.lock().unwrap(), sample_rate);
 
+    if channel == ChannelSelect::All {
+        let channels = state.channel_samples.lock().unwrap().clone();
+        forensic.channel_reports = channels
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let mut report = forensic_report(c, sample_rate, weighting, &enf_params, speech.as_ref());
+                report.channel = ChannelSelect::Channel(i);
+                report
+            })
+            .collect();
+    }
+
+    let mut cached = state.forensic_data.lock().unwrap();
+    *cached = forensic.with_detectors(std::mem::take(&mut *cached));
+    Ok(cached.clone())
+}
+
+/// Core forensic metrics for one signal. With `speech` segments from a VAD
+/// run, the SNR compares the speech against the pauses instead of the loud
+/// and quiet ends of the level distribution.
+fn forensic_report(
+    samples: &[f32],
+    sample_rate: u32,
+    weighting: Weighting,
+    enf_params: &enf::EnfParams,
+    speech: Option<&vad::VadReport>,
+) -> ForensicData {
     let sr = sample_rate as f32;
-    let mut forensic = ForensicData::default();
+    let mut forensic = ForensicData {
+        level_weighting: weighting,
+        ..Default::default()
+    };
 
     // Quality metrics
-    let peak = samples.iter().fold(0.0f32, |m, &s| m.max(s.abs()));
-    let rms = (samples.iter().map(|&s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    let weighted = weighting.apply(samples, sample_rate);
+
+    let peak = weighted.iter().fold(0.0f32, |m, &s| m.max(s.abs()));
+    let rms = (weighted.iter().map(|&s| s * s).sum::<f32>() / weighted.len() as f32).sqrt();
 
     if rms > 0.0 {
         forensic.dynamic_range_db = 20.0 * (peak / rms).log10();
     }
 
     // Clipping detection
-    let clip_threshold = 0.99;
-    forensic.clipped_count = samples.iter().filter(|&&s| s.abs() > clip_threshold).count();
-    forensic.has_clipping = forensic.clipped_count > samples.len() / 10000;
+    let clipping = clipping::classify(samples, sample_rate);
+    forensic.clipped_count = clipping.clipped_samples;
+    forensic.has_clipping = clipping.hard_clip_events > 0;
+    forensic.clipping = Some(clipping);
 
-    // SNR estimation
+    // SNR estimation, leaving out wind noise unless it covers everything
+    let wind = wind::detect_wind(samples, sample_rate);
     let frame_size = (0.02 * sr) as usize;
-    let mut frame_powers: Vec<f32> = samples
+    let frame_power = |chunk: &[f32]| chunk.iter().map(|&s| s * s).sum::<f32>() / chunk.len() as f32;
+    let frame_span = |i: usize| (i as f32 * frame_size as f32 / sr, (i + 1) as f32 * frame_size as f32 / sr);
+    let mut frames: Vec<(usize, f32)> = weighted
         .chunks(frame_size)
-        .map(|chunk| chunk.iter().map(|&s| s * s).sum::<f32>() / chunk.len() as f32)
+        .enumerate()
+        .filter(|(i, _)| !wind.overlaps(frame_span(*i).0, frame_span(*i).1))
+        .map(|(i, chunk)| (i, frame_power(chunk)))
         .collect();
+    if frames.is_empty() {
+        frames = weighted.chunks(frame_size).map(frame_power).enumerate().collect();
+    }
+    forensic.wind = Some(wind);
 
-    if !frame_powers.is_empty() {
+    let in_speech = |i: usize| speech.is_some_and(|s| s.overlaps_speech(frame_span(i).0, frame_span(i).1));
+    let speech_powers: Vec<f32> = frames.iter().filter(|f| in_speech(f.0)).map(|f| f.1).collect();
+    let pause_powers: Vec<f32> = frames.iter().filter(|f| !in_speech(f.0)).map(|f| f.1).collect();
+    if !speech_powers.is_empty() && !pause_powers.is_empty() {
+        let mean = |powers: &[f32]| powers.iter().sum::<f32>() / powers.len() as f32;
+        let noise_power = mean(&pause_powers).max(1e-10);
+        let signal_power = (mean(&speech_powers) - noise_power).max(1e-10);
+        forensic.snr_db = 10.0 * (signal_power / noise_power).log10();
+    } else if !frames.is_empty() {
+        let mut frame_powers: Vec<f32> = frames.iter().map(|f| f.1).collect();
         frame_powers.sort_by(|a, b| a.partial_cmp(b).unwrap());
         let noise_power = frame_powers[frame_powers.len() / 20].max(1e-10);
         let signal_power = frame_powers[frame_powers.len() * 9 / 10];
@@ -275,7 +696,7 @@ async fn analyze_forensics(state: State<'_, AudioState>) -> Result<ForensicData,
     let threshold_mult = 8.0;
 
     let mut i = window_size;
-    while i < diff.len() - window_size {
+    while i < diff.len().saturating_sub(window_size) {
         let local_mean: f32 = diff[i - window_size..i + window_size].iter().sum::<f32>()
             / (2 * window_size) as f32;
         if diff[i] > local_mean * threshold_mult && diff[i] > 0.1 {
@@ -286,196 +707,3632 @@ async fn analyze_forensics(state: State<'_, AudioState>) -> Result<ForensicData,
         }
     }
 
-    // ENF detection - analyze 50Hz (Europe/Asia) and 60Hz (Americas) power line hum
-    let spectrogram = state.spectrogram.lock().unwrap();
+    forensic.splice_events = splice::detect_splices(samples, sample_rate, 4.0);
+    forensic.phase_resets = splice::phase_resets(samples, sample_rate, 0.3);
+    forensic.ambience_changes = noise::segment_background(samples, sample_rate, 5.0, 4.0).changes;
 
-    if !spectrogram.is_empty() {
-        let max_freq = 8000.0; // Default max frequency
-        let n_freqs = spectrogram.first().map(|f| f.len()).unwrap_or(0);
+    // Line-up tones at the head of the recording
+    let head = samples.len().min(60 * sample_rate as usize);
+    forensic.calibration_tones = tones::detect_calibration_tones(&samples[..head], sample_rate, 1.0);
 
-        for grid_freq in [50.0f32, 60.0] {
-            let nyq = sr / 2.0;
-            if grid_freq >= nyq || n_freqs < 3 {
-                continue;
-            }
+    // ENF detection - hum at 50Hz (Europe/Asia) or 60Hz (Americas) and its
+    // harmonics, on a narrowband STFT that resolves them
+    let stft = enf::narrowband_stft(samples, sample_rate, enf_params);
+    if let Some(detection) = enf::detect(&stft) {
+        forensic.enf_phase_jumps = enf::phase_jumps(&stft, detection.grid_freq, detection.strongest_harmonic(), 45.0);
+        forensic.enf_segments = enf::segment_confidence(&stft, detection.grid_freq, 10.0);
+        forensic.enf_present = true;
+        forensic.grid_freq = detection.grid_freq;
+        forensic.enf_strength_db = detection.strength_db;
+        forensic.enf_harmonics = detection.harmonics;
+        forensic.enf_track = Some(detection.track);
+    }
 
-            // Find the frequency bin for the grid frequency
-            let target_bin = ((grid_freq / max_freq) * n_freqs as f32) as usize;
-            if target_bin >= n_freqs || target_bin < 2 {
-                continue;
-            }
+    forensic
+}
 
-            // Extract energy at ENF frequency over time
-            let mut enf_energies: Vec<f32> = Vec::new();
-            for frame in spectrogram.iter() {
-                if target_bin < frame.len() {
-                    // Average over a small frequency range around target
-                    let low = target_bin.saturating_sub(1);
-                    let high = (target_bin + 2).min(frame.len());
-                    let energy: f32 = frame[low..high].iter().sum::<f32>() / (high - low) as f32;
-                    enf_energies.push(energy);
-                }
-            }
+/// Detect edit points from spectral flux, phase deviation and MFCC change.
+/// `threshold` (default 4) is in robust standard deviations; lower values
+/// report weaker, less certain events.
+#[tauri::command]
+async fn detect_splices(
+    threshold: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<splice::SpliceEvent>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
 
-            if enf_energies.is_empty() {
-                continue;
-            }
+    let events = splice::detect_splices(&samples, sample_rate, threshold.unwrap_or(4.0));
+    info!("Splice detection: {} events", events.len());
 
-            // Check if ENF is present (energy significantly above background)
-            let avg_enf = enf_energies.iter().sum::<f32>() / enf_energies.len() as f32;
-            let total_bins: usize = spectrogram.iter().map(|f| f.len()).sum();
-            let avg_all: f32 = if total_bins > 0 {
-                spectrogram.iter().flat_map(|f| f.iter()).sum::<f32>() / total_bins as f32
-            } else {
-                0.0
-            };
+    state.forensic_data.lock().unwrap().splice_events = events.clone();
+    Ok(events)
+}
 
-            let enf_strength = avg_enf - avg_all;
-            if enf_strength > 5.0 {  // At least 5dB above average
-                forensic.enf_present = true;
-                forensic.grid_freq = grid_freq;
-                forensic.enf_strength_db = enf_strength;
-                break;
-            }
-        }
-    }
+/// Find places where the frame-to-frame phase coherence of the low or mid
+/// band collapses, a sign of cut-and-paste edits. `min_drop` (default 0.3)
+/// is the required fall in coherence (0..1) below the surrounding material.
+#[tauri::command]
+async fn detect_phase_resets(
+    min_drop: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<splice::PhaseReset>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
 
-    *state.forensic_data.lock().unwrap() = forensic.clone();
-    Ok(forensic)
+    let resets = splice::phase_resets(&samples, sample_rate, min_drop.unwrap_or(0.3).clamp(0.05, 1.0));
+    info!("Phase continuity: {} resets", resets.len());
+
+    state.forensic_data.lock().unwrap().phase_resets = resets.clone();
+    Ok(resets)
 }
 
-/// Get current forensic data
+/// Find clicks, pops and dropouts. `threshold` (default 8) is in robust
+/// standard deviations of the matched prediction residual; vinyl with light
+/// surface noise may want 6, heavily damaged transfers 10 or more.
 #[tauri::command]
-fn get_forensic_data(state: State<'_, AudioState>) -> ForensicData {
-    state.forensic_data.lock().unwrap().clone()
+async fn detect_clicks(
+    threshold: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<clicks::ClickEvent>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let events = clicks::detect_clicks(&samples, sample_rate, threshold.unwrap_or(8.0));
+    let dropouts = events.iter().filter(|e| matches!(e.kind, clicks::ClickKind::Dropout)).count();
+    info!("Click detection: {} clicks, {} dropouts", events.len() - dropouts, dropouts);
+
+    state.forensic_data.lock().unwrap().click_events = events.clone();
+    Ok(events)
 }
 
-/// Get audio samples for playback (limited to avoid IPC crashes with large files)
+/// Classify the clipping in the recording: flat-topped digital clipping,
+/// inter-sample overs of the 4x oversampled true peak, and stretches held
+/// down by a limiter or soft clipper, each with counts and the worst time
 #[tauri::command]
-fn get_audio_samples(state: State<'_, AudioState>) -> Result<AudioSamples, String> {
-    let samples = state.samples_interleaved.lock().unwrap().clone();
+async fn detect_clipping(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<clipping::ClippingReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
     let sample_rate = *state.sample_rate.lock().unwrap();
-    let channels = *state.channels.lock().unwrap();
 
-    if samples.is_empty() {
-        return Err("No audio loaded".to_string());
-    }
+    let report = clipping::classify(&samples, sample_rate);
+    info!(
+        "Clipping: {} hard clips ({} samples), {} inter-sample overs (true peak {:.2} dBTP), {:.1} s limited",
+        report.hard_clip_events, report.clipped_samples, report.inter_sample_overs, report.true_peak_dbtp, report.limited_secs
+    );
 
-    // Limit transfer size to prevent IPC crashes (max ~5 million samples = ~60s stereo)
-    const MAX_SAMPLES: usize = 5_000_000;
-    let limited_samples = if samples.len() > MAX_SAMPLES {
-        warn!("Audio too large for full playback ({} samples), limiting to {} samples", samples.len(), MAX_SAMPLES);
-        samples[..MAX_SAMPLES].to_vec()
-    } else {
-        samples
-    };
+    let mut forensic = state.forensic_data.lock().unwrap();
+    forensic.clipped_count = report.clipped_samples;
+    forensic.has_clipping = report.hard_clip_events > 0;
+    forensic.clipping = Some(report.clone());
+    Ok(report)
+}
 
-    Ok(AudioSamples {
-        samples: limited_samples,
-        sample_rate,
-        channels,
-    })
+/// Find thumps from the microphone being handled or knocked, with their
+/// times, for authenticity notes and for cleaning up takes
+#[tauri::command]
+async fn detect_handling_noise(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<handling::HandlingEvent>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let events = handling::detect_handling(&samples, sample_rate);
+    info!("Handling noise detection: {} thumps", events.len());
+
+    state.forensic_data.lock().unwrap().handling_noise = events.clone();
+    Ok(events)
 }
 
-/// Get audio samples in chunks for large files
+/// Decode the touch-tone digits dialed in a call recording, each with its
+/// time, gathered into the numbers they were dialed as
 #[tauri::command]
-fn get_audio_samples_chunk(chunk_index: usize, chunk_size: usize, state: State<'_, AudioState>) -> Result<AudioSamples, String> {
-    let samples = state.samples_interleaved.lock().unwrap();
+async fn detect_dtmf(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<dtmf::DtmfReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
     let sample_rate = *state.sample_rate.lock().unwrap();
-    let channels = *state.channels.lock().unwrap();
 
-    if samples.is_empty() {
-        return Err("No audio loaded".to_string());
-    }
+    let report = dtmf::detect(&samples, sample_rate);
+    info!(
+        "DTMF: {} digits in {} sequences ({})",
+        report.digits.len(),
+        report.sequences.len(),
+        report.sequences.iter().map(|s| s.digits.as_str()).collect::<Vec<_>>().join(", ")
+    );
 
-    let start = chunk_index * chunk_size;
-    let end = (start + chunk_size).min(samples.len());
+    state.forensic_data.lock().unwrap().dtmf = Some(report.clone());
+    Ok(report)
+}
 
-    if start >= samples.len() {
-        return Err("Chunk index out of range".to_string());
+/// Find on-off keyed tones and decode them as Morse, with the time of every
+/// character and the speed and pitch of each transmission
+#[tauri::command]
+async fn decode_morse(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<morse::CwTransmission>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let transmissions = morse::detect(&samples, sample_rate);
+    info!("Morse: {} transmissions", transmissions.len());
+    for t in &transmissions {
+        info!("  {:.2}-{:.2} s at {:.0} Hz, {:.1} WPM: {}", t.start_time, t.end_time, t.frequency_hz, t.wpm, t.text);
     }
 
-    Ok(AudioSamples {
-        samples: samples[start..end].to_vec(),
-        sample_rate,
-        channels,
-    })
+    state.forensic_data.lock().unwrap().cw_transmissions = transmissions.clone();
+    Ok(transmissions)
 }
 
-/// Get total sample count for chunked loading
+/// Look for content above 17 kHz: tracking beacons, bat calls, the
+/// continuous pilots some devices leave, each with its band and time
 #[tauri::command]
-fn get_audio_sample_count(state: State<'_, AudioState>) -> Result<usize, String> {
-    let samples = state.samples_interleaved.lock().unwrap();
-    if samples.is_empty() {
-        return Err("No audio loaded".to_string());
+async fn detect_ultrasonic(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<ultrasonic::UltrasonicReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = ultrasonic::detect(&samples, sample_rate)
+        .ok_or_else(|| format!("Ultrasonic detection needs a sample rate above about 35 kHz, not {} Hz", sample_rate))?;
+    info!("Ultrasonic: {} events ({:.2} s) in {:.0}-{:.0} Hz", report.events.len(), report.active_secs, report.low_hz, report.high_hz);
+    for e in &report.events {
+        info!("  {:.3}-{:.3} s {:.0}-{:.0} Hz, {:?}{}", e.start_time, e.end_time, e.low_hz, e.high_hz, e.kind,
+            if e.continuous { " (continuous)" } else { "" });
     }
-    Ok(samples.len())
+
+    state.forensic_data.lock().unwrap().ultrasonic = Some(report.clone());
+    Ok(report)
 }
 
-/// Export selected audio range to WAV file
+/// Shift the band `low_hz`..`high_hz` (an ultrasonic event, usually) down
+/// to start at 1 kHz and write it to a mono WAV file for listening.
+/// `start_time`/`end_time` default to the whole recording.
 #[tauri::command]
-async fn export_audio(
+async fn export_heterodyne(
     output_path: String,
-    start_time: f32,
-    end_time: f32,
+    low_hz: f32,
+    high_hz: f32,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    channel: Option<ChannelSelect>,
     state: State<'_, AudioState>,
 ) -> Result<(), String> {
-    info!("Exporting audio: {:.3}s - {:.3}s to {}", start_time, end_time, output_path);
-
-    let samples = state.samples_interleaved.lock().unwrap().clone();
+    check_output(&state, &output_path)?;
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
     let sample_rate = *state.sample_rate.lock().unwrap();
-    let channels = *state.channels.lock().unwrap();
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
 
-    if samples.is_empty() {
-        return Err("No audio loaded".to_string());
-    }
+    let shifted = ultrasonic::heterodyne(&samples[start..end], sample_rate, low_hz, high_hz)?;
+    write_wav(&output_path, &shifted, sample_rate, 1, export::WavSampleFormat::Float32, false, new_files_only(&state))?;
 
-    // Calculate sample indices for the selection
-    let samples_per_frame = channels;
-    let start_frame = (start_time * sample_rate as f32) as usize;
-    let end_frame = (end_time * sample_rate as f32) as usize;
-    let start_sample = start_frame * samples_per_frame;
-    let end_sample = (end_frame * samples_per_frame).min(samples.len());
+    info!("{:.0}-{:.0} Hz heterodyned ({} samples) to {}", low_hz, high_hz, shifted.len(), output_path);
+    Ok(())
+}
 
-    if start_sample >= end_sample {
-        return Err("Invalid selection range".to_string());
-    }
+/// Split the recording into speech and non-speech segments. `method`
+/// (default features) picks the detector and `aggressiveness` (0-3,
+/// default 1) how readily the sub-band model leaves noise out. The result
+/// is kept so later analyses (the forensic SNR) can be gated on speech.
+#[tauri::command]
+async fn detect_voice_activity(
+    method: Option<vad::VadMethod>,
+    aggressiveness: Option<u8>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<vad::VadReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
 
-    let selected_samples = &samples[start_sample..end_sample];
-    info!("Exporting {} samples ({} frames)", selected_samples.len(), selected_samples.len() / channels);
+    let report = vad::detect(&samples, sample_rate, method.unwrap_or_default(), aggressiveness.unwrap_or(1));
+    info!(
+        "Voice activity: {} speech segments, {:.1} s ({:.0}%)",
+        report.segments.iter().filter(|s| s.speech).count(),
+        report.speech_secs,
+        100.0 * report.speech_fraction
+    );
 
-    // Create WAV file
-    let spec = hound::WavSpec {
-        channels: channels as u16,
-        sample_rate,
-        bits_per_sample: 32,
-        sample_format: hound::SampleFormat::Float,
+    *state.speech.lock().unwrap() = Some(report.clone());
+    Ok(report)
+}
+
+/// Transcribe the speech offline with a whisper.cpp model (`model_path`, a
+/// ggml `.bin` file; builds with the `whisper` feature). `language` is an
+/// ISO 639-1 code, detected when left out. After voice activity detection
+/// only the speech segments are decoded. Progress is emitted as
+/// `transcription-progress` events (percent); the transcript, with word
+/// timestamps, is kept for `search_transcript`.
+#[tauri::command]
+async fn transcribe(
+    model_path: String,
+    language: Option<String>,
+    channel: Option<ChannelSelect>,
+    app: AppHandle,
+    state: State<'_, AudioState>,
+) -> Result<transcribe::Transcript, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let speech = state.speech.lock().unwrap().as_ref().map(|s| s.speech_ranges());
+    let language = language.filter(|l| !l.is_empty() && l != "auto");
+
+    let progress = move |percent: f32| {
+        if let Err(e) = app.emit("transcription-progress", percent) {
+            warn!("Failed to emit transcription progress: {}", e);
+        }
     };
+    let transcript =
+        transcribe::transcribe(&samples, sample_rate, &model_path, language.as_deref(), speech.as_deref(), progress)?;
+    info!(
+        "Transcription: {} segments, {} words ({})",
+        transcript.segments.len(),
+        transcript.segments.iter().map(|s| s.words.len()).sum::<usize>(),
+        transcript.language
+    );
 
-    let mut writer = hound::WavWriter::create(&output_path, spec)
-        .map_err(|e| format!("Failed to create WAV file: {}", e))?;
+    *state.transcript.lock().unwrap() = Some(transcript.clone());
+    Ok(transcript)
+}
 
-    for &sample in selected_samples {
-        writer.write_sample(sample)
-            .map_err(|e| format!("Failed to write sample: {}", e))?;
-    }
+/// Find `query` in the last transcript, with the times of the matching words
+#[tauri::command]
+fn search_transcript(query: String, state: State<'_, AudioState>) -> Result<Vec<transcribe::TranscriptMatch>, String> {
+    let transcript = state.transcript.lock().unwrap();
+    let transcript = transcript.as_ref().ok_or("No transcript; run transcribe first")?;
+    Ok(transcript.search(&query))
+}
 
-    writer.finalize()
-        .map_err(|e| format!("Failed to finalize WAV file: {}", e))?;
+/// Write the last transcript as SRT or WebVTT subtitles or as JSON with the
+/// word timestamps. `format` defaults to the one `output_path`'s extension
+/// names, SRT otherwise.
+#[tauri::command]
+fn export_transcript(
+    output_path: String,
+    format: Option<transcribe::TranscriptFormat>,
+    state: State<'_, AudioState>,
+) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let transcript = state.transcript.lock().unwrap();
+    let transcript = transcript.as_ref().ok_or("No transcript; run transcribe first")?;
+    let format = format.unwrap_or_else(|| transcribe::TranscriptFormat::from_path(&output_path));
 
-    info!("Export complete: {}", output_path);
+    let text = transcript.render(format)?;
+    write_file(&state, &output_path, text, "transcript")?;
+
+    info!("Transcript ({} segments, {:?}) written to {}", transcript.segments.len(), format, output_path);
     Ok(())
 }
 
-fn main() {
-    // Configure logging with tauri-plugin-log
-    // Logs go to: stdout, webview console, and optionally log files
-    let log_plugin = tauri_plugin_log::Builder::new()
-        .targets([
-            Target::new(TargetKind::Stdout),
-            Target::new(TargetKind::Webview),
-            Target::new(TargetKind::LogDir { file_name: None }), // Writes to app log directory
-        ])
+/// Split the recording where the background noise between speech or music
+/// changes character. `block_length` (default 5 s) sets the comparison
+/// resolution and `threshold_db` (default 4) the RMS band-level difference
+/// that counts as a different environment.
+#[tauri::command]
+async fn segment_background(
+    block_length: Option<f32>,
+    threshold_db: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<noise::AmbienceReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let block_length = block_length.unwrap_or(5.0);
+    if !(1.0..=60.0).contains(&block_length) {
+        return Err("Block length must be between 1 and 60 seconds".to_string());
+    }
+    let report = noise::segment_background(&samples, sample_rate, block_length, threshold_db.unwrap_or(4.0).max(0.5));
+    info!("Background noise: {} segments", report.segments.len());
+
+    state.forensic_data.lock().unwrap().ambience_changes = report.changes.clone();
+    Ok(report)
+}
+
+/// Capture a noise print from a selection of background noise. The print
+/// is kept when other audio is loaded, so it can be matched against other
+/// recordings.
+#[tauri::command]
+async fn capture_noise_print(
+    start_time: f32,
+    end_time: f32,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<noise::NoisePrint, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let (start, end) = selection_range(Some(start_time), Some(end_time), sample_rate, samples.len())?;
+    let print = noise::capture_print(&samples[start..end], sample_rate, start as f32 / sample_rate as f32)?;
+    info!("Noise print captured: {:.2}s - {:.2}s, floor {:.1} dBFS", print.start_time, print.end_time, print.floor_dbfs);
+
+    *state.noise_print.lock().unwrap() = Some(print.clone());
+    Ok(print)
+}
+
+#[derive(Serialize)]
+struct NoiseMatchResult {
+    print: noise::NoisePrint,
+    target_path: Option<String>,
+    matches: Vec<noise::NoiseMatch>,
+}
+
+/// Match the captured noise print against the background noise of the
+/// loaded audio, or of the comparison file when `comparison` is set, per
+/// block of `block_length` seconds (default 5)
+#[tauri::command]
+async fn match_noise_print(
+    block_length: Option<f32>,
+    comparison: Option<bool>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<NoiseMatchResult, String> {
+    let print = state.noise_print.lock().unwrap().clone().ok_or("No noise print captured")?;
+    let block_length = block_length.unwrap_or(5.0);
+    if !(1.0..=60.0).contains(&block_length) {
+        return Err("Block length must be between 1 and 60 seconds".to_string());
+    }
+
+    let (samples, sample_rate, target_path) = if comparison.unwrap_or(false) {
+        let comparison = state.comparison.lock().unwrap();
+        let comparison = comparison.as_ref().ok_or("No comparison audio loaded")?;
+        let samples = match channel.unwrap_or_default() {
+            ChannelSelect::Channel(c) => comparison.channel_samples.get(c).cloned()
+                .ok_or_else(|| format!("Channel {} does not exist in comparison audio", c))?,
+            _ => comparison.samples.clone(),
+        };
+        (samples, comparison.sample_rate, Some(comparison.path.clone()))
+    } else {
+        let samples = select_signal(&state, channel.unwrap_or_default())?;
+        (samples, *state.sample_rate.lock().unwrap(), state.source_path.lock().unwrap().clone())
+    };
+
+    let matches = noise::match_print(&print, &samples, sample_rate, block_length)?;
+    info!("Noise print: {} of {} blocks match", matches.iter().filter(|m| m.matches).count(), matches.len());
+
+    Ok(NoiseMatchResult { print, target_path, matches })
+}
+
+/// Find stretches that occur twice in the recording, such as room tone
+/// copied over a deletion. `min_duration` defaults to 0.5 s and
+/// `min_similarity` (normalized correlation) to 0.9.
+#[tauri::command]
+async fn detect_duplicates(
+    min_duration: Option<f32>,
+    min_similarity: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<duplication::DuplicatePair>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let min_duration = min_duration.unwrap_or(0.5).max(0.05);
+    let min_similarity = min_similarity.unwrap_or(0.9).clamp(0.5, 1.0);
+    let pairs = duplication::detect_duplicates(&samples, sample_rate, min_duration, min_similarity);
+    info!("Copy-move detection: {} duplicated stretches", pairs.len());
+
+    state.forensic_data.lock().unwrap().duplicates = pairs.clone();
+    Ok(pairs)
+}
+
+/// Look for the frame grids of earlier MP3/AAC encodings; more than one
+/// grid means the audio went through several lossy generations
+#[tauri::command]
+async fn analyze_compression(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<compression::CompressionReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = compression::analyze(&samples, sample_rate);
+    info!("Compression history: {} encoder grids", report.generations);
+
+    state.forensic_data.lock().unwrap().compression = Some(report.clone());
+    Ok(report)
+}
+
+/// Measure the bit depth the samples really use, against the one the file
+/// declares. Runs on all channels unless `channel` picks one; the mono
+/// downmix would add resolution of its own.
+#[tauri::command]
+async fn analyze_bit_depth(channel: Option<usize>, state: State<'_, AudioState>) -> Result<bitdepth::BitDepthReport, String> {
+    let samples = match channel {
+        Some(c) => select_signal(&state, ChannelSelect::Channel(c))?,
+        None => state.samples_interleaved.lock().unwrap().clone(),
+    };
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+
+    let report = bitdepth::analyze(&samples, *state.bits_per_sample.lock().unwrap());
+    info!("Bit depth: {:.1} effective of {:?} declared", report.effective_bits, report.declared_bits);
+
+    state.forensic_data.lock().unwrap().bit_depth = Some(report.clone());
+    Ok(report)
+}
+
+/// Screen the least significant bits of integer PCM for hidden data, region
+/// by region and channel by channel. Needs the word length the file states.
+#[tauri::command]
+async fn screen_steganography(state: State<'_, AudioState>) -> Result<stego::StegoReport, String> {
+    let bits = state.bits_per_sample.lock().unwrap().ok_or("LSB screening needs integer PCM with a stated bit depth")?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let report = stego::screen(&state.channel_samples.lock().unwrap(), sample_rate, bits)
+        .ok_or_else(|| format!("The samples are not {}-bit integers (float data has no LSB to screen)", bits))?;
+    info!(
+        "LSB screening: {} regions flagged, max score {:.2}, {:.0}% of regions assessable",
+        report.flagged.len(),
+        report.max_score,
+        100.0 * report.assessable_fraction
+    );
+
+    state.forensic_data.lock().unwrap().steganography = Some(report.clone());
+    Ok(report)
+}
+
+/// Decode SMPTE linear timecode: start timecode, frame rate, drop-frame and
+/// every point where the timecode jumps. Searches all channels for the one
+/// carrying it unless `channel` picks one.
+#[tauri::command]
+async fn decode_ltc(channel: Option<usize>, state: State<'_, AudioState>) -> Result<ltc::LtcTrack, String> {
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let track = match channel {
+        Some(c) => ltc::decode(&select_signal(&state, ChannelSelect::Channel(c))?, sample_rate)
+            .map(|track| ltc::LtcTrack { channel: c, ..track }),
+        None => ltc::decode_channels(&state.channel_samples.lock().unwrap(), sample_rate),
+    };
+    let track = track.ok_or("No linear timecode found")?;
+    info!(
+        "LTC on channel {}: {} to {} at {} fps{} ({:.3} measured), {} discontinuities",
+        track.channel,
+        track.start_timecode,
+        track.end_timecode,
+        track.frame_rate,
+        if track.drop_frame { " drop-frame" } else { "" },
+        track.measured_fps,
+        track.discontinuities.len()
+    );
+
+    state.forensic_data.lock().unwrap().ltc = Some(track.clone());
+    Ok(track)
+}
+
+/// Look for the sharp high-frequency cutoff and top-band gaps of an earlier
+/// lossy encoding in audio that now presents as lossless. The bitrate is
+/// estimated for the codec found by the compression analysis (MP3 if none).
+#[tauri::command]
+async fn detect_transcode(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<compression::CutoffReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let codec = state.forensic_data.lock().unwrap().compression.as_ref().and_then(|c| c.codec);
+    let report = compression::spectral_cutoff(&samples, sample_rate, codec.unwrap_or(compression::CodecFamily::Mp3));
+    info!("Spectral cutoff: {:?} Hz (transcoded: {})", report.cutoff_hz, report.transcoded);
+
+    state.forensic_data.lock().unwrap().spectral_cutoff = Some(report.clone());
+    Ok(report)
+}
+
+/// Look for audio upsampled from a lower rate and estimate that rate
+#[tauri::command]
+async fn detect_upsampling(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<resample::UpsamplingReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = resample::detect_upsampling(&samples, sample_rate);
+    info!("Upsampling: {} (original rate {:?})", report.upsampled, report.original_rate);
+
+    state.forensic_data.lock().unwrap().upsampling = Some(report.clone());
+    Ok(report)
+}
+
+/// Look for imaging, aliasing and periodic interpolation patterns left by
+/// a sample-rate converter
+#[tauri::command]
+async fn detect_src_artifacts(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<resample::SrcArtifactReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = resample::detect_src_artifacts(&samples, sample_rate);
+    info!(
+        "SRC artifacts: imaging {:?}, pattern {:?}, aliasing {}",
+        report.imaging_rate, report.pattern_rate, report.aliasing
+    );
+
+    state.forensic_data.lock().unwrap().src_artifacts = Some(report.clone());
+    Ok(report)
+}
+
+/// Score the likelihood that the audio is a microphone re-capture of a
+/// playback
+#[tauri::command]
+async fn detect_rerecording(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<rerecord::RerecordReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = rerecord::detect_rerecording(&samples, sample_rate);
+    info!("Re-recording confidence: {:.2}", report.confidence);
+
+    state.forensic_data.lock().unwrap().rerecording = Some(report.clone());
+    Ok(report)
+}
+
+/// Screen the speech for signs of TTS or voice conversion, per segment of
+/// `segment_length` seconds (default 4). `model_path` adds the scores of an
+/// ONNX classifier (builds with the `onnx` feature).
+#[tauri::command]
+async fn screen_synthetic_speech(
+    segment_length: Option<f32>,
+    model_path: Option<String>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<synthetic::SyntheticReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let segment_length = segment_length.unwrap_or(4.0).clamp(1.0, 60.0);
+
+    let report = synthetic::screen(&samples, sample_rate, segment_length, model_path.as_deref())?;
+    info!("Synthetic speech score: {:?} (model {:?})", report.score, report.model_score);
+
+    state.forensic_data.lock().unwrap().synthetic_speech = Some(report.clone());
+    Ok(report)
+}
+
+/// Flag spectrogram regions with the artifacts of neural vocoders
+#[tauri::command]
+async fn detect_vocoder_artifacts(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<vocoder::VocoderRegion>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let regions = vocoder::detect_artifacts(&samples, sample_rate);
+    info!("Vocoder artifacts: {} regions", regions.len());
+
+    state.forensic_data.lock().unwrap().vocoder_regions = regions.clone();
+    Ok(regions)
+}
+
+/// Check the voice's pitch against its formant spacing for signs of a
+/// pitch or formant shift, and estimate the shift factor
+#[tauri::command]
+async fn detect_pitch_shift(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<disguise::DisguiseReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = disguise::analyze(&samples, sample_rate);
+    info!("Pitch shift: factor {:?} (flagged: {})", report.shift_factor, report.likely_shifted);
+
+    state.forensic_data.lock().unwrap().pitch_shift = Some(report.clone());
+    Ok(report)
+}
+
+/// Look for time-scale modification: frame-rate modulation, smeared
+/// transients and repeats that give the stretch factor
+#[tauri::command]
+async fn detect_time_stretch(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<stretch::StretchReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = stretch::detect_time_stretch(&samples, sample_rate);
+    info!(
+        "Time stretch: {} (frame period {:?} ms, {} regions)",
+        report.stretched, report.frame_period_ms, report.regions.len()
+    );
+
+    state.forensic_data.lock().unwrap().time_stretch = Some(report.clone());
+    Ok(report)
+}
+
+/// Extract the recorder fingerprint of the loaded audio: noise floor
+/// shape, hiss, DC offset and gain-control behavior
+#[tauri::command]
+async fn fingerprint_device(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<recorder::DeviceFingerprint, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let print = recorder::fingerprint(&samples, sample_rate);
+    info!("Device fingerprint: floor {:?} dBFS, hiss slope {:?} dB/oct", print.floor_dbfs, print.hiss_slope_db);
+
+    state.forensic_data.lock().unwrap().device_fingerprint = Some(print.clone());
+    Ok(print)
+}
+
+/// Look for deep, narrow, persistent notches in the long-term spectrum that
+/// point to filtering (a removed beep or tone) beyond hum removal
+#[tauri::command]
+async fn detect_notches(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<notch::NotchReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = notch::detect_notches(&samples, sample_rate);
+    info!("Notch detection: {} notches, suspicious: {}", report.notches.len(), report.suspicious);
+
+    state.forensic_data.lock().unwrap().notches = Some(report.clone());
+    Ok(report)
+}
+
+/// Find stretches of wind noise on the microphone, to triage outdoor
+/// recordings and exclude the affected time ranges from measurements
+#[tauri::command]
+async fn detect_wind(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<wind::WindReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = wind::detect_wind(&samples, sample_rate);
+    info!("Wind detection: {} regions, {:.1} s", report.regions.len(), report.affected_secs);
+
+    state.forensic_data.lock().unwrap().wind = Some(report.clone());
+    Ok(report)
+}
+
+/// Look for automatic gain control: the background dipping after loud
+/// events and climbing back, with the events that show it
+#[tauri::command]
+async fn detect_agc(
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<agc::AgcReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = agc::detect(&samples, sample_rate);
+    info!("AGC detection: {} of {} pauses recover, detected: {}",
+        report.events.len(), report.pauses_examined, report.detected);
+
+    state.forensic_data.lock().unwrap().agc = Some(report.clone());
+    Ok(report)
+}
+
+/// Identify the encoder of the loaded file from its headers, frame
+/// statistics and coded bandwidth
+#[tauri::command]
+async fn fingerprint_encoder(state: State<'_, AudioState>) -> Result<encoder::EncoderFingerprint, String> {
+    let path = state.source_path.lock().unwrap().clone().ok_or("No file loaded")?;
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let cached = state.forensic_data.lock().unwrap().compression.clone();
+    let compression = match cached {
+        Some(report) => report,
+        None => compression::analyze(&select_signal(&state, ChannelSelect::default())?, sample_rate),
+    };
+    let fingerprint = encoder::fingerprint(&bytes, sample_rate, compression.bandwidth_hz);
+    info!("Encoder fingerprint: {:?} ({:?})", fingerprint.family, fingerprint.basis);
+
+    state.forensic_data.lock().unwrap().encoder = Some(fingerprint.clone());
+    Ok(fingerprint)
+}
+
+/// Set the decimation rate, frame and hop of the ENF analysis STFT
+/// (`None` restores the defaults). Takes effect on the next ENF analysis.
+#[tauri::command]
+fn set_enf_params(params: Option<enf::EnfParams>, state: State<'_, AudioState>) -> Result<enf::EnfParams, String> {
+    let params = params.unwrap_or_default();
+    params.validate()?;
+    info!("ENF parameters: {:?}", params);
+    *state.enf_params.lock().unwrap() = params;
+    Ok(params)
+}
+
+#[tauri::command]
+fn get_enf_params(state: State<'_, AudioState>) -> enf::EnfParams {
+    *state.enf_params.lock().unwrap()
+}
+
+/// Extract the mains frequency trajectory. `grid_freq` defaults to the
+/// detected grid, or whichever of 50/60 Hz carries the stronger hum;
+/// `harmonic` measures on one hum harmonic instead of combining all
+/// harmonics that carry the signal.
+#[tauri::command]
+async fn extract_enf(
+    grid_freq: Option<f32>,
+    harmonic: Option<usize>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<enf::EnfTrack, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let enf_params = *state.enf_params.lock().unwrap();
+    let stft = enf::narrowband_stft(&samples, sample_rate, &enf_params);
+    if stft.times.is_empty() {
+        return Err("Audio too short for ENF analysis".to_string());
+    }
+    let detected = {
+        let forensic = state.forensic_data.lock().unwrap();
+        forensic.enf_present.then_some(forensic.grid_freq)
+    };
+    let grid_freq = grid_freq.or(detected);
+
+    let track = match harmonic {
+        Some(h) => match grid_freq {
+            Some(f) => enf::track(&stft, f, h),
+            None => enf::GRID_FREQS
+                .iter()
+                .filter_map(|&f| enf::track(&stft, f, h))
+                .max_by(|a, b| a.median_strength_db().total_cmp(&b.median_strength_db())),
+        },
+        None => match grid_freq {
+            Some(f) => enf::analyze_grid(&stft, f).map(|d| d.track),
+            None => enf::most_likely_grid(&stft).map(|d| d.track),
+        },
+    }
+    .ok_or("Grid frequency harmonic is out of range for the analysis")?;
+    info!("ENF track: {} frames at {} Hz (harmonics {:?})", track.times.len(), track.grid_freq, track.harmonics);
+
+    state.forensic_data.lock().unwrap().enf_track = Some(track.clone());
+    Ok(track)
+}
+
+/// Map which parts of the recording carry usable hum: ENF strength per
+/// block of `segment_length` seconds (default 10). `grid_freq` defaults to
+/// the detected grid, or whichever of 50/60 Hz is stronger overall.
+#[tauri::command]
+async fn compute_enf_segments(
+    segment_length: Option<f32>,
+    grid_freq: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<enf::EnfSegment>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let enf_params = *state.enf_params.lock().unwrap();
+    let stft = enf::narrowband_stft(&samples, sample_rate, &enf_params);
+    if stft.times.is_empty() {
+        return Err("Audio too short for ENF analysis".to_string());
+    }
+    let grid_freq = match grid_freq {
+        Some(f) => f,
+        None => enf::most_likely_grid(&stft)
+            .map(|d| d.grid_freq)
+            .ok_or("Grid frequency is out of range for the analysis")?,
+    };
+
+    let segments = enf::segment_confidence(&stft, grid_freq, segment_length.unwrap_or(10.0));
+    info!("ENF segments: {} of {} blocks carry {} Hz hum",
+        segments.iter().filter(|s| s.present).count(), segments.len(), grid_freq);
+
+    state.forensic_data.lock().unwrap().enf_segments = segments.clone();
+    Ok(segments)
+}
+
+/// Write the extracted ENF track as CSV with `time,frequency,strength`
+/// columns (seconds, Hz, dB)
+#[tauri::command]
+fn export_enf_csv(output_path: String, state: State<'_, AudioState>) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let forensic = state.forensic_data.lock().unwrap();
+    let track = forensic.enf_track.as_ref().ok_or("No ENF track extracted")?;
+
+    let mut csv = String::from("time,frequency,strength\n");
+    for ((time, freq), strength) in track.times.iter().zip(&track.freqs).zip(&track.strength_db) {
+        csv.push_str(&format!("{:.3},{:.5},{:.2}\n", time, freq, strength));
+    }
+    write_file(&state, &output_path, csv, "CSV file")?;
+
+    info!("ENF track ({} points) written to {}", track.times.len(), output_path);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ForensicReport<'a> {
+    schema_version: u32,
+    generated_at: String,                 // RFC 3339, UTC
+    tool: report::Tool,
+    file: Option<report::FileIdentity>,   // None for generated audio
+    audio: AudioInfo,
+    samples_sha256: String,               // Decoded interleaved samples, little-endian f32
+    parameters: AnalysisParameters,
+    results: &'a ForensicData,
+    markers: Vec<markers::Marker>,        // Made by the examiner or imported
+    evidence_log: Vec<evidence::EvidenceEvent>,  // Evidence mode switched on and off this run
+}
+
+/// Settings the results were computed with
+#[derive(Serialize)]
+struct AnalysisParameters {
+    enf: enf::EnfParams,
+    calibration_offset_db: f32,
+    downmix: Vec<f32>,                    // Weights of the mono analysis signal
+    spectrogram: Option<SpectrogramInfo>,
+}
+
+/// Write everything found so far as JSON for archiving and for diffing
+/// between runs: the results, the source file's metadata and SHA-256 (read
+/// again from disk, so a file changed since loading shows up), a hash of the
+/// decoded samples, the analysis settings and the tool version. The layout
+/// is versioned by `schema_version`.
+///
+/// As HTML the same report becomes a self-contained document for the
+/// deliverable: the spectrogram, waveform and ENF track with splices and
+/// ENF phase jumps marked, the findings and measurements as tables, and the
+/// hashes. It prints to PDF from any browser. `format` defaults to the one
+/// `output_path`'s extension names, JSON otherwise. With `sign`, a manifest
+/// of the report's SHA-256 signed with this installation's key is written
+/// beside it (`<report>.sha256` and `<report>.sha256.sig`).
+#[tauri::command]
+async fn export_report(
+    output_path: String,
+    format: Option<report::ReportFormat>,
+    sign: Option<bool>,
+    app: AppHandle,
+    state: State<'_, AudioState>,
+) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let format = format.unwrap_or_else(|| report::ReportFormat::from_path(&output_path));
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    let file = state.source_path.lock().unwrap().clone().map(|path| report::identify(&path)).transpose()?;
+
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let layout = state.channel_layout.lock().unwrap().clone();
+    let downmix = state.downmix.lock().unwrap().clone();
+    let audio = AudioInfo {
+        duration: (samples.len() / layout.len().max(1)) as f32 / sample_rate as f32,
+        sample_rate,
+        channels: layout.len(),
+        layout_name: channels::layout_name(&layout),
+        channel_layout: layout,
+        downmix: downmix.clone(),
+        bits_per_sample: *state.bits_per_sample.lock().unwrap(),
+    };
+    let parameters = AnalysisParameters {
+        enf: *state.enf_params.lock().unwrap(),
+        calibration_offset_db: *state.calibration_offset_db.lock().unwrap(),
+        downmix,
+        spectrogram: *state.spec_info.lock().unwrap(),
+    };
+
+    let forensic = state.forensic_data.lock().unwrap().clone();
+    let report = ForensicReport {
+        schema_version: report::SCHEMA_VERSION,
+        generated_at: report::timestamp(),
+        tool: report::Tool::current(),
+        file,
+        audio,
+        samples_sha256: report::samples_sha256(&samples),
+        parameters,
+        results: &forensic,
+        markers: state.markers.lock().unwrap().markers.clone(),
+        evidence_log: state.evidence_log.lock().unwrap().clone(),
+    };
+    let text = match format {
+        report::ReportFormat::Json => serde_json::to_string_pretty(&report).map_err(|e| format!("Failed to serialize report: {}", e))?,
+        report::ReportFormat::Html => {
+            let value = serde_json::to_value(&report).map_err(|e| format!("Failed to serialize report: {}", e))?;
+            let (figures, markers) = report_figures(&state, &forensic);
+            report::html(&value, &figures, &markers)
+        }
+    };
+    write_file(&state, &output_path, text, "report")?;
+    if sign.unwrap_or(false) {
+        sign_outputs(&app, std::slice::from_ref(&output_path), &format!("{}.sha256", output_path))?;
+    }
+
+    info!("Report ({:?}, schema {}) written to {}", format, report::SCHEMA_VERSION, output_path);
+    Ok(())
+}
+
+/// This installation's signing key, made on first use and kept in the
+/// app's data directory
+fn signing_key(app: &AppHandle) -> Result<ed25519_dalek::SigningKey, String> {
+    let dir = app.path().app_data_dir().map_err(|e| format!("No app data directory: {}", e))?;
+    sign::load_or_create_key(&dir.join(sign::KEY_FILE))
+}
+
+/// Hash `paths` into a manifest at `manifest_path` signed with this
+/// installation's key
+fn sign_outputs(app: &AppHandle, paths: &[String], manifest_path: &str) -> Result<sign::SignedManifest, String> {
+    let state = app.state::<AudioState>();
+    check_output(&state, manifest_path)?;
+    check_output(&state, &format!("{}.{}", manifest_path, sign::SIGNATURE_EXTENSION))?;
+    let signed = sign::sign(paths, manifest_path, &signing_key(app)?, new_files_only(&state))?;
+    info!("Signed {} files into {} (key {})", signed.files.len(), manifest_path, signed.public_key);
+    Ok(signed)
+}
+
+/// Sign exported files (reports, clips, figures, anything) after the fact:
+/// their SHA-256 go into a manifest, `manifest_path` or the first file's
+/// path plus `.sha256`, signed with this installation's Ed25519 key into
+/// `<manifest>.sig`. The manifest is in `sha256sum` format.
+#[tauri::command]
+async fn sign_files(paths: Vec<String>, manifest_path: Option<String>, app: AppHandle) -> Result<sign::SignedManifest, String> {
+    let manifest_path = match manifest_path {
+        Some(path) => path,
+        None => format!("{}.sha256", paths.first().ok_or("No files to sign")?),
+    };
+    sign_outputs(&app, &paths, &manifest_path)
+}
+
+/// Check a signed manifest: the signature over it, and every file it lists
+/// against its hash. The signature is expected from `public_key` (hex)
+/// when given, else from this installation's key when it has one.
+#[tauri::command]
+async fn verify_signature(manifest_path: String, public_key: Option<String>, app: AppHandle) -> Result<sign::Verification, String> {
+    let trusted = match public_key {
+        Some(text) => Some(sign::parse_public_key(&text)?),
+        None => {
+            let dir = app.path().app_data_dir().map_err(|e| format!("No app data directory: {}", e))?;
+            let path = dir.join(sign::KEY_FILE);
+            if path.exists() { Some(sign::load_or_create_key(&path)?.verifying_key()) } else { None }
+        }
+    };
+    let verification = sign::verify(&manifest_path, trusted.as_ref())?;
+    info!("Verified {}: signature {}, {} of {} files intact", manifest_path,
+        if verification.signature_valid { "valid" } else { "INVALID" },
+        verification.files.iter().filter(|f| f.intact).count(), verification.files.len());
+    Ok(verification)
+}
+
+/// This installation's public key (hex), to give to whoever will verify
+/// its signatures
+#[tauri::command]
+async fn signing_public_key(app: AppHandle) -> Result<String, String> {
+    Ok(sign::public_key(&signing_key(&app)?))
+}
+
+/// Figure size in the HTML report (pixels)
+const REPORT_FIGURE_WIDTH: usize = 1600;
+const REPORT_SPECTROGRAM_HEIGHT: usize = 400;
+const REPORT_WAVEFORM_HEIGHT: usize = 160;
+
+/// The HTML report's figures, and the time-located findings to mark on them
+fn report_figures(state: &AudioState, forensic: &ForensicData) -> (Vec<report::Figure>, Vec<report::Marker>) {
+    let samples = state.samples.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let duration = samples.len() as f32 / sample_rate as f32;
+
+    let mut markers: Vec<report::Marker> = forensic.splice_events.iter()
+        .map(|e| report::Marker { time: e.time, kind: "Splice", detail: format!("confidence {:.2}", e.confidence) })
+        .collect();
+    if forensic.splice_events.is_empty() {
+        markers.extend(forensic.splice_times.iter().map(|&time| report::Marker { time, kind: "Splice", detail: String::new() }));
+    }
+    markers.extend(forensic.enf_phase_jumps.iter().map(|j| report::Marker {
+        time: j.time,
+        kind: "ENF phase jump",
+        detail: format!("{:.0}\u{b0} on harmonic {}", j.jump_deg, j.harmonic),
+    }));
+    markers.extend(state.markers.lock().unwrap().markers.iter().map(|m| {
+        let mut detail = m.label.clone();
+        match (m.end_time, m.frequency) {
+            (Some(end), Some(f)) => detail.push_str(&format!(" (to {}, {:.0} to {:.0} Hz)", report::clock(end), f.low_hz, f.high_hz)),
+            (Some(end), None) => detail.push_str(&format!(" (to {})", report::clock(end))),
+            _ => {}
+        }
+        if !m.notes.is_empty() {
+            detail.push_str(&format!(": {}", m.notes));
+        }
+        report::Marker { time: m.start_time, kind: "Marker", detail }
+    }));
+
+    // The spectrogram on screen, or one of the whole band when none is cached
+    let cached = state.spectrogram.lock().unwrap().clone();
+    let (data, top_hz) = match *state.spec_info.lock().unwrap() {
+        Some(info) if !cached.is_empty() => (cached, info.bins as f32 * info.bin_hz),
+        _ => {
+            let n_fft = 2048;
+            let hop = (samples.len() / REPORT_FIGURE_WIDTH).max(n_fft / 4);
+            let nyquist = sample_rate as f32 / 2.0;
+            (spectrum::spectrogram_db(&samples, sample_rate, n_fft, hop, WindowType::default(), nyquist).1, nyquist)
+        }
+    };
+    let mut figures = vec![
+        report::Figure {
+            title: "Spectrogram".to_string(),
+            content: report::FigureContent::Raster(figures::spectrogram(&data, REPORT_FIGURE_WIDTH, REPORT_SPECTROGRAM_HEIGHT, figures::levels(&data, 100.0))),
+            duration,
+            y_range: (0.0, top_hz),
+            y_unit: "Hz",
+        },
+        report::Figure {
+            title: "Waveform".to_string(),
+            content: report::FigureContent::Raster(figures::waveform(&samples, REPORT_FIGURE_WIDTH, REPORT_WAVEFORM_HEIGHT)),
+            duration,
+            y_range: (-1.0, 1.0),
+            y_unit: "",
+        },
+    ];
+    if let Some(track) = forensic.enf_track.as_ref().filter(|t| !t.times.is_empty()) {
+        let (low, high) = track.freqs.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &f| (lo.min(f), hi.max(f)));
+        let pad = ((high - low) * 0.1).max(0.01);
+        figures.push(report::Figure {
+            title: format!("ENF track ({} Hz grid)", forensic.grid_freq),
+            content: report::FigureContent::Line(track.times.iter().copied().zip(track.freqs.iter().copied()).collect()),
+            duration,
+            y_range: (low - pad, high + pad),
+            y_unit: "Hz",
+        });
+    }
+    (figures, markers)
+}
+
+/// Write one time series as CSV with a time column (seconds), for a
+/// spreadsheet or Python: the ENF track (as extracted), loudness, RMS and
+/// peak level, spectral features or phase correlation. `interval` is the
+/// step between values in seconds (the window of RMS and correlation, the
+/// hop of loudness and spectral features), with a default suiting each.
+/// RMS and spectral features are measured on `channel`, loudness on all
+/// channels, correlation between the first two.
+#[tauri::command]
+async fn export_time_series(
+    output_path: String,
+    series: series::SeriesKind,
+    interval: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let step = |default: f32| interval.unwrap_or(default).max(0.005);
+
+    let table = match series {
+        series::SeriesKind::Enf => {
+            let forensic = state.forensic_data.lock().unwrap();
+            let track = forensic.enf_track.as_ref().ok_or("No ENF track extracted")?;
+            series::Table::from_columns(track.times.clone(), vec![("frequency_hz", track.freqs.clone()), ("strength_db", track.strength_db.clone())])
+        }
+        series::SeriesKind::Loudness => {
+            let channels = state.channel_samples.lock().unwrap().clone();
+            if channels.is_empty() {
+                return Err("No audio loaded".to_string());
+            }
+            let (times, momentary, short_term) = loudness::curve(&channels, sample_rate, step(0.1));
+            series::Table::from_columns(times, vec![("momentary_lufs", momentary), ("short_term_lufs", short_term)])
+        }
+        series::SeriesKind::Rms => {
+            let samples = select_signal(&state, channel.unwrap_or_default())?;
+            series::rms(&samples, sample_rate, step(0.05), *state.calibration_offset_db.lock().unwrap())
+        }
+        series::SeriesKind::SpectralFeatures => {
+            let samples = select_signal(&state, channel.unwrap_or_default())?;
+            series::spectral_features(&samples, sample_rate, step(0.02))
+        }
+        series::SeriesKind::PhaseCorrelation => {
+            let (left, right, _, _) = channel_pair(&state, None, None)?;
+            let window = (step(0.05) * sample_rate as f32) as usize;
+            let (times, correlation) = stereo::correlation_track(&left, &right, sample_rate, window, window);
+            series::Table::from_columns(times, vec![("correlation", correlation)])
+        }
+    };
+    if table.times.is_empty() {
+        return Err("Audio too short for this series".to_string());
+    }
+    write_file(&state, &output_path, table.csv(), "CSV file")?;
+
+    info!("{:?} series ({} rows) written to {}", series, table.times.len(), output_path);
+    Ok(())
+}
+
+/// Write the spectrogram last computed (frames × bins, dB) for NumPy.
+/// `.npy` holds the matrix alone; `.npz` adds its frame start times and bin
+/// frequencies, and feature arrays of the same signal at the same hop: the
+/// spectral features and the RMS and peak level, each with its own times.
+/// The parameters go to a JSON sidecar, `<output_path>.json`.
+#[tauri::command]
+async fn export_numpy(output_path: String, state: State<'_, AudioState>) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let info = state.spec_info.lock().unwrap().ok_or("No spectrogram; run compute_spectrogram first")?;
+    let data = state.spectrogram.lock().unwrap().clone();
+    let times = state.spec_times.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let spectrogram = numpy::Array::matrix("spectrogram", &data);
+    let npz = std::path::Path::new(&output_path).extension().is_some_and(|e| e.eq_ignore_ascii_case("npz"));
+    let arrays = if npz {
+        let samples = select_signal(&state, info.channel)?;
+        let hop = info.hop_length as f32 / sample_rate as f32;
+        let features = series::spectral_features(&samples, sample_rate, hop);
+        let levels = series::rms(&samples, sample_rate, hop, *state.calibration_offset_db.lock().unwrap());
+
+        let mut arrays = vec![
+            spectrogram,
+            numpy::Array::vector("times", times),
+            numpy::Array::vector("freqs", (0..info.bins).map(|k| k as f32 * info.bin_hz).collect()),
+        ];
+        for (prefix, table) in [("features", features), ("level", levels)] {
+            arrays.push(numpy::Array::vector(&format!("{}_times", prefix), table.times.clone()));
+            for (c, name) in table.columns.iter().enumerate() {
+                arrays.push(numpy::Array::vector(name, table.rows.iter().map(|row| row[c]).collect()));
+            }
+        }
+        arrays
+    } else {
+        vec![spectrogram]
+    };
+
+    let bytes = if npz { numpy::npz(&arrays)? } else { numpy::npy(&arrays[0]) };
+    write_file(&state, &output_path, bytes, "NumPy file")?;
+
+    let sidecar = serde_json::json!({
+        "tool": report::Tool::current(),
+        "source_path": *state.source_path.lock().unwrap(),
+        "sample_rate": sample_rate,
+        "spectrogram": info,
+        "spectrogram_units": "dB, 20·log10 of the FFT magnitude of the windowed frame (not normalized)",
+        "calibration_offset_db": *state.calibration_offset_db.lock().unwrap(),
+        "arrays": arrays.iter().map(|a| serde_json::json!({ "name": a.name, "shape": a.shape })).collect::<Vec<_>>(),
+    });
+    let sidecar_path = format!("{}.json", output_path);
+    check_output(&state, &sidecar_path)?;
+    let json = serde_json::to_string_pretty(&sidecar).map_err(|e| format!("Failed to serialize parameters: {}", e))?;
+    write_file(&state, &sidecar_path, json, &sidecar_path)?;
+
+    info!("{} arrays ({} x {} spectrogram) written to {}", arrays.len(), info.frames, info.bins, output_path);
+    Ok(())
+}
+
+/// Write the findings of `sources` (all kinds by default) as an Audacity
+/// label track, to review them in Audacity or any editor that reads one.
+/// Findings come from the analyses already run; returns how many labels
+/// were written.
+#[tauri::command]
+fn export_labels(output_path: String, sources: Option<Vec<labels::LabelSource>>, state: State<'_, AudioState>) -> Result<usize, String> {
+    check_output(&state, &output_path)?;
+    let sources = sources.unwrap_or_else(|| labels::LabelSource::ALL.to_vec());
+    let forensic = state.forensic_data.lock().unwrap();
+    let speech = state.speech.lock().unwrap();
+
+    let mut found = Vec::new();
+    for source in sources {
+        match source {
+            labels::LabelSource::Splices => {
+                found.extend(forensic.splice_events.iter().map(|e| labels::Label::point(e.time, format!("Splice ({:.2})", e.confidence))));
+                if forensic.splice_events.is_empty() {
+                    found.extend(forensic.splice_times.iter().map(|&t| labels::Label::point(t, "Splice".to_string())));
+                }
+            }
+            labels::LabelSource::PhaseResets => found.extend(forensic.phase_resets.iter().map(|r| {
+                let band = match r.band {
+                    splice::PhaseBand::Low => "low",
+                    splice::PhaseBand::Mid => "mid",
+                };
+                labels::Label::point(r.time, format!("Phase reset ({} band)", band))
+            })),
+            labels::LabelSource::EnfPhaseJumps => found.extend(forensic.enf_phase_jumps.iter()
+                .map(|j| labels::Label::point(j.time, format!("ENF phase jump {:.0}\u{b0}", j.jump_deg)))),
+            labels::LabelSource::Clicks => found.extend(forensic.click_events.iter().map(|c| labels::Label {
+                start: c.time,
+                end: c.time + c.duration_ms / 1000.0,
+                text: format!("{:?} {:.1} dBFS", c.kind, c.amplitude_dbfs),
+                frequency: None,
+            })),
+            labels::LabelSource::Clipping => {
+                if let Some(report) = &forensic.clipping {
+                    found.extend(report.events.iter().map(|e| labels::Label {
+                        start: e.start_time,
+                        end: e.end_time,
+                        text: match e.kind {
+                            clipping::ClipKind::HardClip => format!("Hard clip at {:.1} dBFS", e.level_dbfs),
+                            clipping::ClipKind::InterSampleOver => format!("Inter-sample over {:.1} dBTP", e.level_dbfs),
+                            clipping::ClipKind::Limiting => format!("Limiting at {:.1} dBFS", e.level_dbfs),
+                        },
+                        frequency: None,
+                    }));
+                }
+            }
+            labels::LabelSource::HandlingNoise => found.extend(forensic.handling_noise.iter().map(|h| labels::Label {
+                start: h.time,
+                end: h.time + h.duration_ms / 1000.0,
+                text: format!("Handling noise {:.1} dBFS", h.peak_dbfs),
+                frequency: None,
+            })),
+            labels::LabelSource::Speech => {
+                if let Some(report) = speech.as_ref() {
+                    found.extend(report.segments.iter().filter(|s| s.speech)
+                        .map(|s| labels::Label { start: s.start_time, end: s.end_time, text: "Speech".to_string(), frequency: None }));
+                }
+            }
+            labels::LabelSource::Markers => found.extend(state.markers.lock().unwrap().markers.iter().map(|m| labels::Label {
+                start: m.start_time,
+                end: m.end_time.unwrap_or(m.start_time),
+                text: m.label.clone(),
+                frequency: m.frequency,
+            })),
+            labels::LabelSource::Duplicates => found.extend(forensic.duplicates.iter().map(|d| labels::Label {
+                start: d.target_start,
+                end: d.target_start + d.duration,
+                text: format!("Copy of {:.3}s ({:.2})", d.source_start, d.similarity),
+                frequency: None,
+            })),
+        }
+    }
+
+    write_file(&state, &output_path, labels::audacity(&found), "labels")?;
+    info!("{} labels written to {}", found.len(), output_path);
+    Ok(found.len())
+}
+
+#[derive(Serialize)]
+struct LabelImport {
+    markers: Vec<markers::Marker>,      // Added
+    skipped: usize,                     // Outside the recording, or with times that are not numbers
+}
+
+/// Read the labels of an Audacity label track, a Praat TextGrid or an ELAN
+/// document into markers (regions where a label spans time, boxes where an
+/// Audacity label has frequencies too), keeping the tier each came from.
+/// `format` defaults to the one named by `path`'s extension, Audacity
+/// otherwise. Labels a marker could not hold are skipped and counted.
+#[tauri::command]
+fn import_labels(path: String, format: Option<labels::LabelFormat>, state: State<'_, AudioState>) -> Result<LabelImport, String> {
+    let extent = loaded_extent(&state)?;
+    let format = format.unwrap_or_else(|| labels::LabelFormat::from_path(&path));
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let imported = labels::parse(&labels::decode_text(&bytes)?, format)?;
+
+    let nyquist = extent.1;
+    let total = imported.len();
+    let valid: Vec<_> = imported
+        .into_iter()
+        .filter_map(|l| {
+            let end = (l.label.end > l.label.start).then_some(l.label.end);
+            let frequency = l.label.frequency
+                .filter(|f| end.is_some() && f.low_hz < nyquist)
+                .map(|f| markers::FrequencyRange { low_hz: f.low_hz, high_hz: f.high_hz.min(nyquist) });
+            let checked = l.label.end.is_finite() && markers::validate(l.label.start, end, frequency, None, extent).is_ok();
+            checked.then_some((l, end, frequency))
+        })
+        .collect();
+    let skipped = total - valid.len();
+    if skipped > 0 {
+        warn!("Skipped {} of {} labels in {}: outside the recording or not valid times", skipped, total, path);
+    }
+
+    let mut added = Vec::new();
+    if !valid.is_empty() {
+        let before = edit_state(&state);
+        state.history.lock().unwrap().record("Import labels", before);
+        let mut markers = state.markers.lock().unwrap();
+        for (l, end, frequency) in valid {
+            let marker = markers.add(l.label.start, end, l.label.text, l.tier);
+            marker.frequency = frequency;
+            added.push(marker.clone());
+        }
+    }
+    info!("Imported {} markers from {} ({:?})", added.len(), path, format);
+    Ok(LabelImport { markers: added, skipped })
+}
+
+/// The markers on the loaded recording, in the order they were made
+#[tauri::command]
+fn list_markers(state: State<'_, AudioState>) -> Vec<markers::Marker> {
+    state.markers.lock().unwrap().markers.clone()
+}
+
+/// Length (seconds) and Nyquist frequency (Hz) of the loaded recording, or
+/// an error when none is
+fn loaded_extent(state: &AudioState) -> Result<(f32, f32), String> {
+    let frames = state.samples.lock().unwrap().len();
+    if frames == 0 {
+        return Err("No audio loaded".to_string());
+    }
+    let sample_rate = *state.sample_rate.lock().unwrap() as f32;
+    Ok((frames as f32 / sample_rate, sample_rate / 2.0))
+}
+
+/// Mark a point in time, or a region when `end_time` is given, with a
+/// label, a colour ("#rrggbb") and free-text notes. A region given a
+/// `frequency` range too covers that box of the spectrogram only.
+#[tauri::command]
+fn add_marker(
+    start_time: f32,
+    end_time: Option<f32>,
+    label: Option<String>,
+    color: Option<String>,
+    notes: Option<String>,
+    frequency: Option<markers::FrequencyRange>,
+    state: State<'_, AudioState>,
+) -> Result<markers::Marker, String> {
+    markers::validate(start_time, end_time, frequency, color.as_deref(), loaded_extent(&state)?)?;
+    let before = edit_state(&state);
+    state.history.lock().unwrap().record("Add marker", before);
+    let mut set = state.markers.lock().unwrap();
+    let marker = set.add(start_time, end_time, label.unwrap_or_default(), None);
+    marker.color = color;
+    marker.notes = notes.unwrap_or_default();
+    marker.frequency = frequency;
+    Ok(marker.clone())
+}
+
+/// Change a marker's times, frequencies, label, colour or notes; what
+/// `update` leaves out stays as it is
+#[tauri::command]
+fn update_marker(id: u64, update: markers::MarkerUpdate, state: State<'_, AudioState>) -> Result<markers::Marker, String> {
+    let (duration, nyquist) = loaded_extent(&state)?;
+    let before = edit_state(&state);
+    let marker = state.markers.lock().unwrap().update(id, update, duration, nyquist).cloned()?;
+    state.history.lock().unwrap().record("Edit marker", before);
+    Ok(marker)
+}
+
+#[tauri::command]
+fn delete_marker(id: u64, state: State<'_, AudioState>) -> Result<(), String> {
+    let before = edit_state(&state);
+    state.markers.lock().unwrap().delete(id)?;
+    state.history.lock().unwrap().record("Delete marker", before);
+    Ok(())
+}
+
+/// The session of the loaded recording as it stands
+fn current_session(state: &AudioState) -> Result<session::Session, String> {
+    loaded_extent(state)?;
+    let results = serde_json::to_value(&*state.forensic_data.lock().unwrap())
+        .map_err(|e| format!("Failed to serialize results: {}", e))?;
+    Ok(session::Session {
+        session_version: session::SESSION_VERSION,
+        saved_at: report::timestamp(),
+        source_path: state.source_path.lock().unwrap().clone(),
+        markers: state.markers.lock().unwrap().clone(),
+        selections: state.selections.lock().unwrap().clone(),
+        results: Some(results),
+    })
+}
+
+/// Put a session's markers, selections and results in place of the loaded
+/// recording's. Results that do not read back (JSON writes a measurement
+/// that is not a number as null) are left to be run again.
+fn apply_session(state: &AudioState, session: &session::Session) {
+    if let Some(results) = &session.results {
+        match serde_json::from_value::<ForensicData>(results.clone()) {
+            Ok(forensic) => *state.forensic_data.lock().unwrap() = forensic,
+            Err(e) => warn!("The session's analysis results are not restored: {}", e),
+        }
+    }
+    *state.markers.lock().unwrap() = session.markers.clone();
+    *state.selections.lock().unwrap() = session.selections.clone();
+}
+
+/// The time ranges selected in the view, kept with the session
+#[tauri::command]
+fn set_selections(selections: Vec<TimeRange>, state: State<'_, AudioState>) -> Result<(), String> {
+    let (duration, _) = loaded_extent(&state)?;
+    if let Some(bad) = selections.iter().find(|s| !(s.start_time >= 0.0 && s.end_time > s.start_time && s.end_time <= duration)) {
+        return Err(format!("Selection {:.3} to {:.3} s is not within the recording", bad.start_time, bad.end_time));
+    }
+    let before = edit_state(&state);
+    state.history.lock().unwrap().record("Change selections", before);
+    *state.selections.lock().unwrap() = selections;
+    Ok(())
+}
+
+/// Save the session of the loaded recording (its markers, selections and
+/// analysis results) as JSON
+#[tauri::command]
+fn save_session(output_path: String, state: State<'_, AudioState>) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let session = current_session(&state)?;
+    if new_files_only(&state) {
+        session::save_new(&output_path, &session)?;
+    } else {
+        session::save(&output_path, &session)?;
+    }
+    info!("Session ({} markers) saved to {}", session.markers.markers.len(), output_path);
+    Ok(())
+}
+
+/// Take up a saved session on the recording it was saved for, which must
+/// be the one loaded; its markers, selections and results replace the
+/// current ones. Returns the session.
+#[tauri::command]
+fn load_session(path: String, state: State<'_, AudioState>) -> Result<session::Session, String> {
+    loaded_extent(&state)?;
+    let session = session::load(&path)?;
+    let loaded = state.source_path.lock().unwrap().clone();
+    if session.source_path.is_some() && session.source_path != loaded {
+        return Err(format!("The session belongs to {}; load that recording first", session.source_path.unwrap_or_default()));
+    }
+    let before = edit_state(&state);
+    state.history.lock().unwrap().record("Load session", before);
+    apply_session(&state, &session);
+    info!("Session loaded from {} ({} markers)", path, session.markers.markers.len());
+    Ok(session)
+}
+
+/// `name` in the app's data directory
+fn app_data_file(app: &AppHandle, name: &str) -> Result<std::path::PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| format!("No app data directory: {}", e))?;
+    Ok(dir.join(name))
+}
+
+/// Autosave the session every `autosave_secs` of the settings, when there
+/// is a recording loaded and anything changed since the last save
+fn autosave_loop(app: AppHandle) {
+    let mut last_saved = String::new();
+    loop {
+        let interval = app.state::<AudioState>().settings.lock().unwrap().autosave_secs;
+        std::thread::sleep(std::time::Duration::from_secs(if interval == 0 { 5 } else { interval as u64 }));
+        if interval == 0 {
+            continue;
+        }
+        let state = app.state::<AudioState>();
+        let Ok(mut session) = current_session(&state) else { continue };
+        // Compared without the time, which always differs
+        session.saved_at.clear();
+        let Ok(contents) = serde_json::to_string(&session) else { continue };
+        if contents == last_saved {
+            continue;
+        }
+        session.saved_at = report::timestamp();
+        match app_data_file(&app, session::AUTOSAVE_FILE).and_then(|path| session::save(path, &session)) {
+            Ok(()) => {
+                debug!("Session autosaved ({} markers)", session.markers.markers.len());
+                last_saved = contents;
+            }
+            Err(e) => warn!("Autosave failed: {}", e),
+        }
+    }
+}
+
+/// What became of a file taken from the watch folder, emitted as
+/// "watch-folder-file"
+#[derive(Clone, Serialize)]
+struct WatchedFile {
+    path: String,
+    sha256: Option<String>,             // Of the file as taken
+    audio: Option<AudioInfo>,
+    forensic: Option<ForensicData>,     // When the settings ask for analysis
+    error: Option<String>,
+}
+
+/// Hash, decode and, if asked, analyze a file from the watch folder on its
+/// own. The recording under examination, with its markers, history and
+/// session, is left as it is; the interface opens the file if wanted.
+fn take_watched_file(app: &AppHandle, path: String, analyze: bool) -> WatchedFile {
+    let mut watched = WatchedFile { path: path.clone(), sha256: None, audio: None, forensic: None, error: None };
+    let decoded = match report::identify(&path).and_then(|identity| Ok((identity, decode::decode_file(&path)?))) {
+        Ok((identity, decoded)) => {
+            watched.sha256 = Some(identity.sha256);
+            decoded
+        }
+        Err(e) => {
+            watched.error = Some(e);
+            return watched;
+        }
+    };
+    let (channel_samples, samples, info) = decoded_audio(&decoded);
+
+    if analyze {
+        let enf_params = *app.state::<AudioState>().enf_params.lock().unwrap();
+        let mut forensic = forensic_report(&samples, decoded.sample_rate, Weighting::default(), &enf_params, None);
+        forensic.dc_offsets = dc::measure(&channel_samples, decoded.sample_rate);
+        watched.forensic = Some(forensic);
+    }
+    watched.audio = Some(info);
+    watched
+}
+
+/// Poll the watch folder of the settings every `interval_secs`, decoding
+/// each new recording (and analyzing it, if asked) and emitting the outcome.
+/// A change of folder starts over with what is in the new one as known.
+fn watch_loop(app: AppHandle) {
+    let mut folder: Option<watch::WatchFolder> = None;
+    loop {
+        let settings = app.state::<AudioState>().settings.lock().unwrap().watch.clone();
+        std::thread::sleep(std::time::Duration::from_secs(settings.interval_secs.max(1) as u64));
+        let state = app.state::<AudioState>();
+        let Some(directory) = settings.directory else {
+            if folder.take().is_some() {
+                info!("Stopped watching for new recordings");
+                *state.watch.lock().unwrap() = watch::WatchStatus::default();
+            }
+            continue;
+        };
+
+        if folder.as_ref().is_none_or(|f| f.directory != std::path::Path::new(&directory)) {
+            folder = match watch::WatchFolder::new(&directory) {
+                Ok(f) => {
+                    info!("Watching {} for new recordings", directory);
+                    *state.watch.lock().unwrap() = watch::WatchStatus { directory: Some(directory), ..Default::default() };
+                    Some(f)
+                }
+                Err(e) => {
+                    warn!("Watch folder unavailable: {}", e);
+                    *state.watch.lock().unwrap() = watch::WatchStatus { last_error: Some(e), ..Default::default() };
+                    None
+                }
+            };
+            continue;
+        }
+        let Some(watched) = folder.as_mut() else { continue };
+
+        let paths = match watched.poll() {
+            Ok(paths) => paths,
+            Err(e) => {
+                warn!("Watch folder unreadable: {}", e);
+                state.watch.lock().unwrap().last_error = Some(e);
+                continue;
+            }
+        };
+        for path in paths {
+            let path = path.to_string_lossy().into_owned();
+            info!("New recording in the watch folder: {}", path);
+            let taken = take_watched_file(&app, path.clone(), settings.analyze);
+            {
+                let mut status = state.watch.lock().unwrap();
+                status.files_taken += 1;
+                status.last_file = Some(path);
+                status.last_error = taken.error.clone();
+            }
+            if let Some(e) = &taken.error {
+                warn!("Watch folder file {}: {}", taken.path, e);
+            }
+            if let Err(e) = app.emit("watch-folder-file", taken) {
+                warn!("Failed to emit watch folder result: {}", e);
+            }
+        }
+    }
+}
+
+/// At startup: keep the autosave of a run that did not shut down cleanly
+/// for recovery, then mark this run as running
+fn prepare_recovery(app: &AppHandle) -> Result<(), String> {
+    let running = app_data_file(app, session::RUNNING_FILE)?;
+    let autosave = app_data_file(app, session::AUTOSAVE_FILE)?;
+    if running.exists() && autosave.exists() {
+        std::fs::rename(&autosave, app_data_file(app, session::RECOVERY_FILE)?).map_err(|e| format!("Failed to keep the autosave: {}", e))?;
+        warn!("The last run did not shut down cleanly; its session can be recovered");
+    }
+    if let Some(dir) = running.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&running, std::process::id().to_string()).map_err(|e| format!("Failed to write {}: {}", running.display(), e))
+}
+
+/// A clean shutdown: nothing to recover
+fn finish_recovery(app: &AppHandle) {
+    for name in [session::AUTOSAVE_FILE, session::RUNNING_FILE] {
+        if let Ok(path) = app_data_file(app, name) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// The session autosaved before the last run ended without a clean
+/// shutdown, if there is one to recover
+#[tauri::command]
+fn recovery_available(app: AppHandle) -> Result<Option<session::RecoveryInfo>, String> {
+    let path = app_data_file(&app, session::RECOVERY_FILE)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(session::load(&path)?.recovery_info()))
+}
+
+#[derive(Serialize)]
+struct RecoveredSession {
+    audio: AudioInfo,
+    session: session::Session,
+}
+
+/// Reload the recording of the session autosaved before a crash and take
+/// up its markers, selections and results. The autosave is then dropped.
+#[tauri::command]
+async fn recover_session(app: AppHandle, state: State<'_, AudioState>) -> Result<RecoveredSession, String> {
+    let path = app_data_file(&app, session::RECOVERY_FILE)?;
+    let session = session::load(&path)?;
+    let source = session.source_path.clone().ok_or("The autosaved session has no recording to reload")?;
+    let audio = open_recording(source, &app, &state)?;
+    apply_session(&state, &session);
+    std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    info!("Recovered the session autosaved at {} ({} markers)", session.saved_at, session.markers.markers.len());
+    Ok(RecoveredSession { audio, session })
+}
+
+#[tauri::command]
+fn discard_recovery(app: AppHandle) -> Result<(), String> {
+    let path = app_data_file(&app, session::RECOVERY_FILE)?;
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove {}: {}", path.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+/// What exported figures mark: splices, ENF phase jumps and markers
+fn figure_markers(state: &AudioState, forensic: &ForensicData) -> Vec<plot::TimeMarker> {
+    let finding = |start: f32, kind: &'static str, color: [u8; 3]| plot::TimeMarker { start, end: None, label: String::new(), band: None, kind, color };
+    let mut marked: Vec<plot::TimeMarker> = forensic.splice_events.iter().map(|e| finding(e.time, "Splice", [230, 25, 75])).collect();
+    if forensic.splice_events.is_empty() {
+        marked.extend(forensic.splice_times.iter().map(|&t| finding(t, "Splice", [230, 25, 75])));
+    }
+    marked.extend(forensic.enf_phase_jumps.iter().map(|j| finding(j.time, "ENF phase jump", [245, 130, 49])));
+    marked.extend(state.markers.lock().unwrap().markers.iter().map(|m| plot::TimeMarker {
+        start: m.start_time,
+        end: m.end_time,
+        label: m.label.clone(),
+        band: m.frequency.map(|f| (f.low_hz, f.high_hz)),
+        kind: "Marker",
+        color: m.color.as_deref().and_then(markers::parse_color).unwrap_or([240, 50, 230]),
+    }));
+    marked
+}
+
+/// The band searched around each mains hum harmonic found, shaded on
+/// exported spectrograms
+fn enf_bands(forensic: &ForensicData) -> Vec<plot::FrequencyBand> {
+    forensic.enf_harmonics.iter()
+        .filter(|h| forensic.enf_present && h.present)
+        .map(|h| plot::FrequencyBand {
+            low: h.freq - enf::SEARCH_HZ * h.order as f32,
+            high: h.freq + enf::SEARCH_HZ * h.order as f32,
+            label: format!("{} Hz", h.freq),
+            kind: "ENF band",
+            color: [70, 240, 240],
+        })
+        .collect()
+}
+
+/// The title of an exported figure: the one asked for, else the file name
+fn figure_title(state: &AudioState, title: Option<String>, fallback: &str) -> String {
+    title.unwrap_or_else(|| match state.source_path.lock().unwrap().as_deref() {
+        Some(path) => std::path::Path::new(path).file_name().map_or(path.to_string(), |n| n.to_string_lossy().into_owned()),
+        None => fallback.to_string(),
+    })
+}
+
+/// Render the spectrogram of `start_time` to `end_time` (the whole
+/// recording by default) as a figure for publication, with frequency and
+/// time axes, a colour bar, the title and, marked on it, splices, ENF
+/// phase jumps, markers and the band searched around each mains hum
+/// harmonic found. It is computed afresh at the size `options` asks for,
+/// so it does not depend on the view. PNG, or SVG by extension or `format`.
+#[tauri::command]
+async fn export_spectrogram_image(
+    output_path: String,
+    format: Option<plot::ImageFormat>,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    options: Option<plot::FigureOptions>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    let (width, height, n_fft) = (options.width, options.height, options.n_fft);
+
+    // About one frame per pixel column, overlapping on short selections
+    let sr = sample_rate as f32;
+    let hop = ((end - start) / width).max(n_fft / 8).max(1);
+    let max_freq = options.max_freq.unwrap_or(sr / 2.0).clamp(1.0, sr / 2.0);
+    let (_, data) = spectrum::spectrogram_db(&samples[start..end], sample_rate, n_fft, hop, WindowType::default(), max_freq);
+    let bins = data.first().map_or(0, Vec::len);
+    if bins == 0 {
+        return Err("Selection shorter than one FFT frame".to_string());
+    }
+
+    let forensic = state.forensic_data.lock().unwrap().clone();
+    let (markers, bands) = if options.markers {
+        (figure_markers(&state, &forensic), enf_bands(&forensic))
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let figure = plot::Plot {
+        content: plot::PlotContent::Spectrogram { data: &data, max_freq: bins as f32 * sr / n_fft as f32, levels: figures::levels(&data, options.range_db) },
+        start: start as f32 / sr,
+        end: end as f32 / sr,
+        title: figure_title(&state, options.title, "Spectrogram"),
+        markers,
+        bands,
+        playhead: None,
+    };
+    let format = format.unwrap_or_else(|| plot::ImageFormat::from_path(&output_path));
+    let bytes = plot::render(&figure, width, height, format)?;
+    write_file(&state, &output_path, bytes, "image")?;
+
+    info!("Spectrogram figure ({} x {}, {:?}) written to {}", width, height, format, output_path);
+    Ok(())
+}
+
+/// Render the waveform of `start_time` to `end_time` (the whole recording
+/// by default) as an exhibit: each pixel column's peak range with its RMS,
+/// time and amplitude axes, the title and, unless `options` leaves them
+/// out, splices, ENF phase jumps and markers. PNG, or SVG by extension or
+/// `format`; `options` sets the size in pixels.
+#[tauri::command]
+async fn export_waveform_image(
+    output_path: String,
+    format: Option<plot::ImageFormat>,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    options: Option<plot::FigureOptions>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+    let options = options.unwrap_or_default();
+    options.validate()?;
+
+    let forensic = state.forensic_data.lock().unwrap().clone();
+    let sr = sample_rate as f32;
+    let figure = plot::Plot {
+        content: plot::PlotContent::Waveform(&samples[start..end]),
+        start: start as f32 / sr,
+        end: end as f32 / sr,
+        title: figure_title(&state, options.title, "Waveform"),
+        markers: if options.markers { figure_markers(&state, &forensic) } else { Vec::new() },
+        bands: Vec::new(),
+        playhead: None,
+    };
+    let format = format.unwrap_or_else(|| plot::ImageFormat::from_path(&output_path));
+    let bytes = plot::render(&figure, options.width, options.height, format)?;
+    write_file(&state, &output_path, bytes, "image")?;
+
+    info!("Waveform figure ({} x {}, {:?}) written to {}", options.width, options.height, format, output_path);
+    Ok(())
+}
+
+/// Render the spectrogram of `start_time` to `end_time` (the whole
+/// recording by default) as a video with its sound: the spectrogram
+/// scrolls past a playhead in the middle of the frame, with the axes, the
+/// time played and, unless `options` leaves them out, findings and markers
+/// drawn as in the image export. Frames are drawn here and encoded (H.264
+/// and AAC, in the container `output_path`'s extension names) by `ffmpeg`,
+/// which must be on the PATH. Clips run up to five minutes.
+#[tauri::command]
+async fn export_spectrogram_video(
+    output_path: String,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    options: Option<video::VideoOptions>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<(), String> {
+    check_output(&state, &output_path)?;
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    let sr = sample_rate as f32;
+    if (end - start) as f32 / sr > video::MAX_SECS {
+        return Err(format!("Video clips run up to {} s; select a shorter range", video::MAX_SECS));
+    }
+
+    // About one spectrogram frame per pixel column, over the selection and
+    // the half window either side of it that the first and last frames show
+    let n_fft = options.n_fft;
+    let half = options.window_secs / 2.0;
+    let hop = ((options.window_secs * sr) as usize / options.width).max(1);
+    let pad = (half * sr) as usize + n_fft;
+    let (from, to) = (start.saturating_sub(pad), (end + pad).min(samples.len()));
+    let max_freq = options.max_freq.unwrap_or(sr / 2.0).clamp(1.0, sr / 2.0);
+    let (_, data) = spectrum::spectrogram_db(&samples[from..to], sample_rate, n_fft, hop, WindowType::default(), max_freq);
+    let bins = data.first().map_or(0, Vec::len);
+    if bins == 0 {
+        return Err("Selection shorter than one FFT frame".to_string());
+    }
+    let levels = figures::levels(&data, options.range_db);
+
+    // A window's worth of silence at either end, so every frame's window
+    // lies within the frames
+    let frame_secs = hop as f32 / sr;
+    let first_time = (from + n_fft / 2) as f32 / sr;
+    let columns = (options.window_secs / frame_secs).ceil() as usize;
+    let silence = vec![f32::NEG_INFINITY; bins];
+    let padded: Vec<Vec<f32>> = std::iter::repeat_n(silence.clone(), columns)
+        .chain(data)
+        .chain(std::iter::repeat_n(silence, columns))
+        .collect();
+
+    let forensic = state.forensic_data.lock().unwrap().clone();
+    let (markers, bands) = if options.markers {
+        (figure_markers(&state, &forensic), enf_bands(&forensic))
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let title = figure_title(&state, options.title.clone(), "Spectrogram");
+    let top_hz = bins as f32 * sr / n_fft as f32;
+    let draw = |index: usize| {
+        let time = start as f32 / sr + index as f32 / options.fps as f32;
+        let first = (((time - half - first_time) / frame_secs).round() as isize + columns as isize).max(0) as usize;
+        let first = first.min(padded.len() - columns);
+        plot::image(&plot::Plot {
+            content: plot::PlotContent::Spectrogram { data: &padded[first..first + columns], max_freq: top_hz, levels },
+            start: time - half,
+            end: time + half,
+            title: format!("{}   {}", title, report::clock(time)),
+            markers: markers.clone(),
+            bands: bands.clone(),
+            playhead: Some(time),
+        }, options.width, options.height)
+    };
+
+    // The sound of the selection, all channels, for ffmpeg to mux
+    let channels = *state.channels.lock().unwrap();
+    let interleaved = state.samples_interleaved.lock().unwrap()[start * channels..end * channels].to_vec();
+    let audio_path = scratch_file("spectrogram-video", "wav")?;
+    if let Err(e) = write_wav(&audio_path, &interleaved, sample_rate, channels, export::WavSampleFormat::Float32, false, false) {
+        let _ = std::fs::remove_file(&audio_path);
+        return Err(e);
+    }
+
+    let frames = (((end - start) as f32 / sr) * options.fps as f32).ceil().max(1.0) as usize;
+    info!("Rendering {} video frames ({} x {}) to {}", frames, options.width, options.height, output_path);
+    // ffmpeg writes the file itself, into one created new here
+    let encoded = evidence::create_output(&output_path, new_files_only(&state))
+        .and_then(|_| video::encode(&output_path, &audio_path, &options, frames, draw));
+    let _ = std::fs::remove_file(&audio_path);
+    encoded?;
+
+    info!("Spectrogram video written to {}", output_path);
+    Ok(())
+}
+
+/// A new, empty file in the system's temporary directory (never the
+/// evidence working directory), named for this process and a counter and
+/// created only if no file has that name, so renders running at once never
+/// share one
+fn scratch_file(stem: &str, extension: &str) -> Result<String, String> {
+    static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    loop {
+        let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("{}-{}-{}.{}", stem, std::process::id(), n, extension));
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path.to_string_lossy().into_owned()),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Failed to create a scratch file in {}: {}", std::env::temp_dir().display(), e)),
+        }
+    }
+}
+
+/// In evidence mode, refuse a write anywhere but a new file inside the
+/// working directory
+fn check_output(state: &AudioState, path: &str) -> Result<(), String> {
+    match state.evidence.lock().unwrap().as_ref() {
+        Some(mode) => mode.check_output(path),
+        None => Ok(()),
+    }
+}
+
+/// Whether outputs must be created as new files (evidence mode is on)
+fn new_files_only(state: &AudioState) -> bool {
+    state.evidence.lock().unwrap().is_some()
+}
+
+/// Write `contents` to `path`, in evidence mode only as a new file; `what`
+/// names it in the error
+fn write_file(state: &AudioState, path: &str, contents: impl AsRef<[u8]>, what: &str) -> Result<(), String> {
+    evidence::create_output(path, new_files_only(state))?
+        .write_all(contents.as_ref())
+        .map_err(|e| format!("Failed to write {}: {}", what, e))
+}
+
+/// Turn evidence mode on, writing into `working_dir`: from then on the
+/// source is hashed as it is loaded, and every export, figure, report,
+/// session and signature must be a new file inside the working directory.
+/// The loaded recording, if any, is hashed at once.
+#[tauri::command]
+fn enable_evidence_mode(working_dir: String, app: AppHandle, state: State<'_, AudioState>) -> Result<evidence::EvidenceStatus, String> {
+    let mut mode = evidence::EvidenceMode::new(&working_dir)?;
+    if let Some(path) = state.source_path.lock().unwrap().clone() {
+        mode.source = Some(evidence::SourceSeal { sha256: report::identify(&path)?.sha256, path });
+    }
+    info!("Evidence mode on, writing to {}", mode.working_dir.display());
+    log_evidence_switch(&app, &state, mode.event(true, report::timestamp()));
+    *state.evidence.lock().unwrap() = Some(mode);
+    Ok(current_evidence_status(&state))
+}
+
+/// Turn evidence mode off. The switch is logged, and shows in the status
+/// and in every report written afterwards.
+#[tauri::command]
+fn disable_evidence_mode(app: AppHandle, state: State<'_, AudioState>) -> evidence::EvidenceStatus {
+    let mode = state.evidence.lock().unwrap().take();
+    if let Some(mode) = mode {
+        warn!("Evidence mode off (was writing to {})", mode.working_dir.display());
+        log_evidence_switch(&app, &state, mode.event(false, report::timestamp()));
+    }
+    current_evidence_status(&state)
+}
+
+/// Keep a switch of evidence mode for this run's reports and append it to
+/// the log in the app's data directory
+fn log_evidence_switch(app: &AppHandle, state: &AudioState, event: evidence::EvidenceEvent) {
+    if let Err(e) = app_data_file(app, evidence::EVIDENCE_LOG_FILE).and_then(|path| evidence::append_log(&path, &event)) {
+        warn!("Evidence mode switch not logged to disk: {}", e);
+    }
+    state.evidence_log.lock().unwrap().push(event);
+}
+
+fn current_evidence_status(state: &AudioState) -> evidence::EvidenceStatus {
+    let mode = state.evidence.lock().unwrap().clone();
+    let mut status = mode.map_or_else(evidence::EvidenceStatus::disabled, |mode| mode.status());
+    status.log = state.evidence_log.lock().unwrap().clone();
+    status
+}
+
+/// Whether evidence mode is on, and if so whether the source still has the
+/// SHA-256 it had when loaded, with this run's switches on and off
+#[tauri::command]
+async fn evidence_status(state: State<'_, AudioState>) -> Result<evidence::EvidenceStatus, String> {
+    Ok(current_evidence_status(&state))
+}
+
+fn settings_path(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app_data_file(app, settings::SETTINGS_FILE)
+}
+
+/// Put a file just opened first among the recent files, with its SHA-256
+fn remember_recent(app: &AppHandle, state: &AudioState, path: &str, sha256: String) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    settings.add_recent(path.to_string(), sha256, report::timestamp());
+    settings::save(&settings_path(app)?, &settings)
+}
+
+/// The preferences: analysis and export defaults, the colour map and the
+/// files opened recently (most recent first, with their SHA-256 then)
+#[tauri::command]
+fn get_settings(state: State<'_, AudioState>) -> settings::Settings {
+    state.settings.lock().unwrap().clone()
+}
+
+/// Replace the preferences and keep them for the next launch. The ENF
+/// settings take effect at once. The recent files, with the hashes taken
+/// as they were opened, are kept as stored; `clear_recent_files` empties
+/// them. Returns the preferences as stored.
+#[tauri::command]
+fn set_settings(settings: settings::Settings, app: AppHandle, state: State<'_, AudioState>) -> Result<settings::Settings, String> {
+    settings.validate()?;
+    let mut settings = settings;
+    settings.settings_version = settings::SETTINGS_VERSION;
+    let mut stored = state.settings.lock().unwrap();
+    settings.recent_files = stored.recent_files.clone();
+    settings::save(&settings_path(&app)?, &settings)?;
+    *state.enf_params.lock().unwrap() = settings.analysis.enf;
+    *stored = settings.clone();
+    info!("Settings saved");
+    Ok(settings)
+}
+
+/// The watch folder as its thread last left it
+#[tauri::command]
+fn watch_status(state: State<'_, AudioState>) -> watch::WatchStatus {
+    state.watch.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn clear_recent_files(app: AppHandle, state: State<'_, AudioState>) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    settings.recent_files.clear();
+    settings::save(&settings_path(&app)?, &settings)
+}
+
+fn edit_state(state: &AudioState) -> EditState {
+    EditState {
+        markers: state.markers.lock().unwrap().clone(),
+        selections: state.selections.lock().unwrap().clone(),
+        downmix: state.downmix.lock().unwrap().clone(),
+        calibration_offset_db: *state.calibration_offset_db.lock().unwrap(),
+        forensic: state.forensic_data.lock().unwrap().clone(),
+        speech: state.speech.lock().unwrap().clone(),
+    }
+}
+
+/// Put `edit` in place, remixing the analysis signal when its downmix
+/// differs from the current one
+fn restore_edit(state: &AudioState, edit: EditState) {
+    if *state.downmix.lock().unwrap() != edit.downmix {
+        *state.samples.lock().unwrap() = channels::downmix(&state.channel_samples.lock().unwrap(), &edit.downmix);
+        state.spectrogram.lock().unwrap().clear();
+        state.spec_times.lock().unwrap().clear();
+        *state.spec_info.lock().unwrap() = None;
+        *state.downmix.lock().unwrap() = edit.downmix;
+    }
+    *state.markers.lock().unwrap() = edit.markers;
+    *state.selections.lock().unwrap() = edit.selections;
+    *state.calibration_offset_db.lock().unwrap() = edit.calibration_offset_db;
+    *state.forensic_data.lock().unwrap() = edit.forensic;
+    *state.speech.lock().unwrap() = edit.speech;
+}
+
+/// The state after an undo or redo, for the interface to show
+#[derive(Serialize)]
+struct HistoryStep {
+    action: String,                     // The change undone or made again
+    history: undo::HistoryStatus,
+    markers: Vec<markers::Marker>,
+    selections: Vec<TimeRange>,
+    downmix: Vec<f32>,
+    calibration_offset_db: f32,
+}
+
+fn history_step(state: &AudioState, action: String) -> HistoryStep {
+    HistoryStep {
+        action,
+        history: state.history.lock().unwrap().status(),
+        markers: state.markers.lock().unwrap().markers.clone(),
+        selections: state.selections.lock().unwrap().clone(),
+        downmix: state.downmix.lock().unwrap().clone(),
+        calibration_offset_db: *state.calibration_offset_db.lock().unwrap(),
+    }
+}
+
+/// Step back over the latest change to markers, selections, downmix or
+/// calibration on the loaded recording
+#[tauri::command]
+fn undo(state: State<'_, AudioState>) -> Result<HistoryStep, String> {
+    let step = state.history.lock().unwrap().undo(|| edit_state(&state));
+    let (action, before) = step.ok_or("Nothing to undo")?;
+    restore_edit(&state, before);
+    info!("Undid: {}", action);
+    Ok(history_step(&state, action))
+}
+
+#[tauri::command]
+fn redo(state: State<'_, AudioState>) -> Result<HistoryStep, String> {
+    let step = state.history.lock().unwrap().redo(|| edit_state(&state));
+    let (action, after) = step.ok_or("Nothing to redo")?;
+    restore_edit(&state, after);
+    info!("Redid: {}", action);
+    Ok(history_step(&state, action))
+}
+
+/// What `undo` and `redo` would change next
+#[tauri::command]
+fn history_status(state: State<'_, AudioState>) -> undo::HistoryStatus {
+    state.history.lock().unwrap().status()
+}
+
+/// Detect abrupt steps in the phase of the mains hum, measured on its
+/// strongest harmonic unless `harmonic` is given. Steps larger than
+/// `threshold_deg` (default 45°) are reported as likely edit points.
+#[tauri::command]
+async fn detect_enf_phase_jumps(
+    threshold_deg: Option<f32>,
+    harmonic: Option<usize>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<enf::PhaseJump>, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let enf_params = *state.enf_params.lock().unwrap();
+    let stft = enf::narrowband_stft(&samples, sample_rate, &enf_params);
+    let detection = enf::detect(&stft).ok_or("No ENF detected")?;
+    let harmonic = harmonic.unwrap_or_else(|| detection.strongest_harmonic());
+    let jumps = enf::phase_jumps(&stft, detection.grid_freq, harmonic, threshold_deg.unwrap_or(45.0));
+    info!("ENF phase: {} jumps on harmonic {} of {} Hz", jumps.len(), harmonic, detection.grid_freq);
+
+    state.forensic_data.lock().unwrap().enf_phase_jumps = jumps.clone();
+    Ok(jumps)
+}
+
+/// Characterize the mains hum harmonic by harmonic: level, width and
+/// stability of each, for judging the electrical environment and setting
+/// up hum removal. `grid_freq` defaults to the detected grid, or whichever
+/// of 50/60 Hz carries more hum.
+#[tauri::command]
+async fn analyze_hum(
+    grid_freq: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<hum::HumReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let detected = {
+        let forensic = state.forensic_data.lock().unwrap();
+        forensic.enf_present.then_some(forensic.grid_freq)
+    };
+    let report = hum::analyze(&samples, sample_rate, grid_freq.or(detected))
+        .ok_or("Audio too short for hum analysis")?;
+    info!("Hum: {} Hz grid, {} of {} harmonics present", report.grid_freq,
+        report.harmonics.iter().filter(|h| h.present).count(), report.harmonics.len());
+
+    state.forensic_data.lock().unwrap().hum = Some(report.clone());
+    Ok(report)
+}
+
+#[tauri::command]
+async fn measure_level(
+    start_time: f32,
+    end_time: f32,
+    weighting: Option<Weighting>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<LevelMeasurement, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let (start, end) = selection_range(Some(start_time), Some(end_time), sample_rate, samples.len())?;
+
+    let weighting = weighting.unwrap_or_default();
+    debug!("Measuring {:?}-weighted level over {} samples", weighting, end - start);
+
+    // Filter from the start of the file so the selection sees a settled filter state
+    let weighted = weighting.apply(&samples[..end], sample_rate);
+    let selection = &weighted[start..];
+
+    let calibration_offset_db = *state.calibration_offset_db.lock().unwrap();
+    let to_db = |power: f32| 10.0 * power.max(1e-20).log10() + calibration_offset_db;
+    let mean_square = selection.iter().map(|&s| s * s).sum::<f32>() / selection.len() as f32;
+    let peak = selection.iter().fold(0.0f32, |m, &s| m.max(s.abs()));
+
+    // Exponential time weighting on the squared signal
+    let sr = sample_rate as f32;
+    let alpha_fast = 1.0 - (-1.0 / (0.125 * sr)).exp();
+    let alpha_slow = 1.0 - (-1.0 / sr).exp();
+    // Seeded with the Leq so the meters do not start from silence
+    let mut fast = mean_square;
+    let mut slow = mean_square;
+    let mut max_fast = 0.0f32;
+    let mut min_fast = f32::MAX;
+    let mut max_slow = 0.0f32;
+    for &s in selection {
+        let sq = s * s;
+        fast += alpha_fast * (sq - fast);
+        slow += alpha_slow * (sq - slow);
+        max_fast = max_fast.max(fast);
+        min_fast = min_fast.min(fast);
+        max_slow = max_slow.max(slow);
+    }
+
+    Ok(LevelMeasurement {
+        weighting,
+        start_time: start as f32 / sr,
+        end_time: end as f32 / sr,
+        rms_dbfs: to_db(mean_square),
+        peak_dbfs: to_db(peak * peak),
+        max_fast_dbfs: to_db(max_fast),
+        min_fast_dbfs: to_db(min_fast),
+        max_slow_dbfs: to_db(max_slow),
+        calibration_offset_db,
+    })
+}
+
+/// Compute 1/3-octave band levels (IEC 61260 filter bank) for a time range.
+/// When `interval` is given, also returns a band-level time series.
+#[tauri::command]
+async fn compute_octave_bands(
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    interval: Option<f32>,
+    weighting: Option<Weighting>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<OctaveBandData, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let sr = sample_rate as f32;
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+
+    let weighting = weighting.unwrap_or_default();
+    // Pre-roll lets the narrow low-frequency bands settle before the selection
+    let preroll_start = start.saturating_sub(sample_rate as usize / 2);
+    let input = weighting.apply(&samples[preroll_start..end], sample_rate);
+    let offset = start - preroll_start;
+
+    let interval_len = interval
+        .filter(|&t| t > 0.0)
+        .map(|t| ((t * sr) as usize).max(1));
+
+    let bank = octave::third_octave_bank(sample_rate);
+    let center_freqs: Vec<f32> = bank.iter().map(|b| b.nominal_freq).collect();
+    info!("Computing {} third-octave bands over {} samples", bank.len(), end - start);
+
+    // Each band yields (overall mean square, per-interval mean squares)
+    let band_powers: Vec<(f32, Vec<f32>)> = bank
+        .into_par_iter()
+        .map(|mut band| {
+            let filtered = band.filter.process_buffer(&input);
+            let selection = &filtered[offset..];
+            let mean_sq = |x: &[f32]| x.iter().map(|&s| s * s).sum::<f32>() / x.len() as f32;
+            let series = interval_len
+                .map(|len| selection.chunks(len).map(mean_sq).collect())
+                .unwrap_or_default();
+            (mean_sq(selection), series)
+        })
+        .collect();
+
+    let calibration_offset_db = *state.calibration_offset_db.lock().unwrap();
+    let to_db = |power: f32| 10.0 * power.max(1e-20).log10() + calibration_offset_db;
+    let n_intervals = band_powers.first().map(|(_, s)| s.len()).unwrap_or(0);
+    let times = interval_len
+        .map(|len| (0..n_intervals).map(|i| (start + i * len) as f32 / sr).collect())
+        .unwrap_or_default();
+    let series_db = (0..n_intervals)
+        .map(|i| band_powers.iter().map(|(_, s)| to_db(s[i])).collect())
+        .collect();
+
+    Ok(OctaveBandData {
+        center_freqs,
+        levels_db: band_powers.iter().map(|(p, _)| to_db(*p)).collect(),
+        times,
+        series_db,
+        weighting,
+        calibration_offset_db,
+    })
+}
+
+/// Compute a Welch power spectral density estimate (dB/Hz) for a time range
+#[tauri::command]
+async fn compute_psd(
+    segment_length: Option<usize>,
+    overlap: Option<f32>,
+    window: Option<WindowType>,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<PsdData, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+
+    let segment_length = segment_length.unwrap_or(8192);
+    if segment_length < 16 {
+        return Err("Segment length must be at least 16 samples".to_string());
+    }
+    if end - start < segment_length {
+        return Err("Selection is shorter than one segment".to_string());
+    }
+    let overlap = overlap.unwrap_or(0.5).clamp(0.0, 0.95);
+    let window = window.unwrap_or_default();
+
+    debug!("Welch PSD: {} samples, segment {}, overlap {:.2}, {:?} window", end - start, segment_length, overlap, window);
+    let psd = spectrum::welch_psd(
+        &samples[start..end],
+        sample_rate,
+        segment_length,
+        (segment_length as f32 * overlap) as usize,
+        window,
+    );
+
+    let calibration_offset_db = *state.calibration_offset_db.lock().unwrap();
+    Ok(PsdData {
+        freqs: psd.freqs,
+        psd_db: psd.power.iter().map(|&p| 10.0 * p.max(1e-20).log10() + calibration_offset_db).collect(),
+        segment_length,
+        overlap,
+        window,
+        segments: psd.segments,
+        enbw_hz: psd.enbw_hz,
+        calibration_offset_db,
+    })
+}
+
+/// Track the level of specific frequencies over time with the Goertzel
+/// algorithm. `window` (default 0.1 s) sets the frequency resolution,
+/// `hop` defaults to half the window.
+#[tauri::command]
+async fn track_tones(
+    frequencies: Vec<f32>,
+    window: Option<f32>,
+    hop: Option<f32>,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<ToneTrackData, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+
+    if frequencies.is_empty() {
+        return Err("No frequencies given".to_string());
+    }
+    if frequencies.iter().any(|&f| !(f > 0.0 && f < sample_rate as f32 / 2.0)) {
+        return Err("Frequencies must be between 0 Hz and Nyquist".to_string());
+    }
+    let window = window.unwrap_or(0.1).max(0.001);
+    let window_len = (window * sample_rate as f32) as usize;
+    let hop_len = ((hop.unwrap_or(window / 2.0) * sample_rate as f32) as usize).max(1);
+    if end - start < window_len {
+        return Err("Selection is shorter than one window".to_string());
+    }
+
+    let (times, tracks) = tones::track_tones(&samples[start..end], sample_rate, &frequencies, window_len, hop_len);
+    let offset = start as f32 / sample_rate as f32;
+    debug!("Tone tracking: {} frequencies over {} windows", tracks.len(), times.len());
+
+    Ok(ToneTrackData {
+        times: times.iter().map(|t| t + offset).collect(),
+        tracks,
+        window,
+        resolution_hz: 2.0 / window,
+    })
+}
+
+#[derive(Serialize)]
+struct CalibrationResult {
+    tones: Vec<tones::CalibrationTone>,
+    calibration_offset_db: f32,  // Offset now applied to measurements
+}
+
+/// Detect steady calibration tones within the first `search_duration`
+/// seconds (default 60). With `apply_offset`, the first tone matching a
+/// nominal line-up level sets the offset added to level, 1/3-octave and PSD
+/// results so they read as if the chain were aligned.
+#[tauri::command]
+async fn detect_calibration_tones(
+    search_duration: Option<f32>,
+    min_duration: Option<f32>,
+    apply_offset: Option<bool>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<CalibrationResult, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (_, end) = selection_range(None, Some(search_duration.unwrap_or(60.0)), sample_rate, samples.len())?;
+
+    let tones = tones::detect_calibration_tones(&samples[..end], sample_rate, min_duration.unwrap_or(1.0));
+    info!("Calibration tones: {:?}", tones.iter().map(|t| (t.frequency, t.level_dbfs)).collect::<Vec<_>>());
+
+    if apply_offset.unwrap_or(false) {
+        let offset = tones.iter().find_map(|t| t.offset_db).ok_or("No tone at a nominal line-up level")?;
+        let before = edit_state(&state);
+        state.history.lock().unwrap().record("Apply calibration offset", before);
+        *state.calibration_offset_db.lock().unwrap() = offset;
+        info!("Calibration offset set to {:.2} dB", offset);
+    }
+
+    state.forensic_data.lock().unwrap().calibration_tones = tones.clone();
+    Ok(CalibrationResult {
+        tones,
+        calibration_offset_db: *state.calibration_offset_db.lock().unwrap(),
+    })
+}
+
+/// Measure wow and flutter over the selection: on the longest steady test
+/// tone, near `tone_hz` when given, or on `tone_hz` throughout when the tone
+/// wavers too much in level to be found; without a tone, wow alone from the
+/// pitch of sustained notes
+#[tauri::command]
+async fn measure_wow_flutter(
+    tone_hz: Option<f32>,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<flutter::FlutterReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+
+    let mut report = flutter::measure(&samples[start..end], sample_rate, tone_hz)
+        .ok_or("No test tone or sustained notes to measure wow and flutter on")?;
+    let offset = start as f32 / sample_rate as f32;
+    report.start_time += offset;
+    report.end_time += offset;
+    info!("Wow and flutter ({:?}): {:.3}% weighted peak, {:.3}% wow RMS",
+        report.source, report.weighted_peak_percent, report.wow_rms_percent);
+
+    state.forensic_data.lock().unwrap().wow_flutter = Some(report.clone());
+    Ok(report)
+}
+
+/// Set the calibration offset (dB) added to level measurements directly;
+/// `None` clears it
+#[tauri::command]
+fn set_calibration_offset(offset_db: Option<f32>, state: State<'_, AudioState>) -> Result<f32, String> {
+    let offset = offset_db.unwrap_or(0.0);
+    if !offset.is_finite() {
+        return Err("Offset must be finite".to_string());
+    }
+    let before = edit_state(&state);
+    state.history.lock().unwrap().record("Set calibration offset", before);
+    *state.calibration_offset_db.lock().unwrap() = offset;
+    Ok(offset)
+}
+
+/// Measure fundamental frequency, THD and THD+N of a test tone in a time range
+#[tauri::command]
+async fn measure_distortion(
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    max_harmonic: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<distortion::DistortionReport, String> {
+    let samples = state.samples.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+    let report = distortion::measure_thd(&samples[start..end], sample_rate, max_harmonic.unwrap_or(10))
+        .ok_or("Selection too short or contains no tone")?;
+
+    info!("THD+N: {:.1} Hz fundamental, THD {:.4}%, THD+N {:.4}%",
+        report.fundamental_hz, report.thd_percent, report.thd_n_percent);
+    Ok(report)
+}
+
+/// Locate a logarithmic sweep in a time range, deconvolve it and return the
+/// impulse and magnitude response. Passing the sweep's frequencies and
+/// duration (e.g. from the signal generator) skips the rate estimation.
+#[tauri::command]
+async fn measure_sweep_response(
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    start_freq: Option<f32>,
+    end_freq: Option<f32>,
+    sweep_duration: Option<f32>,
+    ir_length: Option<f32>,
+    state: State<'_, AudioState>,
+) -> Result<sweep::SweepResponse, String> {
+    let samples = state.samples.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+    let selection = &samples[start..end];
+    let offset = start as f32 / sample_rate as f32;
+    let detected = sweep::detect_sweep(selection, sample_rate);
+
+    let (params, refine) = match (start_freq, end_freq, sweep_duration) {
+        (Some(f1), Some(f2), Some(duration)) => {
+            if f1 <= 0.0 || f2 <= f1 || duration <= 0.0 {
+                return Err("Invalid sweep parameters".to_string());
+            }
+            let params = sweep::SweepParams {
+                start_time: detected.map(|d| d.start_time).unwrap_or(0.0),
+                duration,
+                start_freq: f1,
+                end_freq: f2,
+            };
+            (params, false)
+        }
+        _ => (detected.ok_or("No logarithmic sweep found in selection")?, true),
+    };
+
+    info!("Sweep at {:.2}s: {:.1} Hz -> {:.1} Hz over {:.2}s",
+        offset + params.start_time, params.start_freq, params.end_freq, params.duration);
+
+    let mut response = sweep::measure_response(selection, sample_rate, params, ir_length.unwrap_or(0.5), refine)
+        .ok_or("Sweep too short to deconvolve")?;
+    response.sweep.start_time += offset;
+    response.ir_peak_time += offset;
+    Ok(response)
+}
+
+/// Estimate the reverberation time (RT60 and EDT per octave band) of a time
+/// range from the decays in the material itself, without a test signal
+#[tauri::command]
+async fn estimate_reverb(
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<reverb::ReverbReport, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let (start, end) = selection_range(start_time, end_time, sample_rate, samples.len())?;
+    let report = reverb::estimate(&samples[start..end], sample_rate);
+    info!("Reverberation: RT60 {:?} s, EDT {:?} s from {} decays", report.rt60_s, report.edt_s, report.decays);
+    Ok(report)
+}
+
+/// Fetch two channels for stereo analyses, defaulting to the first pair
+fn channel_pair(state: &AudioState, left: Option<usize>, right: Option<usize>) -> Result<(Vec<f32>, Vec<f32>, usize, usize), String> {
+    let channels = state.channel_samples.lock().unwrap();
+    if channels.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    if channels.len() < 2 {
+        return Err("Stereo analysis requires at least two channels".to_string());
+    }
+
+    let (l, r) = (left.unwrap_or(0), right.unwrap_or(1));
+    if l == r || l >= channels.len() || r >= channels.len() {
+        return Err(format!("Invalid channel pair {}/{} for {} channels", l, r, channels.len()));
+    }
+    Ok((channels[l].clone(), channels[r].clone(), l, r))
+}
+
+/// Windowed L/R correlation coefficient over time, for mono-compatibility
+/// checks and spotting sections where one channel was replaced
+#[tauri::command]
+async fn compute_phase_correlation(
+    window: Option<f32>,
+    left_channel: Option<usize>,
+    right_channel: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<CorrelationTrack, String> {
+    let (left, right, l, r) = channel_pair(&state, left_channel, right_channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let window = window.unwrap_or(0.1).max(0.005);
+    let window_len = (window * sample_rate as f32) as usize;
+    let (times, correlation) = stereo::correlation_track(&left, &right, sample_rate, window_len, window_len / 2);
+    debug!("Phase correlation: {} windows of {:.3}s", times.len(), window);
+
+    Ok(CorrelationTrack {
+        times,
+        correlation,
+        overall: stereo::correlation(&left, &right),
+        window,
+        left_channel: l,
+        right_channel: r,
+    })
+}
+
+/// Detect duplicated-mono, delayed-copy and mid-derived pseudo-stereo,
+/// reporting the channel relationship and any inter-channel delay
+#[tauri::command]
+async fn analyze_stereo(
+    left_channel: Option<usize>,
+    right_channel: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<stereo::StereoReport, String> {
+    let (left, right, l, r) = channel_pair(&state, left_channel, right_channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = stereo::analyze_relationship(&left, &right, sample_rate);
+    info!("Channels {}/{}: {:?}, delay {:.2} ms", l, r, report.relationship, report.delay_ms);
+
+    if (l, r) == (0, 1) {
+        state.forensic_data.lock().unwrap().stereo = Some(report.clone());
+    }
+    Ok(report)
+}
+
+/// Measure tape azimuth error from the delay between the channels across
+/// frequency. With `tape_speed_ips` and `track_spacing_mm` the delay is
+/// also given as a head angle.
+#[tauri::command]
+async fn measure_azimuth(
+    tape_speed_ips: Option<f32>,
+    track_spacing_mm: Option<f32>,
+    left_channel: Option<usize>,
+    right_channel: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<azimuth::AzimuthReport, String> {
+    let (left, right, l, r) = channel_pair(&state, left_channel, right_channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = azimuth::measure(&left, &right, sample_rate, tape_speed_ips.zip(track_spacing_mm))
+        .ok_or("The channels have too little in common to measure azimuth")?;
+    info!("Azimuth {}/{}: {:.1} us, {:.0} deg at 10 kHz", l, r, report.delay_us, report.phase_at_10k_deg);
+
+    if (l, r) == (0, 1) {
+        state.forensic_data.lock().unwrap().azimuth = Some(report.clone());
+    }
+    Ok(report)
+}
+
+/// Per-channel RMS balance over time and intervals where one channel drops
+/// to silence while another continues (cable faults, careless channel edits)
+#[tauri::command]
+async fn analyze_channel_balance(
+    window: Option<f32>,
+    silence_threshold_db: Option<f32>,
+    min_dropout: Option<f32>,
+    state: State<'_, AudioState>,
+) -> Result<stereo::BalanceReport, String> {
+    let channels = state.channel_samples.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    if channels.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+
+    let window = (window.unwrap_or(0.05).max(0.001) * sample_rate as f32) as usize;
+    let report = stereo::channel_balance(
+        &channels,
+        sample_rate,
+        window,
+        silence_threshold_db.unwrap_or(-60.0),
+        min_dropout.unwrap_or(0.1),
+    );
+    info!("Channel balance: {} dropouts across {} channels", report.dropouts.len(), channels.len());
+
+    state.forensic_data.lock().unwrap().channel_dropouts = report.dropouts.clone();
+    Ok(report)
+}
+
+/// Find sections where two channels are polarity-inverted relative to each
+/// other (strongly negative short-term correlation at near-equal levels)
+#[tauri::command]
+async fn detect_polarity_inversions(
+    window: Option<f32>,
+    threshold: Option<f32>,
+    left_channel: Option<usize>,
+    right_channel: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<Vec<TimeRange>, String> {
+    let (left, right, l, r) = channel_pair(&state, left_channel, right_channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let window = (window.unwrap_or(0.1).max(0.005) * sample_rate as f32) as usize;
+    let ranges = stereo::polarity_inversions(&left, &right, sample_rate, window, threshold.unwrap_or(-0.8));
+    info!("Channels {}/{}: {} polarity-inverted sections", l, r, ranges.len());
+
+    if (l, r) == (0, 1) {
+        state.forensic_data.lock().unwrap().polarity_inversions = ranges.clone();
+    }
+    Ok(ranges)
+}
+
+/// Stereo width (side/mid energy ratio) over time, flagging abrupt width
+/// changes that often coincide with edit points
+#[tauri::command]
+async fn compute_stereo_width(
+    window: Option<f32>,
+    jump_threshold_db: Option<f32>,
+    left_channel: Option<usize>,
+    right_channel: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<stereo::WidthTrack, String> {
+    let (left, right, _, _) = channel_pair(&state, left_channel, right_channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let window = (window.unwrap_or(0.2).max(0.005) * sample_rate as f32) as usize;
+    let track = stereo::width_track(&left, &right, sample_rate, window, jump_threshold_db.unwrap_or(10.0));
+    debug!("Stereo width: {} windows, {} discontinuities", track.times.len(), track.discontinuities.len());
+    Ok(track)
+}
+
+/// Get current forensic data
+#[tauri::command]
+fn get_forensic_data(state: State<'_, AudioState>) -> ForensicData {
+    state.forensic_data.lock().unwrap().clone()
+}
+
+/// Limit on samples sent for playback, to prevent IPC crashes (~5 million
+/// samples = ~60s stereo)
+const MAX_PLAYBACK_SAMPLES: usize = 5_000_000;
+
+/// Seconds of audio before a chunk that the playback EQ is run over first,
+/// so its filters have settled by the chunk's start
+const PLAYBACK_EQ_SETTLE_SECS: usize = 1;
+
+/// Set the EQ `bands` that playback applies, in order (none to play the
+/// recording as it is). Exports and their previews take their own `eq`.
+#[tauri::command]
+fn set_playback_eq(bands: Vec<eq::EqBand>, state: State<'_, AudioState>) -> Result<(), String> {
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    if sample_rate > 0 {
+        eq::chain(&bands, sample_rate)?;
+    }
+    info!("Playback EQ: {} bands", bands.len());
+    *state.playback_eq.lock().unwrap() = bands;
+    Ok(())
+}
+
+/// Interleaved `samples[start..end]` through the playback EQ, run from up to
+/// `PLAYBACK_EQ_SETTLE_SECS` earlier
+fn playback_samples(state: &AudioState, samples: &[f32], sample_rate: u32, channels: usize, start: usize, end: usize) -> Result<Vec<f32>, String> {
+    let bands = state.playback_eq.lock().unwrap().clone();
+    if bands.is_empty() {
+        return Ok(samples[start..end].to_vec());
+    }
+    let from = (start / channels).saturating_sub(PLAYBACK_EQ_SETTLE_SECS * sample_rate as usize) * channels;
+    let to = (end.div_ceil(channels) * channels).min(samples.len());
+    let buffer = export::ExportBuffer { samples: samples[from..to].to_vec(), channels, sample_rate };
+    let filtered = export::equalize(buffer, &bands).map_err(|e| format!("Playback EQ: {}", e))?;
+    Ok(filtered.samples[start - from..end - from].to_vec())
+}
+
+/// Get audio samples for playback (limited to avoid IPC crashes with large
+/// files), through the playback EQ
+#[tauri::command]
+fn get_audio_samples(state: State<'_, AudioState>) -> Result<AudioSamples, String> {
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+
+    if samples.len() > MAX_PLAYBACK_SAMPLES {
+        warn!("Audio too large for full playback ({} samples), limiting to {} samples", samples.len(), MAX_PLAYBACK_SAMPLES);
+    }
+    let limited_samples = playback_samples(&state, &samples, sample_rate, channels, 0, samples.len().min(MAX_PLAYBACK_SAMPLES))?;
+
+    Ok(AudioSamples {
+        samples: limited_samples,
+        sample_rate,
+        channels,
+    })
+}
+
+/// Get audio samples in chunks for large files, through the playback EQ
+#[tauri::command]
+fn get_audio_samples_chunk(chunk_index: usize, chunk_size: usize, state: State<'_, AudioState>) -> Result<AudioSamples, String> {
+    let samples = state.samples_interleaved.lock().unwrap();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+
+    let start = chunk_index * chunk_size;
+    let end = (start + chunk_size).min(samples.len());
+
+    if start >= samples.len() {
+        return Err("Chunk index out of range".to_string());
+    }
+
+    Ok(AudioSamples {
+        samples: playback_samples(&state, &samples, sample_rate, channels, start, end)?,
+        sample_rate,
+        channels,
+    })
+}
+
+/// Get total sample count for chunked loading
+#[tauri::command]
+fn get_audio_sample_count(state: State<'_, AudioState>) -> Result<usize, String> {
+    let samples = state.samples_interleaved.lock().unwrap();
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    Ok(samples.len())
+}
+
+/// Export selected audio range to WAV, or to MP3, Ogg Vorbis or Opus (with
+/// the `opus` feature) for review copies, optionally restricted to a subset
+/// of channels (see `ExportOptions`). Returns how much of it clipped.
+#[tauri::command]
+async fn export_audio(
+    output_path: String,
+    start_time: f32,
+    end_time: f32,
+    options: Option<export::ExportOptions>,
+    app: AppHandle,
+    state: State<'_, AudioState>,
+) -> Result<export::Clipping, String> {
+    check_output(&state, &output_path)?;
+    info!("Exporting audio: {:.3}s - {:.3}s to {}", start_time, end_time, output_path);
+    let options = prepare_options(options.unwrap_or_default(), &state)?;
+
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    let clipping = export_selection(&samples, sample_rate, channels, (start_time, end_time), &output_path, &options, new_files_only(&state))?;
+    if options.sign {
+        sign_outputs(&app, std::slice::from_ref(&output_path), &format!("{}.sha256", output_path))?;
+    }
+
+    info!("Export complete: {}", output_path);
+    Ok(clipping)
+}
+
+/// Fill in what hum removal and noise reduction need from the whole
+/// recording and its analyses, when the options ask for them
+fn prepare_options(mut options: export::ExportOptions, state: &AudioState) -> Result<export::ExportOptions, String> {
+    if options.remove_hum {
+        let forensic = state.forensic_data.lock().unwrap();
+        let report = forensic.hum.as_ref().ok_or("No hum analysis; run analyze_hum first")?;
+        let plan = hum::RemovalPlan::new(report, forensic.enf_track.as_ref()).ok_or("The hum analysis found no hum to remove")?;
+        info!("Removing hum: {} harmonics of {} Hz, {}", plan.harmonics.len(), report.grid_freq,
+            if plan.times.len() > 1 { "following the ENF track" } else { "at fixed frequencies" });
+        options.hum_plan = Some(plan);
+    }
+
+    if let Some(settings) = options.denoise {
+        let samples = state.samples_interleaved.lock().unwrap();
+        let sample_rate = *state.sample_rate.lock().unwrap();
+        let channels = *state.channels.lock().unwrap();
+        let all = channels::deinterleave(&samples, channels);
+        let picked: Vec<&[f32]> = if options.channel_mask.is_empty() {
+            all.iter().map(Vec::as_slice).collect()
+        } else {
+            options.channel_mask.iter()
+                .map(|&c| all.get(c).map(Vec::as_slice).ok_or_else(|| format!("Channel {} does not exist ({} channels)", c, channels)))
+                .collect::<Result<_, _>>()?
+        };
+        let range = match (settings.noise_start, settings.noise_end) {
+            (None, None) => None,
+            (start, end) => Some(selection_range(start, end, sample_rate, all.first().map_or(0, Vec::len))?),
+        };
+        info!("Noise print from {}", range.map_or("the quietest frames".to_string(),
+            |(start, end)| format!("{:.2}-{:.2}s", start as f32 / sample_rate as f32, end as f32 / sample_rate as f32)));
+        options.noise_profile = Some(denoise::noise_profile(&picked, sample_rate, range)?);
+    }
+    Ok(options)
+}
+
+/// Export a rectangle of the spectrogram alone: `start_time`..`end_time`,
+/// keeping `low_hz`..`high_hz` of each STFT frame and resynthesizing by
+/// overlap-add, to listen to one component (a whisper under music) on its
+/// own. `options` as for `export_audio`.
+#[tauri::command]
+async fn export_spectral_selection(
+    output_path: String,
+    start_time: f32,
+    end_time: f32,
+    low_hz: f32,
+    high_hz: f32,
+    options: Option<export::ExportOptions>,
+    state: State<'_, AudioState>,
+) -> Result<export::Clipping, String> {
+    check_output(&state, &output_path)?;
+    info!("Exporting spectral selection: {:.3}s - {:.3}s, {:.0}-{:.0} Hz to {}", start_time, end_time, low_hz, high_hz, output_path);
+    let mut options = prepare_options(options.unwrap_or_default(), &state)?;
+    options.isolate_band = Some((low_hz, high_hz));
+
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    let clipping = export_selection(&samples, sample_rate, channels, (start_time, end_time), &output_path, &options, new_files_only(&state))?;
+
+    info!("Export complete: {}", output_path);
+    Ok(clipping)
+}
+
+/// Join `selections` of the recording, in the order given, into one file
+/// with `crossfade_secs` (0.5 by default) of overlap between neighbours: a
+/// highlights reel. Each selection is processed as `options` ask, save the
+/// fades and normalization, which apply to the reel as a whole.
+#[tauri::command]
+async fn export_concatenated(
+    output_path: String,
+    selections: Vec<TimeRange>,
+    crossfade_secs: Option<f32>,
+    crossfade_shape: Option<export::FadeShape>,
+    options: Option<export::ExportOptions>,
+    app: AppHandle,
+    state: State<'_, AudioState>,
+) -> Result<export::Clipping, String> {
+    check_output(&state, &output_path)?;
+    let options = prepare_options(options.unwrap_or_default(), &state)?;
+    let crossfade = crossfade_secs.unwrap_or(0.5);
+    info!("Exporting {} selections joined with {} s crossfades to {}", selections.len(), crossfade, output_path);
+
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    if selections.is_empty() {
+        return Err("No selections to join".to_string());
+    }
+    let format = options.format.unwrap_or_else(|| export::ExportFormat::from_path(&output_path));
+    let mut piece_options = options.clone();
+    (piece_options.fade_in_secs, piece_options.fade_out_secs, piece_options.normalize) = (None, None, None);
+    let pieces = selections
+        .par_iter()
+        .enumerate()
+        .map(|(i, range)| {
+            process_selection(&samples, sample_rate, channels, range.start_time, range.end_time, format, &piece_options)
+                .map_err(|e| format!("Selection {}: {}", i + 1, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Equal power by default: the joins are between unrelated material
+    let joined = export::concatenate(pieces, crossfade, crossfade_shape.unwrap_or(export::FadeShape::EqualPower))?;
+    let output = export::finish(joined, &options)?;
+    let clipping = write_output(&output, format, &output_path, &options, new_files_only(&state))?;
+    if options.sign {
+        sign_outputs(&app, std::slice::from_ref(&output_path), &format!("{}.sha256", output_path))?;
+    }
+
+    info!("Export complete: {}", output_path);
+    Ok(clipping)
+}
+
+/// Cut `start_time`..`end_time` out of interleaved `samples`, process it and
+/// write it in the format `options` or the path's extension asks for
+fn export_selection(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    (start_time, end_time): (f32, f32),
+    output_path: &str,
+    options: &export::ExportOptions,
+    new_only: bool,
+) -> Result<export::Clipping, String> {
+    let format = options.format.unwrap_or_else(|| export::ExportFormat::from_path(output_path));
+    let output = process_selection(samples, sample_rate, channels, start_time, end_time, format, options)?;
+    write_output(&output, format, output_path, options, new_only)
+}
+
+/// Write processed `output` to `output_path` in `format`, with `new_only`
+/// (evidence mode) as a new file, returning how much of it clipped
+fn write_output(
+    output: &export::ExportBuffer,
+    format: export::ExportFormat,
+    output_path: &str,
+    options: &export::ExportOptions,
+    new_only: bool,
+) -> Result<export::Clipping, String> {
+    info!("Exporting {} samples ({} frames, {} channels)",
+        output.samples.len(), output.samples.len() / output.channels, output.channels);
+    let mut clipping = export::Clipping::measure(&output.samples);
+    if clipping.samples_over > 0 {
+        warn!("{} samples past full scale (peak {:+.1} dBFS) in {}", clipping.samples_over, clipping.peak_dbfs.unwrap_or(0.0), output_path);
+    }
+
+    match format {
+        export::ExportFormat::Wav => clipping.samples_clamped = write_wav(
+            output_path,
+            &output.samples,
+            output.sample_rate,
+            output.channels,
+            options.sample_format,
+            options.dither,
+            new_only,
+        )?,
+        export::ExportFormat::Opus => opus::encode(
+            output,
+            output_path,
+            options.bitrate_kbps,
+            options.bitrate_mode.unwrap_or_default(),
+            options.opus_application.unwrap_or_default(),
+            new_only,
+        )?,
+        format => {
+            // The encoder writes the file itself, into one created new here
+            evidence::create_output(output_path, new_only)?;
+            lossy::encode(output, format, output_path, options.bitrate_kbps, options.quality)?
+        }
+    }
+    Ok(clipping)
+}
+
+/// Cut `start_time`..`end_time` out of interleaved `samples` and apply the
+/// processing `options` ask for, ready for a writer of `format`
+fn process_selection(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    start_time: f32,
+    end_time: f32,
+    format: export::ExportFormat,
+    options: &export::ExportOptions,
+) -> Result<export::ExportBuffer, String> {
+    // Calculate sample indices for the selection
+    let samples_per_frame = channels;
+    let start_frame = (start_time * sample_rate as f32) as usize;
+    let end_frame = (end_time * sample_rate as f32) as usize;
+    let start_sample = start_frame * samples_per_frame;
+    let end_sample = (end_frame * samples_per_frame).min(samples.len());
+
+    if start_sample >= end_sample {
+        return Err("Invalid selection range".to_string());
+    }
+
+    let selected = export::ExportBuffer {
+        samples: samples[start_sample..end_sample].to_vec(),
+        channels,
+        sample_rate,
+    };
+    let mut options = options.clone();
+    options.sample_rate = options.sample_rate.or_else(|| format.required_rate(sample_rate));
+    let offset = start_frame as f32 / sample_rate as f32;
+    options.hum_plan = options.hum_plan.map(|plan| plan.shifted(offset));
+    for region in &mut options.spectral_repairs {
+        region.start_time -= offset;
+        region.end_time -= offset;
+    }
+    export::process(selected, &options)
+}
+
+#[derive(Serialize)]
+struct PreviewResult {
+    #[serde(flatten)]
+    audio: AudioSamples,
+    clipping: export::Clipping,         // Of the whole processed selection, before any truncation
+}
+
+/// Process a selection as `export_audio` would and return it for playback,
+/// so the options (noise reduction, hum removal, ...) can be heard before
+/// anything is written, with how much of it would clip
+#[tauri::command]
+async fn preview_export(
+    start_time: f32,
+    end_time: f32,
+    options: Option<export::ExportOptions>,
+    state: State<'_, AudioState>,
+) -> Result<PreviewResult, String> {
+    let options = prepare_options(options.unwrap_or_default(), &state)?;
+    let format = options.format.unwrap_or(export::ExportFormat::Wav);
+    let output = process_loaded(&state, start_time, end_time, format, &options)?;
+    info!("Previewing {:.3}s - {:.3}s ({} frames)", start_time, end_time, output.samples.len() / output.channels);
+    let mut clipping = export::Clipping::measure(&output.samples);
+    if let Some(bits) = options.sample_format.integer_bits().filter(|_| format == export::ExportFormat::Wav) {
+        clipping.samples_clamped = export::to_integer(&output.samples, bits, options.dither).1;
+    }
+    Ok(PreviewResult { audio: playback_buffer(output), clipping })
+}
+
+/// The selection backwards, to listen for backward-masked content. Export
+/// it with `reverse` set in the options.
+#[tauri::command]
+async fn reverse_selection(start_time: f32, end_time: f32, state: State<'_, AudioState>) -> Result<AudioSamples, String> {
+    let options = export::ExportOptions { reverse: true, ..Default::default() };
+    let output = process_loaded(&state, start_time, end_time, export::ExportFormat::Wav, &options)?;
+    info!("Reversed {:.3}s - {:.3}s", start_time, end_time);
+    Ok(playback_buffer(output))
+}
+
+/// The selection with its polarity flipped, to hear against another
+/// recording in a manual null test. Export it with `invert_polarity` set
+/// in the options.
+#[tauri::command]
+async fn invert_selection(start_time: f32, end_time: f32, state: State<'_, AudioState>) -> Result<AudioSamples, String> {
+    let options = export::ExportOptions { invert_polarity: true, ..Default::default() };
+    let output = process_loaded(&state, start_time, end_time, export::ExportFormat::Wav, &options)?;
+    info!("Inverted the polarity of {:.3}s - {:.3}s", start_time, end_time);
+    Ok(playback_buffer(output))
+}
+
+/// `process_selection` on the loaded audio
+fn process_loaded(
+    state: &AudioState,
+    start_time: f32,
+    end_time: f32,
+    format: export::ExportFormat,
+    options: &export::ExportOptions,
+) -> Result<export::ExportBuffer, String> {
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    process_selection(&samples, sample_rate, channels, start_time, end_time, format, options)
+}
+
+/// Processed audio as sent for playback, cut to what the IPC can carry
+fn playback_buffer(mut output: export::ExportBuffer) -> AudioSamples {
+    if output.samples.len() > MAX_PLAYBACK_SAMPLES {
+        warn!("Preview too large for playback ({} samples), limiting to {} samples", output.samples.len(), MAX_PLAYBACK_SAMPLES);
+        output.samples.truncate(MAX_PLAYBACK_SAMPLES / output.channels * output.channels);
+    }
+    AudioSamples {
+        samples: output.samples,
+        sample_rate: output.sample_rate,
+        channels: output.channels,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BatchExportFailure {
+    name: String,
+    error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BatchExportSummary {
+    exported: Vec<String>,              // Paths written, in selection order
+    failures: Vec<BatchExportFailure>,
+}
+
+/// Export every selection to its own file in `output_dir` with the same
+/// options, several at a time. A failing excerpt does not stop the others;
+/// it is listed in the summary. Progress is emitted as
+/// `batch-export-progress` events (percent).
+#[tauri::command]
+async fn export_selections(
+    output_dir: String,
+    selections: Vec<export::BatchSelection>,
+    options: Option<export::ExportOptions>,
+    app: AppHandle,
+    state: State<'_, AudioState>,
+) -> Result<BatchExportSummary, String> {
+    let options = prepare_options(options.unwrap_or_default(), &state)?;
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    info!("Batch exporting {} selections to {}", selections.len(), output_dir);
+    let summary = export_batch(&samples, sample_rate, channels, &output_dir, &selections, &options, &app)?;
+    if options.sign && !summary.exported.is_empty() {
+        let manifest = std::path::Path::new(&output_dir).join("manifest.sha256");
+        sign_outputs(&app, &summary.exported, &manifest.to_string_lossy())?;
+    }
+    Ok(summary)
+}
+
+#[derive(Serialize)]
+struct SplitExportResult {
+    parts: Vec<TimeRange>,              // As exported, padding included
+    #[serde(flatten)]
+    summary: BatchExportSummary,
+}
+
+/// Split the recording at its silences (see `SilenceSplit`) and export each
+/// part to `output_dir` as `<base_name>_01`, `<base_name>_02`, ... with the
+/// format's extension, like `export_selections`
+#[tauri::command]
+async fn export_split_on_silence(
+    output_dir: String,
+    base_name: String,
+    split: Option<silence::SilenceSplit>,
+    options: Option<export::ExportOptions>,
+    app: AppHandle,
+    state: State<'_, AudioState>,
+) -> Result<SplitExportResult, String> {
+    let options = prepare_options(options.unwrap_or_default(), &state)?;
+    let samples = state.samples_interleaved.lock().unwrap().clone();
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let channels = *state.channels.lock().unwrap();
+
+    if samples.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    let parts = silence::sounding_parts(&samples, channels, sample_rate, &split.unwrap_or_default())?;
+    if parts.is_empty() {
+        return Err("Nothing rises above the silence threshold".to_string());
+    }
+    info!("Splitting on silence: {} parts to {}", parts.len(), output_dir);
+
+    let width = parts.len().to_string().len().max(2);
+    let selections: Vec<export::BatchSelection> = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| export::BatchSelection {
+            start_time: part.start_time,
+            end_time: part.end_time,
+            name: format!("{}_{:0width$}", base_name, i + 1, width = width),
+        })
+        .collect();
+    let summary = export_batch(&samples, sample_rate, channels, &output_dir, &selections, &options, &app)?;
+    if options.sign && !summary.exported.is_empty() {
+        let manifest = std::path::Path::new(&output_dir).join("manifest.sha256");
+        sign_outputs(&app, &summary.exported, &manifest.to_string_lossy())?;
+    }
+    Ok(SplitExportResult { parts, summary })
+}
+
+/// Export `selections` of interleaved `samples` into `output_dir` in
+/// parallel, emitting `batch-export-progress`
+fn export_batch(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    output_dir: &str,
+    selections: &[export::BatchSelection],
+    options: &export::ExportOptions,
+    app: &AppHandle,
+) -> Result<BatchExportSummary, String> {
+    if !std::path::Path::new(output_dir).is_dir() {
+        return Err(format!("Output directory {} does not exist", output_dir));
+    }
+
+    // Resolve every path first so that two excerpts never write one file
+    let mut seen = std::collections::HashSet::new();
+    let paths: Vec<Result<String, String>> = selections
+        .iter()
+        .map(|selection| {
+            let name = std::path::Path::new(&selection.name);
+            if name.file_name().is_none_or(|file| file != name.as_os_str()) {
+                return Err("The name must be a plain file name, without directories".to_string());
+            }
+            let mut path = std::path::Path::new(output_dir).join(name);
+            if path.extension().is_none() {
+                path.set_extension(options.format.map_or("wav", export::ExportFormat::extension));
+            }
+            let path = path.to_string_lossy().into_owned();
+            if !seen.insert(path.clone()) {
+                return Err(format!("Another selection is already exported to {}", path));
+            }
+            check_output(&app.state::<AudioState>(), &path)?;
+            Ok(path)
+        })
+        .collect();
+
+    let new_only = new_files_only(&app.state::<AudioState>());
+    let completed = std::sync::atomic::AtomicUsize::new(0);
+    let results: Vec<Result<String, String>> = selections
+        .par_iter()
+        .zip(paths)
+        .map(|(selection, path)| {
+            let result = path.and_then(|path| {
+                export_selection(samples, sample_rate, channels, (selection.start_time, selection.end_time), &path, options, new_only)
+                    .map(|_| path)
+            });
+            let done = completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            if let Err(e) = app.emit("batch-export-progress", 100.0 * done as f32 / selections.len() as f32) {
+                warn!("Failed to emit batch export progress: {}", e);
+            }
+            result
+        })
+        .collect();
+
+    let mut summary = BatchExportSummary { exported: Vec::new(), failures: Vec::new() };
+    for (selection, result) in selections.iter().zip(results) {
+        match result {
+            Ok(path) => summary.exported.push(path),
+            Err(error) => {
+                warn!("Batch export of {} failed: {}", selection.name, error);
+                summary.failures.push(BatchExportFailure { name: selection.name.clone(), error });
+            }
+        }
+    }
+    info!("Batch export complete: {} written, {} failed", summary.exported.len(), summary.failures.len());
+    Ok(summary)
+}
+
+/// Write interleaved samples to a WAV file, as 32-bit float or as 16- or
+/// 24-bit integers (rounded, optionally dithered, and clamped), returning
+/// how many samples were clamped
+fn write_wav(
+    path: &str,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    format: export::WavSampleFormat,
+    dither: bool,
+    new_only: bool,
+) -> Result<usize, String> {
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate,
+        bits_per_sample: format.integer_bits().unwrap_or(32) as u16,
+        sample_format: if format.integer_bits().is_some() { hound::SampleFormat::Int } else { hound::SampleFormat::Float },
+    };
+
+    let file = evidence::create_output(path, new_only)?;
+    let mut writer = hound::WavWriter::new(std::io::BufWriter::new(file), spec)
+        .map_err(|e| format!("Failed to create WAV file: {}", e))?;
+
+    let clamped = if let Some(bits) = format.integer_bits() {
+        let (codes, clamped) = export::to_integer(samples, bits, dither);
+        if clamped > 0 {
+            warn!("{} samples clamped to {}-bit full scale", clamped, bits);
+        }
+        for code in codes {
+            writer.write_sample(code)
+                .map_err(|e| format!("Failed to write sample: {}", e))?;
+        }
+        clamped
+    } else {
+        for &sample in samples {
+            writer.write_sample(sample)
+                .map_err(|e| format!("Failed to write sample: {}", e))?;
+        }
+        0
+    };
+
+    writer.finalize()
+        .map_err(|e| format!("Failed to finalize WAV file: {}", e))?;
+    Ok(clamped)
+}
+
+/// Generate a test signal, load it as the current audio and optionally
+/// write it to a WAV file
+#[tauri::command]
+async fn generate_signal(
+    spec: generator::SignalSpec,
+    output_path: Option<String>,
+    state: State<'_, AudioState>,
+) -> Result<AudioInfo, String> {
+    let channels = spec.channels.max(1);
+    if channels > 8 {
+        return Err("At most 8 channels can be generated".to_string());
+    }
+
+    info!("Generating {:?}: {:.2}s at {} Hz", spec.signal, spec.duration, spec.sample_rate);
+    let mono = generator::generate(&spec)?;
+    let interleaved: Vec<f32> = mono
+        .iter()
+        .flat_map(|&s| std::iter::repeat_n(s, channels))
+        .collect();
+
+    if let Some(path) = output_path {
+        check_output(&state, &path)?;
+        write_wav(&path, &interleaved, spec.sample_rate, channels, export::WavSampleFormat::Float32, false, new_files_only(&state))?;
+        info!("Generated signal written to {}", path);
+    }
+
+    let generated = decode::DecodedAudio {
+        interleaved,
+        sample_rate: spec.sample_rate,
+        layout: channels::generic_labels(channels),
+        bits_per_sample: None,
+    };
+    Ok(store_audio(&state, generated))
+}
+
+/// The loaded audio and the comparison file as signals to compare: their
+/// mixes, or `channel` of both. Also returns the comparison's rate and path.
+fn comparison_signals(state: &AudioState, channel: Option<usize>) -> Result<(Vec<f32>, Vec<f32>, u32, String), String> {
+    let comparison = state.comparison.lock().unwrap();
+    let comparison = comparison.as_ref().ok_or("No comparison audio loaded")?;
+    let (reference, other) = match channel {
+        Some(c) => {
+            let channels = state.channel_samples.lock().unwrap();
+            let reference = channels.get(c).cloned().ok_or_else(|| format!("Channel {} does not exist", c))?;
+            let other = comparison.channel_samples.get(c).cloned()
+                .ok_or_else(|| format!("Channel {} does not exist in comparison audio", c))?;
+            (reference, other)
+        }
+        None => (state.samples.lock().unwrap().clone(), comparison.samples.clone()),
+    };
+    if reference.is_empty() {
+        return Err("No audio loaded".to_string());
+    }
+    Ok((reference, other, comparison.sample_rate, comparison.path.clone()))
+}
+
+/// `comparison_signals` for the comparisons that need both files at one
+/// rate, returning that rate
+fn comparison_pair(state: &AudioState, channel: Option<usize>) -> Result<(Vec<f32>, Vec<f32>, u32, String), String> {
+    let (reference, other, comparison_rate, path) = comparison_signals(state, channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    if comparison_rate != sample_rate {
+        return Err(format!(
+            "Sample rates differ ({} Hz vs {} Hz)",
+            sample_rate, comparison_rate
+        ));
+    }
+    Ok((reference, other, sample_rate, path))
+}
+
+#[derive(Serialize)]
+struct NullTestResult {
+    comparison_path: String,
+    #[serde(flatten)]
+    report: nulltest::NullTestReport,
+}
+
+/// Null test the loaded audio against the comparison file: align, invert,
+/// sum and report the residual, optionally writing the difference to WAV
+#[tauri::command]
+async fn null_test(
+    options: Option<nulltest::NullTestOptions>,
+    state: State<'_, AudioState>,
+) -> Result<NullTestResult, String> {
+    let options = options.unwrap_or_default();
+    if let Some(path) = &options.output_path {
+        check_output(&state, path)?;
+    }
+    let (reference, other, sample_rate, comparison_path) = comparison_pair(&state, options.channel)?;
+
+    info!("Null test against {}", comparison_path);
+    let (report, difference) = nulltest::null_test(&reference, &other, sample_rate, &options)?;
+    info!("Null test: offset {} samples, residual {:.1} dB ({:?})",
+        report.offset_samples, report.null_depth_db, report.verdict);
+
+    if let Some(path) = &options.output_path {
+        write_wav(path, &difference, sample_rate, 1, export::WavSampleFormat::Float32, false, new_files_only(&state))?;
+        info!("Difference signal written to {}", path);
+    }
+
+    Ok(NullTestResult {
+        comparison_path,
+        report,
+    })
+}
+
+#[derive(Serialize)]
+struct DifferenceResult {
+    comparison_path: String,
+    #[serde(flatten)]
+    report: difference::DifferenceReport,
+}
+
+/// Align the comparison file to the loaded audio and subtract their
+/// spectrograms, with the difference summarized per octave band
+#[tauri::command]
+async fn compare_spectrograms(
+    options: Option<difference::DifferenceOptions>,
+    state: State<'_, AudioState>,
+) -> Result<DifferenceResult, String> {
+    let options = options.unwrap_or_default();
+    let (reference, other, sample_rate, comparison_path) = comparison_pair(&state, options.channel)?;
+
+    info!("Difference spectrogram against {}", comparison_path);
+    let report = difference::difference(&reference, &other, sample_rate, &options)?;
+    info!("Difference spectrogram: offset {} samples, mean {:.1} dB, {} divergent ranges",
+        report.offset_samples, report.mean_abs_db, report.divergent_ranges.len());
+
+    Ok(DifferenceResult {
+        comparison_path,
+        report,
+    })
+}
+
+#[derive(Serialize)]
+struct DriftResult {
+    comparison_path: String,
+    #[serde(flatten)]
+    report: drift::DriftReport,
+}
+
+/// Estimate how fast the comparison file's sample clock runs against the
+/// loaded audio's, from offsets measured along the recording
+#[tauri::command]
+async fn estimate_clock_drift(
+    options: Option<drift::DriftOptions>,
+    state: State<'_, AudioState>,
+) -> Result<DriftResult, String> {
+    let options = options.unwrap_or_default();
+    let (reference, other, sample_rate, comparison_path) = comparison_pair(&state, options.channel)?;
+
+    info!("Clock drift against {}", comparison_path);
+    let report = drift::estimate_drift(&reference, &other, sample_rate, &options)?;
+    match report.drift_ppm {
+        Some(ppm) => info!("Clock drift: {:.2} ppm from {} of {} points", ppm, report.points_fitted, report.points.len()),
+        None => warn!("Clock drift: too few points correlate to fit ({} of {})", report.points_fitted, report.points.len()),
+    }
+
+    Ok(DriftResult {
+        comparison_path,
+        report,
+    })
+}
+
+#[derive(Serialize)]
+struct SimilarityResult {
+    comparison_path: String,
+    #[serde(flatten)]
+    report: similarity::SimilarityReport,
+}
+
+/// Score how much of the comparison file is material from the loaded
+/// audio, mapping the stretches it repeats
+#[tauri::command]
+async fn compare_content(
+    options: Option<similarity::SimilarityOptions>,
+    state: State<'_, AudioState>,
+) -> Result<SimilarityResult, String> {
+    let options = options.unwrap_or_default();
+    let (reference, other, sample_rate, comparison_path) = comparison_pair(&state, options.channel)?;
+
+    info!("Content similarity against {}", comparison_path);
+    let report = similarity::compare(&reference, &other, sample_rate, &options)?;
+    info!("Content similarity: score {:.2} ({:?}), {} matched regions covering {:.0}% of the comparison",
+        report.score, report.verdict, report.regions.len(), 100.0 * report.comparison_overlap);
+
+    Ok(SimilarityResult {
+        comparison_path,
+        report,
+    })
+}
+
+#[derive(Serialize)]
+struct DeviceComparisonResult {
+    comparison_path: String,
+    #[serde(flatten)]
+    report: recorder::DeviceComparison,
+}
+
+/// Compare the recorder fingerprints of the loaded audio and the
+/// comparison file, to test whether one recorder made both
+#[tauri::command]
+async fn compare_devices(
+    channel: Option<usize>,
+    state: State<'_, AudioState>,
+) -> Result<DeviceComparisonResult, String> {
+    // Each file is fingerprinted at its own rate
+    let (reference, other, comparison_rate, comparison_path) = comparison_signals(&state, channel)?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+
+    let report = recorder::compare(
+        recorder::fingerprint(&reference, sample_rate),
+        recorder::fingerprint(&other, comparison_rate),
+    );
+    info!("Device comparison against {}: distance {:?} ({:?})", comparison_path, report.distance, report.verdict);
+
+    Ok(DeviceComparisonResult {
+        comparison_path,
+        report,
+    })
+}
+
+#[derive(Serialize)]
+struct RoomComparisonResult {
+    second_path: Option<String>,
+    #[serde(flatten)]
+    report: room::RoomComparison,
+}
+
+/// Compare the acoustic environment of two recordings: the `first` range of
+/// the loaded audio (default all of it) against the `second` range of the
+/// comparison file when `comparison` is set, or of the loaded audio
+#[tauri::command]
+async fn compare_rooms(
+    first: Option<TimeRange>,
+    second: Option<TimeRange>,
+    comparison: Option<bool>,
+    channel: Option<ChannelSelect>,
+    state: State<'_, AudioState>,
+) -> Result<RoomComparisonResult, String> {
+    let samples = select_signal(&state, channel.unwrap_or_default())?;
+    let sample_rate = *state.sample_rate.lock().unwrap();
+    let (other, other_rate, second_path) = if comparison.unwrap_or(false) {
+        let comparison = state.comparison.lock().unwrap();
+        let comparison = comparison.as_ref().ok_or("No comparison audio loaded")?;
+        let other = match channel.unwrap_or_default() {
+            ChannelSelect::Channel(c) => comparison.channel_samples.get(c).cloned()
+                .ok_or_else(|| format!("Channel {} does not exist in comparison audio", c))?,
+            _ => comparison.samples.clone(),
+        };
+        (other, comparison.sample_rate, Some(comparison.path.clone()))
+    } else if second.is_some() {
+        (samples.clone(), sample_rate, state.source_path.lock().unwrap().clone())
+    } else {
+        return Err("Select a second range or use the comparison file".to_string());
+    };
+
+    let range = |selection: Option<TimeRange>, rate: u32, len: usize| {
+        selection_range(selection.map(|r| r.start_time), selection.map(|r| r.end_time), rate, len)
+    };
+    let (start, end) = range(first, sample_rate, samples.len())?;
+    let (other_start, other_end) = range(second, other_rate, other.len())?;
+    let report = room::compare(
+        room::profile(&samples[start..end], sample_rate),
+        room::profile(&other[other_start..other_end], other_rate),
+    );
+    info!("Room comparison: similarity {:.2} ({:?})", report.similarity, report.verdict);
+
+    Ok(RoomComparisonResult { second_path, report })
+}
+
+fn main() {
+    // Configure logging with tauri-plugin-log
+    // Logs go to: stdout, webview console, and optionally log files
+    let log_plugin = tauri_plugin_log::Builder::new()
+        .targets([
+            Target::new(TargetKind::Stdout),
+            Target::new(TargetKind::Webview),
+            Target::new(TargetKind::LogDir { file_name: None }), // Writes to app log directory
+        ])
         .level(if cfg!(debug_assertions) {
             log::LevelFilter::Debug
         } else {
@@ -492,26 +4349,172 @@ fn main() {
         .manage(AudioState {
             samples: Mutex::new(Vec::new()),
             samples_interleaved: Mutex::new(Vec::new()),
+            channel_samples: Mutex::new(Vec::new()),
+            channel_layout: Mutex::new(Vec::new()),
+            downmix: Mutex::new(Vec::new()),
             sample_rate: Mutex::new(44100),
             channels: Mutex::new(2),
             spectrogram: Mutex::new(Vec::new()),
             spec_times: Mutex::new(Vec::new()),
+            spec_info: Mutex::new(None),
             forensic_data: Mutex::new(ForensicData::default()),
+            comparison: Mutex::new(None),
+            enf_params: Mutex::new(enf::EnfParams::default()),
+            calibration_offset_db: Mutex::new(0.0),
+            source_path: Mutex::new(None),
+            bits_per_sample: Mutex::new(None),
+            noise_print: Mutex::new(None),
+            speech: Mutex::new(None),
+            transcript: Mutex::new(None),
+            markers: Mutex::new(markers::MarkerSet::default()),
+            selections: Mutex::new(Vec::new()),
+            settings: Mutex::new(settings::Settings::default()),
+            evidence: Mutex::new(None),
+            history: Mutex::new(undo::History::default()),
+            watch: Mutex::new(watch::WatchStatus::default()),
+            evidence_log: Mutex::new(Vec::new()),
+            playback_eq: Mutex::new(Vec::new()),
         })
         .invoke_handler(tauri::generate_handler![
             load_audio,
+            load_comparison_audio,
             compute_spectrogram,
+            get_spectrogram_info,
+            set_downmix,
             analyze_forensics,
+            detect_splices,
+            detect_phase_resets,
+            detect_clicks,
+            detect_handling_noise,
+            detect_dtmf,
+            decode_morse,
+            detect_ultrasonic,
+            export_heterodyne,
+            detect_clipping,
+            segment_background,
+            detect_voice_activity,
+            transcribe,
+            search_transcript,
+            export_transcript,
+            capture_noise_print,
+            match_noise_print,
+            detect_duplicates,
+            analyze_compression,
+            fingerprint_encoder,
+            detect_transcode,
+            analyze_bit_depth,
+            screen_steganography,
+            decode_ltc,
+            detect_upsampling,
+            detect_src_artifacts,
+            detect_rerecording,
+            screen_synthetic_speech,
+            detect_vocoder_artifacts,
+            detect_pitch_shift,
+            detect_time_stretch,
+            fingerprint_device,
+            detect_notches,
+            detect_agc,
+            detect_wind,
+            set_enf_params,
+            get_enf_params,
+            extract_enf,
+            export_enf_csv,
+            export_report,
+            sign_files,
+            verify_signature,
+            signing_public_key,
+            export_time_series,
+            export_numpy,
+            export_labels,
+            import_labels,
+            list_markers,
+            add_marker,
+            update_marker,
+            delete_marker,
+            save_session,
+            set_selections,
+            load_session,
+            recovery_available,
+            recover_session,
+            discard_recovery,
+            undo,
+            redo,
+            history_status,
+            get_settings,
+            set_settings,
+            clear_recent_files,
+            watch_status,
+            enable_evidence_mode,
+            disable_evidence_mode,
+            evidence_status,
+            export_spectrogram_image,
+            export_waveform_image,
+            export_spectrogram_video,
+            compute_enf_segments,
+            detect_enf_phase_jumps,
+            analyze_hum,
+            measure_level,
+            compute_octave_bands,
+            compute_psd,
+            track_tones,
+            detect_calibration_tones,
+            measure_wow_flutter,
+            set_calibration_offset,
+            measure_distortion,
+            measure_sweep_response,
+            estimate_reverb,
+            compute_phase_correlation,
+            analyze_stereo,
+            measure_azimuth,
+            analyze_channel_balance,
+            detect_polarity_inversions,
+            compute_stereo_width,
             get_forensic_data,
             get_audio_samples,
             get_audio_samples_chunk,
+            set_playback_eq,
             get_audio_sample_count,
             export_audio,
+            export_selections,
+            export_split_on_silence,
+            export_spectral_selection,
+            export_concatenated,
+            preview_export,
+            reverse_selection,
+            invert_selection,
+            generate_signal,
+            null_test,
+            compare_spectrograms,
+            estimate_clock_drift,
+            compare_content,
+            compare_devices,
+            compare_rooms,
         ])
-        .setup(|_app| {
+        .setup(|app| {
+            match settings_path(app.handle()).and_then(|path| settings::load(&path)) {
+                Ok(settings) => {
+                    let state = app.state::<AudioState>();
+                    *state.enf_params.lock().unwrap() = settings.analysis.enf;
+                    *state.settings.lock().unwrap() = settings;
+                }
+                Err(e) => warn!("Starting from default settings: {}", e),
+            }
+            if let Err(e) = prepare_recovery(app.handle()) {
+                warn!("Crash recovery unavailable: {}", e);
+            }
+            let handle = app.handle().clone();
+            std::thread::spawn(move || autosave_loop(handle));
+            let handle = app.handle().clone();
+            std::thread::spawn(move || watch_loop(handle));
             info!("Audio Visualizer started successfully");
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                finish_recovery(app);
+            }
+        });
 }
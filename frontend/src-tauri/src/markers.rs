@@ -0,0 +1,134 @@
+//! Markers and regions on the timeline, made here or imported from other
+//! annotation tools
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    pub id: u64,
+    pub start_time: f32,
+    pub end_time: Option<f32>,    // Regions only; a marker is a point in time
+    pub label: String,
+    #[serde(default)]
+    pub color: Option<String>,    // "#rrggbb"; the app's default when unset
+    #[serde(default)]
+    pub notes: String,            // Free text
+    #[serde(default)]
+    pub frequency: Option<FrequencyRange>,  // Regions only: a box on the spectrogram
+    pub tier: Option<String>,     // Tier or track an imported marker came from
+}
+
+/// The frequencies a time-frequency region covers, in Hz
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrequencyRange {
+    pub low_hz: f32,
+    pub high_hz: f32,
+}
+
+/// Changes to a marker; fields left out stay as they are. `end_time`,
+/// `color` and `frequency` given as null clear them (a region becomes a
+/// point marker, a time-frequency region one of the whole band).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MarkerUpdate {
+    pub start_time: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub end_time: Option<Option<f32>>,
+    pub label: Option<String>,
+    #[serde(deserialize_with = "present")]
+    pub color: Option<Option<String>>,
+    pub notes: Option<String>,
+    #[serde(deserialize_with = "present")]
+    pub frequency: Option<Option<FrequencyRange>>,
+}
+
+/// A field that was given, null or not, as opposed to left out
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// The components of a "#rrggbb" colour
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let digits = color.strip_prefix('#').filter(|d| d.len() == 6 && d.is_ascii())?;
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// The markers of the loaded recording, in the order they were made
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MarkerSet {
+    next_id: u64,
+    pub markers: Vec<Marker>,
+}
+
+impl MarkerSet {
+    /// Add a marker (or a region, given `end_time` past `start_time`) and
+    /// return it
+    pub fn add(&mut self, start_time: f32, end_time: Option<f32>, label: String, tier: Option<String>) -> &mut Marker {
+        self.next_id += 1;
+        let end_time = end_time.filter(|&end| end > start_time);
+        self.markers.push(Marker { id: self.next_id, start_time, end_time, label, color: None, notes: String::new(), frequency: None, tier });
+        self.markers.last_mut().unwrap()
+    }
+
+    /// Apply `update` to marker `id`, keeping it within `duration` seconds
+    /// and `nyquist` Hz
+    pub fn update(&mut self, id: u64, update: MarkerUpdate, duration: f32, nyquist: f32) -> Result<&Marker, String> {
+        let marker = self.markers.iter_mut().find(|m| m.id == id).ok_or_else(|| format!("No marker {}", id))?;
+        let start_time = update.start_time.unwrap_or(marker.start_time);
+        let end_time = update.end_time.unwrap_or(marker.end_time);
+        let color = update.color.unwrap_or_else(|| marker.color.clone());
+        // A region made a point marker loses its frequencies with its end
+        let frequency = update.frequency.unwrap_or(marker.frequency).filter(|_| end_time.is_some() || update.frequency.is_some());
+        validate(start_time, end_time, frequency, color.as_deref(), (duration, nyquist))?;
+
+        marker.start_time = start_time;
+        marker.end_time = end_time;
+        marker.color = color;
+        marker.frequency = frequency;
+        if let Some(label) = update.label {
+            marker.label = label;
+        }
+        if let Some(notes) = update.notes {
+            marker.notes = notes;
+        }
+        Ok(marker)
+    }
+
+    pub fn delete(&mut self, id: u64) -> Result<Marker, String> {
+        let index = self.markers.iter().position(|m| m.id == id).ok_or_else(|| format!("No marker {}", id))?;
+        Ok(self.markers.remove(index))
+    }
+}
+
+/// Check a marker's times lie within a recording of `duration` seconds,
+/// a region's end after its start, any frequencies (a region's only) within
+/// 0 to `nyquist` Hz, and the colour is "#rrggbb"
+pub fn validate(
+    start_time: f32,
+    end_time: Option<f32>,
+    frequency: Option<FrequencyRange>,
+    color: Option<&str>,
+    (duration, nyquist): (f32, f32),
+) -> Result<(), String> {
+    if !(0.0..=duration).contains(&start_time) {
+        return Err(format!("Marker time {:.3} s is outside the recording (0 to {:.3} s)", start_time, duration));
+    }
+    if let Some(end) = end_time {
+        if !(end > start_time && end <= duration) {
+            return Err(format!("Region end {:.3} s must lie after its start and within the recording", end));
+        }
+    }
+    if let Some(range) = frequency {
+        if end_time.is_none() {
+            return Err("A frequency range needs a region, with an end time".to_string());
+        }
+        if !((0.0..nyquist).contains(&range.low_hz) && range.high_hz > range.low_hz && range.high_hz <= nyquist) {
+            return Err(format!("Frequency range {:.1} to {:.1} Hz must rise and lie within 0 to {:.1} Hz", range.low_hz, range.high_hz, nyquist));
+        }
+    }
+    if let Some(color) = color.filter(|c| parse_color(c).is_none()) {
+        return Err(format!("Marker colour must be given as #rrggbb, not {}", color));
+    }
+    Ok(())
+}
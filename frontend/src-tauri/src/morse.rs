@@ -0,0 +1,338 @@
+//! Morse (CW) detection and decoding
+
+use crate::spectrum::{self, WindowType};
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+/// Band CW is tuned into for listening (Hz), capped below Nyquist
+const TONE_BAND: (f32, f32) = (200.0, 3000.0);
+/// Tones are looked for block by block (seconds) in a spectrum of this
+/// resolution (Hz); a tone stands this far over the spectrum around it (dB)
+/// within this distance (Hz)
+const SEARCH_BLOCK_SECS: f32 = 10.0;
+const RESOLUTION_HZ: f32 = 8.0;
+const MIN_PROMINENCE_DB: f32 = 12.0;
+const SURROUND_HZ: f32 = 150.0;
+/// Tones closer than this (Hz) are one tone, and at most this many are decoded
+const MIN_TONE_SPACING_HZ: f32 = 40.0;
+const MAX_TONES: usize = 4;
+/// Envelope: averaging window and hop (seconds). 8 ms passes 60 WPM keying
+/// and is 125 Hz wide, narrow enough to keep neighbouring signals out
+const ENVELOPE_SECS: f32 = 0.008;
+const HOP_SECS: f32 = 0.002;
+/// Marks stand this far over the floor (dB); the key goes up again this far
+/// under the threshold (dB) and blips shorter than this (seconds) are noise
+const MIN_CONTRAST_DB: f32 = 12.0;
+const HYSTERESIS_DB: f32 = 3.0;
+const MIN_ELEMENT_SECS: f32 = 0.012;
+/// A mark is a lone tone when it stands this far (dB) over the level this
+/// far either side of it (Hz), where speech and music have other partials
+const MIN_PURITY_DB: f32 = 10.0;
+const SIDE_OFFSET_HZ: f32 = 250.0;
+/// Silence that ends a transmission (seconds), and the elements it needs
+const TRANSMISSION_GAP_SECS: f32 = 2.0;
+const MIN_ELEMENTS: usize = 6;
+/// Dit lengths decoded (seconds): 60 down to 5 WPM
+const DIT_RANGE_SECS: (f32, f32) = (0.02, 0.24);
+/// Share of marks that must fit a dit or a dah for keying, not speech or
+/// music that happens to sit on the tone
+const MIN_KEYING_FIT: f32 = 0.8;
+
+/// International Morse code (ITU-R M.1677), with the common punctuation
+const CODE: [(&str, char); 54] = [
+    (".-", 'A'), ("-...", 'B'), ("-.-.", 'C'), ("-..", 'D'), (".", 'E'), ("..-.", 'F'),
+    ("--.", 'G'), ("....", 'H'), ("..", 'I'), (".---", 'J'), ("-.-", 'K'), (".-..", 'L'),
+    ("--", 'M'), ("-.", 'N'), ("---", 'O'), (".--.", 'P'), ("--.-", 'Q'), (".-.", 'R'),
+    ("...", 'S'), ("-", 'T'), ("..-", 'U'), ("...-", 'V'), (".--", 'W'), ("-..-", 'X'),
+    ("-.--", 'Y'), ("--..", 'Z'),
+    ("-----", '0'), (".----", '1'), ("..---", '2'), ("...--", '3'), ("....-", '4'),
+    (".....", '5'), ("-....", '6'), ("--...", '7'), ("---..", '8'), ("----.", '9'),
+    (".-.-.-", '.'), ("--..--", ','), ("..--..", '?'), (".----.", '\''), ("-.-.--", '!'),
+    ("-..-.", '/'), ("-.--.", '('), ("-.--.-", ')'), (".-...", '&'), ("---...", ':'),
+    ("-.-.-.", ';'), ("-...-", '='), (".-.-.", '+'), ("-....-", '-'), ("..--.-", '_'),
+    (".-..-.", '"'), ("...-..-", '$'), (".--.-.", '@'),
+];
+/// Stands in for a code that is not in the table
+const UNKNOWN: char = '*';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CwCharacter {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub code: String,       // Dots and dashes as keyed
+    pub character: char,    // '*' when the code is not in the table
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CwTransmission {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub frequency_hz: f32,
+    pub wpm: f32,               // PARIS words per minute, from the dit length
+    pub level_dbfs: f32,        // Median over the marks
+    pub contrast_db: f32,       // Marks over the floor between them
+    pub keying_fit: f32,        // Share of marks that fit a dit or a dah
+    pub text: String,
+    pub characters: Vec<CwCharacter>,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+/// Candidate CW tones: narrow peaks that stand out of the spectrum of some
+/// block, strongest first
+fn find_tones(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    let sr = sample_rate as f32;
+    let segment = ((sr / RESOLUTION_HZ) as usize).next_power_of_two();
+    let block = ((SEARCH_BLOCK_SECS * sr) as usize).max(2 * segment);
+    let bin_hz = sr / segment as f32;
+    let (lo, hi) = ((TONE_BAND.0 / bin_hz) as usize, (TONE_BAND.1.min(0.45 * sr) / bin_hz) as usize);
+    let surround = (SURROUND_HZ / bin_hz) as usize;
+    if samples.len() < 2 * segment || lo <= surround || hi <= lo {
+        return Vec::new();
+    }
+
+    let starts: Vec<usize> = (0..).map(|i| i * block / 2).take_while(|&s| s == 0 || s + block <= samples.len()).collect();
+    let mut peaks: Vec<(f32, f32)> = starts
+        .par_iter()
+        .flat_map_iter(|&start| {
+            let psd = spectrum::welch_psd(
+                &samples[start..(start + block).min(samples.len())],
+                sample_rate,
+                segment,
+                segment / 2,
+                WindowType::Hann,
+            );
+            let db: Vec<f32> = psd.power.iter().map(|p| 10.0 * p.max(1e-20).log10()).collect();
+            (lo..=hi.min(db.len() - surround - 1))
+                .filter(|&b| db[b] > db[b - 1] && db[b] >= db[b + 1])
+                .filter_map(|b| {
+                    // The surround leaves out the tone's own main lobe
+                    let around: Vec<f32> =
+                        (b - surround..=b + surround).filter(|&k| k.abs_diff(b) > 2).map(|k| db[k]).collect();
+                    let prominence = db[b] - percentile(&around, 0.5);
+                    (prominence >= MIN_PROMINENCE_DB).then_some((b as f32 * bin_hz, prominence))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut tones: Vec<f32> = Vec::new();
+    for (frequency, _) in peaks {
+        if tones.len() < MAX_TONES && tones.iter().all(|t| (t - frequency).abs() >= MIN_TONE_SPACING_HZ) {
+            tones.push(frequency);
+        }
+    }
+    tones
+}
+
+/// Level of the tone at `frequency` (dBFS) every hop: the signal mixed down
+/// to 0 Hz and averaged over the envelope window
+fn envelope(samples: &[f32], sample_rate: u32, frequency: f32, hop: usize, window_hops: usize) -> Vec<f32> {
+    let step = Complex::from_polar(1.0, -2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64);
+    let mut phasor = Complex::new(1.0f64, 0.0);
+    let mut sum = Complex::new(0.0f64, 0.0);
+    // Running sum at every hop boundary, so each window is a difference
+    let mut cumulative = vec![sum];
+    for (n, &x) in samples.iter().enumerate() {
+        sum += phasor * x as f64;
+        phasor *= step;
+        if (n + 1) % hop == 0 {
+            phasor /= phasor.norm();
+            cumulative.push(sum);
+        }
+    }
+    let window = (hop * window_hops) as f64;
+    (0..cumulative.len().saturating_sub(window_hops))
+        .map(|k| {
+            // A sine of amplitude a mixes down to a / 2, and carries a² / 2
+            let mean = (cumulative[k + window_hops] - cumulative[k]) / window;
+            (10.0 * (2.0 * mean.norm_sqr()).max(1e-20).log10()) as f32
+        })
+        .collect()
+}
+
+/// Key-down stretches (start and end hop) in an envelope: above the
+/// midpoint between floor and mark level, with hysteresis, blips dropped
+/// and breaks of the same length bridged, and standing clear of the
+/// `sides` level. Also the threshold (dB).
+fn key_marks(levels: &[f32], sides: &[f32], min_hops: usize) -> Option<(Vec<(usize, usize)>, f32)> {
+    let floor = percentile(levels, 0.5);
+    let top = percentile(levels, 0.999);
+    if top - floor < MIN_CONTRAST_DB {
+        return None;
+    }
+    let threshold = (floor + top) / 2.0;
+    let mut marks: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+    for (k, &level) in levels.iter().enumerate() {
+        match start {
+            None if level >= threshold => start = Some(k),
+            Some(s) if level < threshold - HYSTERESIS_DB => {
+                match marks.last_mut() {
+                    Some(last) if s - last.1 < min_hops => last.1 = k,
+                    _ => marks.push((s, k)),
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        marks.push((s, levels.len()));
+    }
+    marks.retain(|&(s, e)| {
+        let purity: Vec<f32> = (s..e).map(|k| levels[k] - sides[k]).collect();
+        e - s >= min_hops && percentile(&purity, 0.5) >= MIN_PURITY_DB
+    });
+    Some((marks, threshold))
+}
+
+/// Dit length (hops) of a transmission: the shorter of two clusters of mark
+/// lengths, or, when every mark is alike, whichever of dit and dah the gaps
+/// inside characters (one dit) say they are
+fn dit_length(marks: &[(usize, usize)]) -> f32 {
+    let lengths: Vec<f32> = marks.iter().map(|(s, e)| ((e - s) as f32).ln()).collect();
+    let (mut short, mut long) = (percentile(&lengths, 0.0), percentile(&lengths, 1.0));
+    for _ in 0..10 {
+        let (shorts, longs): (Vec<f32>, Vec<f32>) = lengths.iter().partition(|&&l| (l - short).abs() <= (l - long).abs());
+        short = shorts.iter().sum::<f32>() / shorts.len().max(1) as f32;
+        long = if longs.is_empty() { short } else { longs.iter().sum::<f32>() / longs.len() as f32 };
+    }
+    if long - short >= 2f32.ln() {
+        return short.exp();
+    }
+    let gaps: Vec<f32> = marks.windows(2).map(|w| (w[1].0 - w[0].1) as f32).collect();
+    let gap = percentile(&gaps, 0.25);
+    if short.exp() >= 2.0 * gap { short.exp() / 3.0 } else { short.exp() }
+}
+
+/// Decode one transmission's marks, if they are keyed like Morse
+fn decode(marks: &[(usize, usize)], levels: &[f32], threshold: f32, frequency: f32, hop_secs: f32) -> Option<CwTransmission> {
+    if marks.len() < MIN_ELEMENTS {
+        return None;
+    }
+    let dit = dit_length(marks);
+    let dit_secs = dit * hop_secs;
+    if !(DIT_RANGE_SECS.0..=DIT_RANGE_SECS.1).contains(&dit_secs) {
+        return None;
+    }
+    let fits = marks
+        .iter()
+        .filter(|(s, e)| {
+            let dits = (e - s) as f32 / dit;
+            (0.5..=1.8).contains(&dits) || (2.0..=4.5).contains(&dits)
+        })
+        .count();
+    let keying_fit = fits as f32 / marks.len() as f32;
+    if keying_fit < MIN_KEYING_FIT {
+        return None;
+    }
+
+    // Elements: a dah from two dits; gaps: within a character under two
+    // dits, between characters under five, between words beyond
+    let time = |hop: usize| hop as f32 * hop_secs;
+    let mut characters: Vec<CwCharacter> = Vec::new();
+    let mut text = String::new();
+    let mut code = String::new();
+    let mut start = marks[0].0;
+    for (i, &(s, e)) in marks.iter().enumerate() {
+        code.push(if (e - s) as f32 >= 2.0 * dit { '-' } else { '.' });
+        let gap = marks.get(i + 1).map(|next| (next.0 - e) as f32 / dit);
+        if gap.is_some_and(|g| g < 2.0) {
+            continue;
+        }
+        let character = CODE.iter().find(|(c, _)| *c == code).map_or(UNKNOWN, |(_, ch)| *ch);
+        text.push(character);
+        characters.push(CwCharacter { start_time: time(start), end_time: time(e), code: std::mem::take(&mut code), character });
+        if gap.is_some_and(|g| g >= 5.0) {
+            text.push(' ');
+        }
+        if let Some(next) = marks.get(i + 1) {
+            start = next.0;
+        }
+    }
+
+    // Speed from a dit and the gap after it, which together last two dits
+    // however the keying is weighted or the envelope stretches the marks
+    let periods: Vec<f32> = marks
+        .windows(2)
+        .filter(|w| ((w[0].1 - w[0].0) as f32) < 2.0 * dit && ((w[1].0 - w[0].1) as f32) < 2.0 * dit)
+        .map(|w| (w[1].0 - w[0].0) as f32 / 2.0)
+        .collect();
+    let speed_dit = if periods.is_empty() { dit } else { percentile(&periods, 0.5) };
+
+    let inside = |k: usize| marks.iter().any(|&(s, e)| (s..e).contains(&k));
+    let span = marks[0].0..marks[marks.len() - 1].1;
+    let mark_levels: Vec<f32> = span.clone().filter(|&k| inside(k)).map(|k| levels[k]).collect();
+    let space_levels: Vec<f32> = span.filter(|&k| !inside(k)).map(|k| levels[k]).collect();
+    let level_dbfs = percentile(&mark_levels, 0.5);
+    let floor = if space_levels.is_empty() { threshold } else { percentile(&space_levels, 0.5) };
+    Some(CwTransmission {
+        start_time: time(marks[0].0),
+        end_time: time(marks[marks.len() - 1].1),
+        frequency_hz: frequency,
+        wpm: 1.2 / (speed_dit * hop_secs),
+        level_dbfs,
+        contrast_db: level_dbfs - floor,
+        keying_fit,
+        text,
+        characters,
+    })
+}
+
+/// Find on-off keyed tones and decode them as Morse. Candidate tones are
+/// narrow peaks in the spectrum of some stretch of the recording; on each,
+/// the tone's level is followed in 2 ms steps and cut into key-down marks
+/// at the midpoint between its floor and its peak. Marks are gathered into
+/// transmissions at long silences, and each transmission is decoded at its
+/// own speed: its dit length comes from the marks themselves, and elements,
+/// character and word gaps are told apart in dit units (1, 3 and 7 for
+/// ideal keying). Transmissions whose marks do not fall into dits and dahs
+/// are left out, which keeps steady tones, music and speech out.
+pub fn detect(samples: &[f32], sample_rate: u32) -> Vec<CwTransmission> {
+    let sr = sample_rate as f32;
+    let hop = ((HOP_SECS * sr) as usize).max(1);
+    let hop_secs = hop as f32 / sr;
+    let window_hops = ((ENVELOPE_SECS / hop_secs).round() as usize).max(1);
+    let min_hops = ((MIN_ELEMENT_SECS / hop_secs).round() as usize).max(1);
+    let gap_hops = (TRANSMISSION_GAP_SECS / hop_secs) as usize;
+
+    let mut transmissions: Vec<CwTransmission> = find_tones(samples, sample_rate)
+        .par_iter()
+        .flat_map_iter(|&frequency| {
+            let levels = envelope(samples, sample_rate, frequency, hop, window_hops);
+            // The quieter side, so another signal on one side does not count
+            let above = envelope(samples, sample_rate, frequency + SIDE_OFFSET_HZ, hop, window_hops);
+            let sides: Vec<f32> = if frequency > SIDE_OFFSET_HZ + TONE_BAND.0 / 2.0 {
+                let below = envelope(samples, sample_rate, frequency - SIDE_OFFSET_HZ, hop, window_hops);
+                above.iter().zip(&below).map(|(a, b)| a.min(*b)).collect()
+            } else {
+                above
+            };
+            let Some((marks, threshold)) = key_marks(&levels, &sides, min_hops) else {
+                return Vec::new();
+            };
+            // The envelope is centered half a window after each hop
+            let shift = window_hops / 2;
+            let marks: Vec<(usize, usize)> = marks.iter().map(|&(s, e)| (s + shift, e + shift)).collect();
+            let levels: Vec<f32> = std::iter::repeat_n(levels[0], shift).chain(levels.iter().copied()).collect();
+            let mut found = Vec::new();
+            let mut first = 0;
+            for i in 1..=marks.len() {
+                if i == marks.len() || marks[i].0 - marks[i - 1].1 >= gap_hops {
+                    found.extend(decode(&marks[first..i], &levels, threshold, frequency, hop_secs));
+                    first = i;
+                }
+            }
+            found
+        })
+        .collect();
+    transmissions.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+    transmissions
+}
@@ -0,0 +1,402 @@
+//! Background noise (room tone) profiling, consistency segmentation and noise prints
+
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+const N_FFT: usize = 2048;
+const HOP: usize = 1024;
+const N_BANDS: usize = 24;
+/// A frame is a pause when within this many dB of the local quiet level
+const PAUSE_MARGIN_DB: f32 = 6.0;
+/// Span over which the local quiet level (10th percentile) is taken (seconds)
+const QUIET_SPAN: f32 = 10.0;
+/// Least pause material for a block to get a noise profile (seconds)
+const MIN_PAUSE: f32 = 0.5;
+/// Frequency range a noise print covers (Hz; the top is also held below
+/// 0.45 × the sample rate)
+const PRINT_RANGE: (f32, f32) = (50.0, 16_000.0);
+/// Width of the trend taken out of a print to leave its fine structure
+/// (octaves)
+const DETAIL_OCTAVES: f32 = 1.0 / 3.0;
+/// Fine-structure correlation and broad-shape distance (dB) a region needs
+/// to match a print
+const MIN_MATCH_SIMILARITY: f32 = 0.5;
+const MAX_MATCH_DISTANCE_DB: f32 = 4.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AmbienceSegment {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub floor_dbfs: f32,            // Mean pause level
+    pub band_levels_db: Vec<f32>,   // Pause spectrum per band (dBFS)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmbienceChange {
+    pub time: f32,
+    pub distance_db: f32,  // RMS band-level difference between the two profiles
+}
+
+#[derive(Serialize)]
+pub struct AmbienceReport {
+    pub band_freqs: Vec<f32>,       // Geometric band centers
+    pub segments: Vec<AmbienceSegment>,
+    pub changes: Vec<AmbienceChange>,
+}
+
+/// Long-term spectrum of a stretch of background noise, to find again in
+/// other regions or recordings
+#[derive(Debug, Clone, Serialize)]
+pub struct NoisePrint {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub sample_rate: u32,
+    pub freqs: Vec<f32>,
+    pub spectrum_db: Vec<f32>,      // Mean power per bin (dBFS)
+    pub floor_dbfs: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NoiseMatch {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub similarity: f32,            // Correlation of the fine spectral structure (-1..1)
+    pub distance_db: f32,           // RMS difference of the spectra once levels are matched
+    pub level_difference_db: f32,   // Region floor less the print's
+    pub matches: bool,
+}
+
+/// Log-spaced band edges (Hz) from 50 Hz to 16 kHz or just below Nyquist
+fn band_edges(sample_rate: u32) -> Vec<f32> {
+    let (lo, hi) = (50.0f32, 16_000.0f32.min(0.45 * sample_rate as f32));
+    (0..=N_BANDS).map(|i| lo * (hi / lo).powf(i as f32 / N_BANDS as f32)).collect()
+}
+
+/// Band levels (dBFS) of every frame
+fn band_levels(samples: &[f32], sample_rate: u32) -> Vec<[f32; N_BANDS]> {
+    let window = WindowType::Hann.coefficients(N_FFT);
+    let win_power: f32 = window.iter().map(|w| w * w).sum();
+    let bin_hz = sample_rate as f32 / N_FFT as f32;
+    let edges: Vec<usize> = band_edges(sample_rate).iter().map(|f| ((f / bin_hz) as usize).max(1)).collect();
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * HOP)
+        .take_while(|&start| start + N_FFT <= samples.len())
+        .collect();
+
+    starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(N_FFT);
+            let mut input: Vec<f32> = samples[start..start + N_FFT].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+
+            let mut levels = [0.0f32; N_BANDS];
+            for (b, level) in levels.iter_mut().enumerate() {
+                let hi = edges[b + 1].max(edges[b] + 1).min(spectrum.len());
+                let power: f32 = spectrum[edges[b]..hi].iter().map(|c| c.norm_sqr()).sum();
+                // Mean square of the band's share of the signal
+                *level = 10.0 * (2.0 * power / (N_FFT as f32 * win_power)).max(1e-20).log10();
+            }
+            levels
+        })
+        .collect()
+}
+
+fn total_db(levels: &[f32; N_BANDS]) -> f32 {
+    10.0 * levels.iter().map(|l| 10f32.powf(l / 10.0)).sum::<f32>().max(1e-20).log10()
+}
+
+/// RMS difference between two band profiles (dB)
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    (a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f32>() / a.len() as f32).sqrt()
+}
+
+fn mean_profile<'a>(frames: impl Iterator<Item = &'a [f32; N_BANDS]>) -> Option<Vec<f32>> {
+    let mut sum = [0.0f32; N_BANDS];
+    let mut count = 0;
+    for f in frames {
+        sum.iter_mut().zip(f).for_each(|(s, &l)| *s += l);
+        count += 1;
+    }
+    (count > 0).then(|| sum.iter().map(|s| s / count as f32).collect())
+}
+
+/// Frames near the local quiet level (10th percentile over `QUIET_SPAN`)
+fn pause_frames(totals: &[f32], frame_secs: f32) -> Vec<bool> {
+    let quiet_half = ((QUIET_SPAN / 2.0 / frame_secs) as usize).max(1);
+    let quiet_levels: Vec<f32> = (0..totals.len().div_ceil(quiet_half))
+        .into_par_iter()
+        .map(|b| {
+            let center = b * quiet_half + quiet_half / 2;
+            let mut v = totals[center.saturating_sub(quiet_half)..(center + quiet_half).min(totals.len())].to_vec();
+            v.sort_by(f32::total_cmp);
+            v[v.len() / 10]
+        })
+        .collect();
+    totals
+        .iter()
+        .enumerate()
+        .map(|(i, &t)| t > -120.0 && t < quiet_levels[i / quiet_half] + PAUSE_MARGIN_DB)
+        .collect()
+}
+
+/// Split the recording into stretches of consistent background noise.
+///
+/// Pauses are the frames near the local quiet level; their spectrum is
+/// averaged per block of `block_secs`. A block whose profile differs from
+/// the running segment by more than `threshold_db`, with the next profiled
+/// block agreeing with it rather than the old segment, starts a new
+/// segment; the boundary is then placed between the pause frames that
+/// match the old and the new profile.
+pub fn segment_background(samples: &[f32], sample_rate: u32, block_secs: f32, threshold_db: f32) -> AmbienceReport {
+    let sr = sample_rate as f32;
+    let frame_secs = HOP as f32 / sr;
+    let frames = band_levels(samples, sample_rate);
+    let totals: Vec<f32> = frames.iter().map(total_db).collect();
+    let edges = band_edges(sample_rate);
+    let band_freqs = edges.windows(2).map(|w| (w[0] * w[1]).sqrt()).collect();
+
+    // Pause frames: within the margin of the 10th percentile of their surroundings
+    let pause = pause_frames(&totals, frame_secs);
+
+    // Noise profile per block
+    let block = ((block_secs / frame_secs) as usize).max(1);
+    let min_pause = (MIN_PAUSE / frame_secs) as usize;
+    let profiles: Vec<Option<Vec<f32>>> = (0..frames.len().div_ceil(block))
+        .map(|b| {
+            let range = b * block..((b + 1) * block).min(frames.len());
+            let count = pause[range.clone()].iter().filter(|&&p| p).count();
+            if count < min_pause.max(1) {
+                return None;
+            }
+            mean_profile(range.filter(|&i| pause[i]).map(|i| &frames[i]))
+        })
+        .collect();
+    let profiled: Vec<usize> = (0..profiles.len()).filter(|&b| profiles[b].is_some()).collect();
+
+    // Segment boundaries at block resolution, as indices into `profiled`:
+    // (first differing block, first block of the new segment's profile)
+    let profile = |j: usize| profiles[profiled[j]].as_ref().unwrap();
+    let mut boundaries: Vec<(usize, usize)> = Vec::new();
+    let mut segment_start = 0;
+    for j in 1..profiled.len() {
+        if segment_start >= j {
+            continue;
+        }
+        let current = mean_profile_blocks(&profiles, &profiled[segment_start..j]);
+        if distance(&current, profile(j)) <= threshold_db {
+            continue;
+        }
+        // Confirm with the following block so one odd block does not split
+        let next = (j + 1 < profiled.len()).then(|| profile(j + 1));
+        if next.is_some_and(|next| distance(next, profile(j)) >= distance(next, &current)) {
+            continue;
+        }
+        // A block straddling the change is a blend; the new profile starts after it
+        let blended = next.is_some_and(|next| distance(next, profile(j)) > threshold_db);
+        segment_start = if blended { j + 1 } else { j };
+        boundaries.push((j, segment_start));
+    }
+
+    // Refine each boundary to a frame between the last block of the old
+    // segment and the first clean block of the new one
+    let mut changes = Vec::new();
+    let mut cut_frames = vec![0];
+    let mut segment_first = 0;
+    for &(j, new_j) in &boundaries {
+        let old = mean_profile_blocks(&profiles, &profiled[segment_first..j]);
+        let new = profile(new_j);
+        let lo = profiled[j - 1] * block;
+        let hi = ((profiled[new_j] + 1) * block).min(frames.len());
+        // Split where the most pause frames sit on the side whose profile they match
+        let matches_new: Vec<(usize, bool)> = (lo..hi)
+            .filter(|&i| pause[i])
+            .map(|i| (i, distance(&frames[i], new) < distance(&frames[i], &old)))
+            .collect();
+        let total_new = matches_new.iter().filter(|m| m.1).count();
+        let (mut old_before, mut new_before) = (0, 0);
+        let mut best = (profiled[j] * block, 0);
+        for &(i, is_new) in &matches_new {
+            let score = old_before + total_new - new_before;
+            if score > best.1 {
+                best = (i, score);
+            }
+            if is_new { new_before += 1 } else { old_before += 1 }
+        }
+        changes.push(AmbienceChange {
+            time: (best.0 * HOP) as f32 / sr,
+            distance_db: distance(&old, new),
+        });
+        cut_frames.push(best.0);
+        segment_first = new_j;
+    }
+    cut_frames.push(frames.len());
+
+    let segments = cut_frames
+        .windows(2)
+        .filter_map(|w| {
+            let band_levels_db = mean_profile((w[0]..w[1]).filter(|&i| pause[i]).map(|i| &frames[i]))?;
+            let floor = (w[0]..w[1]).filter(|&i| pause[i]).map(|i| totals[i]).sum::<f32>()
+                / (w[0]..w[1]).filter(|&i| pause[i]).count() as f32;
+            Some(AmbienceSegment {
+                start_time: (w[0] * HOP) as f32 / sr,
+                end_time: ((w[1] * HOP) as f32 / sr).min(samples.len() as f32 / sr),
+                floor_dbfs: floor,
+                band_levels_db,
+            })
+        })
+        .collect();
+
+    AmbienceReport { band_freqs, segments, changes }
+}
+
+/// Mean of the profiles of `blocks`
+fn mean_profile_blocks(profiles: &[Option<Vec<f32>>], blocks: &[usize]) -> Vec<f32> {
+    let mut sum = [0.0f32; N_BANDS];
+    for &b in blocks {
+        sum.iter_mut().zip(profiles[b].as_ref().unwrap()).for_each(|(s, &l)| *s += l);
+    }
+    sum.iter().map(|s| s / blocks.len().max(1) as f32).collect()
+}
+
+/// Power per bin (mean square of the bin's share of the signal) of every frame
+fn power_frames(samples: &[f32]) -> Vec<Vec<f32>> {
+    let window = WindowType::Hann.coefficients(N_FFT);
+    let scale = 2.0 / (N_FFT as f32 * window.iter().map(|w| w * w).sum::<f32>());
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * HOP)
+        .take_while(|&start| start + N_FFT <= samples.len())
+        .collect();
+
+    starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(N_FFT);
+            let mut input: Vec<f32> = samples[start..start + N_FFT].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            spectrum.iter().map(|c| c.norm_sqr() * scale).collect()
+        })
+        .collect()
+}
+
+/// Bins a print covers
+fn print_bins(sample_rate: u32) -> std::ops::Range<usize> {
+    let bin_hz = sample_rate as f32 / N_FFT as f32;
+    let hi = PRINT_RANGE.1.min(0.45 * sample_rate as f32);
+    (PRINT_RANGE.0 / bin_hz).ceil() as usize..(hi / bin_hz) as usize + 1
+}
+
+/// Mean power spectrum (dB) of the given frames over `bins`
+fn mean_spectrum_db<'a>(frames: impl Iterator<Item = &'a Vec<f32>>, bins: &std::ops::Range<usize>) -> Option<Vec<f32>> {
+    let mut sum = vec![0.0f32; bins.len()];
+    let mut count = 0;
+    for f in frames {
+        sum.iter_mut().zip(&f[bins.clone()]).for_each(|(s, &p)| *s += p);
+        count += 1;
+    }
+    (count > 0).then(|| sum.iter().map(|s| 10.0 * (s / count as f32).max(1e-20).log10()).collect())
+}
+
+/// `db` less its moving average over `DETAIL_OCTAVES` around each bin,
+/// leaving lines, resonances and other fine structure
+fn fine_structure(db: &[f32], first_bin: usize) -> Vec<f32> {
+    let half = 2f32.powf(DETAIL_OCTAVES / 2.0);
+    let mut prefix = vec![0.0f32; db.len() + 1];
+    for (i, &d) in db.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + d;
+    }
+    (0..db.len())
+        .map(|i| {
+            let k = (first_bin + i) as f32;
+            let lo = ((k / half) as usize).saturating_sub(first_bin).min(i.saturating_sub(2));
+            let hi = (((k * half) as usize + 1).saturating_sub(first_bin)).max(i + 3).min(db.len());
+            db[i] - (prefix[hi] - prefix[lo]) / (hi - lo) as f32
+        })
+        .collect()
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let (ma, mb) = (a.iter().sum::<f32>() / n, b.iter().sum::<f32>() / n);
+    let (mut ab, mut aa, mut bb) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        ab += (x - ma) * (y - mb);
+        aa += (x - ma).powi(2);
+        bb += (y - mb).powi(2);
+    }
+    if aa > 0.0 && bb > 0.0 { ab / (aa * bb).sqrt() } else { 0.0 }
+}
+
+/// Capture the noise print of `selection`, which should hold background
+/// noise only; `start_time` is where the selection begins
+pub fn capture_print(selection: &[f32], sample_rate: u32, start_time: f32) -> Result<NoisePrint, String> {
+    if selection.len() < 2 * N_FFT {
+        return Err(format!("Select at least {:.2} s of background noise", (2 * N_FFT) as f32 / sample_rate as f32));
+    }
+    let bins = print_bins(sample_rate);
+    let frames = power_frames(selection);
+    let spectrum_db = mean_spectrum_db(frames.iter(), &bins).ok_or("Selection too short")?;
+    let floor = frames.iter().map(|f| f[1..].iter().sum::<f32>()).sum::<f32>() / frames.len() as f32;
+    let bin_hz = sample_rate as f32 / N_FFT as f32;
+    Ok(NoisePrint {
+        start_time,
+        end_time: start_time + selection.len() as f32 / sample_rate as f32,
+        sample_rate,
+        freqs: bins.clone().map(|k| k as f32 * bin_hz).collect(),
+        spectrum_db,
+        floor_dbfs: 10.0 * floor.max(1e-20).log10(),
+    })
+}
+
+/// Compare `print` with the background noise of every block of
+/// `block_secs`. Each block's pauses (frames near the local quiet level)
+/// are averaged and compared in two ways: the correlation of the fine
+/// structure left once the spectral trend is taken out (hum and its
+/// harmonics, fan and equipment lines, resonances), which links noise to
+/// a common source, and the RMS difference of the broad shape once the
+/// levels are matched. Blocks without enough pause material are skipped.
+pub fn match_print(print: &NoisePrint, samples: &[f32], sample_rate: u32, block_secs: f32) -> Result<Vec<NoiseMatch>, String> {
+    if sample_rate != print.sample_rate {
+        return Err(format!("Sample rates differ ({} Hz print vs {} Hz audio)", print.sample_rate, sample_rate));
+    }
+    let sr = sample_rate as f32;
+    let frame_secs = HOP as f32 / sr;
+    let bins = print_bins(sample_rate);
+    let frames = power_frames(samples);
+    let totals: Vec<f32> = frames.iter().map(|f| 10.0 * f[1..].iter().sum::<f32>().max(1e-20).log10()).collect();
+    let pause = pause_frames(&totals, frame_secs);
+    let print_detail = fine_structure(&print.spectrum_db, bins.start);
+
+    let block = ((block_secs / frame_secs) as usize).max(1);
+    let min_pause = ((MIN_PAUSE / frame_secs) as usize).max(1);
+    let matches = (0..frames.len().div_ceil(block))
+        .filter_map(|b| {
+            let range = b * block..((b + 1) * block).min(frames.len());
+            if range.clone().filter(|&i| pause[i]).count() < min_pause {
+                return None;
+            }
+            let db = mean_spectrum_db(range.clone().filter(|&i| pause[i]).map(|i| &frames[i]), &bins)?;
+            let difference: Vec<f32> = db.iter().zip(&print.spectrum_db).map(|(a, b)| a - b).collect();
+            let level = difference.iter().sum::<f32>() / difference.len() as f32;
+            let distance_db = (difference.iter().map(|d| (d - level).powi(2)).sum::<f32>() / difference.len() as f32).sqrt();
+            let similarity = correlation(&fine_structure(&db, bins.start), &print_detail);
+            let floor = range.clone().filter(|&i| pause[i]).map(|i| 10f32.powf(totals[i] / 10.0)).sum::<f32>()
+                / range.clone().filter(|&i| pause[i]).count() as f32;
+            Some(NoiseMatch {
+                start_time: (range.start * HOP) as f32 / sr,
+                end_time: ((range.end * HOP + N_FFT - HOP) as f32 / sr).min(samples.len() as f32 / sr),
+                similarity,
+                distance_db,
+                level_difference_db: 10.0 * floor.log10() - print.floor_dbfs,
+                matches: similarity >= MIN_MATCH_SIMILARITY && distance_db <= MAX_MATCH_DISTANCE_DB,
+            })
+        })
+        .collect();
+    Ok(matches)
+}
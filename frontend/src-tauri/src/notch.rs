@@ -0,0 +1,189 @@
+//! Notch-filter evidence: deep, narrow, persistent holes in the long-term spectrum
+
+use crate::enf::GRID_FREQS;
+use crate::spectrum::{self, WindowType};
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Frequency resolution of the long-term spectrum (Hz)
+const RESOLUTION_HZ: f32 = 1.0;
+/// The long-term spectrum is smoothed over this fraction of the frequency
+/// either side (at least one bin), so the narrow dips of a room's
+/// frequency response fill in while a filter's notch, with its wide
+/// skirts, stays
+const SMOOTH_FRACTION: f32 = 0.005;
+/// Notches searched from this frequency (Hz) up to 0.45 × the sample rate
+const LOWEST_HZ: f32 = 40.0;
+/// The baseline is the median level within this many octaves either side,
+/// and at least this many bins
+const BASELINE_OCTAVES: f32 = 1.0 / 3.0;
+const MIN_BASELINE_BINS: usize = 8;
+/// Least depth below the baseline (dB)
+const MIN_DEPTH_DB: f32 = 15.0;
+/// Widest notch at half its depth, relative to its frequency
+const MAX_RELATIVE_WIDTH: f32 = 0.08;
+/// Depth at which a frame shows the notch (dB), and the fraction of active
+/// frames that must show it
+const FRAME_DEPTH_DB: f32 = 6.0;
+const MIN_PERSISTENCE: f32 = 0.8;
+/// Frames within this much of the loudest are active (dB)
+const ACTIVE_RANGE_DB: f32 = 40.0;
+/// Below this, a room's modes leave persistent dips of their own, so
+/// notches there are reported but not flagged (Hz)
+const MODAL_LIMIT_HZ: f32 = 500.0;
+/// Notches this close to a mains harmonic (Hz, or one bin if wider), up to
+/// this harmonic, are put down to hum removal when at least this many sit
+/// on harmonics of the same grid frequency
+const HUM_TOLERANCE_HZ: f32 = 2.0;
+const MAX_HUM_HARMONIC: f32 = 50.0;
+const MIN_HUM_NOTCHES: usize = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notch {
+    pub frequency_hz: f32,      // Deepest bin
+    pub depth_db: f32,          // Below the median of the surrounding third octaves
+    pub width_hz: f32,          // At half the depth
+    pub persistence: f32,       // Fraction of active frames that show it
+    pub mains_hz: Option<f32>,  // Grid frequency it is a harmonic of (hum removal)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotchReport {
+    pub resolution_hz: f32,
+    pub notches: Vec<Notch>,    // Persistent notches, lowest first
+    pub suspicious: bool,       // At least one above the room modes that hum removal does not explain
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    let (_, &mut m, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    m
+}
+
+/// Look for notches a filter has cut into the recording: dips in the
+/// long-term spectrum far below the surrounding level, narrow relative to
+/// their frequency, and present in most frames, which natural sources, whose
+/// spectra move, do not leave. Removing a censoring beep, a tone or feedback
+/// leaves such a notch; so does hum removal, which is legitimate and
+/// reported as such when notches sit on the harmonics of a mains frequency.
+pub fn detect_notches(samples: &[f32], sample_rate: u32) -> NotchReport {
+    let sr = sample_rate as f32;
+    let segment = ((sr / RESOLUTION_HZ) as usize).next_power_of_two();
+    let bin_hz = sr / segment as f32;
+    let mut report = NotchReport { resolution_hz: bin_hz, notches: Vec::new(), suspicious: false };
+    if samples.len() < 2 * segment {
+        return report;
+    }
+
+    let psd = spectrum::welch_psd(samples, sample_rate, segment, segment / 2, WindowType::Hann);
+    let level: Vec<f32> = (0..psd.power.len())
+        .map(|b| {
+            let half = ((b as f32 * SMOOTH_FRACTION).round() as usize).max(1);
+            let near = &psd.power[b.saturating_sub(half)..(b + half + 1).min(psd.power.len())];
+            10.0 * (near.iter().sum::<f32>() / near.len() as f32).max(1e-20).log10()
+        })
+        .collect();
+    let (lo, hi) = ((LOWEST_HZ / bin_hz) as usize, ((0.45 * sr / bin_hz) as usize).min(level.len() - 1));
+    let ratio = 2f32.powf(BASELINE_OCTAVES);
+    let depth: Vec<f32> = (0..level.len())
+        .into_par_iter()
+        .map(|b| {
+            if b < lo || b > hi {
+                return 0.0;
+            }
+            let below = b.saturating_sub(((b as f32 - b as f32 / ratio) as usize).max(MIN_BASELINE_BINS));
+            let above = (b + ((b as f32 * ratio - b as f32) as usize).max(MIN_BASELINE_BINS)).min(level.len() - 1);
+            median(&mut level[below..=above].to_vec()) - level[b]
+        })
+        .collect();
+
+    // Candidate notches: the deepest bin of each run past the threshold,
+    // extended to where the depth halves
+    let mut candidates = Vec::new();
+    let mut b = lo;
+    while b <= hi {
+        if depth[b] < MIN_DEPTH_DB {
+            b += 1;
+            continue;
+        }
+        let run_end = (b..=hi).find(|&i| depth[i] < MIN_DEPTH_DB).unwrap_or(hi + 1);
+        let center = (b..run_end).fold(b, |best, i| if depth[i] > depth[best] { i } else { best });
+        let half = depth[center] / 2.0;
+        let left = (lo..=center).rev().find(|&i| depth[i] < half).map_or(lo, |i| i + 1);
+        let right = (center..=hi).find(|&i| depth[i] < half).map_or(hi, |i| i - 1);
+        let frequency = center as f32 * bin_hz;
+        if ((right - left + 1) as f32 * bin_hz) <= MAX_RELATIVE_WIDTH * frequency {
+            candidates.push((left, center, right));
+        }
+        b = run_end.max(right + 1);
+    }
+    if candidates.is_empty() {
+        return report;
+    }
+
+    // Persistence: per frame, the notch against the bins one to two notch
+    // widths either side
+    let window = WindowType::Hann.coefficients(segment);
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * segment)
+        .take_while(|&start| start + segment <= samples.len())
+        .collect();
+    let frames: Vec<(f32, Vec<bool>)> = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(segment);
+            let mut input: Vec<f32> = samples[start..start + segment].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+            let mean = |a: usize, b: usize| power[a..b].iter().sum::<f32>() / (b - a).max(1) as f32;
+            let shows = candidates
+                .iter()
+                .map(|&(left, _, right)| {
+                    let width = right - left + 1;
+                    let notch = mean(left, right + 1);
+                    let sides = (mean(left.saturating_sub(2 * width), left.saturating_sub(width))
+                        + mean((right + 1 + width).min(power.len()), (right + 1 + 2 * width).min(power.len())))
+                        / 2.0;
+                    10.0 * (sides / notch.max(1e-20)).log10() >= FRAME_DEPTH_DB
+                })
+                .collect();
+            (10.0 * power.iter().sum::<f32>().max(1e-20).log10(), shows)
+        })
+        .collect();
+    let loudest = frames.iter().map(|f| f.0).fold(f32::NEG_INFINITY, f32::max);
+    let active: Vec<&Vec<bool>> = frames.iter().filter(|f| f.0 >= loudest - ACTIVE_RANGE_DB).map(|f| &f.1).collect();
+
+    for (n, &(left, center, right)) in candidates.iter().enumerate() {
+        let persistence = active.iter().filter(|shows| shows[n]).count() as f32 / active.len().max(1) as f32;
+        if persistence < MIN_PERSISTENCE {
+            continue;
+        }
+        report.notches.push(Notch {
+            frequency_hz: center as f32 * bin_hz,
+            depth_db: depth[center],
+            width_hz: (right - left + 1) as f32 * bin_hz,
+            persistence,
+            mains_hz: None,
+        });
+    }
+
+    // Hum removal notches the fundamental and harmonics together; a lone
+    // notch on a harmonic (1 kHz is the 20th of 50 Hz) is not explained
+    for grid in GRID_FREQS {
+        let on_harmonic = |n: &Notch| {
+            let harmonic = (n.frequency_hz / grid).round();
+            (1.0..=MAX_HUM_HARMONIC).contains(&harmonic)
+                && (n.frequency_hz - harmonic * grid).abs() <= HUM_TOLERANCE_HZ.max(bin_hz)
+        };
+        if report.notches.iter().filter(|n| on_harmonic(n)).count() >= MIN_HUM_NOTCHES {
+            for n in report.notches.iter_mut().filter(|n| n.mains_hz.is_none() && on_harmonic(n)) {
+                n.mains_hz = Some(grid);
+            }
+        }
+    }
+    report.suspicious = report.notches.iter().any(|n| n.mains_hz.is_none() && n.frequency_hz >= MODAL_LIMIT_HZ);
+    report
+}
@@ -0,0 +1,169 @@
+//! Null test: align two recordings, subtract one from the other and
+//! characterize what is left
+
+use crate::align;
+use crate::segments::{self, TimeRange};
+use serde::{Deserialize, Serialize};
+
+/// Residual level below which the copies are treated as the same audio
+/// after format conversion (dB relative to the reference)
+const TRANSPARENT_DB: f32 = -90.0;
+
+/// Window residual (relative to the louder of the two windows) above which
+/// that window is counted as not cancelling at all
+const DIVERGENT_DB: f32 = -6.0;
+
+/// Windows quieter than this in both files are never flagged
+const SILENCE_DBFS: f32 = -70.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NullTestOptions {
+    /// Compare this channel of both files instead of their mixes
+    pub channel: Option<usize>,
+    /// Scale the comparison by the least-squares gain before subtracting
+    pub match_gain: bool,
+    /// Largest offset searched (seconds)
+    pub max_offset: f32,
+    /// Window for the residual track (seconds)
+    pub window: f32,
+    /// Write the difference signal to this WAV file
+    pub output_path: Option<String>,
+}
+
+impl Default for NullTestOptions {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            match_gain: true,
+            max_offset: 10.0,
+            window: 0.1,
+            output_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NullVerdict {
+    BitIdentical,   // Every overlapping sample matches exactly
+    Transparent,    // Residual below -90 dB: same audio, different container/format
+    Processed,      // Uniform residual: re-encoded, resampled, EQ'd or dithered
+    Edited,         // Regions that do not cancel at all, or differing lengths
+}
+
+#[derive(Serialize)]
+pub struct NullTestReport {
+    pub offset_samples: i64,         // comparison[i + offset] lines up with reference[i]
+    pub offset_seconds: f32,
+    pub alignment_correlation: f32,
+    pub gain_db: f32,                // Applied to the comparison before subtracting
+    pub inverted: bool,              // Comparison had opposite polarity
+    pub overlap_start: f32,          // Overlap in reference time (seconds)
+    pub overlap_duration: f32,
+    pub reference_rms_dbfs: f32,
+    pub residual_rms_dbfs: f32,
+    pub residual_peak_dbfs: f32,
+    pub null_depth_db: f32,          // Residual RMS relative to the reference RMS
+    pub bit_identical: bool,
+    pub verdict: NullVerdict,
+    pub times: Vec<f32>,             // Window starts (reference time)
+    pub residual_db: Vec<f32>,       // Residual level per window (dBFS)
+    pub divergent_ranges: Vec<TimeRange>,
+}
+
+fn to_db(power: f64) -> f32 {
+    (10.0 * power.max(1e-20).log10()) as f32
+}
+
+/// Align `other` to `reference`, subtract it and report the residual.
+/// Returns the report and the difference signal over the overlap.
+pub fn null_test(
+    reference: &[f32],
+    other: &[f32],
+    sample_rate: u32,
+    options: &NullTestOptions,
+) -> Result<(NullTestReport, Vec<f32>), String> {
+    let sr = sample_rate as f32;
+    let (offset, correlation) = align::find_offset(reference, other, sample_rate, options.max_offset);
+
+    // Overlap in reference indices
+    let start = (-offset).max(0) as usize;
+    let end = (reference.len() as i64).min(other.len() as i64 - offset).max(0) as usize;
+    if start >= end {
+        return Err("Files do not overlap".to_string());
+    }
+    let a = &reference[start..end];
+    let b = &other[(start as i64 + offset) as usize..(end as i64 + offset) as usize];
+
+    let bit_identical = a == b;
+    let gain = if options.match_gain {
+        let dot: f64 = a.iter().zip(b).map(|(&x, &y)| x as f64 * y as f64).sum();
+        let energy: f64 = b.iter().map(|&y| y as f64 * y as f64).sum();
+        if energy > 0.0 { dot / energy } else { 1.0 }
+    } else if correlation < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+
+    let difference: Vec<f32> = a.iter().zip(b).map(|(&x, &y)| (x as f64 - gain * y as f64) as f32).collect();
+    let mean_square = |x: &[f32]| x.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / x.len().max(1) as f64;
+    let reference_power = mean_square(a);
+    let residual_power = if bit_identical { 0.0 } else { mean_square(&difference) };
+    let residual_peak = difference.iter().fold(0.0f32, |m, &s| m.max(s.abs()));
+
+    // Windowed residual, flagging windows that do not cancel
+    let window = ((options.window.max(0.01) * sr) as usize).max(1);
+    let mut times = Vec::new();
+    let mut residual_db = Vec::new();
+    let mut divergent = Vec::new();
+    for (k, chunk) in difference.chunks(window).enumerate() {
+        let range = k * window..k * window + chunk.len();
+        let level = mean_square(&a[range.clone()]).max(mean_square(&b[range]) * gain * gain);
+        let residual = to_db(mean_square(chunk));
+        times.push((start + k * window) as f32 / sr);
+        residual_db.push(residual);
+        divergent.push(to_db(level) > SILENCE_DBFS && residual - to_db(level) > DIVERGENT_DB);
+    }
+    let divergent_ranges: Vec<TimeRange> = segments::runs_to_ranges(&segments::flag_runs(&divergent, 1), window, window, sample_rate)
+        .into_iter()
+        .map(|r| TimeRange {
+            start_time: r.start_time + start as f32 / sr,
+            end_time: (r.end_time + start as f32 / sr).min(end as f32 / sr),
+        })
+        .collect();
+
+    let null_depth_db = to_db(residual_power) - to_db(reference_power);
+    // More than one window's worth of unmatched material at either end
+    let length_mismatch = reference.len().max(other.len()) - (end - start) > window;
+    let verdict = if !divergent_ranges.is_empty() || length_mismatch {
+        NullVerdict::Edited
+    } else if bit_identical {
+        NullVerdict::BitIdentical
+    } else if null_depth_db < TRANSPARENT_DB {
+        NullVerdict::Transparent
+    } else {
+        NullVerdict::Processed
+    };
+
+    let report = NullTestReport {
+        offset_samples: offset,
+        offset_seconds: offset as f32 / sr,
+        alignment_correlation: correlation,
+        gain_db: (20.0 * gain.abs().max(1e-10).log10()) as f32,
+        inverted: gain < 0.0,
+        overlap_start: start as f32 / sr,
+        overlap_duration: (end - start) as f32 / sr,
+        reference_rms_dbfs: to_db(reference_power),
+        residual_rms_dbfs: to_db(residual_power),
+        residual_peak_dbfs: 20.0 * residual_peak.max(1e-10).log10(),
+        null_depth_db,
+        bit_identical,
+        verdict,
+        times,
+        residual_db,
+        divergent_ranges,
+    };
+    Ok((report, difference))
+}
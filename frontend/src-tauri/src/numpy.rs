@@ -0,0 +1,95 @@
+//! NumPy files: `.npy` arrays and `.npz` archives of them (an uncompressed
+//! ZIP, as `numpy.savez` writes), float32 in C order
+
+/// A named float32 array
+pub struct Array {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+impl Array {
+    pub fn vector(name: &str, data: Vec<f32>) -> Self {
+        Self { name: name.to_string(), shape: vec![data.len()], data }
+    }
+
+    /// Rows of equal length as a two-dimensional array
+    pub fn matrix(name: &str, rows: &[Vec<f32>]) -> Self {
+        let columns = rows.first().map_or(0, Vec::len);
+        Self { name: name.to_string(), shape: vec![rows.len(), columns], data: rows.concat() }
+    }
+}
+
+/// The array as a version 1.0 `.npy` file
+pub fn npy(array: &Array) -> Vec<u8> {
+    let shape = match array.shape.as_slice() {
+        [n] => format!("({},)", n),
+        dims => format!("({})", dims.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+    // Magic, version and length take 10 bytes; the header pads the data to 64
+    let padded = (10 + header.len() + 1).div_ceil(64) * 64 - 10;
+    header.extend(std::iter::repeat_n(' ', padded - header.len() - 1));
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(10 + header.len() + 4 * array.data.len());
+    out.extend_from_slice(b"\x93NUMPY\x01\x00");
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend(array.data.iter().flat_map(|v| v.to_le_bytes()));
+    out
+}
+
+/// The arrays as an `.npz` archive, each stored as `<name>.npy`
+pub fn npz(arrays: &[Array]) -> Result<Vec<u8>, String> {
+    const DOS_DATE: u16 = (1 << 5) | 1;  // 1980-01-01, the earliest ZIP date
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+
+    for array in arrays {
+        let name = format!("{}.npy", array.name);
+        let data = npy(array);
+        let (size, offset) = match (u32::try_from(data.len()), u32::try_from(out.len())) {
+            (Ok(size), Ok(offset)) => (size, offset),
+            _ => return Err("Arrays too large for an .npz archive (4 GB)".to_string()),
+        };
+        let crc = crc32fast::hash(&data);
+
+        // Version needed, flags, method (stored), time, date, CRC, sizes, name length, extra length
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&20u16.to_le_bytes());
+        fields.extend_from_slice(&[0; 6]);
+        fields.extend_from_slice(&DOS_DATE.to_le_bytes());
+        fields.extend_from_slice(&crc.to_le_bytes());
+        fields.extend_from_slice(&size.to_le_bytes());
+        fields.extend_from_slice(&size.to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&[0; 2]);
+
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        out.extend_from_slice(&fields);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&data);
+
+        // Central directory entry: version made by, the same fields, then
+        // comment length, disk, attributes and the local header's offset
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&fields);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let start = u32::try_from(out.len()).map_err(|_| "Arrays too large for an .npz archive (4 GB)".to_string())?;
+    let count = arrays.len() as u16;
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&start.to_le_bytes());
+    out.extend_from_slice(&[0; 2]);
+    Ok(out)
+}
@@ -0,0 +1,78 @@
+//! IEC 61260 fractional-octave filter bank (1/3-octave, base-10)
+
+use crate::filters::{Biquad, FilterChain};
+use num_complex::Complex64;
+use std::f64::consts::PI;
+
+/// Nominal mid-band frequencies for band numbers -17..=13 relative to 1 kHz
+const NOMINAL_FREQS: [f32; 31] = [
+    20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0,
+    500.0, 630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0, 4000.0, 5000.0, 6300.0,
+    8000.0, 10000.0, 12500.0, 16000.0, 20000.0,
+];
+
+/// Butterworth prototype order per band edge (6th-order bandpass, Class 1 shape)
+const ORDER: usize = 3;
+
+pub struct OctaveBand {
+    pub nominal_freq: f32,
+    pub filter: FilterChain,
+}
+
+/// Build the 1/3-octave bank for `sample_rate`, omitting bands whose upper
+/// edge would reach Nyquist
+pub fn third_octave_bank(sample_rate: u32) -> Vec<OctaveBand> {
+    let sr = sample_rate as f64;
+    NOMINAL_FREQS
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &nominal)| {
+            let x = i as f64 - 17.0;
+            let center = 1000.0 * 10f64.powf(x / 10.0);
+            let lower = center * 10f64.powf(-1.0 / 20.0);
+            let upper = center * 10f64.powf(1.0 / 20.0);
+            if upper >= sr / 2.0 * 0.95 {
+                return None;
+            }
+            Some(OctaveBand {
+                nominal_freq: nominal,
+                filter: butterworth_bandpass(lower, upper, sr),
+            })
+        })
+        .collect()
+}
+
+/// Digital Butterworth bandpass between `lower` and `upper` Hz, normalized to
+/// unity gain at the geometric center frequency
+fn butterworth_bandpass(lower: f64, upper: f64, sr: f64) -> FilterChain {
+    let k = 2.0 * sr;
+    // Prewarp the band edges for the bilinear transform
+    let wl = k * (PI * lower / sr).tan();
+    let wu = k * (PI * upper / sr).tan();
+    let w0_sq = wl * wu;
+    let bw = wu - wl;
+
+    let mut sections = Vec::with_capacity(ORDER);
+    for n in 0..ORDER {
+        // Lowpass prototype pole, then the two bandpass poles it maps to
+        let theta = PI * (2 * n + ORDER + 1) as f64 / (2 * ORDER) as f64;
+        let p = Complex64::from_polar(1.0, theta);
+        let disc = (p * p * bw * bw - 4.0 * w0_sq).sqrt();
+        for s in [(p * bw + disc) / 2.0, (p * bw - disc) / 2.0] {
+            // Each upper-half-plane pole forms a section with its conjugate
+            if s.im <= 0.0 {
+                continue;
+            }
+            let z = (k + s) / (k - s);
+            sections.push(Biquad::new(1.0, 0.0, -1.0, -2.0 * z.re, z.norm_sqr()));
+        }
+    }
+
+    let mut chain = FilterChain::new(sections);
+    let center = (lower * upper).sqrt();
+    let gain = chain.magnitude(center, sr);
+    if gain > 0.0 {
+        chain.scale(1.0 / gain);
+    }
+    chain
+}
@@ -0,0 +1,137 @@
+//! Ogg Opus export with libopus
+
+use crate::export::{BitrateMode, ExportBuffer, ExportFormat, OpusApplication};
+
+/// Bitrate (kbit/s per channel) when none is given, and the range libopus takes
+const DEFAULT_KBPS_PER_CHANNEL: u32 = 64;
+const BITRATE_RANGE: (u32, u32) = (6, 510);
+/// Packets of 20 ms, and a page ended every second so players can seek
+#[cfg(feature = "opus")]
+const FRAME_SECS: f32 = 0.02;
+#[cfg(feature = "opus")]
+const PAGE_PACKETS: usize = 50;
+/// Fixed stream serial, so exporting the same selection twice gives the
+/// same file
+#[cfg(feature = "opus")]
+const SERIAL: u32 = 0x4f70_7573;
+
+/// Check the options against what Opus in Ogg can carry, returning the bitrate
+fn validate(buffer: &ExportBuffer, bitrate_kbps: Option<u32>) -> Result<u32, String> {
+    if !(1..=2).contains(&buffer.channels) {
+        return Err(format!("Opus export takes one or two channels, not {}; pick them with channel_mask", buffer.channels));
+    }
+    if ExportFormat::Opus.required_rate(buffer.sample_rate).is_some() {
+        return Err(format!("Opus takes 8, 12, 16, 24 or 48 kHz, not {} Hz", buffer.sample_rate));
+    }
+    let kbps = bitrate_kbps.unwrap_or(DEFAULT_KBPS_PER_CHANNEL * buffer.channels as u32);
+    if !(BITRATE_RANGE.0..=BITRATE_RANGE.1).contains(&kbps) {
+        return Err(format!("Opus bitrate must be between {} and {} kbit/s, not {}", BITRATE_RANGE.0, BITRATE_RANGE.1, kbps));
+    }
+    Ok(kbps)
+}
+
+/// The identification header (RFC 7845), channel mapping family 0
+#[cfg(feature = "opus")]
+fn opus_head(channels: usize, pre_skip: u16, input_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+    head.push(0);
+    head
+}
+
+/// The comment header, naming the encoder and nothing else
+#[cfg(feature = "opus")]
+fn opus_tags(vendor: &str) -> Vec<u8> {
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+/// Encode `buffer` (mono or stereo at an Opus sample rate) to an Ogg Opus
+/// file at `path`, at `bitrate_kbps` (64 kbit/s per channel by default)
+/// under `mode`, tuned for `application`. The encoder's lookahead is
+/// recorded as pre-skip and the end trimmed by the last granule position,
+/// so the file decodes to exactly the selection. With `new_only` (evidence
+/// mode) the file must not exist yet.
+#[cfg(feature = "opus")]
+pub fn encode(
+    buffer: &ExportBuffer,
+    path: &str,
+    bitrate_kbps: Option<u32>,
+    mode: BitrateMode,
+    application: OpusApplication,
+    new_only: bool,
+) -> Result<(), String> {
+    use audiopus::coder::Encoder;
+    use audiopus::{Application, Bitrate, Channels, SampleRate};
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+    use std::io::Write;
+
+    let kbps = validate(buffer, bitrate_kbps)?;
+    let opus_error = |e: audiopus::Error| format!("Opus encoder: {}", e);
+    let rate = SampleRate::try_from(buffer.sample_rate as i32).map_err(opus_error)?;
+    let channels = if buffer.channels == 1 { Channels::Mono } else { Channels::Stereo };
+    let application = match application {
+        OpusApplication::Voip => Application::Voip,
+        OpusApplication::Audio => Application::Audio,
+        OpusApplication::LowDelay => Application::LowDelay,
+    };
+
+    let mut encoder = Encoder::new(rate, channels, application).map_err(opus_error)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(kbps as i32 * 1000)).map_err(opus_error)?;
+    encoder.set_vbr(mode != BitrateMode::Cbr).map_err(opus_error)?;
+    encoder.set_vbr_constraint(mode == BitrateMode::ConstrainedVbr).map_err(opus_error)?;
+
+    // Granule positions count 48 kHz samples whatever the input rate
+    let scale = (48_000 / buffer.sample_rate) as usize;
+    let lookahead = encoder.lookahead().map_err(opus_error)? as usize;
+    let frame = (FRAME_SECS * buffer.sample_rate as f32) as usize;
+    let length = buffer.samples.len() / buffer.channels;
+    // Silence after the selection pushes its end out through the lookahead
+    let mut padded = buffer.samples.clone();
+    padded.resize((length + lookahead).div_ceil(frame) * frame * buffer.channels, 0.0);
+    let end_granule = ((lookahead + length) * scale) as u64;
+
+    let file = crate::evidence::create_output(path, new_only)?;
+    let mut writer = PacketWriter::new(std::io::BufWriter::new(file));
+    let write_error = |e: std::io::Error| format!("Failed to write Opus file: {}", e);
+    let head = opus_head(buffer.channels, (lookahead * scale) as u16, buffer.sample_rate);
+    writer.write_packet(head.into_boxed_slice(), SERIAL, PacketWriteEndInfo::EndPage, 0).map_err(write_error)?;
+    let tags = opus_tags(concat!("libopus, ", env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")));
+    writer.write_packet(tags.into_boxed_slice(), SERIAL, PacketWriteEndInfo::EndPage, 0).map_err(write_error)?;
+
+    let mut packet = vec![0u8; 4000];
+    let packets = padded.len() / (frame * buffer.channels);
+    for (i, chunk) in padded.chunks(frame * buffer.channels).enumerate() {
+        let size = encoder.encode_float(chunk, &mut packet).map_err(opus_error)?;
+        let granule = (((i + 1) * frame * scale) as u64).min(end_granule);
+        let end = if i + 1 == packets {
+            PacketWriteEndInfo::EndStream
+        } else if (i + 1) % PAGE_PACKETS == 0 {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        writer.write_packet(packet[..size].to_vec().into_boxed_slice(), SERIAL, end, granule).map_err(write_error)?;
+    }
+    writer.into_inner().flush().map_err(write_error)
+}
+
+#[cfg(not(feature = "opus"))]
+pub fn encode(
+    buffer: &ExportBuffer,
+    _path: &str,
+    bitrate_kbps: Option<u32>,
+    _mode: BitrateMode,
+    _application: OpusApplication,
+    _new_only: bool,
+) -> Result<(), String> {
+    validate(buffer, bitrate_kbps)?;
+    Err("This build has no Opus encoder (enable the `opus` feature)".to_string())
+}
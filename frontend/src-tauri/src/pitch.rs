@@ -0,0 +1,99 @@
+//! Autocorrelation pitch tracking of speech
+
+use crate::filters;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+
+/// Rate speech is analyzed at
+pub const ANALYSIS_RATE: u32 = 16_000;
+/// Analysis frame and hop (seconds)
+const FRAME_SECS: f32 = 0.04;
+pub const HOP_SECS: f32 = 0.01;
+/// Pitch search range (Hz)
+const MIN_F0: f32 = 70.0;
+const MAX_F0: f32 = 400.0;
+/// Normalized autocorrelation a frame needs to count as voiced
+const VOICING: f32 = 0.5;
+
+pub struct PitchFrame {
+    pub level_db: f32,      // Frame level (dBFS)
+    pub f0: Option<f32>,    // Fundamental when voiced (Hz)
+    pub periodicity: f32,   // Normalized autocorrelation at the pitch lag (0 when unvoiced)
+}
+
+/// Lowpass and linearly interpolate to `ANALYSIS_RATE`
+pub fn to_analysis_rate(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    if sample_rate == ANALYSIS_RATE {
+        return samples.to_vec();
+    }
+    let filtered = if sample_rate > ANALYSIS_RATE {
+        filters::butterworth_lowpass(0.45 * ANALYSIS_RATE as f64, 8, sample_rate as f64).process_buffer(samples)
+    } else {
+        samples.to_vec()
+    };
+    let step = sample_rate as f64 / ANALYSIS_RATE as f64;
+    let n = (filtered.len() as f64 / step) as usize;
+    (0..n)
+        .map(|i| {
+            let t = i as f64 * step;
+            let k = t as usize;
+            let frac = (t - k as f64) as f32;
+            let next = filtered[(k + 1).min(filtered.len() - 1)];
+            filtered[k] * (1.0 - frac) + next * frac
+        })
+        .collect()
+}
+
+/// Level, pitch and voicing of every frame of `samples` at `ANALYSIS_RATE`
+pub fn track(samples: &[f32]) -> Vec<PitchFrame> {
+    let sr = ANALYSIS_RATE as f32;
+    let frame = (FRAME_SECS * sr) as usize;
+    let hop = (HOP_SECS * sr) as usize;
+    let n_fft = (2 * frame).next_power_of_two();
+    let (min_lag, max_lag) = ((sr / MAX_F0) as usize, (sr / MIN_F0) as usize);
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * hop)
+        .take_while(|&start| start + frame <= samples.len())
+        .collect();
+
+    starts
+        .par_iter()
+        .map(|&start| {
+            let segment = &samples[start..start + frame];
+            let energy: f32 = segment.iter().map(|s| s * s).sum();
+            let level_db = 10.0 * (energy / frame as f32).max(1e-20).log10();
+            let unvoiced = PitchFrame { level_db, f0: None, periodicity: 0.0 };
+            if energy <= 0.0 {
+                return unvoiced;
+            }
+
+            // Autocorrelation through the power spectrum
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(n_fft);
+            let ifft = planner.plan_fft_inverse(n_fft);
+            let mut input = vec![0.0f32; n_fft];
+            input[..frame].copy_from_slice(segment);
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            spectrum.iter_mut().for_each(|c| *c = c.norm_sqr().into());
+            let mut acf = ifft.make_output_vec();
+            ifft.process(&mut spectrum, &mut acf).unwrap();
+
+            // Unbiased, normalized by the energy at lag 0
+            let r = |lag: usize| acf[lag] / acf[0] * frame as f32 / (frame - lag) as f32;
+            let Some(best) = (min_lag..=max_lag.min(frame / 2)).max_by(|&a, &b| r(a).total_cmp(&r(b))) else {
+                return unvoiced;
+            };
+            let peak = r(best);
+            if peak < VOICING || best == min_lag || best >= max_lag.min(frame / 2) {
+                return unvoiced;
+            }
+            // Parabolic interpolation of the peak lag
+            let (a, b, c) = (r(best - 1), peak, r(best + 1));
+            let denom = a - 2.0 * b + c;
+            let shift = if denom.abs() > 1e-9 { 0.5 * (a - c) / denom } else { 0.0 };
+            PitchFrame { level_db, f0: Some(sr / (best as f32 + shift)), periodicity: peak.min(1.0) }
+        })
+        .collect()
+}
+
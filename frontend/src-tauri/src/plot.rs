@@ -0,0 +1,492 @@
+//! Figures for publication: the spectrogram (with a colour bar) or the
+//! waveform with its axes and findings marked on it, laid out once and
+//! drawn either as SVG or as a PNG of any size
+
+use crate::figures::{self, Image};
+use crate::report::escape;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Glyphs of printable ASCII from the space on, five columns of seven rows
+/// (lowest bit at the top), for text in PNG figures
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00], [0x14, 0x08, 0x3e, 0x08, 0x14], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00], [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e], [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00], [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f], [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f], [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07], [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78], [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c], [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c], [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+/// Rows of marker labels stacked along the top of the plot before labels
+/// that fit nowhere are left out
+const LABEL_ROWS: usize = 4;
+/// Longest marker label drawn, in characters
+const MAX_LABEL: usize = 40;
+
+const AXIS: [u8; 3] = [60, 60, 60];
+const TEXT: [u8; 3] = [20, 20, 20];
+
+/// File formats a figure exports to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    Png,
+    /// Vector axes and text around the spectrogram raster, for editing
+    Svg,
+}
+
+impl ImageFormat {
+    /// The format a file name's extension stands for, PNG when it names none
+    pub fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("svg") => Self::Svg,
+            _ => Self::Png,
+        }
+    }
+}
+
+/// Size and content of an exported figure; the FFT, frequency and level
+/// settings apply to spectrograms
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FigureOptions {
+    pub width: usize,           // Pixels
+    pub height: usize,
+    pub n_fft: usize,
+    pub max_freq: Option<f32>,  // Hz; Nyquist when unset
+    pub range_db: f32,          // Colour bar span below the loudest value
+    pub title: Option<String>,  // The file name when unset
+    pub markers: bool,          // Mark findings and markers
+}
+
+impl Default for FigureOptions {
+    fn default() -> Self {
+        Self {
+            width: 2400,
+            height: 1200,
+            n_fft: 2048,
+            max_freq: None,
+            range_db: 100.0,
+            title: None,
+            markers: true,
+        }
+    }
+}
+
+impl FigureOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(200..=16384).contains(&self.width) || !(150..=16384).contains(&self.height) {
+            return Err("Image size must be between 200 × 150 and 16384 × 16384 pixels".to_string());
+        }
+        if !(64..=65536).contains(&self.n_fft) {
+            return Err("FFT size must be between 64 and 65536".to_string());
+        }
+        if self.range_db.is_nan() || self.range_db <= 0.0 {
+            return Err("Colour range must be above 0 dB".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A time (or span, given an end) marked from the bottom to the top
+#[derive(Clone)]
+pub struct TimeMarker {
+    pub start: f32,
+    pub end: Option<f32>,
+    pub label: String,          // Drawn beside the line when not empty
+    pub band: Option<(f32, f32)>,  // Hz; a span drawn as a box of these on a spectrogram
+    pub kind: &'static str,     // Named in the legend
+    pub color: [u8; 3],
+}
+
+/// A frequency band shaded from left to right
+#[derive(Clone)]
+pub struct FrequencyBand {
+    pub low: f32,
+    pub high: f32,
+    pub label: String,
+    pub kind: &'static str,
+    pub color: [u8; 3],
+}
+
+/// What a figure shows from left to right
+pub enum PlotContent<'a> {
+    /// Frames × bins in dB, as `spectrogram_db` returns, of 0 to `max_freq`
+    /// Hz, coloured between the floor and top `levels` (dB)
+    Spectrogram { data: &'a [Vec<f32>], max_freq: f32, levels: (f32, f32) },
+    /// Samples, full scale ±1
+    Waveform(&'a [f32]),
+}
+
+/// A figure of `start` to `end` seconds with what to mark on it. Bands are
+/// frequencies, drawn on a spectrogram only.
+pub struct Plot<'a> {
+    pub content: PlotContent<'a>,
+    pub start: f32,
+    pub end: f32,
+    pub title: String,
+    pub markers: Vec<TimeMarker>,
+    pub bands: Vec<FrequencyBand>,
+    pub playhead: Option<f32>,  // Time of a video frame, marked in white
+}
+
+#[derive(Clone, Copy)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// What a figure is drawn from, in pixels from the top left
+enum Shape {
+    Raster { x: f32, y: f32, image: Image },
+    Rect { x: f32, y: f32, width: f32, height: f32, color: [u8; 3], opacity: f32 },
+    /// Horizontal or vertical only
+    Line { x1: f32, y1: f32, x2: f32, y2: f32, color: [u8; 3], width: f32, dashed: bool },
+    /// `y` is the middle of the line of text; `vertical` reads upwards
+    Text { x: f32, y: f32, text: String, scale: usize, color: [u8; 3], anchor: Anchor, vertical: bool },
+}
+
+/// A tick value as short text: up to three decimals, trailing zeros dropped
+fn number(value: f32) -> String {
+    format!("{}", (value * 1000.0).round() / 1000.0 + 0.0)
+}
+
+/// Lay the figure out on `width` by `height` pixels. Text and lines scale
+/// with the smaller side, so a large export reads as a small one does.
+fn layout(plot: &Plot, width: usize, height: usize) -> Result<Vec<Shape>, String> {
+    let scale = (width.min(height) / 300).clamp(1, 8);
+    let s = scale as f32;
+    let line = 10.0 * s;
+    let text_width = |text: &str| text.chars().count() as f32 * 6.0 * s;
+    let text = |x: f32, y: f32, text: String, anchor: Anchor| Shape::Text { x, y, text, scale, color: TEXT, anchor, vertical: false };
+
+    let duration = (plot.end - plot.start).max(1e-3);
+    // Frequency up the side of a spectrogram, amplitude up a waveform's;
+    // a spectrogram's levels on the colour bar
+    let (low, high, unit, axis_title) = match plot.content {
+        PlotContent::Spectrogram { max_freq, .. } if max_freq >= 2000.0 => (0.0, max_freq, 1000.0, "Frequency (kHz)"),
+        PlotContent::Spectrogram { max_freq, .. } => (0.0, max_freq, 1.0, "Frequency (Hz)"),
+        PlotContent::Waveform(_) => (-1.0, 1.0, 1.0, "Amplitude (full scale)"),
+    };
+    let value_ticks = figures::ticks(low / unit, high / unit, 6);
+    let value_labels: Vec<String> = value_ticks.iter().map(|&v| number(v)).collect();
+    let (levels, bands): (_, &[FrequencyBand]) = match plot.content {
+        PlotContent::Spectrogram { levels, .. } => (Some(levels).filter(|(floor, top)| top.is_finite() && floor < top), &plot.bands),
+        PlotContent::Waveform(_) => (None, &[]),
+    };
+    let db_ticks = levels.map_or(Vec::new(), |(floor_db, top_db)| figures::ticks(floor_db, top_db, 5));
+    let db_labels: Vec<String> = db_ticks.iter().map(|&v| number(v)).collect();
+
+    let mut kinds: Vec<(&str, [u8; 3])> = Vec::new();
+    for (kind, color) in plot.markers.iter().map(|m| (m.kind, m.color)).chain(bands.iter().map(|b| (b.kind, b.color))) {
+        if !kinds.iter().any(|(k, _)| *k == kind) {
+            kinds.push((kind, color));
+        }
+    }
+
+    // Margins: the vertical axis on the left, any colour bar on the right,
+    // title and legend above, the time axis below
+    let pad = line / 2.0;
+    let tick = 4.0 * s;
+    let widest = |labels: &[String]| labels.iter().map(|l| text_width(l)).fold(0.0, f32::max);
+    let left = (pad + line + pad + widest(&value_labels) + tick + s).round();
+    let bar_width = (1.5 * line).round();
+    let right = match levels {
+        Some(_) => (line + bar_width + tick + 2.0 * s + widest(&db_labels) + pad + line + pad).round(),
+        None => (line + pad).round(),
+    };
+    let top = (pad + line * (!plot.title.is_empty() as u8 + !kinds.is_empty() as u8).max(1) as f32 + pad).round();
+    let bottom = (tick + 2.0 * line + pad).round();
+    let (plot_width, plot_height) = (width as f32 - left - right, height as f32 - top - bottom);
+    if plot_width < 16.0 || plot_height < 16.0 {
+        return Err("Image too small for the axes and labels".to_string());
+    }
+    let x = |t: f32| left + (t - plot.start) / duration * plot_width;
+    let y = |v: f32| top + plot_height - (v - low) / (high - low).max(1e-6) * plot_height;
+
+    let mut shapes = vec![Shape::Raster {
+        x: left,
+        y: top,
+        image: match plot.content {
+            PlotContent::Spectrogram { data, levels, .. } => figures::spectrogram(data, plot_width as usize, plot_height as usize, levels),
+            PlotContent::Waveform(samples) => figures::waveform(samples, plot_width as usize, plot_height as usize),
+        },
+    }];
+
+    for band in bands {
+        if band.low >= high || band.high <= low {
+            continue;
+        }
+        let (y_high, y_low) = (y(band.high.min(high)), y(band.low.max(low)));
+        let middle = (y_high + y_low) / 2.0;
+        let thickness = (y_low - y_high).max(2.0 * s);
+        shapes.push(Shape::Rect { x: left, y: middle - thickness / 2.0, width: plot_width, height: thickness, color: band.color, opacity: 0.4 });
+        if !band.label.is_empty() {
+            shapes.push(Shape::Text {
+                x: left + plot_width - 2.0 * s, y: middle - thickness / 2.0 - line / 2.0,
+                text: band.label.clone(), scale, color: band.color, anchor: Anchor::End, vertical: false,
+            });
+        }
+    }
+
+    // Marker labels go in the first row along the top where they clear the
+    // previous label; those fitting in none are left out
+    let mut row_ends = [f32::NEG_INFINITY; LABEL_ROWS];
+    let mut labels = Vec::new();
+    let mut sorted: Vec<&TimeMarker> = plot.markers.iter()
+        .filter(|m| m.end.unwrap_or(m.start) >= plot.start && m.start <= plot.end)
+        .collect();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+    for marker in sorted {
+        let start = x(marker.start.max(plot.start));
+        match marker.end {
+            Some(end) => {
+                let end = x(end.min(plot.end));
+                let (box_top, box_bottom) = match (marker.band, &plot.content) {
+                    (Some((band_low, _)), PlotContent::Spectrogram { .. }) if band_low >= high => continue,
+                    (Some((band_low, band_high)), PlotContent::Spectrogram { .. }) => (y(band_high.min(high)), y(band_low.max(low))),
+                    _ => (top, top + plot_height),
+                };
+                shapes.push(Shape::Rect { x: start, y: box_top, width: (end - start).max(1.0), height: box_bottom - box_top, color: marker.color, opacity: 0.2 });
+                for edge in [start, end] {
+                    shapes.push(Shape::Line { x1: edge, y1: box_top, x2: edge, y2: box_bottom, color: marker.color, width: s, dashed: false });
+                }
+                if box_top > top || box_bottom < top + plot_height {
+                    for edge in [box_top, box_bottom] {
+                        shapes.push(Shape::Line { x1: start, y1: edge, x2: end, y2: edge, color: marker.color, width: s, dashed: false });
+                    }
+                }
+            }
+            None => shapes.push(Shape::Line { x1: start, y1: top, x2: start, y2: top + plot_height, color: marker.color, width: s, dashed: true }),
+        }
+        if marker.label.is_empty() {
+            continue;
+        }
+        let label: String = marker.label.chars().take(MAX_LABEL).collect();
+        let (label_x, label_width) = (start + 2.0 * s, text_width(&label) + 2.0 * s);
+        if label_x + label_width > left + plot_width {
+            continue;
+        }
+        if let Some(row) = row_ends.iter().position(|&end| end < label_x) {
+            row_ends[row] = label_x + label_width;
+            let row_y = top + s + row as f32 * (line + s);
+            labels.push(Shape::Rect { x: label_x, y: row_y, width: label_width, height: line, color: [0, 0, 0], opacity: 0.6 });
+            labels.push(Shape::Text { x: label_x + s, y: row_y + line / 2.0, text: label, scale, color: [255, 255, 255], anchor: Anchor::Start, vertical: false });
+        }
+    }
+    shapes.extend(labels);
+    if let Some(time) = plot.playhead.filter(|t| (plot.start..=plot.end).contains(t)) {
+        shapes.push(Shape::Line { x1: x(time), y1: top, x2: x(time), y2: top + plot_height, color: [255, 255, 255], width: s, dashed: false });
+    }
+
+    // Frame and axes
+    let axis_width = (s / 2.0).max(1.0);
+    let (right_edge, bottom_edge) = (left + plot_width, top + plot_height);
+    for (x1, y1, x2, y2) in [(left, top, right_edge, top), (left, bottom_edge, right_edge, bottom_edge), (left, top, left, bottom_edge), (right_edge, top, right_edge, bottom_edge)] {
+        shapes.push(Shape::Line { x1, y1, x2, y2, color: AXIS, width: axis_width, dashed: false });
+    }
+    // Seconds on short spans, minutes and seconds on longer ones
+    let long = duration >= 60.0;
+    let time_ticks = figures::ticks(plot.start, plot.end, (plot_width / (12.0 * line)).clamp(2.0, 12.0) as usize);
+    for t in time_ticks.into_iter().filter(|&t| t >= 0.0) {
+        let label = if long { format!("{}:{:02}", t as u64 / 60, t as u64 % 60) } else { number(t) };
+        shapes.push(Shape::Line { x1: x(t), y1: bottom_edge, x2: x(t), y2: bottom_edge + tick, color: AXIS, width: axis_width, dashed: false });
+        shapes.push(text(x(t), bottom_edge + tick + line / 2.0, label, Anchor::Middle));
+    }
+    shapes.push(text(left + plot_width / 2.0, bottom_edge + tick + 1.5 * line, if long { "Time (m:ss)" } else { "Time (s)" }.to_string(), Anchor::Middle));
+    for (v, label) in value_ticks.iter().zip(value_labels) {
+        let vy = y(v * unit);
+        shapes.push(Shape::Line { x1: left - tick, y1: vy, x2: left, y2: vy, color: AXIS, width: axis_width, dashed: false });
+        shapes.push(text(left - tick - s, vy, label, Anchor::End));
+    }
+    shapes.push(Shape::Text {
+        x: pad + line / 2.0, y: top + plot_height / 2.0,
+        text: axis_title.to_string(), scale, color: TEXT, anchor: Anchor::Middle, vertical: true,
+    });
+
+    // Colour bar, the loudest value at the top
+    if let Some((floor_db, top_db)) = levels {
+        let bar_x = right_edge + line;
+        let mut bar = Image::new(bar_width as usize, plot_height as usize, [0; 3]);
+        for row in 0..bar.height {
+            let color = figures::viridis(1.0 - row as f32 / (bar.height - 1).max(1) as f32);
+            (0..bar.width).for_each(|column| bar.set(column, row, color));
+        }
+        shapes.push(Shape::Raster { x: bar_x, y: top, image: bar });
+        for (x1, y1, x2, y2) in [(bar_x, top, bar_x + bar_width, top), (bar_x, bottom_edge, bar_x + bar_width, bottom_edge), (bar_x, top, bar_x, bottom_edge), (bar_x + bar_width, top, bar_x + bar_width, bottom_edge)] {
+            shapes.push(Shape::Line { x1, y1, x2, y2, color: AXIS, width: axis_width, dashed: false });
+        }
+        for (v, label) in db_ticks.iter().zip(db_labels) {
+            let vy = bottom_edge - (v - floor_db) / (top_db - floor_db) * plot_height;
+            shapes.push(Shape::Line { x1: bar_x + bar_width, y1: vy, x2: bar_x + bar_width + tick, y2: vy, color: AXIS, width: axis_width, dashed: false });
+            shapes.push(text(bar_x + bar_width + tick + 2.0 * s, vy, label, Anchor::Start));
+        }
+        shapes.push(Shape::Text {
+            x: width as f32 - pad - line / 2.0, y: top + plot_height / 2.0,
+            text: "Level (dB)".to_string(), scale, color: TEXT, anchor: Anchor::Middle, vertical: true,
+        });
+    }
+
+    // Title, then the legend of what is marked
+    let mut row_y = pad + line / 2.0;
+    if !plot.title.is_empty() {
+        shapes.push(text(left, row_y, plot.title.clone(), Anchor::Start));
+        row_y += line;
+    }
+    let mut key_x = left;
+    for (kind, color) in kinds {
+        if key_x + 1.5 * line + 2.0 * s + text_width(kind) > width as f32 - pad {
+            break;
+        }
+        shapes.push(Shape::Rect { x: key_x, y: row_y - line / 4.0, width: 1.5 * line, height: line / 2.0, color, opacity: 1.0 });
+        shapes.push(text(key_x + 1.5 * line + 2.0 * s, row_y, kind.to_string(), Anchor::Start));
+        key_x += 1.5 * line + 2.0 * s + text_width(kind) + line;
+    }
+    Ok(shapes)
+}
+
+fn hex_color(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn svg(shapes: &[Shape], width: usize, height: usize) -> Result<Vec<u8>, String> {
+    let mut out = format!(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\">\n",
+        "<rect width=\"{0}\" height=\"{1}\" fill=\"#ffffff\"/>\n"), width, height);
+    for shape in shapes {
+        match shape {
+            Shape::Raster { x, y, image } => {
+                let _ = writeln!(out, "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"/>",
+                    x, y, image.width, image.height, STANDARD.encode(image.png()?));
+            }
+            Shape::Rect { x, y, width, height, color, opacity } => {
+                let _ = writeln!(out, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+                    x, y, width, height, hex_color(*color), opacity);
+            }
+            Shape::Line { x1, y1, x2, y2, color, width, dashed } => {
+                let dash = if *dashed { format!(" stroke-dasharray=\"{} {}\"", 4.0 * width, 3.0 * width) } else { String::new() };
+                let _ = writeln!(out, "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\"{}/>",
+                    x1, y1, x2, y2, hex_color(*color), width, dash);
+            }
+            Shape::Text { x, y, text, scale, color, anchor, vertical } => {
+                let anchor = match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                };
+                let rotate = if *vertical { format!(" transform=\"rotate(-90 {:.1} {:.1})\"", x, y) } else { String::new() };
+                let _ = writeln!(out, "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{}\" fill=\"{}\" text-anchor=\"{}\" dominant-baseline=\"central\"{}>{}</text>",
+                    x, y, 10 * scale, hex_color(*color), anchor, rotate, escape(text));
+            }
+        }
+    }
+    out.push_str("</svg>\n");
+    Ok(out.into_bytes())
+}
+
+/// Fill the pixels from (`x`, `y`) to (`x + width`, `y + height`), rounded
+/// to whole pixels and at least one wide
+fn fill(image: &mut Image, x: f32, y: f32, width: f32, height: f32, color: [u8; 3], opacity: f32) {
+    let (x0, y0) = (x.round().max(0.0) as usize, y.round().max(0.0) as usize);
+    let (x1, y1) = (((x + width).round() as usize).max(x0 + 1), ((y + height).round() as usize).max(y0 + 1));
+    for py in y0..y1.min(image.height) {
+        for px in x0..x1.min(image.width) {
+            image.blend(px, py, color, opacity);
+        }
+    }
+}
+
+fn draw_text(image: &mut Image, (x, y): (f32, f32), text: &str, scale: usize, color: [u8; 3], anchor: Anchor, vertical: bool) {
+    let s = scale as f32;
+    let length = text.chars().count() as f32 * 6.0 * s - s;
+    let offset = match anchor {
+        Anchor::Start => 0.0,
+        Anchor::Middle => (length / 2.0).round(),
+        Anchor::End => length,
+    };
+    let (along, across) = if vertical { (y + offset, x - 3.5 * s) } else { (x - offset, y - 3.5 * s) };
+    for (i, c) in text.chars().enumerate() {
+        let glyph = FONT[(c as usize).checked_sub(0x20).filter(|&g| g < FONT.len()).unwrap_or('?' as usize - 0x20)];
+        for (column, bits) in glyph.iter().enumerate() {
+            let a = (i * 6 + column) as f32 * s;
+            for row in (0..7).filter(|r| (bits >> r) & 1 == 1) {
+                let b = across + row as f32 * s;
+                match vertical {
+                    false => fill(image, along + a, b, s, s, color, 1.0),
+                    true => fill(image, b, along - a - s, s, s, color, 1.0),
+                }
+            }
+        }
+    }
+}
+
+fn raster(shapes: &[Shape], width: usize, height: usize) -> Image {
+    let mut image = Image::new(width, height, [255, 255, 255]);
+    for shape in shapes {
+        match shape {
+            Shape::Raster { x, y, image: source } => {
+                let (x0, y0) = (x.round() as usize, y.round() as usize);
+                for row in 0..source.height {
+                    for column in 0..source.width {
+                        let i = 3 * (row * source.width + column);
+                        image.set(x0 + column, y0 + row, [source.pixels[i], source.pixels[i + 1], source.pixels[i + 2]]);
+                    }
+                }
+            }
+            Shape::Rect { x, y, width, height, color, opacity } => fill(&mut image, *x, *y, *width, *height, *color, *opacity),
+            Shape::Line { x1, y1, x2, y2, color, width, dashed } => {
+                let vertical = x1 == x2;
+                let (from, to) = if vertical { (y1.min(*y2), y1.max(*y2)) } else { (x1.min(*x2), x1.max(*x2)) };
+                let (dash, gap) = if *dashed { (4.0 * width, 3.0 * width) } else { (to - from, 0.0) };
+                let mut position = from;
+                while position < to {
+                    let length = dash.min(to - position);
+                    match vertical {
+                        true => fill(&mut image, x1 - width / 2.0, position, *width, length, *color, 1.0),
+                        false => fill(&mut image, position, y1 - width / 2.0, length, *width, *color, 1.0),
+                    }
+                    position += dash + gap;
+                }
+            }
+            Shape::Text { x, y, text, scale, color, anchor, vertical } => draw_text(&mut image, (*x, *y), text, *scale, *color, *anchor, *vertical),
+        }
+    }
+    image
+}
+
+/// The figure as an image `width` by `height` pixels, such as a frame of a
+/// video
+pub fn image(plot: &Plot, width: usize, height: usize) -> Result<Image, String> {
+    Ok(raster(&layout(plot, width, height)?, width, height))
+}
+
+/// The figure, `width` by `height` pixels, as a PNG or SVG file's contents
+pub fn render(plot: &Plot, width: usize, height: usize, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let shapes = layout(plot, width, height)?;
+    match format {
+        ImageFormat::Png => raster(&shapes, width, height).png(),
+        ImageFormat::Svg => svg(&shapes, width, height),
+    }
+}
@@ -0,0 +1,237 @@
+//! Recorder fingerprinting from the noise floor, DC and gain behavior
+
+use crate::rerecord;
+use serde::{Deserialize, Serialize};
+
+/// Spectrum frames (samples)
+const N_FFT: usize = 4096;
+const HOP: usize = 2048;
+/// Third-octave bands from this center (Hz) up to 0.45 × the sample rate
+const LOWEST_BAND_HZ: f32 = 50.0;
+/// Frames within this much of the 10th-percentile level are pauses, and
+/// frames within this much of the 95th percentile active (dB)
+const PAUSE_MARGIN_DB: f32 = 6.0;
+const ACTIVE_RANGE_DB: f32 = 20.0;
+/// Least pause material for a floor profile (seconds)
+const MIN_PAUSE_SECS: f32 = 1.0;
+/// Bands above this (Hz) describe the preamp hiss
+const HISS_BAND_HZ: f32 = 2000.0;
+/// Block the DC offset is followed in (seconds)
+const DC_BLOCK_SECS: f32 = 1.0;
+/// Pauses this soon after activity show the gain control's release, and
+/// pauses this long after it the settled gain (seconds)
+const RELEASE_SECS: f32 = 0.5;
+const SETTLED_SECS: f32 = 2.0;
+/// Differences one recorder shows from file to file, used to scale the
+/// comparison: floor shape (dB RMS), hiss slope (dB/octave), DC offset
+/// (full scale), floor kurtosis and AGC release dip (dB)
+const PROFILE_TOLERANCE_DB: f32 = 3.0;
+const SLOPE_TOLERANCE: f32 = 2.0;
+const DC_TOLERANCE: f32 = 1e-3;
+const KURTOSIS_TOLERANCE: f32 = 1.0;
+const RELEASE_TOLERANCE_DB: f32 = 3.0;
+/// Scaled distances up to which two files are consistent with one
+/// recorder, and from which they are not
+const CONSISTENT_DISTANCE: f32 = 1.5;
+const INCONSISTENT_DISTANCE: f32 = 2.5;
+
+const CAVEAT: &str = "A fingerprint describes the recording chain's noise and gain behavior, not a serial \
+number: identical models can match, and input gain, settings, cables and acoustic background can move \
+one recorder's fingerprint. Compare files recorded at similar settings and in quiet conditions.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceFingerprint {
+    pub band_freqs: Vec<f32>,               // Third-octave centers of the floor profile
+    pub floor_profile_db: Vec<f32>,         // Pause spectrum per band, less its mean (shape only)
+    pub floor_dbfs: Option<f32>,            // Pause level
+    pub hiss_slope_db: Option<f32>,         // Slope of the floor above 2 kHz (dB per octave)
+    pub floor_kurtosis: Option<f32>,        // Of the pause samples; 3 for Gaussian hiss
+    pub dc_offset: f32,                     // Mean sample value (full scale)
+    pub dc_drift: f32,                      // Standard deviation of the 1 s means
+    pub agc_release_db: Option<f32>,        // Floor just after activity against the settled floor
+    pub active_spread_db: Option<f32>,      // Standard deviation of the active frame levels
+    pub vector: Vec<f32>,                   // The features above scaled by their tolerances
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceVerdict {
+    Consistent,     // Within what one recorder varies by
+    Inconclusive,
+    Inconsistent,   // Differs by more than one recorder plausibly would
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceComparison {
+    pub reference: DeviceFingerprint,
+    pub other: DeviceFingerprint,
+    pub profile_distance_db: Option<f32>,   // RMS floor-shape difference over the shared bands
+    pub distance: Option<f32>,              // Length of the scaled feature difference
+    pub verdict: DeviceVerdict,
+    pub caveat: &'static str,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted[((p * (sorted.len() - 1) as f32) as usize).min(sorted.len() - 1)]
+}
+
+fn mean_std(values: &[f32]) -> (f32, f32) {
+    let n = values.len().max(1) as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    (mean, (values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n).sqrt())
+}
+
+/// Least-squares slope of `y` against `x`
+fn slope(x: &[f32], y: &[f32]) -> Option<f32> {
+    let (mx, _) = mean_std(x);
+    let (my, _) = mean_std(y);
+    let den: f32 = x.iter().map(|a| (a - mx).powi(2)).sum();
+    (x.len() >= 2 && den > 0.0).then(|| x.iter().zip(y).map(|(a, b)| (a - mx) * (b - my)).sum::<f32>() / den)
+}
+
+/// Extract the features a recorder leaves on everything it records: the
+/// spectral shape and sample distribution of its noise floor (preamp
+/// hiss), its DC offset and how that drifts, and the traces of automatic
+/// gain control (a floor that dips after loud passages and recovers, and
+/// a narrow spread of active levels). Levels are left out of the vector,
+/// since they follow the input gain.
+pub fn fingerprint(samples: &[f32], sample_rate: u32) -> DeviceFingerprint {
+    let sr = sample_rate as f32;
+    let centers: Vec<f32> = (0..)
+        .map(|i| LOWEST_BAND_HZ * 2f32.powf(i as f32 / 3.0))
+        .take_while(|&f| f * 2f32.powf(1.0 / 6.0) < 0.45 * sr)
+        .collect();
+    let bands: Vec<(f32, f32)> = centers.iter().map(|&f| (f / 2f32.powf(1.0 / 6.0), f * 2f32.powf(1.0 / 6.0))).collect();
+
+    // DC offset and its drift
+    let (dc_offset, _) = mean_std(samples);
+    let block = ((DC_BLOCK_SECS * sr) as usize).max(1);
+    let block_means: Vec<f32> = samples.chunks(block).filter(|c| c.len() == block).map(|c| mean_std(c).0).collect();
+    let (_, dc_drift) = mean_std(&block_means);
+
+    let mut print = DeviceFingerprint {
+        band_freqs: centers.clone(),
+        floor_profile_db: Vec::new(),
+        floor_dbfs: None,
+        hiss_slope_db: None,
+        floor_kurtosis: None,
+        dc_offset,
+        dc_drift,
+        agc_release_db: None,
+        active_spread_db: None,
+        vector: Vec::new(),
+    };
+    let frames = rerecord::band_frames(samples, sample_rate, N_FFT, HOP, &bands);
+    if frames.is_empty() || bands.is_empty() {
+        print.vector = feature_vector(&print);
+        return print;
+    }
+    let totals: Vec<f32> = frames
+        .iter()
+        .map(|f| 10.0 * f.iter().map(|l| 10f32.powf(l / 10.0)).sum::<f32>().log10())
+        .collect();
+    let quiet = percentile(&totals, 0.1);
+    let pauses: Vec<usize> = (0..frames.len()).filter(|&i| totals[i] <= quiet + PAUSE_MARGIN_DB).collect();
+    let frame_secs = HOP as f32 / sr;
+
+    if pauses.len() as f32 * frame_secs >= MIN_PAUSE_SECS {
+        // Floor shape and hiss slope
+        let profile: Vec<f32> = (0..bands.len())
+            .map(|b| pauses.iter().map(|&i| frames[i][b]).sum::<f32>() / pauses.len() as f32)
+            .collect();
+        let (mean, _) = mean_std(&profile);
+        print.floor_profile_db = profile.iter().map(|l| l - mean).collect();
+        let hiss: Vec<usize> = (0..bands.len()).filter(|&b| centers[b] >= HISS_BAND_HZ).collect();
+        let octaves: Vec<f32> = hiss.iter().map(|&b| centers[b].log2()).collect();
+        let levels: Vec<f32> = hiss.iter().map(|&b| profile[b]).collect();
+        print.hiss_slope_db = slope(&octaves, &levels);
+
+        // Level and sample distribution of the pauses, DC removed
+        let pause_samples: Vec<f32> = pauses
+            .iter()
+            .flat_map(|&i| &samples[i * HOP + (N_FFT - HOP) / 2..i * HOP + (N_FFT + HOP) / 2])
+            .map(|s| s - dc_offset)
+            .collect();
+        let power = pause_samples.iter().map(|s| s * s).sum::<f32>() / pause_samples.len() as f32;
+        if power > 0.0 {
+            print.floor_dbfs = Some(10.0 * power.log10());
+            let fourth = pause_samples.iter().map(|s| s.powi(4)).sum::<f32>() / pause_samples.len() as f32;
+            print.floor_kurtosis = Some(fourth / (power * power));
+        }
+
+        // Gain control: the floor right after activity against the floor
+        // once the gain has settled. A gain control lifts the floor out of
+        // the pauses as it recovers, so every frame well below the active
+        // level counts here
+        let active = (percentile(&totals, 0.95) - ACTIVE_RANGE_DB).max(quiet + 2.0 * PAUSE_MARGIN_DB);
+        let inactive = (quiet + active) / 2.0;
+        let (release, settled) = ((RELEASE_SECS / frame_secs) as usize, (SETTLED_SECS / frame_secs) as usize);
+        let mut since = usize::MAX;
+        let (mut early, mut late) = (Vec::new(), Vec::new());
+        for &total in &totals {
+            since = if total >= active { 0 } else { since.saturating_add(1) };
+            if total < inactive {
+                if since <= release {
+                    early.push(total);
+                } else if since >= settled && since != usize::MAX {
+                    late.push(total);
+                }
+            }
+        }
+        if !early.is_empty() && !late.is_empty() {
+            print.agc_release_db = Some(mean_std(&early).0 - mean_std(&late).0);
+        }
+        let active_levels: Vec<f32> = totals.iter().copied().filter(|&t| t >= active).collect();
+        print.active_spread_db = (active_levels.len() >= 2).then(|| mean_std(&active_levels).1);
+    }
+    print.vector = feature_vector(&print);
+    print
+}
+
+/// The comparable features, each divided by the difference one recorder
+/// shows between files; missing ones are NaN
+fn feature_vector(print: &DeviceFingerprint) -> Vec<f32> {
+    let scaled = |value: Option<f32>, tolerance: f32| value.map_or(f32::NAN, |v| v / tolerance);
+    let mut vector: Vec<f32> = print.floor_profile_db.iter().map(|l| l / PROFILE_TOLERANCE_DB).collect();
+    vector.extend([
+        scaled(print.hiss_slope_db, SLOPE_TOLERANCE),
+        scaled(Some(print.dc_offset), DC_TOLERANCE),
+        scaled(print.floor_kurtosis, KURTOSIS_TOLERANCE),
+        scaled(print.agc_release_db, RELEASE_TOLERANCE_DB),
+    ]);
+    vector
+}
+
+/// Compare two fingerprints. The floor shapes are compared over the bands
+/// both cover, with their means over those bands removed; each feature
+/// difference is scaled by its tolerance and the distance is the length
+/// of the differences both files have, so one feature far out of
+/// tolerance is not averaged away by the others.
+pub fn compare(reference: DeviceFingerprint, other: DeviceFingerprint) -> DeviceComparison {
+    let shared = reference.floor_profile_db.len().min(other.floor_profile_db.len());
+    let profile_distance_db = (shared > 0).then(|| {
+        let (a, b) = (&reference.floor_profile_db[..shared], &other.floor_profile_db[..shared]);
+        let offset = mean_std(a).0 - mean_std(b).0;
+        (a.iter().zip(b).map(|(x, y)| (x - y - offset).powi(2)).sum::<f32>() / shared as f32).sqrt()
+    });
+
+    let scalars = |print: &DeviceFingerprint| print.vector[print.floor_profile_db.len()..].to_vec();
+    let mut terms: Vec<f32> = scalars(&reference)
+        .iter()
+        .zip(&scalars(&other))
+        .map(|(a, b)| a - b)
+        .filter(|d| d.is_finite())
+        .collect();
+    let distance = profile_distance_db.map(|d| {
+        terms.push(d / PROFILE_TOLERANCE_DB);
+        terms.iter().map(|t| t * t).sum::<f32>().sqrt()
+    });
+    let verdict = match distance {
+        Some(d) if d <= CONSISTENT_DISTANCE => DeviceVerdict::Consistent,
+        Some(d) if d >= INCONSISTENT_DISTANCE => DeviceVerdict::Inconsistent,
+        _ => DeviceVerdict::Inconclusive,
+    };
+    DeviceComparison { reference, other, profile_distance_db, distance, verdict, caveat: CAVEAT }
+}
@@ -0,0 +1,154 @@
+//! Spectral editing: time-frequency rectangles attenuated, filled in from
+//! the spectrum around them, or kept alone
+
+use crate::spectrum;
+use serde::Deserialize;
+
+/// STFT frame (seconds, rounded up to a power of two): about 20 Hz bins
+const FRAME_SECS: f32 = 0.046;
+/// Bins either side of the rectangle taken along, covering a tone's
+/// leakage through the Hann window's main lobe
+const LEAKAGE_BINS: usize = 2;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairMode {
+    /// Turn the region down by `attenuation_db`
+    Attenuate,
+    /// Replace the region's magnitudes with those interpolated between the
+    /// frames just before and after it, keeping the phase
+    #[default]
+    Interpolate,
+}
+
+/// A rectangle of the spectrogram to repair, in seconds into the recording
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RepairRegion {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    #[serde(default)]
+    pub mode: RepairMode,
+    /// Attenuation: how far the region is turned down (dB), 30 by default
+    #[serde(default)]
+    pub attenuation_db: Option<f32>,
+}
+
+impl RepairRegion {
+    pub fn validate(&self, sample_rate: u32) -> Result<(), String> {
+        let nyquist = sample_rate as f32 / 2.0;
+        if (self.start_time..self.end_time).is_empty() {
+            return Err("A repair region must end after it starts".to_string());
+        }
+        if !(0.0 <= self.low_hz && self.low_hz < self.high_hz && self.high_hz <= nyquist) {
+            return Err(format!("A repair region's frequencies must rise from 0 to at most {} Hz", nyquist));
+        }
+        if !(0.0..=120.0).contains(&self.attenuation_db.unwrap_or(0.0)) {
+            return Err("Repair attenuation must be between 0 and 120 dB".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// STFT frame length at `sample_rate`
+fn frame_length(sample_rate: u32) -> usize {
+    ((FRAME_SECS * sample_rate as f32) as usize).next_power_of_two()
+}
+
+/// Keep only `low_hz`..`high_hz` of every STFT frame of one channel and
+/// resynthesize by overlap-add: the sound of a band of the spectrogram
+/// alone. Bins straddling a limit are kept at half gain.
+pub fn isolate(samples: &[f32], sample_rate: u32, low_hz: f32, high_hz: f32) -> Vec<f32> {
+    let n_fft = frame_length(sample_rate);
+    let bin_hz = sample_rate as f32 / n_fft as f32;
+    let gains: Vec<f32> = (0..=n_fft / 2)
+        .map(|k| {
+            let (lo, hi) = ((k as f32 - 0.5) * bin_hz, (k as f32 + 0.5) * bin_hz);
+            if lo >= low_hz && hi <= high_hz {
+                1.0
+            } else if hi > low_hz && lo < high_hz {
+                0.5
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    spectrum::stft_filter(samples, n_fft, |_, spectrum| spectrum.iter_mut().zip(&gains).for_each(|(bin, g)| *bin *= g))
+}
+
+/// Repair `region` (times into `samples`) in one channel. Only a stretch a
+/// few frames either side of the region is run through the STFT, which
+/// leaves everything beyond it untouched.
+pub fn repair(samples: &mut [f32], sample_rate: u32, region: &RepairRegion) {
+    let sr = sample_rate as f32;
+    let n_fft = frame_length(sample_rate);
+    let hop = n_fft / 4;
+    let bin_hz = sr / n_fft as f32;
+    let bins = ((region.low_hz / bin_hz).floor() as usize).saturating_sub(LEAKAGE_BINS)
+        ..=((region.high_hz / bin_hz).ceil() as usize + LEAKAGE_BINS).min(n_fft / 2);
+
+    let (start, end) = ((region.start_time.max(0.0) * sr) as usize, ((region.end_time * sr) as usize).min(samples.len()));
+    if start >= end {
+        return;
+    }
+    let lo = start.saturating_sub(2 * n_fft);
+    let hi = (end + 2 * n_fft).min(samples.len());
+    let span = &samples[lo..hi];
+
+    // Frame i of the stretch is centred on lo + i·hop + hop - n_fft/2. Frames
+    // whose middle half meets the region are repaired, from the nearest
+    // frames whose window stays clear of it.
+    let center = |i: usize| (lo + i * hop + hop) as f32 - (n_fft / 2) as f32;
+    let (start, end) = (start as f32, end as f32);
+    let quarter = (n_fft / 4) as f32;
+    let half = (n_fft / 2) as f32;
+    let n_frames = span.len().div_ceil(hop) + 3;
+    let repaired: Vec<usize> = (0..n_frames).filter(|&i| center(i) >= start - quarter && center(i) <= end + quarter).collect();
+    let (Some(&first), Some(&last)) = (repaired.first(), repaired.last()) else {
+        return;
+    };
+    let before = (0..first).rev().find(|&i| center(i) <= start - half);
+    let after = (last + 1..n_frames).find(|&i| center(i) >= end + half && center(i) < (hi as f32));
+
+    // Magnitudes of the reference frames, from a first pass
+    let mut reference: [Option<Vec<f32>>; 2] = [None, None];
+    if region.mode == RepairMode::Interpolate {
+        spectrum::stft_filter(span, n_fft, |i, spectrum| {
+            for (frame, slot) in [before, after].iter().zip(reference.iter_mut()) {
+                if Some(i) == *frame {
+                    *slot = Some(spectrum[bins.clone()].iter().map(|c| c.norm()).collect());
+                }
+            }
+        });
+    }
+
+    let gain = 10f32.powf(-region.attenuation_db.unwrap_or(30.0) / 20.0);
+    let output = spectrum::stft_filter(span, n_fft, |i, spectrum| {
+        if !(first..=last).contains(&i) {
+            return;
+        }
+        let target: Option<Vec<f32>> = match (&reference[0], &reference[1]) {
+            (Some(a), Some(b)) => {
+                let (from, to) = (before.unwrap_or(0) as f32, after.unwrap_or(0) as f32);
+                let t = (i as f32 - from) / (to - from);
+                Some(a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect())
+            }
+            (Some(only), None) | (None, Some(only)) => Some(only.clone()),
+            (None, None) => None,
+        };
+        let bins = &mut spectrum[bins.clone()];
+        match target {
+            Some(magnitudes) => {
+                for (bin, magnitude) in bins.iter_mut().zip(magnitudes) {
+                    let norm = bin.norm();
+                    // Never louder than the damaged bin: the fill replaces, it does not add
+                    *bin *= if norm > 0.0 { magnitude.min(norm) / norm } else { 0.0 };
+                }
+            }
+            // Attenuation, or interpolation with no clear frames around the region
+            None => bins.iter_mut().for_each(|bin| *bin *= gain),
+        }
+    });
+    samples[lo..hi].copy_from_slice(&output);
+}
@@ -0,0 +1,370 @@
+//! Archival reports: the identity of the examined file and of the tool, to
+//! store alongside the analysis results, and the printable HTML rendering
+
+use crate::figures::{ticks, Image};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::io::Read;
+
+/// Version of the JSON report layout; raised whenever a field is renamed,
+/// removed or changes meaning, so archived reports can be told apart
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Rows of one table and items of one list shown in the HTML rendering;
+/// the JSON report keeps them all
+const MAX_ROWS: usize = 500;
+const MAX_ITEMS: usize = 40;
+/// Plot area of the figures (SVG units) and the margins holding the axes
+const PLOT_WIDTH: f32 = 1000.0;
+const MARGIN_LEFT: f32 = 70.0;
+const MARGIN_BOTTOM: f32 = 28.0;
+/// Colours of the kinds of marker, in order of first appearance
+const MARKER_COLORS: [&str; 6] = ["#e6194b", "#f58231", "#911eb4", "#3cb44b", "#4363d8", "#f032e6"];
+
+/// File formats a report exports to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    /// Everything, for archiving and diffing
+    Json,
+    /// A self-contained document with figures, to read or print to PDF
+    Html,
+}
+
+impl ReportFormat {
+    /// The format a file name's extension stands for, JSON when it names none
+    pub fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("html") | Some("htm") => Self::Html,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// A finding marked on the figures and listed with them
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub time: f32,
+    pub kind: &'static str,
+    pub detail: String,
+}
+
+pub enum FigureContent {
+    Raster(Image),
+    /// A curve through (time, value) points
+    Line(Vec<(f32, f32)>),
+}
+
+/// A figure spanning the recording from left to right
+pub struct Figure {
+    pub title: String,
+    pub content: FigureContent,
+    pub duration: f32,         // Seconds across the width
+    pub y_range: (f32, f32),   // Values at the bottom and top edges
+    pub y_unit: &'static str,
+}
+
+/// The program that wrote a report
+#[derive(Debug, Clone, Serialize)]
+pub struct Tool {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+impl Tool {
+    pub fn current() -> Self {
+        Self { name: env!("CARGO_PKG_NAME"), version: env!("CARGO_PKG_VERSION") }
+    }
+}
+
+/// A file as found on disk when the report was written
+#[derive(Debug, Clone, Serialize)]
+pub struct FileIdentity {
+    pub path: String,
+    pub size_bytes: u64,
+    pub modified: Option<String>,  // RFC 3339, UTC, where the filesystem keeps it
+    pub sha256: String,            // Of the file's bytes
+}
+
+/// Current time as RFC 3339 in UTC, to the second
+pub fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Size, modification time and SHA-256 of the file at `path`, read in
+/// blocks so long recordings are not held in memory twice
+pub fn identify(path: &str) -> Result<FileIdentity, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let metadata = file.metadata().map_err(|e| format!("Failed to read metadata of {}: {}", path, e))?;
+
+    let mut hasher = Sha256::new();
+    let mut block = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut block).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&block[..n]);
+    }
+
+    Ok(FileIdentity {
+        path: path.to_string(),
+        size_bytes: metadata.len(),
+        modified: metadata.modified().ok().map(|t| DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Secs, true)),
+        sha256: hex(&hasher.finalize()),
+    })
+}
+
+/// SHA-256 of decoded samples as little-endian 32-bit floats: identifies
+/// the audio itself, whatever container or tags it came in
+pub fn samples_sha256(samples: &[f32]) -> String {
+    let mut hasher = Sha256::new();
+    for chunk in samples.chunks(1 << 16) {
+        let bytes: Vec<u8> = chunk.iter().flat_map(|s| s.to_le_bytes()).collect();
+        hasher.update(&bytes);
+    }
+    hex(&hasher.finalize())
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// `seconds` as h:mm:ss.mmm
+pub fn clock(seconds: f32) -> String {
+    let ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!("{}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+/// A scalar as table text: integers as they are, other numbers to a
+/// precision that suits their size
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "\u{2014}".to_string(),
+        Value::String(text) => escape(text),
+        Value::Number(n) if n.is_i64() || n.is_u64() => n.to_string(),
+        Value::Number(n) => {
+            let x = n.as_f64().unwrap_or(0.0);
+            if x == 0.0 || (1e-3..1e6).contains(&x.abs()) { format!("{:.4}", x).trim_end_matches('0').trim_end_matches('.').to_string() } else { format!("{:.3e}", x) }
+        }
+        other => other.to_string(),
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+fn label(key: &str) -> String {
+    let text = key.replace('_', " ");
+    let mut chars = text.chars();
+    chars.next().map_or(String::new(), |c| c.to_uppercase().chain(chars).collect())
+}
+
+/// Any JSON value as HTML: objects as two-column tables, arrays of objects
+/// as tables with a column per field, other arrays as lists
+fn render(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            out.push_str("<table class=\"fields\">");
+            for (key, value) in map.iter().filter(|(_, v)| !is_empty(v)) {
+                let _ = write!(out, "<tr><th>{}</th><td>", escape(&label(key)));
+                render(out, value);
+                out.push_str("</td></tr>");
+            }
+            out.push_str("</table>");
+        }
+        Value::Array(items) if items.iter().all(Value::is_object) => {
+            let mut columns: Vec<&String> = Vec::new();
+            for key in items.iter().filter_map(Value::as_object).flat_map(|map| map.keys()) {
+                if !columns.contains(&key) {
+                    columns.push(key);
+                }
+            }
+            out.push_str("<table class=\"rows\"><tr>");
+            columns.iter().for_each(|c| { let _ = write!(out, "<th>{}</th>", escape(&label(c))); });
+            out.push_str("</tr>");
+            for item in items.iter().take(MAX_ROWS) {
+                out.push_str("<tr>");
+                for column in &columns {
+                    match item.get(column.as_str()) {
+                        Some(cell @ (Value::Object(_) | Value::Array(_))) => {
+                            let text = cell.to_string();
+                            let short: String = text.chars().take(80).collect();
+                            let _ = write!(out, "<td class=\"nested\">{}{}</td>", escape(&short), if short.len() < text.len() { "\u{2026}" } else { "" });
+                        }
+                        cell => { let _ = write!(out, "<td>{}</td>", cell.map_or(String::new(), scalar)); }
+                    }
+                }
+                out.push_str("</tr>");
+            }
+            out.push_str("</table>");
+            if items.len() > MAX_ROWS {
+                let _ = write!(out, "<p class=\"note\">First {} of {} rows; the JSON report has them all.</p>", MAX_ROWS, items.len());
+            }
+        }
+        Value::Array(items) => {
+            let shown: Vec<String> = items.iter().take(MAX_ITEMS).map(scalar).collect();
+            out.push_str(&shown.join(", "));
+            if items.len() > MAX_ITEMS {
+                let _ = write!(out, " \u{2026} ({} in all)", items.len());
+            }
+        }
+        other => out.push_str(&scalar(other)),
+    }
+}
+
+/// A figure as inline SVG: the image or curve with its axes, and a line at
+/// each marker
+fn render_figure(out: &mut String, figure: &Figure, markers: &[Marker], kinds: &[&str]) {
+    let height = match &figure.content {
+        FigureContent::Raster(image) => (PLOT_WIDTH * image.height as f32 / image.width.max(1) as f32).round(),
+        FigureContent::Line(_) => 200.0,
+    };
+    let duration = figure.duration.max(1e-3);
+    let (low, high) = figure.y_range;
+    let x = |t: f32| MARGIN_LEFT + t / duration * PLOT_WIDTH;
+    let y = |v: f32| if high > low { (high - v) / (high - low) * height } else { height / 2.0 };
+
+    let _ = write!(out, "<figure><figcaption>{}</figcaption><svg viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">",
+        escape(&figure.title), MARGIN_LEFT + PLOT_WIDTH + 10.0, height + MARGIN_BOTTOM);
+    match &figure.content {
+        FigureContent::Raster(image) => {
+            let png = image.png().unwrap_or_default();
+            let _ = write!(out, "<image x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"/>",
+                MARGIN_LEFT, PLOT_WIDTH, height, STANDARD.encode(png));
+        }
+        FigureContent::Line(points) => {
+            let path: Vec<String> = points.iter().map(|&(t, v)| format!("{:.1},{:.1}", x(t), y(v))).collect();
+            let _ = write!(out, "<polyline fill=\"none\" stroke=\"#28509a\" stroke-width=\"1.2\" points=\"{}\"/>", path.join(" "));
+        }
+    }
+    let _ = write!(out, "<rect x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#444\"/>", MARGIN_LEFT, PLOT_WIDTH, height);
+
+    // Seconds on short recordings, minutes and seconds on longer ones
+    let time_label = |t: f32| if duration < 60.0 { format!("{} s", scalar(&Value::from(t as f64))) } else { format!("{}:{:02}", t as u64 / 60, t as u64 % 60) };
+    for t in ticks(0.0, duration, 10) {
+        let _ = write!(out, "<line x1=\"{0:.1}\" x2=\"{0:.1}\" y1=\"{1}\" y2=\"{2}\" stroke=\"#444\"/><text x=\"{0:.1}\" y=\"{3}\" text-anchor=\"middle\">{4}</text>",
+            x(t), height, height + 4.0, height + 18.0, time_label(t));
+    }
+    for v in ticks(low, high, 4) {
+        let _ = write!(out, "<line x1=\"{0}\" x2=\"{1}\" y1=\"{2:.1}\" y2=\"{2:.1}\" stroke=\"#444\"/><text x=\"{3}\" y=\"{2:.1}\" text-anchor=\"end\" dominant-baseline=\"middle\">{4} {5}</text>",
+            MARGIN_LEFT - 4.0, MARGIN_LEFT, y(v), MARGIN_LEFT - 6.0, scalar(&Value::from(v as f64)), figure.y_unit);
+    }
+    for marker in markers.iter().filter(|m| (0.0..=duration).contains(&m.time)) {
+        let color = MARKER_COLORS[kinds.iter().position(|k| *k == marker.kind).unwrap_or(0) % MARKER_COLORS.len()];
+        let _ = write!(out, "<line x1=\"{0:.1}\" x2=\"{0:.1}\" y1=\"0\" y2=\"{1}\" stroke=\"{2}\" stroke-width=\"1.5\" stroke-dasharray=\"4 3\"><title>{3} at {4}: {5}</title></line>",
+            x(marker.time), height, color, marker.kind, clock(marker.time), escape(&marker.detail));
+    }
+    out.push_str("</svg></figure>");
+}
+
+/// The report as one self-contained HTML document: evidence identity,
+/// figures with the findings marked, the findings listed, every result and
+/// the analysis settings. `report` is the JSON report.
+pub fn html(report: &Value, figures: &[Figure], markers: &[Marker]) -> String {
+    let mut kinds: Vec<&str> = Vec::new();
+    for marker in markers {
+        if !kinds.contains(&marker.kind) {
+            kinds.push(marker.kind);
+        }
+    }
+    let mut out = String::from(concat!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Forensic audio report</title><style>",
+        "body{font:13px/1.4 sans-serif;color:#222;max-width:1100px;margin:2em auto;padding:0 1em}",
+        "h1{font-size:22px}h2{font-size:17px;border-bottom:1px solid #999;margin-top:2em}h3{font-size:14px}",
+        "table{border-collapse:collapse;margin:.5em 0}th,td{border:1px solid #ccc;padding:2px 6px;text-align:left;vertical-align:top}",
+        "table.fields>tbody>tr>th,table.fields>tr>th{background:#f2f2f2;white-space:nowrap}table.rows th{background:#e6e6e6}",
+        "td.nested,.hash{font-family:monospace;font-size:11px;word-break:break-all}.note{color:#666;font-style:italic}",
+        "figure{margin:1em 0;break-inside:avoid}figcaption{font-weight:bold;margin-bottom:.3em}svg{width:100%;font:11px sans-serif}",
+        ".key{display:inline-block;width:1em;height:.6em;margin:0 .3em 0 1em}",
+        "@page{size:A4;margin:15mm}@media print{body{margin:0;max-width:none}h2{break-after:avoid}}",
+        "</style></head><body><h1>Forensic audio report</h1>"));
+
+    let _ = write!(out, "<p>Generated {} by {} {} (report schema {}).</p>",
+        scalar(&report["generated_at"]), scalar(&report["tool"]["name"]), scalar(&report["tool"]["version"]), scalar(&report["schema_version"]));
+
+    out.push_str("<h2>Evidence</h2>");
+    match &report["file"] {
+        Value::Object(file) => {
+            let _ = write!(out, "<table class=\"fields\"><tr><th>File</th><td>{}</td></tr><tr><th>Size</th><td>{} bytes</td></tr><tr><th>Modified</th><td>{}</td></tr><tr><th>SHA-256 (file)</th><td class=\"hash\">{}</td></tr>",
+                scalar(&file["path"]), scalar(&file["size_bytes"]), scalar(&file["modified"]), scalar(&file["sha256"]));
+        }
+        _ => out.push_str("<table class=\"fields\"><tr><th>File</th><td>Generated audio, no source file</td></tr>"),
+    }
+    let _ = write!(out, "<tr><th>SHA-256 (decoded samples)</th><td class=\"hash\">{}</td></tr>", scalar(&report["samples_sha256"]));
+    match report["evidence_log"].as_array().filter(|log| !log.is_empty()) {
+        Some(log) => {
+            for event in log {
+                let switch = if event["enabled"].as_bool() == Some(true) { "on" } else { "off" };
+                let _ = write!(out, "<tr><th>Evidence mode {}</th><td>{} (working directory {})</td></tr>",
+                    switch, scalar(&event["at"]), scalar(&event["working_dir"]));
+            }
+        }
+        None => out.push_str("<tr><th>Evidence mode</th><td>Not used this run</td></tr>"),
+    }
+    out.push_str("</table>");
+    render(&mut out, &report["audio"]);
+
+    if !figures.is_empty() {
+        out.push_str("<h2>Figures</h2>");
+        if !kinds.is_empty() {
+            out.push_str("<p>Marked:");
+            for (i, kind) in kinds.iter().enumerate() {
+                let _ = write!(out, "<span class=\"key\" style=\"background:{}\"></span>{}", MARKER_COLORS[i % MARKER_COLORS.len()], kind);
+            }
+            out.push_str("</p>");
+        }
+        figures.iter().for_each(|figure| render_figure(&mut out, figure, markers, &kinds));
+    }
+
+    out.push_str("<h2>Findings</h2>");
+    if markers.is_empty() {
+        out.push_str("<p>No time-located findings.</p>");
+    } else {
+        out.push_str("<table class=\"rows\"><tr><th>Time</th><th>Finding</th><th>Detail</th></tr>");
+        let mut sorted: Vec<&Marker> = markers.iter().collect();
+        sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for marker in sorted.iter().take(MAX_ROWS) {
+            let _ = write!(out, "<tr><td>{}</td><td>{}</td><td>{}</td></tr>", clock(marker.time), marker.kind, escape(&marker.detail));
+        }
+        out.push_str("</table>");
+        if markers.len() > MAX_ROWS {
+            let _ = write!(out, "<p class=\"note\">First {} of {} findings.</p>", MAX_ROWS, markers.len());
+        }
+    }
+
+    out.push_str("<h2>Measurements</h2>");
+    if let Value::Object(results) = &report["results"] {
+        let (scalars, sections): (Vec<_>, Vec<_>) = results.iter()
+            .filter(|(_, v)| !is_empty(v))
+            .partition(|(_, v)| !v.is_object() && !v.is_array());
+        out.push_str("<table class=\"fields\">");
+        for (key, value) in scalars {
+            let _ = write!(out, "<tr><th>{}</th><td>{}</td></tr>", escape(&label(key)), scalar(value));
+        }
+        out.push_str("</table>");
+        for (key, value) in sections {
+            let _ = write!(out, "<h3>{}</h3>", escape(&label(key)));
+            render(&mut out, value);
+        }
+    }
+
+    out.push_str("<h2>Analysis settings</h2>");
+    render(&mut out, &report["parameters"]);
+    out.push_str("</body></html>\n");
+    out
+}
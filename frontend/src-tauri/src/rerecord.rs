@@ -0,0 +1,174 @@
+//! Re-recording detection: a playback captured again through a microphone
+
+use crate::reverb;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Spectrum frames for the band limits and room tone
+const SPECTRUM_FFT: usize = 4096;
+const SPECTRUM_HOP: usize = 2048;
+/// Third-octave bands from 63 Hz up
+const N_THIRDS: usize = 22;
+/// Bands decaying faster than this are too quick for their shape to show
+/// through the envelope smoothing (seconds)
+const MIN_DECAY_SECS: f32 = 0.15;
+/// Least level range between pauses and speech to judge the speech band (dB)
+const MIN_SPEECH_RANGE_DB: f32 = 12.0;
+/// Speech excess over the pauses, below its median over the bands, that
+/// still counts as passed by the recording chain (dB)
+const PASSBAND_DROP_DB: f32 = 20.0;
+/// Least lead-in/out material to compare, and margin left for the decay of
+/// the speech into it (seconds)
+const MIN_TONE_SECS: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerecordReport {
+    pub confidence: f32,                // 0..1, mean of the indicator scores below
+    pub likely: bool,                   // Confidence of at least 0.5
+    pub decays: usize,                  // Free decays measured
+    pub decay_time_s: Option<f32>,      // Mid-band RT60
+    pub decay_shape: Option<f32>,       // Median over the bands of EDT / RT60
+    pub speech_low_hz: Option<f32>,     // Lowest third octave the speech reaches (within 20 dB of its typical excess over the pauses)
+    pub speech_high_hz: Option<f32>,    // Highest such band
+    pub tone_step_db: Option<f32>,      // Pause level during the speech over lead-in/out silence
+}
+
+/// Power (dB) of each frame in each band `(lo, hi)` (Hz)
+pub fn band_frames(samples: &[f32], sample_rate: u32, n_fft: usize, hop: usize, bands: &[(f32, f32)]) -> Vec<Vec<f32>> {
+    let window = WindowType::BlackmanHarris.coefficients(n_fft);
+    let bin_hz = sample_rate as f32 / n_fft as f32;
+    let bins: Vec<(usize, usize)> = bands
+        .iter()
+        .map(|&(lo, hi)| {
+            let lo = ((lo / bin_hz).round() as usize).clamp(1, n_fft / 2);
+            (lo, ((hi / bin_hz).round() as usize).clamp(lo + 1, n_fft / 2 + 1))
+        })
+        .collect();
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * hop)
+        .take_while(|&start| start + n_fft <= samples.len())
+        .collect();
+
+    starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(n_fft);
+            let mut input: Vec<f32> = samples[start..start + n_fft].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            bins.iter()
+                .map(|&(lo, hi)| 10.0 * spectrum[lo..hi].iter().map(|c| c.norm_sqr()).sum::<f32>().max(1e-20).log10())
+                .collect()
+        })
+        .collect()
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted[((p * (sorted.len() - 1) as f32) as usize).min(sorted.len() - 1)]
+}
+
+/// Look for signs that the audio is a microphone capture of a playback:
+/// decays that start rounded, as a second room's reverberation does on top
+/// of the first; speech that stops short of the low frequencies (a small
+/// loudspeaker's low cut); and pauses within the speech that sit above the
+/// lead-in and lead-out silence (the original room tone played back over
+/// the new one). Each indicator is scored 0..1 and the confidence is their
+/// mean. This is a screening aid: heavily processed or synthetic speech can
+/// show the same traits.
+pub fn detect_rerecording(samples: &[f32], sample_rate: u32) -> RerecordReport {
+    let sr = sample_rate as f32;
+    let mut report = RerecordReport {
+        confidence: 0.0,
+        likely: false,
+        decays: 0,
+        decay_time_s: None,
+        decay_shape: None,
+        speech_low_hz: None,
+        speech_high_hz: None,
+        tone_step_db: None,
+    };
+    if samples.len() < 2 * SPECTRUM_FFT {
+        return report;
+    }
+    let mut scores = Vec::new();
+
+    // Decay shape per octave band
+    let reverb = reverb::estimate(samples, sample_rate);
+    report.decays = reverb.decays;
+    report.decay_time_s = reverb.rt60_s;
+    let shapes: Vec<f32> = reverb
+        .bands
+        .iter()
+        .filter_map(|b| Some(b.edt_s? / b.rt60_s.filter(|&rt| rt >= MIN_DECAY_SECS)?))
+        .collect();
+    if !shapes.is_empty() {
+        let shape = percentile(&shapes, 0.5);
+        report.decay_shape = Some(shape);
+        // The direct sound dies with the source, so a single room's early
+        // decay is about as fast as its late decay; a second room adds its
+        // own reverberation to the direct sound and rounds the start
+        scores.push(((shape - 1.0) / 0.4).clamp(0.0, 1.0));
+    }
+
+    // Speech band against the pauses, per third octave
+    let thirds: Vec<(f32, f32)> = (0..N_THIRDS)
+        .map(|i| 63.0 * 2f32.powf(i as f32 / 3.0))
+        .filter(|&f| f * 2f32.powf(1.0 / 6.0) < sr / 2.0)
+        .map(|f| (f / 2f32.powf(1.0 / 6.0), f * 2f32.powf(1.0 / 6.0)))
+        .collect();
+    let frames = band_frames(samples, sample_rate, SPECTRUM_FFT, SPECTRUM_HOP, &thirds);
+    let totals: Vec<f32> = frames
+        .iter()
+        .map(|f| 10.0 * f.iter().map(|l| 10f32.powf(l / 10.0)).sum::<f32>().log10())
+        .collect();
+    let quiet = percentile(&totals, 0.1);
+    let loud = percentile(&totals, 0.7);
+    if loud - quiet >= MIN_SPEECH_RANGE_DB {
+        let mean_band = |b: usize, keep: &dyn Fn(f32) -> bool| {
+            let levels: Vec<f32> = frames.iter().zip(&totals).filter(|(_, &t)| keep(t)).map(|(f, _)| f[b]).collect();
+            levels.iter().sum::<f32>() / levels.len().max(1) as f32
+        };
+        let excess: Vec<f32> = (0..thirds.len())
+            .map(|b| mean_band(b, &|t| t >= loud) - mean_band(b, &|t| t <= quiet))
+            .collect();
+        let typical = percentile(&excess, 0.5);
+        let passed: Vec<usize> = (0..thirds.len()).filter(|&b| excess[b] >= typical - PASSBAND_DROP_DB).collect();
+        let center = |b: usize| (thirds[b].0 * thirds[b].1).sqrt();
+        report.speech_low_hz = passed.first().map(|&b| center(b));
+        report.speech_high_hz = passed.last().map(|&b| center(b));
+        // Voices reach down to 100-200 Hz; small loudspeakers stop near 300 Hz
+        if let Some(low) = report.speech_low_hz {
+            scores.push(((low - 150.0) / 200.0).clamp(0.0, 1.0));
+        }
+
+        // Room tone: the quietest frames between the first and last speech
+        // frame against the frames before and after, less the decay of the
+        // speech into them
+        let active: Vec<usize> = (0..totals.len()).filter(|&i| totals[i] >= loud).collect();
+        if let (Some(&first), Some(&last)) = (active.first(), active.last()) {
+            let margin = (MIN_TONE_SECS * sr / SPECTRUM_HOP as f32) as usize;
+            let outside: Vec<f32> = totals[..first.saturating_sub(margin)]
+                .iter()
+                .chain(totals.get(last + 1 + margin..).unwrap_or_default())
+                .copied()
+                .collect();
+            if outside.len() >= margin && last - first >= 10 * margin {
+                let inside = percentile(&totals[first..=last], 0.02);
+                let step = inside - percentile(&outside, 0.5);
+                report.tone_step_db = Some(step);
+                scores.push((step / 6.0).clamp(0.0, 1.0));
+            }
+        }
+    }
+
+    if !scores.is_empty() {
+        report.confidence = scores.iter().sum::<f32>() / scores.len() as f32;
+        report.likely = report.confidence >= 0.5;
+    }
+    report
+}
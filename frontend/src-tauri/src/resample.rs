@@ -0,0 +1,249 @@
+//! Resampling history: upsampled sources and converter artifacts
+
+use crate::compression;
+use crate::spectrum::{self, WindowType};
+use serde::{Deserialize, Serialize};
+
+/// Long-term spectrum segment length (samples). The spectrum uses a
+/// Blackman-Harris window: Hann leakage from the passband would fill the
+/// stopband a good resampler leaves.
+const LTAS_SEGMENT: usize = 8192;
+/// Lowest band limit searched for (Hz); telephone audio ends near 3.4 kHz
+const MIN_CUTOFF_HZ: f32 = 3000.0;
+/// Rates audio is commonly recorded or distributed at
+const STANDARD_RATES: [u32; 12] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000, 176400];
+/// Range of cutoffs, as a fraction of a rate's Nyquist, that resampling
+/// filters (and the source's own converter) leave
+const PASSBAND_RANGE: (f32, f32) = (0.8, 1.01);
+/// A converter at the file's own rate passes at least this fraction of its Nyquist
+const CONVERTER_BANDWIDTH: f32 = 0.9;
+/// Stopband depth (dB under the passband) an image-free interpolation leaves
+const MIN_STOPBAND_DB: f32 = 30.0;
+/// Reference band widths around the cutoff (Hz)
+const EDGE_GAP_HZ: f32 = 100.0;
+const EDGE_SPAN_HZ: f32 = 500.0;
+/// Most audio examined for converter artifacts (seconds)
+const MAX_ARTIFACT_SECS: f32 = 60.0;
+/// Spectrogram frame for the image search
+const IMAGE_FRAME: usize = 2048;
+/// Mirror correlation that marks spectral images
+const MIN_MIRROR_CORRELATION: f32 = 0.5;
+/// Window over which the interpolation residual is normalized (samples)
+const RESIDUAL_WINDOW: usize = 64;
+/// Segment length of the residual spectrum
+const PATTERN_SEGMENT: usize = 8192;
+/// Height of an interpolation line over the residual spectrum around it (dB)
+const MIN_PATTERN_DB: f32 = 10.0;
+/// Level within the top 2% of the band, relative to 80-90% of Nyquist,
+/// above which no anti-alias filter rolled the spectrum off (dB)
+const MAX_ROLLOFF_DB: f32 = -3.0;
+/// The top band must clear 16-bit quantization noise by this much to count
+/// as content rather than a flat noise floor (dB)
+const CONTENT_MARGIN_DB: f32 = 20.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsamplingReport {
+    pub upsampled: bool,
+    pub original_rate: Option<u32>,    // Lowest standard rate whose Nyquist the band limit fits under
+    pub cutoff_hz: Option<f32>,        // -6 dB point of the band limit
+    pub passband_ratio: Option<f32>,   // Cutoff as a fraction of the original Nyquist
+    pub transition_hz: Option<f32>,    // Width of the interpolation filter's transition band
+    pub stopband_db: f32,              // Level above the cutoff, relative to the band below it
+}
+
+/// Look for audio that was upsampled from a lower rate: a sharp band limit
+/// just under a standard rate's Nyquist with nothing above it up to the
+/// file's own Nyquist. The width of the transition band tells a steep
+/// polyphase resampler from a gentle one. A lossy encoder's lowpass that
+/// happens to fall under a standard Nyquist (16 kHz for 32 kHz) reads the
+/// same; compare with the compression analysis.
+pub fn detect_upsampling(samples: &[f32], sample_rate: u32) -> UpsamplingReport {
+    let mut report = UpsamplingReport {
+        upsampled: false,
+        original_rate: None,
+        cutoff_hz: None,
+        passband_ratio: None,
+        transition_hz: None,
+        stopband_db: 0.0,
+    };
+    let psd = spectrum::welch_psd(samples, sample_rate, LTAS_SEGMENT, LTAS_SEGMENT / 2, WindowType::BlackmanHarris);
+    if psd.segments == 0 {
+        return report;
+    }
+    let db: Vec<f32> = psd.power.iter().map(|p| 10.0 * p.max(1e-20).log10()).collect();
+    let bin_hz = sample_rate as f32 / LTAS_SEGMENT as f32;
+    let Some((crossing, _)) = compression::band_edge(&db, bin_hz, MIN_CUTOFF_HZ) else {
+        return report;
+    };
+    let cutoff = crossing as f32 * bin_hz;
+    report.cutoff_hz = Some(cutoff);
+
+    let bins = |hz: f32| (hz / bin_hz).round() as usize;
+    let (gap, span) = (bins(EDGE_GAP_HZ).max(1), bins(EDGE_SPAN_HZ).max(2));
+    let pass = compression::mean_db(&db, crossing.saturating_sub(gap + span), crossing.saturating_sub(gap));
+    let stop = compression::mean_db(&db, crossing + span, db.len());
+    if !stop.is_finite() {
+        return report;
+    }
+    report.stopband_db = stop - pass;
+
+    // Transition band: from the last bin within 3 dB of the passband to the
+    // first within 3 dB of the stopband
+    let start = (0..=crossing).rev().find(|&k| db[k] >= pass - 3.0).unwrap_or(crossing);
+    let end = (crossing..db.len()).find(|&k| db[k] <= stop + 3.0).unwrap_or(crossing);
+    report.transition_hz = Some((end - start) as f32 * bin_hz);
+
+    let original = STANDARD_RATES.iter().copied().filter(|&rate| rate < sample_rate).find(|&rate| {
+        let ratio = cutoff / (rate as f32 / 2.0);
+        (PASSBAND_RANGE.0..=PASSBAND_RANGE.1).contains(&ratio)
+    });
+    report.original_rate = original;
+    report.passband_ratio = original.map(|rate| cutoff / (rate as f32 / 2.0));
+    report.upsampled = original.is_some()
+        && report.stopband_db <= -MIN_STOPBAND_DB
+        && cutoff < CONVERTER_BANDWIDTH * sample_rate as f32 / 2.0;
+    report
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SrcArtifactReport {
+    pub resampled: bool,
+    pub imaging_rate: Option<u32>,       // Rate whose Nyquist the spectrum mirrors around
+    pub mirror_correlation: f32,         // Frame-wise correlation of the spectrum either side of it
+    pub image_level_db: f32,             // Images relative to the band they mirror
+    pub pattern_rate: Option<u32>,       // Rate the periodic interpolation pattern points to
+    pub pattern_hz: Option<f32>,         // Frequency of that pattern in the residual
+    pub pattern_db: f32,                 // Pattern line over the residual spectrum around it
+    pub nyquist_rolloff_db: f32,         // Top 2% of the band relative to 80-90% of Nyquist
+    pub aliasing: bool,                  // Full level up to Nyquist: no anti-alias filter
+}
+
+/// Frame-wise correlation of the dB spectrum below `mirror` (bin) with the
+/// spectrum above it, over `width` bins either side, and the mean level
+/// above relative to below. Frames without content below are skipped.
+fn mirror_similarity(frames: &[Vec<f32>], mirror: usize, width: usize) -> (f32, f32) {
+    let (mut correlation, mut level, mut judged) = (0.0f32, 0.0f32, 0);
+    for frame in frames {
+        let below: Vec<f32> = (1..=width).map(|j| frame[mirror - j]).collect();
+        let above: Vec<f32> = (1..=width).map(|j| frame[mirror + j]).collect();
+        let mean_below = below.iter().sum::<f32>() / width as f32;
+        let mean_above = above.iter().sum::<f32>() / width as f32;
+        let (mut ab, mut aa, mut bb) = (0.0f32, 0.0f32, 0.0f32);
+        for (b, a) in below.iter().zip(&above) {
+            let (b, a) = (b - mean_below, a - mean_above);
+            ab += a * b;
+            aa += a * a;
+            bb += b * b;
+        }
+        if aa <= 0.0 || bb <= 0.0 || mean_below < -150.0 {
+            continue;
+        }
+        correlation += ab / (aa * bb).sqrt();
+        level += mean_above - mean_below;
+        judged += 1;
+    }
+    if judged == 0 {
+        return (0.0, 0.0);
+    }
+    (correlation / judged as f32, level / judged as f32)
+}
+
+/// Frequency (Hz) at which interpolating from `from` Hz to `to` Hz repeats
+/// its pattern of filter phases, folded into the output band; zero for
+/// integer decimation
+fn pattern_frequency(from: u32, to: u32) -> f32 {
+    let f = from % to;
+    f.min(to - f) as f32
+}
+
+/// Look for the traces a sample-rate converter leaves: spectral images of
+/// the band below a lower rate's Nyquist mirrored above it (upsampling
+/// without a proper interpolation filter), a periodic pattern in the
+/// second-difference residual at the line a conversion from a standard
+/// rate predicts (interpolating resamplers, after Kirchner), and a
+/// spectrum that runs at full level into Nyquist (decimation without an
+/// anti-alias filter). Good polyphase converters leave none of these;
+/// see `detect_upsampling` for the band limit they do leave.
+pub fn detect_src_artifacts(samples: &[f32], sample_rate: u32) -> SrcArtifactReport {
+    let sr = sample_rate as f32;
+    let nyquist = sr / 2.0;
+    let samples = &samples[..samples.len().min((MAX_ARTIFACT_SECS * sr) as usize)];
+    let mut report = SrcArtifactReport {
+        resampled: false,
+        imaging_rate: None,
+        mirror_correlation: 0.0,
+        image_level_db: 0.0,
+        pattern_rate: None,
+        pattern_hz: None,
+        pattern_db: 0.0,
+        nyquist_rolloff_db: 0.0,
+        aliasing: false,
+    };
+    if samples.len() < 2 * PATTERN_SEGMENT {
+        return report;
+    }
+
+    // Images: the strongest mirror around a lower standard Nyquist
+    let (_, frames) = spectrum::spectrogram_db(samples, sample_rate, IMAGE_FRAME, IMAGE_FRAME, WindowType::BlackmanHarris, nyquist + 1.0);
+    let bin_hz = sr / IMAGE_FRAME as f32;
+    for rate in STANDARD_RATES.iter().copied().filter(|&rate| rate < sample_rate) {
+        let mirror = ((rate as f32 / 2.0) / bin_hz).round() as usize;
+        let width = ((0.2 * rate as f32 / 2.0).min(nyquist - rate as f32 / 2.0) / bin_hz) as usize;
+        if width < (EDGE_SPAN_HZ / bin_hz) as usize || mirror + width >= IMAGE_FRAME / 2 {
+            continue;
+        }
+        let (correlation, level) = mirror_similarity(&frames, mirror, width);
+        if correlation > report.mirror_correlation {
+            report.mirror_correlation = correlation;
+            report.image_level_db = level;
+            report.imaging_rate = Some(rate).filter(|_| correlation >= MIN_MIRROR_CORRELATION);
+        }
+    }
+
+    // Interpolation pattern: second-difference residual, normalized by its
+    // local power so loud passages do not dominate
+    let residual: Vec<f32> = samples.windows(3).map(|w| w[1] - 0.5 * (w[0] + w[2])).collect();
+    let power: Vec<f32> = residual.iter().map(|e| e * e).collect();
+    let mut local = vec![0.0f32; power.len()];
+    let mut running = 0.0f64;
+    for (i, &p) in power.iter().enumerate() {
+        running += p as f64;
+        if i >= RESIDUAL_WINDOW {
+            running -= power[i - RESIDUAL_WINDOW] as f64;
+        }
+        local[i] = (running / RESIDUAL_WINDOW.min(i + 1) as f64) as f32;
+    }
+    let normalized: Vec<f32> = power.iter().zip(&local).map(|(&p, &l)| if l > 1e-20 { p / l } else { 0.0 }).collect();
+    let psd = spectrum::welch_psd(&normalized, sample_rate, PATTERN_SEGMENT, PATTERN_SEGMENT / 2, WindowType::BlackmanHarris);
+    let pattern_bin_hz = sr / PATTERN_SEGMENT as f32;
+    let db: Vec<f32> = psd.power.iter().map(|p| 10.0 * p.max(1e-30).log10()).collect();
+    for rate in STANDARD_RATES.iter().copied().filter(|&rate| rate != sample_rate) {
+        let f = pattern_frequency(rate, sample_rate);
+        let k = (f / pattern_bin_hz).round() as usize;
+        if k < 60 || k + 60 >= db.len() {
+            continue;
+        }
+        let line = db[k - 2..=k + 2].iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let mut around: Vec<f32> = db[k - 50..k - 5].iter().chain(&db[k + 6..k + 51]).copied().collect();
+        let mid = around.len() / 2;
+        let (_, &mut median, _) = around.select_nth_unstable_by(mid, f32::total_cmp);
+        if line - median > report.pattern_db {
+            report.pattern_db = line - median;
+            report.pattern_hz = Some(f);
+            report.pattern_rate = Some(rate).filter(|_| line - median >= MIN_PATTERN_DB);
+        }
+    }
+
+    // Aliasing: no rolloff into Nyquist
+    let ltas = spectrum::welch_psd(samples, sample_rate, LTAS_SEGMENT, LTAS_SEGMENT / 2, WindowType::BlackmanHarris);
+    let ltas_db: Vec<f32> = ltas.power.iter().map(|p| 10.0 * p.max(1e-20).log10()).collect();
+    let n = ltas_db.len();
+    let top = compression::mean_db(&ltas_db, n * 98 / 100, n - 1);
+    let reference = compression::mean_db(&ltas_db, n * 8 / 10, n * 9 / 10);
+    let quantization_db = 10.0 * (2f32.powi(-30) / 12.0 / nyquist).log10();
+    report.nyquist_rolloff_db = top - reference;
+    report.aliasing = report.nyquist_rolloff_db > MAX_ROLLOFF_DB && top > quantization_db + CONTENT_MARGIN_DB;
+
+    report.resampled = report.imaging_rate.is_some() || report.pattern_rate.is_some() || report.aliasing;
+    report
+}
@@ -0,0 +1,154 @@
+//! Blind reverberation time (RT60/EDT) estimation from free decays
+
+use crate::rerecord;
+use serde::Serialize;
+
+/// Envelope frames for the decays (short, to resolve them)
+const DECAY_FFT: usize = 1024;
+const DECAY_HOP: usize = 128;
+/// Octave bands whose decays are measured (center frequencies, Hz)
+const DECAY_BANDS: [f32; 7] = [125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0];
+/// Bands averaged for the single-figure values (Hz)
+const MID_BANDS: [f32; 2] = [500.0, 1000.0];
+/// Envelope smoothing before the decays are followed (seconds)
+const SMOOTH_SECS: f32 = 0.02;
+/// A decay starts at least this far above the band's floor, may rise this
+/// much above its lowest point so far, and is fitted once it has fallen
+/// this far (dB)
+const DECAY_START_DB: f32 = 25.0;
+const DECAY_RIPPLE_DB: f32 = 3.0;
+const MIN_FALL_DB: f32 = 15.0;
+/// Levels within this much of the peak are still the sustained source (dB)
+const PLATEAU_DB: f32 = 1.0;
+/// The fit stops this far above the floor (dB)
+const FLOOR_MARGIN_DB: f32 = 5.0;
+/// Least decays for a band's values
+const MIN_DECAYS: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BandReverb {
+    pub center_hz: f32,
+    pub decays: usize,              // Decay phases measured in the band
+    pub rt60_s: Option<f32>,        // Median over the decays
+    pub edt_s: Option<f32>,         // Median early decay time
+    pub rt60_iqr_s: Option<f32>,    // Interquartile range of the RT60s
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReverbReport {
+    pub bands: Vec<BandReverb>,
+    pub rt60_s: Option<f32>,        // Mean of the 500 Hz and 1 kHz bands
+    pub edt_s: Option<f32>,
+    pub decays: usize,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted[((p * (sorted.len() - 1) as f32) as usize).min(sorted.len() - 1)]
+}
+
+/// Slope of a least-squares line through `levels` (per frame)
+fn fitted_slope(levels: &[f32]) -> f32 {
+    let n = levels.len() as f32;
+    let mx = (n - 1.0) / 2.0;
+    let my = levels.iter().sum::<f32>() / n;
+    let (num, den) = levels.iter().enumerate().fold((0.0, 0.0), |(num, den), (i, &y)| {
+        let dx = i as f32 - mx;
+        (num + dx * (y - my), den + dx * dx)
+    });
+    num / den
+}
+
+/// Decay phases in one band's level envelope, as (RT60, EDT) in seconds.
+/// From each peak well above the floor the smoothed envelope is followed
+/// while it keeps falling (within the ripple noise-like tails show); a
+/// phase that falls far enough gets a regression line over its whole
+/// length for the RT60, and the time its first 10 dB take for the EDT,
+/// both extrapolated to 60 dB.
+fn decay_phases(levels: &[f32], hop_secs: f32) -> Vec<(f32, f32)> {
+    let half = ((SMOOTH_SECS / hop_secs) as usize / 2).max(1);
+    let power: Vec<f32> = levels.iter().map(|l| 10f32.powf(l / 10.0)).collect();
+    let smooth: Vec<f32> = (0..power.len())
+        .map(|i| {
+            let window = &power[i.saturating_sub(half)..(i + half + 1).min(power.len())];
+            10.0 * (window.iter().sum::<f32>() / window.len() as f32).max(1e-20).log10()
+        })
+        .collect();
+    if smooth.len() < 3 {
+        return Vec::new();
+    }
+    let floor = percentile(&smooth, 0.1);
+    let mut phases = Vec::new();
+    let mut t = 1;
+    while t + 1 < smooth.len() {
+        let peak = smooth[t];
+        if peak < floor + DECAY_START_DB || peak < smooth[t - 1] || peak < smooth[t + 1] {
+            t += 1;
+            continue;
+        }
+        let mut low = peak;
+        let mut end = t + 1;
+        while end < smooth.len() && smooth[end] <= low + DECAY_RIPPLE_DB && smooth[end] > floor + FLOOR_MARGIN_DB {
+            low = low.min(smooth[end]);
+            end += 1;
+        }
+        if peak - low >= MIN_FALL_DB {
+            // From where the envelope last leaves the peak (the source
+            // stopping) to its lowest point, so a sustained note before the
+            // fall and a rise at the end are left out
+            let last = (t..end).fold(t, |best, i| if smooth[i] < smooth[best] { i } else { best });
+            let start = (t..=last).rev().find(|&i| smooth[i] >= peak - PLATEAU_DB).unwrap_or(t);
+            let early = (start..=last).find(|&i| smooth[i] <= peak - 10.0).unwrap_or(last) - start;
+            let slope = fitted_slope(&smooth[start..=last]);
+            if last > start + 1 && slope < 0.0 {
+                phases.push((-60.0 * hop_secs / slope, 6.0 * early as f32 * hop_secs));
+            }
+        }
+        t = end.max(t + 1);
+    }
+    phases
+}
+
+/// Estimate the reverberation time per octave band without a test signal.
+/// Speech and music stop often enough for the room's own decay to show:
+/// each band's envelope is searched for falls after the source stops, and
+/// the median of their slopes taken. Sources that fade out rather than stop
+/// lengthen the estimate, so it is an upper bound on material without
+/// abrupt stops; background noise limits the measurable range to about
+/// 20 dB, so the RT60 is extrapolated from that much.
+pub fn estimate(samples: &[f32], sample_rate: u32) -> ReverbReport {
+    let sr = sample_rate as f32;
+    let centers: Vec<f32> = DECAY_BANDS.iter().copied().filter(|&f| f * 2f32.sqrt() < sr / 2.0).collect();
+    let octaves: Vec<(f32, f32)> = centers.iter().map(|&f| (f / 2f32.sqrt(), f * 2f32.sqrt())).collect();
+    let frames = rerecord::band_frames(samples, sample_rate, DECAY_FFT, DECAY_HOP, &octaves);
+    let hop_secs = DECAY_HOP as f32 / sr;
+
+    let bands: Vec<BandReverb> = centers
+        .iter()
+        .enumerate()
+        .map(|(b, &center_hz)| {
+            let levels: Vec<f32> = frames.iter().map(|f| f[b]).collect();
+            let phases = decay_phases(&levels, hop_secs);
+            let mut band = BandReverb { center_hz, decays: phases.len(), rt60_s: None, edt_s: None, rt60_iqr_s: None };
+            if phases.len() >= MIN_DECAYS {
+                let (late, early): (Vec<f32>, Vec<f32>) = phases.into_iter().unzip();
+                band.rt60_s = Some(percentile(&late, 0.5));
+                band.edt_s = Some(percentile(&early, 0.5));
+                band.rt60_iqr_s = Some(percentile(&late, 0.75) - percentile(&late, 0.25));
+            }
+            band
+        })
+        .collect();
+
+    let mid = |value: fn(&BandReverb) -> Option<f32>| {
+        let values: Vec<f32> = bands.iter().filter(|b| MID_BANDS.contains(&b.center_hz)).filter_map(value).collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+    };
+    ReverbReport {
+        rt60_s: mid(|b| b.rt60_s),
+        edt_s: mid(|b| b.edt_s),
+        decays: bands.iter().map(|b| b.decays).sum(),
+        bands,
+    }
+}
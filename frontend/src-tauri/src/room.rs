@@ -0,0 +1,200 @@
+//! Acoustic environment profiling and matching between recordings
+
+use crate::rerecord;
+use crate::reverb;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::Serialize;
+
+/// Frames for the cepstrum and the long-term spectrum
+const SPECTRUM_FFT: usize = 2048;
+const SPECTRUM_HOP: usize = 1024;
+/// Third-octave range of the long-term spectrum (Hz)
+const LTAS_RANGE: (f32, f32) = (100.0, 8000.0);
+/// Delays searched for early reflections (seconds)
+const REFLECTION_RANGE: (f32, f32) = (0.001, 0.02);
+/// Frames within this much of the loudest are active (dB)
+const ACTIVE_RANGE_DB: f32 = 30.0;
+/// Decay time ratio, reflection correlation and LTAS distance (dB) at
+/// which each similarity falls to zero
+const DECAY_RATIO_LIMIT: f32 = 2.0;
+const LTAS_LIMIT_DB: f32 = 10.0;
+/// Weights of the decay, reflection and LTAS similarities
+const WEIGHTS: [f32; 3] = [0.5, 0.2, 0.3];
+/// Overall similarity from which two recordings are consistent with one
+/// room, and below which they are not
+const SAME_ROOM: f32 = 0.7;
+const OTHER_ROOM: f32 = 0.4;
+
+const CAVEAT: &str = "Room acoustics change with source and microphone position, and the spectrum also \
+follows the talker and the recording chain. A high similarity supports a common room; a low one argues \
+against it only when the material is comparable (similar speech, distance and equipment).";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomProfile {
+    pub reverb: reverb::ReverbReport,       // Per-octave RT60 and EDT
+    pub reflection_delays_ms: Vec<f32>,     // Quefrency axis of the reflection pattern
+    pub reflection_pattern: Vec<f32>,       // Mean cepstrum of the active frames, normalized
+    pub ltas_bands: Vec<f32>,               // Third-octave centers
+    pub ltas_db: Vec<f32>,                  // Long-term spectrum of the active frames, less its mean
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomVerdict {
+    SameRoom,       // Consistent with one room
+    Inconclusive,
+    DifferentRoom,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomComparison {
+    pub first: RoomProfile,
+    pub second: RoomProfile,
+    pub decay_similarity: Option<f32>,      // 0..1 from the per-band decay time ratios
+    pub reflection_similarity: Option<f32>, // Correlation of the reflection patterns, floored at 0
+    pub ltas_similarity: Option<f32>,       // 0..1 from the RMS difference of the spectra
+    pub similarity: f32,                    // Weighted mean of those available
+    pub verdict: RoomVerdict,
+    pub caveat: &'static str,
+}
+
+/// Mean power cepstrum of the active frames over `REFLECTION_RANGE`. A
+/// reflection delayed by τ adds a ripple of period 1/τ to every frame's
+/// log spectrum, which the cepstrum gathers at quefrency τ; the talker's
+/// pitch moves and averages out.
+fn reflection_pattern(samples: &[f32], sample_rate: u32) -> (Vec<f32>, Vec<f32>) {
+    let sr = sample_rate as f32;
+    let window = WindowType::Hann.coefficients(SPECTRUM_FFT);
+    let (lo, hi) = ((REFLECTION_RANGE.0 * sr) as usize, ((REFLECTION_RANGE.1 * sr) as usize).min(SPECTRUM_FFT / 2));
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * SPECTRUM_HOP)
+        .take_while(|&start| start + SPECTRUM_FFT <= samples.len())
+        .collect();
+    let frames: Vec<(f32, Vec<f32>)> = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(SPECTRUM_FFT);
+            let ifft = planner.plan_fft_inverse(SPECTRUM_FFT);
+            let mut input: Vec<f32> = samples[start..start + SPECTRUM_FFT].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let energy: f32 = spectrum.iter().map(|c| c.norm_sqr()).sum();
+            spectrum.iter_mut().for_each(|c| *c = (c.norm_sqr() + 1e-12).ln().into());
+            let mut cepstrum = ifft.make_output_vec();
+            ifft.process(&mut spectrum, &mut cepstrum).unwrap();
+            (10.0 * energy.max(1e-20).log10(), cepstrum[lo..hi].to_vec())
+        })
+        .collect();
+    let delays = (lo..hi).map(|q| 1000.0 * q as f32 / sr).collect();
+    let loudest = frames.iter().map(|f| f.0).fold(f32::NEG_INFINITY, f32::max);
+    let active: Vec<&Vec<f32>> = frames.iter().filter(|f| f.0 >= loudest - ACTIVE_RANGE_DB).map(|f| &f.1).collect();
+    if active.is_empty() || hi <= lo {
+        return (delays, Vec::new());
+    }
+    let mut mean = vec![0.0f32; hi - lo];
+    for c in &active {
+        mean.iter_mut().zip(c.iter()).for_each(|(m, v)| *m += v / active.len() as f32);
+    }
+    let average = mean.iter().sum::<f32>() / mean.len() as f32;
+    let norm = mean.iter().map(|v| (v - average).powi(2)).sum::<f32>().sqrt();
+    (delays, mean.iter().map(|v| if norm > 0.0 { (v - average) / norm } else { 0.0 }).collect())
+}
+
+/// Profile the room a recording was made in: per-octave decay times from
+/// the free decays after speech or other transients, the early-reflection
+/// pattern in the cepstrum, and the long-term spectrum of the active
+/// material.
+pub fn profile(samples: &[f32], sample_rate: u32) -> RoomProfile {
+    let sr = sample_rate as f32;
+    let reverb = reverb::estimate(samples, sample_rate);
+    let (reflection_delays_ms, reflection_pattern) = reflection_pattern(samples, sample_rate);
+
+    let ltas_bands: Vec<f32> = (0..)
+        .map(|i| LTAS_RANGE.0 * 2f32.powf(i as f32 / 3.0))
+        .take_while(|&f| f <= LTAS_RANGE.1 && f * 2f32.powf(1.0 / 6.0) < sr / 2.0)
+        .collect();
+    let thirds: Vec<(f32, f32)> = ltas_bands.iter().map(|&f| (f / 2f32.powf(1.0 / 6.0), f * 2f32.powf(1.0 / 6.0))).collect();
+    let frames = rerecord::band_frames(samples, sample_rate, SPECTRUM_FFT, SPECTRUM_HOP, &thirds);
+    let totals: Vec<f32> = frames
+        .iter()
+        .map(|f| 10.0 * f.iter().map(|l| 10f32.powf(l / 10.0)).sum::<f32>().log10())
+        .collect();
+    let loudest = totals.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let active: Vec<usize> = (0..frames.len()).filter(|&i| totals[i] >= loudest - ACTIVE_RANGE_DB).collect();
+    let ltas_db = if active.is_empty() {
+        Vec::new()
+    } else {
+        let power: Vec<f32> = (0..thirds.len())
+            .map(|b| 10.0 * (active.iter().map(|&i| 10f32.powf(frames[i][b] / 10.0)).sum::<f32>() / active.len() as f32).log10())
+            .collect();
+        let mean = power.iter().sum::<f32>() / power.len() as f32;
+        power.iter().map(|p| p - mean).collect()
+    };
+
+    RoomProfile {
+        reverb,
+        reflection_delays_ms,
+        reflection_pattern,
+        ltas_bands,
+        ltas_db,
+    }
+}
+
+/// Compare two room profiles. Each available aspect gives a similarity
+/// from 0 to 1: decay times by their mean log ratio over the bands both
+/// measured (a factor of `DECAY_RATIO_LIMIT` scores 0), reflections by the
+/// correlation of their patterns, and the long-term spectra by their RMS
+/// difference over the shared bands, mean removed.
+pub fn compare(first: RoomProfile, second: RoomProfile) -> RoomComparison {
+    let ratios: Vec<f32> = first
+        .reverb
+        .bands
+        .iter()
+        .zip(&second.reverb.bands)
+        .filter_map(|(a, b)| Some((a.rt60_s? / b.rt60_s?).ln().abs()))
+        .collect();
+    let decay_similarity = (!ratios.is_empty())
+        .then(|| (1.0 - ratios.iter().sum::<f32>() / ratios.len() as f32 / DECAY_RATIO_LIMIT.ln()).clamp(0.0, 1.0));
+
+    let reflection_similarity = (!first.reflection_pattern.is_empty()
+        && first.reflection_pattern.len() == second.reflection_pattern.len())
+    .then(|| first.reflection_pattern.iter().zip(&second.reflection_pattern).map(|(a, b)| a * b).sum::<f32>().max(0.0));
+
+    let shared = first.ltas_db.len().min(second.ltas_db.len());
+    let ltas_similarity = (shared > 0).then(|| {
+        let (a, b) = (&first.ltas_db[..shared], &second.ltas_db[..shared]);
+        let offset = (a.iter().sum::<f32>() - b.iter().sum::<f32>()) / shared as f32;
+        let rms = (a.iter().zip(b).map(|(x, y)| (x - y - offset).powi(2)).sum::<f32>() / shared as f32).sqrt();
+        (1.0 - rms / LTAS_LIMIT_DB).clamp(0.0, 1.0)
+    });
+
+    let (sum, weight) = [decay_similarity, reflection_similarity, ltas_similarity]
+        .iter()
+        .zip(WEIGHTS)
+        .filter_map(|(s, w)| Some((s.as_ref()? * w, w)))
+        .fold((0.0, 0.0), |(sum, weight), (s, w)| (sum + s, weight + w));
+    let similarity = if weight > 0.0 { sum / weight } else { 0.0 };
+    let verdict = if decay_similarity.is_none() {
+        RoomVerdict::Inconclusive
+    } else if similarity >= SAME_ROOM {
+        RoomVerdict::SameRoom
+    } else if similarity < OTHER_ROOM {
+        RoomVerdict::DifferentRoom
+    } else {
+        RoomVerdict::Inconclusive
+    };
+
+    RoomComparison {
+        first,
+        second,
+        decay_similarity,
+        reflection_similarity,
+        ltas_similarity,
+        similarity,
+        verdict,
+        caveat: CAVEAT,
+    }
+}
@@ -0,0 +1,41 @@
+//! Time-range bookkeeping shared by the frame-based detectors
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start_time: f32,
+    pub end_time: f32,
+}
+
+/// Runs of consecutive `true` flags lasting at least `min_len` frames,
+/// as half-open frame index ranges
+pub fn flag_runs(flags: &[bool], min_len: usize) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut run_start = None;
+    for i in 0..=flags.len() {
+        match (flags.get(i).copied().unwrap_or(false), run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                if i - start >= min_len.max(1) {
+                    runs.push((start, i));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Convert runs over frames that start every `hop` samples and span
+/// `window` samples into time ranges
+pub fn runs_to_ranges(runs: &[(usize, usize)], hop: usize, window: usize, sample_rate: u32) -> Vec<TimeRange> {
+    let sr = sample_rate as f32;
+    runs.iter()
+        .map(|&(start, end)| TimeRange {
+            start_time: (start * hop) as f32 / sr,
+            end_time: ((end - 1) * hop + window) as f32 / sr,
+        })
+        .collect()
+}
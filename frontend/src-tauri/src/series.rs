@@ -0,0 +1,139 @@
+//! Time series of the recording laid out as CSV tables, for processing in
+//! a spreadsheet or in Python
+
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::Deserialize;
+
+/// FFT length of the spectral features
+const FEATURE_FFT: usize = 2048;
+/// Fraction of a frame's energy below the rolloff frequency
+const ROLLOFF_FRACTION: f32 = 0.85;
+
+/// The series `export_time_series` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesKind {
+    /// The extracted ENF track: mains frequency and its strength
+    Enf,
+    /// Momentary and short-term loudness (BS.1770)
+    Loudness,
+    /// RMS and peak level per window
+    Rms,
+    /// Spectral centroid, spread, rolloff, flatness and flux per frame
+    SpectralFeatures,
+    /// L/R correlation coefficient per window
+    PhaseCorrelation,
+}
+
+/// Named columns of values at common times
+pub struct Table {
+    pub columns: Vec<&'static str>,
+    pub times: Vec<f32>,
+    pub rows: Vec<Vec<f32>>,
+}
+
+impl Table {
+    /// Build from whole columns, which must all be as long as `times`
+    pub fn from_columns(times: Vec<f32>, columns: Vec<(&'static str, Vec<f32>)>) -> Self {
+        let rows = (0..times.len()).map(|i| columns.iter().map(|(_, values)| values[i]).collect()).collect();
+        Self { columns: columns.iter().map(|(name, _)| *name).collect(), times, rows }
+    }
+
+    /// The table as CSV, time (seconds) in the first column. Values that
+    /// are not finite (the level of digital silence) are left empty.
+    pub fn csv(&self) -> String {
+        let mut out = std::iter::once("time").chain(self.columns.iter().copied()).collect::<Vec<_>>().join(",");
+        out.push('\n');
+        for (time, row) in self.times.iter().zip(&self.rows) {
+            out.push_str(&format!("{:.4}", time));
+            for value in row {
+                out.push(',');
+                if value.is_finite() {
+                    out.push_str(&format!("{}", value));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// RMS and peak level (dBFS, plus `offset_db`) of consecutive windows of
+/// `window` seconds, timed at their centres
+pub fn rms(samples: &[f32], sample_rate: u32, window: f32, offset_db: f32) -> Table {
+    let sr = sample_rate as f32;
+    let len = ((window * sr) as usize).max(1);
+    let (mut times, mut rms_db, mut peak_db) = (Vec::new(), Vec::new(), Vec::new());
+    for (i, chunk) in samples.chunks(len).enumerate() {
+        let mean_square = chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32;
+        let peak = chunk.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        times.push((i * len) as f32 / sr + chunk.len() as f32 / sr / 2.0);
+        rms_db.push(10.0 * mean_square.log10() + offset_db);
+        peak_db.push(20.0 * peak.log10() + offset_db);
+    }
+    Table::from_columns(times, vec![("rms_dbfs", rms_db), ("peak_dbfs", peak_db)])
+}
+
+/// Spectral shape of Hann-windowed frames `hop` seconds apart, timed at
+/// their centres: centroid and spread (power-weighted mean and standard
+/// deviation of frequency), rolloff (below which 85% of the power lies),
+/// flatness (geometric over arithmetic mean power, 0 tonal to 1 noise-like)
+/// and flux (how much the magnitude spectrum rose since the previous frame,
+/// the root sum of squares of the increases)
+pub fn spectral_features(samples: &[f32], sample_rate: u32, hop: f32) -> Table {
+    let sr = sample_rate as f32;
+    let hop = ((hop * sr) as usize).max(1);
+    let window = WindowType::Hann.coefficients(FEATURE_FFT);
+    let bin_hz = sr / FEATURE_FFT as f32;
+    let starts: Vec<usize> = (0..).map(|i| i * hop).take_while(|&s| s + FEATURE_FFT <= samples.len()).collect();
+
+    let spectra: Vec<Vec<f32>> = starts
+        .par_iter()
+        .map(|&start| {
+            let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FEATURE_FFT);
+            let mut frame: Vec<f32> = samples[start..start + FEATURE_FFT].iter().zip(&window).map(|(s, w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut frame, &mut spectrum).unwrap();
+            spectrum.iter().map(|c| c.norm()).collect()
+        })
+        .collect();
+
+    let mut columns: [Vec<f32>; 5] = Default::default();
+    for (i, magnitudes) in spectra.iter().enumerate() {
+        let power: Vec<f32> = magnitudes.iter().map(|m| m * m).collect();
+        let total: f32 = power.iter().sum();
+        let freq = |k: usize| k as f32 * bin_hz;
+        let (centroid, spread, rolloff, flatness) = if total > 0.0 {
+            let centroid = power.iter().enumerate().map(|(k, p)| freq(k) * p).sum::<f32>() / total;
+            let spread = (power.iter().enumerate().map(|(k, p)| (freq(k) - centroid).powi(2) * p).sum::<f32>() / total).sqrt();
+            let mut below = 0.0;
+            let rolloff = power.iter().position(|p| {
+                below += p;
+                below >= ROLLOFF_FRACTION * total
+            });
+            let log_mean = power.iter().map(|p| p.max(1e-20).ln()).sum::<f32>() / power.len() as f32;
+            (centroid, spread, freq(rolloff.unwrap_or(0)), log_mean.exp() / (total / power.len() as f32))
+        } else {
+            (f32::NAN, f32::NAN, f32::NAN, f32::NAN)
+        };
+        let flux = match i.checked_sub(1).map(|j| &spectra[j]) {
+            Some(previous) => magnitudes.iter().zip(previous).map(|(m, p)| (m - p).max(0.0).powi(2)).sum::<f32>().sqrt(),
+            None => 0.0,
+        };
+        for (column, value) in columns.iter_mut().zip([centroid, spread, rolloff, flatness, flux]) {
+            column.push(value);
+        }
+    }
+
+    let times = starts.iter().map(|&s| (s + FEATURE_FFT / 2) as f32 / sr).collect();
+    let [centroid, spread, rolloff, flatness, flux] = columns;
+    Table::from_columns(times, vec![
+        ("centroid_hz", centroid),
+        ("spread_hz", spread),
+        ("rolloff_hz", rolloff),
+        ("flatness", flatness),
+        ("flux", flux),
+    ])
+}
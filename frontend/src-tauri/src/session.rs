@@ -0,0 +1,85 @@
+//! Sessions: the work on a recording that is not kept in the file itself
+//! (its markers, selections and analysis results), saved as JSON to take up
+//! again later, and autosaved to recover from a crash
+
+use crate::markers::MarkerSet;
+use crate::segments::TimeRange;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Version of the session layout, raised when a field is renamed, removed
+/// or changes meaning; newer sessions are refused rather than misread
+pub const SESSION_VERSION: u32 = 1;
+/// The autosaved session, in the app's data directory
+pub const AUTOSAVE_FILE: &str = "autosave.json";
+/// The autosave of a run that ended without a clean shutdown, kept apart
+/// so the next run's autosaves leave it alone until recovered or discarded
+pub const RECOVERY_FILE: &str = "recovery.json";
+/// Present in the app's data directory while the app runs; found at
+/// startup, it means the last run did not shut down cleanly
+pub const RUNNING_FILE: &str = "running";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub session_version: u32,
+    pub saved_at: String,               // RFC 3339, UTC
+    pub source_path: Option<String>,    // Recording the session belongs to
+    pub markers: MarkerSet,
+    #[serde(default)]
+    pub selections: Vec<TimeRange>,     // Time ranges selected in the view
+    #[serde(default)]
+    pub results: Option<serde_json::Value>,  // Analysis results, as the JSON report has them
+}
+
+/// What a recoverable autosave holds, to offer it
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryInfo {
+    pub saved_at: String,
+    pub source_path: Option<String>,
+    pub markers: usize,
+    pub selections: usize,
+    pub has_results: bool,
+}
+
+impl Session {
+    pub fn recovery_info(&self) -> RecoveryInfo {
+        RecoveryInfo {
+            saved_at: self.saved_at.clone(),
+            source_path: self.source_path.clone(),
+            markers: self.markers.markers.len(),
+            selections: self.selections.len(),
+            has_results: self.results.is_some(),
+        }
+    }
+}
+
+/// Write the session to `path` by way of a temporary file beside it, so a
+/// crash part way leaves the previous save whole
+pub fn save(path: impl AsRef<Path>, session: &Session) -> Result<(), String> {
+    let path = path.as_ref();
+    let json = serde_json::to_string_pretty(session).map_err(|e| format!("Failed to serialize session: {}", e))?;
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, json).map_err(|e| format!("Failed to write session: {}", e))?;
+    std::fs::rename(&temporary, path).map_err(|e| format!("Failed to write session: {}", e))
+}
+
+/// Write the session to `path` as a new file, for evidence mode: refused
+/// if the file exists, and never by way of a temporary file
+pub fn save_new(path: impl AsRef<Path>, session: &Session) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(session).map_err(|e| format!("Failed to serialize session: {}", e))?;
+    crate::evidence::create_output(path, true)?
+        .write_all(json.as_bytes())
+        .map_err(|e| format!("Failed to write session: {}", e))
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Session, String> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let session: Session = serde_json::from_str(&text).map_err(|e| format!("Not a session file: {}", e))?;
+    if session.session_version > SESSION_VERSION {
+        return Err(format!("{} was saved by a newer version (session format {})", path.display(), session.session_version));
+    }
+    Ok(session)
+}
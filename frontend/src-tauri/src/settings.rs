@@ -0,0 +1,192 @@
+//! Preferences kept between launches: analysis and export defaults, the
+//! colour map of the spectrogram view, the autosave interval, the watch
+//! folder and the files opened recently, as JSON in the app's data directory
+
+use crate::enf::EnfParams;
+use crate::export::{ExportFormat, WavSampleFormat};
+use crate::plot::FigureOptions;
+use crate::report::ReportFormat;
+use crate::spectrum::WindowType;
+use crate::video::VideoOptions;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File the settings are kept in, in the app's data directory
+pub const SETTINGS_FILE: &str = "settings.json";
+/// Version of the settings layout; fields added later take their defaults
+pub const SETTINGS_VERSION: u32 = 1;
+/// Recent files remembered, the oldest forgotten first
+pub const MAX_RECENT: usize = 20;
+
+/// Colour maps of the spectrogram view
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Magma,
+    Inferno,
+    Grayscale,
+}
+
+/// Settings analyses start from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisDefaults {
+    pub max_freq: f32,          // Top of the spectrogram view (Hz)
+    pub n_fft: usize,
+    pub hop_length: usize,
+    pub window: WindowType,
+    pub enf: EnfParams,
+}
+
+impl Default for AnalysisDefaults {
+    fn default() -> Self {
+        Self {
+            max_freq: 8000.0,
+            n_fft: 2048,
+            hop_length: 512,
+            window: WindowType::default(),
+            enf: EnfParams::default(),
+        }
+    }
+}
+
+/// Settings exports start from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportDefaults {
+    pub directory: Option<String>,  // Where the save dialogs open
+    pub audio_format: ExportFormat,
+    pub wav_sample_format: WavSampleFormat,
+    pub report_format: ReportFormat,
+    pub sign: bool,                 // Sign deliverables as they are written
+    pub figure: FigureOptions,
+    pub video: VideoOptions,
+}
+
+impl Default for ExportDefaults {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            audio_format: ExportFormat::Wav,
+            wav_sample_format: WavSampleFormat::default(),
+            report_format: ReportFormat::Html,
+            sign: false,
+            figure: FigureOptions::default(),
+            video: VideoOptions::default(),
+        }
+    }
+}
+
+/// A folder polled for new recordings, each hashed, decoded and, if asked,
+/// analyzed as it arrives, apart from the one under examination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchSettings {
+    pub directory: Option<String>,  // None: not watching
+    pub interval_secs: u32,         // Between polls
+    pub analyze: bool,              // Run the forensic analysis on each file
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        Self { directory: None, interval_secs: 5, analyze: true }
+    }
+}
+
+/// A file opened before, with its SHA-256 then, so a file changed since
+/// can be told apart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentFile {
+    pub path: String,
+    pub sha256: String,
+    pub opened_at: String,      // RFC 3339, UTC
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub settings_version: u32,
+    pub analysis: AnalysisDefaults,
+    pub export: ExportDefaults,
+    pub colormap: Colormap,
+    pub autosave_secs: u32,             // Between autosaves of the session; 0 turns them off
+    pub watch: WatchSettings,
+    pub recent_files: Vec<RecentFile>,  // Most recent first
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            settings_version: SETTINGS_VERSION,
+            analysis: AnalysisDefaults::default(),
+            export: ExportDefaults::default(),
+            colormap: Colormap::default(),
+            autosave_secs: 60,
+            watch: WatchSettings::default(),
+            recent_files: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        let analysis = &self.analysis;
+        if analysis.max_freq.is_nan() || analysis.max_freq <= 0.0 {
+            return Err("Maximum frequency must be above 0 Hz".to_string());
+        }
+        if !(64..=65536).contains(&analysis.n_fft) {
+            return Err("FFT size must be between 64 and 65536".to_string());
+        }
+        if analysis.hop_length == 0 || analysis.hop_length > analysis.n_fft {
+            return Err("Hop length must be between 1 and the FFT size".to_string());
+        }
+        if self.autosave_secs > 0 && self.autosave_secs < 5 {
+            return Err("Autosave interval must be at least 5 seconds (or 0 for none)".to_string());
+        }
+        if self.watch.interval_secs == 0 {
+            return Err("Watch folder interval must be at least 1 second".to_string());
+        }
+        if let Some(directory) = &self.watch.directory {
+            if !Path::new(directory).is_dir() {
+                return Err(format!("Watch folder {} is not a directory", directory));
+            }
+        }
+        analysis.enf.validate()?;
+        self.export.figure.validate()?;
+        self.export.video.validate()
+    }
+
+    /// Put `path` first among the recent files, once
+    pub fn add_recent(&mut self, path: String, sha256: String, opened_at: String) {
+        self.recent_files.retain(|f| f.path != path);
+        self.recent_files.insert(0, RecentFile { path, sha256, opened_at });
+        self.recent_files.truncate(MAX_RECENT);
+    }
+}
+
+/// The settings kept at `path`, the defaults when there are none yet
+pub fn load(path: &Path) -> Result<Settings, String> {
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let settings: Settings = serde_json::from_str(&text).map_err(|e| format!("Settings file {} is damaged: {}", path.display(), e))?;
+    if settings.settings_version > SETTINGS_VERSION {
+        return Err(format!("{} was saved by a newer version (settings format {})", path.display(), settings.settings_version));
+    }
+    Ok(settings)
+}
+
+/// Write the settings to `path` by way of a temporary file beside it, so
+/// a crash part way leaves the old ones whole
+pub fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let temporary = path.with_extension("json.tmp");
+    std::fs::write(&temporary, json).map_err(|e| format!("Failed to write settings: {}", e))?;
+    std::fs::rename(&temporary, path).map_err(|e| format!("Failed to write settings: {}", e))
+}
@@ -0,0 +1,187 @@
+//! Signed manifests of exported files: the SHA-256 of each in `sha256sum`
+//! format, and an Ed25519 signature of the manifest by this installation's
+//! key, so a deliverable can later be shown to be the file as exported
+
+use crate::evidence::create_output;
+use crate::report::{self, hex, Tool};
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// File the signing key is kept in, in the app's data directory: the
+/// 32-byte Ed25519 secret, readable by the user only
+pub const KEY_FILE: &str = "signing_key.ed25519";
+/// Extension the signature of a manifest gets, after the manifest's name
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// The signature written beside a manifest. Only the manifest's bytes are
+/// signed; the time and tool are for the reader.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signature {
+    pub algorithm: String,
+    pub public_key: String,     // Hex
+    pub signature: String,      // Hex, of the manifest file's bytes
+    pub signed_at: String,
+    pub tool: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHash {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// What `sign` wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedManifest {
+    pub manifest_path: String,
+    pub signature_path: String,
+    pub public_key: String,
+    pub files: Vec<FileHash>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileCheck {
+    pub path: String,
+    pub expected_sha256: String,
+    pub actual_sha256: Option<String>,  // None when the file cannot be read
+    pub intact: bool,
+}
+
+/// The outcome of checking a manifest, its signature and the files it lists
+#[derive(Debug, Clone, Serialize)]
+pub struct Verification {
+    pub signature_valid: bool,
+    pub public_key: String,
+    pub signed_at: String,
+    pub trusted_key: Option<bool>,  // Whether the key is the one expected, when one is
+    pub files: Vec<FileCheck>,
+    pub intact: bool,               // A valid signature and every file unchanged
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 == 1 || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+/// The signing key kept at `path`, made from the system's random source
+/// (and the directory with it) when there is none yet
+pub fn load_or_create_key(path: &Path) -> Result<SigningKey, String> {
+    if path.exists() {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read signing key {}: {}", path.display(), e))?;
+        let secret: [u8; 32] = bytes.try_into().map_err(|_| format!("Signing key {} is damaged", path.display()))?;
+        return Ok(SigningKey::from_bytes(&secret));
+    }
+
+    let mut secret = [0u8; 32];
+    getrandom::fill(&mut secret).map_err(|e| format!("No random source for a signing key: {}", e))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(|e| format!("Failed to create signing key {}: {}", path.display(), e))?;
+    std::io::Write::write_all(&mut file, &secret).map_err(|e| format!("Failed to write signing key {}: {}", path.display(), e))?;
+    log::info!("Created signing key {}", path.display());
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// The public half of a key as hex, to hand to whoever verifies
+pub fn public_key(key: &SigningKey) -> String {
+    hex(key.verifying_key().as_bytes())
+}
+
+pub fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = unhex(text.trim()).and_then(|b| b.try_into().ok()).ok_or("A public key is 64 hex digits")?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid public key: {}", e))
+}
+
+/// Hash `paths` into a manifest at `manifest_path` and sign it with `key`
+/// into `<manifest_path>.sig`. Files beside the manifest are listed by name,
+/// so the set can be moved together; others by their full path. With
+/// `new_only` (evidence mode) neither file may exist yet.
+pub fn sign(paths: &[String], manifest_path: &str, key: &SigningKey, new_only: bool) -> Result<SignedManifest, String> {
+    if paths.is_empty() {
+        return Err("No files to sign".to_string());
+    }
+    let base = Path::new(manifest_path).parent().unwrap_or(Path::new(""));
+    let mut manifest = String::new();
+    let mut files = Vec::new();
+    for path in paths {
+        let sha256 = report::identify(path)?.sha256;
+        let listed = match Path::new(path).parent() {
+            Some(parent) if parent == base => Path::new(path).file_name().map_or(path.clone(), |n| n.to_string_lossy().into_owned()),
+            _ => path.clone(),
+        };
+        if listed.contains('\n') {
+            return Err(format!("Cannot list a file name with a line break: {:?}", listed));
+        }
+        manifest.push_str(&format!("{}  {}\n", sha256, listed));
+        files.push(FileHash { path: path.clone(), sha256 });
+    }
+
+    let signature = Signature {
+        algorithm: "Ed25519".to_string(),
+        public_key: public_key(key),
+        signature: hex(&key.sign(manifest.as_bytes()).to_bytes()),
+        signed_at: report::timestamp(),
+        tool: format!("{} {}", Tool::current().name, Tool::current().version),
+    };
+    let signature_path = format!("{}.{}", manifest_path, SIGNATURE_EXTENSION);
+    create_output(manifest_path, new_only)?
+        .write_all(manifest.as_bytes())
+        .map_err(|e| format!("Failed to write manifest: {}", e))?;
+    let json = serde_json::to_string_pretty(&signature).map_err(|e| format!("Failed to serialize signature: {}", e))?;
+    create_output(&signature_path, new_only)?
+        .write_all(json.as_bytes())
+        .map_err(|e| format!("Failed to write signature: {}", e))?;
+
+    Ok(SignedManifest { manifest_path: manifest_path.to_string(), signature_path, public_key: signature.public_key, files })
+}
+
+/// Check the manifest at `manifest_path` against its signature and every
+/// file it lists against its hash. `trusted` is the key the signature is
+/// expected from; without one, any valid signature is reported as such.
+pub fn verify(manifest_path: &str, trusted: Option<&VerifyingKey>) -> Result<Verification, String> {
+    let manifest = std::fs::read(manifest_path).map_err(|e| format!("Failed to read {}: {}", manifest_path, e))?;
+    let signature_path = format!("{}.{}", manifest_path, SIGNATURE_EXTENSION);
+    let text = std::fs::read_to_string(&signature_path).map_err(|e| format!("Failed to read {}: {}", signature_path, e))?;
+    let signature: Signature = serde_json::from_str(&text).map_err(|e| format!("Not a signature file: {}", e))?;
+    if signature.algorithm != "Ed25519" {
+        return Err(format!("Unsupported signature algorithm {}", signature.algorithm));
+    }
+
+    let key = parse_public_key(&signature.public_key)?;
+    let signature_valid = unhex(&signature.signature)
+        .and_then(|b| <[u8; 64]>::try_from(b).ok())
+        .map(|bytes| key.verify_strict(&manifest, &ed25519_dalek::Signature::from_bytes(&bytes)).is_ok())
+        .unwrap_or(false);
+
+    let base = Path::new(manifest_path).parent().unwrap_or(Path::new(""));
+    let text = String::from_utf8(manifest).map_err(|_| "The manifest is not text".to_string())?;
+    let mut files = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        // "<hash>  <name>", or "<hash> *<name>" as sha256sum writes in binary mode
+        let (expected, name) = line.split_once(' ').ok_or_else(|| format!("Malformed manifest line: {}", line))?;
+        let name = name.strip_prefix(' ').or_else(|| name.strip_prefix('*')).unwrap_or(name);
+        let path = base.join(name).to_string_lossy().into_owned();
+        let actual = report::identify(&path).ok().map(|identity| identity.sha256);
+        let intact = actual.as_deref() == Some(&expected.to_lowercase());
+        files.push(FileCheck { path, expected_sha256: expected.to_string(), actual_sha256: actual, intact });
+    }
+
+    let trusted_key = trusted.map(|t| *t == key);
+    Ok(Verification {
+        signature_valid,
+        public_key: signature.public_key,
+        signed_at: signature.signed_at,
+        intact: signature_valid && trusted_key != Some(false) && !files.is_empty() && files.iter().all(|f| f.intact),
+        trusted_key,
+        files,
+    })
+}
@@ -0,0 +1,84 @@
+//! Silence detection for splitting a recording into its separate parts
+
+use crate::segments::{flag_runs, TimeRange};
+use serde::Deserialize;
+
+/// Level frames (seconds)
+const FRAME_SECS: f32 = 0.01;
+
+/// How `sounding_parts` tells silence from sound; every field has a default
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct SilenceSplit {
+    /// Frames whose loudest channel stays below this RMS level (dBFS) are
+    /// silent; set it above the hiss or room tone between the parts
+    pub threshold_db: f32,
+    /// Shortest silence that separates two parts (seconds); shorter pauses
+    /// stay inside a part
+    pub min_silence: f32,
+    /// Parts shorter than this are dropped as clicks and thumps (seconds)
+    pub min_length: f32,
+    /// Silence kept before and after each part (seconds), at most up to
+    /// the middle of the gap to the next
+    pub padding: f32,
+}
+
+impl Default for SilenceSplit {
+    fn default() -> Self {
+        Self { threshold_db: -45.0, min_silence: 1.0, min_length: 1.0, padding: 0.25 }
+    }
+}
+
+/// The parts of interleaved `samples` separated by silence, as `split`
+/// defines it, in order
+pub fn sounding_parts(samples: &[f32], channels: usize, sample_rate: u32, split: &SilenceSplit) -> Result<Vec<TimeRange>, String> {
+    if !(-120.0..=0.0).contains(&split.threshold_db) {
+        return Err("Silence threshold must be between -120 and 0 dBFS".to_string());
+    }
+    if !(0.01..=60.0).contains(&split.min_silence) {
+        return Err("Minimum silence must be between 0.01 and 60 seconds".to_string());
+    }
+    if !(0.0..=3600.0).contains(&split.min_length) || !(0.0..=10.0).contains(&split.padding) {
+        return Err("Minimum length must be at most an hour and padding at most 10 seconds".to_string());
+    }
+
+    let channels = channels.max(1);
+    let sr = sample_rate as f32;
+    let frame = ((FRAME_SECS * sr) as usize).max(1);
+    let threshold = 10f32.powf(split.threshold_db / 10.0);
+    let sounding: Vec<bool> = samples
+        .chunks(frame * channels)
+        .map(|block| {
+            let frames = (block.len() / channels).max(1) as f32;
+            (0..channels).any(|c| block.iter().skip(c).step_by(channels).map(|s| s * s).sum::<f32>() / frames > threshold)
+        })
+        .collect();
+
+    // Bridge pauses shorter than the minimum silence, then drop the blips
+    let min_gap = (split.min_silence / FRAME_SECS).round() as usize;
+    let mut parts: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in flag_runs(&sounding, 1) {
+        match parts.last_mut() {
+            Some(last) if start - last.1 < min_gap => last.1 = end,
+            _ => parts.push((start, end)),
+        }
+    }
+    let min_len = (split.min_length / FRAME_SECS).round() as usize;
+    parts.retain(|&(start, end)| end - start >= min_len);
+
+    let duration = (samples.len() / channels) as f32 / sr;
+    let to_time = |f: usize| ((f * frame) as f32 / sr).min(duration);
+    let ranges = parts
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, end))| {
+            let earliest = if i == 0 { 0.0 } else { (to_time(parts[i - 1].1) + to_time(start)) / 2.0 };
+            let latest = parts.get(i + 1).map_or(duration, |next| (to_time(end) + to_time(next.0)) / 2.0);
+            TimeRange {
+                start_time: (to_time(start) - split.padding).max(earliest),
+                end_time: (to_time(end) + split.padding).min(latest),
+            }
+        })
+        .collect();
+    Ok(ranges)
+}
@@ -0,0 +1,290 @@
+//! Content similarity between two recordings: fingerprint matches map the
+//! stretches of the comparison copied from the reference, and dynamic time
+//! warping of their MFCCs measures how alike they sound overall
+
+use crate::duplication::{self, MAX_OCCURRENCES, MIN_HITS};
+use crate::splice::{self, N_MFCC};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Sub-fingerprints per block when growing a match (Haitsma and Kalker's
+/// block length)
+const BLOCK_FRAMES: usize = 256;
+/// Bit error rate up to which a block still counts as the same material;
+/// unrelated audio sits near 0.5
+const MAX_BIT_ERROR_RATE: f32 = 0.35;
+/// MFCC frames are averaged into segments this long (seconds) for the
+/// time warping, or longer so neither file has more than `MAX_SEGMENTS`
+const SEGMENT_SECS: f32 = 0.5;
+const MAX_SEGMENTS: usize = 2000;
+/// Mean MFCC distance per warping step at which the similarity is 0.5
+const DTW_HALF_DISTANCE: f32 = 10.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimilarityOptions {
+    /// Compare this channel of both files instead of their mixes
+    pub channel: Option<usize>,
+    /// Shortest matched stretch reported (seconds)
+    pub min_duration: f32,
+}
+
+impl Default for SimilarityOptions {
+    fn default() -> Self {
+        Self { channel: None, min_duration: 2.0 }
+    }
+}
+
+impl SimilarityOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_duration.is_nan() || self.min_duration < 0.5 {
+            return Err("Shortest match must be at least 0.5 s".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityVerdict {
+    Copied,     // Stretches of the reference found in the comparison
+    Similar,    // No copied stretch, but the two sound alike (a re-recording, or heavy processing)
+    Unrelated,
+}
+
+/// A stretch of the comparison whose fingerprints match the reference
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchedRegion {
+    pub reference_start: f32,
+    pub comparison_start: f32,
+    pub duration: f32,
+    pub offset: f32,            // comparison_start - reference_start (seconds)
+    pub bit_error_rate: f32,    // Over the stretch; 0 = identical fingerprints
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarityReport {
+    pub score: f32,                         // The larger of the comparison's overlap and the MFCC similarity, 0 to 1
+    pub verdict: SimilarityVerdict,
+    pub comparison_overlap: f32,            // Share of the comparison's duration matched to the reference
+    pub reference_overlap: f32,             // Share of the reference's duration found in the comparison
+    pub dtw_distance: f32,                  // Mean MFCC distance per step of the warping path
+    pub dtw_similarity: f32,                // 1 for identical timbre, falling towards 0
+    pub dtw_segment_secs: f32,
+    pub regions: Vec<MatchedRegion>,
+}
+
+/// Bit error rate between `a[i]` and `b[i + offset]` over `len` frames from
+/// `i = start`, counting the frames both have fingerprints for; `None` when
+/// fewer than half do
+fn bit_error_rate(a: &[Option<u32>], b: &[Option<u32>], start: usize, len: usize, offset: i64) -> Option<f32> {
+    let (mut errors, mut frames) = (0u32, 0usize);
+    for i in start..start + len {
+        let j = i as i64 + offset;
+        if i >= a.len() || j < 0 || j as usize >= b.len() {
+            return None;
+        }
+        if let (Some(x), Some(y)) = (a[i], b[j as usize]) {
+            errors += (x ^ y).count_ones();
+            frames += 1;
+        }
+    }
+    (frames * 2 >= len).then(|| errors as f32 / (frames * 32) as f32)
+}
+
+/// Stretches (reference start, end, offset) in frames where the comparison
+/// repeats the reference: offsets voted for by exact sub-fingerprint
+/// matches, grown block by block while the bit error rate stays low
+fn match_fingerprints(a: &[Option<u32>], b: &[Option<u32>], min_frames: usize) -> Vec<(usize, usize, i64)> {
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, print) in a.iter().enumerate() {
+        if let Some(bits) = print {
+            index.entry(*bits).or_default().push(i);
+        }
+    }
+    index.retain(|_, frames| frames.len() <= MAX_OCCURRENCES);
+
+    // Vote for offsets between exact matches, remembering where in the reference
+    let mut votes: HashMap<i64, Vec<usize>> = HashMap::new();
+    for (j, print) in b.iter().enumerate() {
+        for &i in print.and_then(|bits| index.get(&bits)).into_iter().flatten() {
+            votes.entry(j as i64 - i as i64).or_default().push(i);
+        }
+    }
+    let mut candidates: Vec<(i64, Vec<usize>)> = votes.into_iter().filter(|(_, hits)| hits.len() >= MIN_HITS).collect();
+    candidates.sort_by_key(|(offset, hits)| (std::cmp::Reverse(hits.len()), *offset));
+
+    let mut found: Vec<(usize, usize, i64)> = Vec::new();
+    for (offset, mut hits) in candidates {
+        hits.sort_unstable();
+        // Hits far apart at the same offset are separate copies
+        let mut first = 0;
+        for k in 1..=hits.len() {
+            if k < hits.len() && hits[k] - hits[k - 1] <= BLOCK_FRAMES {
+                continue;
+            }
+            let cluster = &hits[first..k];
+            first = k;
+            if cluster.len() < MIN_HITS {
+                continue;
+            }
+            let seed = cluster[cluster.len() / 2].saturating_sub(BLOCK_FRAMES / 2);
+            let known = found.iter().any(|&(start, end, o)| (start..end).contains(&seed) && o.abs_diff(offset) <= 2);
+            let matches = |start: usize| bit_error_rate(a, b, start, BLOCK_FRAMES, offset).is_some_and(|ber| ber <= MAX_BIT_ERROR_RATE);
+            if known || !matches(seed) {
+                continue;
+            }
+            let mut start = seed;
+            while start >= BLOCK_FRAMES && matches(start - BLOCK_FRAMES) {
+                start -= BLOCK_FRAMES;
+            }
+            let mut end = seed + BLOCK_FRAMES;
+            while matches(end) {
+                end += BLOCK_FRAMES;
+            }
+            if end - start >= min_frames {
+                found.push((start, end, offset));
+            }
+        }
+    }
+    found.sort_by_key(|&(start, _, offset)| (start, offset));
+    found
+}
+
+/// Total length of the union of `ranges`
+fn covered(mut ranges: Vec<(i64, i64)>) -> i64 {
+    ranges.sort_unstable();
+    let mut total = 0;
+    let mut reach = i64::MIN;
+    for (start, end) in ranges {
+        let start = start.max(reach);
+        if end > start {
+            total += end - start;
+            reach = end;
+        }
+    }
+    total
+}
+
+/// Per-file mean-normalized MFCCs averaged over segments of `frames` frames,
+/// so a different microphone or EQ (a constant offset) cancels
+fn mfcc_segments(samples: &[f32], sample_rate: u32, frames: usize) -> Vec<[f32; N_MFCC]> {
+    let mfccs = splice::mfcc_frames(samples, sample_rate);
+    let mut segments: Vec<[f32; N_MFCC]> = mfccs
+        .chunks(frames)
+        .map(|chunk| {
+            let mut mean = [0.0f32; N_MFCC];
+            for m in chunk {
+                for (total, c) in mean.iter_mut().zip(m) {
+                    *total += c / chunk.len() as f32;
+                }
+            }
+            mean
+        })
+        .collect();
+    let n = segments.len().max(1) as f32;
+    let mut file_mean = [0.0f32; N_MFCC];
+    for s in &segments {
+        for (total, c) in file_mean.iter_mut().zip(s) {
+            *total += c / n;
+        }
+    }
+    for s in &mut segments {
+        for (c, m) in s.iter_mut().zip(&file_mean) {
+            *c -= m;
+        }
+    }
+    segments
+}
+
+/// Dynamic time warping of two MFCC sequences: the cost of the cheapest
+/// monotonic path from their starts to their ends, per step of the path
+fn dtw_distance(a: &[[f32; N_MFCC]], b: &[[f32; N_MFCC]]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return f32::INFINITY;
+    }
+    let distance = |x: &[f32; N_MFCC], y: &[f32; N_MFCC]| x.iter().zip(y).map(|(p, q)| (p - q).powi(2)).sum::<f32>().sqrt();
+    // Rows of (accumulated cost, path length), one row of the matrix at a time
+    let mut previous: Vec<(f32, u32)> = Vec::with_capacity(b.len());
+    for (i, x) in a.iter().enumerate() {
+        let mut row: Vec<(f32, u32)> = Vec::with_capacity(b.len());
+        for (j, y) in b.iter().enumerate() {
+            let d = distance(x, y);
+            let best = [
+                (i > 0).then(|| previous[j]),
+                (j > 0).then(|| row[j - 1]),
+                (i > 0 && j > 0).then(|| previous[j - 1]),
+            ]
+            .into_iter()
+            .flatten()
+            .min_by(|p, q| p.0.total_cmp(&q.0))
+            .unwrap_or((0.0, 0));
+            row.push((best.0 + d, best.1 + 1));
+        }
+        previous = row;
+    }
+    let (cost, steps) = previous[b.len() - 1];
+    cost / steps as f32
+}
+
+/// Compare `comparison` against `reference`: where the comparison repeats
+/// stretches of the reference, and how alike the two sound overall
+pub fn compare(reference: &[f32], comparison: &[f32], sample_rate: u32, options: &SimilarityOptions) -> Result<SimilarityReport, String> {
+    options.validate()?;
+    let sr = sample_rate as f32;
+    let (frame, hop) = duplication::frame_and_hop(sample_rate);
+    if reference.len() < 2 * frame || comparison.len() < 2 * frame {
+        return Err("Both files must be longer than two fingerprint frames".to_string());
+    }
+
+    let a = duplication::fingerprints(reference, sample_rate, frame, hop);
+    let b = duplication::fingerprints(comparison, sample_rate, frame, hop);
+    let min_frames = ((options.min_duration * sr) as usize / hop).max(BLOCK_FRAMES);
+    let found = match_fingerprints(&a, &b, min_frames);
+
+    let regions: Vec<MatchedRegion> = found
+        .iter()
+        .map(|&(start, end, offset)| {
+            let reference_start = (start * hop) as f32 / sr;
+            let offset_secs = (offset * hop as i64) as f32 / sr;
+            MatchedRegion {
+                reference_start,
+                comparison_start: reference_start + offset_secs,
+                duration: ((end - start) * hop) as f32 / sr,
+                offset: offset_secs,
+                bit_error_rate: bit_error_rate(&a, &b, start, end - start, offset).unwrap_or(0.5),
+            }
+        })
+        .collect();
+    let reference_overlap = covered(found.iter().map(|&(start, end, _)| (start as i64, end as i64)).collect()) as f32 / a.len() as f32;
+    let comparison_overlap = covered(found.iter().map(|&(start, end, offset)| (start as i64 + offset, end as i64 + offset)).collect()) as f32
+        / b.len() as f32;
+
+    let longest = reference.len().max(comparison.len()) as f32 / sr;
+    let segment_secs = SEGMENT_SECS.max(longest / MAX_SEGMENTS as f32);
+    let segment_frames = ((segment_secs * sr) as usize / splice::HOP).max(1);
+    let dtw_distance = dtw_distance(
+        &mfcc_segments(reference, sample_rate, segment_frames),
+        &mfcc_segments(comparison, sample_rate, segment_frames),
+    );
+    let dtw_similarity = 1.0 / (1.0 + dtw_distance / DTW_HALF_DISTANCE);
+
+    let verdict = if !regions.is_empty() {
+        SimilarityVerdict::Copied
+    } else if dtw_similarity >= 0.5 {
+        SimilarityVerdict::Similar
+    } else {
+        SimilarityVerdict::Unrelated
+    };
+
+    Ok(SimilarityReport {
+        score: comparison_overlap.max(dtw_similarity).min(1.0),
+        verdict,
+        comparison_overlap: comparison_overlap.min(1.0),
+        reference_overlap: reference_overlap.min(1.0),
+        dtw_distance,
+        dtw_similarity,
+        dtw_segment_secs: (segment_frames * splice::HOP) as f32 / sr,
+        regions,
+    })
+}
@@ -0,0 +1,210 @@
+//! Window functions and scaled spectral estimates
+
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowType {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+    FlatTop,
+}
+
+impl WindowType {
+    /// Periodic (DFT-even) window of length `n`
+    pub fn coefficients(self, n: usize) -> Vec<f32> {
+        let cosine_sum = |a: &[f32]| -> Vec<f32> {
+            (0..n)
+                .map(|i| {
+                    let x = 2.0 * PI * i as f32 / n as f32;
+                    a.iter()
+                        .enumerate()
+                        .map(|(k, &ak)| {
+                            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                            sign * ak * (k as f32 * x).cos()
+                        })
+                        .sum()
+                })
+                .collect()
+        };
+
+        match self {
+            WindowType::Rectangular => vec![1.0; n],
+            WindowType::Hann => cosine_sum(&[0.5, 0.5]),
+            WindowType::Hamming => cosine_sum(&[0.54, 0.46]),
+            WindowType::Blackman => cosine_sum(&[0.42, 0.5, 0.08]),
+            WindowType::BlackmanHarris => cosine_sum(&[0.35875, 0.48829, 0.14128, 0.01168]),
+            WindowType::FlatTop => cosine_sum(&[0.21557895, 0.41663158, 0.27726316, 0.083578947, 0.006947368]),
+        }
+    }
+}
+
+/// One-sided power spectral density estimate
+pub struct Psd {
+    pub freqs: Vec<f32>,
+    pub power: Vec<f32>,   // Linear power density (FS²/Hz)
+    pub segments: usize,
+    pub enbw_hz: f32,      // Equivalent noise bandwidth of one bin
+}
+
+/// Welch's averaged, modified periodogram with per-segment mean removal
+pub fn welch_psd(
+    samples: &[f32],
+    sample_rate: u32,
+    segment_len: usize,
+    overlap: usize,
+    window: WindowType,
+) -> Psd {
+    let sr = sample_rate as f32;
+    let win = window.coefficients(segment_len);
+    let win_power: f32 = win.iter().map(|w| w * w).sum();
+    let win_sum: f32 = win.iter().sum();
+    let hop = segment_len.saturating_sub(overlap).max(1);
+
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * hop)
+        .take_while(|&start| start + segment_len <= samples.len())
+        .collect();
+
+    let n_bins = segment_len / 2 + 1;
+    let sum = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(segment_len);
+
+            let segment = &samples[start..start + segment_len];
+            let mean = segment.iter().sum::<f32>() / segment_len as f32;
+            let mut input: Vec<f32> = segment
+                .iter()
+                .zip(win.iter())
+                .map(|(&s, &w)| (s - mean) * w)
+                .collect();
+
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            spectrum.iter().map(|c| c.norm_sqr()).collect::<Vec<f32>>()
+        })
+        .reduce(
+            || vec![0.0; n_bins],
+            |mut acc, p| {
+                acc.iter_mut().zip(p).for_each(|(a, b)| *a += b);
+                acc
+            },
+        );
+
+    let scale = 1.0 / (sr * win_power * starts.len().max(1) as f32);
+    let power = sum
+        .iter()
+        .enumerate()
+        .map(|(k, &p)| {
+            // Fold negative frequencies, except at DC and Nyquist
+            let one_sided = if k == 0 || (segment_len.is_multiple_of(2) && k == n_bins - 1) { 1.0 } else { 2.0 };
+            p * scale * one_sided
+        })
+        .collect();
+
+    Psd {
+        freqs: (0..n_bins).map(|k| k as f32 * sr / segment_len as f32).collect(),
+        power,
+        segments: starts.len(),
+        enbw_hz: sr * win_power / (win_sum * win_sum),
+    }
+}
+
+/// Magnitude spectrogram in dB, keeping bins below `max_freq`.
+/// Returns (frame start times, frames × bins).
+pub fn spectrogram_db(
+    samples: &[f32],
+    sample_rate: u32,
+    n_fft: usize,
+    hop_length: usize,
+    window: WindowType,
+    max_freq: f32,
+) -> (Vec<f32>, Vec<Vec<f32>>) {
+    let sr = sample_rate as f32;
+    let window = window.coefficients(n_fft);
+
+    // Limit frequency bins
+    let max_bin = ((max_freq / sr) * n_fft as f32) as usize;
+    let max_bin = max_bin.min(n_fft / 2 + 1);
+
+    // Frame positions
+    let frame_starts: Vec<usize> = (0..)
+        .map(|i| i * hop_length)
+        .take_while(|&start| start + n_fft <= samples.len())
+        .collect();
+
+    // Parallel FFT computation
+    frame_starts
+        .par_iter()
+        .map(|&frame_start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(n_fft);
+
+            let mut input: Vec<f32> = samples[frame_start..frame_start + n_fft]
+                .iter()
+                .zip(window.iter())
+                .map(|(&s, &w)| s * w)
+                .collect();
+
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+
+            let magnitudes: Vec<f32> = spectrum[..max_bin]
+                .iter()
+                .map(|c| 20.0 * (c.norm() + 1e-10).log10())
+                .collect();
+
+            (frame_start as f32 / sr, magnitudes)
+        })
+        .unzip()
+}
+
+/// Rewrite `samples` through a short-time Fourier transform: Hann-windowed
+/// frames of `n_fft` samples every quarter frame are handed to `modify`
+/// (with their index) in order, and the modified spectra are overlap-added
+/// back. With `modify` leaving the spectra alone the output equals the
+/// input; the first frame starts before the signal, so its edges are
+/// covered as fully as the middle.
+pub fn stft_filter(samples: &[f32], n_fft: usize, mut modify: impl FnMut(usize, &mut [Complex<f32>])) -> Vec<f32> {
+    let hop = (n_fft / 4).max(1);
+    let window = WindowType::Hann.coefficients(n_fft);
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(n_fft);
+    let ifft = planner.plan_fft_inverse(n_fft);
+
+    let mut out = vec![0.0f32; samples.len()];
+    let mut weight = vec![0.0f32; samples.len()];
+    let mut spectrum = fft.make_output_vec();
+    let mut frame = vec![0.0f32; n_fft];
+    let first = -(n_fft as isize) + hop as isize;
+    for (index, start) in (first..samples.len() as isize).step_by(hop).enumerate() {
+        for (i, (x, &w)) in frame.iter_mut().zip(&window).enumerate() {
+            *x = usize::try_from(start + i as isize).ok().and_then(|j| samples.get(j)).map_or(0.0, |&s| s * w);
+        }
+        fft.process(&mut frame, &mut spectrum).unwrap();
+        modify(index, &mut spectrum);
+        // The inverse needs real DC and Nyquist bins
+        spectrum[0].im = 0.0;
+        if let Some(last) = spectrum.last_mut() {
+            last.im = 0.0;
+        }
+        ifft.process(&mut spectrum, &mut frame).unwrap();
+        for (i, (&y, &w)) in frame.iter().zip(&window).enumerate() {
+            if let Some(j) = usize::try_from(start + i as isize).ok().filter(|&j| j < samples.len()) {
+                out[j] += y * w / n_fft as f32;
+                weight[j] += w * w;
+            }
+        }
+    }
+    out.iter().zip(&weight).map(|(&y, &w)| if w > 1e-6 { y / w } else { 0.0 }).collect()
+}
@@ -0,0 +1,370 @@
+//! Multi-feature edit-point detection: spectral flux, phase deviation and
+//! MFCC change between neighbouring frames
+
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+const N_FFT: usize = 2048;
+pub const HOP: usize = 512;
+const MEL_BANDS: usize = 26;
+pub const N_MFCC: usize = 13;
+/// Material averaged either side of a boundary for the MFCC comparison
+/// (seconds); long enough that a single drum hit barely moves the mean
+const MFCC_CONTEXT_SECS: f32 = 0.5;
+/// Frames per parallel work block
+const BLOCK: usize = 512;
+/// Span of the local statistics the features are normalized against (seconds)
+const STATS_SPAN: f32 = 8.0;
+/// Closest two reported events may be (seconds)
+const MIN_GAP: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpliceEvent {
+    pub time: f32,
+    pub confidence: f32,     // 0.5 at the detection threshold, approaching 1 above it
+    pub flux_score: f32,     // Robust z-scores of each feature against its surroundings
+    pub phase_score: f32,
+    pub mfcc_score: f32,
+}
+
+/// Triangular mel filterbank over `n_bins` FFT bins
+fn mel_filterbank(sample_rate: u32, n_bins: usize) -> Vec<Vec<(usize, f32)>> {
+    let mel = |f: f32| 2595.0 * (1.0 + f / 700.0).log10();
+    let hz = |m: f32| 700.0 * (10f32.powf(m / 2595.0) - 1.0);
+    let bin_hz = sample_rate as f32 / N_FFT as f32;
+    let (lo, hi) = (mel(20.0), mel((sample_rate as f32 / 2.0).min(16_000.0)));
+    let edges: Vec<f32> = (0..MEL_BANDS + 2)
+        .map(|i| hz(lo + (hi - lo) * i as f32 / (MEL_BANDS + 1) as f32) / bin_hz)
+        .collect();
+
+    (0..MEL_BANDS)
+        .map(|b| {
+            let (left, center, right) = (edges[b], edges[b + 1], edges[b + 2]);
+            (left.ceil() as usize..=(right.floor() as usize).min(n_bins - 1))
+                .filter_map(|k| {
+                    let x = k as f32;
+                    let w = if x <= center { (x - left) / (center - left).max(1e-6) } else { (right - x) / (right - center).max(1e-6) };
+                    (w > 0.0).then_some((k, w))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// MFCCs (without c0, so level changes alone do not register) from a power spectrum
+fn mfcc(power: &[f32], filterbank: &[Vec<(usize, f32)>]) -> [f32; N_MFCC] {
+    let log_mel: Vec<f32> = filterbank
+        .iter()
+        .map(|band| band.iter().map(|&(k, w)| power[k] * w).sum::<f32>().max(1e-12).ln())
+        .collect();
+    let mut coeffs = [0.0f32; N_MFCC];
+    for (c, coeff) in coeffs.iter_mut().enumerate() {
+        *coeff = log_mel
+            .iter()
+            .enumerate()
+            .map(|(m, &v)| v * (PI * (c + 1) as f32 * (m as f32 + 0.5) / MEL_BANDS as f32).cos())
+            .sum();
+    }
+    coeffs
+}
+
+/// Magnitudes and phases of one STFT frame
+struct Frame {
+    mag: Vec<f32>,
+    phase: Vec<f32>,
+}
+
+/// Run `f` on every Hann-windowed STFT frame together with the two frames
+/// before it (fewer at the start), in parallel blocks
+fn scan_frames<T: Send>(samples: &[f32], f: impl Fn(&Frame, &[Frame]) -> T + Sync) -> Vec<T> {
+    let n_frames = if samples.len() >= N_FFT { (samples.len() - N_FFT) / HOP + 1 } else { 0 };
+    let window = WindowType::Hann.coefficients(N_FFT);
+
+    let blocks: Vec<usize> = (0..n_frames).step_by(BLOCK).collect();
+    blocks
+        .par_iter()
+        .flat_map_iter(|&block_start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(N_FFT);
+            let mut spectrum = fft.make_output_vec();
+            // Each block recomputes the two frames preceding it
+            let mut history: Vec<Frame> = Vec::with_capacity(3);
+            let mut out = Vec::with_capacity(BLOCK);
+
+            for k in block_start.saturating_sub(2)..(block_start + BLOCK).min(n_frames) {
+                let start = k * HOP;
+                let mut input: Vec<f32> = samples[start..start + N_FFT].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+                fft.process(&mut input, &mut spectrum).unwrap();
+                let frame = Frame {
+                    mag: spectrum.iter().map(|c| c.norm()).collect(),
+                    phase: spectrum.iter().map(|c| c.arg()).collect(),
+                };
+                if k >= block_start {
+                    out.push(f(&frame, &history));
+                }
+                history.push(frame);
+                if history.len() > 2 {
+                    history.remove(0);
+                }
+            }
+            out
+        })
+        .collect()
+}
+
+/// Phase error of each bin against linear extrapolation from the two previous frames
+fn phase_error(frame: &Frame, p1: &Frame, p2: &Frame, bin: usize) -> f32 {
+    let d = frame.phase[bin] - 2.0 * p1.phase[bin] + p2.phase[bin];
+    (d + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// Per frame: (spectral flux, phase deviation, MFCCs)
+fn frame_features(samples: &[f32], sample_rate: u32) -> Vec<(f32, f32, [f32; N_MFCC])> {
+    let filterbank = mel_filterbank(sample_rate, N_FFT / 2 + 1);
+    scan_frames(samples, |frame, history| {
+        let total: f32 = frame.mag.iter().sum::<f32>().max(1e-12);
+        let flux = history.last().map_or(0.0, |prev| {
+            frame.mag.iter().zip(&prev.mag).map(|(&m, &p)| (m - p).max(0.0)).sum::<f32>() / total
+        });
+        let phase_dev = match history {
+            [p2, p1] => {
+                (0..frame.mag.len())
+                    .map(|b| frame.mag[b] * phase_error(frame, p1, p2, b).abs())
+                    .sum::<f32>()
+                    / total
+            }
+            _ => 0.0,
+        };
+        let power: Vec<f32> = frame.mag.iter().map(|m| m * m).collect();
+        (flux, phase_dev, mfcc(&power, &filterbank))
+    })
+}
+
+/// MFCCs of every frame, `HOP` samples apart
+pub fn mfcc_frames(samples: &[f32], sample_rate: u32) -> Vec<[f32; N_MFCC]> {
+    let filterbank = mel_filterbank(sample_rate, N_FFT / 2 + 1);
+    scan_frames(samples, |frame, _| {
+        let power: Vec<f32> = frame.mag.iter().map(|m| m * m).collect();
+        mfcc(&power, &filterbank)
+    })
+}
+
+/// Distance between the mean MFCCs of the `context` frames before and after each frame
+fn mfcc_change(mfccs: &[[f32; N_MFCC]], context: usize) -> Vec<f32> {
+    let mut prefix = vec![[0.0f32; N_MFCC]; mfccs.len() + 1];
+    for (i, m) in mfccs.iter().enumerate() {
+        for c in 0..N_MFCC {
+            prefix[i + 1][c] = prefix[i][c] + m[c];
+        }
+    }
+    (0..mfccs.len())
+        .map(|k| {
+            if k < context || k + context > mfccs.len() {
+                return 0.0;
+            }
+            (0..N_MFCC)
+                .map(|c| {
+                    let before = (prefix[k][c] - prefix[k - context][c]) / context as f32;
+                    let after = (prefix[k + context][c] - prefix[k][c]) / context as f32;
+                    (after - before).powi(2)
+                })
+                .sum::<f32>()
+                .sqrt()
+        })
+        .collect()
+}
+
+/// Robust z-scores against the median and MAD of the surrounding `span` frames
+fn local_z_scores(values: &[f32], span: usize) -> Vec<f32> {
+    let half = (span / 2).max(1);
+    // Statistics per block of `half` frames, each over the span centered on it
+    let stats: Vec<(f32, f32)> = (0..values.len().div_ceil(half))
+        .into_par_iter()
+        .map(|b| {
+            let center = b * half + half / 2;
+            let lo = center.saturating_sub(half);
+            let hi = (center + half).min(values.len());
+            let mut v = values[lo..hi].to_vec();
+            v.sort_by(f32::total_cmp);
+            let median = v[v.len() / 2];
+            let mut dev: Vec<f32> = v.iter().map(|x| (x - median).abs()).collect();
+            dev.sort_by(f32::total_cmp);
+            // Floor the spread so near-constant features do not turn tiny wobbles into outliers
+            let sigma = (1.4826 * dev[dev.len() / 2]).max(0.1 * median.abs()).max(1e-6);
+            (median, sigma)
+        })
+        .collect();
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let (median, sigma) = stats[i / half];
+            (x - median) / sigma
+        })
+        .collect()
+}
+
+/// Edit points where the spectral envelope changes persistently (MFCC means
+/// before vs after) at the same frame as a flux or phase discontinuity.
+/// Requiring both rejects drum hits (a local discontinuity with the same
+/// material either side) and gradual timbre changes (no discontinuity);
+/// `threshold` applies to the weaker of the two robust z-scores.
+pub fn detect_splices(samples: &[f32], sample_rate: u32, threshold: f32) -> Vec<SpliceEvent> {
+    let sr = sample_rate as f32;
+    let features = frame_features(samples, sample_rate);
+    let context = ((MFCC_CONTEXT_SECS * sr / HOP as f32) as usize).max(1);
+    if features.len() < 2 * context {
+        return Vec::new();
+    }
+    let span = (STATS_SPAN * sr / HOP as f32) as usize;
+    let flux = local_z_scores(&features.iter().map(|f| f.0).collect::<Vec<_>>(), span);
+    let phase = local_z_scores(&features.iter().map(|f| f.1).collect::<Vec<_>>(), span);
+    let mfccs: Vec<[f32; N_MFCC]> = features.iter().map(|f| f.2).collect();
+    let change = local_z_scores(&mfcc_change(&mfccs, context), span);
+
+    let combined: Vec<f32> = (0..features.len())
+        .map(|k| {
+            change[k].min(flux[k].max(phase[k]))
+        })
+        .collect();
+
+    // Strongest frame within each neighbourhood above the threshold
+    let gap = ((MIN_GAP * sr / HOP as f32) as usize).max(1);
+    let mut events: Vec<SpliceEvent> = Vec::new();
+    let mut last: Option<usize> = None;
+    for k in 0..combined.len() {
+        if combined[k] < threshold {
+            continue;
+        }
+        let event = SpliceEvent {
+            time: (k * HOP + N_FFT / 2) as f32 / sr,
+            confidence: 1.0 / (1.0 + (threshold - combined[k]).exp()),
+            flux_score: flux[k],
+            phase_score: phase[k],
+            mfcc_score: change[k],
+        };
+        match (last, events.last_mut()) {
+            (Some(prev), Some(prev_event)) if k - prev < gap => {
+                if event.confidence > prev_event.confidence {
+                    *prev_event = event;
+                    last = Some(k);
+                }
+            }
+            _ => {
+                events.push(event);
+                last = Some(k);
+            }
+        }
+    }
+    events
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseBand {
+    Low,  // 60-500 Hz
+    Mid,  // 500-4000 Hz
+}
+
+impl PhaseBand {
+    fn range(self) -> (f32, f32) {
+        match self {
+            PhaseBand::Low => (60.0, 500.0),
+            PhaseBand::Mid => (500.0, 4000.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseReset {
+    pub time: f32,
+    pub band: PhaseBand,
+    pub coherence_before: f32,  // Typical coherence of the material either side
+    pub coherence_after: f32,
+    pub coherence_at: f32,      // Coherence across the reset
+}
+
+/// Frames either side of a reset that its dip can reach: a cut is inside
+/// four overlapping frames, and each coherence value spans three frames
+const RESET_REACH: usize = N_FFT / HOP + 2;
+
+/// Magnitude-weighted coherence of the phase evolution in a band: 1 when
+/// every component's phase advances as extrapolated from the previous two
+/// frames (steady partials), near 0 for noise or a phase reset
+fn band_coherence(frame: &Frame, history: &[Frame], bins: (usize, usize)) -> Option<f32> {
+    let [p2, p1] = history else { return None };
+    let (mut re, mut im, mut total) = (0.0f32, 0.0f32, 0.0f32);
+    for b in bins.0..bins.1 {
+        let e = phase_error(frame, p1, p2, b);
+        re += frame.mag[b] * e.cos();
+        im += frame.mag[b] * e.sin();
+        total += frame.mag[b];
+    }
+    (total > 1e-6).then(|| re.hypot(im) / total)
+}
+
+/// Places where phase coherence in the low or mid band collapses between
+/// stretches of coherent material. Cut-and-paste edits reset the phase of
+/// every partial at once, which shows up here even when levels match.
+/// `min_drop` is the required fall below the weaker side's coherence.
+pub fn phase_resets(samples: &[f32], sample_rate: u32, min_drop: f32) -> Vec<PhaseReset> {
+    let sr = sample_rate as f32;
+    let bin = |f: f32| ((f * N_FFT as f32 / sr) as usize).min(N_FFT / 2);
+    let bands = [PhaseBand::Low, PhaseBand::Mid];
+    let coherence: Vec<[Option<f32>; 2]> = scan_frames(samples, |frame, history| {
+        bands.map(|band| {
+            let (lo, hi) = band.range();
+            let bins = (bin(lo), bin(hi));
+            if bins.0 >= bins.1 { None } else { band_coherence(frame, history, bins) }
+        })
+    });
+
+    let context = ((0.5 * sr / HOP as f32) as usize).max(1);
+    let gap = ((MIN_GAP * sr / HOP as f32) as usize).max(1);
+    let median = |values: Vec<f32>| -> Option<f32> {
+        let mut v = values;
+        v.sort_by(f32::total_cmp);
+        v.get(v.len() / 2).copied()
+    };
+
+    let mut resets: Vec<PhaseReset> = Vec::new();
+    for (i, &band) in bands.iter().enumerate() {
+        let series: Vec<Option<f32>> = coherence.iter().map(|c| c[i]).collect();
+        let mut last: Option<(usize, f32)> = None;
+        for k in RESET_REACH + context..series.len().saturating_sub(RESET_REACH + context) {
+            let Some(at) = series[k] else { continue };
+            let before = median(series[k - RESET_REACH - context..k - RESET_REACH].iter().flatten().copied().collect());
+            let after = median(series[k + RESET_REACH..k + RESET_REACH + context].iter().flatten().copied().collect());
+            let (Some(before), Some(after)) = (before, after) else { continue };
+            let drop = before.min(after) - at;
+            if drop < min_drop {
+                continue;
+            }
+
+            let reset = PhaseReset {
+                time: (k * HOP + N_FFT / 2) as f32 / sr,
+                band,
+                coherence_before: before,
+                coherence_after: after,
+                coherence_at: at,
+            };
+            match last {
+                Some((prev, prev_drop)) if k - prev < gap => {
+                    if drop > prev_drop {
+                        *resets.last_mut().unwrap() = reset;
+                        last = Some((k, drop));
+                    }
+                }
+                _ => {
+                    resets.push(reset);
+                    last = Some((k, drop));
+                }
+            }
+        }
+    }
+    resets.sort_by(|a, b| a.time.total_cmp(&b.time));
+    resets
+}
@@ -0,0 +1,183 @@
+//! Screening for data hidden in the least significant bits of PCM
+
+use crate::segments::TimeRange;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Regions scored separately (seconds)
+const REGION_SECS: f32 = 2.0;
+/// Samples a region needs for its statistics to mean anything
+const MIN_SAMPLES: usize = 2048;
+/// Sample pair analysis needs this share of neighbouring samples in the
+/// same value pair; louder audio moves too far between samples, and its
+/// LSBs are noise anyway
+const MIN_SAME_PAIR_SHARE: f64 = 0.15;
+/// Values within this many LSBs of zero make the value histogram, which
+/// must hold this many samples and be this uneven between neighbouring
+/// values (mean share) to show a pairing
+const HISTOGRAM_RANGE_LSB: i64 = 32;
+const MIN_HISTOGRAM_SAMPLES: usize = 1024;
+const MIN_HISTOGRAM_IMBALANCE: f32 = 0.05;
+/// A region is flagged from this score
+const FLAG_SCORE: f32 = 0.2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StegoRegion {
+    pub channel: usize,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub rms_lsb: f32,                   // Level in LSBs of the word
+    pub lsb_entropy: f32,               // LSB plane entropy from 8-bit words (bits per bit)
+    pub embedding_rate: Option<f32>,    // Sample pair analysis estimate of samples carrying data (0-1)
+    pub pair_ratio: Option<f32>,        // Unevenness of values (2k, 2k+1) over (2k-1, 2k): about 1 naturally
+    pub assessable: bool,               // Quiet enough for either statistic
+    pub score: f32,                     // 0 (clean) to 1 (data very likely)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StegoReport {
+    pub bits: u32,                      // Word length screened
+    pub regions: Vec<StegoRegion>,
+    pub assessable_fraction: f32,       // Of the regions, over all channels
+    pub flagged: Vec<TimeRange>,        // Merged over channels
+    pub max_score: f32,
+    pub likely_embedded: bool,
+}
+
+/// Integer codes of `samples` at `bits`, or `None` when they are not on
+/// that grid (float data or a different word length)
+fn integer_codes(samples: &[f32], bits: u32) -> Option<Vec<i64>> {
+    let scale = 2f64.powi(bits as i32 - 1);
+    let codes: Vec<i64> = samples.iter().map(|&v| (v as f64 * scale).round() as i64).collect();
+    let off_grid = samples.iter().zip(&codes).filter(|(v, n)| (**v as f64 * scale - **n as f64).abs() > 1e-3).count();
+    (off_grid * 1000 <= samples.len()).then_some(codes)
+}
+
+/// Fraction of samples carrying embedded bits, by sample pair analysis
+/// (Dumitrescu, Wu and Wang) on neighbouring samples: LSB replacement
+/// moves pairs between classes that a natural signal keeps in balance.
+/// `None` when too few neighbours are close for the estimate to hold.
+fn sample_pair_rate(codes: &[i64]) -> Option<f32> {
+    let (mut x, mut y, mut k) = (0f64, 0f64, 0f64);
+    for w in codes.windows(2) {
+        let (r, s) = (w[0], w[1]);
+        let even = s.rem_euclid(2) == 0;
+        if (even && r < s) || (!even && r > s) {
+            x += 1.0;
+        }
+        if (even && r > s) || (!even && r < s) {
+            y += 1.0;
+        }
+        if r.div_euclid(2) == s.div_euclid(2) {
+            k += 1.0;
+        }
+    }
+    let pairs = (codes.len() - 1) as f64;
+    if k < MIN_SAME_PAIR_SHARE * pairs {
+        return None;
+    }
+    let (a, b, c) = (2.0 * k, 2.0 * (2.0 * x - pairs), y - x);
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Some(0.0);
+    }
+    let roots = [(-b + discriminant.sqrt()) / (2.0 * a), (-b - discriminant.sqrt()) / (2.0 * a)];
+    // The smaller root is the share of samples flipped, half the rate
+    Some((2.0 * roots[0].min(roots[1])).clamp(0.0, 1.0) as f32)
+}
+
+/// Entropy of the LSB plane read as 8-bit words, per bit
+fn lsb_entropy(codes: &[i64]) -> f32 {
+    let mut counts = [0usize; 256];
+    for word in codes.chunks_exact(8) {
+        let byte = word.iter().fold(0usize, |b, n| (b << 1) | (n & 1) as usize);
+        counts[byte] += 1;
+    }
+    let total = (codes.len() / 8).max(1) as f64;
+    let entropy: f64 = counts.iter().filter(|&&c| c > 0).map(|&c| -(c as f64 / total) * (c as f64 / total).log2()).sum();
+    (entropy / 8.0) as f32
+}
+
+/// How unequal the counts of the value pairs (2k, 2k + 1) near zero are
+/// against the pairs (2k - 1, 2k) straddling them. Replacing LSBs swaps
+/// values within the first kind of pair only, evening those out, so the
+/// ratio falls from about 1 towards 0 as more samples carry data.
+fn pair_ratio(codes: &[i64]) -> Option<f32> {
+    let mut histogram = vec![0usize; 2 * HISTOGRAM_RANGE_LSB as usize + 2];
+    for &n in codes.iter().filter(|n| (-HISTOGRAM_RANGE_LSB..=HISTOGRAM_RANGE_LSB).contains(*n)) {
+        histogram[(n + HISTOGRAM_RANGE_LSB) as usize] += 1;
+    }
+    let imbalance = |pairs: &[usize]| {
+        let (difference, total) = pairs
+            .chunks_exact(2)
+            .fold((0usize, 0usize), |(d, t), pair| (d + pair[0].abs_diff(pair[1]), t + pair[0] + pair[1]));
+        difference as f32 / total.max(1) as f32
+    };
+    // HISTOGRAM_RANGE_LSB is even, so index 0 holds an even value
+    let straddling = imbalance(&histogram[1..histogram.len() - 1]);
+    let total: usize = histogram.iter().sum();
+    (total >= MIN_HISTOGRAM_SAMPLES && straddling >= MIN_HISTOGRAM_IMBALANCE).then(|| imbalance(&histogram) / straddling)
+}
+
+fn score_region(codes: &[i64], sample_rate: f32, channel: usize, start: usize) -> StegoRegion {
+    let rms_lsb = (codes.iter().map(|&n| (n * n) as f64).sum::<f64>() / codes.len() as f64).sqrt() as f32;
+    let embedding_rate = sample_pair_rate(codes);
+    let pair_ratio = pair_ratio(codes);
+    let score = embedding_rate.unwrap_or(0.0).max(pair_ratio.map_or(0.0, |r| (1.0 - r).clamp(0.0, 1.0)));
+    StegoRegion {
+        channel,
+        start_time: start as f32 / sample_rate,
+        end_time: (start + codes.len()) as f32 / sample_rate,
+        rms_lsb,
+        lsb_entropy: lsb_entropy(codes),
+        embedding_rate,
+        pair_ratio,
+        assessable: embedding_rate.is_some() || pair_ratio.is_some(),
+        score,
+    }
+}
+
+/// Screen integer PCM for data hidden by LSB replacement. Each channel is
+/// cut into 2 s regions and each scored two ways: sample pair analysis
+/// estimates the share of samples carrying data from how neighbouring
+/// values pair up, and in the noise floor (quiet regions) the low bits of a
+/// natural recording are lopsided (digital silence, dither that favours
+/// some values), where embedding leaves a random LSB plane and evens out
+/// the counts of each value pair. A screening: flagged regions deserve a
+/// look with the right extraction tool, not a conclusion. `None` for float
+/// data, which has no LSB to embed in.
+pub fn screen(channels: &[Vec<f32>], sample_rate: u32, bits: u32) -> Option<StegoReport> {
+    let sr = sample_rate as f32;
+    let codes: Vec<Vec<i64>> = channels.iter().map(|c| integer_codes(c, bits)).collect::<Option<_>>()?;
+    let region = ((REGION_SECS * sr) as usize).max(MIN_SAMPLES);
+
+    let mut regions: Vec<StegoRegion> = codes
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(channel, codes)| {
+            codes
+                .chunks(region)
+                .enumerate()
+                .filter(|(_, c)| c.len() >= MIN_SAMPLES)
+                .map(move |(i, chunk)| score_region(chunk, sr, channel, i * region))
+        })
+        .collect();
+    regions.sort_by(|a, b| a.start_time.total_cmp(&b.start_time).then(a.channel.cmp(&b.channel)));
+
+    let mut flagged: Vec<TimeRange> = Vec::new();
+    for r in regions.iter().filter(|r| r.score >= FLAG_SCORE) {
+        match flagged.last_mut() {
+            Some(last) if r.start_time <= last.end_time => last.end_time = last.end_time.max(r.end_time),
+            _ => flagged.push(TimeRange { start_time: r.start_time, end_time: r.end_time }),
+        }
+    }
+    let max_score = regions.iter().map(|r| r.score).fold(0.0, f32::max);
+    Some(StegoReport {
+        bits,
+        assessable_fraction: regions.iter().filter(|r| r.assessable).count() as f32 / regions.len().max(1) as f32,
+        likely_embedded: !flagged.is_empty(),
+        regions,
+        flagged,
+        max_score,
+    })
+}
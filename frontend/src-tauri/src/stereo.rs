@@ -0,0 +1,381 @@
+//! Two-channel relationship measurements (correlation, width, balance)
+
+use crate::align;
+use crate::segments::{self, TimeRange};
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Energy below which a window is treated as silent
+const SILENCE_ENERGY: f32 = 1e-10;
+
+/// Longest excerpt used for the stereo relationship tests
+const MAX_EXCERPT_SECONDS: f32 = 30.0;
+
+/// Largest inter-channel delay searched for delayed-copy pseudo-stereo
+const MAX_DELAY_SECONDS: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StereoRelationship {
+    /// Both channels carry the same signal (possibly at different gain)
+    DuplicatedMono,
+    /// One channel is the inverted copy of the other
+    InvertedMono,
+    /// One channel is a time-shifted copy of the other
+    DelayedCopy,
+    /// Side signal is a filtered version of mid (comb/phase-shift or decorrelator upmix)
+    MidDerived,
+    /// Independent content in both channels
+    TrueStereo,
+    /// Too little signal to decide
+    Silent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StereoReport {
+    pub relationship: StereoRelationship,
+    pub correlation: f32,
+    pub peak_xcorr: f32,         // Normalized cross-correlation at the best lag
+    pub delay_samples: i64,      // Positive when the right channel lags
+    pub delay_ms: f32,
+    pub gain_difference_db: f32, // Right relative to left
+    pub side_to_mid_db: f32,
+    pub mid_side_coherence: f32, // Power-weighted magnitude-squared coherence
+}
+
+/// Pearson-style correlation coefficient of two equally long signals
+/// (+1 identical, 0 unrelated, -1 inverted); 0 for silence
+pub fn correlation(left: &[f32], right: &[f32]) -> f32 {
+    let (lr, ll, rr) = left
+        .iter()
+        .zip(right)
+        .fold((0.0f64, 0.0f64, 0.0f64), |(lr, ll, rr), (&l, &r)| {
+            let (l, r) = (l as f64, r as f64);
+            (lr + l * r, ll + l * l, rr + r * r)
+        });
+    let norm = (ll * rr).sqrt();
+    if norm <= SILENCE_ENERGY as f64 {
+        0.0
+    } else {
+        (lr / norm) as f32
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelDropout {
+    pub channel: usize,
+    pub start_time: f32,
+    pub end_time: f32,
+}
+
+#[derive(Serialize)]
+pub struct BalanceReport {
+    pub times: Vec<f32>,                  // Window start times
+    pub channel_levels_db: Vec<Vec<f32>>, // Per channel, per window RMS (dBFS)
+    pub overall_db: Vec<f32>,             // Per channel RMS over the file
+    pub balance_db: Vec<f32>,             // Channel 1 minus channel 0 per window
+    pub dropouts: Vec<ChannelDropout>,
+}
+
+#[derive(Serialize)]
+pub struct WidthTrack {
+    pub times: Vec<f32>,            // Window centers
+    pub width_db: Vec<f32>,         // Side/mid energy ratio, clamped to ±60 dB
+    pub discontinuities: Vec<f32>,  // Times of abrupt width changes
+    pub window: f32,
+}
+
+/// Mid (L + R) / 2 and side (L - R) / 2 signals
+pub fn mid_side(left: &[f32], right: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    left.iter()
+        .zip(right)
+        .map(|(&l, &r)| ((l + r) * 0.5, (l - r) * 0.5))
+        .unzip()
+}
+
+/// Start indices of `window`-long frames advancing by `hop`
+pub fn frame_starts(len: usize, window: usize, hop: usize) -> Vec<usize> {
+    (0..)
+        .map(|i| i * hop.max(1))
+        .take_while(|&start| start + window <= len)
+        .collect()
+}
+
+/// Windowed correlation coefficient between two channels.
+/// Returns (window center times, coefficients).
+pub fn correlation_track(left: &[f32], right: &[f32], sample_rate: u32, window: usize, hop: usize) -> (Vec<f32>, Vec<f32>) {
+    let len = left.len().min(right.len());
+    let sr = sample_rate as f32;
+    frame_starts(len, window, hop)
+        .par_iter()
+        .map(|&start| {
+            let end = start + window;
+            ((start + window / 2) as f32 / sr, correlation(&left[start..end], &right[start..end]))
+        })
+        .unzip()
+}
+
+/// Classify how the two channels relate: duplicated or delayed copies,
+/// mid-derived upmixes, or genuine stereo
+pub fn analyze_relationship(left: &[f32], right: &[f32], sample_rate: u32) -> StereoReport {
+    let sr = sample_rate as f32;
+    let len = left.len().min(right.len());
+    // Centered excerpt keeps long files fast
+    let excerpt = ((MAX_EXCERPT_SECONDS * sr) as usize).min(len);
+    let start = (len - excerpt) / 2;
+    let (left, right) = (&left[start..start + excerpt], &right[start..start + excerpt]);
+
+    let energy = |x: &[f32]| x.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>();
+    let (el, er) = (energy(left), energy(right));
+    let (mid, side) = mid_side(left, right);
+    let (em, es) = (energy(&mid), energy(&side));
+
+    let correlation = correlation(left, right);
+    let gain_difference_db = 10.0 * ((er.max(1e-20) / el.max(1e-20)).log10()) as f32;
+    let side_to_mid_db = (10.0 * ((es.max(1e-20) / em.max(1e-20)).log10()) as f32).clamp(-120.0, 120.0);
+
+    let max_lag = (MAX_DELAY_SECONDS * sr) as usize;
+    let (delay_samples, peak_xcorr) = align::xcorr_lag(left, right, max_lag);
+    // Align side to mid first so delay-based upmixes stay coherent per segment
+    let (side_lag, _) = align::xcorr_lag(&mid, &side, max_lag);
+    let shift = side_lag.unsigned_abs() as usize;
+    let mid_side_coherence = if side_lag >= 0 {
+        coherence(&mid[..excerpt - shift], &side[shift..], 4096)
+    } else {
+        coherence(&mid[shift..], &side[..excerpt - shift], 4096)
+    };
+
+    let relationship = if el.min(er) < SILENCE_ENERGY as f64 * excerpt as f64 {
+        StereoRelationship::Silent
+    } else if correlation > 0.999 {
+        StereoRelationship::DuplicatedMono
+    } else if correlation < -0.999 {
+        StereoRelationship::InvertedMono
+    } else if delay_samples != 0 && peak_xcorr.abs() > 0.95 {
+        StereoRelationship::DelayedCopy
+    } else if mid_side_coherence > 0.9 {
+        StereoRelationship::MidDerived
+    } else {
+        StereoRelationship::TrueStereo
+    };
+
+    StereoReport {
+        relationship,
+        correlation,
+        peak_xcorr,
+        delay_samples,
+        delay_ms: delay_samples as f32 * 1000.0 / sr,
+        gain_difference_db,
+        side_to_mid_db,
+        mid_side_coherence,
+    }
+}
+
+/// Welch spectra per bin, summed over the segments
+pub struct CrossSpectra {
+    pub pxx: Vec<f64>,
+    pub pyy: Vec<f64>,
+    pub pxy: Vec<Complex<f64>>,     // conj(X)·Y
+}
+
+/// Welch auto- and cross-spectra of `x` and `y` over Hann-windowed,
+/// half-overlapping segments; `None` when the signals are shorter than one
+/// segment
+pub fn cross_spectra(x: &[f32], y: &[f32], segment_len: usize) -> Option<CrossSpectra> {
+    let len = x.len().min(y.len());
+    let window = WindowType::Hann.coefficients(segment_len);
+    let starts = frame_starts(len, segment_len, segment_len / 2);
+    if starts.is_empty() {
+        return None;
+    }
+
+    let n_bins = segment_len / 2 + 1;
+    let zero = || (vec![0.0f64; n_bins], vec![0.0f64; n_bins], vec![Complex::new(0.0f64, 0.0); n_bins]);
+    let (pxx, pyy, pxy) = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(segment_len);
+            let spectrum = |sig: &[f32]| {
+                let mut input: Vec<f32> = sig[start..start + segment_len]
+                    .iter()
+                    .zip(&window)
+                    .map(|(&s, &w)| s * w)
+                    .collect();
+                let mut out = fft.make_output_vec();
+                fft.process(&mut input, &mut out).unwrap();
+                out
+            };
+            let (sx, sy) = (spectrum(x), spectrum(y));
+            let mut acc = zero();
+            for k in 0..n_bins {
+                let (a, b) = (sx[k], sy[k]);
+                acc.0[k] = a.norm_sqr() as f64;
+                acc.1[k] = b.norm_sqr() as f64;
+                let c = a.conj() * b;
+                acc.2[k] = Complex::new(c.re as f64, c.im as f64);
+            }
+            acc
+        })
+        .reduce(zero, |mut a, b| {
+            for k in 0..n_bins {
+                a.0[k] += b.0[k];
+                a.1[k] += b.1[k];
+                a.2[k] += b.2[k];
+            }
+            a
+        });
+    Some(CrossSpectra { pxx, pyy, pxy })
+}
+
+/// Magnitude-squared coherence of `x` and `y` from Welch cross-spectra,
+/// averaged across frequency with weights proportional to the power of `x`
+pub fn coherence(x: &[f32], y: &[f32], segment_len: usize) -> f32 {
+    let Some(CrossSpectra { pxx, pyy, pxy }) = cross_spectra(x, y, segment_len) else {
+        return 0.0;
+    };
+    let n_bins = segment_len / 2 + 1;
+
+    let (mut weighted, mut total) = (0.0f64, 0.0f64);
+    for k in 1..n_bins {
+        let denom = pxx[k] * pyy[k];
+        if denom > 0.0 {
+            weighted += pxx[k] * pxy[k].norm_sqr() / denom;
+            total += pxx[k];
+        }
+    }
+    if total > 0.0 { (weighted / total) as f32 } else { 0.0 }
+}
+
+/// Windowed per-channel RMS levels plus intervals where a channel falls below
+/// `silence_db` while another stays at least 20 dB above that threshold
+pub fn channel_balance(
+    channels: &[Vec<f32>],
+    sample_rate: u32,
+    window: usize,
+    silence_db: f32,
+    min_dropout: f32,
+) -> BalanceReport {
+    let sr = sample_rate as f32;
+    let window = window.max(1);
+    let to_db = |power: f64| (10.0 * power.max(1e-20).log10()) as f32;
+    let rms_db = |x: &[f32]| to_db(x.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>() / x.len().max(1) as f64);
+
+    let channel_levels_db: Vec<Vec<f32>> = channels
+        .par_iter()
+        .map(|c| c.chunks(window).map(rms_db).collect())
+        .collect();
+    let overall_db = channels.iter().map(|c| rms_db(c)).collect();
+    let n_windows = channel_levels_db.iter().map(Vec::len).min().unwrap_or(0);
+    let times = (0..n_windows).map(|i| (i * window) as f32 / sr).collect();
+
+    let balance_db = if channels.len() >= 2 {
+        (0..n_windows).map(|i| channel_levels_db[1][i] - channel_levels_db[0][i]).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut dropouts = Vec::new();
+    let min_windows = ((min_dropout * sr) as usize).div_ceil(window);
+    for (ch, levels) in channel_levels_db.iter().enumerate() {
+        let dropped: Vec<bool> = (0..n_windows)
+            .map(|i| {
+                levels[i] < silence_db
+                    && channel_levels_db
+                        .iter()
+                        .enumerate()
+                        .any(|(other, l)| other != ch && l[i] > silence_db + 20.0)
+            })
+            .collect();
+        let runs = segments::flag_runs(&dropped, min_windows);
+        dropouts.extend(
+            segments::runs_to_ranges(&runs, window, window, sample_rate)
+                .into_iter()
+                .map(|range| ChannelDropout {
+                    channel: ch,
+                    start_time: range.start_time,
+                    end_time: range.end_time,
+                }),
+        );
+    }
+    dropouts.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+
+    BalanceReport {
+        times,
+        channel_levels_db,
+        overall_db,
+        balance_db,
+        dropouts,
+    }
+}
+
+/// Sections where the two channels are polarity-inverted copies of each
+/// other: short-term correlation below `threshold` with levels within 3 dB
+pub fn polarity_inversions(
+    left: &[f32],
+    right: &[f32],
+    sample_rate: u32,
+    window: usize,
+    threshold: f32,
+) -> Vec<TimeRange> {
+    let len = left.len().min(right.len());
+    let hop = (window / 2).max(1);
+    let energy = |x: &[f32]| x.iter().map(|&s| s * s).sum::<f32>();
+
+    let inverted: Vec<bool> = frame_starts(len, window, hop)
+        .par_iter()
+        .map(|&start| {
+            let (l, r) = (&left[start..start + window], &right[start..start + window]);
+            let (el, er) = (energy(l), energy(r));
+            if el.min(er) <= SILENCE_ENERGY * window as f32 {
+                return false;
+            }
+            let level_diff_db = 10.0 * (er / el).log10();
+            correlation(l, r) < threshold && level_diff_db.abs() < 3.0
+        })
+        .collect();
+
+    // Require a few consecutive windows so isolated transients do not count
+    let runs = segments::flag_runs(&inverted, 3);
+    segments::runs_to_ranges(&runs, hop, window, sample_rate)
+}
+
+/// Side/mid energy ratio per window, with jumps of more than `jump_db`
+/// between adjacent non-silent windows reported as discontinuities
+pub fn width_track(left: &[f32], right: &[f32], sample_rate: u32, window: usize, jump_db: f32) -> WidthTrack {
+    let sr = sample_rate as f32;
+    let len = left.len().min(right.len());
+    let hop = (window / 2).max(1);
+
+    // (center time, width in dB, whether the window carries signal)
+    let frames: Vec<(f32, f32, bool)> = frame_starts(len, window, hop)
+        .par_iter()
+        .map(|&start| {
+            let (mut em, mut es) = (0.0f32, 0.0f32);
+            for i in start..start + window {
+                let (m, s) = ((left[i] + right[i]) * 0.5, (left[i] - right[i]) * 0.5);
+                em += m * m;
+                es += s * s;
+            }
+            let audible = em + es > SILENCE_ENERGY * window as f32;
+            let width = (10.0 * (es.max(1e-20) / em.max(1e-20)).log10()).clamp(-60.0, 60.0);
+            ((start + window / 2) as f32 / sr, if audible { width } else { -60.0 }, audible)
+        })
+        .collect();
+
+    let discontinuities = frames
+        .windows(2)
+        .filter(|w| w[0].2 && w[1].2 && (w[1].1 - w[0].1).abs() > jump_db)
+        .map(|w| (w[0].0 + w[1].0) / 2.0)
+        .collect();
+
+    WidthTrack {
+        times: frames.iter().map(|f| f.0).collect(),
+        width_db: frames.iter().map(|f| f.1).collect(),
+        discontinuities,
+        window: window as f32 / sr,
+    }
+}
@@ -0,0 +1,319 @@
+//! Time-scale modification (phase vocoder / WSOLA) artifact detection
+
+use crate::pitch::{self, HOP_SECS};
+use crate::spectrum::{self, WindowType};
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Spacing of the band-level series (seconds)
+const LEVEL_STEP_SECS: f32 = 0.001;
+/// Frame length of the level spectrogram (seconds, rounded up to a power of two)
+const LEVEL_FRAME_SECS: f32 = 0.004;
+/// Bands the spectrum is split into, each followed on its own
+const N_BANDS: usize = 16;
+/// Window whose mean level is taken out, removing syllable-rate changes (seconds)
+const LEVEL_DETREND_SECS: f32 = 0.1;
+/// Segment of the modulation spectrum over the whole audio and within a block (level steps)
+const MODULATION_SEGMENT: usize = 8192;
+const BLOCK_SEGMENT: usize = 2048;
+/// Frame rates searched for (Hz): synthesis hops of 4 to 100 ms
+const FRAME_RATE_RANGE: (f32, f32) = (10.0, 250.0);
+/// Height of a frame-rate line over the flux spectrum around it (dB)
+const MIN_LINE_DB: f32 = 8.0;
+/// Same, within one block
+const MIN_BLOCK_LINE_DB: f32 = 5.0;
+/// Neighbourhood the line is compared with (Hz either side)
+const LINE_NEIGHBOURHOOD_HZ: f32 = 5.0;
+/// Block the modulation is judged and reported in, and the step between
+/// blocks (seconds)
+const BLOCK_SECS: f32 = 8.0;
+const BLOCK_STEP_SECS: f32 = 4.0;
+/// Most audio examined (seconds)
+const MAX_SECS: f32 = 120.0;
+/// Onsets rise by at least this much within the look-back (dB)
+const ONSET_RISE_DB: f32 = 20.0;
+const ONSET_LOOKBACK_SECS: f32 = 0.05;
+/// Band whose envelope times the onsets (Hz and up)
+const ONSET_BAND_HZ: f32 = 2000.0;
+/// Least onsets for an attack time, and the share of the sharpest used
+const MIN_ONSETS: usize = 5;
+const SHARPEST_SHARE: f32 = 0.25;
+/// Attack time beyond which even the sharpest onsets are smeared (ms)
+const MAX_ATTACK_MS: f32 = 10.0;
+/// Shortest repeat lag looked for (seconds); shorter lags follow the
+/// spectrum's own correlation
+const MIN_REPEAT_SECS: f32 = 0.001;
+/// Block length of the repeat search (samples)
+const REPEAT_BLOCK: usize = 1 << 16;
+/// Height of the repeat-lag peak over the median of the lag range
+const MIN_REPEAT_RATIO: f32 = 4.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StretchRegion {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub modulation_db: f32,     // Frame-rate line over the block's modulation spectrum
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StretchReport {
+    pub stretched: bool,
+    pub frame_period_ms: Option<f32>,       // Period of the frame-rate modulation
+    pub modulation_db: f32,                 // Strongest frame-rate line over the modulation spectrum around it
+    pub attack_ms: Option<f32>,             // Rise time of the sharpest onsets (top band)
+    pub smeared_transients: bool,
+    pub repeat_lag_ms: Option<f32>,         // Offset of material repeated across frame boundaries
+    pub stretch_if_slowed: Option<f32>,     // New over original duration, if the material was slowed down
+    pub stretch_if_sped_up: Option<f32>,    // Same, if it was sped up; the repeat cannot tell the two apart
+    pub regions: Vec<StretchRegion>,
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    let (_, &mut m, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    m
+}
+
+/// Height of the spectrum at `bin` over the median of its neighbourhood (dB)
+fn line_height(db: &[f32], bin: usize, neighbourhood: usize) -> f32 {
+    if bin < neighbourhood || bin + neighbourhood >= db.len() {
+        return 0.0;
+    }
+    let line = db[bin - 1..=bin + 1].iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let mut around: Vec<f32> = db[bin - neighbourhood..bin - 2].iter().chain(&db[bin + 3..=bin + neighbourhood]).copied().collect();
+    line - median(&mut around)
+}
+
+/// Modulation spectrum (dB) of the detrended band levels: the power
+/// spectra of the bands are summed, so a modulation at one rate adds up
+/// whatever its phase in each band
+fn modulation_spectrum(levels: &[Vec<f32>], rate: u32, segment: usize) -> Vec<f32> {
+    let mut total = vec![0.0f32; segment / 2 + 1];
+    for band in levels {
+        let psd = spectrum::welch_psd(band, rate, segment, segment / 2, WindowType::Hann);
+        total.iter_mut().zip(&psd.power).for_each(|(t, p)| *t += p);
+    }
+    total.iter().map(|p| 10.0 * p.max(1e-30).log10()).collect()
+}
+
+/// Magnitude, per lag, of the frame-rate component of the products
+/// x[n]·x[n + lag]: material repeated at a fixed offset across frame
+/// boundaries correlates at that offset only where frames overlap
+fn repeat_profile(x: &[f32], frame_rate: f32, sample_rate: f32, max_lag: usize) -> Vec<f32> {
+    let n = 2 * REPEAT_BLOCK;
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n);
+    let inverse = planner.plan_fft_inverse(n);
+    let w = 2.0 * std::f32::consts::PI * frame_rate / sample_rate;
+
+    let correlate = |a: &[f32], b: &[f32]| {
+        let spectrum_of = |x: &[f32]| {
+            let mut buf = vec![0.0f32; n];
+            buf[..x.len()].copy_from_slice(x);
+            let mut out = forward.make_output_vec();
+            forward.process(&mut buf, &mut out).unwrap();
+            out
+        };
+        let mut cross: Vec<_> = spectrum_of(a).iter().zip(&spectrum_of(b)).map(|(x, y)| x.conj() * y).collect();
+        let last = cross.len() - 1;
+        cross[0].im = 0.0;
+        cross[last].im = 0.0;
+        let mut xcorr = inverse.make_output_vec();
+        inverse.process(&mut cross, &mut xcorr).unwrap();
+        xcorr.truncate(max_lag);
+        xcorr
+    };
+
+    // Magnitudes are summed over the blocks: the frame rate is not known
+    // precisely enough to keep its phase over the whole file
+    let mut profile = vec![0.0f32; max_lag];
+    let mut start = 0;
+    while start + max_lag < x.len() {
+        let end = (start + REPEAT_BLOCK).min(x.len() - max_lag);
+        let block = &x[start..end];
+        let ahead = &x[start..end + max_lag];
+        let cos: Vec<f32> = block.iter().enumerate().map(|(i, v)| v * (w * i as f32).cos()).collect();
+        let sin: Vec<f32> = block.iter().enumerate().map(|(i, v)| v * (w * i as f32).sin()).collect();
+        let (re, im) = (correlate(&cos, ahead), correlate(&sin, ahead));
+        profile.iter_mut().zip(re.iter().zip(&im)).for_each(|(p, (r, i))| *p += r.hypot(*i));
+        start = end;
+    }
+    profile
+}
+
+/// Look for the traces time-scale modification leaves: band levels
+/// modulated at the frame rate of the overlap-add (phase vocoder
+/// phasiness, WSOLA crossfades), onsets smeared over a frame
+/// length, and material repeated across frame boundaries at a fixed
+/// offset. The offset, with the frame period, gives the stretch factor
+/// (OLA and WSOLA in noise-like passages; a phase vocoder repeats
+/// nothing, and WSOLA splices voiced speech seamlessly at whole pitch
+/// periods). Flagged blocks that touch are merged into one region.
+pub fn detect_time_stretch(samples: &[f32], sample_rate: u32) -> StretchReport {
+    let sr = sample_rate as f32;
+    let samples = &samples[..samples.len().min((MAX_SECS * sr) as usize)];
+    let mut report = StretchReport {
+        stretched: false,
+        frame_period_ms: None,
+        modulation_db: 0.0,
+        attack_ms: None,
+        smeared_transients: false,
+        repeat_lag_ms: None,
+        stretch_if_slowed: None,
+        stretch_if_sped_up: None,
+        regions: Vec::new(),
+    };
+    let hop = ((LEVEL_STEP_SECS * sr).round() as usize).max(1);
+    let n_fft = ((LEVEL_FRAME_SECS * sr) as usize).next_power_of_two();
+    let step_rate = sr / hop as f32;
+    let (_, frames) = spectrum::spectrogram_db(samples, sample_rate, n_fft, hop, WindowType::Hann, sr / 2.0 + 1.0);
+    if frames.len() < 2 * BLOCK_SEGMENT {
+        return report;
+    }
+
+    // Band levels less their local mean
+    let n_bins = frames[0].len() - 1;
+    let half = (LEVEL_DETREND_SECS * step_rate) as usize / 2;
+    let levels: Vec<Vec<f32>> = (0..N_BANDS)
+        .map(|b| {
+            let (lo, hi) = (1 + b * n_bins / N_BANDS, 1 + (b + 1) * n_bins / N_BANDS);
+            let level: Vec<f32> = frames
+                .iter()
+                .map(|f| 10.0 * f[lo..hi].iter().map(|db| 10f32.powf(db / 10.0)).sum::<f32>().max(1e-20).log10())
+                .collect();
+            let mut prefix = vec![0.0f64; level.len() + 1];
+            for (i, &l) in level.iter().enumerate() {
+                prefix[i + 1] = prefix[i] + l as f64;
+            }
+            (0..level.len())
+                .map(|i| {
+                    let (lo, hi) = (i.saturating_sub(half), (i + half + 1).min(level.len()));
+                    level[i] - ((prefix[hi] - prefix[lo]) / (hi - lo) as f64) as f32
+                })
+                .collect()
+        })
+        .collect();
+
+    // Frame-rate line over the whole stretch of audio
+    let rate = step_rate.round() as u32;
+    let segment = MODULATION_SEGMENT.min(1 << (frames.len() / 2).ilog2());
+    let db = modulation_spectrum(&levels, rate, segment);
+    let bin_hz = step_rate / segment as f32;
+    let neighbourhood = (LINE_NEIGHBOURHOOD_HZ / bin_hz) as usize;
+    let lo = (FRAME_RATE_RANGE.0 / bin_hz) as usize;
+    let hi = ((FRAME_RATE_RANGE.1 / bin_hz) as usize).min(db.len());
+    let heights: Vec<f32> = (0..hi).map(|k| if k >= lo { line_height(&db, k, neighbourhood) } else { 0.0 }).collect();
+    let Some(strongest) = (lo..hi).max_by(|&a, &b| heights[a].total_cmp(&heights[b])) else {
+        return report;
+    };
+    // The overlap-add modulates at the frame rate and its harmonics; take
+    // the lowest of them that stands out
+    let fundamental = (2..=4)
+        .rev()
+        .map(|d| (strongest as f32 / d as f32).round() as usize)
+        .find(|&k| k > lo && heights[k - 1..=k + 1].iter().any(|&h| h >= MIN_LINE_DB))
+        .map_or(strongest, |k| (k - 1..=k + 1).max_by(|&a, &b| heights[a].total_cmp(&heights[b])).unwrap());
+    report.modulation_db = heights[strongest];
+    let frame_rate = fundamental as f32 * bin_hz;
+
+    // Blocks carrying the line, which may stand out where the whole does
+    // not when only a passage was stretched
+    let block = (BLOCK_SECS * step_rate) as usize;
+    let block_bin_hz = step_rate / BLOCK_SEGMENT as f32;
+    let block_bin = (frame_rate / block_bin_hz).round() as usize;
+    let block_neighbourhood = (LINE_NEIGHBOURHOOD_HZ / block_bin_hz) as usize;
+    for first in (0..levels[0].len()).step_by((BLOCK_STEP_SECS * step_rate) as usize) {
+        let last = (first + block).min(levels[0].len());
+        if last - first < 2 * BLOCK_SEGMENT {
+            continue;
+        }
+        let chunk: Vec<Vec<f32>> = levels.iter().map(|l| l[first..last].to_vec()).collect();
+        let height = line_height(&modulation_spectrum(&chunk, rate, BLOCK_SEGMENT), block_bin, block_neighbourhood);
+        if height < MIN_BLOCK_LINE_DB {
+            continue;
+        }
+        let (start_time, end_time) = (first as f32 / step_rate, last as f32 / step_rate);
+        match report.regions.last_mut() {
+            Some(previous) if previous.end_time >= start_time => {
+                previous.end_time = end_time;
+                previous.modulation_db = previous.modulation_db.max(height);
+            }
+            _ => report.regions.push(StretchRegion { start_time, end_time, modulation_db: height }),
+        }
+    }
+
+    if heights[strongest] >= MIN_LINE_DB || !report.regions.is_empty() {
+        report.frame_period_ms = Some(1000.0 / frame_rate);
+
+        // Repeats across frame boundaries, on the unvoiced audio (voiced
+        // speech correlates at its pitch period anyway) normalized over a
+        // fraction of the frame period, so neither loud passages nor the
+        // overlap-add's own level modulation dominate
+        let period = (sr / frame_rate) as usize;
+        let voiced: Vec<bool> = pitch::track(&pitch::to_analysis_rate(samples, sample_rate)).iter().map(|f| f.f0.is_some()).collect();
+        let pitch_hop = HOP_SECS * sr;
+        let power: Vec<f32> = samples.iter().map(|s| s * s).collect();
+        let mut prefix = vec![0.0f64; power.len() + 1];
+        for (i, &p) in power.iter().enumerate() {
+            prefix[i + 1] = prefix[i] + p as f64;
+        }
+        let reach = period / 8;
+        let normalized: Vec<f32> = (0..samples.len())
+            .map(|i| {
+                // Pitch frames are four hops long
+                let frame = (i as f32 / pitch_hop) as usize;
+                if (frame.saturating_sub(3)..=frame).any(|f| voiced.get(f).copied().unwrap_or(false)) {
+                    return 0.0;
+                }
+                let (lo, hi) = (i.saturating_sub(reach), (i + reach + 1).min(samples.len()));
+                let mean = (prefix[hi] - prefix[lo]) / (hi - lo) as f64;
+                if mean > 1e-20 { samples[i] / mean.sqrt() as f32 } else { 0.0 }
+            })
+            .collect();
+        let min_lag = (MIN_REPEAT_SECS * sr) as usize;
+        if period > 4 * min_lag && samples.len() > 4 * period {
+            let profile = repeat_profile(&normalized, frame_rate, sr, period);
+            let lags = &profile[min_lag..];
+            let (peak, &height) = lags.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+            let typical = median(&mut lags.to_vec());
+            if height >= MIN_REPEAT_RATIO * typical {
+                let lag = (min_lag + peak) as f32;
+                report.repeat_lag_ms = Some(1000.0 * lag / sr);
+                report.stretch_if_slowed = Some(period as f32 / (period as f32 - lag)).filter(|f| f.is_finite() && *f > 0.0);
+                report.stretch_if_sped_up = Some(period as f32 / (period as f32 + lag));
+            }
+        }
+    }
+
+    // Onset sharpness in the top band
+    let onset_bin = ((ONSET_BAND_HZ / (sr / n_fft as f32)) as usize).min(frames[0].len() - 1);
+    let envelope: Vec<f32> = frames
+        .iter()
+        .map(|f| 10.0 * f[onset_bin..].iter().map(|db| 10f32.powf(db / 10.0)).sum::<f32>().max(1e-20).log10())
+        .collect();
+    let lookback = (ONSET_LOOKBACK_SECS * step_rate) as usize;
+    let mut attacks = Vec::new();
+    let mut t = lookback;
+    while t + lookback < envelope.len() {
+        let peak = envelope[t];
+        let is_peak = envelope[t - lookback..t + lookback].iter().all(|&v| v <= peak);
+        let base = envelope[t - lookback..t].iter().copied().fold(f32::INFINITY, f32::min);
+        if !is_peak || peak - base < ONSET_RISE_DB {
+            t += 1;
+            continue;
+        }
+        let rise = (0..lookback).find(|&d| envelope[t - d - 1] < peak - ONSET_RISE_DB).map_or(lookback, |d| d + 1);
+        attacks.push(rise as f32 * 1000.0 / step_rate);
+        t += lookback;
+    }
+    if attacks.len() >= MIN_ONSETS {
+        attacks.sort_by(f32::total_cmp);
+        let sharpest = &attacks[..((SHARPEST_SHARE * attacks.len() as f32) as usize).max(1)];
+        let attack = sharpest.iter().sum::<f32>() / sharpest.len() as f32;
+        report.attack_ms = Some(attack);
+        report.smeared_transients = attack > MAX_ATTACK_MS;
+    }
+
+    report.stretched = report.frame_period_ms.is_some() || report.smeared_transients;
+    report
+}
@@ -0,0 +1,318 @@
+//! Exponential (log) sine sweep generation, detection and deconvolution
+
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use serde::Serialize;
+use std::f64::consts::PI;
+
+const TRACK_FFT: usize = 4096;
+const TRACK_HOP: usize = 1024;
+/// Points in the returned log-spaced magnitude response
+const RESPONSE_POINTS: usize = 256;
+
+/// Parameters of a log sweep located in (or supplied for) a recording
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SweepParams {
+    pub start_time: f32,
+    pub duration: f32,
+    pub start_freq: f32,
+    pub end_freq: f32,
+}
+
+#[derive(Serialize)]
+pub struct SweepResponse {
+    pub sweep: SweepParams,
+    pub impulse_response: Vec<f32>,
+    pub ir_peak_time: f32,       // Recording time at which the deconvolved sweep (direct sound) starts
+    pub freqs: Vec<f32>,
+    pub magnitude_db: Vec<f32>,  // 1/6-octave smoothed, 0 dB = unity gain
+}
+
+/// Generate an exponential sweep from `f1` to `f2` Hz lasting `duration` seconds
+pub fn log_sweep(f1: f32, f2: f32, duration: f32, sample_rate: u32) -> Vec<f32> {
+    let sr = sample_rate as f64;
+    let (f1, f2, t) = (f1 as f64, f2 as f64, duration as f64);
+    let rate = t / (f2 / f1).ln();
+    let n = (t * sr) as usize;
+    (0..n)
+        .map(|i| {
+            let time = i as f64 / sr;
+            (2.0 * PI * f1 * rate * ((time / rate).exp() - 1.0)).sin() as f32
+        })
+        .collect()
+}
+
+/// Find the longest rising log-frequency tone in `samples` by tracking the
+/// dominant STFT peak and fitting ln(f) = a + b·t over the run
+pub fn detect_sweep(samples: &[f32], sample_rate: u32) -> Option<SweepParams> {
+    let sr = sample_rate as f32;
+    let window = WindowType::Hann.coefficients(TRACK_FFT);
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(TRACK_FFT);
+    let mut spectrum = fft.make_output_vec();
+
+    // (frame center time, peak frequency, peak power, share of frame power)
+    let mut track = Vec::new();
+    let mut start = 0;
+    while start + TRACK_FFT <= samples.len() {
+        let mut input: Vec<f32> = samples[start..start + TRACK_FFT]
+            .iter()
+            .zip(&window)
+            .map(|(&s, &w)| s * w)
+            .collect();
+        fft.process(&mut input, &mut spectrum).ok()?;
+        let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+        let total: f32 = power.iter().sum::<f32>().max(1e-20);
+        let (peak, &peak_power) = power
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|a, b| a.1.total_cmp(b.1))?;
+        // A sweep smears over a band proportional to its frequency within one frame
+        let lo = (peak as f32 * 0.8) as usize;
+        let hi = ((peak as f32 * 1.25) as usize + 3).min(power.len());
+        let lobe: f32 = power[lo.saturating_sub(2)..hi].iter().sum();
+        let t = (start + TRACK_FFT / 2) as f32 / sr;
+        track.push((t, peak as f32 * sr / TRACK_FFT as f32, peak_power, lobe / total));
+        start += TRACK_HOP;
+    }
+
+    let loudest = track.iter().map(|f| f.2).fold(0.0f32, f32::max);
+    let active = |f: &(f32, f32, f32, f32)| f.2 > loudest * 1e-4 && f.3 > 0.5 && f.1 > 0.0;
+
+    // Longest run of active frames whose frequency does not fall
+    let mut best: Option<(usize, usize)> = None;
+    let mut run_start = None;
+    for i in 0..=track.len() {
+        let continues = i < track.len()
+            && active(&track[i])
+            && run_start.is_none_or(|_| track[i].1 >= track[i - 1].1 * 0.97);
+        match (continues, run_start) {
+            (true, None) => run_start = Some(i),
+            (true, Some(_)) => {}
+            (false, Some(s)) => {
+                if best.is_none_or(|(bs, be)| i - s > be - bs) {
+                    best = Some((s, i));
+                }
+                run_start = if i < track.len() && active(&track[i]) { Some(i) } else { None };
+            }
+            (false, None) => {}
+        }
+    }
+
+    let (s, e) = best?;
+    if e - s < 8 || track[e - 1].1 < track[s].1 * 4.0 {
+        return None;
+    }
+
+    // Least-squares fit of ln(f) against time
+    let points: Vec<(f64, f64)> = track[s..e].iter().map(|f| (f.0 as f64, (f.1 as f64).ln())).collect();
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_l = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_l)).sum();
+    let var: f64 = points.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+    if var <= 0.0 {
+        return None;
+    }
+    let slope = cov / var;
+    if slope <= 0.0 {
+        return None;
+    }
+    let intercept = mean_l - slope * mean_t;
+
+    // Extend half a frame at each end, where the sweep is still partly covered
+    let half_frame = TRACK_FFT as f64 / 2.0 / sr as f64;
+    let t0 = (points[0].0 - half_frame).max(0.0);
+    let t1 = (points[points.len() - 1].0 + half_frame).min(samples.len() as f64 / sr as f64);
+    let nyquist = sr as f64 / 2.0;
+
+    Some(SweepParams {
+        start_time: t0 as f32,
+        duration: (t1 - t0) as f32,
+        start_freq: (intercept + slope * t0).exp().max(1.0) as f32,
+        end_freq: (intercept + slope * t1).exp().min(nyquist) as f32,
+    })
+}
+
+/// Deconvolve the recorded sweep against a regenerated reference, returning
+/// the linear impulse response and its smoothed magnitude response.
+/// With `refine`, the sweep rate is first tuned to the recording (needed
+/// when the parameters come from [`detect_sweep`] rather than the generator).
+pub fn measure_response(
+    samples: &[f32],
+    sample_rate: u32,
+    sweep: SweepParams,
+    ir_length: f32,
+    refine: bool,
+) -> Option<SweepResponse> {
+    let sr = sample_rate as f32;
+    if sweep.duration * sr < TRACK_FFT as f32 {
+        return None;
+    }
+
+    // Recorded segment: short lead-in, the sweep, then room for the decay
+    let pre = (0.05 * sr) as usize;
+    let seg_start = ((sweep.start_time * sr) as usize).saturating_sub(pre);
+    let ir_len = ((ir_length * sr) as usize).max(256);
+    let seg_end = (seg_start + pre + (sweep.duration * sr) as usize + ir_len).min(samples.len());
+    let recorded = &samples[seg_start..seg_end];
+
+    let sweep = if refine { refine_rate(recorded, sample_rate, sweep) } else { sweep };
+    let (ir_in, ir_quad) = deconvolve(recorded, sample_rate, &sweep)?;
+
+    let (peak, _) = ir_in
+        .iter()
+        .zip(&ir_quad)
+        .map(|(i, q)| i * i + q * q)
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    // The starting phase of a detected sweep is unknown, which rotates the
+    // response by a constant phase. Recover it from the quadrature component
+    // at the envelope peak, keeping the rotation within ±90° so an in-phase
+    // measurement keeps its polarity.
+    let mut theta = ir_quad[peak].atan2(ir_in[peak]);
+    if theta > std::f32::consts::FRAC_PI_2 {
+        theta -= std::f32::consts::PI;
+    } else if theta < -std::f32::consts::FRAC_PI_2 {
+        theta += std::f32::consts::PI;
+    }
+    let (sin_t, cos_t) = theta.sin_cos();
+
+    let ir_start = peak.saturating_sub((0.002 * sr) as usize);
+    let ir_end = (ir_start + ir_len).min(ir_in.len());
+    let impulse_response: Vec<f32> = (ir_start..ir_end)
+        .map(|i| cos_t * ir_in[i] + sin_t * ir_quad[i])
+        .collect();
+
+    let (freqs, magnitude_db) = smoothed_response(&impulse_response, sample_rate, sweep.start_freq, sweep.end_freq);
+
+    Some(SweepResponse {
+        sweep,
+        impulse_response,
+        ir_peak_time: (seg_start + peak) as f32 / sr,
+        freqs,
+        magnitude_db,
+    })
+}
+
+/// Regularized spectral division of `recorded` by the reference sweep.
+/// Returns the in-phase impulse response and its Hilbert transform.
+fn deconvolve(recorded: &[f32], sample_rate: u32, sweep: &SweepParams) -> Option<(Vec<f32>, Vec<f32>)> {
+    let sr = sample_rate as f32;
+    let reference = log_sweep(sweep.start_freq, sweep.end_freq, sweep.duration, sample_rate);
+
+    let n = (recorded.len() + reference.len()).next_power_of_two();
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(n);
+    let inverse = planner.plan_fft_inverse(n);
+
+    let spectrum_of = |x: &[f32]| {
+        let mut buf = vec![0.0f32; n];
+        buf[..x.len()].copy_from_slice(x);
+        let mut out = forward.make_output_vec();
+        forward.process(&mut buf, &mut out).map(|_| out)
+    };
+    let y = spectrum_of(recorded).ok()?;
+    let x = spectrum_of(&reference).ok()?;
+
+    // Regularization is heavier outside the swept band
+    let max_x = x.iter().map(|c| c.norm_sqr()).fold(0.0f32, f32::max);
+    let bin_hz = sr / n as f32;
+    let mut h: Vec<Complex<f32>> = y
+        .iter()
+        .zip(&x)
+        .enumerate()
+        .map(|(k, (&yk, &xk))| {
+            let f = k as f32 * bin_hz;
+            let in_band = f >= sweep.start_freq && f <= sweep.end_freq;
+            let eps = if in_band { 1e-6 } else { 1e-1 } * max_x;
+            yk * xk.conj() / (xk.norm_sqr() + eps) / n as f32
+        })
+        .collect();
+    let last = h.len() - 1;
+    h[0].im = 0.0;
+    h[last].im = 0.0;
+
+    // Hilbert transform: -90° rotation of the positive frequencies
+    let mut h_quad: Vec<Complex<f32>> = h.iter().map(|&c| c * Complex::new(0.0, -1.0)).collect();
+    h_quad[0] = Complex::new(0.0, 0.0);
+    h_quad[last] = Complex::new(0.0, 0.0);
+
+    let mut ir_in = inverse.make_output_vec();
+    let mut ir_quad = inverse.make_output_vec();
+    inverse.process(&mut h, &mut ir_in).ok()?;
+    inverse.process(&mut h_quad, &mut ir_quad).ok()?;
+    Some((ir_in, ir_quad))
+}
+
+/// Share of the deconvolved energy concentrated in the strongest sample;
+/// peaks when the reference sweep rate matches the recording
+fn peak_sharpness(recorded: &[f32], sample_rate: u32, sweep: &SweepParams) -> f32 {
+    let Some((ir_in, ir_quad)) = deconvolve(recorded, sample_rate, sweep) else {
+        return 0.0;
+    };
+    let envelope = ir_in.iter().zip(&ir_quad).map(|(i, q)| i * i + q * q);
+    let (peak, total) = envelope.fold((0.0f32, 0.0f32), |(p, t), e| (p.max(e), t + e));
+    if total > 0.0 { peak / total } else { 0.0 }
+}
+
+/// Tune the sweep's log-rate around the detected value. A pure time shift
+/// only delays the response, so the fitted line is rotated about its center.
+fn refine_rate(recorded: &[f32], sample_rate: u32, sweep: SweepParams) -> SweepParams {
+    let center_freq = (sweep.start_freq * sweep.end_freq).sqrt();
+    let half = sweep.duration / 2.0;
+    let with_rate = |rate: f32| SweepParams {
+        start_freq: center_freq * (-rate * half).exp(),
+        end_freq: center_freq * (rate * half).exp(),
+        ..sweep
+    };
+
+    let mut best_rate = (sweep.end_freq / sweep.start_freq).ln() / sweep.duration;
+    let mut span = best_rate * 0.02;
+    for _ in 0..3 {
+        let candidates: Vec<f32> = (-10..=10).map(|i| best_rate + span * i as f32 / 10.0).collect();
+        best_rate = candidates
+            .par_iter()
+            .map(|&rate| (rate, peak_sharpness(recorded, sample_rate, &with_rate(rate))))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(rate, _)| rate)
+            .unwrap_or(best_rate);
+        span /= 10.0;
+    }
+    with_rate(best_rate)
+}
+
+/// Magnitude of `ir` on a log frequency grid, smoothed over 1/6 octave
+fn smoothed_response(ir: &[f32], sample_rate: u32, f1: f32, f2: f32) -> (Vec<f32>, Vec<f32>) {
+    let sr = sample_rate as f32;
+    let n = ir.len().next_power_of_two().max(8192);
+    let mut buf = vec![0.0f32; n];
+    buf[..ir.len()].copy_from_slice(ir);
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(n);
+    let mut spectrum = fft.make_output_vec();
+    if fft.process(&mut buf, &mut spectrum).is_err() {
+        return (Vec::new(), Vec::new());
+    }
+    let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+    let bin_hz = sr / n as f32;
+
+    let (lo, hi) = (f1.max(bin_hz).ln(), f2.min(sr / 2.0).ln());
+    let step = (hi - lo) / (RESPONSE_POINTS - 1) as f32;
+    let half_band = 2f32.powf(1.0 / 12.0);
+
+    (0..RESPONSE_POINTS)
+        .map(|i| {
+            let f = (lo + step * i as f32).exp();
+            let a = ((f / half_band / bin_hz) as usize).min(power.len() - 1);
+            let b = ((f * half_band / bin_hz).ceil() as usize).clamp(a + 1, power.len());
+            let mean = power[a..b].iter().sum::<f32>() / (b - a) as f32;
+            (f, 10.0 * mean.max(1e-20).log10())
+        })
+        .unzip()
+}
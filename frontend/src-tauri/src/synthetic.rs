@@ -0,0 +1,149 @@
+//! Synthetic speech (TTS / voice conversion) screening
+
+use crate::pitch::{self, PitchFrame, ANALYSIS_RATE, HOP_SECS};
+use serde::{Deserialize, Serialize};
+
+/// Least voiced material for a segment to be scored (seconds)
+const MIN_VOICED_SECS: f32 = 0.5;
+/// Largest frame-to-frame F0 change still read as the same voiced run (%)
+const MAX_F0_STEP: f32 = 10.0;
+
+const CAVEAT: &str = "Heuristic screening, not a classifier verdict: the scores are not calibrated \
+probabilities. Modern TTS and voice conversion can pass every check, and trained, monotone or heavily \
+processed natural voices can fail them. Treat a high score as a reason for closer examination.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticSegment {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub score: Option<f32>,         // 0..1 from the features below; None with too little voiced speech
+    pub model_score: Option<f32>,   // Synthetic probability from the ONNX model, when one was given
+    pub voiced_secs: f32,
+    pub f0_variation: f32,          // Mean frame-to-frame F0 change in voiced runs (%)
+    pub periodicity: f32,           // Median voiced-frame autocorrelation peak
+    pub floor_dbfs: f32,            // Quietest frame level
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticReport {
+    pub score: Option<f32>,         // Mean of the segment scores
+    pub model_score: Option<f32>,   // Mean of the model's segment scores
+    pub segments: Vec<SyntheticSegment>,
+    pub caveat: String,
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    let (_, &mut m, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    m
+}
+
+/// Features and heuristic score of one segment's frames
+fn score_segment(frames: &[PitchFrame], start_time: f32, end_time: f32) -> SyntheticSegment {
+    let voiced: Vec<&PitchFrame> = frames.iter().filter(|f| f.f0.is_some()).collect();
+    let voiced_secs = voiced.len() as f32 * HOP_SECS;
+
+    // Frame-to-frame F0 change within voiced runs
+    let steps: Vec<f32> = frames
+        .windows(2)
+        .filter_map(|w| {
+            let (f1, f2) = (w[0].f0?, w[1].f0?);
+            let step = 200.0 * (f2 - f1).abs() / (f1 + f2);
+            (step < MAX_F0_STEP).then_some(step)
+        })
+        .collect();
+    let f0_variation = steps.iter().sum::<f32>() / steps.len().max(1) as f32;
+    let mut peaks: Vec<f32> = voiced.iter().map(|v| v.periodicity).collect();
+    let periodicity = if peaks.is_empty() { 0.0 } else { median(&mut peaks) };
+    let floor_dbfs = frames.iter().map(|f| f.level_db).fold(f32::INFINITY, f32::min);
+
+    // Natural voices wander by 1-2% between 10 ms frames and are never
+    // perfectly periodic; pauses of generated audio are often digital silence
+    let score = (voiced_secs >= MIN_VOICED_SECS && !steps.is_empty()).then(|| {
+        let smooth = ((1.5 - f0_variation) / 1.0).clamp(0.0, 1.0);
+        let periodic = ((periodicity - 0.85) / 0.1).clamp(0.0, 1.0);
+        let silent = ((-70.0 - floor_dbfs) / 30.0).clamp(0.0, 1.0);
+        (smooth + periodic + silent) / 3.0
+    });
+
+    SyntheticSegment {
+        start_time,
+        end_time,
+        score,
+        model_score: None,
+        voiced_secs,
+        f0_variation,
+        periodicity,
+        floor_dbfs,
+    }
+}
+
+/// Synthetic probability per segment from an ONNX model taking a
+/// `[1, samples]` waveform at 16 kHz and returning either one logit or two
+/// (synthetic, bona fide) as in the ASVspoof baselines
+#[cfg(feature = "onnx")]
+fn model_scores(model_path: &str, audio: &[f32], bounds: &[(usize, usize)]) -> Result<Vec<f32>, String> {
+    use ort::session::Session;
+    use ort::value::Tensor;
+
+    let mut session = Session::builder()
+        .and_then(|b| b.commit_from_file(model_path))
+        .map_err(|e| format!("Failed to load model: {e}"))?;
+    bounds
+        .iter()
+        .map(|&(start, end)| {
+            let input = Tensor::from_array(([1usize, end - start], audio[start..end].to_vec())).map_err(|e| e.to_string())?;
+            let outputs = session.run(ort::inputs![input]).map_err(|e| e.to_string())?;
+            let (_, logits) = outputs[0].try_extract_tensor::<f32>().map_err(|e| e.to_string())?;
+            match *logits {
+                [logit] => Ok(1.0 / (1.0 + (-logit).exp())),
+                [synthetic, bona_fide, ..] => Ok(1.0 / (1.0 + (bona_fide - synthetic).exp())),
+                [] => Err("Model returned no output".to_string()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "onnx"))]
+fn model_scores(_model_path: &str, _audio: &[f32], _bounds: &[(usize, usize)]) -> Result<Vec<f32>, String> {
+    Err("This build has no ONNX support (enable the `onnx` feature)".to_string())
+}
+
+/// Score the likelihood that speech is synthetic (TTS or voice
+/// conversion), per segment of `segment_secs`. The heuristic score rises
+/// with unnaturally smooth pitch, near-perfect periodicity and digitally
+/// silent pauses. With `model_path`, an ONNX classifier also scores each
+/// segment; the features are computed either way.
+pub fn screen(samples: &[f32], sample_rate: u32, segment_secs: f32, model_path: Option<&str>) -> Result<SyntheticReport, String> {
+    let audio = pitch::to_analysis_rate(samples, sample_rate);
+    let frames = pitch::track(&audio);
+    let segment = ((segment_secs / HOP_SECS) as usize).max(1);
+    let rate = ANALYSIS_RATE as usize;
+
+    let mut segments: Vec<SyntheticSegment> = frames
+        .chunks(segment)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let start = (i * segment) as f32 * HOP_SECS;
+            score_segment(chunk, start, start + chunk.len() as f32 * HOP_SECS)
+        })
+        .collect();
+
+    if let Some(path) = model_path {
+        let bounds: Vec<(usize, usize)> = segments
+            .iter()
+            .map(|s| ((s.start_time * rate as f32) as usize, ((s.end_time * rate as f32) as usize).min(audio.len())))
+            .collect();
+        for (segment, score) in segments.iter_mut().zip(model_scores(path, &audio, &bounds)?) {
+            segment.model_score = Some(score);
+        }
+    }
+
+    let mean = |values: Vec<f32>| (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32);
+    Ok(SyntheticReport {
+        score: mean(segments.iter().filter_map(|s| s.score).collect()),
+        model_score: mean(segments.iter().filter_map(|s| s.model_score).collect()),
+        segments,
+        caveat: CAVEAT.to_string(),
+    })
+}
@@ -0,0 +1,163 @@
+//! Single-frequency energy tracking with the Goertzel algorithm
+
+use crate::distortion;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Line-up levels in use: -12 (film), -14, -18 (EBU R68), -20 dBFS (SMPTE RP155)
+const NOMINAL_LEVELS: [f32; 4] = [-12.0, -14.0, -18.0, -20.0];
+/// Largest difference from a nominal level still attributed to it (dB)
+const NOMINAL_TOLERANCE_DB: f32 = 1.5;
+/// Share of a frame's energy in its spectral peak for the frame to count as a tone
+const TONE_PURITY: f32 = 0.95;
+const TONE_FRAME: usize = 4096;
+
+#[derive(Serialize)]
+pub struct ToneTrack {
+    pub freq: f32,
+    pub levels_db: Vec<f32>,  // Sine amplitude per frame (dBFS)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationTone {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub duration: f32,
+    pub frequency: f32,
+    pub level_dbfs: f32,              // Sine peak level (AES17 convention)
+    pub nominal_dbfs: Option<f32>,    // Line-up level the tone matches, if any
+    pub offset_db: Option<f32>,       // Correction implied by the nominal level
+}
+
+/// Amplitude of the component at `freq` in an already windowed frame,
+/// given the window's coefficient sum (generalized Goertzel, so `freq`
+/// need not fall on a bin)
+pub fn goertzel_amplitude(frame: &[f32], freq: f32, sample_rate: u32, window_sum: f32) -> f32 {
+    let w = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+    let coeff = 2.0 * w.cos();
+    let (mut s1, mut s2) = (0.0f64, 0.0f64);
+    for &x in frame {
+        let s0 = x as f64 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    let power = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+    (2.0 * power.sqrt() / window_sum as f64) as f32
+}
+
+/// Level of each of `freqs` over Hann-windowed frames of `window` samples
+/// every `hop` samples. Returns (frame center times, one track per frequency).
+pub fn track_tones(
+    samples: &[f32],
+    sample_rate: u32,
+    freqs: &[f32],
+    window: usize,
+    hop: usize,
+) -> (Vec<f32>, Vec<ToneTrack>) {
+    let sr = sample_rate as f32;
+    let coefficients = WindowType::Hann.coefficients(window);
+    let window_sum: f32 = coefficients.iter().sum();
+    let starts: Vec<usize> = (0..)
+        .map(|i| i * hop.max(1))
+        .take_while(|&start| start + window <= samples.len())
+        .collect();
+
+    // Frames × frequencies
+    let levels: Vec<Vec<f32>> = starts
+        .par_iter()
+        .map(|&start| {
+            let frame: Vec<f32> = samples[start..start + window]
+                .iter()
+                .zip(&coefficients)
+                .map(|(&s, &w)| s * w)
+                .collect();
+            freqs
+                .iter()
+                .map(|&f| 20.0 * goertzel_amplitude(&frame, f, sample_rate, window_sum).max(1e-10).log10())
+                .collect()
+        })
+        .collect();
+
+    let tracks = freqs
+        .iter()
+        .enumerate()
+        .map(|(i, &freq)| ToneTrack {
+            freq,
+            levels_db: levels.iter().map(|frame| frame[i]).collect(),
+        })
+        .collect();
+    let times = starts.iter().map(|&start| (start + window / 2) as f32 / sr).collect();
+    (times, tracks)
+}
+
+/// Dominant bin and the share of the frame's energy in its main lobe
+fn frame_tonality(frame: &[f32], window: &[f32]) -> (usize, f32, f32) {
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(frame.len());
+    let mut input: Vec<f32> = frame.iter().zip(window).map(|(&s, &w)| s * w).collect();
+    let mut spectrum = fft.make_output_vec();
+    fft.process(&mut input, &mut spectrum).unwrap();
+    let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+
+    let total: f32 = power[1..].iter().sum();
+    let peak = (1..power.len()).max_by(|&a, &b| power[a].total_cmp(&power[b])).unwrap_or(1);
+    let lobe: f32 = power[peak.saturating_sub(3).max(1)..(peak + 4).min(power.len())].iter().sum();
+    let mean_square = frame.iter().map(|&s| s * s).sum::<f32>() / frame.len() as f32;
+    (peak, if total > 0.0 { lobe / total } else { 0.0 }, mean_square)
+}
+
+/// Steady sine tones of at least `min_duration` seconds in `samples`, such
+/// as line-up tones at the head of a recording
+pub fn detect_calibration_tones(samples: &[f32], sample_rate: u32, min_duration: f32) -> Vec<CalibrationTone> {
+    let sr = sample_rate as f32;
+    let window = WindowType::Hann.coefficients(TONE_FRAME);
+    let frames: Vec<(usize, f32, f32)> = samples
+        .par_chunks_exact(TONE_FRAME)
+        .map(|frame| frame_tonality(frame, &window))
+        .collect();
+
+    // Runs of tonal frames on the same bin whose level stays within 1 dB
+    let tonal = |i: usize| frames[i].1 > TONE_PURITY && frames[i].2 > 0.0;
+    let same_tone = |start: usize, i: usize| {
+        frames[i].0.abs_diff(frames[start].0) <= 1 && (10.0 * (frames[i].2 / frames[start].2).log10()).abs() < 1.0
+    };
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < frames.len() {
+        if !tonal(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        while i < frames.len() && tonal(i) && same_tone(start, i) {
+            i += 1;
+        }
+        runs.push((start, i));
+    }
+
+    runs.into_iter()
+        .filter(|&(start, end)| (end - start) as f32 * TONE_FRAME as f32 / sr >= min_duration)
+        .filter_map(|(start, end)| {
+            let (a, b) = (start * TONE_FRAME, end * TONE_FRAME);
+            let report = distortion::measure_thd(&samples[a..b], sample_rate, 5)?;
+            let level = report.fundamental_dbfs;
+            let nominal = NOMINAL_LEVELS
+                .iter()
+                .copied()
+                .filter(|n| (n - level).abs() <= NOMINAL_TOLERANCE_DB)
+                .min_by(|x, y| (x - level).abs().total_cmp(&(y - level).abs()));
+            Some(CalibrationTone {
+                start_time: a as f32 / sr,
+                end_time: b as f32 / sr,
+                duration: (b - a) as f32 / sr,
+                frequency: report.fundamental_hz,
+                level_dbfs: level,
+                nominal_dbfs: nominal,
+                offset_db: nominal.map(|n| n - level),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,232 @@
+//! Offline speech recognition with whisper.cpp
+
+use crate::segments::TimeRange;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptWord {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub text: String,
+    pub probability: f32,   // Mean token probability
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptSegment {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub text: String,
+    pub words: Vec<TranscriptWord>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    pub language: String,           // As requested, or as detected
+    pub model_path: String,
+    pub segments: Vec<TranscriptSegment>,
+}
+
+/// File formats a transcript exports to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptFormat {
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT subtitles
+    Vtt,
+    /// The transcript with its word timestamps
+    Json,
+}
+
+impl TranscriptFormat {
+    /// The format a file name's extension stands for, SRT when it names none
+    pub fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("vtt") => Self::Vtt,
+            Some("json") => Self::Json,
+            _ => Self::Srt,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptMatch {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub segment: usize,     // Index into the transcript's segments
+    pub text: String,       // The segment's text
+}
+
+impl Transcript {
+    /// Segments whose text contains `query` (case-insensitive), timed to the
+    /// words that match when word timestamps cover it
+    pub fn search(&self, query: &str) -> Vec<TranscriptMatch> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.text.to_lowercase().contains(&query))
+            .map(|(i, s)| {
+                let words: Vec<&TranscriptWord> = s
+                    .words
+                    .iter()
+                    .filter(|w| {
+                        let word = w.text.trim().to_lowercase();
+                        !word.is_empty() && (query.contains(&word) || word.contains(&query))
+                    })
+                    .collect();
+                let (start_time, end_time) = match (words.first(), words.last()) {
+                    (Some(first), Some(last)) => (first.start_time, last.end_time),
+                    _ => (s.start_time, s.end_time),
+                };
+                TranscriptMatch { start_time, end_time, segment: i, text: s.text.clone() }
+            })
+            .collect()
+    }
+
+    /// The transcript as `format`. Subtitles get one cue per segment with
+    /// text; JSON keeps everything, word timestamps included.
+    pub fn render(&self, format: TranscriptFormat) -> Result<String, String> {
+        let cues = self.segments.iter().filter(|s| !s.text.is_empty());
+        match format {
+            TranscriptFormat::Srt => Ok(cues
+                .enumerate()
+                .map(|(i, s)| {
+                    format!("{}\n{} --> {}\n{}\n\n", i + 1, timestamp(s.start_time, ','), timestamp(s.end_time, ','), s.text)
+                })
+                .collect()),
+            TranscriptFormat::Vtt => Ok(std::iter::once("WEBVTT\n\n".to_string())
+                .chain(cues.map(|s| format!("{} --> {}\n{}\n\n", timestamp(s.start_time, '.'), timestamp(s.end_time, '.'), s.text)))
+                .collect()),
+            TranscriptFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// `HH:MM:SS` and milliseconds after `separator` (a comma for SRT, a point
+/// for WebVTT)
+fn timestamp(seconds: f32, separator: char) -> String {
+    let ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}{}{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, separator, ms % 1000)
+}
+
+/// Transcribe `samples` with the whisper.cpp model at `model_path` (a ggml
+/// `.bin` file). `language` is an ISO 639-1 code, or `None` to detect it.
+/// With `speech` ranges (from voice activity detection) only those are
+/// decoded, which keeps whisper from inventing words in long silences.
+/// `progress` receives the overall progress in percent.
+#[cfg(feature = "whisper")]
+pub fn transcribe(
+    samples: &[f32],
+    sample_rate: u32,
+    model_path: &str,
+    language: Option<&str>,
+    speech: Option<&[TimeRange]>,
+    progress: impl Fn(f32) + Clone + 'static,
+) -> Result<Transcript, String> {
+    use crate::pitch;
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    let audio = pitch::to_analysis_rate(samples, sample_rate);
+    let sr = pitch::ANALYSIS_RATE as f32;
+    let ranges: Vec<(usize, usize)> = match speech {
+        Some(ranges) => ranges
+            .iter()
+            .map(|r| (((r.start_time * sr) as usize).min(audio.len()), ((r.end_time * sr) as usize).min(audio.len())))
+            .filter(|(start, end)| end > start)
+            .collect(),
+        None => vec![(0, audio.len())],
+    };
+    let total = ranges.iter().map(|(start, end)| end - start).sum::<usize>().max(1) as f32;
+
+    let context = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+        .map_err(|e| format!("Failed to load model: {e}"))?;
+    let mut state = context.create_state().map_err(|e| e.to_string())?;
+    let mut detected = language.map(str::to_string);
+    let mut segments = Vec::new();
+    let mut done = 0;
+
+    for &(start, end) in &ranges {
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language.unwrap_or("auto")));
+        params.set_token_timestamps(true);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        let (report, before, share) = (progress.clone(), done as f32 / total, (end - start) as f32 / total);
+        params.set_progress_callback_safe(move |percent: i32| report(100.0 * (before + share * percent as f32 / 100.0)));
+
+        state.full(params, &audio[start..end]).map_err(|e| format!("Transcription failed: {e}"))?;
+        if detected.is_none() {
+            detected = state
+                .full_lang_id_from_state()
+                .ok()
+                .and_then(whisper_rs::get_lang_str)
+                .map(str::to_string);
+        }
+
+        // whisper times are in centiseconds from the start of the range
+        let offset = start as f32 / sr;
+        let time = |centiseconds: i64| offset + centiseconds as f32 / 100.0;
+        for s in 0..state.full_n_segments().map_err(|e| e.to_string())? {
+            let text = state.full_get_segment_text_lossy(s).map_err(|e| e.to_string())?;
+            let mut words: Vec<TranscriptWord> = Vec::new();
+            let mut probabilities: Vec<f32> = Vec::new();
+            for t in 0..state.full_n_tokens(s).map_err(|e| e.to_string())? {
+                let data = state.full_get_token_data(s, t).map_err(|e| e.to_string())?;
+                let piece = state.full_get_token_text_lossy(s, t).map_err(|e| e.to_string())?;
+                // Special tokens (timestamps, end of text) are not words
+                if data.id >= context.token_eot() {
+                    continue;
+                }
+                match words.last_mut() {
+                    // A piece without a leading space continues the word
+                    Some(word) if !piece.starts_with(' ') => {
+                        word.text.push_str(&piece);
+                        word.end_time = time(data.t1);
+                        probabilities.push(data.p);
+                        word.probability = probabilities.iter().sum::<f32>() / probabilities.len() as f32;
+                    }
+                    _ => {
+                        probabilities = vec![data.p];
+                        words.push(TranscriptWord {
+                            start_time: time(data.t0),
+                            end_time: time(data.t1),
+                            text: piece.trim_start().to_string(),
+                            probability: data.p,
+                        });
+                    }
+                }
+            }
+            segments.push(TranscriptSegment {
+                start_time: time(state.full_get_segment_t0(s).map_err(|e| e.to_string())?),
+                end_time: time(state.full_get_segment_t1(s).map_err(|e| e.to_string())?),
+                text: text.trim().to_string(),
+                words,
+            });
+        }
+        done += end - start;
+    }
+    progress(100.0);
+
+    Ok(Transcript {
+        language: detected.unwrap_or_else(|| "auto".to_string()),
+        model_path: model_path.to_string(),
+        segments,
+    })
+}
+
+#[cfg(not(feature = "whisper"))]
+pub fn transcribe(
+    _samples: &[f32],
+    _sample_rate: u32,
+    _model_path: &str,
+    _language: Option<&str>,
+    _speech: Option<&[TimeRange]>,
+    _progress: impl Fn(f32) + Clone + 'static,
+) -> Result<Transcript, String> {
+    Err("This build has no speech recognition (enable the `whisper` feature)".to_string())
+}
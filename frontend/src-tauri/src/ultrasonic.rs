@@ -0,0 +1,278 @@
+//! Ultrasonic and near-ultrasonic content: beacons, tracking tones, bat calls
+
+use crate::filters;
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Lower edge of the band searched (Hz); the top is just under Nyquist
+const LOW_EDGE_HZ: f32 = 17_000.0;
+const TOP_FRACTION: f32 = 0.49;
+/// Audible band compared against, to tell broadband transients (clicks,
+/// sibilants) that merely reach past 17 kHz from content of its own (Hz)
+const AUDIBLE_BAND: (f32, f32) = (8000.0, 16_000.0);
+/// Analysis frame (seconds, rounded up to a power of two), half overlapped:
+/// short enough for bat calls, narrow enough (94 Hz at 48 kHz) for beacons
+const FRAME_SECS: f32 = 0.01;
+/// Frames the per-bin floor (median) is taken over, evenly spread
+const MAX_FLOOR_FRAMES: usize = 4000;
+/// A bin is active this far over its floor (dB), on top of however far the
+/// audible band rose in the same frame, and at least this loud (dBFS, sine
+/// level)
+const MIN_EXCESS_DB: f32 = 15.0;
+const MIN_BIN_DBFS: f32 = -100.0;
+/// While the audible band rises this far (dB), as for a click or a
+/// sibilant, only tones carried on from the next frame or the last count
+const TRANSIENT_RISE_DB: f32 = 10.0;
+/// Active frames this close together (seconds) are one event
+const MERGE_SECS: f32 = 0.05;
+/// Events whose frames are at most this wide (Hz) within this range of
+/// their peak (dB) are tones; a peak that moves this far (Hz) is a sweep
+const TONE_WIDTH_HZ: f32 = 300.0;
+const PEAK_RANGE_DB: f32 = 20.0;
+const SWEEP_HZ: f32 = 1000.0;
+/// A continuous tone stands this far over the floor around it (dB) within
+/// this distance (Hz)
+const CONTINUOUS_PROMINENCE_DB: f32 = 15.0;
+const BASELINE_HZ: f32 = 1000.0;
+/// Heterodyned audio puts the lower band edge here (Hz)
+const HETERODYNE_BASE_HZ: f32 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UltrasonicKind {
+    /// Narrow and steady: beacons, tracking and pilot tones
+    Tone,
+    /// A peak that moves: echolocation calls, chirps
+    Sweep,
+    /// Spread over the band
+    Broadband,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UltrasonicEvent {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    pub peak_hz: f32,
+    pub sweep_hz: f32,          // Peak frequency at the end less at the start
+    pub level_dbfs: f32,        // Loudest frame, summed over the active bins
+    pub excess_db: f32,         // Peak over the floor of its bin
+    pub kind: UltrasonicKind,
+    pub continuous: bool,       // Present throughout, found in the long-term spectrum
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UltrasonicReport {
+    pub low_hz: f32,            // Band searched
+    pub high_hz: f32,
+    pub events: Vec<UltrasonicEvent>,
+    pub active_secs: f32,       // Time covered by events, continuous tones aside
+    pub present: bool,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+/// Active ultrasonic bins of a frame: index, level (dBFS), excess (dB)
+type Active = Vec<(usize, f32, f32)>;
+
+fn peak_of(active: &Active) -> (usize, f32, f32) {
+    active.iter().fold(active[0], |p, &a| if a.1 > p.1 { a } else { p })
+}
+
+/// Span (bins) of the active bins within range of the frame's peak
+fn frame_width(active: &Active) -> usize {
+    let top = peak_of(active).1;
+    let near = active.iter().filter(|a| a.1 >= top - PEAK_RANGE_DB).map(|a| a.0);
+    let (lo, hi) = near.fold((usize::MAX, 0), |(lo, hi), b| (lo.min(b), hi.max(b)));
+    hi - lo
+}
+
+/// Look for significant content above 17 kHz. Every bin from the audible
+/// comparison band up gets a floor, its median level over the recording;
+/// frames where ultrasonic bins rise well above their floor, by more than
+/// the audible band rises with them (a click or a sibilant), make events,
+/// described by band, peak and how the peak moves. Tones present all the
+/// way through leave no rise, so the floor itself is searched for narrow
+/// peaks too. `None` when the sample rate leaves no band above 17 kHz.
+pub fn detect(samples: &[f32], sample_rate: u32) -> Option<UltrasonicReport> {
+    let sr = sample_rate as f32;
+    let top = TOP_FRACTION * sr;
+    let n_fft = ((FRAME_SECS * sr) as usize).next_power_of_two();
+    let hop = n_fft / 2;
+    let bin_hz = sr / n_fft as f32;
+    if top < LOW_EDGE_HZ + 10.0 * bin_hz || samples.len() < n_fft {
+        return None;
+    }
+    let first = (AUDIBLE_BAND.0 / bin_hz) as usize;
+    let audible_end = (AUDIBLE_BAND.1 / bin_hz) as usize;
+    let ultra = (LOW_EDGE_HZ / bin_hz).ceil() as usize;
+    let last = (top / bin_hz) as usize;
+    let window = WindowType::Hann.coefficients(n_fft);
+    // Levels of a sine that falls on a bin, in dBFS
+    let scale = 4.0 / window.iter().sum::<f32>().powi(2);
+    let spectrum_db = |start: usize| -> Vec<f32> {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(n_fft);
+        let mut input: Vec<f32> = samples[start..start + n_fft].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+        let mut spectrum = fft.make_output_vec();
+        fft.process(&mut input, &mut spectrum).unwrap();
+        spectrum[first..=last].iter().map(|c| 10.0 * (c.norm_sqr() * scale).max(1e-20).log10()).collect()
+    };
+
+    let starts: Vec<usize> = (0..).map(|i| i * hop).take_while(|&start| start + n_fft <= samples.len()).collect();
+    let stride = starts.len().div_ceil(MAX_FLOOR_FRAMES).max(1);
+    let sampled: Vec<Vec<f32>> = starts.par_iter().step_by(stride).map(|&start| spectrum_db(start)).collect();
+    let floor: Vec<f32> =
+        (0..=last - first).map(|b| percentile(&sampled.iter().map(|s| s[b]).collect::<Vec<_>>(), 0.5)).collect();
+
+    let analyzed: Vec<(Active, f32)> = starts
+        .par_iter()
+        .map(|&start| {
+            let levels = spectrum_db(start);
+            let excess = |b: usize| levels[b - first] - floor[b - first];
+            let audible = (first..=audible_end).map(excess).sum::<f32>() / (audible_end - first + 1) as f32;
+            let active: Active = (ultra..=last)
+                .filter(|&b| excess(b) >= MIN_EXCESS_DB + audible.max(0.0) && levels[b - first] >= MIN_BIN_DBFS)
+                .map(|b| (b, levels[b - first], excess(b)))
+                .collect();
+            (active, audible)
+        })
+        .collect();
+    let tone_bins = (TONE_WIDTH_HZ / bin_hz) as usize;
+    let carried = |i: usize, peak: usize| {
+        [i.wrapping_sub(1), i + 1]
+            .iter()
+            .filter_map(|&j| analyzed.get(j))
+            .any(|(a, _)| !a.is_empty() && peak_of(a).0.abs_diff(peak) <= 2)
+    };
+    let frames: Vec<Active> = analyzed
+        .iter()
+        .enumerate()
+        .map(|(i, (active, audible))| {
+            let keep = active.is_empty()
+                || *audible < TRANSIENT_RISE_DB
+                || (frame_width(active) <= tone_bins && carried(i, peak_of(active).0));
+            if keep { active.clone() } else { Vec::new() }
+        })
+        .collect();
+
+    let mut events = Vec::new();
+    let hop_secs = hop as f32 / sr;
+    let merge = ((MERGE_SECS / hop_secs) as usize).max(1);
+    let mut i = 0;
+    while i < frames.len() {
+        if frames[i].is_empty() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i;
+        while i < frames.len() && i - end <= merge {
+            if !frames[i].is_empty() {
+                end = i;
+            }
+            i += 1;
+        }
+        i = end + 1;
+        let run: Vec<&Active> = frames[start..=end].iter().filter(|f| !f.is_empty()).collect();
+        let bins = run.iter().flat_map(|f| f.iter().map(|a| a.0));
+        let (low, high) = bins.fold((usize::MAX, 0), |(lo, hi), b| (lo.min(b), hi.max(b)));
+        let peak = run.iter().map(|f| peak_of(f)).fold((0, f32::MIN, 0.0), |p, a| if a.1 > p.1 { a } else { p });
+        // Over the Hann window's noise bandwidth, so a lone sine reads its level
+        let power = |f: &Active| f.iter().map(|a| 10f32.powf(a.1 / 10.0)).sum::<f32>() / 1.5;
+        let loudest = run.iter().map(|f| power(f)).fold(0.0, f32::max);
+        let sweep_hz = (peak_of(run[run.len() - 1]).0 as f32 - peak_of(run[0]).0 as f32) * bin_hz;
+        let widths: Vec<f32> = run.iter().map(|f| frame_width(f) as f32 * bin_hz).collect();
+        let width = percentile(&widths, 0.5);
+        events.push(UltrasonicEvent {
+            start_time: (start * hop) as f32 / sr,
+            end_time: (end * hop + n_fft) as f32 / sr,
+            low_hz: low as f32 * bin_hz,
+            high_hz: high as f32 * bin_hz,
+            peak_hz: peak.0 as f32 * bin_hz,
+            sweep_hz,
+            level_dbfs: 10.0 * loudest.max(1e-20).log10(),
+            excess_db: peak.2,
+            kind: if sweep_hz.abs() >= SWEEP_HZ {
+                UltrasonicKind::Sweep
+            } else if width <= TONE_WIDTH_HZ {
+                UltrasonicKind::Tone
+            } else {
+                UltrasonicKind::Broadband
+            },
+            continuous: false,
+        });
+    }
+    let active_secs = events.iter().map(|e| e.end_time - e.start_time).fold(0.0, |total, secs| total + secs);
+
+    // Continuous tones: narrow peaks of the floor itself
+    let reach = (BASELINE_HZ / bin_hz) as usize;
+    let duration = samples.len() as f32 / sr;
+    for b in ultra..=last {
+        let level = floor[b - first];
+        let (lo, hi) = (b.saturating_sub(reach).max(first), (b + reach).min(last));
+        let is_peak = (lo..=hi).all(|k| floor[k - first] <= level);
+        let baseline = percentile(&floor[lo - first..=hi - first], 0.5);
+        if is_peak && level - baseline >= CONTINUOUS_PROMINENCE_DB && level >= MIN_BIN_DBFS {
+            events.push(UltrasonicEvent {
+                start_time: 0.0,
+                end_time: duration,
+                low_hz: (b - 1) as f32 * bin_hz,
+                high_hz: (b + 1) as f32 * bin_hz,
+                peak_hz: b as f32 * bin_hz,
+                sweep_hz: 0.0,
+                level_dbfs: level,
+                excess_db: level - baseline,
+                kind: UltrasonicKind::Tone,
+                continuous: true,
+            });
+        }
+    }
+    events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+
+    Some(UltrasonicReport { low_hz: LOW_EDGE_HZ, high_hz: top, present: !events.is_empty(), events, active_secs })
+}
+
+/// Shift the band `low_hz`..`high_hz` down so it starts at 1 kHz, for
+/// listening. Single-sideband: the band is mixed to 0 Hz as a complex
+/// signal, cut to its width, and mixed back up, so nothing folds over and
+/// everything outside the band is dropped.
+pub fn heterodyne(samples: &[f32], sample_rate: u32, low_hz: f32, high_hz: f32) -> Result<Vec<f32>, String> {
+    let sr = sample_rate as f64;
+    if !(low_hz > 0.0 && low_hz < high_hz && (high_hz as f64) < sr / 2.0) {
+        return Err(format!("The band must lie between 0 and {} Hz", sample_rate / 2));
+    }
+    let width = (high_hz - low_hz) as f64;
+    let center = (low_hz as f64 + high_hz as f64) / 2.0;
+    let target = HETERODYNE_BASE_HZ as f64 + width / 2.0;
+    let mut real = filters::butterworth_lowpass(width / 2.0, 8, sr);
+    let mut imag = filters::butterworth_lowpass(width / 2.0, 8, sr);
+    let down = Complex::from_polar(1.0, -2.0 * std::f64::consts::PI * center / sr);
+    let up = Complex::from_polar(1.0, 2.0 * std::f64::consts::PI * target / sr);
+    let (mut down_phase, mut up_phase) = (Complex::new(1.0f64, 0.0), Complex::new(1.0f64, 0.0));
+
+    Ok(samples
+        .iter()
+        .enumerate()
+        .map(|(n, &x)| {
+            let mixed = down_phase * x as f64;
+            let baseband = Complex::new(real.process(mixed.re as f32) as f64, imag.process(mixed.im as f32) as f64);
+            let y = 2.0 * (baseband * up_phase).re;
+            down_phase *= down;
+            up_phase *= up;
+            if n % 1024 == 1023 {
+                down_phase /= down_phase.norm();
+                up_phase /= up_phase.norm();
+            }
+            y as f32
+        })
+        .collect())
+}
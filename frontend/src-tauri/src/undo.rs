@@ -0,0 +1,66 @@
+//! Undo and redo: the state before each change, kept as a whole so a step
+//! back restores it exactly
+
+use serde::Serialize;
+
+/// Changes remembered, the oldest forgotten first
+pub const MAX_STEPS: usize = 100;
+
+/// What `undo` and `redo` would do next, named for the interface
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryStatus {
+    pub undo: Option<String>,
+    pub redo: Option<String>,
+}
+
+/// Named states before (undo) and after (redo) changes, the latest last
+pub struct History<T> {
+    undo: Vec<(String, T)>,
+    redo: Vec<(String, T)>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new() }
+    }
+}
+
+impl<T> History<T> {
+    /// Remember `before`, the state a change called `action` started from.
+    /// What could be redone is dropped, as it no longer follows on.
+    pub fn record(&mut self, action: &str, before: T) {
+        self.undo.push((action.to_string(), before));
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Step back over the latest change: its name and the state to restore.
+    /// `current` is kept to redo it.
+    pub fn undo(&mut self, current: impl FnOnce() -> T) -> Option<(String, T)> {
+        let (action, before) = self.undo.pop()?;
+        self.redo.push((action.clone(), current()));
+        Some((action, before))
+    }
+
+    /// Make the latest undone change again: its name and the state to
+    /// restore. `current` is kept to undo it once more.
+    pub fn redo(&mut self, current: impl FnOnce() -> T) -> Option<(String, T)> {
+        let (action, after) = self.redo.pop()?;
+        self.undo.push((action.clone(), current()));
+        Some((action, after))
+    }
+
+    pub fn status(&self) -> HistoryStatus {
+        HistoryStatus {
+            undo: self.undo.last().map(|(action, _)| action.clone()),
+            redo: self.redo.last().map(|(action, _)| action.clone()),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
@@ -0,0 +1,282 @@
+//! Voice activity detection: speech and non-speech segments
+
+use crate::pitch;
+use crate::segments::{self, TimeRange};
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Analysis frame at `pitch::ANALYSIS_RATE` (samples), hopped like the
+/// pitch track so the two line up frame for frame
+const FRAME_LEN: usize = 512;
+/// Sub-bands of the speech spectrum (Hz), split as the WebRTC detector does
+const BANDS: [(f32, f32); 6] =
+    [(80.0, 250.0), (250.0, 500.0), (500.0, 1000.0), (1000.0, 2000.0), (2000.0, 3000.0), (3000.0, 4000.0)];
+/// Band the spectral flatness is taken over (Hz)
+const FLATNESS_BAND: (f32, f32) = (250.0, 4000.0);
+/// Frames quieter than this (dBFS) are never speech
+const MIN_LEVEL_DBFS: f32 = -60.0;
+/// Features: the noise floor is this percentile of the frame level over
+/// this long around each frame (seconds), and a speech frame stands this
+/// far above it (dB) and is either voiced or has a spectral flatness below
+/// this, the formant structure of a consonant
+const FLOOR_PERCENTILE: f32 = 0.1;
+const FLOOR_WINDOW_SECS: f32 = 10.0;
+const MIN_SNR_DB: f32 = 6.0;
+const MAX_FLATNESS: f32 = 0.3;
+/// Sub-band model: weight of each band in the mean log-likelihood ratio
+/// (WebRTC's spectrum weights), starting spread of the noise model and
+/// least it may shrink to (dB), the speech model's distance above the
+/// noise at the start and least distance, its spread (dB), and how fast
+/// either model follows the frames assigned to it (per frame)
+const BAND_WEIGHTS: [f32; 6] = [6.0, 8.0, 10.0, 12.0, 14.0, 16.0];
+const NOISE_SD_DB: f32 = 3.0;
+const MIN_NOISE_SD_DB: f32 = 1.5;
+const SPEECH_OFFSET_DB: f32 = 15.0;
+const MIN_SPEECH_OFFSET_DB: f32 = 6.0;
+const SPEECH_SD_DB: f32 = 8.0;
+const ADAPT_RATE: f32 = 0.02;
+/// Sub-band model, by aggressiveness 0 to 3: the weighted mean
+/// log-likelihood ratio, or a single band's, that makes a frame speech
+const MEAN_LLR_THRESHOLDS: [f32; 4] = [0.5, 1.0, 2.0, 3.0];
+const BAND_LLR_THRESHOLDS: [f32; 4] = [4.0, 5.0, 6.0, 8.0];
+/// Smoothing: speech runs shorter than this are dropped, the rest extended
+/// this far before and after (the hangover over unvoiced word edges), and
+/// pauses shorter than this bridged (seconds)
+const MIN_SPEECH_SECS: f32 = 0.1;
+const PRE_ROLL_SECS: f32 = 0.05;
+const HANGOVER_SECS: f32 = 0.2;
+const MIN_PAUSE_SECS: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VadMethod {
+    /// Level over the local noise floor, gated on voicing and spectral shape
+    #[default]
+    Features,
+    /// Noise and speech Gaussians per sub-band, adapted as it goes
+    SubbandModel,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VadSegment {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub speech: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VadReport {
+    pub method: VadMethod,
+    pub aggressiveness: u8,
+    pub segments: Vec<VadSegment>,      // Alternating, covering the whole signal
+    pub speech_secs: f32,
+    pub speech_fraction: f32,
+    pub speech_level_dbfs: Option<f32>, // Median frame level (80-4000 Hz) inside speech
+    pub noise_level_dbfs: Option<f32>,  // Median frame level outside it
+}
+
+impl VadReport {
+    /// The speech segments as time ranges
+    pub fn speech_ranges(&self) -> Vec<TimeRange> {
+        self.segments
+            .iter()
+            .filter(|s| s.speech)
+            .map(|s| TimeRange { start_time: s.start_time, end_time: s.end_time })
+            .collect()
+    }
+
+    /// Whether `start..end` (seconds) overlaps speech
+    pub fn overlaps_speech(&self, start: f32, end: f32) -> bool {
+        self.segments.iter().any(|s| s.speech && start < s.end_time && end > s.start_time)
+    }
+}
+
+/// One frame's features
+#[derive(Clone, Copy)]
+struct Frame {
+    level_dbfs: f32,
+    bands_dbfs: [f32; 6],
+    flatness: f32,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+fn frame_features(power: &[f32], bin_hz: f32, scale: f32) -> Frame {
+    let bin = |f: f32| ((f / bin_hz).round() as usize).clamp(1, power.len() - 1);
+    let band_power = |(lo, hi): (f32, f32)| power[bin(lo)..bin(hi).max(bin(lo) + 1)].iter().sum::<f32>() * scale;
+    let to_db = |p: f32| 10.0 * p.max(1e-20).log10();
+    let bands_dbfs = BANDS.map(|b| to_db(band_power(b)));
+    let level_dbfs = to_db(BANDS.iter().map(|&b| band_power(b)).sum());
+
+    let flat = &power[bin(FLATNESS_BAND.0)..bin(FLATNESS_BAND.1)];
+    let arithmetic = flat.iter().sum::<f32>() / flat.len().max(1) as f32;
+    let geometric = (flat.iter().map(|p| p.max(1e-20).ln()).sum::<f32>() / flat.len().max(1) as f32).exp();
+    let flatness = if arithmetic > 0.0 { geometric / arithmetic } else { 1.0 };
+
+    Frame { level_dbfs, bands_dbfs, flatness }
+}
+
+/// Features: speech frames stand clear of a rolling noise floor and are
+/// voiced or spectrally shaped, which steady noise and broadband bangs are not
+fn features_decision(frames: &[Frame], voiced: &[bool], frames_per_sec: usize) -> Vec<bool> {
+    let half = ((FLOOR_WINDOW_SECS / 2.0) as usize * frames_per_sec).max(1);
+    let levels: Vec<f32> = frames.iter().map(|f| f.level_dbfs).collect();
+    // Re-estimated once a second, which is plenty for a 10 s percentile
+    let floors: Vec<f32> = (0..frames.len().div_ceil(frames_per_sec.max(1)))
+        .map(|s| {
+            let center = s * frames_per_sec;
+            percentile(&levels[center.saturating_sub(half)..(center + half).min(levels.len())], FLOOR_PERCENTILE)
+        })
+        .collect();
+    frames
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            f.level_dbfs >= MIN_LEVEL_DBFS
+                && f.level_dbfs - floors[i / frames_per_sec.max(1)] >= MIN_SNR_DB
+                && (voiced.get(i).copied().unwrap_or(false) || f.flatness <= MAX_FLATNESS)
+        })
+        .collect()
+}
+
+/// Log density of a Gaussian, without the constant
+fn log_gaussian(x: f32, mean: f32, sd: f32) -> f32 {
+    -sd.ln() - (x - mean).powi(2) / (2.0 * sd * sd)
+}
+
+/// Sub-band model: per band, the likelihood of the level under a speech
+/// Gaussian against a noise Gaussian, each following the frames it wins
+fn subband_decision(frames: &[Frame], aggressiveness: usize) -> Vec<bool> {
+    let total_weight: f32 = BAND_WEIGHTS.iter().sum();
+    let mut noise_mean: [f32; 6] = std::array::from_fn(|b| {
+        percentile(&frames.iter().map(|f| f.bands_dbfs[b]).collect::<Vec<_>>(), FLOOR_PERCENTILE)
+    });
+    let mut noise_var = [NOISE_SD_DB * NOISE_SD_DB; 6];
+    let mut speech_mean = noise_mean.map(|m| m + SPEECH_OFFSET_DB);
+
+    frames
+        .iter()
+        .map(|f| {
+            // Below the noise mean is noise, however unlikely under either model
+            let llr: [f32; 6] = std::array::from_fn(|b| {
+                let x = f.bands_dbfs[b].max(noise_mean[b]);
+                log_gaussian(x, speech_mean[b], SPEECH_SD_DB) - log_gaussian(x, noise_mean[b], noise_var[b].sqrt())
+            });
+            let mean_llr = llr.iter().zip(BAND_WEIGHTS).map(|(l, w)| l * w).sum::<f32>() / total_weight;
+            let speech = f.level_dbfs >= MIN_LEVEL_DBFS
+                && (mean_llr >= MEAN_LLR_THRESHOLDS[aggressiveness]
+                    || llr.iter().any(|&l| l >= BAND_LLR_THRESHOLDS[aggressiveness]));
+            for b in 0..BANDS.len() {
+                let x = f.bands_dbfs[b];
+                if speech {
+                    speech_mean[b] += ADAPT_RATE * (x - speech_mean[b]);
+                } else {
+                    // Noise falling away is followed at once
+                    let rate = if x < noise_mean[b] { 5.0 * ADAPT_RATE } else { ADAPT_RATE };
+                    noise_mean[b] += rate * (x - noise_mean[b]);
+                    noise_var[b] = (noise_var[b] + ADAPT_RATE * ((x - noise_mean[b]).powi(2) - noise_var[b]))
+                        .max(MIN_NOISE_SD_DB * MIN_NOISE_SD_DB);
+                }
+                speech_mean[b] = speech_mean[b].max(noise_mean[b] + MIN_SPEECH_OFFSET_DB);
+            }
+            speech
+        })
+        .collect()
+}
+
+/// Drop speech runs too short to be a word, extend the rest over the quiet
+/// edges of words and bridge the pauses inside phrases
+fn smooth(flags: &[bool], frames_per_sec: f32) -> Vec<bool> {
+    let frames = |secs: f32| (secs * frames_per_sec).round() as usize;
+    let mut smoothed = vec![false; flags.len()];
+    for (start, end) in segments::flag_runs(flags, frames(MIN_SPEECH_SECS)) {
+        let range = start.saturating_sub(frames(PRE_ROLL_SECS))..(end + frames(HANGOVER_SECS)).min(flags.len());
+        smoothed[range].iter_mut().for_each(|s| *s = true);
+    }
+    let gaps: Vec<bool> = smoothed.iter().map(|s| !s).collect();
+    for (start, end) in segments::flag_runs(&gaps, 1) {
+        if start > 0 && end < flags.len() && end - start < frames(MIN_PAUSE_SECS) {
+            smoothed[start..end].iter_mut().for_each(|s| *s = true);
+        }
+    }
+    smoothed
+}
+
+/// Split the signal into speech and non-speech segments. The signal is
+/// taken down to 16 kHz and split into 32 ms frames every 10 ms; `method`
+/// picks how a frame is judged (see `VadMethod`), `aggressiveness` (0 to 3,
+/// as in WebRTC) how readily the sub-band model calls noise non-speech. The
+/// frame decisions are smoothed so words keep their quiet edges and short
+/// pauses inside a phrase stay speech. Music and singing are voiced and
+/// read as speech; the sub-band model, going by level alone, also takes
+/// bangs and other loud noises for it.
+pub fn detect(samples: &[f32], sample_rate: u32, method: VadMethod, aggressiveness: u8) -> VadReport {
+    let aggressiveness = aggressiveness.min(3);
+    let analysis = pitch::to_analysis_rate(samples, sample_rate);
+    let sr = pitch::ANALYSIS_RATE as f32;
+    let hop = (pitch::HOP_SECS * sr) as usize;
+    let duration = samples.len() as f32 / sample_rate as f32;
+    let bin_hz = sr / FRAME_LEN as f32;
+    let window = WindowType::Hann.coefficients(FRAME_LEN);
+    // Power spectrum sums to mean square (one-sided)
+    let scale = 2.0 / (FRAME_LEN as f32 * window.iter().map(|w| w * w).sum::<f32>());
+
+    let starts: Vec<usize> =
+        (0..).map(|i| i * hop).take_while(|&start| start + FRAME_LEN <= analysis.len()).collect();
+    let frames: Vec<Frame> = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(FRAME_LEN);
+            let mut input: Vec<f32> =
+                analysis[start..start + FRAME_LEN].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+            frame_features(&power, bin_hz, scale)
+        })
+        .collect();
+
+    let frames_per_sec = 1.0 / pitch::HOP_SECS;
+    let flags = match method {
+        VadMethod::Features => {
+            let voiced: Vec<bool> = pitch::track(&analysis).iter().map(|p| p.f0.is_some()).collect();
+            features_decision(&frames, &voiced, frames_per_sec.round() as usize)
+        }
+        VadMethod::SubbandModel => subband_decision(&frames, aggressiveness as usize),
+    };
+    let flags = smooth(&flags, frames_per_sec);
+
+    let time = |frame: usize| (frame as f32 * pitch::HOP_SECS).min(duration);
+    let mut segments: Vec<VadSegment> = Vec::new();
+    for (i, &speech) in flags.iter().enumerate() {
+        match segments.last_mut() {
+            Some(last) if last.speech == speech => last.end_time = time(i + 1),
+            _ => segments.push(VadSegment { start_time: time(i), end_time: time(i + 1), speech }),
+        }
+    }
+    if let Some(last) = segments.last_mut() {
+        last.end_time = duration;
+    }
+
+    let level_where = |speech: bool| {
+        let levels: Vec<f32> = frames.iter().zip(&flags).filter(|(_, &s)| s == speech).map(|(f, _)| f.level_dbfs).collect();
+        (!levels.is_empty()).then(|| percentile(&levels, 0.5))
+    };
+    let speech_secs: f32 = segments.iter().filter(|s| s.speech).map(|s| s.end_time - s.start_time).sum();
+    VadReport {
+        method,
+        aggressiveness,
+        speech_secs,
+        speech_fraction: if duration > 0.0 { speech_secs / duration } else { 0.0 },
+        speech_level_dbfs: level_where(true),
+        noise_level_dbfs: level_where(false),
+        segments,
+    }
+}
@@ -0,0 +1,146 @@
+//! Video clips of the spectrogram scrolling past a playhead, with the sound,
+//! encoded by the ffmpeg command-line tool
+
+use crate::figures::Image;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+/// Longest clip rendered (seconds); its spectrogram is held in memory
+pub const MAX_SECS: f32 = 300.0;
+/// Frames drawn in parallel before they are piped to the encoder
+const BATCH: usize = 32;
+
+/// Size, rate and content of a spectrogram video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoOptions {
+    pub width: usize,           // Pixels, even
+    pub height: usize,
+    pub fps: u32,
+    pub window_secs: f32,       // Time across a frame, the playhead in the middle
+    pub n_fft: usize,
+    pub max_freq: Option<f32>,  // Hz; Nyquist when unset
+    pub range_db: f32,          // Colour bar span below the loudest value
+    pub title: Option<String>,  // The file name when unset
+    pub markers: bool,          // Mark findings and markers
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fps: 30,
+            window_secs: 10.0,
+            n_fft: 1024,
+            max_freq: None,
+            range_db: 100.0,
+            title: None,
+            markers: true,
+        }
+    }
+}
+
+impl VideoOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(320..=3840).contains(&self.width) || !(240..=2160).contains(&self.height) {
+            return Err("Video size must be between 320 × 240 and 3840 × 2160 pixels".to_string());
+        }
+        if self.width % 2 == 1 || self.height % 2 == 1 {
+            return Err("Video width and height must be even".to_string());
+        }
+        if !(1..=60).contains(&self.fps) {
+            return Err("Frame rate must be between 1 and 60 fps".to_string());
+        }
+        if !(1.0..=120.0).contains(&self.window_secs) {
+            return Err("Window must be between 1 and 120 seconds".to_string());
+        }
+        if !(64..=65536).contains(&self.n_fft) {
+            return Err("FFT size must be between 64 and 65536".to_string());
+        }
+        if self.range_db.is_nan() || self.range_db <= 0.0 {
+            return Err("Colour range must be above 0 dB".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// ffmpeg's arguments: raw RGB frames on its input, the sound from
+/// `audio_path`, H.264 and AAC out to `path` in the container its
+/// extension names
+fn command_line(path: &str, audio_path: &str, options: &VideoOptions) -> Vec<String> {
+    let size = format!("{}x{}", options.width, options.height);
+    let fps = options.fps.to_string();
+    [
+        "-hide_banner", "-loglevel", "error", "-y",
+        "-f", "rawvideo", "-pix_fmt", "rgb24", "-s", &size, "-r", &fps, "-i", "-",
+        "-i", audio_path,
+        "-map", "0:v", "-map", "1:a",
+        "-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18",
+        "-c:a", "aac", "-b:a", "192k",
+        "-shortest", path,
+    ]
+    .iter()
+    .map(|a| a.to_string())
+    .collect()
+}
+
+/// Encode `frames` frames, each drawn by `frame` from its index at the
+/// size of `options`, with the sound of `audio_path` into the video file at
+/// `path`. `ffmpeg` must be on the PATH.
+pub fn encode(
+    path: &str,
+    audio_path: &str,
+    options: &VideoOptions,
+    frames: usize,
+    frame: impl Fn(usize) -> Result<Image, String> + Sync,
+) -> Result<(), String> {
+    let mut child = Command::new("ffmpeg")
+        .args(command_line(path, audio_path, options))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "Video export needs the `ffmpeg` program on the PATH".to_string(),
+            _ => format!("Failed to start ffmpeg: {}", e),
+        })?;
+
+    // Collect the encoder's messages on another thread so they cannot block it
+    let mut stderr = child.stderr.take().ok_or("Encoder messages unavailable")?;
+    let messages = std::thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+
+    let mut stdin = child.stdin.take().ok_or("Encoder input unavailable")?;
+    let indices: Vec<usize> = (0..frames).collect();
+    let mut fed = Ok(());
+    'batches: for batch in indices.chunks(BATCH) {
+        let images = match batch.par_iter().map(|&i| frame(i)).collect::<Result<Vec<Image>, String>>() {
+            Ok(images) => images,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+        for image in images {
+            if let Err(e) = stdin.write_all(&image.pixels) {
+                fed = Err(e);
+                break 'batches;
+            }
+        }
+    }
+    drop(stdin);
+
+    let status = child.wait().map_err(|e| format!("ffmpeg failed: {}", e))?;
+    let messages = messages.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!("ffmpeg failed ({}): {}", status, messages.trim()));
+    }
+    fed.map_err(|e| format!("Failed to pipe frames to ffmpeg: {}", e))
+}
@@ -0,0 +1,184 @@
+//! Neural vocoder artifacts located on the spectrogram
+
+use crate::compression;
+use crate::spectrum::{self, WindowType};
+use serde::{Deserialize, Serialize};
+
+const N_FFT: usize = 1024;
+const HOP: usize = 512;
+/// Block the artifacts are judged and reported in (seconds)
+const BLOCK_SECS: f32 = 1.0;
+/// Share of a block's frames taken as pauses and as speech
+const PAUSE_SHARE: f32 = 0.2;
+const SPEECH_SHARE: f32 = 0.3;
+/// Least level difference between a block's speech and pauses (dB)
+const MIN_RANGE_DB: f32 = 20.0;
+/// Lowest noise-floor edge searched for (Hz)
+const MIN_EDGE_HZ: f32 = 2000.0;
+/// Fall across a band limit of the noise floor (dB)
+const EDGE_DROP_DB: f32 = 20.0;
+/// How far above the floor's edge the speech must reach (Hz)
+const EDGE_MARGIN_HZ: f32 = 1000.0;
+/// Band whose frame-to-frame change measures smoothing (Hz)
+const HARMONIC_BAND: (f32, f32) = (1000.0, 4000.0);
+/// Mean frame-to-frame change of the spectral shape below which speech is
+/// over-smoothed (dB)
+const MIN_FLUX_DB: f32 = 2.0;
+/// Least run of consecutive speech frames to judge smoothing on (seconds)
+const MIN_RUN_SECS: f32 = 0.2;
+/// Lower edge of the band searched for checkerboard patterns (Hz)
+const HIGH_BAND_HZ: f32 = 4000.0;
+/// Autocorrelation of the detrended high band that marks a pattern
+const MIN_PATTERN: f32 = 0.5;
+/// Bins in the moving average the high band is detrended with
+const DETREND_BINS: usize = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VocoderArtifact {
+    BandLimitedFloor,      // Pause noise ends at a frequency the speech goes past
+    HarmonicSmoothing,     // Speech spectrum too steady from frame to frame
+    Checkerboard,          // Regular comb across the high band
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocoderRegion {
+    pub kind: VocoderArtifact,
+    pub start_time: f32,
+    pub end_time: f32,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    pub strength: f32,  // Floor drop (dB), spectral change (dB) or pattern correlation
+}
+
+/// Normalized autocorrelation of `x` at `lag`
+fn autocorrelation(x: &[f32], lag: usize) -> f32 {
+    let energy: f32 = x.iter().map(|v| v * v).sum();
+    if energy <= 0.0 || lag >= x.len() {
+        return 0.0;
+    }
+    x.iter().zip(&x[lag..]).map(|(a, b)| a * b).sum::<f32>() / energy
+}
+
+/// `x` less its moving average over `width` points
+fn detrend(x: &[f32], width: usize) -> Vec<f32> {
+    let half = width / 2;
+    (0..x.len())
+        .map(|i| {
+            let window = &x[i.saturating_sub(half)..(i + half + 1).min(x.len())];
+            x[i] - window.iter().sum::<f32>() / window.len() as f32
+        })
+        .collect()
+}
+
+/// Mean dB spectrum of the given frames
+fn mean_spectrum(frames: &[Vec<f32>], picks: &[usize]) -> Vec<f32> {
+    let mut sum = vec![0.0f32; frames[0].len()];
+    for &i in picks {
+        sum.iter_mut().zip(&frames[i]).for_each(|(s, v)| *s += v);
+    }
+    sum.iter().map(|s| s / picks.len().max(1) as f32).collect()
+}
+
+/// Look for the artifacts neural vocoders leave, block by block: a pause
+/// noise floor cut off at a frequency the speech extends past (a vocoder
+/// generating at a lower rate, or noise synthesized in a band), speech
+/// whose harmonic band hardly changes between frames (over-smoothed
+/// acoustic features), and a regular comb across the high band
+/// (transposed-convolution upsampling). Flagged blocks of one kind that
+/// touch are merged into one region.
+pub fn detect_artifacts(samples: &[f32], sample_rate: u32) -> Vec<VocoderRegion> {
+    let sr = sample_rate as f32;
+    let nyquist = sr / 2.0;
+    let bin_hz = sr / N_FFT as f32;
+    let (_, frames) = spectrum::spectrogram_db(samples, sample_rate, N_FFT, HOP, WindowType::Hann, nyquist + 1.0);
+    let block = ((BLOCK_SECS * sr) as usize / HOP).max(1);
+    let min_run = ((MIN_RUN_SECS * sr) as usize / HOP).max(2);
+    let band = |lo: f32, hi: f32| ((lo / bin_hz) as usize, ((hi / bin_hz) as usize).min(N_FFT / 2));
+    let (harmonic_lo, harmonic_hi) = band(HARMONIC_BAND.0, HARMONIC_BAND.1);
+    let (high_lo, high_hi) = band(HIGH_BAND_HZ, 0.9 * nyquist);
+    let time = |frame: usize| frame as f32 * HOP as f32 / sr;
+
+    let mut regions: Vec<VocoderRegion> = Vec::new();
+    let mut flag = |kind: VocoderArtifact, first: usize, last: usize, low_hz: f32, high_hz: f32, strength: f32| {
+        let (start_time, end_time) = (time(first), time(last) + N_FFT as f32 / sr);
+        if let Some(previous) = regions.iter_mut().rev().find(|r| r.kind == kind) {
+            if previous.end_time >= start_time {
+                previous.end_time = end_time;
+                previous.low_hz = previous.low_hz.min(low_hz);
+                previous.high_hz = previous.high_hz.max(high_hz);
+                // Lower spectral change is the stronger smoothing
+                previous.strength = match kind {
+                    VocoderArtifact::HarmonicSmoothing => previous.strength.min(strength),
+                    _ => previous.strength.max(strength),
+                };
+                return;
+            }
+        }
+        regions.push(VocoderRegion { kind, start_time, end_time, low_hz, high_hz, strength });
+    };
+
+    for first in (0..frames.len()).step_by(block) {
+        let last = (first + block).min(frames.len()) - 1;
+        let levels: Vec<f32> = frames[first..=last]
+            .iter()
+            .map(|f| 10.0 * f.iter().map(|db| 10f32.powf(db / 10.0)).sum::<f32>().log10())
+            .collect();
+        let mut order: Vec<usize> = (0..levels.len()).collect();
+        order.sort_by(|&a, &b| levels[a].total_cmp(&levels[b]));
+        let pauses: Vec<usize> = order[..((PAUSE_SHARE * order.len() as f32) as usize).max(1)].iter().map(|i| first + i).collect();
+        let mut speech: Vec<usize> = order[order.len() - ((SPEECH_SHARE * order.len() as f32) as usize).max(1)..]
+            .iter()
+            .map(|i| first + i)
+            .collect();
+        speech.sort_unstable();
+        let range = levels[*order.last().unwrap()] - levels[order[0]];
+
+        // Band-limited floor
+        if range >= MIN_RANGE_DB {
+            let floor = mean_spectrum(&frames, &pauses);
+            let voice = mean_spectrum(&frames, &speech);
+            let floor_edge = compression::band_edge(&floor, bin_hz, MIN_EDGE_HZ).filter(|&(_, drop)| drop >= EDGE_DROP_DB);
+            if let Some((edge, drop)) = floor_edge {
+                let edge_hz = edge as f32 * bin_hz;
+                let voice_edge = compression::band_edge(&voice, bin_hz, MIN_EDGE_HZ)
+                    .filter(|&(_, drop)| drop >= EDGE_DROP_DB)
+                    .map_or(nyquist, |(k, _)| k as f32 * bin_hz);
+                if voice_edge >= edge_hz + EDGE_MARGIN_HZ && edge_hz < 0.9 * nyquist {
+                    flag(VocoderArtifact::BandLimitedFloor, first, last, edge_hz, voice_edge, drop);
+                }
+            }
+        }
+
+        // Harmonic smoothing over runs of consecutive speech frames
+        if range >= MIN_RANGE_DB && harmonic_hi > harmonic_lo {
+            let steps: Vec<f32> = speech
+                .windows(2)
+                .filter(|w| w[1] == w[0] + 1)
+                .map(|w| {
+                    let (a, b) = (&frames[w[0]][harmonic_lo..harmonic_hi], &frames[w[1]][harmonic_lo..harmonic_hi]);
+                    // Shape change only; the level may move freely
+                    let gain = a.iter().zip(b).map(|(x, y)| y - x).sum::<f32>() / a.len() as f32;
+                    a.iter().zip(b).map(|(x, y)| (y - x - gain).abs()).sum::<f32>() / a.len() as f32
+                })
+                .collect();
+            let flux = steps.iter().sum::<f32>() / steps.len().max(1) as f32;
+            if steps.len() + 1 >= min_run && flux < MIN_FLUX_DB {
+                flag(VocoderArtifact::HarmonicSmoothing, first, last, HARMONIC_BAND.0, HARMONIC_BAND.1, flux);
+            }
+        }
+
+        // Checkerboard: a regular comb across the high band of the block's
+        // mean spectrum, where voice harmonics smear out as the pitch moves
+        if high_hi > high_lo + 4 * DETREND_BINS {
+            let picks: Vec<usize> = (first..=last).collect();
+            let mean = mean_spectrum(&frames, &picks);
+            let comb = detrend(&mean[high_lo..high_hi], DETREND_BINS);
+            let pattern = (2..DETREND_BINS).map(|lag| autocorrelation(&comb, lag)).fold(0.0f32, f32::max);
+            if pattern >= MIN_PATTERN {
+                flag(VocoderArtifact::Checkerboard, first, last, HIGH_BAND_HZ, 0.9 * nyquist, pattern);
+            }
+        }
+    }
+    regions
+}
@@ -0,0 +1,78 @@
+//! Watch folder: a directory polled for recordings dropped into it. Files
+//! there when watching starts are left alone; a new file is taken once its
+//! size and modification time hold still between two polls, so one still
+//! being copied in is not read half written.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Extensions taken as recordings, compared without case
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "flac", "ogg", "m4a", "aif", "aiff", "caf", "mka", "webm"];
+
+/// What the interface shows of the watch folder
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WatchStatus {
+    pub directory: Option<String>,      // While watching
+    pub files_taken: usize,             // Since watching started
+    pub last_file: Option<String>,
+    pub last_error: Option<String>,     // Why the folder or the last file could not be read
+}
+
+pub struct WatchFolder {
+    pub directory: PathBuf,
+    known: HashMap<PathBuf, (u64, SystemTime)>,     // Taken already, or there from the start
+    settling: HashMap<PathBuf, (u64, SystemTime)>,  // Seen once, not yet still
+}
+
+/// Size and modification time of the recordings in `directory`
+fn scan(directory: &Path) -> Result<HashMap<PathBuf, (u64, SystemTime)>, String> {
+    let entries = std::fs::read_dir(directory).map_err(|e| format!("Failed to read {}: {}", directory.display(), e))?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let hidden = path.file_name()?.to_str()?.starts_with('.');
+            let extension = path.extension()?.to_str()?.to_lowercase();
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            (!hidden && AUDIO_EXTENSIONS.contains(&extension.as_str()))
+                .then(|| (path, (metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH))))
+        })
+        .collect())
+}
+
+impl WatchFolder {
+    /// Start watching `directory`, taking what is in it now as known
+    pub fn new(directory: &str) -> Result<Self, String> {
+        let directory = PathBuf::from(directory);
+        if !directory.is_dir() {
+            return Err(format!("{} is not a directory", directory.display()));
+        }
+        let known = scan(&directory)?;
+        Ok(Self { directory, known, settling: HashMap::new() })
+    }
+
+    /// Recordings new since the last poll, or changed since they were taken,
+    /// that have held still since the poll before, oldest first
+    pub fn poll(&mut self) -> Result<Vec<PathBuf>, String> {
+        let current = scan(&self.directory)?;
+        self.known.retain(|path, _| current.contains_key(path));
+        let mut ready = Vec::new();
+        let mut settling = HashMap::new();
+        for (path, stamp) in current {
+            if self.known.get(&path) == Some(&stamp) {
+                continue;
+            }
+            if self.settling.get(&path) == Some(&stamp) && stamp.0 > 0 {
+                ready.push((stamp.1, path.clone()));
+                self.known.insert(path, stamp);
+            } else {
+                settling.insert(path, stamp);
+            }
+        }
+        self.settling = settling;
+        ready.sort();
+        Ok(ready.into_iter().map(|(_, path)| path).collect())
+    }
+}
@@ -0,0 +1,58 @@
+//! IEC 61672 frequency weightings for level measurements
+
+use crate::filters::{Biquad, FilterChain};
+use serde::{Deserialize, Serialize};
+
+// Pole frequencies of the analog A/C weighting prototypes (IEC 61672-1)
+const F1: f64 = 20.598_997;
+const F2: f64 = 107.652_65;
+const F3: f64 = 737.862_23;
+const F4: f64 = 12_194.217;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weighting {
+    A,
+    C,
+    #[default]
+    Z,
+}
+
+impl Weighting {
+    /// Build the weighting filter for `sample_rate`, normalized to 0 dB at 1 kHz.
+    /// Returns `None` for Z-weighting (flat).
+    pub fn filter(self, sample_rate: u32) -> Option<FilterChain> {
+        let sr = sample_rate as f64;
+        let w = |f: f64| 2.0 * std::f64::consts::PI * f;
+        let (w1, w2, w3) = (w(F1), w(F2), w(F3));
+        // Prewarp the high pole so the bilinear transform keeps it in place
+        let w4 = 2.0 * sr * (w(F4) / (2.0 * sr)).tan();
+
+        // Double pole at F1 with s² zeros, and double pole at F4
+        let low = Biquad::from_analog([1.0, 0.0, 0.0], [1.0, 2.0 * w1, w1 * w1], sr);
+        let high = Biquad::from_analog([0.0, 0.0, 1.0], [1.0, 2.0 * w4, w4 * w4], sr);
+
+        let mut chain = match self {
+            Weighting::Z => return None,
+            Weighting::C => FilterChain::new(vec![low, high]),
+            Weighting::A => {
+                let mid = Biquad::from_analog([1.0, 0.0, 0.0], [1.0, w2 + w3, w2 * w3], sr);
+                FilterChain::new(vec![low, mid, high])
+            }
+        };
+
+        let gain_1k = chain.magnitude(1000.0, sr);
+        if gain_1k > 0.0 {
+            chain.scale(1.0 / gain_1k);
+        }
+        Some(chain)
+    }
+
+    /// Return a weighted copy of `samples` (unchanged for Z-weighting)
+    pub fn apply(self, samples: &[f32], sample_rate: u32) -> Vec<f32> {
+        match self.filter(sample_rate) {
+            Some(mut chain) => chain.process_buffer(samples),
+            None => samples.to_vec(),
+        }
+    }
+}
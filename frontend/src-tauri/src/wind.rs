@@ -0,0 +1,207 @@
+//! Wind noise detection from the low-frequency spectrum and its gusts
+
+use crate::spectrum::WindowType;
+use rayon::prelude::*;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Analysis frame (seconds, rounded up to a power of two), hopped by half
+const FRAME_SECS: f32 = 0.05;
+/// Wind band, and the band it is compared against (Hz)
+const LOW_BAND: (f32, f32) = (20.0, 200.0);
+const MID_BAND: (f32, f32) = (200.0, 4000.0);
+/// Octave bands the spectral slope is fitted over (center frequencies, Hz)
+const SLOPE_BANDS: [f32; 5] = [40.0, 80.0, 160.0, 320.0, 640.0];
+/// A wind frame: the low band at least this loud (dBFS) and this far over
+/// the mid band (dB), the spectral density falling at least this steeply
+/// (dB per octave), and no bin of the low band this far above the fitted
+/// slope (dB), which a bass note or hum would be
+const MIN_LOW_DBFS: f32 = -60.0;
+const MIN_LOW_EXCESS_DB: f32 = 6.0;
+const MAX_SLOPE_DB: f32 = -4.0;
+const MAX_TONAL_DB: f32 = 12.0;
+/// Wind frames count in runs at least this long, which a kick drum or a
+/// plosive is not, and runs this close together form one region, so speech
+/// or music over the wind does not split it (seconds)
+const MIN_RUN_SECS: f32 = 0.15;
+const MERGE_SECS: f32 = 0.5;
+/// Gusts: the low band of the wind frames in the region and this much
+/// either side of it spreading over this many dB between its 10th and 90th
+/// percentiles (seconds, dB); a steady rumble (traffic, ventilation) stays
+/// within a few dB
+const MIN_GUST_DB: f32 = 6.0;
+const GUST_CONTEXT_SECS: f32 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindRegion {
+    pub start_time: f32,
+    pub end_time: f32,
+    pub peak_dbfs: f32,         // Loudest frame of the 20-200 Hz band
+    pub low_excess_db: f32,     // Median of the 20-200 Hz band over 200-4000 Hz
+    pub slope_db_oct: f32,      // Median spectral density slope from 40 to 640 Hz
+    pub gust_db: f32,           // Spread of the low band over the wind frames in and around it
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindReport {
+    pub regions: Vec<WindRegion>,
+    pub affected_secs: f32,
+    pub affected_fraction: f32,
+}
+
+impl WindReport {
+    /// Whether `start..end` (seconds) overlaps a wind region
+    pub fn overlaps(&self, start: f32, end: f32) -> bool {
+        self.regions.iter().any(|r| start < r.end_time && end > r.start_time)
+    }
+}
+
+/// One frame's wind features
+#[derive(Clone, Copy)]
+struct Frame {
+    low_dbfs: f32,
+    low_excess_db: f32,
+    slope_db_oct: f32,
+    tonal_db: f32,
+}
+
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted.get((p * sorted.len().saturating_sub(1) as f32) as usize).copied().unwrap_or(0.0)
+}
+
+/// Least-squares slope of `y` against `x`
+fn slope(x: &[f32], y: &[f32]) -> f32 {
+    let n = x.len().max(1) as f32;
+    let (mx, my) = (x.iter().sum::<f32>() / n, y.iter().sum::<f32>() / n);
+    let den: f32 = x.iter().map(|a| (a - mx).powi(2)).sum();
+    if den > 0.0 { x.iter().zip(y).map(|(a, b)| (a - mx) * (b - my)).sum::<f32>() / den } else { 0.0 }
+}
+
+fn frame_features(power: &[f32], bin_hz: f32, scale: f32) -> Frame {
+    let bin = |f: f32| ((f / bin_hz).round() as usize).clamp(1, power.len() - 1);
+    let band_dbfs = |(lo, hi): (f32, f32)| {
+        let (lo, hi) = (bin(lo), bin(hi).max(bin(lo) + 1));
+        10.0 * (power[lo..hi].iter().sum::<f32>() * scale).max(1e-20).log10()
+    };
+    let low_dbfs = band_dbfs(LOW_BAND);
+
+    // Spectral density per octave band against the octave number
+    let (octaves, densities): (Vec<f32>, Vec<f32>) = SLOPE_BANDS
+        .iter()
+        .map(|&f| {
+            let (lo, hi) = (bin(f / 2f32.sqrt()), bin(f * 2f32.sqrt()).max(bin(f / 2f32.sqrt()) + 1));
+            (f.log2(), 10.0 * (power[lo..hi].iter().sum::<f32>() / (hi - lo) as f32).max(1e-20).log10())
+        })
+        .unzip();
+    let slope_db_oct = slope(&octaves, &densities);
+
+    // Bins of the low band against a line through them in dB, the median of
+    // the pairwise slopes so a strong tone does not pull it along
+    let (lo, hi) = (bin(LOW_BAND.0), bin(LOW_BAND.1).max(bin(LOW_BAND.0) + 2));
+    let (freqs, levels): (Vec<f32>, Vec<f32>) =
+        (lo..hi).map(|k| ((k as f32 * bin_hz).log2(), 10.0 * power[k].max(1e-20).log10())).unzip();
+    let slopes: Vec<f32> = (0..freqs.len())
+        .flat_map(|i| (i + 1..freqs.len()).map(move |j| (i, j)))
+        .map(|(i, j)| (levels[j] - levels[i]) / (freqs[j] - freqs[i]))
+        .collect();
+    let a = percentile(&slopes, 0.5);
+    let residual: Vec<f32> = freqs.iter().zip(&levels).map(|(f, l)| l - a * f).collect();
+    let tonal_db = residual.iter().copied().fold(f32::MIN, f32::max) - percentile(&residual, 0.5);
+
+    Frame { low_dbfs, low_excess_db: low_dbfs - band_dbfs(MID_BAND), slope_db_oct, tonal_db }
+}
+
+/// Find stretches of wind noise: turbulence on the microphone piles its
+/// energy into the lowest octaves with a steeply falling, noise-like
+/// spectrum, and comes in gusts. Frames whose low band dominates, falls
+/// steeply and carries no tones are joined into regions, and a region is
+/// kept when the level of those frames swings with the gusts, which a
+/// steady rumble of the same shape does not.
+pub fn detect_wind(samples: &[f32], sample_rate: u32) -> WindReport {
+    let sr = sample_rate as f32;
+    let duration = samples.len() as f32 / sr;
+    let mut report = WindReport { regions: Vec::new(), affected_secs: 0.0, affected_fraction: 0.0 };
+    let n_fft = ((FRAME_SECS * sr) as usize).next_power_of_two();
+    let hop = n_fft / 2;
+    if samples.len() < n_fft || MID_BAND.1 >= sr / 2.0 {
+        return report;
+    }
+    let bin_hz = sr / n_fft as f32;
+    let window = WindowType::Hann.coefficients(n_fft);
+    // Power spectrum sums to mean square (one-sided)
+    let scale = 2.0 / (n_fft as f32 * window.iter().map(|w| w * w).sum::<f32>());
+
+    let starts: Vec<usize> = (0..).map(|i| i * hop).take_while(|&start| start + n_fft <= samples.len()).collect();
+    let frames: Vec<Frame> = starts
+        .par_iter()
+        .map(|&start| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(n_fft);
+            let mut input: Vec<f32> = samples[start..start + n_fft].iter().zip(&window).map(|(&s, &w)| s * w).collect();
+            let mut spectrum = fft.make_output_vec();
+            fft.process(&mut input, &mut spectrum).unwrap();
+            let power: Vec<f32> = spectrum.iter().map(|c| c.norm_sqr()).collect();
+            frame_features(&power, bin_hz, scale)
+        })
+        .collect();
+    let mut windy: Vec<bool> = frames
+        .iter()
+        .map(|f| {
+            f.low_dbfs >= MIN_LOW_DBFS
+                && f.low_excess_db >= MIN_LOW_EXCESS_DB
+                && f.slope_db_oct <= MAX_SLOPE_DB
+                && f.tonal_db <= MAX_TONAL_DB
+        })
+        .collect();
+
+    let hop_secs = hop as f32 / sr;
+    let min_run = ((MIN_RUN_SECS / hop_secs).ceil() as usize).max(1);
+    let mut k = 0;
+    while k < windy.len() {
+        let run = windy[k..].iter().take_while(|&&w| w).count();
+        if run < min_run {
+            windy[k..k + run].iter_mut().for_each(|w| *w = false);
+        }
+        k += run.max(1);
+    }
+    let merge = ((MERGE_SECS / hop_secs) as usize).max(1);
+    let context = ((GUST_CONTEXT_SECS / hop_secs) as usize).max(1);
+    let mut i = 0;
+    while i < windy.len() {
+        if !windy[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i;
+        while i < windy.len() && i - end <= merge {
+            if windy[i] {
+                end = i;
+            }
+            i += 1;
+        }
+        let (start_time, end_time) = (start as f32 * hop_secs, (end as f32 * hop_secs + n_fft as f32 / sr).min(duration));
+        let region: Vec<&Frame> = (start..=end).filter(|&k| windy[k]).map(|k| &frames[k]).collect();
+        let around: Vec<f32> = (start.saturating_sub(context)..(end + context + 1).min(frames.len()))
+            .filter(|&k| windy[k])
+            .map(|k| frames[k].low_dbfs)
+            .collect();
+        let gust_db = percentile(&around, 0.9) - percentile(&around, 0.1);
+        if gust_db < MIN_GUST_DB {
+            continue;
+        }
+        report.regions.push(WindRegion {
+            start_time,
+            end_time,
+            peak_dbfs: region.iter().map(|f| f.low_dbfs).fold(f32::MIN, f32::max),
+            low_excess_db: percentile(&region.iter().map(|f| f.low_excess_db).collect::<Vec<_>>(), 0.5),
+            slope_db_oct: percentile(&region.iter().map(|f| f.slope_db_oct).collect::<Vec<_>>(), 0.5),
+            gust_db,
+        });
+    }
+    report.affected_secs = report.regions.iter().map(|r| r.end_time - r.start_time).sum();
+    report.affected_fraction = report.affected_secs / duration.max(1e-6);
+    report
+}